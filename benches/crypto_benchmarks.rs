@@ -0,0 +1,185 @@
+// benches/crypto_benchmarks.rs
+
+//! Criterion benchmarks for the crypto and I/O hot paths of Ferox Encryptor.
+//!
+//! Everything runs in memory against the streaming/in-memory APIs, and all salts
+//! and IVs come from a fixed-seed RNG so repeated runs process identical data.
+//! Run them with `cargo bench --features bench-internals`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use ferox_encryptor::bench_support::{
+    derive_master_key, encrypt_and_mac_chunks, encrypt_in_memory_with_rng, parse_header,
+};
+use ferox_encryptor::keyfile::combine_password_and_keyfile;
+use ferox_encryptor::progress::{ProgressEvent, ProgressSink};
+use ferox_encryptor::{decrypt_in_memory, DecryptionOptions, EncryptionOptions, KeyFile, Level};
+use indicatif::{ProgressBar, ProgressStyle};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::hint::black_box;
+use std::path::Path;
+
+const RNG_SEED: u64 = 0xFE70_C0DE;
+const PASSWORD: &str = "benchmark_password";
+
+fn seeded_rng() -> StdRng {
+    StdRng::seed_from_u64(RNG_SEED)
+}
+
+/// Deterministic, non-repeating test data.
+fn test_data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn bench_argon2_levels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("argon2_derivation");
+    group.sample_size(10);
+    for level in [Level::Interactive, Level::Moderate, Level::Paranoid] {
+        group.bench_function(BenchmarkId::from_parameter(format!("{level:?}")), |b| {
+            b.iter(|| derive_master_key(black_box(PASSWORD.as_bytes()), &[7u8; 16], level).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_chunk_loop(c: &mut Criterion) {
+    let data = test_data(16 * 1024 * 1024);
+    let mut group = c.benchmark_group("encrypt_and_mac_chunks");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    for buffer_size in [64 * 1024, 1024 * 1024, 4 * 1024 * 1024] {
+        for single_thread in [true, false] {
+            let mode = if single_thread { "serial" } else { "pipelined" };
+            let id = BenchmarkId::new(mode, format!("{}KiB", buffer_size / 1024));
+            group.bench_function(id, |b| {
+                let mut output = Vec::with_capacity(data.len());
+                b.iter(|| {
+                    output.clear();
+                    encrypt_and_mac_chunks(&data, buffer_size, single_thread, &mut output).unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_keyfile_combination(c: &mut Criterion) {
+    let mut group = c.benchmark_group("keyfile_combination");
+    group.sample_size(10);
+    // A fresh keyfile per iteration, so the cached derivation never short-circuits the work.
+    group.bench_function("uncached", |b| {
+        b.iter_batched(
+            KeyFile::generate,
+            |keyfile| combine_password_and_keyfile(PASSWORD, &keyfile).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    let keyfile = KeyFile::generate();
+    combine_password_and_keyfile(PASSWORD, &keyfile).unwrap();
+    group.bench_function("cached", |b| {
+        b.iter(|| combine_password_and_keyfile(black_box(PASSWORD), &keyfile).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_in_memory_roundtrip(c: &mut Criterion) {
+    let encrypt_opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new(PASSWORD)
+    };
+    let decrypt_opts = DecryptionOptions::new(PASSWORD);
+
+    let mut group = c.benchmark_group("in_memory_roundtrip");
+    group.sample_size(10);
+    for (label, len) in [("1MB", 1024 * 1024), ("64MB", 64 * 1024 * 1024)] {
+        let data = test_data(len);
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_function(label, |b| {
+            b.iter(|| {
+                let encrypted = encrypt_in_memory_with_rng(
+                    &data,
+                    "bench.bin",
+                    &encrypt_opts,
+                    &mut seeded_rng(),
+                )
+                .unwrap();
+                decrypt_in_memory(&encrypted, &decrypt_opts).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_header_parsing(c: &mut Criterion) {
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new(PASSWORD)
+    };
+    let encrypted = encrypt_in_memory_with_rng(
+        b"header",
+        "a_fairly_long_original_file_name.txt",
+        &opts,
+        &mut seeded_rng(),
+    )
+    .unwrap();
+    c.bench_function("header_parsing", |b| {
+        b.iter(|| parse_header(black_box(&encrypted)).unwrap())
+    });
+}
+
+/// Progress reporting for a batch of tiny files, as seen by each file's flow.
+///
+/// `hidden_bar_per_file` reproduces what the flows did before they stopped building
+/// their own bars: a styled bar per file that is hidden when stderr is redirected but
+/// still formats every tick. `no_sink` is the path taken now when there is no terminal.
+fn bench_small_file_progress(c: &mut Criterion) {
+    const FILES: usize = 10_000;
+    const FILE_SIZE: u64 = 512;
+    let path = Path::new("tiny.txt");
+
+    let mut group = c.benchmark_group("small_file_progress");
+    group.throughput(Throughput::Elements(FILES as u64));
+    group.bench_function("hidden_bar_per_file", |b| {
+        b.iter(|| {
+            for _ in 0..FILES {
+                let pb = ProgressBar::hidden();
+                pb.set_length(FILE_SIZE);
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+                        .unwrap()
+                        .progress_chars("#>-"),
+                );
+                pb.inc(black_box(FILE_SIZE));
+                pb.finish_with_message("加密完成");
+            }
+        })
+    });
+    group.bench_function("no_sink", |b| {
+        let progress: Option<&dyn ProgressSink> = None;
+        b.iter(|| {
+            for _ in 0..FILES {
+                if let Some(sink) = black_box(progress) {
+                    sink.on_event(&ProgressEvent::FileStarted {
+                        index: 0,
+                        total_files: FILES,
+                        path,
+                    });
+                    sink.on_event(&ProgressEvent::BytesProcessed { bytes: FILE_SIZE });
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_argon2_levels,
+    bench_chunk_loop,
+    bench_keyfile_combination,
+    bench_in_memory_roundtrip,
+    bench_header_parsing,
+    bench_small_file_progress
+);
+criterion_main!(benches);
@@ -0,0 +1,185 @@
+//! # 生成兼容性测试夹具 (Generate Compatibility Fixtures)
+//!
+//! 为当前格式版本的每一种组合（加密算法 × 密钥派生算法 × 安全级别 × 是否使用密钥文件）
+//! 生成一个加密样本，写入 `tests/fixtures/compat/v<格式版本>/`，并在
+//! `tests/fixtures/compat/manifest.json` 中记录每个样本的参数和明文的 SHA-256。
+//! `tests/compat_fixtures_tests.rs` 解密清单中的每一个样本并核对明文哈希。
+//!
+//! 盐、IV、明文和密钥文件都由固定的种子派生，重复运行得到逐字节相同的文件。格式版本升级后再次运行，
+//! 会在新的 `v<格式版本>/` 目录中生成样本并把它们合并进清单；旧版本的样本和清单记录保持不变，
+//! 必须一直能够解密。
+//!
+//! *Writes one deterministic sample per (cipher × KDF × level × keyfile) combination of the
+//! current format version into `tests/fixtures/compat/`, plus a manifest of expected plaintext
+//! hashes. Samples of older format versions are kept and must keep decrypting.*
+//!
+//! ```bash
+//! FEROX_ALLOW_DETERMINISTIC=1 cargo run --example generate_fixtures
+//! ```
+
+use anyhow::{Context, Result};
+use ferox_encryptor::constants::{
+    CIPHER_AES256_CTR_HMAC_SHA256, CURRENT_FORMAT_VERSION, IV_LEN, KDF_ARGON2ID, SALT_LEN,
+};
+use ferox_encryptor::keyfile::KeyFile;
+use ferox_encryptor::{encrypt_in_memory, EncryptionOptions, Level};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 所有样本使用的密码（只用于测试夹具）。
+const PASSWORD: &str = "compat-fixture-password";
+/// 夹具目录中密钥文件的文件名。
+const KEYFILE_NAME: &str = "fixture.key";
+/// 清单的文件名。
+const MANIFEST_NAME: &str = "manifest.json";
+/// 每个样本明文的长度：跨越多个 AES 块，但远小于一个读写缓冲区。
+const PLAINTEXT_LEN: usize = 10_000;
+
+/// 当前程序能够生成的加密算法：`(文件头标识, 夹具名称中使用的名称)`。
+const CIPHERS: [(u8, &str); 1] = [(CIPHER_AES256_CTR_HMAC_SHA256, "aes256ctr-hmacsha256")];
+/// 当前程序能够生成的密钥派生算法：`(文件头标识, 夹具名称中使用的名称)`。
+const KDFS: [(u8, &str); 1] = [(KDF_ARGON2ID, "argon2id")];
+
+/// 清单中一个样本的记录。
+#[derive(Debug, Serialize, Deserialize)]
+struct FixtureEntry {
+    format_version: u8,
+    cipher_id: u8,
+    kdf_id: u8,
+    level: String,
+    keyfile: bool,
+    original_filename: String,
+    plaintext_len: usize,
+    plaintext_sha256: String,
+    ciphertext_sha256: String,
+}
+
+/// `manifest.json` 的内容，样本按相对于夹具目录的路径排列。
+#[derive(Debug, Serialize, Deserialize)]
+struct FixtureManifest {
+    password: String,
+    keyfile: String,
+    fixtures: BTreeMap<String, FixtureEntry>,
+}
+
+/// 由 `label` 派生的确定性字节流：依次为 `SHA-256(label || 计数器)`。
+fn deterministic_bytes(label: &str, len: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(len + 32);
+    let mut counter = 0u64;
+    while bytes.len() < len {
+        let mut hasher = Sha256::new();
+        hasher.update(label.as_bytes());
+        hasher.update(counter.to_le_bytes());
+        bytes.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(data) {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+/// 读取已有的密钥文件；不存在时由固定种子生成并保存。
+fn load_or_create_keyfile(dir: &Path) -> Result<KeyFile> {
+    let path = dir.join(KEYFILE_NAME);
+    if !path.exists() {
+        KeyFile::from_bytes(&deterministic_bytes("ferox-compat-keyfile", 64))?
+            .save_to_file(&path)?;
+    }
+    KeyFile::load_from_file(&path)
+}
+
+fn main() -> Result<()> {
+    let dir = std::env::args_os().nth(1).map_or_else(
+        || PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/compat"),
+        PathBuf::from,
+    );
+    let version_dir = format!("v{CURRENT_FORMAT_VERSION}");
+    fs::create_dir_all(dir.join(&version_dir))
+        .with_context(|| format!("无法创建夹具目录: {}", dir.display()))?;
+    let keyfile = load_or_create_keyfile(&dir)?;
+
+    let manifest_path = dir.join(MANIFEST_NAME);
+    let mut manifest = if manifest_path.exists() {
+        serde_json::from_str(&fs::read_to_string(&manifest_path)?)
+            .with_context(|| format!("无效的夹具清单: {}", manifest_path.display()))?
+    } else {
+        FixtureManifest {
+            password: PASSWORD.to_string(),
+            keyfile: KEYFILE_NAME.to_string(),
+            fixtures: BTreeMap::new(),
+        }
+    };
+    // 只重新生成当前格式版本的样本，旧版本的记录原样保留
+    manifest
+        .fixtures
+        .retain(|_, entry| entry.format_version != CURRENT_FORMAT_VERSION);
+
+    for (cipher_id, cipher) in CIPHERS {
+        for (kdf_id, kdf) in KDFS {
+            for level in Level::ALL {
+                for use_keyfile in [false, true] {
+                    let credential = if use_keyfile { "keyfile" } else { "password" };
+                    let stem = format!(
+                        "{version_dir}_{cipher}_{kdf}_{}_{credential}",
+                        level.name().to_lowercase()
+                    );
+                    let original_filename = format!("{stem}.bin");
+                    let plaintext =
+                        deterministic_bytes(&format!("plaintext:{stem}"), PLAINTEXT_LEN);
+                    let mut salt = [0u8; SALT_LEN];
+                    salt.copy_from_slice(&deterministic_bytes(&format!("salt:{stem}"), SALT_LEN));
+                    let mut iv = [0u8; IV_LEN];
+                    iv.copy_from_slice(&deterministic_bytes(&format!("iv:{stem}"), IV_LEN));
+
+                    let opts = EncryptionOptions {
+                        keyfile: use_keyfile.then_some(&keyfile),
+                        level,
+                        test_nonces: Some((salt, iv)),
+                        ..EncryptionOptions::new(&manifest.password)
+                    };
+                    let ciphertext = encrypt_in_memory(&plaintext, &original_filename, &opts)?;
+                    let relative = format!("{version_dir}/{stem}.feroxcrypt");
+                    fs::write(dir.join(&relative), &ciphertext)?;
+                    println!("{relative} ({} 字节)", ciphertext.len());
+
+                    manifest.fixtures.insert(
+                        relative,
+                        FixtureEntry {
+                            format_version: CURRENT_FORMAT_VERSION,
+                            cipher_id,
+                            kdf_id,
+                            level: level.name().to_string(),
+                            keyfile: use_keyfile,
+                            original_filename,
+                            plaintext_len: plaintext.len(),
+                            plaintext_sha256: sha256_hex(&plaintext),
+                            ciphertext_sha256: sha256_hex(&ciphertext),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fs::write(
+        &manifest_path,
+        serde_json::to_string_pretty(&manifest)? + "\n",
+    )?;
+    println!(
+        "清单共 {} 个样本: {}",
+        manifest.fixtures.len(),
+        manifest_path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! # 生成测试向量 (Generate Test Vector)
+//!
+//! 使用确定性加密生成一个可重现的 `.feroxcrypt` 测试向量，并打印它的十六进制内容。
+//! 相同的种子、密码和明文总是得到逐字节相同的输出，可以作为其他实现的兼容性测试夹具。
+//!
+//! *Produces a reproducible `.feroxcrypt` test vector with deterministic encryption.*
+//!
+//! ⚠️ 确定性加密只能用于测试，绝不能用于真实数据。
+//!
+//! ```bash
+//! FEROX_ALLOW_DETERMINISTIC=1 cargo run --example generate_test_vector
+//! ```
+
+use anyhow::Result;
+use ferox_encryptor::{encrypt_deterministic, EncryptionOptions, Level};
+use std::fmt::Write as _;
+use std::fs;
+use tempfile::TempDir;
+
+/// 测试向量使用的固定种子 (Fixed seed used for the test vector)
+const SEED: [u8; 32] = [0x42; 32];
+
+fn main() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source = temp_dir.path().join("vector.txt");
+    fs::write(&source, b"Ferox Encryptor test vector")?;
+
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new("test-vector-password")
+    };
+    encrypt_deterministic(&source, SEED, &opts)?;
+
+    let encrypted = fs::read(temp_dir.path().join("vector.txt.feroxcrypt"))?;
+    let mut hex = String::with_capacity(encrypted.len() * 2);
+    for byte in &encrypted {
+        write!(hex, "{byte:02x}")?;
+    }
+    println!("password:  test-vector-password");
+    println!("plaintext: Ferox Encryptor test vector");
+    println!("level:     {}", opts.level);
+    println!("seed:      {}", "42".repeat(32));
+    println!("ciphertext ({} 字节):", encrypted.len());
+    println!("{hex}");
+    Ok(())
+}
@@ -0,0 +1,336 @@
+// src/audit.rs
+
+//! # 审计日志模块 (Audit Log)
+//!
+//! 可选地把每一次加密/解密操作（无论成功还是失败）追加到一个 JSON Lines 格式的审计日志中，
+//! 每行一条记录：时间、操作、源文件、目标文件、安全级别、结果和执行操作的用户。
+//!
+//! 每条记录带有一个 `mac` 字段：`HMAC-SHA256(审计密钥, 上一条记录的 mac || 本条记录)`，
+//! 审计密钥由专用的审计密钥文件派生。修改、插入或删除任何一条记录都会使链条在该处断开，
+//! [`verify_audit_log`] 会指出断开的行。日志中不会记录密码或任何密钥材料。
+//!
+//! *Every encryption/decryption can be appended to a JSON Lines audit log. Each record is
+//! chained to the previous one with an HMAC under a key derived from a dedicated audit
+//! keyfile, so tampering with any record is detected and pinpointed by
+//! [`verify_audit_log`]. Passwords and key material are never logged.*
+
+use crate::{
+    batch::BatchResult,
+    constants::{AUDIT_KEY_CONTEXT, AUDIT_TAIL_LEN, CUSTOM_FILE_EXTENSION},
+    inspect::{format_utc_time, inspect_file},
+    keyfile::KeyFile,
+    Level,
+};
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt::{self, Write as _};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 审计记录的操作类型。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOperation {
+    /// 加密文件。
+    Encrypt,
+    /// 解密文件。
+    Decrypt,
+}
+
+/// 审计记录的操作结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    /// 操作成功。
+    Success,
+    /// 操作失败。
+    Failure,
+}
+
+/// # 审计记录 (Audit Record)
+///
+/// 审计日志中的一条记录（不含链式 HMAC）。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// 记录时间 (`YYYY-MM-DD HH:MM:SS UTC`)。
+    pub timestamp: String,
+    /// 操作类型。
+    pub operation: AuditOperation,
+    /// 源文件路径。
+    pub source: PathBuf,
+    /// 目标文件路径；无法确定时为 `None`（例如输出到标准输出，或无法读取的加密文件）。
+    pub target: Option<PathBuf>,
+    /// 安全级别（见 [`Level`] 的 `Display`）；无法确定时为 `None`。
+    pub level: Option<String>,
+    /// 操作结果。
+    pub result: AuditOutcome,
+    /// 失败时的错误信息。
+    pub error: Option<String>,
+    /// 执行操作的用户（`USER` / `USERNAME` 环境变量）。
+    pub user: String,
+}
+
+impl AuditRecord {
+    /// 创建一条当前时间、当前用户的成功记录。
+    pub fn success(
+        operation: AuditOperation,
+        source: &Path,
+        target: Option<&Path>,
+        level: Option<Level>,
+    ) -> Self {
+        Self {
+            timestamp: format_utc_time(SystemTime::now()),
+            operation,
+            source: source.to_path_buf(),
+            target: target.map(Path::to_path_buf),
+            level: level.map(|level| level.to_string()),
+            result: AuditOutcome::Success,
+            error: None,
+            user: current_user(),
+        }
+    }
+
+    /// 创建一条当前时间、当前用户的失败记录。
+    pub fn failure(
+        operation: AuditOperation,
+        source: &Path,
+        target: Option<&Path>,
+        level: Option<Level>,
+        error: &dyn fmt::Display,
+    ) -> Self {
+        Self {
+            result: AuditOutcome::Failure,
+            error: Some(error.to_string()),
+            ..Self::success(operation, source, target, level)
+        }
+    }
+}
+
+/// 日志文件中的一行：记录本身加上链式 HMAC。
+#[derive(Serialize, Deserialize)]
+struct AuditLine {
+    #[serde(flatten)]
+    record: AuditRecord,
+    /// `HMAC-SHA256(审计密钥, 上一行的 mac || 记录的 JSON)`，小写十六进制。
+    mac: String,
+}
+
+/// # 审计日志 (Audit Log)
+///
+/// 以追加方式写入的链式审计日志。追加时持有日志文件的独占锁，
+/// 多个实例同时写入同一个日志不会破坏链条。
+pub struct AuditLog {
+    /// 日志文件的路径。
+    path: PathBuf,
+    /// 由审计密钥文件派生的 HMAC 密钥。
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl AuditLog {
+    /// 使用审计密钥文件打开（或在第一次追加时创建）`path` 处的审计日志。
+    ///
+    /// # 错误
+    ///
+    /// 无法由审计密钥文件派生 HMAC 密钥时返回错误。
+    pub fn open(path: impl Into<PathBuf>, audit_keyfile: &KeyFile) -> Result<Self> {
+        Ok(Self {
+            path: path.into(),
+            key: derive_audit_key(audit_keyfile)?,
+        })
+    }
+
+    /// 日志文件的路径。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 把一条记录链接到日志末尾。
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .with_context(|| format!("无法打开审计日志: {}", self.path.display()))?;
+        file.lock()
+            .with_context(|| format!("无法锁定审计日志: {}", self.path.display()))?;
+
+        let previous_mac = last_mac(&mut file)?;
+        let line = AuditLine {
+            mac: record_mac(&self.key, &previous_mac, record)?,
+            record: record.clone(),
+        };
+        let mut json = serde_json::to_string(&line)?;
+        json.push('\n');
+        file.write_all(json.as_bytes())
+            .and_then(|()| file.sync_data())
+            .with_context(|| format!("写入审计日志失败: {}", self.path.display()))
+    }
+
+    /// 为批量操作中的每个文件追加一条记录。
+    ///
+    /// 成功的文件只有在 [`BatchConfig::track_successful_paths`](crate::batch::BatchConfig::track_successful_paths)
+    /// 为 `true` 时才会出现在 `result` 中。加密时的目标文件是源文件加上 `.feroxcrypt` 扩展名，
+    /// 解密时的目标文件和安全级别从加密文件的文件头中读取。
+    pub fn record_batch(
+        &self,
+        operation: AuditOperation,
+        level: Option<Level>,
+        result: &BatchResult,
+    ) -> Result<()> {
+        for (source, succeeded) in &result.processed {
+            let (target, level) = match operation {
+                AuditOperation::Encrypt => {
+                    let mut target = source.clone().into_os_string();
+                    target.push(".");
+                    target.push(CUSTOM_FILE_EXTENSION);
+                    (Some(PathBuf::from(target)), level)
+                }
+                AuditOperation::Decrypt => match inspect_file(source) {
+                    Ok(info) => (
+                        Some(source.with_file_name(&info.original_filename)),
+                        info.level,
+                    ),
+                    Err(_) => (None, None),
+                },
+            };
+            let record = if *succeeded {
+                AuditRecord::success(operation, source, target.as_deref(), level)
+            } else {
+                let error = result
+                    .failures
+                    .iter()
+                    .find(|(path, _)| path == source)
+                    .map_or_else(|| "未知错误".to_string(), |(_, error)| error.to_string());
+                AuditRecord::failure(operation, source, target.as_deref(), level, &error)
+            };
+            self.append(&record)?;
+        }
+        Ok(())
+    }
+}
+
+/// # 审计日志校验结果 (Audit Verification)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditVerification {
+    /// 日志中的记录数量。
+    pub records: usize,
+    /// 链条断开的行号（从 1 开始）：记录被修改，或它之前的记录被插入或删除。
+    pub invalid_lines: Vec<usize>,
+}
+
+impl AuditVerification {
+    /// 整个链条是否完好。
+    pub fn is_intact(&self) -> bool {
+        self.invalid_lines.is_empty()
+    }
+}
+
+/// 用审计密钥文件检查日志的 HMAC 链条，返回链条断开的行。
+///
+/// 单独修改一条记录只会使该行失效；删除或插入记录会使紧随其后的一行失效。
+///
+/// # 错误
+///
+/// 日志文件无法读取或无法派生 HMAC 密钥时返回错误。
+pub fn verify_audit_log(path: &Path, audit_keyfile: &KeyFile) -> Result<AuditVerification> {
+    let file = File::open(path).with_context(|| format!("无法打开审计日志: {}", path.display()))?;
+    let key = derive_audit_key(audit_keyfile)?;
+
+    let mut verification = AuditVerification::default();
+    let mut previous_mac = String::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("读取审计日志失败: {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        verification.records += 1;
+        let valid = match serde_json::from_str::<AuditLine>(&line) {
+            Ok(parsed) => {
+                let expected = record_mac(&key, &previous_mac, &parsed.record)?;
+                let valid = bool::from(expected.as_bytes().ct_eq(parsed.mac.as_bytes()));
+                previous_mac = parsed.mac;
+                valid
+            }
+            Err(_) => {
+                previous_mac.clear();
+                false
+            }
+        };
+        if !valid {
+            verification.invalid_lines.push(index + 1);
+        }
+    }
+    Ok(verification)
+}
+
+/// 由审计密钥文件派生 HMAC 密钥，与加密使用的派生值相互独立。
+fn derive_audit_key(audit_keyfile: &KeyFile) -> Result<Zeroizing<[u8; 32]>> {
+    let keyfile_hash = audit_keyfile.hash().context("无法派生审计日志密钥")?;
+    let mut mac = HmacSha256::new_from_slice(&keyfile_hash).expect("HMAC 接受任意长度的密钥");
+    mac.update(AUDIT_KEY_CONTEXT);
+    Ok(Zeroizing::new(mac.finalize().into_bytes().into()))
+}
+
+/// 计算一条记录的链式 HMAC（小写十六进制）。
+fn record_mac(key: &[u8; 32], previous_mac: &str, record: &AuditRecord) -> Result<String> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC 接受任意长度的密钥");
+    mac.update(previous_mac.as_bytes());
+    mac.update(&serde_json::to_vec(record)?);
+
+    let mut hex = String::with_capacity(64);
+    for byte in mac.finalize().into_bytes() {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    Ok(hex)
+}
+
+/// 读取日志最后一条记录的 `mac`；日志为空时返回空字符串。
+///
+/// 只读取文件末尾的 [`AUDIT_TAIL_LEN`] 字节，最后一行更长时才读取整个文件。
+fn last_mac(file: &mut File) -> Result<String> {
+    let len = file.metadata()?.len();
+    let mut start = len.saturating_sub(AUDIT_TAIL_LEN);
+    loop {
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).context("读取审计日志失败")?;
+
+        let last_line = tail
+            .split(|&byte| byte == b'\n')
+            .enumerate()
+            .filter(|(_, line)| !line.trim_ascii().is_empty())
+            .last();
+        let Some((position, last)) = last_line else {
+            if start == 0 {
+                return Ok(String::new());
+            }
+            start = 0;
+            continue;
+        };
+        // 从文件中间开始读取时，第一段可能只是某一行的后半部分
+        if position == 0 && start > 0 {
+            start = 0;
+            continue;
+        }
+        let parsed: AuditLine =
+            serde_json::from_slice(last).context("审计日志的最后一行已损坏，无法继续追加")?;
+        return Ok(parsed.mac);
+    }
+}
+
+/// 当前用户名；无法确定时为 `unknown`。
+fn current_user() -> String {
+    ["USER", "USERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|user| !user.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -5,18 +5,55 @@
 //! 该模块提供了对多个文件进行加密或解密的功能。
 //! 它支持目录的递归遍历、按模式包含/排除文件，并能报告详细的处理结果。
 
-use crate::{decrypt::run_decryption_flow, encrypt::run_encryption_flow, keyfile::KeyFile, Level};
-use anyhow::Result;
+use crate::{
+    cleanup::{cleanup_partial_output, commit_partial_file, warn_about_stale_partials},
+    constants::{
+        fixed_overhead_bytes, BUFFER_LEN, CUSTOM_FILE_EXTENSION, EXPIRY_TIME_LEN,
+        MANIFEST_FILE_NAME, SECURITY_LEVEL_LEN,
+    },
+    credentials::{Credential, Credentials},
+    decrypt::{
+        read_header, run_decryption_flow_with_progress, DecryptionOptions, DecryptionOutcome,
+        NameNormalization, OutputPermissions, OverwriteMode,
+    },
+    encrypt::{
+        encrypt_to_partial, encrypted_target_path, ensure_not_special_file,
+        ensure_within_size_limit, hardlink_identity, prepare_encryption,
+        run_encryption_flow_with_progress, EncryptionOptions,
+    },
+    error::{warn_or_fail, FeroxError, Stage, StageContext},
+    format::FileHeader,
+    inspect::{
+        compare_file_with_progress, inspect_file, paired_plaintext_path, verify_file_with_progress,
+    },
+    keyfile::{keyfile_commitment_matches, KeyFile},
+    manifest::RestoreManifest,
+    progress::{ProgressEvent, ProgressSink},
+    readme::write_directory_readmes,
+    stream_io::ByteCounts,
+    watchdog::default_stall_warning,
+    Level,
+};
+use anyhow::{Context, Result};
 use glob::Pattern;
-use std::fs;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Write};
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+#[cfg(not(target_arch = "wasm32"))]
 use walkdir::WalkDir;
 
 /// # 批量操作配置
 ///
 /// 定义了批量处理任务的所有可配置参数。
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct BatchConfig {
     /// 加密时使用的安全级别。
     pub level: Level,
@@ -28,213 +65,2360 @@ pub struct BatchConfig {
     pub include_patterns: Vec<Pattern>,
     /// 用于排除文件的 glob 模式列表。
     pub exclude_patterns: Vec<Pattern>,
+    /// (可选) 接收批量处理进度事件的接收器。
+    ///
+    /// 设置后，各文件的流程把进度事件发送给该接收器；为 `None` 时不报告任何进度。
+    /// 需要终端进度条时可以使用 [`terminal_progress`](crate::progress::terminal_progress)。
+    pub progress_sink: Option<Arc<dyn ProgressSink>>,
+    /// 用于中断清理的共享临时文件登记表。
+    ///
+    /// 每个文件的流程在写入前把目标路径登记到这里，完成后清空。
+    /// 把 `Ctrl+C` 处理器使用的同一个登记表传入，即可在中断时清理不完整的输出文件。
+    pub temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    /// 是否在单个线程中串行执行加密和认证，而不是以流水线方式在独立线程上执行。
+    pub single_thread: bool,
+    /// 并行处理单个文件的线程数（默认为 1，即不拆分文件）。
+    pub threads: usize,
+    /// 读写缓冲区和数据块的大小（字节，默认为 [`BUFFER_LEN`]）。
+    pub buffer_size: usize,
+    /// 加密时是否通过内存映射读取源文件（默认关闭，见 [`EncryptionOptions::mmap`]）。
+    pub mmap: bool,
+    /// (可选) 加密文件的过期时间，见 [`EncryptionOptions::expires_at`]。
+    pub expires_at: Option<SystemTime>,
+    /// 是否使用多个线程并行扫描目录树（默认关闭）。
+    ///
+    /// 适用于包含大量文件的目录树（例如网络存储上的增量运行）；过滤规则和最终的文件顺序
+    /// 与串行扫描完全相同。
+    pub parallel_scan: bool,
+    /// 是否在 [`BatchResult::processed`] 中记录成功处理的文件路径（默认不记录）。
+    ///
+    /// 失败的文件总是会被记录。处理大量文件时，记录所有成功路径会占用额外的内存。
+    pub track_successful_paths: bool,
+    /// 加密前是否跳过正被其他进程打开写入的文件（默认关闭）。
+    ///
+    /// 开启后，加密每个文件之前先尝试获得独占访问：Unix 上为非阻塞的 `flock` 独占锁，
+    /// Windows 上为不共享的打开方式。无法获得时，文件被记录到 [`BatchResult::skipped_locked`]
+    /// 而不是被加密，避免对写入到一半的文件生成损坏的加密副本。
+    pub skip_open_files: bool,
+    /// 是否把密码材料和主密钥锁定在物理内存中（默认关闭，见 [`EncryptionOptions::lock_memory`]）。
+    pub lock_memory: bool,
+    /// 解密时目标文件已存在的处理方式（默认为 [`OverwriteMode::Fail`]）。
+    ///
+    /// 跳过的文件计入 [`BatchResult::skipped_count`]。加密时是否覆盖由 `force_overwrite` 决定。
+    pub overwrite_mode: OverwriteMode,
+    /// (可选) 加密时源文件的大小上限（字节，默认不限制）。
+    ///
+    /// 超过上限的文件在任何密钥派生之前被跳过，记录到 [`BatchResult::skipped_too_large`]，
+    /// 避免误把巨大的文件（例如稀疏的磁盘镜像）纳入批量加密。
+    pub max_file_size: Option<u64>,
+    /// 是否保留扩展属性：加密时记录在文件头中，解密时恢复（默认关闭，见
+    /// [`EncryptionOptions::preserve_xattrs`]）。
+    pub preserve_xattrs: bool,
+    /// 解密出的文件的权限（默认为 [`OutputPermissions::ProcessUmask`]），对每个文件统一适用。
+    ///
+    /// 实际设置的权限位记录在 [`BatchResult::output_modes`] 中。
+    pub permissions: OutputPermissions,
+    /// 解密时恢复文件名使用的 Unicode 规范化形式（默认保持原样，见 [`NameNormalization`]）。
+    pub normalize_names: NameNormalization,
+    /// (可选) 批量加密开始之前检查密码的回调，见 [`BatchConfig::with_password_validator`]。
+    pub password_validator: Option<PasswordValidator>,
+    /// (可选) 无法在目标文件旁边暂存时改用的临时目录（见 [`EncryptionOptions::temp_dir`]）。
+    pub temp_dir: Option<PathBuf>,
+    /// (可选) 解密结果写入的目录（默认写在各加密文件旁边）。
+    ///
+    /// 解密目录时在其中重建相对于该目录的子目录结构；解密文件列表时直接写入该目录。
+    pub output_dir: Option<PathBuf>,
+    /// 是否把所有解密结果直接写入 [`output_dir`](Self::output_dir)，不保留子目录结构（默认关闭）。
+    ///
+    /// 输出只使用文件头中的原始文件名，同名文件按 [`overwrite_mode`](Self::overwrite_mode) 处理
+    /// （[`OverwriteMode::Rename`] 会依次改名）。必须同时设置 `output_dir`。
+    pub flatten: bool,
+    /// 批量解密时凭据看起来不正确之后是否继续处理其余文件（默认关闭）。
+    ///
+    /// 默认情况下，在还没有任何文件解密成功之前，第一个认证失败（或与文件头中的密钥文件承诺
+    /// 不匹配）的文件会中止整个批量解密，而不是对其余每个文件都做完整的密钥派生和认证；
+    /// 结果的 [`BatchResult::aborted_early`] 为 `true`。各文件使用不同凭据的归档需要开启它。
+    pub keep_going: bool,
+    /// 没有任何读写进展多久之后发出停滞警告（默认 60 秒，见 [`EncryptionOptions::stall_warning`]）。
+    pub stall_warning: Duration,
+    /// (可选) 没有任何读写进展多久之后放弃当前文件（默认只警告）。
+    ///
+    /// 被放弃的文件以 [`FeroxError::Stalled`] 记录到失败列表中，批量操作继续处理下一个文件。
+    pub stall_timeout: Option<Duration>,
+    /// 批量加密完成后是否在每个处理过的目录中写入说明文件（默认关闭，见 [`crate::readme`]）。
+    ///
+    /// 说明文件记录工具和格式版本、算法与解密方法，不包含任何机密；写入的路径记录在
+    /// [`BatchResult::readme_paths`] 中。
+    pub write_readme: bool,
+    /// 批量加密时是否在最后重试因正被其他程序占用而失败的文件（默认关闭）。
+    ///
+    /// Windows 上被 Outlook、Excel 等程序以不共享方式打开的文件会因共享冲突而无法读取，
+    /// 归类为 [`FeroxError::FileLocked`]。开启后这些文件先被搁置，其余文件处理完之后再各重试一次，
+    /// 仍然失败的才记录到失败列表中；重试的文件排在 [`BatchResult::processed`] 的最后。
+    pub retry_locked: bool,
+    /// 严格模式：把默认只发出警告或跳过的问题当作失败（默认关闭），适用于无人值守的流水线。
+    ///
+    /// 开启后扫描时无法读取的目录或目录项、残留的 `.part` 文件会使整个批量操作返回错误；
+    /// 特殊文件（扫描目录时也会收集，平时被略过）、超过 `max_file_size` 的文件和 `skip_open_files`
+    /// 发现的被占用文件不再记录为跳过，而是记录到 [`BatchResult::failures`]；无法写入的说明文件也记为失败。
+    /// 单个文件的操作同样以严格模式进行，见 [`EncryptionOptions::strict`] 和 [`DecryptionOptions::strict`]。
+    /// 过滤模式排除的文件和 [`OverwriteMode::Skip`] 跳过的已存在文件是明确要求的跳过，不受影响。
+    pub strict: bool,
+    /// (可选) 批量解密时逐个文件尝试的凭据表（见 [`crate::credentials`]）。
+    ///
+    /// 每个文件依次尝试与之匹配的凭据，最后是调用时传入的密码和密钥文件（密码为空且没有密钥文件时不尝试）；
+    /// 文件头中的密钥文件承诺可以快速排除不可能正确的凭据。所有凭据都认证失败时，失败信息中列出尝试过的凭据。
+    /// 设置后 [`keep_going`](Self::keep_going) 的提前中止不再适用。
+    pub credentials: Option<Arc<Credentials>>,
+    /// 批量加密时同一份内容的多个硬链接是否只加密一次（默认关闭，每个路径各自加密）。
+    ///
+    /// 扫描到与之前加密成功的文件设备号和 inode 号相同的文件时跳过它，记录到
+    /// [`BatchResult::skipped_hardlinks`]，省下的字节数计入 [`BatchResult::hardlink_bytes_saved`]。
+    /// 同时生成恢复校验清单时链接关系记录在清单中，解密时可以用
+    /// [`preserve_hardlinks`](Self::preserve_hardlinks) 重建。只对 Unix 上的逐个加密有效，
+    /// 不影响 [`batch_encrypt_files_atomic`]。
+    pub dedupe_hardlinks: bool,
+    /// 批量解密目录后是否按目录中恢复校验清单记录的链接关系重建硬链接（默认关闭）。
+    ///
+    /// 链接指向已解密的文件，路径与加密时相同（设置了 [`output_dir`](Self::output_dir) 时位于其中）；
+    /// 重建的链接记录在 [`BatchResult::restored_hardlinks`] 中。不能与 [`flatten`](Self::flatten) 同时使用。
+    pub preserve_hardlinks: bool,
+    /// 批量加密时跳过加密结果已是最新的文件（默认关闭），让重复运行同一批量加密不再重复工作。
+    ///
+    /// 目标文件已存在时只读取它的文件头：原始文件名、明文大小和记录的源文件修改时间都与源文件一致时，
+    /// 不做任何密钥派生和加密，记录到 [`BatchResult::skipped_up_to_date`]；不一致（包括没有记录修改时间）
+    /// 时按 [`force_overwrite`](Self::force_overwrite) 照常覆盖或失败。设置后加密的文件会记录源文件的
+    /// 修改时间（见 [`EncryptionOptions::record_source_mtime`]），因此第一次运行也应设置它。
+    /// 不影响 [`batch_encrypt_files_atomic`]。
+    pub skip_if_encrypted_matches: bool,
+    /// 使用密钥文件加密时是否在文件头中记录密钥文件承诺（默认关闭），
+    /// 代价见 [`EncryptionOptions::keyfile_commitment`]。
+    pub keyfile_commitment: bool,
+}
+
+/// 检查密码是否符合调用者密码策略的回调，返回 `Err` 表示拒绝。
+pub type PasswordValidator = Arc<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+impl fmt::Debug for BatchConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchConfig")
+            .field("level", &self.level)
+            .field("force_overwrite", &self.force_overwrite)
+            .field("recursive", &self.recursive)
+            .field("include_patterns", &self.include_patterns)
+            .field("exclude_patterns", &self.exclude_patterns)
+            .field("progress_sink", &self.progress_sink.is_some())
+            .field("temp_file_path", &self.temp_file_path)
+            .field("single_thread", &self.single_thread)
+            .field("threads", &self.threads)
+            .field("buffer_size", &self.buffer_size)
+            .field("mmap", &self.mmap)
+            .field("expires_at", &self.expires_at)
+            .field("parallel_scan", &self.parallel_scan)
+            .field("track_successful_paths", &self.track_successful_paths)
+            .field("skip_open_files", &self.skip_open_files)
+            .field("lock_memory", &self.lock_memory)
+            .field("overwrite_mode", &self.overwrite_mode)
+            .field("max_file_size", &self.max_file_size)
+            .field("preserve_xattrs", &self.preserve_xattrs)
+            .field("permissions", &self.permissions)
+            .field("normalize_names", &self.normalize_names)
+            .field("password_validator", &self.password_validator.is_some())
+            .field("temp_dir", &self.temp_dir)
+            .field("output_dir", &self.output_dir)
+            .field("flatten", &self.flatten)
+            .field("keep_going", &self.keep_going)
+            .field("stall_warning", &self.stall_warning)
+            .field("stall_timeout", &self.stall_timeout)
+            .field("write_readme", &self.write_readme)
+            .field("retry_locked", &self.retry_locked)
+            .field("strict", &self.strict)
+            .field(
+                "credentials",
+                &self
+                    .credentials
+                    .as_ref()
+                    .map(|credentials| credentials.len()),
+            )
+            .field("dedupe_hardlinks", &self.dedupe_hardlinks)
+            .field("preserve_hardlinks", &self.preserve_hardlinks)
+            .field("skip_if_encrypted_matches", &self.skip_if_encrypted_matches)
+            .field("keyfile_commitment", &self.keyfile_commitment)
+            .finish()
+    }
+}
+
+impl BatchConfig {
+    /// 设置批量加密开始之前检查密码的回调，用于实施调用者自己的密码策略
+    /// （最低熵、黑名单、查询 LDAP 策略等）。
+    ///
+    /// 回调在任何文件被读取、任何 Argon2 派生开始之前调用一次；返回 `Err` 时批量加密立即以该错误
+    /// 失败，不处理任何文件。只适用于加密，解密和验证不调用它。
+    ///
+    /// # 安全性
+    ///
+    /// 回调收到的是明文密码，必须是可信的代码：不要记录、保存或把密码发送到不可信的地方。
+    pub fn with_password_validator(
+        mut self,
+        validator: impl Fn(&str) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.password_validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// 用 [`password_validator`](Self::password_validator) 检查密码；没有设置时总是通过。
+    fn validate_password(&self, password: &str) -> Result<()> {
+        match &self.password_validator {
+            Some(validator) => validator(password).context("密码不符合密码策略，未处理任何文件"),
+            None => Ok(()),
+        }
+    }
+
+    /// 根据配置构建单个文件的加密选项。
+    fn encryption_options<'a>(
+        &'a self,
+        password: &'a str,
+        keyfile: Option<&'a KeyFile>,
+    ) -> EncryptionOptions<'a> {
+        EncryptionOptions {
+            password,
+            keyfile,
+            level: self.level,
+            force_overwrite: self.force_overwrite,
+            single_thread: self.single_thread,
+            threads: self.threads,
+            buffer_size: self.buffer_size,
+            mmap: self.mmap,
+            expires_at: self.expires_at,
+            lock_memory: self.lock_memory,
+            max_file_size: self.max_file_size,
+            preserve_xattrs: self.preserve_xattrs,
+            temp_dir: self.temp_dir.as_deref(),
+            recipient_count: 1,
+            delete_source_after_encrypt: false,
+            force_delete_hardlinked: false,
+            stall_warning: self.stall_warning,
+            stall_timeout: self.stall_timeout,
+            strict: self.strict,
+            record_source_mtime: self.skip_if_encrypted_matches,
+            keyfile_commitment: self.keyfile_commitment,
+            rate_limit: None,
+            test_nonces: None,
+        }
+    }
+
+    /// 根据配置构建单个文件的解密选项。
+    fn decryption_options<'a>(
+        &'a self,
+        password: &'a str,
+        keyfile: Option<&'a KeyFile>,
+    ) -> DecryptionOptions<'a> {
+        DecryptionOptions {
+            password,
+            keyfile,
+            single_thread: self.single_thread,
+            threads: self.threads,
+            buffer_size: self.buffer_size,
+            lock_memory: self.lock_memory,
+            overwrite_mode: self.overwrite_mode,
+            preserve_xattrs: self.preserve_xattrs,
+            permissions: self.permissions,
+            normalize_names: self.normalize_names,
+            temp_dir: self.temp_dir.as_deref(),
+            output_dir: self.output_dir.as_deref(),
+            stall_warning: self.stall_warning,
+            stall_timeout: self.stall_timeout,
+            strict: self.strict,
+        }
+    }
 }
 
 impl Default for BatchConfig {
     /// 提供一个默认的 `BatchConfig` 实例。
+    ///
+    /// 安全级别取 [`default_level`](crate::default_level)。
     fn default() -> Self {
         Self {
-            level: Level::Moderate,
+            level: crate::default_level(),
             force_overwrite: false,
             recursive: false,
             // 默认包含所有文件
             include_patterns: vec![Pattern::new("*").unwrap()],
             exclude_patterns: Vec::new(),
+            progress_sink: None,
+            temp_file_path: Arc::new(Mutex::new(None)),
+            single_thread: false,
+            threads: 1,
+            buffer_size: BUFFER_LEN,
+            mmap: false,
+            expires_at: None,
+            parallel_scan: false,
+            track_successful_paths: false,
+            skip_open_files: false,
+            lock_memory: false,
+            overwrite_mode: OverwriteMode::Fail,
+            max_file_size: None,
+            preserve_xattrs: false,
+            permissions: OutputPermissions::ProcessUmask,
+            normalize_names: NameNormalization::None,
+            password_validator: None,
+            temp_dir: None,
+            output_dir: None,
+            flatten: false,
+            keep_going: false,
+            stall_warning: default_stall_warning(),
+            stall_timeout: None,
+            write_readme: false,
+            retry_locked: false,
+            strict: false,
+            credentials: None,
+            dedupe_hardlinks: false,
+            preserve_hardlinks: false,
+            skip_if_encrypted_matches: false,
+            keyfile_commitment: false,
+        }
+    }
+}
+
+/// # 批量操作结果
+///
+/// 存储批量处理任务完成后的统计信息。
+#[derive(Debug)]
+pub struct BatchResult {
+    /// 成功处理的文件数量。
+    pub success_count: usize,
+    /// 处理失败的文件数量。
+    pub failure_count: usize,
+    /// 失败文件的列表（按处理顺序），包含文件路径和归类后的错误。
+    pub failures: Vec<(PathBuf, FeroxError)>,
+    /// 成功处理的总字节数，按操作前的文件元数据统计：加密时是明文大小，解密时是加密文件大小。
+    ///
+    /// 要区分读取和写入的数据量，请使用 `bytes_read` 和 `bytes_written`。
+    pub total_bytes: u64,
+    /// 成功加密或解密的文件在流式处理中实际读取的总字节数（解密时包括文件头和认证标签）。
+    pub bytes_read: u64,
+    /// 成功加密或解密的文件实际写入的总字节数（加密时包括文件头和认证标签）。
+    pub bytes_written: u64,
+    /// 是否以全有或全无的方式完成：所有输出都已提交，或者所有输出都已回滚。
+    ///
+    /// 只有 [`batch_encrypt_files_atomic`] 会设置该字段；其他尽力而为的批量操作始终为 `false`。
+    pub was_atomic: bool,
+    /// 已处理的文件列表（按处理顺序），包含文件路径和是否成功。
+    ///
+    /// 失败的文件总是会被记录；成功的文件只有在 [`BatchConfig::track_successful_paths`]
+    /// 为 `true` 时才会被记录。
+    pub processed: Vec<(PathBuf, bool)>,
+    /// 因正被其他进程打开而跳过、没有加密的文件（见 [`BatchConfig::skip_open_files`]）。
+    pub skipped_locked: Vec<PathBuf>,
+    /// 因目标文件已存在而跳过的文件数量（见 [`OverwriteMode::Skip`]），不计入成功或失败。
+    pub skipped_count: usize,
+    /// 因目标文件已存在而跳过的文件（按处理顺序，与 `skipped_count` 对应）。
+    pub skipped_existing: Vec<PathBuf>,
+    /// 因超过大小上限而跳过、没有加密的文件（见 [`BatchConfig::max_file_size`]）。
+    pub skipped_too_large: Vec<PathBuf>,
+    /// 因不是普通文件（命名管道、设备节点或套接字）而跳过的文件，见 [`FeroxError::SpecialFile`]。
+    pub skipped_special: Vec<PathBuf>,
+    /// 成功解密的文件及其最终输出路径（按处理顺序）。
+    ///
+    /// 只有在 [`BatchConfig::track_successful_paths`] 为 `true` 时才会被记录；
+    /// 输出路径反映了 [`BatchConfig::output_dir`]、[`BatchConfig::flatten`] 和改名的结果。
+    pub output_paths: Vec<(PathBuf, PathBuf)>,
+    /// 解密出的文件及按 [`BatchConfig::permissions`] 实际设置的权限位（按处理顺序）。
+    ///
+    /// 与 `output_paths` 一样只有在 [`BatchConfig::track_successful_paths`] 为 `true` 时才会被记录；
+    /// 非 Unix 平台上没有权限位，始终为空。
+    pub output_modes: Vec<(PathBuf, u32)>,
+    /// 解密的文件及其文件头中的安全级别（按处理顺序），`None` 表示自定义 Argon2 参数。
+    ///
+    /// 与 `output_paths` 一样只有在 [`BatchConfig::track_successful_paths`] 为 `true` 时才会被记录。
+    pub levels: Vec<(PathBuf, Option<Level>)>,
+    /// 失败的文件及出错的操作阶段（按处理顺序），见 [`Stage`]。
+    ///
+    /// 只记录由加密或解密流程产生、带有阶段的错误；与 `failures` 不同，不带阶段的失败不在其中。
+    pub failure_stages: Vec<(PathBuf, Stage)>,
+    /// 批量解密是否因为凭据看起来不正确而提前中止（见 [`BatchConfig::keep_going`]）。
+    pub aborted_early: bool,
+    /// 提前中止时没有处理的文件（批量解密多个路径时也可能是目录），按原来的顺序排列。
+    pub unprocessed: Vec<PathBuf>,
+    /// 批量加密写入的说明文件（见 [`BatchConfig::write_readme`]），每个目录一个文本说明文件的路径。
+    pub readme_paths: Vec<PathBuf>,
+    /// 因与已加密的文件互为硬链接而跳过的文件，以及保存了这份内容的那个文件（见 [`BatchConfig::dedupe_hardlinks`]）。
+    pub skipped_hardlinks: Vec<(PathBuf, PathBuf)>,
+    /// 跳过硬链接重复文件而少加密的字节数。
+    pub hardlink_bytes_saved: u64,
+    /// 批量解密后重建的硬链接及其指向的已解密文件（见 [`BatchConfig::preserve_hardlinks`]）。
+    pub restored_hardlinks: Vec<(PathBuf, PathBuf)>,
+    /// 因加密结果已是最新而跳过、没有重新加密的文件（见 [`BatchConfig::skip_if_encrypted_matches`]）。
+    pub skipped_up_to_date: Vec<PathBuf>,
+    /// 解密成功、但文件头含有本版本不认识的非关键扩展的文件，以及被忽略的扩展类型（按处理顺序）。
+    ///
+    /// 与 `failures` 一样总是会被记录；这些文件可能由更新版本的程序加密，见 [`BatchConfig::strict`]。
+    pub ignored_extensions: Vec<(PathBuf, Vec<u16>)>,
+    /// 与 `processed` 一一对应的每个文件的字节数（失败的文件为 0）。
+    processed_bytes: Vec<u64>,
+    /// 与 `processed` 一一对应的每个文件实际读写的字节数（失败或不是加解密的文件为 0）。
+    processed_counts: Vec<ByteCounts>,
+    /// 是否记录成功处理的文件路径。
+    track_successful_paths: bool,
+}
+
+impl BatchResult {
+    /// 创建一个新的、空的 `BatchResult`。
+    fn new(track_successful_paths: bool) -> Self {
+        Self {
+            success_count: 0,
+            failure_count: 0,
+            failures: Vec::new(),
+            total_bytes: 0,
+            bytes_read: 0,
+            bytes_written: 0,
+            was_atomic: false,
+            processed: Vec::new(),
+            skipped_locked: Vec::new(),
+            skipped_count: 0,
+            skipped_existing: Vec::new(),
+            skipped_too_large: Vec::new(),
+            skipped_special: Vec::new(),
+            output_paths: Vec::new(),
+            output_modes: Vec::new(),
+            levels: Vec::new(),
+            failure_stages: Vec::new(),
+            aborted_early: false,
+            unprocessed: Vec::new(),
+            readme_paths: Vec::new(),
+            skipped_hardlinks: Vec::new(),
+            hardlink_bytes_saved: 0,
+            restored_hardlinks: Vec::new(),
+            skipped_up_to_date: Vec::new(),
+            ignored_extensions: Vec::new(),
+            processed_bytes: Vec::new(),
+            processed_counts: Vec::new(),
+            track_successful_paths,
+        }
+    }
+
+    /// 记录一次成功操作。
+    fn add_success(&mut self, path: &Path, file_size: u64) {
+        self.success_count += 1;
+        self.total_bytes += file_size;
+        if self.track_successful_paths {
+            self.processed.push((path.to_path_buf(), true));
+            self.processed_bytes.push(file_size);
+            self.processed_counts.push(ByteCounts::default());
+        }
+    }
+
+    /// 记录一次成功的加密或解密，以及流式处理中实际读取和写入的字节数。
+    fn add_transfer(&mut self, path: &Path, file_size: u64, counts: ByteCounts) {
+        self.add_success(path, file_size);
+        self.bytes_read += counts.read;
+        self.bytes_written += counts.written;
+        if self.track_successful_paths {
+            if let Some(last) = self.processed_counts.last_mut() {
+                *last = counts;
+            }
+        }
+    }
+
+    /// 路径不是普通文件时记录为跳过（`strict` 时记录为失败）并返回 `true`。
+    ///
+    /// 必须在打开文件之前调用，否则读取没有写入者的命名管道会使整个批量操作挂起。
+    fn skip_special_file(&mut self, path: &Path, strict: bool) -> bool {
+        match ensure_not_special_file(path) {
+            Ok(()) => false,
+            Err(e) if strict => {
+                log::error!("❌ {}: {e}", path.display());
+                self.add_failure(path.to_path_buf(), e);
+                true
+            }
+            Err(e) => {
+                log::warn!("⏭️  已跳过 {}: {e}", path.display());
+                self.skipped_special.push(path.to_path_buf());
+                true
+            }
+        }
+    }
+
+    /// 记录一次失败操作。
+    fn add_failure(&mut self, path: PathBuf, error: FeroxError) {
+        self.failure_count += 1;
+        self.processed.push((path.clone(), false));
+        self.processed_bytes.push(0);
+        self.processed_counts.push(ByteCounts::default());
+        self.failures.push((path, error));
+    }
+
+    /// 记录流程返回的错误：归类为 [`FeroxError`]，并记录错误链中的操作阶段。
+    fn add_error(&mut self, path: PathBuf, error: &anyhow::Error) {
+        if let Some(stage) = Stage::of(error) {
+            self.failure_stages.push((path.clone(), stage));
+        }
+        self.add_failure(path, FeroxError::classify(error));
+    }
+
+    /// 失败的文件 `path` 出错的操作阶段。
+    pub fn failure_stage(&self, path: &Path) -> Option<Stage> {
+        self.failure_stages
+            .iter()
+            .find(|(failed, _)| failed == path)
+            .map(|(_, stage)| *stage)
+    }
+
+    /// 是否所有文件都处理成功。
+    pub fn was_successful(&self) -> bool {
+        self.failure_count == 0
+    }
+
+    /// 第一个失败的文件及其错误（按处理顺序）。
+    pub fn first_failure(&self) -> Option<&(PathBuf, FeroxError)> {
+        self.failures.first()
+    }
+
+    /// 最后一个失败的文件及其错误（按处理顺序）。
+    pub fn last_failure(&self) -> Option<&(PathBuf, FeroxError)> {
+        self.failures.last()
+    }
+
+    /// 所有因认证失败（密码/密钥文件错误或文件被篡改）而失败的文件。
+    pub fn all_authentication_failures(&self) -> Vec<&PathBuf> {
+        self.failures
+            .iter()
+            .filter(|(_, error)| error.is_authentication_failure())
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// 所有因 I/O 错误而失败的文件。
+    pub fn all_io_failures(&self) -> Vec<&PathBuf> {
+        self.failures
+            .iter()
+            .filter(|(_, error)| error.is_io())
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// 所有因正被其他程序占用而失败的文件，见 [`FeroxError::FileLocked`]。
+    pub fn all_locked_failures(&self) -> Vec<&PathBuf> {
+        self.failures
+            .iter()
+            .filter(|(_, error)| error.is_file_locked())
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// 按文件扩展名和顶层子目录分组统计成功、失败和跳过的文件，见 [`BatchBreakdown`]。
+    ///
+    /// `root` 是批量处理的目录：位于其中子目录里的文件按第一层子目录归组，直接位于其中的文件归入
+    /// `"."`；为 `None`（或文件不在 `root` 之下）时按文件所在的目录归组。
+    /// 成功的文件只有在启用了 [`BatchConfig::track_successful_paths`] 时才会被统计。
+    pub fn breakdown(&self, root: Option<&Path>) -> BatchBreakdown {
+        let mut breakdown = BatchBreakdown::default();
+        let succeeded = self
+            .processed
+            .iter()
+            .filter(|(_, success)| *success)
+            .map(|(path, _)| path);
+        let skipped = self
+            .skipped_existing
+            .iter()
+            .chain(&self.skipped_locked)
+            .chain(&self.skipped_too_large)
+            .chain(&self.skipped_special)
+            .chain(self.skipped_hardlinks.iter().map(|(path, _)| path))
+            .chain(&self.skipped_up_to_date);
+        for path in succeeded {
+            breakdown.record(path, root, |counts| counts.succeeded += 1);
+        }
+        for (path, _) in &self.failures {
+            breakdown.record(path, root, |counts| counts.failed += 1);
+        }
+        for path in skipped {
+            breakdown.record(path, root, |counts| counts.skipped += 1);
+        }
+        breakdown
+    }
+
+    /// 以 CSV 格式写出所有已记录的文件，便于在 shell 管道中处理。
+    ///
+    /// 第一行是表头 `path,status,bytes,error_message`，之后每个文件一行（按处理顺序）：
+    /// `status` 为 `success` 或 `failure`，`bytes` 为该文件的字节数（失败的文件为 0），
+    /// `error_message` 只有失败的文件才有。含有逗号、引号或换行的字段按 RFC 4180 加引号。
+    ///
+    /// 与 [`IntoIterator`] 一样，成功的文件只有在启用了
+    /// [`BatchConfig::track_successful_paths`] 时才会出现。
+    ///
+    /// *Writes one CSV row per recorded file, with the per-file byte count.*
+    pub fn to_csv(&self, mut writer: impl Write) -> Result<()> {
+        writer.write_all(CSV_HEADER.as_bytes())?;
+        writer.write_all(b"\n")?;
+
+        let mut failures = self.failures.iter();
+        for ((path, success), bytes) in self.processed.iter().zip(&self.processed_bytes) {
+            let (status, message) = if *success {
+                ("success", String::new())
+            } else {
+                // 失败记录与 `failures` 中的条目按相同顺序一一对应
+                let message = failures
+                    .next()
+                    .map_or_else(String::new, |(_, error)| error.to_string());
+                ("failure", message)
+            };
+            let row = [
+                csv_field(&path.to_string_lossy()),
+                status.to_string(),
+                bytes.to_string(),
+                csv_field(&message),
+            ];
+            writer.write_all(row.join(",").as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// 以 JSON 格式写出统计信息和所有已记录的文件。
+    ///
+    /// 输出是一个对象：`success_count`、`failure_count`、`skipped_count`、`total_bytes`、
+    /// `bytes_read`、`bytes_written`，以及按处理顺序排列的 `files` 数组。每个文件有 `path`、
+    /// `status` 和 `bytes`，与 [`to_csv`](Self::to_csv) 的列相同，另有实际读写的 `bytes_read` 和
+    /// `bytes_written`；失败的文件还有 `error`，流程记录了阶段时还有 `stage`
+    /// （例如 `"key_derivation"` 或 `{"streaming": {"bytes_done": 1048576}}`）。
+    ///
+    /// *Writes the counters and one object per recorded file, including the failing stage.*
+    pub fn to_json(&self, mut writer: impl Write) -> Result<()> {
+        let mut failures = self.failures.iter();
+        let mut stages = self.failure_stages.iter().peekable();
+        let files: Vec<serde_json::Value> = self
+            .processed
+            .iter()
+            .zip(&self.processed_bytes)
+            .zip(&self.processed_counts)
+            .map(|(((path, success), bytes), counts)| {
+                let mut file = serde_json::json!({
+                    "path": path.to_string_lossy(),
+                    "status": if *success { "success" } else { "failure" },
+                    "bytes": bytes,
+                    "bytes_read": counts.read,
+                    "bytes_written": counts.written,
+                });
+                if !*success {
+                    // 失败记录与 `failures` 中的条目按相同顺序一一对应，`failure_stages` 是它的子序列
+                    if let Some((_, error)) = failures.next() {
+                        file["error"] = error.to_string().into();
+                    }
+                    if let Some((_, stage)) = stages.next_if(|(failed, _)| failed == path) {
+                        file["stage"] = serde_json::to_value(stage)?;
+                    }
+                }
+                Ok(file)
+            })
+            .collect::<Result<_>>()?;
+        let report = serde_json::json!({
+            "success_count": self.success_count,
+            "failure_count": self.failure_count,
+            "skipped_count": self.skipped_count,
+            "total_bytes": self.total_bytes,
+            "bytes_read": self.bytes_read,
+            "bytes_written": self.bytes_written,
+            "aborted_early": self.aborted_early,
+            "unprocessed": self.unprocessed.iter().map(|path| path.to_string_lossy()).collect::<Vec<_>>(),
+            "files": files,
+        });
+        serde_json::to_writer_pretty(&mut writer, &report)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// 读取 [`to_csv`](Self::to_csv) 写出的 CSV，重建批量操作结果。
+    ///
+    /// 重建的结果记录了所有行（相当于启用了 `track_successful_paths`），
+    /// 失败文件的错误归类为 [`FeroxError::Other`]；跳过的文件不在 CSV 中，因此不会恢复。
+    ///
+    /// # 错误
+    ///
+    /// 表头不匹配、某行字段数不是 4、状态不是 `success`/`failure` 或字节数无效时返回错误。
+    pub fn from_csv(mut reader: impl Read) -> Result<BatchResult> {
+        let mut text = String::new();
+        reader
+            .read_to_string(&mut text)
+            .context("无法读取 CSV 数据")?;
+
+        let mut records = parse_csv(&text)?.into_iter();
+        match records.next() {
+            Some(header) if header.join(",") == CSV_HEADER => {}
+            _ => anyhow::bail!("CSV 表头必须是 “{CSV_HEADER}”"),
         }
+
+        let mut result = BatchResult::new(true);
+        for (index, record) in records.enumerate() {
+            // 表头是第 1 行
+            let line = index + 2;
+            let [path, status, bytes, message]: [String; 4] =
+                record.try_into().map_err(|record: Vec<String>| {
+                    anyhow::anyhow!("CSV 第 {line} 行有 {} 个字段，应为 4 个", record.len())
+                })?;
+            let bytes: u64 = bytes
+                .parse()
+                .with_context(|| format!("CSV 第 {line} 行的字节数无效: {bytes}"))?;
+            let path = PathBuf::from(path);
+            match status.as_str() {
+                "success" => result.add_success(&path, bytes),
+                "failure" => {
+                    result.add_failure(path, FeroxError::Other(message));
+                    // 保留原始行中的字节数
+                    if let Some(last) = result.processed_bytes.last_mut() {
+                        *last = bytes;
+                    }
+                }
+                other => anyhow::bail!("CSV 第 {line} 行的状态无效: {other}"),
+            }
+        }
+        Ok(result)
+    }
+
+    /// 将另一批操作的结果合并到当前结果中。
+    fn merge(&mut self, other: BatchResult) {
+        self.success_count += other.success_count;
+        self.failure_count += other.failure_count;
+        self.failures.extend(other.failures);
+        self.total_bytes += other.total_bytes;
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+        self.processed.extend(other.processed);
+        self.processed_bytes.extend(other.processed_bytes);
+        self.processed_counts.extend(other.processed_counts);
+        self.skipped_locked.extend(other.skipped_locked);
+        self.skipped_count += other.skipped_count;
+        self.skipped_existing.extend(other.skipped_existing);
+        self.skipped_too_large.extend(other.skipped_too_large);
+        self.skipped_special.extend(other.skipped_special);
+        self.output_paths.extend(other.output_paths);
+        self.output_modes.extend(other.output_modes);
+        self.levels.extend(other.levels);
+        self.failure_stages.extend(other.failure_stages);
+        self.aborted_early |= other.aborted_early;
+        self.unprocessed.extend(other.unprocessed);
+        self.skipped_hardlinks.extend(other.skipped_hardlinks);
+        self.hardlink_bytes_saved += other.hardlink_bytes_saved;
+        self.restored_hardlinks.extend(other.restored_hardlinks);
+        self.skipped_up_to_date.extend(other.skipped_up_to_date);
+        self.ignored_extensions.extend(other.ignored_extensions);
+    }
+}
+
+impl IntoIterator for BatchResult {
+    type Item = (PathBuf, Result<(), String>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    /// 按处理顺序遍历所有已记录的文件：成功为 `Ok(())`，失败为带错误消息的 `Err`。
+    ///
+    /// 成功的文件只有在启用了 [`BatchConfig::track_successful_paths`] 时才会出现。
+    ///
+    /// *Iterates over the recorded files in processing order. Successful files are
+    /// only included when `track_successful_paths` was enabled.*
+    fn into_iter(self) -> Self::IntoIter {
+        let mut failures = self.failures.into_iter();
+        self.processed
+            .into_iter()
+            .map(|(path, success)| {
+                if success {
+                    (path, Ok(()))
+                } else {
+                    // 失败记录与 `failures` 中的条目按相同顺序一一对应
+                    let message = failures
+                        .next()
+                        .map_or_else(String::new, |(_, error)| error.to_string());
+                    (path, Err(message))
+                }
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+/// 一组文件中成功、失败和跳过的数量。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupCounts {
+    /// 成功处理的文件数量。
+    pub succeeded: usize,
+    /// 处理失败的文件数量。
+    pub failed: usize,
+    /// 跳过的文件数量（目标已存在、正被打开、超过大小上限或不是普通文件）。
+    pub skipped: usize,
+}
+
+impl GroupCounts {
+    /// 该组的文件总数。
+    pub fn total(&self) -> usize {
+        self.succeeded + self.failed + self.skipped
+    }
+}
+
+/// # 分组统计 (Batch Breakdown)
+///
+/// [`BatchResult::breakdown`] 的结果：按扩展名和顶层子目录分组的处理统计，
+/// 用于一眼看出失败集中在哪类文件或哪个目录（例如某个目录中被 Outlook 锁定的 `.pst` 文件）。
+///
+/// *Success, failure and skip counts grouped by file extension and by top-level subdirectory.*
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchBreakdown {
+    /// 按小写扩展名分组；加密文件按 `.feroxcrypt` 之前的扩展名归组，没有扩展名的文件归入 `""`。
+    pub by_extension: BTreeMap<String, GroupCounts>,
+    /// 按顶层子目录分组（见 [`BatchResult::breakdown`]）。
+    pub by_directory: BTreeMap<String, GroupCounts>,
+}
+
+impl BatchBreakdown {
+    /// 用 `bump` 同时更新 `path` 所属的扩展名分组和目录分组。
+    fn record(&mut self, path: &Path, root: Option<&Path>, bump: fn(&mut GroupCounts)) {
+        bump(self.by_extension.entry(extension_key(path)).or_default());
+        bump(
+            self.by_directory
+                .entry(directory_key(path, root))
+                .or_default(),
+        );
+    }
+}
+
+/// 文件的分组扩展名：小写，去掉加密文件的 `.feroxcrypt` 后缀。
+fn extension_key(path: &Path) -> String {
+    let path = match path.extension() {
+        Some(extension) if extension == CUSTOM_FILE_EXTENSION => {
+            Path::new(path.file_stem().unwrap_or_default())
+        }
+        _ => path,
+    };
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// 文件的分组目录：`root` 之下的第一层子目录，直接位于 `root` 中时为 `"."`。
+fn directory_key(path: &Path, root: Option<&Path>) -> String {
+    if let Some(relative) = root.and_then(|root| path.strip_prefix(root).ok()) {
+        let mut components = relative.components();
+        return match (components.next(), components.next()) {
+            (Some(first), Some(_)) => first.as_os_str().to_string_lossy().into_owned(),
+            _ => ".".to_string(),
+        };
+    }
+    path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map_or_else(|| ".".to_string(), |parent| parent.display().to_string())
+}
+
+/// [`BatchResult::to_csv`] 写出的表头。
+const CSV_HEADER: &str = "path,status,bytes,error_message";
+
+/// 按 RFC 4180 转义一个 CSV 字段：含有逗号、引号或换行时用引号包围，内部引号加倍。
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 把 CSV 文本解析为记录列表，支持带引号的字段（可包含逗号、换行和加倍的引号）。
+///
+/// 行尾可以是 `\n` 或 `\r\n`；空行被忽略。
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        anyhow::bail!("CSV 数据在引号字段中途结束");
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// 查找目录中的孤立旁路元数据文件：`*.feroxcrypt.meta.json` 存在，但对应的 `.feroxcrypt` 文件已不存在。
+///
+/// 遵循 `config` 中的递归、并行扫描和包含/排除设置，结果按路径排序。
+///
+/// # 错误
+///
+/// `dir` 不是目录时返回错误。
+pub fn find_orphaned_sidecars(dir: &Path, config: &BatchConfig) -> Result<Vec<PathBuf>> {
+    let orphans = collect_files(dir, config, false)?
+        .into_iter()
+        .filter(|path| sidecar_target(path).is_some_and(|target| !target.exists()))
+        .collect();
+    Ok(orphans)
+}
+
+/// 删除目录中的孤立旁路元数据文件（见 [`find_orphaned_sidecars`]），返回被删除的路径。
+///
+/// `dry_run` 为 `true` 时只返回找到的文件，不删除任何内容。
+///
+/// # 错误
+///
+/// `dir` 不是目录，或删除某个文件失败时返回错误；在此之前找到的文件已被删除。
+pub fn cleanup_orphaned_sidecars(
+    dir: &Path,
+    config: &BatchConfig,
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    let orphans = find_orphaned_sidecars(dir, config)?;
+    if !dry_run {
+        for path in &orphans {
+            fs::remove_file(path)
+                .with_context(|| format!("无法删除孤立的旁路文件: {}", path.display()))?;
+            log::info!("已删除孤立的旁路文件: {}", path.display());
+        }
+    }
+    Ok(orphans)
+}
+
+/// 旁路元数据文件对应的加密文件路径；`path` 不是 `*.feroxcrypt.meta.json` 时返回 `None`。
+fn sidecar_target(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let target = name.strip_suffix(crate::constants::SIDECAR_FILE_SUFFIX)?;
+    let stem = target.strip_suffix(crate::constants::CUSTOM_FILE_EXTENSION)?;
+    (stem.ends_with('.') && stem.len() > 1).then(|| path.with_file_name(target))
+}
+
+/// 批量加密计划中对一个文件预计执行的操作。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// 加密该文件。
+    Encrypt,
+    /// 不是普通文件（命名管道、设备节点或套接字），将被跳过。
+    SkipSpecialFile,
+    /// 正被其他进程打开（见 [`BatchConfig::skip_open_files`]），将被跳过。
+    SkipOpenFile,
+    /// 超过大小上限（见 [`BatchConfig::max_file_size`]），将被跳过。
+    SkipTooLarge,
+    /// 与之前计划加密的文件是同一份内容的硬链接（见 [`BatchConfig::dedupe_hardlinks`]），将被跳过。
+    SkipHardLinkDuplicate,
+    /// 目标位置已有与之对应的加密文件（见 [`BatchConfig::skip_if_encrypted_matches`]），将被跳过。
+    SkipAlreadyUpToDate,
+}
+
+/// 批量加密计划中的一个文件。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedItem {
+    /// 源文件路径。
+    pub source: PathBuf,
+    /// 加密输出的目标路径。
+    pub target: PathBuf,
+    /// 源文件的大小（字节）；无法读取元数据时为 0。
+    pub size: u64,
+    /// 加密文件的预计大小（字节）。
+    ///
+    /// 包含文件头、安全级别和过期时间扩展以及认证标签；使用密钥文件时文件头还会多出
+    /// `4 + KEYFILE_COMMITMENT_LEN` 字节的承诺，保留扩展属性时多出的字节数取决于属性本身。
+    pub estimated_output_size: u64,
+    /// 预计执行的操作。
+    pub action: PlannedAction,
+    /// 预检发现的问题，例如跳过的原因、已存在的目标文件或无效的源文件。
+    ///
+    /// 对 [`PlannedAction::Encrypt`] 的文件，除“将被覆盖”之外的警告都意味着执行时该文件会失败。
+    pub warnings: Vec<String>,
+}
+
+/// # 批量加密计划 (Batch Plan)
+///
+/// [`plan_batch_encrypt`] 的结果：一次批量加密将处理的文件、目标路径、大小和预检警告。
+/// 生成计划只扫描和检查文件，不需要密码，也不会修改任何文件；图形界面可以先展示计划，
+/// 用户确认后再用 [`execute_batch_plan`] 执行同一份计划。
+///
+/// *The work plan of a batch encryption as data: per-file source, target, size and preflight
+/// warnings. Planning needs no password; [`execute_batch_plan`] runs exactly these files.*
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchPlan {
+    /// 计划中的文件，按执行顺序排列。
+    pub items: Vec<PlannedItem>,
+    /// 与单个文件无关的警告，例如目录中残留的不完整输出文件。
+    pub warnings: Vec<String>,
+}
+
+impl BatchPlan {
+    /// 计划加密的文件数量。
+    pub fn encrypt_count(&self) -> usize {
+        self.items_to_encrypt().count()
+    }
+
+    /// 计划跳过的文件数量。
+    pub fn skip_count(&self) -> usize {
+        self.items.len() - self.encrypt_count()
+    }
+
+    /// 计划加密的文件的总大小（字节）。
+    pub fn total_bytes(&self) -> u64 {
+        self.items_to_encrypt().map(|item| item.size).sum()
+    }
+
+    /// 计划加密的文件加密后的预计总大小（字节）。
+    pub fn estimated_output_bytes(&self) -> u64 {
+        self.items_to_encrypt()
+            .map(|item| item.estimated_output_size)
+            .sum()
+    }
+
+    /// 计划或其中任何一个文件是否带有预检警告。
+    pub fn has_warnings(&self) -> bool {
+        !self.warnings.is_empty() || self.items.iter().any(|item| !item.warnings.is_empty())
+    }
+
+    fn items_to_encrypt(&self) -> impl Iterator<Item = &PlannedItem> {
+        self.items
+            .iter()
+            .filter(|item| item.action == PlannedAction::Encrypt)
     }
 }
 
-/// # 批量操作结果
+/// 为一组路径生成批量加密计划，其中既可以有文件也可以有目录。
+///
+/// 与 [`batch_encrypt_paths`] 的处理范围相同：文件按列表排在前面，随后是每个目录中按 `config`
+/// 的递归和过滤设置收集到的文件。只扫描和检查文件，不需要密码，也不修改任何文件。
+pub fn plan_batch_encrypt(paths: &[PathBuf], config: &BatchConfig) -> Result<BatchPlan> {
+    let (directories, files): (Vec<&PathBuf>, Vec<&PathBuf>) =
+        paths.iter().partition(|path| path.is_dir());
+    let files: Vec<PathBuf> = files.into_iter().cloned().collect();
+    let mut plan = plan_batch_encrypt_files(&files, config);
+    for directory in directories {
+        plan.items.extend(
+            plan_batch_encrypt_files(&collect_files(directory, config, false)?, config).items,
+        );
+        plan.warnings.extend(warn_about_stale_partials(
+            directory,
+            config.recursive,
+            config.strict,
+        )?);
+    }
+    if config.dedupe_hardlinks {
+        mark_hardlink_duplicates(&mut plan.items);
+    }
+    Ok(plan)
+}
+
+/// 为一个具体的文件列表生成批量加密计划，文件按列表顺序排列。
+pub fn plan_batch_encrypt_files(files: &[PathBuf], config: &BatchConfig) -> BatchPlan {
+    let mut items: Vec<PlannedItem> = files.iter().map(|path| plan_item(path, config)).collect();
+    if config.dedupe_hardlinks {
+        mark_hardlink_duplicates(&mut items);
+    }
+    BatchPlan {
+        items,
+        warnings: Vec::new(),
+    }
+}
+
+/// 把与前面计划加密的文件互为硬链接的文件标记为 [`PlannedAction::SkipHardLinkDuplicate`]。
+fn mark_hardlink_duplicates(items: &mut [PlannedItem]) {
+    let mut planned: HashMap<(u64, u64), PathBuf> = HashMap::new();
+    for item in items
+        .iter_mut()
+        .filter(|item| item.action == PlannedAction::Encrypt)
+    {
+        let Some(link) = hardlink_identity(&item.source) else {
+            continue;
+        };
+        match planned.get(&link) {
+            Some(original) => {
+                item.action = PlannedAction::SkipHardLinkDuplicate;
+                item.warnings.push(format!(
+                    "与 {} 是同一个文件的硬链接，内容只加密一次",
+                    original.display()
+                ));
+            }
+            None => {
+                planned.insert(link, item.source.clone());
+            }
+        }
+    }
+}
+
+/// 检查一个源文件并生成它的计划项。
+fn plan_item(source: &Path, config: &BatchConfig) -> PlannedItem {
+    let size = fs::metadata(source).map_or(0, |metadata| metadata.len());
+    let mut warnings = Vec::new();
+    let action = match encryption_skip(source, config) {
+        Some((action, reason)) => {
+            warnings.push(reason.to_string());
+            action
+        }
+        None => PlannedAction::Encrypt,
+    };
+    let up_to_date = action == PlannedAction::Encrypt
+        && config.skip_if_encrypted_matches
+        && encrypted_target_matches(source);
+    let action = if up_to_date {
+        warnings.push("目标文件已是该文件的加密结果".to_string());
+        PlannedAction::SkipAlreadyUpToDate
+    } else {
+        action
+    };
+    let target = match prepare_encryption(source, config.force_overwrite || up_to_date) {
+        Ok((_, target)) => {
+            if target.exists() && !up_to_date {
+                warnings.push(format!("目标文件 {} 已存在，将被覆盖", target.display()));
+            }
+            target
+        }
+        Err(e) => {
+            if action == PlannedAction::Encrypt {
+                warnings.push(format!("{e:#}"));
+            }
+            encrypted_target_path(source)
+        }
+    };
+    let filename_len = source
+        .file_name()
+        .map_or(0, |name| name.to_string_lossy().len());
+    let mut overhead = fixed_overhead_bytes() + 4 + SECURITY_LEVEL_LEN + filename_len;
+    if config.expires_at.is_some() {
+        overhead += 4 + EXPIRY_TIME_LEN;
+    }
+    PlannedItem {
+        source: source.to_path_buf(),
+        target,
+        size,
+        estimated_output_size: size + overhead as u64,
+        action,
+        warnings,
+    }
+}
+
+/// 源文件是否应当跳过而不加密，返回跳过的操作和原因。
+///
+/// 计划阶段和执行阶段使用同样的检查，保证执行计划与直接批量加密的结果相同。
+/// 严格模式下执行阶段把原因记录为失败。
+fn encryption_skip(path: &Path, config: &BatchConfig) -> Option<(PlannedAction, FeroxError)> {
+    if let Err(e) = ensure_not_special_file(path) {
+        return Some((PlannedAction::SkipSpecialFile, e));
+    }
+    if config.skip_open_files && is_open_by_another_process(path) {
+        return Some((
+            PlannedAction::SkipOpenFile,
+            FeroxError::FileLocked("文件正被其他进程打开".to_string()),
+        ));
+    }
+    // 在任何密钥派生之前跳过超过大小上限的文件；无法读取元数据时由之后的加密流程报告
+    let limit = config.max_file_size?;
+    let metadata = fs::metadata(path).ok()?;
+    let e = ensure_within_size_limit(&metadata, Some(limit)).err()?;
+    Some((PlannedAction::SkipTooLarge, e))
+}
+
+/// `source` 的加密目标是否已存在并且仍然对应它：文件头中的原始文件名、明文大小和记录的
+/// 源文件修改时间都与 `source` 一致。只读取目标的文件头，不需要密码。
+fn encrypted_target_matches(source: &Path) -> bool {
+    let (Ok(metadata), Ok(info)) = (
+        fs::metadata(source),
+        inspect_file(&encrypted_target_path(source)),
+    ) else {
+        return false;
+    };
+    source
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy() == info.original_filename)
+        && info.ciphertext_len == metadata.len()
+        && info.source_modified.is_some()
+        && info.source_modified == metadata.modified().ok()
+}
+
+/// 执行批量加密计划，依次处理计划中的每个文件，返回与直接批量加密相同的结果。
+///
+/// 计划可能是在一段时间之前生成的：每个文件在处理之前会重新检查，计划生成之后才出现的问题
+/// （例如文件被其他进程打开）与直接批量加密一样处理，计划中预计的操作只用于展示。
+/// 安全级别、覆盖方式等加密设置以及进度回调 ([`BatchConfig::progress_sink`]) 取自 `config`，
+/// 应当与生成计划时使用的配置相同。
+pub fn execute_batch_plan(
+    plan: &BatchPlan,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    config.validate_password(password)?;
+    log::info!("开始批量加密 {} 个文件...", plan.items.len());
+    emit(
+        config.progress_sink.as_deref(),
+        ProgressEvent::BatchStarted {
+            total_files: plan.items.len(),
+            total_bytes: plan.items.iter().map(|item| item.size).sum(),
+        },
+    );
+    encrypt_file_sequence(
+        plan.items.iter().map(|item| item.source.clone()),
+        Some(plan.items.len()),
+        password,
+        keyfile,
+        config,
+    )
+}
+
+/// 批量加密指定目录中的文件。
+pub fn batch_encrypt_directory(
+    directory: &Path,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    let plan = plan_batch_encrypt(&[directory.to_path_buf()], config)?;
+    execute_batch_plan(&plan, password, keyfile, config)
+}
+
+/// 批量加密一个具体的文件列表。
+pub fn batch_encrypt_files(
+    files: &[PathBuf],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    execute_batch_plan(
+        &plan_batch_encrypt_files(files, config),
+        password,
+        keyfile,
+        config,
+    )
+}
+
+/// 批量加密迭代器逐个产生的文件，不需要预先把所有路径收集到内存中。
+///
+/// 适用于按需发现文件的超大目录：每处理完一个文件才取下一个路径。需要限制文件数量时，
+/// 可以先对迭代器调用 [`Iterator::take`]。
+///
+/// 文件总数和总字节数事先未知，进度事件中的 `total_files` 取迭代器
+/// [`size_hint`](Iterator::size_hint) 的精确值（未知时为 0），`total_bytes` 为 0。
+pub fn batch_encrypt_iter(
+    files: impl Iterator<Item = PathBuf>,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    config.validate_password(password)?;
+    let total_files = match files.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(lower),
+        _ => None,
+    };
+    log::info!("开始批量加密文件...");
+    emit(
+        config.progress_sink.as_deref(),
+        ProgressEvent::BatchStarted {
+            total_files: total_files.unwrap_or(0),
+            total_bytes: 0,
+        },
+    );
+    encrypt_file_sequence(files, total_files, password, keyfile, config)
+}
+
+/// 逐个加密 `files` 中的文件并汇总结果，`total_files` 为已知的文件总数（只用于日志和进度事件）。
+///
+/// 调用者负责在此之前发送 `BatchStarted` 事件。
+fn encrypt_file_sequence(
+    files: impl Iterator<Item = PathBuf>,
+    total_files: Option<usize>,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    let mut result = BatchResult::new(config.track_successful_paths);
+    let opts = config.encryption_options(password, keyfile);
+    let progress = config.progress_sink.as_deref();
+    let mut encrypted_dirs = BTreeSet::new();
+    // 设置了 retry_locked 时被占用的文件先搁置，最后再重试
+    let mut locked = Vec::new();
+    // 设置了 dedupe_hardlinks 时，已加密成功的硬链接内容及保存它的文件
+    let mut encrypted_links: HashMap<(u64, u64), PathBuf> = HashMap::new();
+
+    let encrypt_one = |index: usize, file_path: &Path| {
+        emit(
+            progress,
+            ProgressEvent::FileStarted {
+                index,
+                total_files: total_files.unwrap_or(0),
+                path: file_path,
+            },
+        );
+        // 对每个文件调用单独的加密处理函数
+        let outcome = catch_file_panic(file_path, &config.temp_file_path, || {
+            process_single_encryption(
+                file_path,
+                &opts,
+                Arc::clone(&config.temp_file_path),
+                progress,
+            )
+        });
+        emit(
+            progress,
+            ProgressEvent::FileFinished {
+                path: file_path,
+                success: outcome.is_ok(),
+            },
+        );
+        outcome
+    };
+    let mut record =
+        |result: &mut BatchResult, file_path: PathBuf, outcome: Result<(u64, ByteCounts)>| {
+            match outcome {
+                Ok((file_size, counts)) => {
+                    result.add_transfer(&file_path, file_size, counts);
+                    log::info!("✅ 成功加密: {}", file_path.display());
+                    if config.write_readme {
+                        encrypted_dirs.insert(output_directory(&file_path));
+                    }
+                }
+                Err(e) => {
+                    log::error!("❌ {e:#}");
+                    result.add_error(file_path, &e);
+                }
+            }
+        };
+
+    for (index, file_path) in files.enumerate() {
+        if let Some((action, reason)) = encryption_skip(&file_path, config) {
+            if config.strict {
+                log::error!("❌ {}: {reason}", file_path.display());
+                result.add_failure(file_path, reason);
+                continue;
+            }
+            log::warn!("⏭️  已跳过 {}: {reason}", file_path.display());
+            match action {
+                PlannedAction::SkipSpecialFile => result.skipped_special.push(file_path),
+                PlannedAction::SkipOpenFile => result.skipped_locked.push(file_path),
+                PlannedAction::SkipTooLarge
+                | PlannedAction::SkipHardLinkDuplicate
+                | PlannedAction::SkipAlreadyUpToDate
+                | PlannedAction::Encrypt => result.skipped_too_large.push(file_path),
+            }
+            continue;
+        }
+        if config.skip_if_encrypted_matches && encrypted_target_matches(&file_path) {
+            log::info!("⏩ 加密文件已是最新，已跳过: {}", file_path.display());
+            result.skipped_up_to_date.push(file_path);
+            continue;
+        }
+        let link = config
+            .dedupe_hardlinks
+            .then(|| hardlink_identity(&file_path))
+            .flatten();
+        if let Some(original) = link.and_then(|link| encrypted_links.get(&link)) {
+            log::info!(
+                "🔗 {} 与 {} 是同一个文件的硬链接，内容只加密一次",
+                file_path.display(),
+                original.display()
+            );
+            result.hardlink_bytes_saved += fs::metadata(&file_path).map_or(0, |m| m.len());
+            result.skipped_hardlinks.push((file_path, original.clone()));
+            continue;
+        }
+        match total_files {
+            Some(total) => log::info!(
+                "正在处理文件 {}/{}: {}",
+                index + 1,
+                total,
+                file_path.display()
+            ),
+            None => log::info!("正在处理文件 {}: {}", index + 1, file_path.display()),
+        }
+        match encrypt_one(index, &file_path) {
+            Err(e) if config.retry_locked && FeroxError::classify(&e).is_file_locked() => {
+                log::warn!("🔒 {} 正被其他程序占用，将在最后重试", file_path.display());
+                locked.push((index, file_path));
+            }
+            outcome => {
+                if let (Some(link), Ok(_)) = (link, &outcome) {
+                    encrypted_links.insert(link, file_path.clone());
+                }
+                record(&mut result, file_path, outcome)
+            }
+        }
+    }
+
+    if !locked.is_empty() {
+        log::info!("🔁 重试 {} 个正被其他程序占用的文件...", locked.len());
+        for (index, file_path) in locked {
+            let outcome = encrypt_one(index, &file_path);
+            record(&mut result, file_path, outcome);
+        }
+    }
+
+    log::info!(
+        "批量加密完成: {} 个成功, {} 个失败。",
+        result.success_count,
+        result.failure_count
+    );
+    record_readmes(
+        &mut result,
+        encrypted_dirs.iter().map(PathBuf::as_path),
+        keyfile,
+        config,
+    );
+    emit_batch_finished(progress, &result);
+
+    Ok(result)
+}
+
+/// 在处理过的目录中写入说明文件并记录写入的路径；严格模式下无法写入的目录记为失败。
+fn record_readmes<'a>(
+    result: &mut BatchResult,
+    directories: impl IntoIterator<Item = &'a Path>,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) {
+    let (written, failed) = write_directory_readmes(directories, config.level, keyfile.is_some());
+    result.readme_paths = written;
+    if config.strict {
+        for (directory, e) in failed {
+            result.add_error(directory, &e.context("严格模式: 无法写入说明文件"));
+        }
+    }
+}
+
+/// 加密 `source` 得到的文件所在的目录。
+fn output_directory(source: &Path) -> PathBuf {
+    match source.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// 以全有或全无的方式批量加密一组文件。
+///
+/// 与尽力而为的 [`batch_encrypt_files`] 不同，本函数：
+/// 1. 先验证所有源文件，任何一个无效都不会开始加密；
+/// 2. 把每个文件加密到各自的 `.part` 临时文件；
+/// 3. 只有全部加密成功后，才依次把所有临时文件重命名为最终的目标文件；
+/// 4. 任何一步失败时删除所有临时文件，不留下部分输出。
+///
+/// 返回结果的 [`BatchResult::was_atomic`] 表示全有或全无的语义是否成立。
+///
+/// # 注意
+///
+/// 多个文件的重命名无法作为一个整体原子地完成：每次重命名本身是原子的，
+/// 但在第一次和最后一次重命名之间存在一个很短的时间窗口，其他进程可能观察到
+/// 只有部分目标文件存在。如果在这个阶段某次重命名失败（例如磁盘被移除），
+/// 已经提交的文件会被保留，`was_atomic` 为 `false`。
+/// 在提交阶段之前被强制终止的进程会留下 `.part` 文件，可以用 `cleanup` 子命令清理。
+pub fn batch_encrypt_files_atomic(
+    files: &[PathBuf],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    config.validate_password(password)?;
+    let mut result = BatchResult::new(config.track_successful_paths);
+    let opts = config.encryption_options(password, keyfile);
+
+    log::info!("开始原子批量加密 {} 个文件...", files.len());
+    let progress = config.progress_sink.as_deref();
+
+    // --- 1. 预先验证所有源文件 ---
+    for file_path in files {
+        if let Err(e) =
+            prepare_encryption(file_path, config.force_overwrite).at_stage(Stage::HeaderParse)
+        {
+            log::error!("❌ 验证失败 {}: {e:#}", file_path.display());
+            result.add_error(file_path.clone(), &e);
+        }
+    }
+    if !result.was_successful() {
+        log::error!(
+            "有 {} 个文件未通过验证，没有加密任何文件。",
+            result.failure_count
+        );
+        result.was_atomic = true;
+        return Ok(result);
+    }
+
+    // --- 2. 把所有文件加密到临时文件 ---
+    emit_batch_started(progress, files);
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(files.len());
+    let mut file_sizes = Vec::with_capacity(files.len());
+    for (index, file_path) in files.iter().enumerate() {
+        emit(
+            progress,
+            ProgressEvent::FileStarted {
+                index,
+                total_files: files.len(),
+                path: file_path,
+            },
+        );
+        let outcome = fs::metadata(file_path)
+            .at_stage(Stage::HeaderParse)
+            .with_context(|| format!("无法加密 {}", file_path.display()))
+            .and_then(|metadata| {
+                catch_file_panic(file_path, &config.temp_file_path, || {
+                    encrypt_to_partial(file_path, &opts, &config.temp_file_path, progress)
+                })
+                .map(|(part_path, target_path, counts)| {
+                    ((metadata.len(), counts), (part_path, target_path))
+                })
+            });
+        // 临时文件由本函数统一管理，不再需要登记表中的记录
+        let registered = config
+            .temp_file_path
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        emit(
+            progress,
+            ProgressEvent::FileFinished {
+                path: file_path,
+                success: outcome.is_ok(),
+            },
+        );
+        match outcome {
+            Ok((file_size, paths)) => {
+                file_sizes.push(file_size);
+                staged.push(paths);
+            }
+            Err(e) => {
+                log::error!("❌ {e:#}");
+                result.add_error(file_path.clone(), &e);
+                // 回滚：删除失败文件的不完整输出以及所有已完成的临时文件
+                if let Some(part_path) = registered {
+                    let _ = fs::remove_file(part_path);
+                }
+                remove_staged_outputs(&staged);
+                result.was_atomic = true;
+                emit_batch_finished(progress, &result);
+                log::error!("原子批量加密失败，已回滚所有输出。");
+                return Ok(result);
+            }
+        }
+    }
+
+    // --- 3. 全部成功后再依次提交 ---
+    for (index, (part_path, target_path)) in staged.iter().enumerate() {
+        if let Err(e) = commit_partial_file(part_path, target_path).at_stage(Stage::Finalize) {
+            log::error!("❌ 提交失败 {}: {e:#}", target_path.display());
+            result.add_error(target_path.clone(), &e);
+            remove_staged_outputs(&staged[index..]);
+            emit_batch_finished(progress, &result);
+            log::error!(
+                "原子批量加密在提交阶段失败，已有 {} 个文件被提交，无法保证全有或全无。",
+                index
+            );
+            return Ok(result);
+        }
+    }
+
+    for (file_path, (file_size, counts)) in files.iter().zip(file_sizes) {
+        result.add_transfer(file_path, file_size, counts);
+    }
+    if config.write_readme {
+        let encrypted_dirs: BTreeSet<PathBuf> =
+            files.iter().map(|file| output_directory(file)).collect();
+        record_readmes(
+            &mut result,
+            encrypted_dirs.iter().map(PathBuf::as_path),
+            keyfile,
+            config,
+        );
+    }
+    result.was_atomic = true;
+    emit_batch_finished(progress, &result);
+    log::info!("原子批量加密完成: {} 个文件全部成功。", files.len());
+    Ok(result)
+}
+
+/// 删除尚未提交的临时输出文件。
+fn remove_staged_outputs(staged: &[(PathBuf, PathBuf)]) {
+    for (part_path, _) in staged {
+        if let Err(e) = fs::remove_file(part_path) {
+            log::error!("清理临时文件 {} 失败: {}", part_path.display(), e);
+        }
+    }
+}
+
+/// 加密一组路径，其中既可以有文件也可以有目录。
+///
+/// 文件按列表加密；目录等同于对该目录调用 [`batch_encrypt_directory`]，
+/// 遵循 `config` 中的递归和过滤设置。所有结果合并为一个 [`BatchResult`]。
+pub fn batch_encrypt_paths(
+    paths: &[PathBuf],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    let plan = plan_batch_encrypt(paths, config)?;
+    execute_batch_plan(&plan, password, keyfile, config)
+}
+
+/// 批量解密指定目录中的文件。
+pub fn batch_decrypt_directory(
+    directory: &Path,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    // 收集所有符合条件的已加密文件
+    if config.preserve_hardlinks && config.flatten {
+        anyhow::bail!("重建硬链接需要保留目录结构，不能与 flatten 同时使用");
+    }
+    let files = collect_files(directory, config, true)?;
+    warn_about_stale_partials(directory, config.recursive, config.strict)?;
+    // 对收集到的文件列表执行解密，输出目录中按相对于 `directory` 的结构存放
+    let mut result = decrypt_files(
+        &files,
+        &config.decryption_options(password, keyfile),
+        OutputLayout::from_config(config, Some(directory))?,
+        config.progress_sink.as_deref(),
+        Arc::clone(&config.temp_file_path),
+        DecryptRun::from_config(config, Some(directory)),
+    )?;
+    if config.preserve_hardlinks && !result.aborted_early {
+        restore_hardlinks(directory, config, &mut result)?;
+    }
+    Ok(result)
+}
+
+/// 按 `directory` 中恢复校验清单记录的链接关系，在解密结果中重建硬链接。
+///
+/// 被链接的文件没有解密出来时只发出警告；链接路径已经是同一个文件时视为已重建，
+/// 被其他文件占用时按 [`OverwriteMode::Skip`] 跳过，其他方式下记为失败。
+fn restore_hardlinks(
+    directory: &Path,
+    config: &BatchConfig,
+    result: &mut BatchResult,
+) -> Result<()> {
+    let manifest_path = directory.join(MANIFEST_FILE_NAME);
+    if !manifest_path.exists() {
+        return warn_or_fail(
+            config.strict,
+            format!("{} 中没有恢复校验清单，无法重建硬链接", directory.display()),
+        )
+        .map_err(anyhow::Error::from);
+    }
+    let manifest = RestoreManifest::load(&manifest_path)?;
+    let output_root = config.output_dir.as_deref().unwrap_or(directory);
+    for (duplicate, original) in &manifest.hardlinks {
+        let original = output_root.join(original);
+        let link = output_root.join(duplicate);
+        if !original.exists() {
+            log::warn!(
+                "⚠️  {} 没有解密出来，无法重建硬链接 {}",
+                original.display(),
+                link.display()
+            );
+            continue;
+        }
+        if link.exists() {
+            let identity = hardlink_identity(&link);
+            if identity.is_some() && identity == hardlink_identity(&original) {
+                result.restored_hardlinks.push((link, original));
+                continue;
+            }
+            if config.overwrite_mode == OverwriteMode::Skip {
+                log::info!("⏭️  目标文件已存在，未重建硬链接: {}", link.display());
+                result.skipped_count += 1;
+                result.skipped_existing.push(link);
+                continue;
+            }
+        }
+        let outcome = link
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::hard_link(&original, &link))
+            .with_context(|| {
+                format!(
+                    "无法创建硬链接 {} -> {}",
+                    link.display(),
+                    original.display()
+                )
+            });
+        match outcome {
+            Ok(()) => {
+                log::info!(
+                    "🔗 已重建硬链接: {} -> {}",
+                    link.display(),
+                    original.display()
+                );
+                result.restored_hardlinks.push((link, original));
+            }
+            Err(e) => {
+                log::error!("❌ {e:#}");
+                result.add_error(link, &e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 批量解密一个具体的已加密文件列表。
+pub fn batch_decrypt_files(
+    files: &[PathBuf],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+) -> Result<BatchResult> {
+    batch_decrypt_files_with_progress(files, password, keyfile, None)
+}
+
+/// 批量解密一个文件列表，并向可选的进度接收器报告进度。
+pub fn batch_decrypt_files_with_progress(
+    files: &[PathBuf],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<BatchResult> {
+    decrypt_files(
+        files,
+        &DecryptionOptions {
+            keyfile,
+            ..DecryptionOptions::new(password)
+        },
+        OutputLayout::default(),
+        progress,
+        Arc::new(Mutex::new(None)),
+        DecryptRun::default(),
+    )
+}
+
+/// 批量解密一个文件列表，使用配置中的进度接收器和临时文件登记表。
 ///
-/// 存储批量处理任务完成后的统计信息。
-#[derive(Debug)]
-pub struct BatchResult {
-    /// 成功处理的文件数量。
-    pub success_count: usize,
-    /// 处理失败的文件数量。
-    pub failure_count: usize,
-    /// 失败文件的列表，包含文件路径和具体的错误信息。
-    pub failures: Vec<(PathBuf, String)>,
-    /// 成功处理的总字节数。
-    pub total_bytes: u64,
+/// 解密时只会用到 `config` 的 `progress_sink`、`temp_file_path`、`single_thread`、
+/// `threads`、`buffer_size`、`lock_memory`、`overwrite_mode`、`preserve_xattrs`、`permissions`、
+/// `normalize_names`、`temp_dir`、`output_dir`、`flatten`、`track_successful_paths`、`keep_going`、
+/// `strict` 和 `credentials` 字段。
+/// 文件列表没有共同的根目录，设置了 `output_dir` 时所有结果都直接写入该目录。
+pub fn batch_decrypt_files_with_config(
+    files: &[PathBuf],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    decrypt_files(
+        files,
+        &config.decryption_options(password, keyfile),
+        OutputLayout::from_config(config, None)?,
+        config.progress_sink.as_deref(),
+        Arc::clone(&config.temp_file_path),
+        DecryptRun::from_config(config, None),
+    )
 }
 
-impl BatchResult {
-    /// 创建一个新的、空的 `BatchResult`。
-    fn new() -> Self {
-        Self {
-            success_count: 0,
-            failure_count: 0,
-            failures: Vec::new(),
-            total_bytes: 0,
+/// 批量解密时各文件的输出目录。
+#[derive(Debug, Clone, Copy, Default)]
+struct OutputLayout<'a> {
+    /// 输出目录；为 `None` 时写在各加密文件旁边。
+    output_dir: Option<&'a Path>,
+    /// 在输出目录中重建相对于该目录的子目录结构；为 `None` 时直接写入输出目录。
+    root: Option<&'a Path>,
+}
+
+impl<'a> OutputLayout<'a> {
+    /// 根据配置确定输出方式；`root` 是被解密的目录（解密文件列表时为 `None`）。
+    fn from_config(config: &'a BatchConfig, root: Option<&'a Path>) -> Result<Self> {
+        let output_dir = config.output_dir.as_deref();
+        if config.flatten && output_dir.is_none() {
+            anyhow::bail!("不保留目录结构 (flatten) 时必须指定输出目录");
         }
+        Ok(Self {
+            output_dir,
+            root: if config.flatten { None } else { root },
+        })
     }
 
-    /// 记录一次成功操作。
-    fn add_success(&mut self, file_size: u64) {
-        self.success_count += 1;
-        self.total_bytes += file_size;
+    /// `file` 的解密结果所在的目录（按需创建）；为 `None` 时写在加密文件旁边。
+    fn target_dir(&self, file: &Path) -> Result<Option<PathBuf>> {
+        let Some(output_dir) = self.output_dir else {
+            return Ok(None);
+        };
+        let relative = self
+            .root
+            .and_then(|root| file.parent()?.strip_prefix(root).ok());
+        let dir = match relative {
+            Some(relative) => output_dir.join(relative),
+            None => output_dir.to_path_buf(),
+        };
+        fs::create_dir_all(&dir).with_context(|| format!("无法创建输出目录: {}", dir.display()))?;
+        Ok(Some(dir))
     }
+}
 
-    /// 记录一次失败操作。
-    fn add_failure(&mut self, path: PathBuf, error: String) {
-        self.failure_count += 1;
-        self.failures.push((path, error));
-    }
+/// 批量解密中对所有文件相同的处理方式。
+#[derive(Debug, Clone, Copy, Default)]
+struct DecryptRun<'a> {
+    /// 见 [`BatchConfig::track_successful_paths`]。
+    track_successful_paths: bool,
+    /// 见 [`BatchConfig::keep_going`]。
+    keep_going: bool,
+    /// 见 [`BatchConfig::credentials`]。
+    credentials: Option<&'a Credentials>,
+    /// 凭据表中的文件模式相对于该目录匹配（解密文件列表时为 `None`）。
+    root: Option<&'a Path>,
 }
 
-/// 批量加密指定目录中的文件。
-pub fn batch_encrypt_directory(
-    directory: &Path,
-    password: &str,
-    keyfile: Option<&KeyFile>,
-    config: &BatchConfig,
-) -> Result<BatchResult> {
-    // 首先，收集所有符合条件的文件
-    let files = collect_files(directory, config, false)?;
-    // 然后，对收集到的文件列表执行加密
-    batch_encrypt_files(&files, password, keyfile, config)
+impl<'a> DecryptRun<'a> {
+    /// 从配置中取出处理方式；`root` 是被解密的目录（解密文件列表时为 `None`）。
+    fn from_config(config: &'a BatchConfig, root: Option<&'a Path>) -> Self {
+        Self {
+            track_successful_paths: config.track_successful_paths,
+            keep_going: config.keep_going,
+            credentials: config.credentials.as_deref(),
+            root,
+        }
+    }
 }
 
-/// 批量加密一个具体的文件列表。
-pub fn batch_encrypt_files(
+/// 批量解密的核心循环。
+///
+/// `keep_going` 为 `false` 并且没有凭据表时，在任何文件解密成功之前出现的第一个认证失败会中止其余文件，
+/// 见 [`BatchConfig::keep_going`]。
+fn decrypt_files(
     files: &[PathBuf],
-    password: &str,
-    keyfile: Option<&KeyFile>,
-    config: &BatchConfig,
+    opts: &DecryptionOptions,
+    layout: OutputLayout<'_>,
+    progress: Option<&dyn ProgressSink>,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    run: DecryptRun<'_>,
 ) -> Result<BatchResult> {
-    let mut result = BatchResult::new();
-    // 创建用于中断清理的共享状态
-    let temp_file_path = Arc::new(Mutex::new(None::<PathBuf>));
+    let mut result = BatchResult::new(run.track_successful_paths);
+    // 有文件解密成功之后凭据就得到了确认，之后的认证失败只说明那个文件有问题；
+    // 使用凭据表时各文件的凭据本来就不同，一个文件的失败说明不了其他文件
+    let mut credentials_confirmed = run.keep_going || run.credentials.is_some();
 
-    log::info!("开始批量加密 {} 个文件...", files.len());
+    log::info!("开始批量解密 {} 个文件...", files.len());
+    emit_batch_started(progress, files);
 
     for (index, file_path) in files.iter().enumerate() {
+        if result.skip_special_file(file_path, opts.strict) {
+            continue;
+        }
         log::info!(
             "正在处理文件 {}/{}: {}",
             index + 1,
             files.len(),
             file_path.display()
         );
+        emit(
+            progress,
+            ProgressEvent::FileStarted {
+                index,
+                total_files: files.len(),
+                path: file_path,
+            },
+        );
 
-        // 对每个文件调用单独的加密处理函数
-        match process_single_encryption(
-            file_path,
-            password,
-            keyfile,
-            config,
-            Arc::clone(&temp_file_path),
-        ) {
-            Ok(file_size) => {
-                result.add_success(file_size);
-                log::info!("✅ 成功加密: {}", file_path.display());
+        // 凭据还未确认时先用密钥文件承诺快速检查，不匹配就不必做完整的密钥派生
+        let outcome = if !credentials_confirmed && keyfile_commitment_rejected(file_path, opts) {
+            Err(FeroxError::AuthenticationFailed)
+                .context("密码或密钥文件与文件头中的密钥文件承诺不匹配")
+                .at_stage(Stage::KeyDerivation)
+                .with_context(|| format!("无法解密 {}", file_path.display()))
+        } else {
+            // 对每个文件调用单独的解密处理函数
+            catch_file_panic(file_path, &temp_file_path, || {
+                let target_dir = layout.target_dir(file_path)?;
+                let opts = DecryptionOptions {
+                    output_dir: target_dir.as_deref().or(opts.output_dir),
+                    ..*opts
+                };
+                match run.credentials {
+                    Some(credentials) => decrypt_with_credentials(
+                        file_path,
+                        &opts,
+                        credentials.candidates(file_path, run.root),
+                        Arc::clone(&temp_file_path),
+                        progress,
+                    ),
+                    None => process_single_decryption(
+                        file_path,
+                        &opts,
+                        Arc::clone(&temp_file_path),
+                        progress,
+                    ),
+                }
+            })
+        };
+        emit(
+            progress,
+            ProgressEvent::FileFinished {
+                path: file_path,
+                success: outcome.is_ok(),
+            },
+        );
+        match outcome {
+            Ok((
+                file_size,
+                DecryptionOutcome::Decrypted {
+                    path: target_path,
+                    mode,
+                    level,
+                    counts,
+                    ignored_extensions,
+                },
+            )) => {
+                credentials_confirmed = true;
+                result.add_transfer(file_path, file_size, counts);
+                if !ignored_extensions.is_empty() {
+                    result
+                        .ignored_extensions
+                        .push((file_path.clone(), ignored_extensions));
+                }
+                log::info!(
+                    "✅ 成功解密: {} -> {} ({})",
+                    file_path.display(),
+                    target_path.display(),
+                    level.map_or("Custom", |level| level.name())
+                );
+                if result.track_successful_paths {
+                    if let Some(mode) = mode {
+                        result.output_modes.push((target_path.clone(), mode));
+                    }
+                    result.levels.push((file_path.clone(), level));
+                    result.output_paths.push((file_path.clone(), target_path));
+                }
+            }
+            Ok((_, DecryptionOutcome::Skipped)) => {
+                result.skipped_count += 1;
+                result.skipped_existing.push(file_path.clone());
+                log::info!("⏭️  目标文件已存在，已跳过: {}", file_path.display());
             }
             Err(e) => {
-                let error_msg = format!("{e:#}");
-                result.add_failure(file_path.clone(), error_msg.clone());
-                log::error!("❌ 加密失败 {}: {}", file_path.display(), error_msg);
+                log::error!("❌ {e:#}");
+                result.add_error(file_path.clone(), &e);
+                let remaining = &files[index + 1..];
+                let rejected = FeroxError::classify(&e).is_authentication_failure();
+                if rejected && !credentials_confirmed && !remaining.is_empty() {
+                    log::error!(
+                        "🛑 凭据似乎与该归档不匹配，已中止，其余 {} 个文件未处理 \
+                         (归档中的文件使用不同的凭据时请使用 --keep-going)",
+                        remaining.len()
+                    );
+                    result.aborted_early = true;
+                    result.unprocessed.extend_from_slice(remaining);
+                    break;
+                }
             }
         }
     }
 
     log::info!(
-        "批量加密完成: {} 个成功, {} 个失败。",
+        "批量解密完成: {} 个成功, {} 个失败, {} 个跳过。",
         result.success_count,
-        result.failure_count
+        result.failure_count,
+        result.skipped_count
     );
+    emit_batch_finished(progress, &result);
 
     Ok(result)
 }
 
-/// 批量解密指定目录中的文件。
-pub fn batch_decrypt_directory(
+/// 解密一组路径，其中既可以有已加密文件也可以有目录。
+///
+/// 文件按列表解密；目录等同于对该目录调用 [`batch_decrypt_directory`]，
+/// 遵循 `config` 中的递归设置。所有结果合并为一个 [`BatchResult`]。
+pub fn batch_decrypt_paths(
+    paths: &[PathBuf],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<BatchResult> {
+    let (directories, files): (Vec<&PathBuf>, Vec<&PathBuf>) =
+        paths.iter().partition(|path| path.is_dir());
+
+    let files: Vec<PathBuf> = files.into_iter().cloned().collect();
+    let mut result = batch_decrypt_files_with_config(&files, password, keyfile, config)?;
+    for (index, directory) in directories.iter().enumerate() {
+        if result.aborted_early {
+            result
+                .unprocessed
+                .extend(directories[index..].iter().map(|dir| dir.to_path_buf()));
+            break;
+        }
+        result.merge(batch_decrypt_directory(
+            directory, password, keyfile, config,
+        )?);
+    }
+    Ok(result)
+}
+
+/// 批量验证指定目录中的已加密文件，不生成任何明文文件。
+pub fn batch_verify_directory(
     directory: &Path,
     password: &str,
     keyfile: Option<&KeyFile>,
     config: &BatchConfig,
 ) -> Result<BatchResult> {
-    // 收集所有符合条件的已加密文件
     let files = collect_files(directory, config, true)?;
-    // 对收集到的文件列表执行解密
-    batch_decrypt_files(&files, password, keyfile)
+    batch_verify_files(&files, password, keyfile, config)
 }
 
-/// 批量解密一个具体的已加密文件列表。
-pub fn batch_decrypt_files(
+/// 批量验证一个已加密文件列表的完整性，不生成任何明文文件。
+///
+/// 只会用到 `config` 的 `progress_sink` 字段。
+pub fn batch_verify_files(
     files: &[PathBuf],
     password: &str,
     keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
 ) -> Result<BatchResult> {
-    let mut result = BatchResult::new();
-    let temp_file_path = Arc::new(Mutex::new(None::<PathBuf>));
+    let mut result = BatchResult::new(config.track_successful_paths);
+    let opts = config.decryption_options(password, keyfile);
+    let progress = config.progress_sink.as_deref();
 
-    log::info!("开始批量解密 {} 个文件...", files.len());
+    log::info!("开始批量验证 {} 个文件...", files.len());
+    emit_batch_started(progress, files);
 
     for (index, file_path) in files.iter().enumerate() {
-        log::info!(
-            "正在处理文件 {}/{}: {}",
-            index + 1,
-            files.len(),
-            file_path.display()
+        if result.skip_special_file(file_path, config.strict) {
+            continue;
+        }
+        emit(
+            progress,
+            ProgressEvent::FileStarted {
+                index,
+                total_files: files.len(),
+                path: file_path,
+            },
         );
 
-        // 对每个文件调用单独的解密处理函数
-        match process_single_decryption(file_path, password, keyfile, Arc::clone(&temp_file_path)) {
-            Ok(file_size) => {
-                result.add_success(file_size);
-                log::info!("✅ 成功解密: {}", file_path.display());
-            }
+        let outcome = fs::metadata(file_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|metadata| {
+                verify_file_with_progress(file_path, &opts, progress)?;
+                Ok(metadata.len())
+            });
+        emit(
+            progress,
+            ProgressEvent::FileFinished {
+                path: file_path,
+                success: outcome.is_ok(),
+            },
+        );
+        match outcome {
+            Ok(file_size) => result.add_success(file_path, file_size),
             Err(e) => {
                 let error_msg = format!("{e:#}");
-                result.add_failure(file_path.clone(), error_msg.clone());
-                log::error!("❌ 解密失败 {}: {}", file_path.display(), error_msg);
+                result.add_error(file_path.clone(), &e);
+                log::error!("❌ 验证失败 {}: {}", file_path.display(), error_msg);
             }
         }
     }
 
     log::info!(
-        "批量解密完成: {} 个成功, {} 个失败。",
+        "批量验证完成: {} 个通过, {} 个失败。",
         result.success_count,
         result.failure_count
     );
+    emit_batch_finished(progress, &result);
 
     Ok(result)
 }
 
-/// 处理单个文件的加密。
-fn process_single_encryption(
-    file_path: &Path,
+/// 批量比较已加密文件与各自的明文文件，不生成任何明文文件。
+///
+/// 每个加密文件按文件头中记录的原始文件名，与同一目录下的明文文件配对
+/// (例如 `report.docx.feroxcrypt` 与 `report.docx`)。明文文件缺失、认证失败
+/// 或内容不一致（[`FeroxError::ContentMismatch`]）都记为失败。
+///
+/// 只会用到 `config` 的 `progress_sink` 字段。
+pub fn batch_compare_files(
+    files: &[PathBuf],
     password: &str,
     keyfile: Option<&KeyFile>,
     config: &BatchConfig,
+) -> Result<BatchResult> {
+    let mut result = BatchResult::new(config.track_successful_paths);
+    let opts = config.decryption_options(password, keyfile);
+    let progress = config.progress_sink.as_deref();
+
+    log::info!("开始批量比较 {} 个文件...", files.len());
+    emit_batch_started(progress, files);
+
+    for (index, file_path) in files.iter().enumerate() {
+        if result.skip_special_file(file_path, config.strict) {
+            continue;
+        }
+        emit(
+            progress,
+            ProgressEvent::FileStarted {
+                index,
+                total_files: files.len(),
+                path: file_path,
+            },
+        );
+
+        let outcome = paired_plaintext_path(file_path).and_then(|plaintext| {
+            let report = compare_file_with_progress(&plaintext, file_path, &opts, progress)?;
+            match report.first_difference {
+                None => Ok(report.decrypted_len),
+                Some(offset) => Err(anyhow::Error::from(FeroxError::ContentMismatch { offset })
+                    .context(format!("与 {} 不一致", plaintext.display()))),
+            }
+        });
+        emit(
+            progress,
+            ProgressEvent::FileFinished {
+                path: file_path,
+                success: outcome.is_ok(),
+            },
+        );
+        match outcome {
+            Ok(file_size) => result.add_success(file_path, file_size),
+            Err(e) => {
+                let error_msg = format!("{e:#}");
+                result.add_error(file_path.clone(), &e);
+                log::error!("❌ 比较失败 {}: {}", file_path.display(), error_msg);
+            }
+        }
+    }
+
+    log::info!(
+        "批量比较完成: {} 个一致, {} 个失败。",
+        result.success_count,
+        result.failure_count
+    );
+    emit_batch_finished(progress, &result);
+
+    Ok(result)
+}
+
+/// 文件是否正被其他进程打开（无法获得独占访问）。
+///
+/// Unix 上尝试获取非阻塞的 `flock` 独占锁，只能发现同样持有 `flock` 锁的进程；
+/// Windows 上以不共享的方式打开文件，任何其他打开了该文件的进程都会导致共享冲突。
+/// 文件无法打开等其他错误不视为被占用，由之后的加密流程报告。
+fn is_open_by_another_process(path: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        match fs::OpenOptions::new().read(true).share_mode(0).open(path) {
+            Ok(_) => false,
+            Err(e) => crate::error::is_sharing_violation(&e),
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        // 锁在文件关闭时释放
+        fs::File::open(path)
+            .is_ok_and(|file| matches!(file.try_lock(), Err(fs::TryLockError::WouldBlock)))
+    }
+}
+
+/// 处理单个文件，把 `process` 中的 panic 转为 [`FeroxError::Panicked`] 错误。
+///
+/// 一个触发程序缺陷的文件不会中止整个批量处理：它未完成的临时输出被清理，
+/// 记为该文件的失败，之后照常处理其余文件。
+fn catch_file_panic<T>(
+    file_path: &Path,
+    temp_file_path: &Mutex<Option<PathBuf>>,
+    process: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(process)).unwrap_or_else(|payload| {
+        cleanup_partial_output(temp_file_path);
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("未知的 panic");
+        Err(FeroxError::Panicked(format!("处理时发生 panic: {message}")))
+            .with_context(|| format!("无法处理 {}", file_path.display()))
+    })
+}
+
+/// 处理单个文件的加密，返回文件大小和实际读写的字节数。
+fn process_single_encryption(
+    file_path: &Path,
+    opts: &EncryptionOptions,
     temp_file_path: Arc<Mutex<Option<PathBuf>>>,
-) -> Result<u64> {
-    let file_size = fs::metadata(file_path)?.len();
+    progress: Option<&dyn ProgressSink>,
+) -> Result<(u64, ByteCounts)> {
+    let file_size = fs::metadata(file_path)
+        .at_stage(Stage::HeaderParse)
+        .with_context(|| format!("无法加密 {}", file_path.display()))?
+        .len();
 
-    run_encryption_flow(
-        file_path,
-        config.force_overwrite,
-        password,
-        config.level,
-        keyfile,
-        temp_file_path,
-    )?;
+    let counts = run_encryption_flow_with_progress(file_path, opts, temp_file_path, progress)?;
 
-    Ok(file_size)
+    Ok((file_size, counts))
 }
 
-/// 处理单个文件的解密。
-fn process_single_decryption(
+/// 用文件头中的密钥文件承诺快速检查凭据：承诺存在并且与密码和密钥文件不匹配时返回 `true`。
+///
+/// 没有使用密钥文件、文件头无法读取或没有记录承诺时返回 `false`，由完整的解密流程给出结果。
+fn keyfile_commitment_rejected(path: &Path, opts: &DecryptionOptions) -> bool {
+    let Some(keyfile) = opts.keyfile else {
+        return false;
+    };
+    let matches = File::open(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| read_header(&mut BufReader::new(file)))
+        .and_then(|header| keyfile_commitment_matches(&header, opts.password, keyfile));
+    matches!(matches, Ok(Some(false)))
+}
+
+/// 依次用凭据表中的凭据解密 `file_path`，最后是 `opts` 中的密码和密钥文件，直到其中一个通过认证。
+///
+/// 尝试之前先读取一次文件头，用密钥文件承诺排除不可能正确的凭据，见 [`credential_rejection`]。
+/// 认证失败以外的错误（目标文件已存在、磁盘已满等）不会因为换一个凭据而消失，直接返回。
+fn decrypt_with_credentials(
     file_path: &Path,
+    opts: &DecryptionOptions,
+    candidates: Vec<&Credential>,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<(u64, DecryptionOutcome)> {
+    let mut attempts: Vec<(&str, &str, Option<&KeyFile>)> = candidates
+        .into_iter()
+        .map(|credential| {
+            (
+                credential.name(),
+                credential.password(),
+                credential.keyfile(),
+            )
+        })
+        .collect();
+    if !opts.password.is_empty() || opts.keyfile.is_some() {
+        attempts.push(("命令行凭据", opts.password, opts.keyfile));
+    }
+    let header = File::open(file_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| read_header(&mut BufReader::new(file)))
+        .ok();
+
+    let mut attempted = Vec::new();
+    for (name, password, keyfile) in attempts {
+        if let Some(reason) = header
+            .as_ref()
+            .and_then(|header| credential_rejection(header, password, keyfile))
+        {
+            log::debug!("凭据 {name} 不适用于 {}: {reason}", file_path.display());
+            attempted.push(format!("{name} ({reason})"));
+            continue;
+        }
+        let attempt_opts = DecryptionOptions {
+            password,
+            keyfile,
+            ..*opts
+        };
+        match process_single_decryption(
+            file_path,
+            &attempt_opts,
+            Arc::clone(&temp_file_path),
+            progress,
+        ) {
+            Ok(outcome) => {
+                log::info!("🔑 {} 使用凭据: {name}", file_path.display());
+                return Ok(outcome);
+            }
+            Err(e) if FeroxError::classify(&e).is_authentication_failure() => {
+                log::debug!("凭据 {name} 未能解密 {}", file_path.display());
+                attempted.push(format!("{name} (认证失败)"));
+            }
+            Err(e) => return Err(e.context(format!("使用凭据 {name} 时出错"))),
+        }
+    }
+
+    Err(FeroxError::CredentialsExhausted { attempted })
+        .at_stage(Stage::KeyDerivation)
+        .with_context(|| format!("无法解密 {}", file_path.display()))
+}
+
+/// 用文件头快速判断一组凭据不可能正确，返回原因；无法判断时返回 `None`，由完整的解密流程给出结果。
+///
+/// 只有加密时使用了密钥文件的文件才在（经过认证的）文件头中记录密钥文件承诺，
+/// 因此有承诺时不带密钥文件的凭据、以及与承诺不匹配的密钥文件都可以直接排除。
+fn credential_rejection(
+    header: &FileHeader,
     password: &str,
     keyfile: Option<&KeyFile>,
+) -> Option<&'static str> {
+    if !header.is_authenticated() || header.keyfile_commitment().is_none() {
+        return None;
+    }
+    match keyfile {
+        None => Some("文件加密时使用了密钥文件"),
+        Some(keyfile) => matches!(
+            keyfile_commitment_matches(header, password, keyfile),
+            Ok(Some(false))
+        )
+        .then_some("与密钥文件承诺不匹配"),
+    }
+}
+
+/// 处理单个文件的解密，返回文件大小和解密结果（是否因目标文件已存在而跳过）。
+fn process_single_decryption(
+    file_path: &Path,
+    opts: &DecryptionOptions,
     temp_file_path: Arc<Mutex<Option<PathBuf>>>,
-) -> Result<u64> {
-    let file_size = fs::metadata(file_path)?.len();
+    progress: Option<&dyn ProgressSink>,
+) -> Result<(u64, DecryptionOutcome)> {
+    let file_size = fs::metadata(file_path)
+        .at_stage(Stage::HeaderParse)
+        .with_context(|| format!("无法解密 {}", file_path.display()))?
+        .len();
+
+    let outcome = run_decryption_flow_with_progress(file_path, opts, temp_file_path, progress)?;
+
+    Ok((file_size, outcome))
+}
 
-    run_decryption_flow(file_path, password, keyfile, temp_file_path)?;
+/// 向进度接收器发送一个事件（如果设置了接收器）。
+fn emit(progress: Option<&dyn ProgressSink>, event: ProgressEvent<'_>) {
+    if let Some(sink) = progress {
+        sink.on_event(&event);
+    }
+}
+
+/// 发送 `BatchStarted` 事件，总字节数根据文件元数据估算。
+fn emit_batch_started(progress: Option<&dyn ProgressSink>, files: &[PathBuf]) {
+    if progress.is_none() {
+        return;
+    }
+    let total_bytes = files
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    emit(
+        progress,
+        ProgressEvent::BatchStarted {
+            total_files: files.len(),
+            total_bytes,
+        },
+    );
+}
 
-    Ok(file_size)
+/// 发送 `BatchFinished` 事件。
+fn emit_batch_finished(progress: Option<&dyn ProgressSink>, result: &BatchResult) {
+    emit(
+        progress,
+        ProgressEvent::BatchFinished {
+            success_count: result.success_count,
+            failure_count: result.failure_count,
+        },
+    );
 }
 
 /// 收集目录下符合条件的文件。
@@ -242,13 +2426,13 @@ fn process_single_decryption(
 /// # 参数
 ///
 /// * `directory` - 要搜索的根目录。
-/// * `config` - 批量操作配置，用于判断是否递归以及如何过滤。
+/// * `config` - 批量操作配置，用于判断是否递归、是否并行扫描以及如何过滤。
 /// * `encrypted_only` - `true` 表示只收集已加密文件，`false` 表示只收集未加密文件。
 ///
 /// # 返回
 ///
-/// 一个包含所有符合条件的文件路径的向量。
-fn collect_files(
+/// 一个包含所有符合条件的文件路径的向量，按路径排序。
+pub(crate) fn collect_files(
     directory: &Path,
     config: &BatchConfig,
     encrypted_only: bool,
@@ -257,18 +2441,200 @@ fn collect_files(
         anyhow::bail!("提供的路径不是一个目录: {}", directory.display());
     }
 
-    // 使用 walkdir 库来遍历文件，它能很好地处理递归和非递归的情况
+    let (mut files, problems) = if config.parallel_scan {
+        collect_files_parallel(directory, config, encrypted_only)
+    } else {
+        collect_files_serial(directory, config, encrypted_only)
+    };
+    // 无法读取的目录和目录项被跳过；严格模式下使整个扫描失败，不留下悄悄漏掉的文件
+    for problem in problems {
+        warn_or_fail(config.strict, format!("扫描时{problem}"))?;
+    }
+
+    // 目录项的返回顺序取决于文件系统，排序后两种扫描方式得到相同且确定的顺序
+    files.sort_unstable();
+    Ok(files)
+}
+
+/// 使用 walkdir 串行扫描目录，它能很好地处理递归和非递归的情况。
+///
+/// 返回收集到的文件和无法读取的目录项的描述。
+#[cfg(not(target_arch = "wasm32"))]
+fn collect_files_serial(
+    directory: &Path,
+    config: &BatchConfig,
+    encrypted_only: bool,
+) -> (Vec<PathBuf>, Vec<String>) {
     let walker = WalkDir::new(directory).max_depth(if config.recursive { usize::MAX } else { 1 });
+    let mut files = Vec::new();
+    let mut problems = Vec::new();
+    for entry in walker {
+        match entry {
+            Ok(entry) => {
+                if is_collected(entry.file_type(), config)
+                    && should_include_file(entry.path(), config, encrypted_only)
+                {
+                    files.push(entry.path().to_path_buf());
+                }
+            }
+            Err(e) => problems.push(match e.path() {
+                Some(path) => format!("无法读取 {}: {e}", path.display()),
+                None => format!("无法读取目录项: {e}"),
+            }),
+        }
+    }
+    (files, problems)
+}
 
-    let files: Vec<PathBuf> = walker
-        .into_iter()
-        .filter_map(Result::ok) // 忽略读取目录中的错误
-        .filter(|e| e.file_type().is_file()) // 只关心文件
-        .map(|e| e.path().to_path_buf())
-        .filter(|path| should_include_file(path, config, encrypted_only)) // 应用过滤规则
-        .collect();
+/// WebAssembly 上没有文件系统，找不到任何文件。
+#[cfg(target_arch = "wasm32")]
+fn collect_files_serial(
+    _directory: &Path,
+    _config: &BatchConfig,
+    _encrypted_only: bool,
+) -> (Vec<PathBuf>, Vec<String>) {
+    (Vec::new(), Vec::new())
+}
 
-    Ok(files)
+/// 扫描时是否收集该类型的目录项。
+///
+/// 只收集普通文件，不跟随也不收集符号链接。命名管道、设备节点和套接字平时被略过；
+/// 严格模式下也收集它们，之后逐个记录为失败，而不是悄悄地不出现在结果中。
+fn is_collected(file_type: fs::FileType, config: &BatchConfig) -> bool {
+    file_type.is_file() || (config.strict && !file_type.is_dir() && !file_type.is_symlink())
+}
+
+/// 并行扫描的共享状态：待读取的目录和正在读取目录的线程数。
+struct ScanQueue {
+    directories: Vec<PathBuf>,
+    busy: usize,
+}
+
+/// 使用多个线程扫描目录树，收集符合条件的文件（顺序不确定）。
+///
+/// 与串行扫描的语义相同：不跟随符号链接，跳过并报告无法读取的目录项，
+/// 非递归时只读取根目录。每个线程从共享队列中取出一个目录读取，
+/// 把其中的子目录放回队列；队列为空且没有线程在读取目录时扫描结束。
+fn collect_files_parallel(
+    directory: &Path,
+    config: &BatchConfig,
+    encrypted_only: bool,
+) -> (Vec<PathBuf>, Vec<String>) {
+    let queue = Mutex::new(ScanQueue {
+        directories: vec![directory.to_path_buf()],
+        busy: 0,
+    });
+    let wakeup = Condvar::new();
+    let workers = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+
+    let worker = || {
+        let mut files = Vec::new();
+        let mut problems = Vec::new();
+        loop {
+            let dir = {
+                let mut state = queue.lock().unwrap_or_else(|e| e.into_inner());
+                loop {
+                    if let Some(dir) = state.directories.pop() {
+                        state.busy += 1;
+                        break dir;
+                    }
+                    if state.busy == 0 {
+                        return (files, problems);
+                    }
+                    state = wakeup.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+            };
+
+            let mut subdirectories = Vec::new();
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries.collect(),
+                Err(e) => {
+                    problems.push(format!("无法读取 {}: {e}", dir.display()));
+                    Vec::new()
+                }
+            };
+            for entry in entries {
+                let (path, file_type) = match entry.and_then(|entry| {
+                    let file_type = entry.file_type()?;
+                    Ok((entry.path(), file_type))
+                }) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        problems.push(format!("无法读取 {} 中的目录项: {e}", dir.display()));
+                        continue;
+                    }
+                };
+                if file_type.is_dir() {
+                    if config.recursive {
+                        subdirectories.push(path);
+                    }
+                } else if is_collected(file_type, config)
+                    && should_include_file(&path, config, encrypted_only)
+                {
+                    files.push(path);
+                }
+            }
+
+            let mut state = queue.lock().unwrap_or_else(|e| e.into_inner());
+            state.directories.append(&mut subdirectories);
+            state.busy -= 1;
+            // 有新目录可读，或者扫描已经结束，都需要唤醒等待的线程
+            wakeup.notify_all();
+        }
+    };
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+        let (mut files, mut problems) = (Vec::new(), Vec::new());
+        for handle in handles {
+            let (found, unreadable) = handle
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e));
+            files.extend(found);
+            problems.extend(unreadable);
+        }
+        (files, problems)
+    })
+}
+
+/// 展开命令行参数中未被 shell 展开的通配符。
+///
+/// Windows 的 cmd 和 PowerShell 不会展开 `*.docx` 之类的参数，程序收到的是字面字符串。
+/// 对于包含通配符 (`*`、`?`、`[`) 且并不实际存在的参数，使用 glob 模式
+/// 相对于当前目录展开，匹配结果按路径排序；其余参数原样保留。
+///
+/// # 错误
+///
+/// 模式无效或没有匹配任何路径时返回错误。
+pub fn expand_glob_arguments(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::with_capacity(paths.len());
+    for path in paths {
+        let Some(pattern) = path.to_str().filter(|p| has_glob_metachars(p)) else {
+            expanded.push(path.clone());
+            continue;
+        };
+        // 名字里确实带有通配符字符的文件按字面处理
+        if path.exists() {
+            expanded.push(path.clone());
+            continue;
+        }
+
+        let mut matches = glob::glob(pattern)
+            .map_err(|e| anyhow::anyhow!("无效的通配符模式 '{pattern}': {e}"))?
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            anyhow::bail!("通配符 '{pattern}' 没有匹配任何文件");
+        }
+        matches.sort();
+        expanded.extend(matches);
+    }
+    Ok(expanded)
+}
+
+/// 判断参数中是否包含 glob 通配符。
+fn has_glob_metachars(arg: &str) -> bool {
+    arg.contains(['*', '?', '['])
 }
 
 /// 判断一个文件是否应该被包含在批量处理中。
@@ -284,6 +2650,24 @@ fn should_include_file(path: &Path, config: &BatchConfig, encrypted_only: bool)
     if !encrypted_only && is_encrypted {
         return false; // 需要未加密文件，但当前文件已加密
     }
+    if path
+        .extension()
+        .is_some_and(|ext| ext == crate::constants::INDEX_FILE_EXTENSION)
+    {
+        return false; // 加密索引文件本身已加密，不作为普通文件处理
+    }
+    if path
+        .file_name()
+        .is_some_and(|name| name == crate::constants::MANIFEST_FILE_NAME)
+    {
+        return false; // 恢复校验清单描述的是其他文件，不作为普通文件处理
+    }
+    if path.file_name().is_some_and(|name| {
+        name == crate::constants::README_FILE_NAME
+            || name == crate::constants::README_JSON_FILE_NAME
+    }) {
+        return false; // 目录说明文件描述的是其他文件，不作为普通文件处理
+    }
 
     // 检查文件名是否匹配任何一个 `include` 模式
     let matches_include = config
@@ -303,3 +2687,170 @@ fn should_include_file(path: &Path, config: &BatchConfig, encrypted_only: bool)
 
     !matches_exclude // 如果不匹配任何 `exclude` 模式，则最终决定包含该文件
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// 生成一棵深度为 `depth`、每层 `fan_out` 个子目录的目录树，每个目录中放几个不同类型的文件。
+    fn build_tree(dir: &Path, depth: usize, fan_out: usize) -> Result<()> {
+        for name in ["a.txt", "b.log", "c.txt.feroxcrypt", "vault.feroxidx"] {
+            fs::write(dir.join(name), name)?;
+        }
+        if depth > 0 {
+            for i in 0..fan_out {
+                let child = dir.join(format!("dir_{i}"));
+                fs::create_dir(&child)?;
+                build_tree(&child, depth - 1, fan_out)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_scan_matches_serial_scan() -> Result<()> {
+        let dir = tempdir()?;
+        build_tree(dir.path(), 5, 3)?;
+
+        let configs = [
+            BatchConfig {
+                recursive: true,
+                ..Default::default()
+            },
+            BatchConfig::default(),
+            BatchConfig {
+                recursive: true,
+                include_patterns: vec![Pattern::new("*.txt*")?],
+                exclude_patterns: vec![Pattern::new("*/dir_1/*")?],
+                ..Default::default()
+            },
+        ];
+        for config in configs {
+            for encrypted_only in [false, true] {
+                let serial = collect_files(dir.path(), &config, encrypted_only)?;
+                let parallel = collect_files(
+                    dir.path(),
+                    &BatchConfig {
+                        parallel_scan: true,
+                        ..config.clone()
+                    },
+                    encrypted_only,
+                )?;
+                assert!(!serial.is_empty());
+                assert_eq!(serial, parallel);
+            }
+        }
+
+        // 递归扫描收集了每个目录中的两个未加密文件: (3^6 - 1) / 2 个目录
+        let all = collect_files(
+            dir.path(),
+            &BatchConfig {
+                recursive: true,
+                parallel_scan: true,
+                ..Default::default()
+            },
+            false,
+        )?;
+        assert_eq!(all.len(), 2 * 364);
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_report_roundtrip() -> Result<()> {
+        let mut result = BatchResult::new(true);
+        result.add_success(Path::new("data/a.txt"), 1024);
+        result.add_failure(
+            PathBuf::from("data/b, \"quoted\".txt"),
+            FeroxError::Other("第一行\n第二行, 含逗号".to_string()),
+        );
+        result.add_success(Path::new("data/c.txt"), 7);
+
+        let mut csv = Vec::new();
+        result.to_csv(&mut csv)?;
+        let text = String::from_utf8(csv.clone())?;
+        assert!(text.starts_with("path,status,bytes,error_message\n"));
+        assert!(text.contains("data/a.txt,success,1024,\n"));
+        assert!(text.contains("\"data/b, \"\"quoted\"\".txt\",failure,0,"));
+
+        let parsed = BatchResult::from_csv(csv.as_slice())?;
+        assert_eq!(parsed.success_count, 2);
+        assert_eq!(parsed.failure_count, 1);
+        assert_eq!(parsed.total_bytes, 1031);
+        assert_eq!(parsed.processed, result.processed);
+        assert_eq!(
+            parsed
+                .first_failure()
+                .map(|(_, e)| e.to_string())
+                .as_deref(),
+            Some("第一行\n第二行, 含逗号")
+        );
+
+        // 再次写出的 CSV 与原来完全相同
+        let mut again = Vec::new();
+        parsed.to_csv(&mut again)?;
+        assert_eq!(again, csv);
+
+        assert!(BatchResult::from_csv("path,status\n".as_bytes()).is_err());
+        assert!(BatchResult::from_csv(
+            "path,status,bytes,error_message\na.txt,done,1,\n".as_bytes()
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_breakdown_groups_by_extension_and_top_level_directory() {
+        let root = Path::new("/backup");
+        let mut result = BatchResult::new(true);
+        result.add_success(Path::new("/backup/docs/a.TXT"), 1);
+        result.add_success(Path::new("/backup/docs/deep/b.txt.feroxcrypt"), 1);
+        result.add_success(Path::new("/backup/README"), 1);
+        for name in ["x.pst", "y.pst", "nested/z.pst"] {
+            result.add_failure(
+                Path::new("/backup/mail").join(name),
+                FeroxError::Other("locked".to_string()),
+            );
+        }
+        result
+            .skipped_locked
+            .push(PathBuf::from("/backup/mail/open.pst"));
+        result
+            .skipped_existing
+            .push(PathBuf::from("/backup/docs/c.txt"));
+
+        let breakdown = result.breakdown(Some(root));
+        let counts = |succeeded, failed, skipped| GroupCounts {
+            succeeded,
+            failed,
+            skipped,
+        };
+        assert_eq!(
+            breakdown.by_extension,
+            BTreeMap::from([
+                (String::new(), counts(1, 0, 0)),
+                ("pst".to_string(), counts(0, 3, 1)),
+                ("txt".to_string(), counts(2, 0, 1)),
+            ])
+        );
+        assert_eq!(
+            breakdown.by_directory,
+            BTreeMap::from([
+                (".".to_string(), counts(1, 0, 0)),
+                ("docs".to_string(), counts(2, 0, 1)),
+                ("mail".to_string(), counts(0, 3, 1)),
+            ])
+        );
+        // 每个文件在两种分组中各计一次
+        let total = |groups: &BTreeMap<String, GroupCounts>| {
+            groups.values().map(GroupCounts::total).sum::<usize>()
+        };
+        assert_eq!(total(&breakdown.by_extension), 8);
+        assert_eq!(total(&breakdown.by_directory), 8);
+
+        // 没有根目录时按所在目录归组
+        let by_parent = result.breakdown(None).by_directory;
+        assert_eq!(by_parent["/backup/mail/nested"], counts(0, 1, 0));
+        assert_eq!(by_parent["/backup/mail"], counts(0, 2, 1));
+    }
+}
@@ -0,0 +1,94 @@
+// src/bench_support.rs
+
+//! # 基准测试支持模块 (Benchmark Support Module)
+//!
+//! 只在启用 `bench-internals` 特性时编译，向 `benches/` 中的基准测试暴露
+//! 文件头解析、数据块加密认证等内部热点路径，以及可以注入随机数生成器的加密接口。
+//! 这些接口不属于稳定的公共 API，也不应在基准测试之外使用。
+//!
+//! *Compiled only with the `bench-internals` feature. Exposes internal hot paths and an
+//! RNG-injectable encryption entry point to the benchmarks; not part of the stable API.*
+
+use crate::{
+    constants::{AES_KEY_LEN, IV_LEN, MASTER_KEY_LEN, SALT_LEN, TAG_LEN},
+    encrypt::{encrypt_stream_with_rng, EncryptionOptions},
+    format::FileHeader,
+    pipeline::{process_stream, ChunkSettings, CipherDirection},
+    Level,
+};
+use anyhow::{anyhow, Context, Result};
+use argon2::{Argon2, Params};
+use ctr::cipher::KeyIvInit;
+use hmac::{Hmac, Mac};
+use rand::{CryptoRng, RngCore};
+use sha2::Sha256;
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+/// 与 [`encrypt_in_memory`](crate::encrypt_in_memory) 相同，但盐和 IV 由 `rng` 产生。
+///
+/// 使用固定种子的生成器时，相同的输入总是得到逐字节相同的输出。
+pub fn encrypt_in_memory_with_rng<G: RngCore + CryptoRng>(
+    plaintext: &[u8],
+    filename: &str,
+    opts: &EncryptionOptions,
+    rng: &mut G,
+) -> Result<Vec<u8>> {
+    let mut reader = plaintext;
+    let mut output = Vec::with_capacity(plaintext.len() + 1024);
+    encrypt_stream_with_rng(&mut reader, &mut output, filename, opts, rng, &mut |_| {})?;
+    Ok(output)
+}
+
+/// 按给定安全级别的 Argon2 参数从密码材料派生主密钥。
+pub fn derive_master_key(
+    password_material: &[u8],
+    salt: &[u8; SALT_LEN],
+    level: Level,
+) -> Result<[u8; MASTER_KEY_LEN]> {
+    let (m_cost, t_cost, p_cost) = level.argon2_params();
+    let params = Params::new(m_cost, t_cost, p_cost, Some(MASTER_KEY_LEN))
+        .map_err(|e| anyhow!("创建 Argon2 参数失败: {}", e))?;
+    let mut master_key = [0u8; MASTER_KEY_LEN];
+    Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(password_material, salt, &mut master_key)
+        .map_err(|e| anyhow!("Argon2密钥派生失败: {}", e))?;
+    Ok(master_key)
+}
+
+/// 使用固定的主密钥和 IV，把 `data` 按 `buffer_size` 分块加密并认证，密文写入 `output`，返回认证标签。
+///
+/// 与加密流程使用同一个数据块处理循环（流水线或串行，由 `single_thread` 决定），不包含密钥派生。
+pub fn encrypt_and_mac_chunks(
+    data: &[u8],
+    buffer_size: usize,
+    single_thread: bool,
+    output: &mut Vec<u8>,
+) -> Result<[u8; TAG_LEN]> {
+    let master_key = [0x42u8; MASTER_KEY_LEN];
+    let (aes_key, hmac_key) = master_key.split_at(AES_KEY_LEN);
+    let cipher = Aes256Ctr::new(aes_key.into(), &[0x24u8; IV_LEN].into());
+    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_key).context("无法创建HMAC实例")?;
+
+    let mut reader = data;
+    process_stream(
+        &mut reader,
+        output,
+        &cipher,
+        &mut |chunk: &[u8]| mac.update(chunk),
+        CipherDirection::Encrypt,
+        &ChunkSettings {
+            buffer_size,
+            single_thread,
+            threads: 1,
+        },
+        &mut |_| {},
+    )?;
+    Ok(mac.finalize().into_bytes().into())
+}
+
+/// 解析加密数据开头的文件头，返回文件头占用的字节数。
+pub fn parse_header(data: &[u8]) -> Result<u64> {
+    let mut reader = data;
+    Ok(FileHeader::read_from(&mut reader)?.len())
+}
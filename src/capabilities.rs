@@ -0,0 +1,129 @@
+// src/capabilities.rs
+
+//! # CPU 加密加速检测模块 (Crypto Acceleration Probe)
+//!
+//! 检测当前 CPU 是否提供 AES 硬件指令（x86 上的 AES-NI、ARMv8 的 AES 扩展）。
+//! 没有硬件加速时，`aes` crate 会回退到常数时间的软件实现，吞吐量可能下降一个数量级。
+//! 处理大文件之前会在调试级别输出检测结果，并在缺少硬件加速时发出一次警告。
+//!
+//! *Detects AES hardware instructions (AES-NI on x86, the ARMv8 AES extension).
+//! Without them the `aes` crate falls back to a much slower constant-time software
+//! implementation; a warning is logged once before the first large operation.*
+
+use crate::constants::LARGE_OPERATION_THRESHOLD;
+use std::fmt;
+use std::sync::Once;
+
+/// # 加密能力报告 (Capability Report)
+///
+/// [`crypto_capabilities`] 的检测结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapabilityReport {
+    /// 目标架构名称 (例如 `x86_64`、`aarch64`)。
+    pub arch: &'static str,
+    /// 检测到的 AES 硬件指令集名称；为 `None` 表示没有可用的硬件加速。
+    pub aes_acceleration: Option<&'static str>,
+}
+
+impl CapabilityReport {
+    /// 是否有 AES 硬件加速。
+    pub fn has_aes_acceleration(&self) -> bool {
+        self.aes_acceleration.is_some()
+    }
+
+    /// 缺少 AES 硬件加速时返回应向用户显示的警告，否则返回 `None`。
+    pub fn acceleration_warning(&self) -> Option<String> {
+        if self.has_aes_acceleration() {
+            return None;
+        }
+        Some(format!(
+            "未检测到 AES 硬件加速 ({})，AES-256 将使用软件实现，加解密速度可能明显下降。",
+            self.arch
+        ))
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.aes_acceleration {
+            Some(feature) => write!(f, "架构 {}，AES 硬件加速: {}", self.arch, feature),
+            None => write!(f, "架构 {}，AES 硬件加速: 不可用 (软件实现)", self.arch),
+        }
+    }
+}
+
+/// 检测当前 CPU 的加密加速能力。
+pub fn crypto_capabilities() -> CapabilityReport {
+    CapabilityReport {
+        arch: std::env::consts::ARCH,
+        aes_acceleration: detect_aes_acceleration(),
+    }
+}
+
+/// 运行时检测 AES 硬件指令。
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn detect_aes_acceleration() -> Option<&'static str> {
+    std::arch::is_x86_feature_detected!("aes").then_some("AES-NI")
+}
+
+/// 运行时检测 AES 硬件指令。
+#[cfg(target_arch = "aarch64")]
+fn detect_aes_acceleration() -> Option<&'static str> {
+    std::arch::is_aarch64_feature_detected!("aes").then_some("ARMv8 AES")
+}
+
+/// 其他架构上没有可检测的 AES 硬件加速。
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+fn detect_aes_acceleration() -> Option<&'static str> {
+    None
+}
+
+/// 在处理 `size` 字节的数据之前调用：数据量达到 [`LARGE_OPERATION_THRESHOLD`] 时，
+/// 在调试级别输出检测结果，缺少硬件加速时发出警告。每个进程只输出一次。
+pub(crate) fn report_before_large_operation(size: u64) {
+    static REPORTED: Once = Once::new();
+    if size < LARGE_OPERATION_THRESHOLD {
+        return;
+    }
+    REPORTED.call_once(|| {
+        let report = crypto_capabilities();
+        log::debug!("加密能力检测: {report}");
+        if let Some(warning) = report.acceleration_warning() {
+            log::warn!("{warning}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_structure_matches_target() {
+        let report = crypto_capabilities();
+        assert_eq!(report.arch, std::env::consts::ARCH);
+        assert_eq!(
+            report.has_aes_acceleration(),
+            report.aes_acceleration.is_some()
+        );
+        assert!(report.to_string().contains(report.arch));
+    }
+
+    #[test]
+    fn test_warning_only_without_aes_acceleration() {
+        let accelerated = CapabilityReport {
+            arch: "x86_64",
+            aes_acceleration: Some("AES-NI"),
+        };
+        assert!(accelerated.acceleration_warning().is_none());
+        assert!(accelerated.to_string().contains("AES-NI"));
+
+        let software = CapabilityReport {
+            arch: "riscv64",
+            aes_acceleration: None,
+        };
+        let warning = software.acceleration_warning().unwrap();
+        assert!(warning.contains("riscv64"));
+        assert!(warning.contains("软件实现"));
+    }
+}
@@ -0,0 +1,567 @@
+// src/cleanup.rs
+
+//! # 中断清理模块 (Interrupt Cleanup Module)
+//!
+//! 加密和解密流程在写入目标文件前，会把目标路径登记到一个共享的
+//! 临时文件登记表 (`Arc<Mutex<Option<PathBuf>>>`) 中，并在完成后清空。
+//! 该模块提供了基于这个登记表的清理函数和终止信号处理器 (Ctrl+C、`SIGTERM`、`SIGHUP`、
+//! Windows 控制台关闭事件)，供命令行模式和交互式模式共同使用。
+//!
+//! 输出总是先写入带建议锁的 `.part` 文件，成功后才重命名为目标文件。
+//! 进程被强制终止（例如 `SIGKILL`）时残留的 `.part` 文件可以通过
+//! [`scan_stale_partials`] 找到，并用 [`remove_stale_partials`] 清理。
+//!
+//! *Encryption and decryption flows register their target path in a shared
+//! temp-file registry before writing and clear it afterwards. This module
+//! provides the cleanup routine and the termination-signal handler built on top of that
+//! registry, shared by the CLI and the interactive mode. Outputs are written to
+//! advisory-locked `.part` files; leftovers of killed runs can be found with
+//! [`scan_stale_partials`].*
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::constants::CUSTOM_FILE_EXTENSION;
+use crate::constants::PARTIAL_FILE_EXTENSION;
+#[cfg(any(unix, windows))]
+use crate::constants::TERMINATION_CLEANUP_TIMEOUT_SECS;
+use crate::error::{warn_or_fail, FeroxError};
+use crate::resume::state_path_for;
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::SystemTime;
+#[cfg(not(target_arch = "wasm32"))]
+use walkdir::WalkDir;
+
+/// 删除登记表中记录的不完整输出文件（如果存在），并清空登记表。
+///
+/// 返回被删除的文件路径；如果没有需要清理的文件，则返回 `None`。
+///
+/// *Removes the partial output recorded in the registry, if any, and clears
+/// the registry. Returns the removed path.*
+pub fn cleanup_partial_output(temp_file_path: &Mutex<Option<PathBuf>>) -> Option<PathBuf> {
+    // 即使锁已中毒（持有锁的线程发生了 panic），也要尽力完成清理
+    let path = temp_file_path
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()?;
+
+    if !path.exists() {
+        return None;
+    }
+
+    log::warn!("正在清理不完整的输出文件: {}", path.display());
+    match std::fs::remove_file(&path) {
+        Ok(()) => {
+            log::info!("清理完成。");
+            Some(path)
+        }
+        Err(e) => {
+            log::error!("清理文件 {} 失败: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// 进程收到终止信号时执行的钩子，由 [`register_termination_hook`] 登记。
+type TerminationHook = Box<dyn FnOnce() + Send>;
+
+/// 已登记的终止钩子，按登记顺序执行。
+static TERMINATION_HOOKS: Mutex<Vec<TerminationHook>> = Mutex::new(Vec::new());
+
+/// 登记一个在进程收到终止信号时执行的钩子，例如刷新清单或审计日志等尚未写出的状态。
+///
+/// 钩子在删除不完整的输出文件之后执行，并且与之一起受到
+/// [`run_termination_cleanup`] 时间上限的约束；每个钩子最多执行一次。
+pub fn register_termination_hook(hook: impl FnOnce() + Send + 'static) {
+    TERMINATION_HOOKS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(Box::new(hook));
+}
+
+/// 信号处理器共用的清理流程：删除登记表中记录的不完整输出文件，然后执行已登记的终止钩子。
+///
+/// 清理在单独的线程中进行，最多等待 `timeout`；返回清理是否在时限内完成。
+/// 超时的时候清理线程仍在运行，调用者随后退出进程即可，不会被它拖住。
+///
+/// *Shared cleanup invoked by the signal handlers: removes the partial output,
+/// runs the registered termination hooks, and gives up after `timeout`.*
+pub fn run_termination_cleanup(
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    timeout: Duration,
+) -> bool {
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        cleanup_partial_output(&temp_file_path);
+        let hooks = std::mem::take(
+            &mut *TERMINATION_HOOKS
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+        for hook in hooks {
+            hook();
+        }
+        let _ = done_tx.send(());
+    });
+
+    let finished = done_rx.recv_timeout(timeout).is_ok();
+    if !finished {
+        log::error!("清理工作在 {} 秒内没有完成，直接退出。", timeout.as_secs());
+    }
+    finished
+}
+
+/// 执行清理，然后以 `exit_code` 退出进程。
+#[cfg(any(unix, windows))]
+fn terminate(temp_file_path: Arc<Mutex<Option<PathBuf>>>, exit_code: i32) -> ! {
+    run_termination_cleanup(
+        temp_file_path,
+        Duration::from_secs(TERMINATION_CLEANUP_TIMEOUT_SECS),
+    );
+    std::process::exit(exit_code);
+}
+
+/// 安装进程级的终止信号处理器。
+///
+/// Unix 上处理 `SIGINT` (Ctrl+C)、`SIGTERM` 和 `SIGHUP`，并按惯例以 128 加信号编号退出
+/// (分别为 130、143、129)。Windows 上处理 Ctrl+C、Ctrl+Break 以及关闭控制台窗口、
+/// 注销和关机事件，以退出码 130 退出。
+///
+/// 收到信号时通过 [`run_termination_cleanup`] 清理登记表中记录的不完整输出文件、
+/// 执行已登记的终止钩子，最多等待 [`TERMINATION_CLEANUP_TIMEOUT_SECS`] 秒。
+/// 每个进程只能安装一次。
+///
+/// *Installs the process-wide termination handler (SIGINT/SIGTERM/SIGHUP on Unix,
+/// console control events on Windows). It runs the bounded cleanup and exits with
+/// the conventional code.*
+#[cfg(unix)]
+pub fn install_interrupt_handler(temp_file_path: Arc<Mutex<Option<PathBuf>>>) -> Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals =
+        Signals::new([SIGINT, SIGTERM, SIGHUP]).context("设置终止信号处理器时出错")?;
+    std::thread::Builder::new()
+        .name("ferox-signals".into())
+        .spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                let name = match signal {
+                    SIGINT => "Ctrl+C (SIGINT)",
+                    SIGTERM => "SIGTERM",
+                    _ => "SIGHUP",
+                };
+                log::info!("\n接收到 {name} 信号，正在准备退出...");
+                terminate(temp_file_path, 128 + signal);
+            }
+        })
+        .context("无法启动信号处理线程")?;
+    Ok(())
+}
+
+/// 安装进程级的终止信号处理器。
+///
+/// Windows 上处理 Ctrl+C、Ctrl+Break 以及关闭控制台窗口、注销和关机事件，以退出码 130 退出。
+#[cfg(windows)]
+pub fn install_interrupt_handler(temp_file_path: Arc<Mutex<Option<PathBuf>>>) -> Result<()> {
+    ctrlc::set_handler(move || {
+        log::info!("\n接收到终止信号，正在准备退出...");
+        terminate(Arc::clone(&temp_file_path), 130);
+    })
+    .context("设置 Ctrl-C 处理器时出错")
+}
+
+/// 没有信号支持的平台（例如 WebAssembly）上无法安装处理器。
+#[cfg(not(any(unix, windows)))]
+pub fn install_interrupt_handler(_temp_file_path: Arc<Mutex<Option<PathBuf>>>) -> Result<()> {
+    bail!("当前平台不支持终止信号处理器")
+}
+
+/// 返回目标文件对应的不完整输出文件路径 (`<目标文件>.part`)。
+pub(crate) fn partial_path_for(target_path: &Path) -> PathBuf {
+    let mut name = target_path.as_os_str().to_owned();
+    name.push(".");
+    name.push(PARTIAL_FILE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// 创建（或截断）用于写入的 `.part` 文件，并持有它的独占建议锁。
+///
+/// 锁在文件关闭时自动释放。如果另一个正在运行的实例持有该文件的锁，则返回错误，
+/// 并且不会修改文件内容。
+pub(crate) fn create_partial_file(part_path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(part_path)
+        .with_context(|| format!("无法创建目标文件: {}", part_path.display()))?;
+    file.try_lock().map_err(|_| {
+        anyhow!(
+            "临时输出文件 {} 正被另一个正在运行的实例使用",
+            part_path.display()
+        )
+    })?;
+    // 拿到锁之后再截断，避免破坏其他实例正在写入的数据
+    file.set_len(0).context("无法截断临时输出文件")?;
+    Ok(file)
+}
+
+/// 打开已有的 `.part` 文件继续写入（不截断），并持有它的独占建议锁。
+///
+/// 用于可续传加密；另一个正在运行的实例持有该文件的锁时返回错误。
+pub(crate) fn open_partial_file(part_path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(part_path)
+        .with_context(|| format!("无法打开临时输出文件: {}", part_path.display()))?;
+    file.try_lock().map_err(|_| {
+        anyhow!(
+            "临时输出文件 {} 正被另一个正在运行的实例使用",
+            part_path.display()
+        )
+    })?;
+    Ok(file)
+}
+
+/// 创建写入 `target_path` 所用的 `.part` 文件，返回它的路径和已加锁的文件。
+///
+/// 优先在目标文件旁边创建；无法创建（例如目录只读或超出配额）且提供了 `temp_dir` 时，
+/// 改为在 `temp_dir` 中创建 `<目标路径哈希>-<文件名>.part`，不同目录中的同名目标不会冲突。
+/// 另一个实例正在使用同一个 `.part` 文件时直接返回错误，不会改用临时目录。
+pub(crate) fn create_staging_file(
+    target_path: &Path,
+    temp_dir: Option<&Path>,
+) -> Result<(PathBuf, File)> {
+    let part_path = partial_path_for(target_path);
+    let error = match create_partial_file(&part_path) {
+        Ok(file) => return Ok((part_path, file)),
+        Err(e) => e,
+    };
+    // 只有 I/O 错误才说明无法在目标目录中暂存；锁冲突必须报告给调用者
+    let (Some(temp_dir), true) = (temp_dir, error.downcast_ref::<std::io::Error>().is_some())
+    else {
+        return Err(error);
+    };
+
+    let hash = blake3::hash(target_path.as_os_str().as_encoded_bytes()).to_hex();
+    let mut name = std::ffi::OsString::from(format!("{}-", &hash[..16]));
+    name.push(part_path.file_name().context("目标路径没有文件名")?);
+    let staged_path = temp_dir.join(name);
+
+    log::warn!(
+        "无法在目标目录中创建临时文件 ({error:#})，改为暂存到: {}",
+        staged_path.display()
+    );
+    let file = create_partial_file(&staged_path)?;
+    Ok((staged_path, file))
+}
+
+/// 将写入完成的 `.part` 文件原子地重命名为最终的目标文件。
+///
+/// `.part` 文件暂存在另一个文件系统上（见 [`create_staging_file`]）时无法重命名，
+/// 改为通过 [`copy_across_devices`] 复制。
+pub(crate) fn commit_partial_file(part_path: &Path, target_path: &Path) -> Result<()> {
+    match fs::rename(part_path, target_path) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            copy_across_devices(part_path, target_path)
+        }
+        result => result.with_context(|| {
+            format!(
+                "无法将临时输出文件 {} 重命名为 {}",
+                part_path.display(),
+                target_path.display()
+            )
+        }),
+    }
+}
+
+/// 把另一个文件系统上的 `.part` 文件提交为目标文件，成功后删除 `.part` 文件。
+///
+/// 先复制到目标文件旁边的 `.part` 文件并 `fsync`，再原子地重命名；目标目录中无法创建该文件时，
+/// 只能直接复制到目标文件并 `fsync`，此时提交不是原子的（中断会留下不完整的目标文件），会发出警告。
+pub(crate) fn copy_across_devices(part_path: &Path, target_path: &Path) -> Result<()> {
+    let copy_into = |mut destination: File| -> Result<()> {
+        let mut source = File::open(part_path)
+            .with_context(|| format!("无法打开临时输出文件: {}", part_path.display()))?;
+        // 保持 `.part` 文件的权限（例如解密时只有所有者可以读写），最终权限由调用者在提交后设置
+        std::io::copy(&mut source, &mut destination)
+            .and_then(|_| destination.set_permissions(source.metadata()?.permissions()))
+            .and_then(|_| destination.sync_all())
+            .with_context(|| format!("无法复制临时输出文件到 {}", target_path.display()))
+    };
+
+    let sibling = partial_path_for(target_path);
+    match create_partial_file(&sibling) {
+        Ok(file) => {
+            let result = copy_into(file).and_then(|()| {
+                fs::rename(&sibling, target_path).with_context(|| {
+                    format!(
+                        "无法将 {} 重命名为 {}",
+                        sibling.display(),
+                        target_path.display()
+                    )
+                })
+            });
+            if result.is_err() {
+                let _ = fs::remove_file(&sibling);
+            }
+            result?;
+        }
+        Err(e) => {
+            log::warn!(
+                "⚠️  无法在目标目录中暂存 ({e:#})，直接复制到 {}：提交不是原子的，中断会留下不完整的文件",
+                target_path.display()
+            );
+            let file = File::create(target_path)
+                .with_context(|| format!("无法创建目标文件: {}", target_path.display()))?;
+            copy_into(file)?;
+        }
+    }
+
+    fs::remove_file(part_path)
+        .with_context(|| format!("无法删除临时输出文件: {}", part_path.display()))
+}
+
+/// # 残留的不完整输出文件 (Stale Partial Output)
+///
+/// 由被强制终止的操作留下的 `.part` 文件。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StalePartial {
+    /// 文件路径。
+    pub path: PathBuf,
+    /// 文件大小（字节）。
+    pub size: u64,
+    /// 距离最后一次修改的时间；无法获取时为 `None`。
+    pub age: Option<Duration>,
+}
+
+/// 扫描目录中残留的不完整输出文件。
+///
+/// 识别两类文件：
+/// - `*.feroxcrypt.part`：未完成的加密输出。
+/// - `<名称>.part`，且同一目录中存在 `<名称>.feroxcrypt`：未完成的解密输出。
+///
+/// 当前被正在运行的实例锁定的文件会被跳过。
+#[cfg(not(target_arch = "wasm32"))]
+pub fn scan_stale_partials(directory: &Path, recursive: bool) -> Result<Vec<StalePartial>> {
+    if !directory.is_dir() {
+        bail!("提供的路径不是一个目录: {}", directory.display());
+    }
+
+    let walker = WalkDir::new(directory).max_depth(if recursive { usize::MAX } else { 1 });
+    let now = SystemTime::now();
+    let mut partials = Vec::new();
+
+    for entry in walker.into_iter().filter_map(Result::ok) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || !is_partial_output(path) {
+            continue;
+        }
+        if is_locked(path) {
+            log::info!("跳过正在被使用的临时文件: {}", path.display());
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        partials.push(StalePartial {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            age: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok()),
+        });
+    }
+
+    Ok(partials)
+}
+
+/// WebAssembly 上没有可以扫描的文件系统。
+#[cfg(target_arch = "wasm32")]
+pub fn scan_stale_partials(_directory: &Path, _recursive: bool) -> Result<Vec<StalePartial>> {
+    bail!("WebAssembly 上不支持扫描目录")
+}
+
+/// 删除扫描到的残留文件，返回实际删除的路径。
+///
+/// 删除前会再次检查锁，扫描之后被其他实例重新使用的文件不会被删除。
+/// 可续传加密留下的检查点文件 (`.feroxstate`) 随对应的 `.part` 文件一起删除。
+pub fn remove_stale_partials(partials: &[StalePartial]) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for partial in partials {
+        if is_locked(&partial.path) {
+            log::warn!("文件正在被使用，已跳过: {}", partial.path.display());
+            continue;
+        }
+        fs::remove_file(&partial.path)
+            .with_context(|| format!("无法删除残留文件: {}", partial.path.display()))?;
+        log::info!("已删除残留文件: {}", partial.path.display());
+        let state_path = state_path_for(&partial.path.with_extension(""));
+        if state_path.is_file() {
+            fs::remove_file(&state_path)
+                .with_context(|| format!("无法删除检查点文件: {}", state_path.display()))?;
+        }
+        removed.push(partial.path.clone());
+    }
+    Ok(removed)
+}
+
+/// 如果目录中存在残留的不完整输出文件，记录一条警告并返回警告的内容；
+/// `strict` 时改为返回 [`FeroxError::StrictViolation`]。
+///
+/// 在批量操作开始时调用；扫描失败不会影响批量操作本身。
+pub(crate) fn warn_about_stale_partials(
+    directory: &Path,
+    recursive: bool,
+    strict: bool,
+) -> Result<Option<String>, FeroxError> {
+    let Ok(partials) = scan_stale_partials(directory, recursive) else {
+        return Ok(None);
+    };
+    if partials.is_empty() {
+        return Ok(None);
+    }
+    let warning = format!(
+        "在 {} 中发现 {} 个残留的不完整输出文件，可以使用 `cleanup` 子命令清理。",
+        directory.display(),
+        partials.len()
+    );
+    warn_or_fail(strict, warning.clone())?;
+    Ok(Some(warning))
+}
+
+/// 判断路径是否为本工具产生的不完整输出文件。
+#[cfg(not(target_arch = "wasm32"))]
+fn is_partial_output(path: &Path) -> bool {
+    if path
+        .extension()
+        .is_none_or(|ext| ext != PARTIAL_FILE_EXTENSION)
+    {
+        return false;
+    }
+    let target = path.with_extension("");
+    if target
+        .extension()
+        .is_some_and(|ext| ext == CUSTOM_FILE_EXTENSION)
+    {
+        return true;
+    }
+    // 解密输出：同目录中存在对应的加密文件
+    let mut encrypted = target.into_os_string();
+    encrypted.push(".");
+    encrypted.push(CUSTOM_FILE_EXTENSION);
+    Path::new(&encrypted).is_file()
+}
+
+/// 判断文件当前是否被其他实例持有建议锁。
+fn is_locked(path: &Path) -> bool {
+    match OpenOptions::new().write(true).open(path) {
+        Ok(file) => file.try_lock().is_err(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_cleanup_removes_registered_file() -> Result<()> {
+        let dir = tempdir()?;
+        let partial = dir.path().join("partial.feroxcrypt");
+        fs::write(&partial, b"incomplete")?;
+
+        let registry = Mutex::new(Some(partial.clone()));
+        assert_eq!(cleanup_partial_output(&registry), Some(partial.clone()));
+        assert!(!partial.exists());
+        assert!(registry.lock().unwrap().is_none());
+
+        // 登记表为空时不做任何事
+        assert_eq!(cleanup_partial_output(&registry), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_staging_falls_back_to_temp_dir() -> Result<()> {
+        let dir = tempdir()?;
+        let scratch = tempdir()?;
+        let target = dir.path().join("out.txt.feroxcrypt");
+
+        // 目标目录可以写入时不使用临时目录
+        let (part, file) = create_staging_file(&target, Some(scratch.path()))?;
+        assert_eq!(part, partial_path_for(&target));
+        drop(file);
+        fs::remove_file(&part)?;
+
+        // 目标旁边的 `.part` 路径被目录占用，模拟无法在目标目录中暂存
+        fs::create_dir(partial_path_for(&target))?;
+        assert!(create_staging_file(&target, None).is_err());
+        let (part, file) = create_staging_file(&target, Some(scratch.path()))?;
+        assert_eq!(part.parent(), Some(scratch.path()));
+        assert!(part.to_string_lossy().ends_with("-out.txt.feroxcrypt.part"));
+        drop(file);
+
+        fs::write(&part, b"ciphertext")?;
+        commit_partial_file(&part, &target)?;
+        assert_eq!(fs::read(&target)?, b"ciphertext");
+        assert!(!part.exists());
+        Ok(())
+    }
+
+    /// 模拟临时目录在另一个文件系统上时的提交：复制、`fsync`，能暂存时再原子地重命名。
+    #[test]
+    fn test_cross_device_commit_copies_and_removes_partial() -> Result<()> {
+        let dir = tempdir()?;
+        let scratch = tempdir()?;
+
+        let target = dir.path().join("atomic.txt");
+        let part = scratch.path().join("atomic.txt.part");
+        fs::write(&part, b"staged elsewhere")?;
+        copy_across_devices(&part, &target)?;
+        assert_eq!(fs::read(&target)?, b"staged elsewhere");
+        assert!(!part.exists());
+        assert!(!partial_path_for(&target).exists());
+
+        // 目标目录中无法暂存时直接复制到目标文件（不是原子的）
+        let target = dir.path().join("direct.txt");
+        fs::create_dir(partial_path_for(&target))?;
+        let part = scratch.path().join("direct.txt.part");
+        fs::write(&part, b"copied directly")?;
+        copy_across_devices(&part, &target)?;
+        assert_eq!(fs::read(&target)?, b"copied directly");
+        assert!(!part.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_termination_cleanup_runs_hooks_within_time_limit() -> Result<()> {
+        let dir = tempdir()?;
+        let partial = dir.path().join("partial.feroxcrypt.part");
+        fs::write(&partial, b"incomplete")?;
+
+        let (hook_tx, hook_rx) = std::sync::mpsc::channel();
+        register_termination_hook(move || hook_tx.send("flushed").unwrap());
+        let registry = Arc::new(Mutex::new(Some(partial.clone())));
+        assert!(run_termination_cleanup(
+            Arc::clone(&registry),
+            Duration::from_secs(10)
+        ));
+        assert!(!partial.exists());
+        assert_eq!(hook_rx.try_recv(), Ok("flushed"));
+
+        // 卡住的钩子不会让退出无限期地等待
+        register_termination_hook(|| std::thread::sleep(Duration::from_secs(5)));
+        let started = std::time::Instant::now();
+        assert!(!run_termination_cleanup(
+            registry,
+            Duration::from_millis(100)
+        ));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        Ok(())
+    }
+}
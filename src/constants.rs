@@ -21,6 +21,47 @@
 /// *and is used for automatic recognition during batch processing.*
 pub const CUSTOM_FILE_EXTENSION: &str = "feroxcrypt";
 
+/// 恢复模式输出文件名的后缀 (Suffix of recovery-mode output files)
+///
+/// [`decrypt_unverified`](crate::decrypt::decrypt_unverified) 写出的明文没有经过认证，
+/// 文件名带有这个后缀以免被误当作正常解密的结果。
+///
+/// *Marks plaintext written by the unauthenticated recovery mode.*
+pub const UNVERIFIED_SUFFIX: &str = ".UNVERIFIED";
+
+/// 写入过程中的不完整输出文件所使用的附加扩展名 (Extra extension for in-progress output files)
+///
+/// 加密和解密都先写入 `<目标文件>.part`，成功完成后再原子地重命名为目标文件。
+/// 因此目录中残留的 `.part` 文件一定是被强制终止的操作留下的不完整输出。
+///
+/// *Encryption and decryption write to `<target>.part` first and atomically rename it
+/// on success, so a leftover `.part` file is always the partial output of a killed run.*
+pub const PARTIAL_FILE_EXTENSION: &str = "part";
+
+/// 可续传加密的检查点文件所使用的附加扩展名 (Extra extension for resumable-encryption checkpoints)
+///
+/// 可续传加密（[`encrypt_resumable`](crate::resume::encrypt_resumable)）在 `<目标文件>.part`
+/// 旁边写入 `<目标文件>.feroxstate`，记录已经持久化的进度；加密成功后删除。
+///
+/// *Resumable encryption records its last durable position in `<target>.feroxstate`
+/// next to `<target>.part`, and removes it on success.*
+pub const STATE_FILE_EXTENSION: &str = "feroxstate";
+
+/// 可续传加密默认每处理多少字节明文写入一次检查点 (Default checkpoint interval of resumable encryption)
+///
+/// 每个检查点都要把 `.part` 文件同步到磁盘，间隔太小会明显拖慢加密；
+/// 中断后最多需要重新加密一个间隔的数据。
+///
+/// *Each checkpoint syncs the partial output to disk; at most one interval is redone after an interruption.*
+pub const RESUME_CHECKPOINT_INTERVAL: u64 = 256 * 1024 * 1024;
+
+/// 由 HMAC 密钥派生检查点认证密钥时使用的上下文 (Context for deriving the resume checkpoint key)
+///
+/// 检查点用 `HMAC-SHA256(HMAC 密钥, RESUME_CHECKPOINT_CONTEXT)` 认证，不会泄露文件本身的 HMAC 值。
+///
+/// *Checkpoints are authenticated under a domain-separated key and never expose the file HMAC.*
+pub const RESUME_CHECKPOINT_CONTEXT: &[u8] = b"ferox-checkpoint";
+
 /// 用于流式读写操作的缓冲区大小 (Buffer size for streaming read/write operations)
 ///
 /// 设置为 4MB 是为了在处理大文件时获得较好的 I/O 性能，同时避免过高的内存消耗。
@@ -31,6 +72,64 @@ pub const CUSTOM_FILE_EXTENSION: &str = "feroxcrypt";
 /// *This size has been tested to provide good performance balance on most systems.*
 pub const BUFFER_LEN: usize = 4 * 1024 * 1024;
 
+/// 允许的最小缓冲区大小 (Minimum allowed buffer size)
+///
+/// 缓冲区大小可以在运行时通过选项调整，默认值为 [`BUFFER_LEN`]。
+///
+/// *The buffer size can be tuned at runtime; the default is [`BUFFER_LEN`].*
+pub const MIN_BUFFER_SIZE: usize = 4 * 1024;
+
+/// 允许的最大缓冲区大小 (Maximum allowed buffer size)
+///
+/// 流水线和并行模式会同时持有多个缓冲区，因此上限不宜过大。
+///
+/// *Pipelined and parallel modes hold several buffers at once, so the upper bound is kept moderate.*
+pub const MAX_BUFFER_SIZE: usize = 256 * 1024 * 1024;
+
+/// 流水线模式下同时在途的数据块数量 (Number of chunks in flight in pipelined mode)
+///
+/// 加密和认证在独立线程上运行时，最多同时有这么多个 [`BUFFER_LEN`] 大小的缓冲区
+/// 在读取、加密、认证和写入之间流转，因此额外的内存占用约为 12MB。
+///
+/// *When the cipher and MAC run on separate threads, at most this many buffers
+/// circulate between reading, encryption, authentication and writing.*
+pub const PIPELINE_DEPTH: usize = 3;
+
+/// 并行 CTR 模式下每个工作线程对应的在途数据块数量 (In-flight chunks per worker in parallel CTR mode)
+///
+/// 同时在途的数据块最多为 `线程数 × 该值` 个，每个大小为 [`BUFFER_LEN`]，
+/// 例如 4 个线程时额外占用约 32MB 内存。
+///
+/// *At most `threads × this value` chunks of [`BUFFER_LEN`] bytes are in flight,
+/// which bounds the memory used by intra-file parallel encryption.*
+pub const PARALLEL_CHUNKS_PER_THREAD: usize = 2;
+
+/// 每个线程的缓冲区池最多保留的字节数 (Maximum bytes kept by each thread's buffer pool)
+///
+/// 处理完一个文件后，数据块缓冲区会留在当前线程中供下一个文件复用，
+/// 批量处理大量小文件时无需为每个文件重新分配并清零缓冲区。
+/// 默认可以保留 4 个 [`BUFFER_LEN`] 大小的缓冲区，超出的部分直接释放。
+///
+/// *Chunk buffers stay on the thread after a file is processed so the next file can
+/// reuse them; anything beyond this many bytes per thread is freed.*
+pub const BUFFER_POOL_MAX_BYTES: usize = 4 * BUFFER_LEN;
+
+/// io_uring 模式下在途的读取或写入数量 (Reads or writes in flight in io_uring mode)
+///
+/// 只在启用 `uring` 特性的 Linux 构建中使用。读取端和写入端各自最多同时提交这么多个
+/// 数据块大小的操作，默认数据块大小下各占用约 16MB 内存。
+///
+/// *Used only on Linux with the `uring` feature: the reader and the writer each keep at
+/// most this many chunk-sized operations in flight.*
+pub const URING_QUEUE_DEPTH: usize = 4;
+
+/// 估计解密时间时用于测量吞吐量的数据量（单位：字节）(Bytes used to calibrate throughput for time estimates)
+///
+/// 16MB 足以让测量不受计时精度影响，在没有 AES 硬件加速的机器上也只需不到一秒。
+///
+/// *Large enough to swamp timer resolution, small enough to finish quickly without AES-NI.*
+pub const ESTIMATE_CALIBRATION_BYTES: usize = 4 * BUFFER_LEN;
+
 // --- 密码学常量 (Cryptographic Constants) ---
 
 /// 使用 Argon2 从用户密码派生出的主密钥的长度（单位：字节）(Master key length derived from user password using Argon2)
@@ -76,6 +175,165 @@ pub const IV_LEN: usize = 16;
 /// *SHA-256 output is fixed at 32 bytes.*
 pub const TAG_LEN: usize = 32;
 
+// --- 文件格式常量 (File Format Constants) ---
+
+/// 版本化文件头开头的魔数 (Magic bytes at the start of a versioned header)
+///
+/// 不以该魔数开头的文件被视为没有版本字段的遗留格式（版本 0）。
+///
+/// *Files that do not start with these bytes use the unversioned legacy format (version 0).*
+pub const FORMAT_MAGIC: [u8; 4] = *b"FXC\0";
+
+/// 遗留格式的版本号 (Version number of the legacy format)
+///
+/// 遗留格式没有魔数和版本字段，文件直接以文件名长度开头，
+/// 认证标签也只覆盖密文。
+///
+/// *The legacy format has no magic or version byte and its tag only covers the ciphertext.*
+pub const LEGACY_FORMAT_VERSION: u8 = 0;
+
+/// 当前写入的文件格式版本 (File format version written by this build)
+///
+/// 版本 1 在魔数之后依次存储版本号、算法标识和扩展区，
+/// 并且认证标签同时覆盖文件头和密文。版本 2 在扩展区之前增加了 1 字节的接收者数量。
+///
+/// *Version 1 stores the version, algorithm identifiers and an extension area
+/// after the magic, and its tag covers both the header and the ciphertext.
+/// Version 2 adds a one-byte recipient count before the extension area.*
+pub const CURRENT_FORMAT_VERSION: u8 = 2;
+
+/// 文件头中开始包含接收者数量字段的格式版本 (First format version with a recipient count)
+///
+/// 更早的版本没有该字段，接收者数量视为 1。
+///
+/// *Older versions have no such field and imply a single recipient.*
+pub const RECIPIENT_COUNT_FORMAT_VERSION: u8 = 2;
+
+/// 加密算法标识：AES-256-CTR + HMAC-SHA256 (Cipher identifier for AES-256-CTR + HMAC-SHA256)
+pub const CIPHER_AES256_CTR_HMAC_SHA256: u8 = 1;
+
+/// 密钥派生算法标识：Argon2id v0x13 (KDF identifier for Argon2id v0x13)
+pub const KDF_ARGON2ID: u8 = 1;
+
+// --- 文件头尺寸 (Header Size Computations) ---
+
+/// 文件头中"魔数 + 版本号 + 算法标识"前导部分的长度（单位：字节）(Length of the versioned header preamble)
+///
+/// 4 字节魔数、1 字节版本号、1 字节加密算法标识和 1 字节密钥派生算法标识。
+///
+/// *4 magic bytes, 1 version byte, 1 cipher id byte and 1 KDF id byte.*
+pub const FORMAT_PREAMBLE_LEN: usize = FORMAT_MAGIC.len() + 3;
+
+/// 文件头中"文件名长度"字段的长度（单位：字节）(Length of the filename-length header field)
+///
+/// 文件名长度以 `u16` 小端序存储。
+///
+/// *The filename length is stored as a little-endian `u16`.*
+pub const FILENAME_LEN_FIELD_LEN: usize = 2;
+
+/// 文件头中 Argon2 参数区的长度（单位：字节）(Length of the Argon2 parameter block in the header)
+///
+/// 依次存储 `m_cost`、`t_cost`、`p_cost`，每个都是 `u32` 小端序。
+///
+/// *Stores `m_cost`, `t_cost` and `p_cost`, each as a little-endian `u32`.*
+pub const ARGON2_PARAMS_LEN: usize = 12;
+
+/// 文件头中"接收者数量"字段的长度（单位：字节）(Length of the recipient-count header field)
+///
+/// 从格式版本 2 开始存在，位于 Argon2 参数区之后、扩展区长度之前。
+///
+/// *Present from format version 2, between the Argon2 parameters and the extension area.*
+pub const RECIPIENT_COUNT_LEN: usize = 1;
+
+/// 文件头中"扩展区长度"字段的长度（单位：字节）(Length of the extension-area length field)
+///
+/// 扩展区的总字节数以 `u16` 小端序存储，扩展区为空时该字段为 0。
+///
+/// *The total size of the extension area is stored as a little-endian `u16`.*
+pub const EXTENSIONS_LEN_FIELD_LEN: usize = 2;
+
+/// 当前格式下，文件名为空且没有扩展时文件头的长度（单位：字节）(Current header size for a zero-length filename)
+///
+/// 即当前格式文件头中所有定长字段的长度之和。
+///
+/// *The sum of all fixed-size fields of the current header format.*
+pub const fn min_header_size() -> usize {
+    FORMAT_PREAMBLE_LEN
+        + legacy_header_size_for_filename(0)
+        + RECIPIENT_COUNT_LEN
+        + EXTENSIONS_LEN_FIELD_LEN
+}
+
+/// 当前格式下，给定原始文件名字节长度时文件头的长度（单位：字节）(Current header size for a given filename length)
+///
+/// 不包含扩展区的内容。所有计算文件头长度的地方都应调用此函数（或遗留格式的对应函数），
+/// 而不是手动相加各字段。
+///
+/// *Excludes the extension area contents. All header size computations should go
+/// through this function (or its legacy counterpart) instead of adding fields inline.*
+pub const fn header_size_for_filename(filename_len: usize) -> usize {
+    min_header_size() + filename_len
+}
+
+/// 遗留格式（版本 0）下，给定原始文件名字节长度时文件头的长度（单位：字节）(Legacy header size for a given filename length)
+///
+/// *Header size of the unversioned legacy format.*
+pub const fn legacy_header_size_for_filename(filename_len: usize) -> usize {
+    FILENAME_LEN_FIELD_LEN + filename_len + SALT_LEN + IV_LEN + ARGON2_PARAMS_LEN
+}
+
+/// 与明文长度无关的固定开销（单位：字节）(Fixed overhead independent of the plaintext length)
+///
+/// 当前格式文件头的定长部分加上末尾的 HMAC 标签，不包含可变长度的文件名。
+/// 没有扩展时，一个加密文件的总长度为 `fixed_overhead_bytes() + 文件名长度 + 明文长度`；
+/// 当前版本加密时总会写入一条安全级别扩展，另外占用 `4 + SECURITY_LEVEL_LEN` 字节。
+///
+/// *Fixed header fields plus the trailing HMAC tag, excluding the variable filename.*
+pub const fn fixed_overhead_bytes() -> usize {
+    min_header_size() + TAG_LEN
+}
+
+// 编译期检查：文件头定长部分的长度必须与格式文档一致。
+// 遗留格式: 2 + 16 + 16 + 12；当前格式: 4 + 1 + 1 + 1 + 2 + 16 + 16 + 12 + 1 + 2。
+const _: () = assert!(legacy_header_size_for_filename(0) == 46);
+const _: () = assert!(min_header_size() == 56);
+const _: () = assert!(fixed_overhead_bytes() == 88);
+
+// --- 文件头字段上限 (Header Field Limits) ---
+
+/// 文件头中原始文件名的最大长度（单位：字节）(Maximum length of the original filename in a header)
+///
+/// 原始文件名只是单个路径成分，常见文件系统的上限是 255 字节（Linux）或 255 个 UTF-16
+/// 单元（Windows、macOS，UTF-8 编码后最多 765 字节），1024 字节留有充足的余量。
+///
+/// *The stored name is a single path component; 1024 bytes comfortably exceeds every
+/// common filesystem limit.*
+pub const MAX_HEADER_FILENAME_LEN: usize = 1024;
+
+/// 文件头扩展区的最大长度（单位：字节）(Maximum size of the header extension area)
+///
+/// *Upper bound on the TLV extension area of a header.*
+pub const MAX_HEADER_EXTENSIONS_LEN: usize = 4 * 1024;
+
+/// 文件头中 Argon2 内存成本的上限（单位：KiB）(Upper bound on the Argon2 memory cost in a header)
+///
+/// 为最高安全级别 (Paranoid, 256 MiB) 的 4 倍。文件头在认证之前就决定了密钥派生的开销，
+/// 该上限防止构造的文件头让解密方分配任意多的内存。
+///
+/// *Four times the Paranoid level; keeps a crafted header from forcing an arbitrarily large
+/// allocation before the file is authenticated.*
+pub const MAX_HEADER_ARGON2_M_COST: u32 = 1024 * 1024;
+
+/// 文件头中 Argon2 时间成本的上限 (Upper bound on the Argon2 time cost in a header)
+///
+/// *Upper bound on the number of Argon2 passes accepted from a header.*
+pub const MAX_HEADER_ARGON2_T_COST: u32 = 16;
+
+/// 文件头中 Argon2 并行度的上限 (Upper bound on the Argon2 parallelism in a header)
+///
+/// *Upper bound on the Argon2 lane count accepted from a header.*
+pub const MAX_HEADER_ARGON2_P_COST: u32 = 16;
+
 // --- 密钥文件常量 (Keyfile Constants) ---
 
 /// 密钥文件的最小允许大小（单位：字节）(Minimum allowed size for keyfiles)
@@ -96,6 +354,23 @@ pub const MIN_KEYFILE_SIZE: usize = 64;
 /// *4KB size is sufficient to provide high-strength security while maintaining reasonable file size.*
 pub const MAX_KEYFILE_SIZE: usize = 4 * 1024; // 4 KB
 
+/// 估算密钥文件熵时采样的字节数 (Bytes sampled when estimating keyfile entropy)
+///
+/// [`KeyFile::entropy_estimate`](crate::keyfile::KeyFile::entropy_estimate) 只统计开头的这些字节。
+///
+/// *Only the first bytes of a keyfile are counted when estimating its entropy.*
+pub const KEYFILE_ENTROPY_SAMPLE_LEN: usize = 256;
+
+/// 加载和验证密钥文件时要求的最低熵（比特/字节）(Minimum keyfile entropy in bits per byte)
+///
+/// 256 字节的样本最多只有 8 比特/字节；即使是真正随机的数据，样本熵平均也只有约 7.18，
+/// 大约每 2000 个随机密钥文件就有一个低于 7.0。6.0 仍能可靠地拒绝文本（约 4.5）和重复的数据，
+/// 而不会误拒随机生成的密钥文件。样本不足 256 字节时，门槛按样本可达到的最大熵等比例降低。
+///
+/// *Random 256-byte samples average about 7.18 bits/byte, so 6.0 rejects text and repeated
+/// data without ever rejecting a generated keyfile.*
+pub const MIN_KEYFILE_ENTROPY_BITS: f64 = 6.0;
+
 /// 用于密钥文件内容派生的盐（单位：字节）(Salt for keyfile content derivation)
 ///
 /// 这个盐是固定的，以确保从相同的密钥文件内容总是能派生出相同的密钥材料。
@@ -111,3 +386,262 @@ pub const KEYFILE_DERIVATION_SALT: &[u8] = b"ferox-encryptor-keyfile-salt";
 ///
 /// *32 bytes provides 256-bit security strength, matching AES-256 key length.*
 pub const KEYFILE_DERIVED_LEN: usize = 32;
+
+/// 密钥文件承诺的长度（单位：字节）(Length of the keyfile commitment)
+///
+/// 承诺是 HMAC-SHA256 的输出，固定为 32 字节。
+///
+/// *The commitment is an HMAC-SHA256 output, fixed at 32 bytes.*
+pub const KEYFILE_COMMITMENT_LEN: usize = 32;
+
+/// 计算密钥文件承诺时使用的固定消息 (Fixed message used to compute the keyfile commitment)
+///
+/// 承诺为 `HMAC-SHA256(密码材料, KEYFILE_COMMITMENT_CONTEXT)`，其中密码材料由密码和密钥文件共同派生。
+///
+/// *The commitment is `HMAC-SHA256(password_material, KEYFILE_COMMITMENT_CONTEXT)`.*
+pub const KEYFILE_COMMITMENT_CONTEXT: &[u8] = b"FEROX_KEYFILE_COMMIT";
+
+/// 文件过期时间字段的长度（单位：字节）(Length of the expiry time field)
+///
+/// 过期时间以小端序的 64 位 Unix 时间戳（秒）存储在文件头扩展区中，0 表示永不过期。
+///
+/// *The expiry is stored as a little-endian 64-bit Unix timestamp in seconds; 0 means no expiry.*
+pub const EXPIRY_TIME_LEN: usize = 8;
+
+/// 源文件修改时间字段的长度（单位：字节）(Length of the source modification time field)
+///
+/// 小端序的 64 位 Unix 时间戳（秒）加上小端序的 32 位纳秒部分，只在加密时要求记录时写入。
+///
+/// *A little-endian 64-bit Unix timestamp in seconds followed by a little-endian 32-bit
+/// nanosecond part; only written when requested.*
+pub const SOURCE_MTIME_LEN: usize = 12;
+
+/// 文件头权限位扩展的长度（字节）。
+pub const FILE_MODE_LEN: usize = 4;
+
+/// 文件头安全级别扩展的长度（字节）。
+///
+/// 记录产生 Argon2 参数的预设级别：1 = Interactive，2 = Moderate，3 = Paranoid，
+/// [`SECURITY_LEVEL_CUSTOM`] 表示参数不属于任何预设级别。
+///
+/// *One byte naming the preset level that produced the Argon2 parameters.*
+pub const SECURITY_LEVEL_LEN: usize = 1;
+
+/// 安全级别扩展中表示自定义 Argon2 参数的值。
+pub const SECURITY_LEVEL_CUSTOM: u8 = 0;
+
+/// 密钥文件分片所使用的文件扩展名 (File extension for keyfile shares)
+///
+/// 使用 Shamir 秘密共享拆分密钥文件时，每个分片保存为一个 `.feroxshare` 文件。
+///
+/// *Each share of a keyfile split with Shamir secret sharing is saved as a `.feroxshare` file.*
+pub const KEYSHARE_FILE_EXTENSION: &str = "feroxshare";
+
+/// 密钥文件分片的魔数 (Magic bytes of a keyfile share)
+pub const KEYSHARE_MAGIC: [u8; 4] = *b"FXS\0";
+
+/// 密钥文件分片的格式版本 (Format version of keyfile shares)
+pub const KEYSHARE_FORMAT_VERSION: u8 = 1;
+
+/// 分片中记录的密钥文件校验值的长度（单位：字节）(Length of the keyfile checksum stored in each share)
+///
+/// 校验值是密钥文件内容 SHA-256 哈希的前 8 字节，用于在重建后确认分片属于同一个密钥文件且未损坏。
+///
+/// *The first 8 bytes of the keyfile's SHA-256 hash, used to confirm a reconstruction.*
+pub const KEYSHARE_CHECKSUM_LEN: usize = 8;
+
+/// 加密索引文件所使用的文件扩展名 (File extension for encrypted filename indexes)
+///
+/// 批量加密时生成的文件名索引保存为 `.feroxidx` 文件；批量加密会跳过这类文件。
+///
+/// *Filename indexes built during batch encryption are saved as `.feroxidx` files,
+/// which batch encryption skips.*
+pub const INDEX_FILE_EXTENSION: &str = "feroxidx";
+
+/// 批量加密目录时生成的索引文件名 (File name of the index built for a directory)
+pub const INDEX_FILE_NAME: &str = "vault.feroxidx";
+
+/// 解密后的索引内容的魔数 (Magic bytes of a decrypted index)
+pub const INDEX_MAGIC: [u8; 4] = *b"FXI\0";
+
+/// 索引内容的格式版本 (Format version of the index payload)
+pub const INDEX_FORMAT_VERSION: u8 = 1;
+
+/// 计算文件名 HMAC 的索引密钥长度（单位：字节）(Length of the index key used to HMAC filenames)
+pub const INDEX_KEY_LEN: usize = 32;
+
+/// 批量加密时生成的恢复校验清单的文件名 (File name of the restore verification manifest)
+///
+/// 清单记录每个源文件明文的 SHA-256 哈希和大小，用于确认恢复结果；批量加密会跳过该文件。
+///
+/// *The manifest records each source file's plaintext SHA-256 and size so a restore can
+/// be checked later; batch encryption skips it.*
+pub const MANIFEST_FILE_NAME: &str = "ferox-manifest.json";
+
+/// 批量加密时写入每个处理过的目录的说明文件名 (File name of the directory readme)
+///
+/// 说明文件记录工具版本、格式版本、算法和解密方法，不包含任何机密；批量加密会跳过该文件。
+///
+/// *Describes the tool, format and algorithms used and how to decrypt; contains no secrets
+/// and is skipped by batch encryption.*
+pub const README_FILE_NAME: &str = "FEROX-ENCRYPTED.txt";
+
+/// 与 [`README_FILE_NAME`] 内容相同的机器可读说明文件名 (File name of the JSON directory readme)
+pub const README_JSON_FILE_NAME: &str = "FEROX-ENCRYPTED.json";
+
+/// 加密文件旁路元数据文件的后缀 (Suffix of sidecar metadata files)
+///
+/// `<名称>.feroxcrypt` 的旁路元数据保存为 `<名称>.feroxcrypt.meta.json`；
+/// 对应的加密文件被删除后，它就成了孤立文件。
+///
+/// *The sidecar of `<name>.feroxcrypt` is `<name>.feroxcrypt.meta.json`; it is orphaned once
+/// the encrypted file is deleted.*
+pub const SIDECAR_FILE_SUFFIX: &str = ".meta.json";
+
+/// 恢复校验清单的格式版本 (Format version of the restore verification manifest)
+pub const MANIFEST_FORMAT_VERSION: u32 = 1;
+
+/// 由审计密钥文件派生审计日志 HMAC 密钥时使用的上下文 (Context for deriving the audit log HMAC key)
+///
+/// 保证审计密钥与同一密钥文件用于加密时的派生值相互独立。
+///
+/// *Keeps the audit key independent of anything derived from the same keyfile for encryption.*
+pub const AUDIT_KEY_CONTEXT: &[u8] = b"ferox-encryptor audit log v1";
+
+/// 追加审计记录时从日志末尾读取的字节数 (Bytes read from the end of the audit log when appending)
+///
+/// 用于找到上一条记录的 HMAC；最后一行更长时会读取整个文件。
+pub const AUDIT_TAIL_LEN: u64 = 64 * 1024;
+
+/// 大型操作的阈值（单位：字节）(Size at which an operation counts as large)
+///
+/// 处理达到该大小的文件之前，会在调试级别输出 CPU 加密加速检测结果，
+/// 并在缺少 AES 硬件加速时发出警告。
+///
+/// *Before processing a file of at least this size, the CPU crypto acceleration report
+/// is logged at debug level, with a warning when AES hardware support is missing.*
+pub const LARGE_OPERATION_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// 稀疏文件的判定比例 (Ratio at which a file counts as sparse)
+///
+/// Unix 上文件实际分配的磁盘空间乘以该值仍小于表观大小时，视为稀疏文件。
+/// 加密会读取并写出全部表观大小，因此警告中会同时给出两个大小。
+///
+/// *A file whose allocated size times this ratio is still below its apparent size is
+/// reported as sparse, since encryption reads and writes the full apparent size.*
+pub const SPARSE_FILE_RATIO: u64 = 8;
+
+/// 禁用进度条的环境变量 (Environment variable that disables progress bars)
+///
+/// 设置为非空值时，即使标准错误连接到终端，也按非终端处理，不创建任何进度条。
+///
+/// *When set to a non-empty value, stderr is treated as not being a terminal and no
+/// progress bars are created.*
+pub const NO_PROGRESS_ENV_VAR: &str = "FEROX_NO_PROGRESS";
+
+/// 等待完成通知钩子结束的默认秒数 (Default completion hook timeout in seconds)
+///
+/// *Hooks still running after this many seconds are killed and reported as a warning.*
+pub const ON_COMPLETE_TIMEOUT_SECS: u64 = 30;
+
+/// 允许确定性加密的环境变量 (Environment variable that allows deterministic encryption)
+///
+/// 只有设置为 `1` 时，[`encrypt_deterministic`](crate::encrypt::encrypt_deterministic) 才会执行，
+/// `EncryptionOptions::test_nonces` 给定的固定盐和 IV 才会被使用。
+///
+/// *Deterministic encryption only runs when this is set to `1`; it is meant for tests only.*
+pub const ALLOW_DETERMINISTIC_ENV_VAR: &str = "FEROX_ALLOW_DETERMINISTIC";
+
+/// 额外的云同步目录列表的环境变量 (Environment variable listing additional sync roots)
+///
+/// 值的格式与 `PATH` 相同（Unix 上以 `:` 分隔，Windows 上以 `;` 分隔）。批量加密前的位置检查
+/// 把这些目录与内置识别的 Dropbox、OneDrive、Google Drive 和 iCloud 目录同等对待。
+///
+/// *Uses the platform `PATH` syntax; these directories are treated like the built-in sync roots.*
+pub const SYNC_ROOTS_ENV_VAR: &str = "FEROX_SYNC_ROOTS";
+
+/// 从硬件熵源读取密钥材料的超时时间（单位：秒）(Timeout for reading key material from a hardware entropy device)
+///
+/// 某些硬件随机数设备（如 `/dev/hwrng`）在熵不足时会阻塞。
+/// 超过该时间仍未读到足够的数据时，操作将失败而不是无限期等待。
+///
+/// *Some hardware RNG devices block when entropy is low. Reading fails instead of waiting forever.*
+pub const HARDWARE_ENTROPY_TIMEOUT_SECS: u64 = 5;
+
+/// 交互式模式记住的会话密码默认的闲置超时（单位：秒）(Default idle timeout of the remembered session password)
+///
+/// 超过该时间没有使用记住的密码时，它会被清除，下一次操作需要重新输入。
+///
+/// *A remembered password that has not been used for this long is cleared.*
+pub const SESSION_PASSWORD_IDLE_TIMEOUT_SECS: u64 = 5 * 60;
+
+/// 交互模式中批量加密预览等待目录扫描的最长时间（秒）。
+///
+/// 超过该时间仍未扫描完的目录树不再等待预览，扫描在后台继续，确认执行后使用它的结果。
+///
+/// *How long the interactive batch preview waits for the directory scan before giving up on
+/// showing counts; the scan keeps running and its result is used for the actual run.*
+pub const PREVIEW_SCAN_TIMEOUT_SECS: u64 = 15;
+
+/// 收到终止信号后清理工作的最长时间（单位：秒）(Upper bound for cleanup after a termination signal)
+///
+/// 清理（删除不完整的输出、执行已登记的终止钩子）超过该时间仍未完成时，进程直接退出，
+/// 避免拖延服务管理器的停止流程（systemd 默认 90 秒后发送 `SIGKILL`）。
+///
+/// *Cleanup after SIGINT/SIGTERM/SIGHUP or a console close event may take at most this long.*
+pub const TERMINATION_CLEANUP_TIMEOUT_SECS: u64 = 5;
+
+/// 流式处理停滞警告的默认间隔（单位：秒）(Default interval before a stalled stream is reported)
+///
+/// 数据流处理期间超过该时间没有任何读写完成时，发出一次停滞警告，指出当前文件、偏移量和
+/// 卡住的读写操作（常见于失去响应的网络挂载）。密钥派生期间不计时。
+///
+/// *A streaming file that makes no read or write progress for this long is reported as
+/// stalled. Key derivation is never counted.*
+pub const STALL_WARNING_SECS: u64 = 60;
+
+/// 估算 Argon2 耗时所用的每 KiB 每轮耗时（单位：纳秒）(Nanoseconds per KiB and pass used to estimate Argon2 time)
+///
+/// 只用于在密钥派生开始时给出预计耗时的提示，按 `m_cost × t_cost` 线性估算；
+/// 实际耗时取决于 CPU 和内存带宽。
+///
+/// *Only used for the expected-duration hint shown when key derivation starts.*
+pub const ARGON2_NANOS_PER_KIB_PASS: u64 = 1_000;
+
+// --- 常量一致性检查 (Constant Coherence) ---
+
+// 编译期检查：密钥和标签长度必须与所用的算法一致，修改其中一个常量而不修改相关常量时无法编译。
+const _: () = assert!(MASTER_KEY_LEN == 2 * AES_KEY_LEN);
+const _: () = assert!(TAG_LEN == 32);
+const _: () = assert!(MIN_KEYFILE_SIZE <= MAX_KEYFILE_SIZE);
+const _: () = assert!(KEYFILE_DERIVED_LEN <= MASTER_KEY_LEN);
+
+/// 在运行时检查常量之间的依赖关系 (Validate the coherence of cryptographic constants)
+///
+/// 与上面的编译期检查相同，但返回描述具体约束的错误，供程序启动时自检和测试使用：
+///
+/// - 主密钥由 AES 密钥和 HMAC 密钥组成：`MASTER_KEY_LEN == 2 * AES_KEY_LEN`
+/// - HMAC-SHA256 的输出为 32 字节：`TAG_LEN == 32`
+/// - 密钥文件的大小范围有效：`MIN_KEYFILE_SIZE <= MAX_KEYFILE_SIZE`
+/// - 密钥文件派生的材料不超过主密钥：`KEYFILE_DERIVED_LEN <= MASTER_KEY_LEN`
+///
+/// *Runtime counterpart of the compile-time assertions on key, tag and keyfile lengths.*
+pub fn validate_constants() -> anyhow::Result<()> {
+    anyhow::ensure!(
+        MASTER_KEY_LEN == 2 * AES_KEY_LEN,
+        "MASTER_KEY_LEN ({MASTER_KEY_LEN}) 必须是 AES_KEY_LEN ({AES_KEY_LEN}) 的两倍：主密钥由 AES 密钥和 HMAC 密钥组成"
+    );
+    anyhow::ensure!(
+        TAG_LEN == 32,
+        "TAG_LEN ({TAG_LEN}) 必须等于 HMAC-SHA256 的输出长度 32"
+    );
+    anyhow::ensure!(
+        MIN_KEYFILE_SIZE <= MAX_KEYFILE_SIZE,
+        "MIN_KEYFILE_SIZE ({MIN_KEYFILE_SIZE}) 不能大于 MAX_KEYFILE_SIZE ({MAX_KEYFILE_SIZE})"
+    );
+    anyhow::ensure!(
+        KEYFILE_DERIVED_LEN <= MASTER_KEY_LEN,
+        "KEYFILE_DERIVED_LEN ({KEYFILE_DERIVED_LEN}) 不能大于 MASTER_KEY_LEN ({MASTER_KEY_LEN})"
+    );
+    Ok(())
+}
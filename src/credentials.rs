@@ -0,0 +1,346 @@
+// src/credentials.rs
+
+//! # 凭据表模块 (Credentials Map)
+//!
+//! 多年积累下来的归档里，不同的文件往往用不同的密码（或密钥文件）加密。批量解密时设置
+//! [`BatchConfig::credentials`](crate::batch::BatchConfig::credentials) 后，每个文件依次尝试
+//! 与其路径匹配的凭据，第一个通过认证的凭据即用于该文件，整个目录可以一次解密完成。
+//!
+//! 凭据表可以是 TOML 或 JSON 文件（按扩展名 `.json` 区分，其他扩展名按 TOML 解析）:
+//!
+//! ```toml
+//! # 对所有文件按顺序尝试的密码
+//! passwords = ["old-password", "new-password"]
+//!
+//! [[credentials]]
+//! name = "2019 归档"
+//! password = "archive-2019"
+//! keyfile = "keys/2019.key"      # 相对于凭据表所在的目录
+//! files = ["2019/**", "*.2019.*"]
+//! ```
+//!
+//! JSON 使用相同的结构，也可以只是一个按顺序尝试的密码数组 (`["a", "b"]`)。
+//!
+//! 对每个文件，先按文件中的顺序尝试 `files` 模式与之匹配的条目，再尝试没有 `files` 的条目，
+//! 最后是 `passwords` 列表。模式与相对于被解密目录的路径、完整路径或文件名匹配。
+//!
+//! *A credentials map lets one batch decryption handle files encrypted under different
+//! passwords or keyfiles: entries whose glob patterns match a file are tried first, then
+//! catch-all entries, in file order. Maps are TOML, or JSON when the extension is `.json`.*
+
+use crate::keyfile::KeyFile;
+use anyhow::{Context, Result};
+use glob::Pattern;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// 凭据表中的一条凭据：密码、可选的密钥文件以及适用的文件模式。
+pub struct Credential {
+    /// 在日志和错误信息中显示的名称（不会显示密码）。
+    name: String,
+    /// 密码。
+    password: Zeroizing<String>,
+    /// (可选) 与密码一起使用的密钥文件。
+    keyfile: Option<KeyFile>,
+    /// 适用的文件；为空时适用于所有文件。
+    patterns: Vec<Pattern>,
+}
+
+impl Credential {
+    /// 凭据的名称。
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 凭据的密码。
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    /// 凭据的密钥文件。
+    pub fn keyfile(&self) -> Option<&KeyFile> {
+        self.keyfile.as_ref()
+    }
+
+    /// 是否只适用于部分文件（设置了文件模式）。
+    pub fn is_targeted(&self) -> bool {
+        !self.patterns.is_empty()
+    }
+
+    /// 文件模式是否与 `file` 匹配；`root` 是被解密的目录。
+    fn matches(&self, file: &Path, root: Option<&Path>) -> bool {
+        let relative = root
+            .and_then(|root| file.strip_prefix(root).ok())
+            .unwrap_or(file);
+        let name = file.file_name().map(Path::new);
+        self.patterns.iter().any(|pattern| {
+            pattern.matches_path(relative)
+                || pattern.matches_path(file)
+                || name.is_some_and(|name| pattern.matches_path(name))
+        })
+    }
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credential")
+            .field("name", &self.name)
+            .field("keyfile", &self.keyfile.is_some())
+            .field("patterns", &self.patterns)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 批量解密时逐个文件尝试的一组凭据，见[模块文档](self)。
+#[derive(Debug, Default)]
+pub struct Credentials {
+    entries: Vec<Credential>,
+}
+
+/// 凭据表文件的内容。
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CredentialsFile {
+    /// 只有一个按顺序尝试的密码数组（仅 JSON）。
+    Passwords(Vec<String>),
+    /// 完整的凭据表。
+    Table(CredentialsTable),
+}
+
+/// 完整的凭据表。
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CredentialsTable {
+    /// 带名称、密钥文件或文件模式的凭据。
+    #[serde(default)]
+    credentials: Vec<CredentialEntry>,
+    /// 对所有文件按顺序尝试的密码。
+    #[serde(default)]
+    passwords: Vec<String>,
+}
+
+/// 凭据表中的一个 `[[credentials]]` 条目。
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CredentialEntry {
+    name: Option<String>,
+    password: String,
+    keyfile: Option<PathBuf>,
+    #[serde(default)]
+    files: Vec<String>,
+}
+
+impl Credentials {
+    /// 创建空的凭据表。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 用一组对所有文件按顺序尝试的密码创建凭据表。
+    pub fn from_passwords<I, S>(passwords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut credentials = Self::new();
+        for password in passwords {
+            let name = format!("密码 #{}", credentials.entries.len() + 1);
+            credentials.entries.push(Credential {
+                name,
+                password: Zeroizing::new(password.into()),
+                keyfile: None,
+                patterns: Vec::new(),
+            });
+        }
+        credentials
+    }
+
+    /// 添加一条凭据；`files` 为空时适用于所有文件。
+    ///
+    /// # 错误
+    ///
+    /// `files` 中有无效的 glob 模式时返回错误。
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        password: impl Into<String>,
+        keyfile: Option<KeyFile>,
+        files: &[&str],
+    ) -> Result<()> {
+        let name = name.into();
+        let patterns = files
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .with_context(|| format!("凭据 {name} 中的文件模式无效: {pattern}"))
+            })
+            .collect::<Result<_>>()?;
+        self.entries.push(Credential {
+            name,
+            password: Zeroizing::new(password.into()),
+            keyfile,
+            patterns,
+        });
+        Ok(())
+    }
+
+    /// 从 TOML 或 JSON 文件加载凭据表（扩展名为 `.json` 时按 JSON 解析）。
+    ///
+    /// 密钥文件的相对路径相对于凭据表所在的目录。
+    ///
+    /// # 错误
+    ///
+    /// 文件无法读取或解析、模式无效或密钥文件无法加载时返回错误。
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = Zeroizing::new(
+            fs::read_to_string(path)
+                .with_context(|| format!("无法读取凭据表: {}", path.display()))?,
+        );
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let credentials = if is_json {
+            Self::from_json_str(&text, base_dir)
+        } else {
+            Self::from_toml_str(&text, base_dir)
+        }
+        .with_context(|| format!("无效的凭据表: {}", path.display()))?;
+        log::info!(
+            "已加载凭据表 {} ({} 条凭据)",
+            path.display(),
+            credentials.len()
+        );
+        Ok(credentials)
+    }
+
+    /// 解析 TOML 格式的凭据表，密钥文件的相对路径相对于 `base_dir`。
+    pub fn from_toml_str(text: &str, base_dir: &Path) -> Result<Self> {
+        let table: CredentialsTable = toml::from_str(text).context("无法解析 TOML")?;
+        Self::from_file(CredentialsFile::Table(table), base_dir)
+    }
+
+    /// 解析 JSON 格式的凭据表，密钥文件的相对路径相对于 `base_dir`。
+    pub fn from_json_str(text: &str, base_dir: &Path) -> Result<Self> {
+        let file: CredentialsFile = serde_json::from_str(text).context("无法解析 JSON")?;
+        Self::from_file(file, base_dir)
+    }
+
+    fn from_file(file: CredentialsFile, base_dir: &Path) -> Result<Self> {
+        let table = match file {
+            CredentialsFile::Passwords(passwords) => CredentialsTable {
+                credentials: Vec::new(),
+                passwords,
+            },
+            CredentialsFile::Table(table) => table,
+        };
+        let mut credentials = Self::new();
+        for (index, entry) in table.credentials.into_iter().enumerate() {
+            let name = entry.name.unwrap_or_else(|| format!("凭据 #{}", index + 1));
+            let keyfile = entry
+                .keyfile
+                .map(|keyfile| {
+                    let keyfile = base_dir.join(keyfile);
+                    KeyFile::load_from_file(&keyfile).with_context(|| {
+                        format!("凭据 {name} 的密钥文件无法加载: {}", keyfile.display())
+                    })
+                })
+                .transpose()?;
+            let files: Vec<&str> = entry.files.iter().map(String::as_str).collect();
+            credentials.add(name, entry.password, keyfile, &files)?;
+        }
+        let offset = credentials.entries.len();
+        for (index, password) in table.passwords.into_iter().enumerate() {
+            credentials.entries.push(Credential {
+                name: format!("密码 #{}", offset + index + 1),
+                password: Zeroizing::new(password),
+                keyfile: None,
+                patterns: Vec::new(),
+            });
+        }
+        Ok(credentials)
+    }
+
+    /// 凭据的数量。
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 凭据表是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 解密 `file` 时依次尝试的凭据；`root` 是被解密的目录（解密文件列表时为 `None`）。
+    ///
+    /// 先是文件模式与 `file` 匹配的凭据，再是适用于所有文件的凭据，各自保持凭据表中的顺序。
+    pub fn candidates<'a>(&'a self, file: &Path, root: Option<&Path>) -> Vec<&'a Credential> {
+        let targeted = self
+            .entries
+            .iter()
+            .filter(|entry| entry.is_targeted() && entry.matches(file, root));
+        let general = self.entries.iter().filter(|entry| !entry.is_targeted());
+        targeted.chain(general).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_prefer_matching_patterns() -> Result<()> {
+        let credentials = Credentials::from_toml_str(
+            r#"
+            passwords = ["fallback"]
+
+            [[credentials]]
+            name = "2019"
+            password = "p2019"
+            files = ["2019/**"]
+
+            [[credentials]]
+            password = "reports"
+            files = ["*.pdf.feroxcrypt"]
+            "#,
+            Path::new(""),
+        )?;
+        assert_eq!(credentials.len(), 3);
+
+        let names = |file: &str| -> Vec<String> {
+            credentials
+                .candidates(Path::new(file), Some(Path::new("/archive")))
+                .iter()
+                .map(|credential| credential.name().to_string())
+                .collect()
+        };
+        assert_eq!(names("/archive/2019/a.txt.feroxcrypt"), ["2019", "密码 #3"]);
+        assert_eq!(names("/archive/x/b.pdf.feroxcrypt"), ["凭据 #2", "密码 #3"]);
+        assert_eq!(names("/archive/c.txt.feroxcrypt"), ["密码 #3"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_password_list_and_errors() -> Result<()> {
+        let credentials = Credentials::from_json_str(r#"["a", "b"]"#, Path::new(""))?;
+        let passwords: Vec<&str> = credentials
+            .candidates(Path::new("x.feroxcrypt"), None)
+            .iter()
+            .map(|credential| credential.password())
+            .collect();
+        assert_eq!(passwords, ["a", "b"]);
+
+        assert!(Credentials::from_toml_str("unknown = 1", Path::new("")).is_err());
+        assert!(Credentials::from_json_str(
+            r#"{"credentials": [{"password": "x", "files": ["[" ]}]}"#,
+            Path::new("")
+        )
+        .is_err());
+        assert!(!format!("{credentials:?}").contains("\"a\""));
+        Ok(())
+    }
+}
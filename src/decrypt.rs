@@ -6,29 +6,262 @@
 //! 读取文件头 -> 派生密钥 -> 流式解密和认证 -> 最终验证。
 
 use crate::{
+    capabilities::report_before_large_operation,
+    cleanup::{cleanup_partial_output, commit_partial_file, create_staging_file},
     constants::{
-        AES_KEY_LEN, BUFFER_LEN, CUSTOM_FILE_EXTENSION, IV_LEN, MASTER_KEY_LEN, SALT_LEN, TAG_LEN,
+        AES_KEY_LEN, BUFFER_LEN, CUSTOM_FILE_EXTENSION, MASTER_KEY_LEN, TAG_LEN, UNVERIFIED_SUFFIX,
     },
-    keyfile::{combine_password_and_keyfile, KeyFile},
+    encrypt::ensure_not_special_file,
+    error::{warn_or_fail, FeroxError, Stage, StageContext},
+    format::FileHeader,
+    inspect::expiry_from_unix,
+    keyfile::{combine_password_and_keyfile, keyfile_commitment_matches, KeyFile},
+    permissions,
+    pipeline::{
+        ensure_valid_buffer_size, process_stream, read_stream, ChunkSettings, CipherDirection,
+    },
+    progress::{with_terminal_progress, ProgressEvent, ProgressSink},
+    secure_memory::{self, SecureBuffer},
+    stream_io::{ByteCounts, Counting, InputFile, OutputFile},
+    watchdog::{self, default_stall_warning, StallSettings},
+    xattrs, Level,
 };
 use anyhow::{anyhow, bail, Context, Result};
 use argon2::{self, Argon2, Params};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use ctr::cipher::{KeyIvInit, StreamCipher};
 use hmac::{Hmac, Mac};
-use indicatif::{ProgressBar, ProgressStyle};
 use sha2::Sha256;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use zeroize::Zeroize;
+use std::time::{Duration, SystemTime};
+use unicode_normalization::UnicodeNormalization;
+use zeroize::{Zeroize, Zeroizing};
 
 // 定义密码学算法的类型别名
 type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
 type HmacSha256 = Hmac<Sha256>;
 
+/// # 覆盖模式 (Overwrite Mode)
+///
+/// 解密的目标文件（由文件头中的原始文件名决定）已经存在时的处理方式。
+///
+/// *What to do when the decrypted file's target path already exists.*
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverwriteMode {
+    /// 中止并返回错误 (默认)。
+    #[default]
+    Fail,
+    /// 跳过该文件，不派生密钥也不写入任何数据。
+    Skip,
+    /// 认证通过后以原子重命名替换已存在的文件。
+    Overwrite,
+    /// 保留已存在的文件，把结果写入第一个未被占用的 `名称 (n).扩展名`。
+    Rename,
+}
+
+/// # 文件名规范化 (Name Normalization)
+///
+/// 恢复文件名时使用的 Unicode 规范化形式。
+///
+/// 文件头按原样记录加密时文件名的 UTF-8 字节：在 macOS 上加密的文件名通常是分解形式 (NFD)，
+/// 在 Linux 上解密后得到外观相同但字节不同的名称，反之亦然。
+///
+/// *Unicode normalization applied to the restored filename; the header keeps the original bytes.*
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameNormalization {
+    /// 与文件头中记录的字节完全相同 (默认)。
+    #[default]
+    None,
+    /// 组合形式 (NFC)，Linux 和 Windows 上的常见形式。
+    Nfc,
+    /// 分解形式 (NFD)，HFS+ 使用的形式。
+    Nfd,
+}
+
+impl NameNormalization {
+    /// 按该形式规范化文件名。
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            Self::None => name.to_string(),
+            Self::Nfc => name.nfc().collect(),
+            Self::Nfd => name.nfd().collect(),
+        }
+    }
+}
+
+/// # 输出权限 (Output Permissions)
+///
+/// 解密出的文件在原子重命名之后设置的 Unix 权限位；解密期间的 `.part` 文件始终为 `0600`。
+/// 非 Unix 平台上不起作用。
+///
+/// *Unix permission bits set on the decrypted file after the atomic rename.*
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputPermissions {
+    /// 使用加密时记录在文件头中的权限位（见
+    /// [`EncryptionOptions::preserve_xattrs`](crate::encrypt::EncryptionOptions::preserve_xattrs)），
+    /// 没有记录时与 [`ProcessUmask`](Self::ProcessUmask) 相同。
+    FromHeader,
+    /// 固定的权限位（例如 `0o600`），setuid、setgid 和粘滞位会被忽略。
+    Fixed(u32),
+    /// 与新建文件相同：`0666` 去掉当前进程 umask 中的位 (默认)。
+    #[default]
+    ProcessUmask,
+}
+
+/// 单个文件解密流程的结果。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DecryptionOutcome {
+    /// 文件已解密并写入最终路径（[`OverwriteMode::Rename`] 时可能与原始文件名不同）。
+    Decrypted {
+        /// 输出文件的最终路径。
+        path: PathBuf,
+        /// 按 [`DecryptionOptions::permissions`] 设置的权限位；非 Unix 平台上为 `None`。
+        mode: Option<u32>,
+        /// 文件头中的安全级别；自定义 Argon2 参数时为 `None`。
+        level: Option<Level>,
+        /// 实际读取的加密文件字节数（包括文件头和认证标签）和写入的明文字节数。
+        counts: ByteCounts,
+        /// 文件头中本版本不认识、已被忽略的非关键扩展类型，见 [`FileHeader::ignored_extensions`]。
+        ignored_extensions: Vec<u16>,
+    },
+    /// 目标文件已存在，按 [`OverwriteMode::Skip`] 跳过了该文件。
+    Skipped,
+}
+
+/// # 解密选项 (Decryption Options)
+///
+/// 汇总一次解密操作所需的凭据，供库调用者使用。
+///
+/// *Bundles the credentials needed for one decryption operation.*
+#[derive(Clone, Copy)]
+pub struct DecryptionOptions<'a> {
+    /// 用于解密的密码。
+    pub password: &'a str,
+    /// (可选) 加密时使用的密钥文件。
+    pub keyfile: Option<&'a KeyFile>,
+    /// 是否在单个线程中串行执行认证和解密（默认在独立线程上以流水线方式执行）。
+    pub single_thread: bool,
+    /// 并行解密单个文件的线程数（默认为 1，即不拆分文件）。
+    ///
+    /// 大于 1 时，文件被拆分为多个数据块由多个线程并行解密；`single_thread` 优先。
+    pub threads: usize,
+    /// 读写缓冲区和数据块的大小（字节，默认为 [`BUFFER_LEN`]）。
+    ///
+    /// 必须在 [`MIN_BUFFER_SIZE`] 和 [`MAX_BUFFER_SIZE`] 之间。
+    pub buffer_size: usize,
+    /// 是否把密码材料和主密钥锁定在物理内存中，防止被换出到磁盘（默认关闭）。
+    ///
+    /// 锁定失败（例如 `RLIMIT_MEMLOCK` 太低）时发出警告并继续解密，见 [`SecureBuffer`]。
+    pub lock_memory: bool,
+    /// 目标文件已存在时的处理方式（默认为 [`OverwriteMode::Fail`]）。
+    ///
+    /// 只适用于由文件头中的原始文件名决定目标路径的解密流程。
+    pub overwrite_mode: OverwriteMode,
+    /// 是否把文件头中记录的扩展属性恢复到解密出的文件上（默认关闭）。
+    ///
+    /// 在认证通过之后才设置；无法设置的属性只发出警告。
+    pub preserve_xattrs: bool,
+    /// 解密出的文件的权限（默认为 [`OutputPermissions::ProcessUmask`]）。
+    ///
+    /// 只适用于写入文件的解密流程，在原子重命名之后设置。
+    pub permissions: OutputPermissions,
+    /// 恢复文件名时使用的 Unicode 规范化形式（默认为 [`NameNormalization::None`]，保持原样）。
+    ///
+    /// 只适用于由文件头中的原始文件名决定目标路径的解密流程。
+    pub normalize_names: NameNormalization,
+    /// (可选) 无法在目标文件旁边创建 `.part` 临时文件时改用的临时目录，见
+    /// [`EncryptionOptions::temp_dir`](crate::encrypt::EncryptionOptions::temp_dir)。
+    pub temp_dir: Option<&'a Path>,
+    /// (可选) 解密结果写入的目录（默认为加密文件所在的目录）。
+    ///
+    /// 只适用于由文件头中的原始文件名决定目标路径的解密流程；目录必须已经存在。
+    pub output_dir: Option<&'a Path>,
+    /// 没有任何读写进展多久之后发出停滞警告，见
+    /// [`EncryptionOptions::stall_warning`](crate::encrypt::EncryptionOptions::stall_warning)。
+    pub stall_warning: Duration,
+    /// (可选) 没有任何读写进展多久之后放弃该文件，见
+    /// [`EncryptionOptions::stall_timeout`](crate::encrypt::EncryptionOptions::stall_timeout)。
+    pub stall_timeout: Option<Duration>,
+    /// 严格模式：把默认只发出警告的问题当作错误（默认关闭）。
+    ///
+    /// 适用于不应恢复或无法恢复的扩展属性、当前平台无法设置的 `--chmod` 权限、`lock_memory`
+    /// 时无法锁定内存，以及文件头中本版本不认识的非关键扩展，返回 [`FeroxError::StrictViolation`]。
+    pub strict: bool,
+}
+
+impl<'a> DecryptionOptions<'a> {
+    /// 使用给定密码创建解密选项（不使用密钥文件）。
+    pub fn new(password: &'a str) -> Self {
+        Self {
+            password,
+            keyfile: None,
+            single_thread: false,
+            threads: 1,
+            buffer_size: BUFFER_LEN,
+            lock_memory: false,
+            overwrite_mode: OverwriteMode::Fail,
+            preserve_xattrs: false,
+            permissions: OutputPermissions::ProcessUmask,
+            normalize_names: NameNormalization::None,
+            temp_dir: None,
+            output_dir: None,
+            stall_warning: default_stall_warning(),
+            stall_timeout: None,
+            strict: false,
+        }
+    }
+}
+
+impl DecryptionOptions<'_> {
+    /// 数据块的处理方式。
+    pub(crate) fn chunk_settings(&self) -> ChunkSettings {
+        ChunkSettings {
+            buffer_size: self.buffer_size,
+            single_thread: self.single_thread,
+            threads: self.threads,
+        }
+    }
+
+    /// 停滞检测的设置。
+    pub(crate) fn stall_settings(&self) -> StallSettings {
+        StallSettings {
+            warning: self.stall_warning,
+            timeout: self.stall_timeout,
+        }
+    }
+}
+
+impl fmt::Debug for DecryptionOptions<'_> {
+    /// 调试输出中绝不包含密码或密钥文件内容。
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecryptionOptions")
+            .field("password", &"<redacted>")
+            .field("keyfile", &self.keyfile.is_some())
+            .field("single_thread", &self.single_thread)
+            .field("threads", &self.threads)
+            .field("buffer_size", &self.buffer_size)
+            .field("lock_memory", &self.lock_memory)
+            .field("overwrite_mode", &self.overwrite_mode)
+            .field("preserve_xattrs", &self.preserve_xattrs)
+            .field("permissions", &self.permissions)
+            .field("normalize_names", &self.normalize_names)
+            .field("stall_warning", &self.stall_warning)
+            .field("stall_timeout", &self.stall_timeout)
+            .field("strict", &self.strict)
+            .finish()
+    }
+}
+
 /// 执行完整的文件解密流程。
 ///
+/// 输出文件保存在源文件所在的目录中，文件名取自文件头中记录的原始文件名；
+/// 目标文件已存在时会中止操作。需要指定输出路径时，请使用
+/// [`run_decryption_flow_no_overwrite_check`]。
+///
 /// # 参数
 ///
 /// * `source_path` - 要解密的 `.feroxcrypt` 文件的路径。
@@ -39,197 +272,924 @@ type HmacSha256 = Hmac<Sha256>;
 /// # 返回
 ///
 /// `Ok(())` 表示成功，否则返回一个描述错误的 `anyhow::Error`。
+///
+/// 启用 `tracing` 特性时，整个流程记录在 `run_decryption_flow` span 中，
+/// 密钥派生和数据流处理分别是它的子 span，失败时记录错误事件。
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        err,
+        skip(password, keyfile, temp_file_path),
+        fields(source_path = %source_path.display())
+    )
+)]
 pub fn run_decryption_flow(
     source_path: &Path,
     password: &str,
     keyfile: Option<&KeyFile>,
     temp_file_path: Arc<Mutex<Option<PathBuf>>>,
 ) -> Result<()> {
+    let opts = DecryptionOptions {
+        keyfile,
+        ..DecryptionOptions::new(password)
+    };
+    with_terminal_progress(source_path, |progress| {
+        run_decryption_flow_with_progress(source_path, &opts, temp_file_path, progress).map(|_| ())
+    })
+}
+
+/// 执行文件解密流程，并将进度报告给可选的 [`ProgressSink`]。
+///
+/// 已处理的字节数以 [`ProgressEvent::BytesProcessed`] 事件发送给接收器；
+/// `progress` 为 `None` 时不报告进度，也不创建任何进度对象。
+/// 目标文件已存在时按 `opts.overwrite_mode` 处理。
+pub(crate) fn run_decryption_flow_with_progress(
+    source_path: &Path,
+    opts: &DecryptionOptions,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<DecryptionOutcome> {
+    run_decryption(source_path, None, opts, temp_file_path, progress)
+}
+
+/// 把加密文件解密到调用者指定的路径，不检查目标文件是否已存在。
+///
+/// 与 [`run_decryption_flow`] 不同，输出路径不由文件头中的原始文件名决定，
+/// 并且已存在的目标文件会在认证通过后被替换。调用者负责确认覆盖是安全的
+/// （例如已经预先检查或预先分配了输出位置）。
+///
+/// # 参数
+///
+/// * `source_path` - 要解密的 `.feroxcrypt` 文件的路径。
+/// * `password` - 用于解密的密码。
+/// * `keyfile` - (可选) 用于解密的密钥文件。
+/// * `temp_file_path` - 线程安全的共享变量，用于在中断时记录临时文件名以供清理。
+/// * `target_path` - 解密输出的完整路径。
+pub fn run_decryption_flow_no_overwrite_check(
+    source_path: &Path,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    target_path: &Path,
+) -> Result<()> {
+    let opts = DecryptionOptions {
+        keyfile,
+        ..DecryptionOptions::new(password)
+    };
+    with_terminal_progress(source_path, |progress| {
+        run_decryption_flow_to_path(source_path, target_path, &opts, temp_file_path, progress)
+    })
+}
+
+/// 把加密文件解密到指定路径，跳过目标文件是否存在的检查（由调用者负责）。
+pub(crate) fn run_decryption_flow_to_path(
+    source_path: &Path,
+    target_path: &Path,
+    opts: &DecryptionOptions,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<()> {
+    run_decryption(
+        source_path,
+        Some(target_path),
+        opts,
+        temp_file_path,
+        progress,
+    )
+    .map(|_| ())
+}
+
+/// 解密流程的实现。
+///
+/// `explicit_target` 为 `None` 时，目标路径由文件头中的原始文件名决定，
+/// 已存在的目标文件按 `opts.overwrite_mode` 处理；为 `Some` 时直接使用给定路径，不做覆盖检查。
+/// 错误带有源文件路径和出错的 [`Stage`]。
+fn run_decryption(
+    source_path: &Path,
+    explicit_target: Option<&Path>,
+    opts: &DecryptionOptions,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<DecryptionOutcome> {
     // 同样使用闭包来包裹核心逻辑，以便统一处理清理操作
     let result = (|| {
-        // --- 1. 输入验证 ---
-        if !source_path.exists() {
-            bail!("文件不存在: {}", source_path.display());
-        }
-        if !source_path.is_file() {
-            bail!("提供的路径不是一个文件: {}", source_path.display());
-        }
-        // 验证文件扩展名是否正确
-        if source_path
-            .extension()
-            .is_none_or(|s| s != CUSTOM_FILE_EXTENSION)
-        {
-            bail!(
-                "文件看起来不是一个有效的加密文件 (必须以 .{} 结尾)",
-                CUSTOM_FILE_EXTENSION
+        let Some(prepared) =
+            prepare_decryption(source_path, explicit_target, opts, &temp_file_path)
+                .at_stage(Stage::HeaderParse)?
+        else {
+            return Ok(DecryptionOutcome::Skipped);
+        };
+        let PreparedDecryption {
+            reader,
+            header,
+            mut target_path,
+            check_overwrite,
+            part_path,
+            target_file,
+            ciphertext_size,
+        } = prepared;
+        let writer = OutputFile::create(target_file, ciphertext_size, opts.buffer_size);
+
+        // --- 5. 流式解密和认证 ---
+        // 停滞检测从第一次读写开始计时，不包括之前的密钥派生
+        let counts = watchdog::watch(source_path, opts.stall_settings(), progress, |watchdog| {
+            let mut reader = Counting::new(
+                watchdog
+                    .reader(reader, header.len())
+                    .at_stage(Stage::HeaderParse)?,
+            );
+            let mut writer = Counting::new(watchdog.writer(writer).at_stage(Stage::HeaderParse)?);
+            watchdog::announce_key_derivation(
+                source_path,
+                (header.m_cost, header.t_cost, header.p_cost),
+                progress,
             );
+            decrypt_body(
+                &mut reader,
+                &header,
+                ciphertext_size,
+                &mut writer,
+                opts,
+                &mut |bytes| {
+                    if let Some(sink) = progress {
+                        sink.on_event(&ProgressEvent::BytesProcessed { bytes });
+                    }
+                },
+            )?;
+
+            // 验证成功，刷新缓冲区并关闭文件
+            writer
+                .flush()
+                .context("刷新文件缓冲区失败")
+                .at_stage(Stage::Finalize)?;
+            // 文件头在准备阶段已经读取
+            let counts = ByteCounts {
+                read: header.len() + reader.count(),
+                written: writer.count(),
+            };
+            drop(writer);
+            anyhow::Ok(counts)
+        })?;
+
+        // 关闭文件之后再原子地重命名为目标文件
+        // （`Overwrite` 模式下重命名会原子地替换已存在的文件）
+        (|| {
+            if opts.preserve_xattrs {
+                xattrs::restore(&part_path, &header.xattrs(), opts.strict)?;
+            }
+            if check_overwrite && target_path.exists() {
+                // 目标文件在解密期间出现：`Skip` 模式丢弃已解密的输出，`Rename` 模式另选名称
+                match opts.overwrite_mode {
+                    OverwriteMode::Skip => {
+                        log::info!("目标文件 {} 已存在，已跳过。", target_path.display());
+                        return Ok(DecryptionOutcome::Skipped);
+                    }
+                    OverwriteMode::Rename => target_path = unused_path(&target_path),
+                    _ => bail!(
+                        "目标文件 {} 已存在，为防止数据覆盖，操作已中止。",
+                        target_path.display()
+                    ),
+                }
+            }
+            commit_partial_file(&part_path, &target_path)?;
+            let mode = permissions::apply(
+                &target_path,
+                opts.permissions,
+                header.file_mode(),
+                opts.strict,
+            )
+            .with_context(|| format!("无法设置 {} 的权限", target_path.display()))?;
+            warn_if_name_transformed(&target_path);
+            log::info!("--- ✅ 验证成功，解密完成! ---");
+            Ok(DecryptionOutcome::Decrypted {
+                path: target_path,
+                mode,
+                level: header.security_level(),
+                counts,
+                ignored_extensions: header.ignored_extensions(),
+            })
+        })()
+        .at_stage(Stage::Finalize)
+    })()
+    .with_context(|| format!("无法解密 {}", source_path.display()));
+
+    // 失败或跳过时删除不完整的明文输出；无论结果如何，都清理共享状态
+    if !matches!(result, Ok(DecryptionOutcome::Decrypted { .. })) {
+        cleanup_partial_output(&temp_file_path);
+    }
+    *temp_file_path
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+
+    result
+}
+
+/// 已读取文件头、准备好临时输出文件，等待流式解密的状态。
+struct PreparedDecryption {
+    reader: InputFile,
+    header: FileHeader,
+    target_path: PathBuf,
+    check_overwrite: bool,
+    part_path: PathBuf,
+    target_file: File,
+    ciphertext_size: u64,
+}
+
+/// 解密流程的文件头阶段：验证输入、读取文件头、确定目标路径并创建临时输出文件。
+///
+/// 目标文件已存在并且 `opts.overwrite_mode` 为 [`OverwriteMode::Skip`] 时返回 `None`。
+fn prepare_decryption(
+    source_path: &Path,
+    explicit_target: Option<&Path>,
+    opts: &DecryptionOptions,
+    temp_file_path: &Mutex<Option<PathBuf>>,
+) -> Result<Option<PreparedDecryption>> {
+    // --- 1. 输入验证 ---
+    if !source_path.exists() {
+        bail!("文件不存在: {}", source_path.display());
+    }
+    ensure_not_special_file(source_path)?;
+    if !source_path.is_file() {
+        bail!("提供的路径不是一个文件: {}", source_path.display());
+    }
+    // 验证文件扩展名是否正确
+    if source_path
+        .extension()
+        .is_none_or(|s| s != CUSTOM_FILE_EXTENSION)
+    {
+        bail!(
+            "文件看起来不是一个有效的加密文件 (必须以 .{} 结尾)",
+            CUSTOM_FILE_EXTENSION
+        );
+    }
+
+    // --- 2. 打开文件并读取文件头 ---
+    let source_file = File::open(source_path).context("无法打开源文件")?;
+    let file_size = source_file.metadata()?.len();
+    report_before_large_operation(file_size);
+    ensure_valid_buffer_size(opts.buffer_size)?;
+    let mut reader = InputFile::open(source_file, file_size, opts.buffer_size);
+    let header = read_header(&mut reader)?;
+    ensure_not_expired(&header)?;
+    warn_about_ignored_extensions(source_path, &header, opts.strict)?;
+
+    // --- 3. 准备目标路径 ---
+    let check_overwrite = explicit_target.is_none();
+    let mut target_path = match explicit_target {
+        Some(target) => target.to_path_buf(),
+        None => {
+            let parent_dir = match opts.output_dir {
+                Some(dir) => dir,
+                None => source_path.parent().context("无法获取父目录")?,
+            };
+            parent_dir.join(opts.normalize_names.apply(&header.original_filename))
         }
+    };
 
-        // --- 2. 打开文件并读取文件头 ---
-        let source_file = File::open(source_path).context("无法打开源文件")?;
-        let file_size = source_file.metadata()?.len();
-        let mut reader = BufReader::with_capacity(BUFFER_LEN, source_file);
-
-        // 读取原始文件名的长度 (2字节)
-        let mut filename_len_bytes = [0u8; 2];
-        reader
-            .read_exact(&mut filename_len_bytes)
-            .context("无法读取文件名长度")?;
-        let filename_len = u16::from_le_bytes(filename_len_bytes) as usize;
-
-        // 读取原始文件名
-        let mut filename_bytes = vec![0u8; filename_len];
-        reader
-            .read_exact(&mut filename_bytes)
-            .context("无法读取文件名")?;
-        let original_filename =
-            String::from_utf8(filename_bytes).context("文件名包含无效的UTF-8字符")?;
-
-        // --- 3. 准备目标路径 ---
-        let parent_dir = source_path.parent().context("无法获取父目录")?;
-        let target_path = parent_dir.join(original_filename);
-
-        // 防止意外覆盖现有文件
-        if target_path.exists() {
+    // 防止意外覆盖现有文件
+    let check_overwrite = check_overwrite && opts.overwrite_mode != OverwriteMode::Overwrite;
+    if check_overwrite && target_path.exists() {
+        if opts.overwrite_mode == OverwriteMode::Skip {
+            log::info!("目标文件 {} 已存在，已跳过。", target_path.display());
+            return Ok(None);
+        }
+        if opts.overwrite_mode == OverwriteMode::Rename {
+            target_path = unused_path(&target_path);
+        } else {
             bail!(
                 "目标文件 {} 已存在，为防止数据覆盖，操作已中止。",
                 target_path.display()
             );
         }
-        log::info!("解密后的文件将保存为: {}", target_path.display());
-
-        // 在开始写入前，将目标路径存入共享状态
-        *temp_file_path.lock().unwrap() = Some(target_path.clone());
-
-        // --- 4. 读取密码学元数据 ---
-        // 必须严格按照加密时写入的顺序来读取
-        let mut salt = [0u8; SALT_LEN];
-        reader.read_exact(&mut salt).context("无法读取Salt")?;
-        let mut iv = [0u8; IV_LEN];
-        reader.read_exact(&mut iv).context("无法读取IV")?;
-
-        // 读取 Argon2 参数
-        let mut m_cost_bytes = [0u8; 4];
-        let mut t_cost_bytes = [0u8; 4];
-        let mut p_cost_bytes = [0u8; 4];
-        reader
-            .read_exact(&mut m_cost_bytes)
-            .context("无法读取 Argon2 m_cost")?;
-        reader
-            .read_exact(&mut t_cost_bytes)
-            .context("无法读取 Argon2 t_cost")?;
-        reader
-            .read_exact(&mut p_cost_bytes)
-            .context("无法读取 Argon2 p_cost")?;
-
-        let m_cost = u32::from_le_bytes(m_cost_bytes);
-        let t_cost = u32::from_le_bytes(t_cost_bytes);
-        let p_cost = u32::from_le_bytes(p_cost_bytes);
-
-        // 使用从文件头读取的参数重新构建 Argon2 配置
-        let argon2_params = Params::new(m_cost, t_cost, p_cost, Some(MASTER_KEY_LEN))
-            .map_err(|e| anyhow!("从文件头创建 Argon2 参数失败: {}", e))?;
-
-        log::info!("文件使用的 Argon2 参数: m_cost={m_cost}, t_cost={t_cost}, p_cost={p_cost}");
-
-        // --- 5. 密钥派生 ---
-        log::info!("正在从密码派生密钥...");
-        let argon2 = Argon2::new(
-            argon2::Algorithm::Argon2id,
-            argon2::Version::V0x13,
-            argon2_params,
-        );
+    }
+    log::info!("解密后的文件将保存为: {}", target_path.display());
 
-        // 根据是否有密钥文件，准备密码材料
-        let mut password_material = if let Some(kf) = keyfile {
-            log::info!("使用密钥文件进行解密。");
-            combine_password_and_keyfile(password, kf)?
-        } else {
-            password.as_bytes().to_vec()
-        };
+    // --- 4. 计算密文大小并准备流式解密 ---
+    let ciphertext_size = ciphertext_len(file_size, &header)?;
 
-        // 使用与加密时完全相同的参数（密码材料、盐）来派生密钥
-        let mut master_key = [0u8; MASTER_KEY_LEN];
-        argon2
-            .hash_password_into(&password_material, &salt, &mut master_key)
-            .map_err(|e| anyhow!("Argon2密钥派生失败: {}", e))?;
+    // 明文先写入带锁的 `.part` 文件，只有认证通过后才会重命名为目标文件
+    let (part_path, target_file) = create_staging_file(&target_path, opts.temp_dir)?;
+    // 未经认证的明文只有所有者可以读取，最终权限在提交之后才设置
+    permissions::restrict_partial(&target_file).context("无法限制临时输出文件的权限")?;
+    // 拿到锁之后再登记，以便中断或认证失败时可以清理
+    *temp_file_path
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(part_path.clone());
+    Ok(Some(PreparedDecryption {
+        reader,
+        header,
+        target_path,
+        check_overwrite,
+        part_path,
+        target_file,
+        ciphertext_size,
+    }))
+}
 
-        password_material.zeroize();
-        log::info!("密钥派生完成。");
-
-        // --- 6. 初始化加密器和 MAC ---
-        let (aes_key, hmac_key) = master_key.split_at(AES_KEY_LEN);
-        let mut cipher = Aes256Ctr::new(aes_key.into(), &iv.into());
-        let mut mac = HmacSha256::new_from_slice(hmac_key).context("无法创建HMAC实例")?;
-
-        // --- 7. 计算密文大小并准备流式解密 ---
-        let header_size = (2 + filename_len + SALT_LEN + IV_LEN + 12) as u64; // +12 for Argon2 params
-        let ciphertext_size = file_size - header_size - TAG_LEN as u64;
-
-        log::info!("开始流式解密文件...");
-        let target_file = File::create(&target_path).context("无法创建目标文件")?;
-        let mut writer = BufWriter::with_capacity(BUFFER_LEN, target_file);
-
-        // 初始化进度条
-        let pb = ProgressBar::new(ciphertext_size);
-        pb.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
-            .progress_chars("#>-"));
-
-        // 使用 `take` 方法精确地只读取密文部分，不包括最后的认证标签
-        let mut ciphertext_reader = reader.take(ciphertext_size);
-        let mut buffer = vec![0u8; BUFFER_LEN];
-        loop {
-            let bytes_read = ciphertext_reader
-                .read(&mut buffer)
-                .context("读取密文失败")?;
-            if bytes_read == 0 {
-                break;
-            }
-            pb.inc(bytes_read as u64);
-            let chunk = &mut buffer[..bytes_read];
+/// 在内存中解密一段完整的 `.feroxcrypt` 格式数据。
+///
+/// # 返回
+///
+/// 一个元组 `(明文, 文件头中记录的原始文件名)`。
+/// 只有在认证标签验证通过后才会返回明文。
+pub fn decrypt_in_memory(data: &[u8], opts: &DecryptionOptions) -> Result<(Vec<u8>, String)> {
+    let mut reader = data;
+    let header = read_header(&mut reader)?;
+    ensure_not_expired(&header)?;
+    let ciphertext_size = ciphertext_len(data.len() as u64, &header)?;
+
+    // 预先分配全部容量，写入过程中不会重新分配而在旧内存中留下明文副本
+    let mut plaintext = Vec::with_capacity(ciphertext_size as usize);
+    let result = decrypt_body(
+        &mut reader,
+        &header,
+        ciphertext_size,
+        &mut plaintext,
+        opts,
+        &mut |_| {},
+    );
+    if let Err(e) = result {
+        // 认证失败时已经写入的明文未经验证，返回之前清零
+        plaintext.zeroize();
+        return Err(e);
+    }
+    Ok((plaintext, header.original_filename))
+}
+
+/// 用密码（和可选的密钥文件）在内存中解密一段 `.feroxcrypt` 格式数据，不涉及任何文件系统操作。
+///
+/// 适用于令牌、配置值、API 密钥之类的小型机密，是 [`decrypt_in_memory`] 使用默认选项的简化形式。
+///
+/// # 返回
+///
+/// 一个元组 `(明文, 文件头中记录的原始文件名)`。只有在认证标签验证通过后才会返回明文。
+pub fn decrypt_to_vec(
+    ciphertext: &[u8],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+) -> Result<(Vec<u8>, String)> {
+    let opts = DecryptionOptions {
+        keyfile,
+        ..DecryptionOptions::new(password)
+    };
+    decrypt_in_memory(ciphertext, &opts)
+}
+
+/// 与 [`decrypt_to_vec`] 相同，但只返回明文，不返回文件头中的原始文件名。
+pub fn decrypt_to_writer_vec(
+    ciphertext: &[u8],
+    password: &str,
+    keyfile: Option<&KeyFile>,
+) -> Result<Vec<u8>> {
+    decrypt_to_vec(ciphertext, password, keyfile).map(|(plaintext, _)| plaintext)
+}
+
+/// 解码由 [`encrypt_file_to_base64`](crate::encrypt::encrypt_file_to_base64) 生成的 Base64 字符串，
+/// 解密后将明文写入 `output_dir` 下的原始文件名。
+///
+/// 为防止数据覆盖，如果目标文件已存在则返回错误。
+///
+/// # 返回
+///
+/// 写入的明文文件路径。
+pub fn decrypt_base64_to_file(
+    b64: &str,
+    output_dir: &Path,
+    opts: &DecryptionOptions,
+) -> Result<PathBuf> {
+    if !output_dir.is_dir() {
+        bail!("提供的路径不是一个目录: {}", output_dir.display());
+    }
+    let data = URL_SAFE_NO_PAD
+        .decode(b64.trim())
+        .context("无效的 Base64 数据")?;
+    let (mut plaintext, original_filename) = decrypt_in_memory(&data, opts)?;
+    let original_filename = opts.normalize_names.apply(&original_filename);
+
+    // 文件头中的文件名必须是一个单纯的文件名，不能包含路径成分
+    if Path::new(&original_filename).file_name() != Some(original_filename.as_ref()) {
+        plaintext.zeroize();
+        bail!("文件头中的原始文件名无效: {original_filename}");
+    }
+
+    let target_path = output_dir.join(&original_filename);
+    let write_result = File::create_new(&target_path)
+        .with_context(|| {
+            format!(
+                "目标文件 {} 已存在或无法创建，为防止数据覆盖，操作已中止。",
+                target_path.display()
+            )
+        })
+        .and_then(|mut file| file.write_all(&plaintext).context("写入目标文件失败"));
+    plaintext.zeroize();
+    write_result?;
+    warn_if_name_transformed(&target_path);
+
+    log::info!("解密后的文件已保存为: {}", target_path.display());
+    Ok(target_path)
+}
+
+/// 只解密加密文件开头的 `n_chunks` 个数据块（每块 [`BUFFER_LEN`] 字节），用于根据魔数快速识别文件类型。
+///
+/// 与完整解密一样派生密钥，但只读取所需的密文；文件比请求的长度短时返回全部明文。
+/// 主密钥在返回之前被擦除。
+///
+/// # 安全性
+///
+/// **返回的明文没有经过认证。** 认证标签位于文件末尾，覆盖全部密文，只读取开头时无法验证，
+/// 因此密码错误、密钥文件错误或文件被篡改时本函数都不会报错，而是返回看起来随机的数据。
+/// 结果只能用于启发式的判断（例如检查 JPEG、PDF、ZIP 的文件头），
+/// 不能作为可信的明文使用；需要可信的明文时请使用完整解密。
+///
+/// # 错误
+///
+/// 文件无法读取、文件头无效、文件已过期或密钥派生失败时返回错误。
+pub fn decrypt_first_n_chunks(
+    path: &Path,
+    n_chunks: usize,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+) -> Result<Vec<u8>> {
+    let file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+    let file_size = file.metadata()?.len();
+    let mut reader = std::io::BufReader::new(file);
+    let header = read_header(&mut reader)?;
+    ensure_not_expired(&header)?;
+    let ciphertext_size = ciphertext_len(file_size, &header)?;
+    let wanted = ciphertext_size.min(n_chunks.saturating_mul(BUFFER_LEN) as u64);
+
+    let opts = DecryptionOptions {
+        keyfile,
+        ..DecryptionOptions::new(password)
+    };
+    let master_key = derive_master_key(&header, &opts)?;
+    // 标签在文件末尾，只读取开头时无法验证，因此不需要 HMAC 密钥
+    let mut cipher = Aes256Ctr::new(master_key[..AES_KEY_LEN].into(), &header.iv.into());
+    drop(master_key);
+
+    let mut plaintext = Vec::with_capacity(wanted as usize);
+    reader
+        .take(wanted)
+        .read_to_end(&mut plaintext)
+        .context("读取密文失败")?;
+    cipher.apply_keystream(&mut plaintext);
+    Ok(plaintext)
+}
+
+/// # 恢复报告 (Recovery Report)
+///
+/// [`decrypt_unverified`] 的结果。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// 写出的明文文件路径（带有 [`UNVERIFIED_SUFFIX`] 后缀）。
+    pub output_path: PathBuf,
+    /// 写出的明文字节数。
+    pub recovered_bytes: u64,
+    /// 认证标签是否恰好验证通过（文件其实完好）。为 `false` 时输出的完整性**没有任何保证**。
+    pub tag_verified: bool,
+    /// 读取密文时遇到的 I/O 错误；恢复在该处停止。
+    pub read_error: Option<String>,
+}
+
+/// 尽力恢复认证标签损坏的加密文件：解密所有能读取的密文，写入 `output` 加上
+/// [`UNVERIFIED_SUFFIX`] 后缀的新文件。
+///
+/// 文件末尾的 [`TAG_LEN`] 字节总是被当作（可能已损坏的）认证标签而不会被解密，
+/// 因此文件被截断时，最后最多 [`TAG_LEN`] 字节的明文会丢失；读取遇到 I/O 错误（例如坏扇区）时，
+/// 已经解密的部分会被保留，恢复在该处停止。
+///
+/// # 安全性
+///
+/// **输出没有经过认证。** 标签无法验证时，密文可能已被篡改，而且密码或密钥文件错误也不会被发现，
+/// 只会得到看起来随机的数据。该函数只用于从损坏的介质上抢救数据，绝不能替代正常解密，
+/// 批量操作也不会使用它。
+///
+/// # 错误
+///
+/// 文件头无法读取、文件已过期、密钥派生失败或输出文件已存在时返回错误。
+/// 文件头记录了密钥文件承诺而密码或密钥文件与之不符时返回 [`FeroxError::AuthenticationFailed`]。
+/// 这些检查都在创建输出文件之前进行；写入恢复文件失败时，不完整的输出文件会被删除。
+pub fn decrypt_unverified(
+    source: &Path,
+    output: &Path,
+    opts: &DecryptionOptions,
+) -> Result<RecoveryReport> {
+    ensure_valid_buffer_size(opts.buffer_size)?;
+    let mut file =
+        File::open(source).with_context(|| format!("无法打开文件: {}", source.display()))?;
+    let file_size = file.metadata()?.len();
+    let header = read_header(&mut file)?;
+    ensure_not_expired(&header)?;
+    let ciphertext_size = file_size.saturating_sub(header.len() + TAG_LEN as u64);
+
+    // 创建输出文件之前先派生密钥并检查能检查的凭据，失败时不留下会阻止重试的空文件
+    if let Some(keyfile) = opts.keyfile {
+        if keyfile_commitment_matches(&header, opts.password, keyfile)? == Some(false) {
+            return Err(FeroxError::AuthenticationFailed.into());
+        }
+    }
+    let master_key = derive_master_key(&header, opts)?;
+    let (aes_key, hmac_key) = master_key.split_at(AES_KEY_LEN);
+    let mut cipher = Aes256Ctr::new(aes_key.into(), &header.iv.into());
+    let mut mac = HmacSha256::new_from_slice(hmac_key).context("无法创建HMAC实例")?;
+    drop(master_key);
+    if header.is_authenticated() {
+        mac.update(&header.encode()?);
+    }
+
+    let mut output_path = output.as_os_str().to_owned();
+    output_path.push(UNVERIFIED_SUFFIX);
+    let output_path = PathBuf::from(output_path);
+    let mut writer = File::create_new(&output_path).with_context(|| {
+        format!(
+            "目标文件 {} 已存在或无法创建，为防止数据覆盖，操作已中止。",
+            output_path.display()
+        )
+    })?;
 
-            // MAC-then-Decrypt 模式的逆过程:
-            // 1. 将从文件读取的密文块送入 HMAC 进行认证
+    log::warn!("⚠️  恢复模式：输出不经过认证，完整性没有任何保证!");
+    let mut reader = (&mut file).take(ciphertext_size);
+    let mut buffer = Zeroizing::new(vec![0u8; opts.buffer_size]);
+    let mut recovered_bytes = 0u64;
+    let recovered = (|| -> Result<Option<String>> {
+        let read_error = loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) => break None,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::warn!("⚠️  在密文偏移 {recovered_bytes} 处读取失败，恢复到此为止: {e}");
+                    break Some(e.to_string());
+                }
+            };
+            let chunk = &mut buffer[..n];
             mac.update(chunk);
-            // 2. 解密数据块 (AES-CTR 的加解密是同一个操作)
             cipher.apply_keystream(chunk);
-            // 3. 将解密后的明文写入目标文件
-            writer.write_all(chunk).context("写入目标文件失败")?;
+            writer.write_all(chunk).context("写入恢复文件失败")?;
+            recovered_bytes += n as u64;
+        };
+        writer.flush().context("刷新恢复文件失败")?;
+        Ok(read_error)
+    })();
+    let read_error = match recovered {
+        Ok(read_error) => read_error,
+        Err(e) => {
+            // 写入失败时删除不完整的恢复文件，以便修正问题后重试
+            drop(writer);
+            if let Err(remove_error) = std::fs::remove_file(&output_path) {
+                log::warn!(
+                    "⚠️  无法删除不完整的恢复文件 {}: {remove_error}",
+                    output_path.display()
+                );
+            }
+            return Err(e);
         }
+    };
 
-        pb.finish_with_message("解密完成，正在验证...");
-
-        // --- 8. 验证认证标签 ---
-        // 读取文件末尾原始的认证标签
-        let mut original_tag = [0u8; TAG_LEN];
-        ciphertext_reader
-            .into_inner() // 获取 `take` 装饰器内部的 reader
-            .read_exact(&mut original_tag)
-            .context("无法读取文件的认证标签")?;
-
-        // 将我们实时计算出的 HMAC 标签与文件中的原始标签进行比较
-        // 这是一个常数时间比较，可以防止时序攻击
-        match mac.verify_slice(&original_tag) {
-            Ok(_) => {
-                // 验证成功，刷新缓冲区，完成写入
-                writer.flush().context("刷新文件缓冲区失败")?;
-                log::info!("--- ✅ 验证成功，解密完成! ---");
-            }
-            Err(_) => {
-                // 验证失败，立即报错并中止。
-                // 这通常意味着密码错误、密钥文件错误或文件已损坏。
-                bail!("严重错误: 认证失败! 文件可能已损坏，或密码/密钥文件错误。");
-            }
+    // 只有文件完整、没有读取错误时，末尾的字节才可能是原始的认证标签
+    let mut tag = [0u8; TAG_LEN];
+    let tag_verified = read_error.is_none()
+        && recovered_bytes == ciphertext_size
+        && file.read_exact(&mut tag).is_ok()
+        && mac.verify_slice(&tag).is_ok();
+    if tag_verified {
+        log::info!("认证标签验证通过，文件其实是完好的；可以直接使用正常解密。");
+    } else {
+        log::warn!(
+            "⚠️  已恢复 {recovered_bytes} 字节到 {}，但认证标签无法验证：数据可能已损坏或被篡改，\
+             密码或密钥文件错误时输出也只是随机数据。",
+            output_path.display()
+        );
+    }
+
+    Ok(RecoveryReport {
+        output_path,
+        recovered_bytes,
+        tag_verified,
+        read_error,
+    })
+}
+
+/// 在 `path` 所在的目录中找到第一个未被占用的 `名称 (n).扩展名`（n 从 1 开始）。
+fn unused_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| e.to_string_lossy());
+    (1u64..)
+        .map(|n| {
+            let name = match &extension {
+                Some(extension) => format!("{stem} ({n}).{extension}"),
+                None => format!("{stem} ({n})"),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("候选名称不会用完")
+}
+
+/// 目标文件系统改变了文件名的字节形式时发出警告。
+///
+/// HFS+ 总是把文件名存为分解形式，其他文件系统（例如某些网络共享）也可能改写文件名；
+/// 此时目录中的名称与文件头记录的（或规范化后的）名称外观相同但字节不同。
+/// 只检查包含非 ASCII 字符的文件名。
+fn warn_if_name_transformed(target_path: &Path) {
+    let (Some(parent), Some(name)) = (target_path.parent(), target_path.file_name()) else {
+        return;
+    };
+    let Some(name) = name.to_str().filter(|name| !name.is_ascii()) else {
+        return;
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        return;
+    };
+    let names: Vec<_> = entries.flatten().map(|entry| entry.file_name()).collect();
+    if names.iter().any(|entry| entry == name) {
+        return;
+    }
+    let stored = names
+        .iter()
+        .filter_map(|entry| entry.to_str())
+        .find(|entry| entry.nfc().eq(name.nfc()));
+    if let Some(stored) = stored {
+        log::warn!(
+            "⚠️  目标文件系统改变了文件名的 Unicode 形式: {} 被存储为 {:?}，依赖文件名字节的工具可能找不到它",
+            target_path.display(),
+            stored
+        );
+    }
+}
+
+/// 读取并解析文件头，自动识别格式版本。
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> Result<FileHeader> {
+    FileHeader::read_from(reader)
+}
+
+/// 文件已超过过期时间时返回 [`FeroxError::FileExpired`]。
+///
+/// 在任何密钥派生之前调用。过期时间受认证标签保护，篡改它会导致之后的认证失败。
+fn ensure_not_expired(header: &FileHeader) -> Result<()> {
+    match expiry_from_unix(header.expiry_time()) {
+        Some(expired_at) if SystemTime::now() > expired_at => {
+            Err(FeroxError::FileExpired { expired_at }.into())
         }
+        _ => Ok(()),
+    }
+}
 
-        // 安全擦除主密钥
-        master_key.zeroize();
-        Ok(())
-    })();
+/// 文件头中有本版本不认识的非关键扩展时发出警告，列出它们的类型；严格模式下拒绝解密。
+///
+/// 这类扩展由更新版本的程序写入，忽略它们不影响解密结果；不认识的关键扩展在解析文件头时就已被拒绝。
+fn warn_about_ignored_extensions(path: &Path, header: &FileHeader, strict: bool) -> Result<()> {
+    let ignored = header.ignored_extensions();
+    if ignored.is_empty() {
+        return Ok(());
+    }
+    let kinds: Vec<String> = ignored.iter().map(|kind| format!("0x{kind:04x}")).collect();
+    warn_or_fail(
+        strict,
+        format!(
+            "{} 的文件头含有本版本不认识的扩展 ({})，已忽略；可能由更新版本的 Ferox Encryptor 写入",
+            path.display(),
+            kinds.join(", ")
+        ),
+    )?;
+    Ok(())
+}
 
-    // 无论成功或失败，都清理共享状态
-    *temp_file_path.lock().unwrap() = None;
+/// 根据加密数据的总长度和文件头计算密文部分的长度。
+pub(crate) fn ciphertext_len(total_len: u64, header: &FileHeader) -> Result<u64> {
+    total_len
+        .checked_sub(header.len() + TAG_LEN as u64)
+        .ok_or_else(|| {
+            FeroxError::InvalidFormat("文件太短，缺少认证标签，文件可能已损坏".to_string()).into()
+        })
+}
 
-    result
+/// 解密流程的核心：派生密钥，从 `reader` 读取恰好 `ciphertext_size` 字节的密文并把明文写入 `writer`，
+/// 最后读取并验证认证标签。
+///
+/// `reader` 必须位于文件头之后。注意：明文在标签验证之前就会被写入 `writer`，
+/// 调用者必须在本函数返回错误时丢弃已写入的数据。错误带有出错的 [`Stage`]；
+/// 认证失败属于 [`Stage::Finalize`]。
+pub(crate) fn decrypt_body<R: Read, W: Write>(
+    reader: &mut R,
+    header: &FileHeader,
+    ciphertext_size: u64,
+    writer: &mut W,
+    opts: &DecryptionOptions,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    // --- 1. 密钥派生 ---
+    let master_key = derive_master_key(header, opts).at_stage(Stage::KeyDerivation)?;
+
+    // --- 2. 初始化加密器和 MAC ---
+    let (aes_key, hmac_key) = master_key.split_at(AES_KEY_LEN);
+    let cipher = Aes256Ctr::new(aes_key.into(), &header.iv.into());
+    let mut mac = HmacSha256::new_from_slice(hmac_key)
+        .context("无法创建HMAC实例")
+        .at_stage(Stage::KeyDerivation)?;
+    if header.is_authenticated() {
+        // 当前格式的认证标签同时覆盖文件头
+        mac.update(&header.encode().at_stage(Stage::HeaderParse)?);
+    }
+
+    // --- 3. 流式解密 ---
+    log::info!("开始流式解密文件...");
+    // 使用 `take` 方法精确地只读取密文部分，不包括最后的认证标签
+    let mut ciphertext_reader = reader.take(ciphertext_size);
+    // MAC-then-Decrypt: 先将密文块送入 HMAC 进行认证，再解密数据块
+    // (AES-CTR 的加解密是同一个操作)。除非设置了 `single_thread`，两个阶段以流水线方式运行；
+    // 设置了多个线程时，密文按顺序送入 HMAC 后由多个线程并行解密。
+    let mut bytes_done = 0;
+    let streamed = process_stream(
+        &mut ciphertext_reader,
+        writer,
+        &cipher,
+        &mut |chunk: &[u8]| mac.update(chunk),
+        CipherDirection::Decrypt,
+        &opts.chunk_settings(),
+        &mut |bytes| {
+            bytes_done += bytes;
+            on_progress(bytes);
+        },
+    );
+    streamed.at_stage(Stage::Streaming { bytes_done })?;
+
+    // --- 4. 验证认证标签 ---
+    // 读取末尾原始的认证标签
+    let mut original_tag = [0u8; TAG_LEN];
+    ciphertext_reader
+        .into_inner() // 获取 `take` 装饰器内部的 reader
+        .read_exact(&mut original_tag)
+        .context("无法读取文件的认证标签")
+        .at_stage(Stage::Finalize)?;
+
+    // 安全擦除主密钥（出错提前返回时由 `Drop` 擦除）
+    drop(master_key);
+
+    // 将我们实时计算出的 HMAC 标签与文件中的原始标签进行比较
+    // 这是一个常数时间比较，可以防止时序攻击
+    if mac.verify_slice(&original_tag).is_err() {
+        // 验证失败，立即报错并中止。
+        // 这通常意味着密码错误、密钥文件错误或文件已损坏。
+        return Err(FeroxError::AuthenticationFailed).at_stage(Stage::Finalize);
+    }
+    Ok(())
+}
+
+/// 只验证认证标签而不解密：派生密钥，用 HMAC 流式读取 `reader` 中恰好 `ciphertext_size`
+/// 字节的密文，再与紧随其后的认证标签比较。
+///
+/// 与 [`decrypt_body`] 相比省去了 AES-CTR 解密，不产生任何明文。
+pub(crate) fn verify_body<R: Read>(
+    reader: &mut R,
+    header: &FileHeader,
+    ciphertext_size: u64,
+    opts: &DecryptionOptions,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    let master_key = derive_master_key(header, opts)?;
+    let mut mac =
+        HmacSha256::new_from_slice(&master_key[AES_KEY_LEN..]).context("无法创建HMAC实例")?;
+    drop(master_key);
+    if header.is_authenticated() {
+        mac.update(&header.encode()?);
+    }
+
+    let mut ciphertext_reader = reader.take(ciphertext_size);
+    update_mac_from_reader(
+        &mut mac,
+        &mut ciphertext_reader,
+        opts.buffer_size,
+        on_progress,
+    )?;
+    let mut original_tag = [0u8; TAG_LEN];
+    ciphertext_reader
+        .into_inner()
+        .read_exact(&mut original_tag)
+        .context("无法读取文件的认证标签")?;
+
+    if mac.verify_slice(&original_tag).is_err() {
+        return Err(FeroxError::AuthenticationFailed.into());
+    }
+    Ok(())
+}
+
+/// 以 `key` 计算 `reader` 中全部数据的 HMAC-SHA256。
+///
+/// 数据按数据块流式读取，内存占用与数据大小无关。配合已知的 HMAC 密钥，
+/// 外部工具可以用它重新计算并核对认证标签：`.feroxcrypt` 文件的标签覆盖文件头和密文，
+/// 即文件除末尾 [`TAG_LEN`] 字节以外的全部内容。
+///
+/// # 错误
+///
+/// 读取 `reader` 失败时返回错误。
+pub fn compute_streaming_hmac(mut reader: impl Read, key: &[u8; 32]) -> Result<[u8; 32]> {
+    let mut mac = HmacSha256::new_from_slice(key).context("无法创建HMAC实例")?;
+    update_mac_from_reader(&mut mac, &mut reader, BUFFER_LEN, &mut |_| {})?;
+    Ok(mac.finalize().into_bytes().into())
+}
+
+/// 以 `key` 计算文件全部内容的 HMAC-SHA256，见 [`compute_streaming_hmac`]。
+///
+/// # 错误
+///
+/// 文件无法打开或读取时返回错误。
+pub fn compute_hmac_for_file(path: &Path, key: &[u8; 32]) -> Result<[u8; 32]> {
+    let file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+    compute_streaming_hmac(file, key).with_context(|| format!("读取文件失败: {}", path.display()))
+}
+
+/// 把 `reader` 的全部数据按 `buffer_size` 大小的数据块送入 `mac`，
+/// 每个数据块以其字节数调用 `on_progress`。
+fn update_mac_from_reader<R: Read>(
+    mac: &mut HmacSha256,
+    reader: &mut R,
+    buffer_size: usize,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    read_stream(reader, buffer_size, |chunk| {
+        mac.update(chunk);
+        on_progress(chunk.len() as u64);
+    })?;
+    Ok(())
+}
+
+/// 按文件头中的参数从密码（和可选的密钥文件）派生主密钥：
+/// 前 [`AES_KEY_LEN`] 字节为 AES 密钥，其余为 HMAC 密钥。
+///
+/// 文件使用尚未实现的加密套件时返回 [`FeroxError::UnsupportedFeature`]。
+fn derive_master_key(header: &FileHeader, opts: &DecryptionOptions) -> Result<SecureBuffer> {
+    // 按文件头中的加密套件选择认证方式；目前只实现了独立 HMAC 认证的套件
+    let suite = header.cipher_suite()?;
+    if suite.is_aead() {
+        return Err(FeroxError::UnsupportedFeature(format!(
+            "尚未实现 {} 的解密",
+            suite.display_name()
+        ))
+        .into());
+    }
+
+    let (m_cost, t_cost, p_cost) = (header.m_cost, header.t_cost, header.p_cost);
+
+    // 使用从文件头读取的参数重新构建 Argon2 配置
+    let argon2_params = Params::new(m_cost, t_cost, p_cost, Some(MASTER_KEY_LEN))
+        .map_err(|e| anyhow!("从文件头创建 Argon2 参数失败: {}", e))?;
+
+    log::info!(
+        "文件使用 {} 安全级别 (Argon2 参数: m_cost={m_cost}, t_cost={t_cost}, p_cost={p_cost})",
+        header
+            .security_level()
+            .map_or("Custom", |level| level.name())
+    );
+
+    log::info!("正在从密码派生密钥...");
+    let argon2 = Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        argon2_params,
+    );
+
+    // 根据是否有密钥文件，准备密码材料
+    let password_material = if let Some(kf) = opts.keyfile {
+        log::info!("使用密钥文件进行解密。");
+        let mut combined = combine_password_and_keyfile(opts.password, kf)?;
+        let material = SecureBuffer::from_slice(&combined, opts.lock_memory);
+        combined.zeroize();
+        material
+    } else {
+        SecureBuffer::from_slice(opts.password.as_bytes(), opts.lock_memory)
+    };
+
+    // 使用与加密时完全相同的参数（密码材料、盐）来派生密钥
+    let mut master_key = SecureBuffer::new(MASTER_KEY_LEN, opts.lock_memory);
+    secure_memory::ensure_locked(
+        &[&password_material, &master_key],
+        opts.lock_memory,
+        opts.strict,
+    )?;
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(
+            tracing::Level::INFO,
+            "argon2_derive",
+            m_cost,
+            t_cost,
+            p_cost
+        )
+        .entered();
+        argon2
+            .hash_password_into(&password_material, &header.salt, &mut master_key)
+            .map_err(|e| anyhow!("Argon2密钥派生失败: {}", e))?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!("密钥派生完成");
+    }
+
+    drop(password_material);
+    log::info!("密钥派生完成。");
+    Ok(master_key)
 }
@@ -6,27 +6,238 @@
 //! 密钥派生、文件读写到生成最终加密文件的完整逻辑。
 
 use crate::{
-    constants::{AES_KEY_LEN, BUFFER_LEN, CUSTOM_FILE_EXTENSION, IV_LEN, MASTER_KEY_LEN, SALT_LEN},
-    keyfile::{combine_password_and_keyfile, KeyFile},
-    Level,
+    capabilities::report_before_large_operation,
+    cleanup::{cleanup_partial_output, commit_partial_file, create_staging_file},
+    constants::{
+        fixed_overhead_bytes, AES_KEY_LEN, ALLOW_DETERMINISTIC_ENV_VAR, BUFFER_LEN,
+        CUSTOM_FILE_EXTENSION, IV_LEN, LARGE_OPERATION_THRESHOLD, MASTER_KEY_LEN,
+        MIN_KEYFILE_ENTROPY_BITS, SALT_LEN,
+    },
+    error::{FeroxError, Stage, StageContext},
+    format::FileHeader,
+    keyfile::{combine_password_and_keyfile, keyfile_commitment, KeyFile},
+    permissions,
+    pipeline::{ensure_valid_buffer_size, process_stream, ChunkSettings, CipherDirection},
+    progress::{with_terminal_progress, ProgressEvent, ProgressSink},
+    secure_memory::{self, SecureBuffer},
+    stream_io::{ByteCounts, Counting, InputFile, OutputFile},
+    watchdog::{self, default_stall_warning, StallSettings},
+    xattrs, Level,
 };
 use anyhow::{anyhow, bail, Context, Result};
 use argon2::{self, Argon2, Params};
-use ctr::cipher::{KeyIvInit, StreamCipher};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ctr::cipher::KeyIvInit;
 use hmac::{Hmac, Mac};
-use indicatif::{ProgressBar, ProgressStyle};
-use rand::{rngs::OsRng, RngCore};
+use memmap2::Mmap;
+use rand::{rngs::OsRng, CryptoRng, RngCore};
 use sha2::Sha256;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use zeroize::Zeroize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::{Zeroize, Zeroizing};
 
 // 定义密码学算法的类型别名，以简化代码
 type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
 type HmacSha256 = Hmac<Sha256>;
 
+/// Base64 输出超过此大小时发出警告 (1 MB)。
+const BASE64_WARN_THRESHOLD: usize = 1024 * 1024;
+
+/// # 加密选项 (Encryption Options)
+///
+/// 汇总一次加密操作所需的凭据和参数，供库调用者使用。
+///
+/// *Bundles the credentials and parameters needed for one encryption operation.*
+#[derive(Clone, Copy)]
+pub struct EncryptionOptions<'a> {
+    /// 用于加密的密码。
+    pub password: &'a str,
+    /// (可选) 用于增强安全性的密钥文件。
+    pub keyfile: Option<&'a KeyFile>,
+    /// 加密的安全级别，决定了 Argon2 的计算成本。
+    pub level: Level,
+    /// 是否强制覆盖已存在的同名加密文件。
+    pub force_overwrite: bool,
+    /// 是否在单个线程中串行执行加密和认证（默认在独立线程上以流水线方式执行）。
+    pub single_thread: bool,
+    /// 并行加密单个文件的线程数（默认为 1，即不拆分文件）。
+    ///
+    /// 大于 1 时，文件被拆分为多个数据块由多个线程并行加密；`single_thread` 优先。
+    pub threads: usize,
+    /// 读写缓冲区和数据块的大小（字节，默认为 [`BUFFER_LEN`]）。
+    ///
+    /// 必须在 [`MIN_BUFFER_SIZE`] 和 [`MAX_BUFFER_SIZE`] 之间。
+    pub buffer_size: usize,
+    /// 是否通过内存映射读取源文件（默认关闭）。
+    ///
+    /// 源文件不是普通文件（例如管道或标准输入）、为空或无法映射时自动改用缓冲读取。
+    /// 加密期间源文件不能被其他进程截断，否则进程可能因访问已不存在的映射页而崩溃 (`SIGBUS`)。
+    pub mmap: bool,
+    /// (可选) 文件的过期时间，超过后拒绝解密（默认不过期）。
+    ///
+    /// 必须晚于当前时间。参见 [`encrypt_with_time_limit`]。
+    pub expires_at: Option<SystemTime>,
+    /// 是否把密码材料和主密钥锁定在物理内存中，防止被换出到磁盘（默认关闭）。
+    ///
+    /// 锁定失败（例如 `RLIMIT_MEMLOCK` 太低）时发出警告并继续加密，见 [`SecureBuffer`]。
+    pub lock_memory: bool,
+    /// (可选) 源文件的大小上限（字节，默认不限制）。
+    ///
+    /// 超过上限的文件在任何密钥派生之前被拒绝，返回 [`FeroxError::FileTooLarge`]。
+    pub max_file_size: Option<u64>,
+    /// 是否把源文件的扩展属性和 Unix 权限位记录在文件头中（默认关闭，见 [`crate::xattrs`]）。
+    ///
+    /// 只适用于加密磁盘上文件的流程；在 Linux 等系统上只记录 `user.*` 属性。
+    /// 权限位供解密时的 [`OutputPermissions::FromHeader`](crate::decrypt::OutputPermissions::FromHeader) 使用。
+    pub preserve_xattrs: bool,
+    /// (可选) 无法在目标文件旁边创建 `.part` 临时文件（例如目录只读或超出配额）时改用的临时目录。
+    ///
+    /// 临时目录在另一个文件系统上时，完成后通过复制、`fsync` 和重命名提交；
+    /// 目标目录中无法暂存时提交不是原子的，会发出警告。
+    pub temp_dir: Option<&'a Path>,
+    /// 写入文件头的接收者数量（默认为 1）。
+    ///
+    /// 为以后的多接收者加密预留，参见 [`encrypt_with_recipient_count_hint`]。
+    pub recipient_count: u8,
+    /// 是否在加密成功后覆写并删除源文件（默认关闭）。
+    ///
+    /// 源文件有多个硬链接时，删除这个路径不会删除其他硬链接指向的明文，此时拒绝加密，
+    /// 除非设置了 `force_delete_hardlinked`。源文件是符号链接或在加密期间被修改过时保留它并发出警告。
+    /// 参见 [`run_encryption_flow_with_hardlink_detection`]。
+    pub delete_source_after_encrypt: bool,
+    /// 源文件有多个硬链接时仍然加密并删除这个路径（默认关闭，只发出警告）。
+    pub force_delete_hardlinked: bool,
+    /// 数据流处理期间没有任何读写进展多久之后发出停滞警告（默认 60 秒）。
+    ///
+    /// 警告通过日志和 [`ProgressEvent::Stalled`] 发出；密钥派生阶段不计时。
+    pub stall_warning: Duration,
+    /// (可选) 没有任何读写进展多久之后放弃该文件并返回 [`FeroxError::Stalled`](crate::FeroxError::Stalled)
+    /// （默认 `None`，只警告）。
+    ///
+    /// 设置后读写在工作线程中执行；卡在系统调用中的工作线程无法被中断，只能留在后台。
+    pub stall_timeout: Option<Duration>,
+    /// 严格模式：把默认只发出警告的问题当作错误（默认关闭）。
+    ///
+    /// 适用于无法读取或放不下的扩展属性、无法读取的权限位，以及 `lock_memory` 时无法锁定内存，
+    /// 返回 [`FeroxError::StrictViolation`]。
+    pub strict: bool,
+    /// 是否在文件头中记录源文件的修改时间（默认关闭）。
+    ///
+    /// 批量加密的 [`BatchConfig::skip_if_encrypted_matches`](crate::batch::BatchConfig::skip_if_encrypted_matches)
+    /// 用它判断已有的加密文件是否仍然对应源文件。修改时间与文件名一样以明文保存在文件头中（受认证保护）。
+    /// 只对文件加密有效，内存加密没有源文件。
+    pub record_source_mtime: bool,
+    /// 使用密钥文件时是否在文件头中记录密钥文件承诺（默认关闭）。
+    ///
+    /// 承诺让 [`verify_keyfile_matches_encrypted_file`](crate::keyfile::verify_keyfile_matches_encrypted_file)
+    /// 和批量解密无需完整的密钥派生就能判断密钥文件和密码是否匹配。代价是它也可以被用来离线猜测密码：
+    /// 每次猜测只需要固定的密钥文件派生（约 19 MiB 内存），而不是 `level` 选择的 Argon2 参数，
+    /// 对 `Moderate` 和 `Paranoid` 级别来说明显更便宜。只在需要这项检查、并且接受这个代价时启用。
+    pub keyfile_commitment: bool,
+    /// (可选) 可续传加密 ([`encrypt_resumable`](crate::resume::encrypt_resumable)) 处理数据的速率上限
+    /// （每秒字节数，默认不限制），避免长时间占满慢速磁盘或 USB 总线的带宽。
+    ///
+    /// 其他加密流程忽略该字段。
+    pub rate_limit: Option<u64>,
+    /// ⚠️ 仅用于测试：固定的盐和 IV（默认 `None`，由 `OsRng` 随机生成）。
+    ///
+    /// 盐和 IV 是格式中仅有的随机数据，固定它们之后相同的输入总是得到逐字节相同的输出，
+    /// 用于锁定各格式版本精确字节的黄金文件测试 (`tests/fixtures/golden`)。
+    /// 用同一组值加密不同的明文会复用 CTR 密钥流，绝不能用于真实数据。
+    /// 与 [`encrypt_deterministic`] 一样，只有设置了环境变量 `FEROX_ALLOW_DETERMINISTIC=1` 时才会被使用，
+    /// 否则加密直接返回错误。
+    #[doc(hidden)]
+    pub test_nonces: Option<([u8; SALT_LEN], [u8; IV_LEN])>,
+}
+
+impl<'a> EncryptionOptions<'a> {
+    /// 使用给定密码和默认参数 (安全级别为 [`default_level`](crate::default_level)，
+    /// 初始为 `Level::Moderate`；无密钥文件，不覆盖) 创建加密选项。
+    pub fn new(password: &'a str) -> Self {
+        Self {
+            password,
+            keyfile: None,
+            level: crate::default_level(),
+            force_overwrite: false,
+            single_thread: false,
+            threads: 1,
+            buffer_size: BUFFER_LEN,
+            mmap: false,
+            expires_at: None,
+            lock_memory: false,
+            max_file_size: None,
+            preserve_xattrs: false,
+            temp_dir: None,
+            recipient_count: 1,
+            delete_source_after_encrypt: false,
+            force_delete_hardlinked: false,
+            stall_warning: default_stall_warning(),
+            stall_timeout: None,
+            strict: false,
+            record_source_mtime: false,
+            keyfile_commitment: false,
+            rate_limit: None,
+            test_nonces: None,
+        }
+    }
+}
+
+impl EncryptionOptions<'_> {
+    /// 数据块的处理方式。
+    pub(crate) fn chunk_settings(&self) -> ChunkSettings {
+        ChunkSettings {
+            buffer_size: self.buffer_size,
+            single_thread: self.single_thread,
+            threads: self.threads,
+        }
+    }
+
+    /// 停滞检测的设置。
+    pub(crate) fn stall_settings(&self) -> StallSettings {
+        StallSettings {
+            warning: self.stall_warning,
+            timeout: self.stall_timeout,
+        }
+    }
+}
+
+impl fmt::Debug for EncryptionOptions<'_> {
+    /// 调试输出中绝不包含密码或密钥文件内容。
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionOptions")
+            .field("password", &"<redacted>")
+            .field("keyfile", &self.keyfile.is_some())
+            .field("level", &self.level)
+            .field("force_overwrite", &self.force_overwrite)
+            .field("single_thread", &self.single_thread)
+            .field("threads", &self.threads)
+            .field("buffer_size", &self.buffer_size)
+            .field("mmap", &self.mmap)
+            .field("expires_at", &self.expires_at)
+            .field("lock_memory", &self.lock_memory)
+            .field("max_file_size", &self.max_file_size)
+            .field("preserve_xattrs", &self.preserve_xattrs)
+            .field("temp_dir", &self.temp_dir)
+            .field("recipient_count", &self.recipient_count)
+            .field(
+                "delete_source_after_encrypt",
+                &self.delete_source_after_encrypt,
+            )
+            .field("force_delete_hardlinked", &self.force_delete_hardlinked)
+            .field("stall_warning", &self.stall_warning)
+            .field("stall_timeout", &self.stall_timeout)
+            .field("strict", &self.strict)
+            .field("keyfile_commitment", &self.keyfile_commitment)
+            .field("rate_limit", &self.rate_limit)
+            .field("test_nonces", &self.test_nonces.is_some())
+            .finish()
+    }
+}
+
 /// 执行完整的文件加密流程。
 ///
 /// # 参数
@@ -41,6 +252,17 @@ type HmacSha256 = Hmac<Sha256>;
 /// # 返回
 ///
 /// `Ok(())` 表示成功，否则返回一个描述错误的 `anyhow::Error`。
+///
+/// 启用 `tracing` 特性时，整个流程记录在 `run_encryption_flow` span 中，
+/// 密钥派生和数据流处理分别是它的子 span，失败时记录错误事件。
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        err,
+        skip(password, keyfile, temp_file_path),
+        fields(source_path = %source_path.display(), level = ?level)
+    )
+)]
 pub fn run_encryption_flow(
     source_path: &Path,
     force_overwrite: bool,
@@ -49,166 +271,957 @@ pub fn run_encryption_flow(
     keyfile: Option<&KeyFile>,
     temp_file_path: Arc<Mutex<Option<PathBuf>>>,
 ) -> Result<()> {
+    let opts = EncryptionOptions {
+        keyfile,
+        level,
+        force_overwrite,
+        ..EncryptionOptions::new(password)
+    };
+    with_terminal_progress(source_path, |progress| {
+        run_encryption_flow_with_progress(source_path, &opts, temp_file_path, progress).map(|_| ())
+    })
+}
+
+/// 加密文件并在文件头中记录过期时间，超过 `expiry` 后解密会被拒绝
+/// （返回 [`FeroxError::FileExpired`](crate::FeroxError::FileExpired)）。
+///
+/// 过期时间受认证标签保护，无法在不破坏文件的情况下修改；但它只是对解密程序的约束，
+/// 并不能阻止拿到密码的人使用修改过的程序解密。
+///
+/// # 错误
+///
+/// `expiry` 不晚于当前时间时返回错误，其余与 [`run_encryption_flow`] 相同。
+pub fn encrypt_with_time_limit(
+    source: &Path,
+    expiry: SystemTime,
+    opts: &EncryptionOptions,
+) -> Result<()> {
+    let opts = EncryptionOptions {
+        expires_at: Some(expiry),
+        ..*opts
+    };
+    with_terminal_progress(source, |progress| {
+        run_encryption_flow_with_progress(source, &opts, Arc::new(Mutex::new(None)), progress)
+            .map(|_| ())
+    })
+}
+
+/// 加密文件并在文件头中记录接收者数量 `recipient_count`。
+///
+/// 多接收者加密尚未实现：文件仍然只用 `opts` 中的密码（和密钥文件）加密，
+/// 接收者数量只是为每个接收者的密钥封装预留的占位。接收者数量大于 1 的文件
+/// 当前程序无法解密（返回 [`FeroxError::MultiRecipientNotSupported`](crate::FeroxError::MultiRecipientNotSupported)），
+/// 等待支持多接收者的版本。
+///
+/// # 错误
+///
+/// `recipient_count` 为 0 时返回错误，其余与 [`run_encryption_flow`] 相同。
+pub fn encrypt_with_recipient_count_hint(
+    source: &Path,
+    recipient_count: u8,
+    opts: &EncryptionOptions,
+) -> Result<()> {
+    let opts = EncryptionOptions {
+        recipient_count,
+        ..*opts
+    };
+    with_terminal_progress(source, |progress| {
+        run_encryption_flow_with_progress(source, &opts, Arc::new(Mutex::new(None)), progress)
+            .map(|_| ())
+    })
+}
+
+/// 执行文件加密流程，并将进度报告给可选的 [`ProgressSink`]。
+///
+/// 已处理的字节数以 [`ProgressEvent::BytesProcessed`] 事件发送给接收器；
+/// `progress` 为 `None` 时不报告进度，也不创建任何进度对象。
+/// 成功时返回实际读取的源文件字节数和写入的加密文件字节数。
+pub(crate) fn run_encryption_flow_with_progress(
+    source_path: &Path,
+    opts: &EncryptionOptions,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<ByteCounts> {
+    run_encryption(source_path, opts, temp_file_path, progress, None)
+}
+
+/// 加密流程的实现；`nonces` 为 `Some` 时使用给定的盐和 IV，而不是随机生成。
+fn run_encryption(
+    source_path: &Path,
+    opts: &EncryptionOptions,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    progress: Option<&dyn ProgressSink>,
+    nonces: Option<([u8; SALT_LEN], [u8; IV_LEN])>,
+) -> Result<ByteCounts> {
     // 将核心逻辑包装在一个闭包中，这样可以利用 `?` 操作符进行错误处理，
     // 并在闭包外部统一处理清理逻辑，实现类似 `try...finally` 的效果。
-    let result = (|| {
-        // --- 1. 输入验证 ---
-        if !source_path.exists() {
-            bail!("文件不存在: {}", source_path.display());
-        }
-        if !source_path.is_file() {
-            bail!("提供的路径不是一个文件: {}", source_path.display());
+    // 删除源文件之前用来确认它在加密期间没有变化
+    let mut source_snapshot = None;
+    let result = (|| -> Result<ByteCounts> {
+        if opts.delete_source_after_encrypt {
+            ensure_no_other_hardlinks(source_path, opts.force_delete_hardlinked)
+                .at_stage(Stage::HeaderParse)?;
+            source_snapshot = Some(
+                fs::symlink_metadata(source_path)
+                    .context("无法读取源文件元数据")
+                    .at_stage(Stage::HeaderParse)?,
+            );
         }
-        // 检查文件是否已经加密
-        if source_path
-            .extension()
-            .is_some_and(|s| s == CUSTOM_FILE_EXTENSION)
-        {
-            bail!(
-                "文件看起来已经被加密过了 (以 .{} 结尾)",
-                CUSTOM_FILE_EXTENSION
+        let (part_path, target_path, counts) =
+            encrypt_to_partial_with_nonces(source_path, opts, &temp_file_path, progress, nonces)?;
+        // 关闭文件后再原子地重命名为目标文件
+        commit_partial_file(&part_path, &target_path).at_stage(Stage::Finalize)?;
+        log::info!("--- ✅ 加密成功! ---");
+        Ok(counts)
+    })()
+    .with_context(|| format!("无法加密 {}", source_path.display()));
+
+    // 失败时删除不完整的输出文件；无论成功或失败，都在函数返回前清理共享状态
+    if result.is_err() {
+        cleanup_partial_output(&temp_file_path);
+    }
+    *temp_file_path
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+
+    // 加密文件已经提交，才删除源文件；删除失败不影响已经成功的加密
+    if let (Ok(_), Some(snapshot)) = (&result, &source_snapshot) {
+        if let Err(e) = delete_source(source_path, snapshot, opts.buffer_size) {
+            log::warn!(
+                "⚠️  加密成功，但没有删除源文件 {}: {e:#}",
+                source_path.display()
             );
         }
+    }
+
+    result
+}
+
+/// 加密文件后覆写并删除源文件；源文件有多个硬链接时返回错误，除非 `force` 为 `true`。
+///
+/// 与 [`run_encryption_flow`] 相同，但使用完整的 [`EncryptionOptions`]，并总是启用
+/// [`delete_source_after_encrypt`](EncryptionOptions::delete_source_after_encrypt)。
+/// 硬链接检查在读取源文件之前进行，被拒绝时不会生成任何输出。
+///
+/// 源文件先用零覆写再删除；强制删除有其他硬链接的文件时只删除这个路径，不覆写。
+/// 覆写只是尽力而为：写时复制的文件系统、快照和 SSD 的磨损均衡都可能保留旧数据。
+///
+/// 源文件是符号链接，或者大小、修改时间与加密前不一致（加密期间被修改过）时不删除它；
+/// 删除失败只记录警告，加密本身仍然成功。
+pub fn run_encryption_flow_with_hardlink_detection(
+    source_path: &Path,
+    opts: &EncryptionOptions,
+    force: bool,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+) -> Result<()> {
+    let opts = EncryptionOptions {
+        delete_source_after_encrypt: true,
+        force_delete_hardlinked: force,
+        ..*opts
+    };
+    with_terminal_progress(source_path, |progress| {
+        run_encryption_flow_with_progress(source_path, &opts, temp_file_path, progress).map(|_| ())
+    })
+}
+
+/// `path` 是否还有其他硬链接（链接数大于 1）。
+///
+/// 没有硬链接计数的平台（例如 Windows）上总是返回 `false`。
+pub fn detect_hardlinks(path: &Path) -> Result<bool> {
+    Ok(hardlink_count(path)? > 1)
+}
+
+/// `path` 的硬链接数量。
+#[cfg(unix)]
+fn hardlink_count(path: &Path) -> Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata =
+        fs::metadata(path).with_context(|| format!("无法读取文件元数据: {}", path.display()))?;
+    Ok(metadata.nlink())
+}
+
+/// 没有硬链接计数的平台。
+#[cfg(not(unix))]
+fn hardlink_count(_path: &Path) -> Result<u64> {
+    Ok(1)
+}
+
+/// 有多个硬链接的文件的标识（设备号和 inode 号），同一份内容的所有硬链接标识相同。
+///
+/// 只有一个链接、无法读取元数据或没有硬链接计数的平台上返回 `None`。
+#[cfg(unix)]
+pub(crate) fn hardlink_identity(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::metadata(path).ok()?;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+/// 没有硬链接计数的平台。
+#[cfg(not(unix))]
+pub(crate) fn hardlink_identity(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// 删除源文件之前的检查：有其他硬链接时发出警告，并在 `force` 为 `false` 时返回错误。
+fn ensure_no_other_hardlinks(source_path: &Path, force: bool) -> Result<()> {
+    let links = hardlink_count(source_path)?;
+    if links <= 1 {
+        return Ok(());
+    }
+    log::warn!(
+        "⚠️  源文件 {} 有 {links} 个硬链接；安全删除这个路径不会删除其他硬链接指向的副本",
+        source_path.display()
+    );
+    if !force {
+        bail!(
+            "源文件 {} 有 {links} 个硬链接，加密后删除它不会删除其他副本；确认无误后请强制执行",
+            source_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// 删除已加密的源文件，`snapshot` 为加密前用 [`fs::symlink_metadata`] 读取的元数据。
+///
+/// 没有其他硬链接时先用零覆写内容并同步到磁盘；有其他硬链接时（已由
+/// [`ensure_no_other_hardlinks`] 确认）只删除这个路径，覆写会破坏其他硬链接看到的同一份数据。
+/// 路径是符号链接或者文件在加密期间被修改过时返回错误，不修改任何文件。
+fn delete_source(path: &Path, snapshot: &fs::Metadata, buffer_size: usize) -> Result<()> {
+    let current = fs::symlink_metadata(path).context("无法读取源文件元数据")?;
+    if current.file_type().is_symlink() {
+        bail!("源文件是符号链接，不会删除它或覆写它指向的文件");
+    }
+    ensure_source_unchanged(snapshot, &current)?;
+    if hardlink_count(path)? > 1 {
+        fs::remove_file(path).context("删除源文件失败")?;
+        log::warn!("⚠️  已删除 {}，其他硬链接仍指向明文", path.display());
+        return Ok(());
+    }
+    let mut options = OpenOptions::new();
+    options.write(true);
+    // 检查之后路径被换成符号链接时打开失败，而不是覆写链接指向的文件
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, libc::O_NOFOLLOW);
+    let mut file = options.open(path).context("无法打开源文件进行覆写")?;
+    let opened = file.metadata().context("无法读取源文件元数据")?;
+    ensure_source_unchanged(snapshot, &opened)?;
+    let zeros = vec![0u8; buffer_size];
+    let mut remaining = opened.len();
+    while remaining > 0 {
+        let len = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..len]).context("覆写源文件失败")?;
+        remaining -= len as u64;
+    }
+    file.sync_all().context("同步覆写的源文件失败")?;
+    drop(file);
+    fs::remove_file(path).context("删除源文件失败")?;
+    log::info!("已覆写并删除源文件: {}", path.display());
+    Ok(())
+}
+
+/// 确认源文件的大小和修改时间与加密前相同，否则删除它会丢失加密之后写入的数据。
+fn ensure_source_unchanged(snapshot: &fs::Metadata, current: &fs::Metadata) -> Result<()> {
+    if snapshot.len() != current.len() || snapshot.modified().ok() != current.modified().ok() {
+        bail!("源文件在加密期间被修改过（大小或修改时间与加密前不一致），为避免丢失数据没有删除它");
+    }
+    Ok(())
+}
+
+/// 验证源文件并计算加密输出的目标路径，返回 `(原始文件名, 目标路径)`。
+///
+/// 如果目标文件已存在且未设置强制覆盖，则返回错误。
+pub(crate) fn prepare_encryption(
+    source_path: &Path,
+    force_overwrite: bool,
+) -> Result<(String, PathBuf)> {
+    let original_filename = validate_source(source_path)?;
+    let target_path = encrypted_target_path(source_path);
+
+    // 如果目标文件已存在且未设置强制覆盖，则报错
+    if !force_overwrite && target_path.exists() {
+        bail!(
+            "目标文件 {} 已存在。如需覆盖，请使用 --force 标志。",
+            target_path.display()
+        );
+    }
+    ensure_distinct_target(source_path, &target_path)?;
+    Ok((original_filename, target_path))
+}
+
+/// 加密 `source_path` 时输出的目标路径：源路径加上 `.feroxcrypt` 扩展名。
+pub(crate) fn encrypted_target_path(source_path: &Path) -> PathBuf {
+    PathBuf::from(format!(
+        "{}.{}",
+        source_path.display(),
+        CUSTOM_FILE_EXTENSION
+    ))
+}
+
+/// 目标路径已存在并且与源文件是同一个文件时返回 [`FeroxError::SourceEqualsTarget`]。
+///
+/// 目标可能是指向源文件的符号链接、硬链接，或者在大小写不敏感的文件系统上只是大小写不同的同一路径。
+/// 必须在打开写入端之前调用。Unix 上比较设备号和 inode，其他平台比较规范化后的路径。
+pub(crate) fn ensure_distinct_target(
+    source_path: &Path,
+    target_path: &Path,
+) -> std::result::Result<(), FeroxError> {
+    let (Ok(source), Ok(target)) = (fs::metadata(source_path), fs::metadata(target_path)) else {
+        return Ok(());
+    };
+    #[cfg(unix)]
+    let same = {
+        use std::os::unix::fs::MetadataExt;
+        (source.dev(), source.ino()) == (target.dev(), target.ino())
+    };
+    #[cfg(not(unix))]
+    let same = {
+        let _ = (source, target);
+        matches!(
+            (source_path.canonicalize(), target_path.canonicalize()),
+            (Ok(source), Ok(target)) if source == target
+        )
+    };
+    if same {
+        return Err(FeroxError::SourceEqualsTarget {
+            source_path: source_path.to_path_buf(),
+            target_path: target_path.to_path_buf(),
+        });
+    }
+    Ok(())
+}
+
+/// 把源文件加密到目标文件对应的 `.part` 文件中，但不重命名。
+///
+/// 成功时返回 `(part 文件路径, 目标路径, 读写字节数)`，`.part` 文件已刷新并关闭，
+/// 由调用者决定何时提交。`.part` 文件在创建后会登记到 `temp_file_path` 中，
+/// 本函数不会清空登记表，也不会在失败时删除它。错误带有源文件路径和出错的 [`Stage`]。
+pub(crate) fn encrypt_to_partial(
+    source_path: &Path,
+    opts: &EncryptionOptions,
+    temp_file_path: &Mutex<Option<PathBuf>>,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<(PathBuf, PathBuf, ByteCounts)> {
+    encrypt_to_partial_with_nonces(source_path, opts, temp_file_path, progress, None)
+        .with_context(|| format!("无法加密 {}", source_path.display()))
+}
+
+/// 与 [`encrypt_to_partial`] 相同，但错误只带有 [`Stage`] 而不带路径；
+/// `nonces` 为 `Some` 时使用给定的盐和 IV，而不是随机生成。
+fn encrypt_to_partial_with_nonces(
+    source_path: &Path,
+    opts: &EncryptionOptions,
+    temp_file_path: &Mutex<Option<PathBuf>>,
+    progress: Option<&dyn ProgressSink>,
+    nonces: Option<([u8; SALT_LEN], [u8; IV_LEN])>,
+) -> Result<(PathBuf, PathBuf, ByteCounts)> {
+    // --- 1. 输入验证与准备路径 ---
+    let (original_filename, target_path) =
+        prepare_encryption(source_path, opts.force_overwrite).at_stage(Stage::HeaderParse)?;
+
+    log::info!("加密后的文件将保存为: {}", target_path.display());
+    log::info!("使用 {} 安全级别进行加密", opts.level);
 
-        // --- 2. 准备路径和文件名 ---
-        let original_filename = source_path
-            .file_name()
-            .context("无法获取文件名")?
-            .to_str()
-            .context("文件名包含无效的UTF-8字符")?;
-
-        // 构建目标加密文件的路径
-        let target_path_str = format!("{}.{}", source_path.display(), CUSTOM_FILE_EXTENSION);
-        let target_path = Path::new(&target_path_str).to_path_buf();
-
-        // 如果目标文件已存在且未设置强制覆盖，则报错
-        if !force_overwrite && target_path.exists() {
-            bail!(
-                "目标文件 {} 已存在。如需覆盖，请使用 --force 标志。",
-                target_path.display()
+    // --- 2. 打开文件流 ---
+    // Windows 上 `File::open` 以允许读、写、删除共享的方式打开源文件，不会妨碍其他程序；
+    // 只有占用者本身拒绝共享时才会出现共享冲突，归类为 `FeroxError::FileLocked`
+    let source_file = File::open(source_path)
+        .context("无法打开源文件")
+        .at_stage(Stage::HeaderParse)?;
+    let metadata = source_file.metadata().at_stage(Stage::HeaderParse)?;
+    ensure_within_size_limit(&metadata, opts.max_file_size).at_stage(Stage::HeaderParse)?;
+    let source_len = metadata.len();
+    if source_len >= LARGE_OPERATION_THRESHOLD {
+        if let Some(allocated) = sparse_allocation(&metadata) {
+            log::warn!(
+                "⚠️  {} 是稀疏文件: 表观大小 {} 字节，实际只占用 {} 字节，\
+                 加密会读取并写出全部 {} 字节，耗时按表观大小计算",
+                source_path.display(),
+                source_len,
+                allocated,
+                source_len
             );
         }
+    }
+    report_before_large_operation(source_len);
+    ensure_valid_buffer_size(opts.buffer_size).at_stage(Stage::HeaderParse)?;
+    let reader = SourceReader::open(source_file, opts).at_stage(Stage::HeaderParse)?;
 
-        log::info!("加密后的文件将保存为: {}", target_path.display());
-        log::info!("使用 {level:?} 安全级别进行加密");
+    // 先写入带锁的 `.part` 文件，成功后再由调用者重命名为目标文件
+    let (part_path, target_file) =
+        create_staging_file(&target_path, opts.temp_dir).at_stage(Stage::HeaderParse)?;
+    // 拿到锁之后再登记，以便中断或失败时可以清理
+    *temp_file_path
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(part_path.clone());
+    let writer = OutputFile::create(target_file, source_len, opts.buffer_size);
 
-        // 在开始写入前，将目标路径存入共享状态，以便中断时可以清理
-        *temp_file_path.lock().unwrap() = Some(target_path.clone());
+    // --- 3. 流式加密 ---
+    let mut on_progress = |bytes| {
+        if let Some(sink) = progress {
+            sink.on_event(&ProgressEvent::BytesProcessed { bytes });
+        }
+    };
+    let header = build_source_header(source_path, &original_filename, &metadata, opts, nonces)
+        .at_stage(Stage::HeaderParse)?;
+    // 停滞检测从第一次读写开始计时，不包括之前的密钥派生
+    watchdog::watch(source_path, opts.stall_settings(), progress, |watchdog| {
+        let mut reader = Counting::new(watchdog.reader(reader, 0).at_stage(Stage::HeaderParse)?);
+        let mut writer = Counting::new(watchdog.writer(writer).at_stage(Stage::HeaderParse)?);
+        watchdog::announce_key_derivation(source_path, opts.level.argon2_params(), progress);
+        encrypt_stream_with_header(&mut reader, &mut writer, &header, opts, &mut on_progress)?;
 
-        // --- 3. 打开文件流 ---
-        let source_file = File::open(source_path).context("无法打开源文件")?;
-        let source_size = source_file.metadata()?.len();
-        let mut reader = BufReader::with_capacity(BUFFER_LEN, source_file);
-        let target_file = File::create(&target_path).context("无法创建目标文件")?;
-        let mut writer = BufWriter::with_capacity(BUFFER_LEN, target_file);
+        // 确保所有缓冲数据都已写入磁盘，并关闭文件（释放锁）
+        writer
+            .flush()
+            .context("刷新文件缓冲区失败")
+            .at_stage(Stage::Finalize)?;
+        let counts = ByteCounts {
+            read: reader.count(),
+            written: writer.count(),
+        };
+        drop(writer);
+        Ok((part_path, target_path, counts))
+    })
+}
 
-        // --- 4. 生成密码学参数 ---
-        // 生成随机的盐和初始化向量 (IV)
+/// 为加密磁盘上的源文件构建文件头：随机（或 `nonces`/`opts.test_nonces` 给定的）盐和 IV，
+/// 以及按选项记录的源文件修改时间、权限位和扩展属性。
+pub(crate) fn build_source_header(
+    source_path: &Path,
+    original_filename: &str,
+    metadata: &fs::Metadata,
+    opts: &EncryptionOptions,
+    nonces: Option<([u8; SALT_LEN], [u8; IV_LEN])>,
+) -> Result<FileHeader> {
+    let nonces = match nonces {
+        Some(nonces) => Some(nonces),
+        None => fixed_test_nonces(opts)?,
+    };
+    let (salt, iv) = nonces.unwrap_or_else(|| {
         let mut salt = [0u8; SALT_LEN];
         OsRng.fill_bytes(&mut salt);
         let mut iv = [0u8; IV_LEN];
         OsRng.fill_bytes(&mut iv);
+        (salt, iv)
+    });
+    let mut header = build_header(original_filename, salt, iv, opts)?;
+    if opts.record_source_mtime {
+        header.set_source_mtime(metadata.modified().ok());
+    }
+    if opts.preserve_xattrs {
+        header.set_file_mode(permissions::capture(source_path, opts.strict)?);
+        let attributes = xattrs::capture(source_path, opts.strict)?;
+        header.set_xattrs(&attributes, opts.strict)?;
+    }
+    Ok(header)
+}
+
+/// 加密时读取源文件的方式。
+enum SourceReader {
+    /// 普通读取：缓冲读取，或启用 `uring` 特性时的 io_uring 预读。
+    File(InputFile),
+    /// 通过只读内存映射读取，`position` 为下一次读取的偏移量。
+    Mapped { map: Mmap, position: usize },
+}
+
+impl SourceReader {
+    /// 按选项打开源文件；请求了内存映射但无法使用时回退到缓冲读取。
+    fn open(file: File, opts: &EncryptionOptions) -> Result<Self> {
+        let metadata = file.metadata().context("无法读取源文件元数据")?;
+        if opts.mmap {
+            // 管道、标准输入等流式输入无法映射，空文件也不能映射
+            if metadata.is_file() && metadata.len() > 0 {
+                // SAFETY: 映射是只读的；`EncryptionOptions::mmap` 的文档要求调用者保证
+                // 加密期间源文件不会被截断。
+                match unsafe { Mmap::map(&file) } {
+                    Ok(map) => {
+                        #[cfg(unix)]
+                        let _ = map.advise(memmap2::Advice::Sequential);
+                        return Ok(Self::Mapped { map, position: 0 });
+                    }
+                    Err(e) => log::warn!("无法映射源文件，改用缓冲读取: {}", e),
+                }
+            } else {
+                log::debug!("源文件不是非空的普通文件，改用缓冲读取");
+            }
+        }
+        Ok(Self::File(InputFile::open(
+            file,
+            metadata.len(),
+            opts.buffer_size,
+        )))
+    }
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(reader) => reader.read(buf),
+            Self::Mapped { map, position } => {
+                let remaining = &map[*position..];
+                let len = remaining.len().min(buf.len());
+                buf[..len].copy_from_slice(&remaining[..len]);
+                *position += len;
+                Ok(len)
+            }
+        }
+    }
+}
 
-        // --- 5. 密钥派生 ---
-        log::info!("正在从密码派生密钥...");
-        // 根据选择的安全级别获取 Argon2 参数
-        let (m_cost, t_cost, p_cost) = level.argon2_params();
-        let argon2_params = Params::new(m_cost, t_cost, p_cost, Some(MASTER_KEY_LEN))
-            .map_err(|e| anyhow!("创建 Argon2 参数失败: {}", e))?;
-        let argon2 = Argon2::new(
-            argon2::Algorithm::Argon2id,
-            argon2::Version::V0x13,
-            argon2_params,
+#[doc = "⚠️ NEVER USE IN PRODUCTION. Deterministic encryption is only safe for testing."]
+///
+/// 确定性地加密文件：盐为 `BLAKE3(seed || "salt")`、IV 为 `BLAKE3(seed || "iv")`（各取前 16 字节），
+/// 相同的种子、密码、选项和明文总是得到逐字节相同的 `.feroxcrypt` 文件，用于测试夹具和可重现的归档。
+///
+/// 用同一个种子和密码加密不同的明文会复用 CTR 密钥流，两份密文异或即可得到明文的异或，
+/// 因此只能在测试中使用。调用前必须设置环境变量 `FEROX_ALLOW_DETERMINISTIC=1`。
+///
+/// # 错误
+///
+/// 环境变量未设置为 `1` 时直接返回错误，不读取源文件；其余与 [`run_encryption_flow`] 相同。
+pub fn encrypt_deterministic(
+    source: &Path,
+    seed: [u8; 32],
+    opts: &EncryptionOptions,
+) -> Result<()> {
+    if std::env::var(ALLOW_DETERMINISTIC_ENV_VAR).as_deref() != Ok("1") {
+        bail!(
+            "确定性加密只能用于测试，需要设置环境变量 {}=1",
+            ALLOW_DETERMINISTIC_ENV_VAR
         );
+    }
+    log::warn!("⚠️  正在使用确定性加密 (仅用于测试): {}", source.display());
 
-        // 根据是否有密钥文件，选择不同的密码材料
-        let mut password_material = if let Some(kf) = keyfile {
-            log::info!("使用密钥文件增强安全性。");
-            combine_password_and_keyfile(password, kf)?
-        } else {
-            password.as_bytes().to_vec()
-        };
+    let nonces = (seeded_bytes(&seed, b"salt"), seeded_bytes(&seed, b"iv"));
+    run_encryption(source, opts, Arc::new(Mutex::new(None)), None, Some(nonces)).map(|_| ())
+}
 
-        // 使用 Argon2 进行密钥派生
-        let mut master_key = [0u8; MASTER_KEY_LEN];
-        argon2
-            .hash_password_into(&password_material, &salt, &mut master_key)
-            .map_err(|e| anyhow!("Argon2密钥派生失败: {}", e))?;
+/// 返回 `opts.test_nonces`。设置了固定的盐和 IV 而环境变量 `FEROX_ALLOW_DETERMINISTIC` 不是 `1` 时返回错误，
+/// 与 [`encrypt_deterministic`] 的限制相同。
+fn fixed_test_nonces(opts: &EncryptionOptions) -> Result<Option<([u8; SALT_LEN], [u8; IV_LEN])>> {
+    if opts.test_nonces.is_none() {
+        return Ok(None);
+    }
+    if std::env::var(ALLOW_DETERMINISTIC_ENV_VAR).as_deref() != Ok("1") {
+        bail!(
+            "固定的盐和 IV 只能用于测试，需要设置环境变量 {}=1",
+            ALLOW_DETERMINISTIC_ENV_VAR
+        );
+    }
+    log::warn!("⚠️  正在使用固定的盐和 IV (仅用于测试)");
+    Ok(opts.test_nonces)
+}
+
+/// 取 `BLAKE3(seed || label)` 的前 `N` 个字节。
+fn seeded_bytes<const N: usize>(seed: &[u8; 32], label: &[u8]) -> [u8; N] {
+    let hash = blake3::Hasher::new().update(seed).update(label).finalize();
+    let mut output = [0u8; N];
+    output.copy_from_slice(&hash.as_bytes()[..N]);
+    output
+}
+
+/// 用密码（和可选的密钥文件）在内存中加密一段数据，不涉及任何文件系统操作。
+///
+/// 适用于令牌、配置值、API 密钥之类的小型机密，是 [`encrypt_in_memory`] 使用默认选项
+/// (安全级别为 [`default_level`](crate::default_level)) 的简化形式；用 [`decrypt_to_vec`](crate::decrypt::decrypt_to_vec) 解密。
+pub fn encrypt_to_vec(
+    plaintext: &[u8],
+    filename: &str,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+) -> Result<Vec<u8>> {
+    let opts = EncryptionOptions {
+        keyfile,
+        ..EncryptionOptions::new(password)
+    };
+    encrypt_in_memory(plaintext, filename, &opts)
+}
+
+/// 在内存中加密一段数据，返回完整的 `.feroxcrypt` 格式字节。
+///
+/// 输出与 [`run_encryption_flow`] 写入磁盘的文件格式完全相同，
+/// `filename` 会作为原始文件名写入文件头。
+///
+/// # 参数
+///
+/// * `plaintext` - 要加密的明文数据。
+/// * `filename` - 写入文件头的原始文件名。
+/// * `opts` - 加密选项（`force_overwrite` 在此函数中无意义，将被忽略）。
+pub fn encrypt_in_memory(
+    plaintext: &[u8],
+    filename: &str,
+    opts: &EncryptionOptions,
+) -> Result<Vec<u8>> {
+    let mut reader = plaintext;
+    let mut output = Vec::with_capacity(fixed_overhead_bytes() + filename.len() + plaintext.len());
+    encrypt_stream(&mut reader, &mut output, filename, opts, &mut |_| {})?;
+    Ok(output)
+}
+
+/// 加密一个文件，并将加密结果编码为 Base64 字符串（URL 安全字符集，无填充）。
+///
+/// 适用于需要把加密内容嵌入 JSON/YAML 等文本配置的场景
+/// （例如 CI/CD 的 secrets 或 Kubernetes ConfigMap）。
+/// 源文件不会被修改，也不会在磁盘上生成 `.feroxcrypt` 文件。
+///
+/// 注意：Base64 编码会使数据体积膨胀约 33%（每 3 字节编码为 4 个字符）。
+/// 当输出超过 1 MB 时会记录一条警告，因为这通常意味着一个大文件
+/// 被嵌入到了以明文传输的配置中。
+pub fn encrypt_file_to_base64(source: &Path, opts: &EncryptionOptions) -> Result<String> {
+    let original_filename = validate_source(source)?;
+    ensure_within_size_limit(&fs::metadata(source)?, opts.max_file_size)?;
+    let plaintext = Zeroizing::new(fs::read(source).context("无法读取源文件")?);
+    let encrypted = encrypt_in_memory(&plaintext, &original_filename, opts)?;
+    let encoded = URL_SAFE_NO_PAD.encode(encrypted);
 
-        // 安全地擦除内存中的密码材料
-        password_material.zeroize();
-        log::info!("密钥派生完成。");
-
-        // --- 6. 分割主密钥并初始化加密器和 MAC ---
-        // 主密钥的前半部分用于 AES 加密，后半部分用于 HMAC 认证
-        let (aes_key, hmac_key) = master_key.split_at(AES_KEY_LEN);
-        let mut cipher = Aes256Ctr::new(aes_key.into(), &iv.into());
-        let mut mac = HmacSha256::new_from_slice(hmac_key).context("无法创建HMAC实例")?;
-
-        // --- 7. 写入文件头 ---
-        // 文件头包含了恢复原始文件名和进行解密所需的所有元数据。
-        // 顺序: 文件名长度 -> 文件名 -> 盐 -> IV -> Argon2参数
-        let filename_bytes = original_filename.as_bytes();
-        if filename_bytes.len() > u16::MAX as usize {
-            bail!("文件名太长了 (超过65535字节)");
+    if encoded.len() > BASE64_WARN_THRESHOLD {
+        log::warn!(
+            "Base64 输出大小为 {} 字节 (超过 1 MB)，大文件不适合嵌入到配置文件中: {}",
+            encoded.len(),
+            source.display()
+        );
+    }
+    Ok(encoded)
+}
+
+/// 文件超过 `limit` 时返回 [`FeroxError::FileTooLarge`]；`limit` 为 `None` 时不限制。
+pub(crate) fn ensure_within_size_limit(
+    metadata: &fs::Metadata,
+    limit: Option<u64>,
+) -> std::result::Result<(), FeroxError> {
+    match limit {
+        Some(limit) if metadata.len() > limit => Err(FeroxError::FileTooLarge {
+            size: metadata.len(),
+            limit,
+            allocated: sparse_allocation(metadata),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// 文件是稀疏文件时返回它实际占用的磁盘空间（字节），见
+/// [`SPARSE_FILE_RATIO`](crate::constants::SPARSE_FILE_RATIO)。
+///
+/// 只在 Unix 上检测；其他平台总是返回 `None`。
+fn sparse_allocation(metadata: &fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use crate::constants::SPARSE_FILE_RATIO;
+        use std::os::unix::fs::MetadataExt;
+        // `st_blocks` 总是以 512 字节为单位
+        let allocated = metadata.blocks().saturating_mul(512);
+        (allocated.saturating_mul(SPARSE_FILE_RATIO) < metadata.len()).then_some(allocated)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// 路径（跟随符号链接）指向命名管道、设备节点或套接字时返回 [`FeroxError::SpecialFile`]。
+///
+/// 必须在打开文件之前调用：打开没有写入者的命名管道会一直阻塞。
+/// 普通文件、目录和无法读取元数据的路径都返回 `Ok`，由调用者按原有方式报告。
+pub(crate) fn ensure_not_special_file(path: &Path) -> std::result::Result<(), FeroxError> {
+    match fs::metadata(path)
+        .ok()
+        .and_then(|metadata| special_file_kind(metadata.file_type()))
+    {
+        Some(kind) => Err(FeroxError::SpecialFile { kind }),
+        None => Ok(()),
+    }
+}
+
+/// 既不是普通文件也不是目录的文件类型的描述。
+fn special_file_kind(file_type: fs::FileType) -> Option<&'static str> {
+    if file_type.is_file() || file_type.is_dir() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            return Some("命名管道 (FIFO)");
         }
-        // 写入原始文件名的长度 (2字节, 小端序)
-        writer.write_all(&(filename_bytes.len() as u16).to_le_bytes())?;
-        // 写入原始文件名
-        writer.write_all(filename_bytes)?;
-        // 写入盐
-        writer.write_all(&salt)?;
-        // 写入IV
-        writer.write_all(&iv)?;
-        // 写入 Argon2 参数 (m_cost, t_cost, p_cost)，共12字节
-        writer.write_all(&m_cost.to_le_bytes())?;
-        writer.write_all(&t_cost.to_le_bytes())?;
-        writer.write_all(&p_cost.to_le_bytes())?;
-
-        // --- 8. 流式加密和认证 ---
-        log::info!("开始流式加密文件...");
-        // 初始化进度条
-        let pb = ProgressBar::new(source_size);
-        pb.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
-            .progress_chars("#>-"));
-
-        let mut buffer = vec![0u8; BUFFER_LEN];
-        loop {
-            // 从源文件读取数据块
-            let bytes_read = reader.read(&mut buffer).context("读取源文件失败")?;
-            if bytes_read == 0 {
-                break; // 文件读取完毕
-            }
-            pb.inc(bytes_read as u64);
-            let chunk = &mut buffer[..bytes_read];
-
-            // Encrypt-then-MAC 模式:
-            // 1. 加密数据块
-            cipher.apply_keystream(chunk);
-            // 2. 将加密后的数据块（密文）送入 HMAC 进行认证
-            mac.update(chunk);
-            // 3. 将加密后的数据块写入目标文件
-            writer.write_all(chunk).context("写入目标文件失败")?;
+        if file_type.is_char_device() {
+            return Some("字符设备");
         }
+        if file_type.is_block_device() {
+            return Some("块设备");
+        }
+        if file_type.is_socket() {
+            return Some("套接字");
+        }
+    }
+    Some("特殊文件")
+}
 
-        // --- 9. 写入认证标签并完成 ---
-        // 在所有数据都处理完毕后，生成最终的 HMAC 认证标签
-        let tag = mac.finalize().into_bytes();
-        // 将标签写入文件的末尾
-        writer.write_all(&tag)?;
-        // 确保所有缓冲数据都已写入磁盘
-        writer.flush().context("刷新文件缓冲区失败")?;
-        pb.finish_with_message("加密完成");
+/// 验证源文件是否可以被加密，并返回其文件名。
+pub(crate) fn validate_source(source_path: &Path) -> Result<String> {
+    if !source_path.exists() {
+        bail!("文件不存在: {}", source_path.display());
+    }
+    ensure_not_special_file(source_path)
+        .with_context(|| format!("无法加密 {}", source_path.display()))?;
+    if !source_path.is_file() {
+        bail!("提供的路径不是一个文件: {}", source_path.display());
+    }
+    // 检查文件是否已经加密
+    if source_path
+        .extension()
+        .is_some_and(|s| s == CUSTOM_FILE_EXTENSION)
+    {
+        bail!(
+            "文件看起来已经被加密过了 (以 .{} 结尾)",
+            CUSTOM_FILE_EXTENSION
+        );
+    }
 
-        log::info!("--- ✅ 加密成功! ---");
-        // 安全地擦除内存中的主密钥
-        master_key.zeroize();
-        Ok(())
-    })();
+    let original_filename = source_path
+        .file_name()
+        .context("无法获取文件名")?
+        .to_str()
+        .context("文件名包含无效的UTF-8字符")?;
+    Ok(original_filename.to_string())
+}
 
-    // 无论成功或失败，都在函数返回前清理共享状态
-    *temp_file_path.lock().unwrap() = None;
+/// 加密流程的核心：从 `reader` 读取明文，向 `writer` 写入完整的加密格式（文件头、密文和认证标签）。
+///
+/// 该函数不关心数据来自文件还是内存，也不负责刷新 `writer`。
+/// 每处理完一个数据块，都会以该块的字节数调用 `on_progress`。
+pub(crate) fn encrypt_stream<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    original_filename: &str,
+    opts: &EncryptionOptions,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    if let Some((salt, iv)) = fixed_test_nonces(opts)? {
+        let header = build_header(original_filename, salt, iv, opts)?;
+        return encrypt_stream_with_header(reader, writer, &header, opts, on_progress);
+    }
+    encrypt_stream_with_rng(
+        reader,
+        writer,
+        original_filename,
+        opts,
+        &mut OsRng,
+        on_progress,
+    )
+}
 
-    result
+/// 与 [`encrypt_stream`] 相同，但盐和 IV 由给定的随机数生成器产生。
+///
+/// 正常流程始终使用 `OsRng`；注入其他生成器只用于需要可重复输出的基准测试。
+pub(crate) fn encrypt_stream_with_rng<R: Read, W: Write, G: RngCore + CryptoRng>(
+    reader: &mut R,
+    writer: &mut W,
+    original_filename: &str,
+    opts: &EncryptionOptions,
+    rng: &mut G,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    // --- 1. 生成密码学参数 ---
+    // 生成随机的盐和初始化向量 (IV)
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let mut iv = [0u8; IV_LEN];
+    rng.fill_bytes(&mut iv);
+    let header = build_header(original_filename, salt, iv, opts)?;
+
+    encrypt_stream_with_header(reader, writer, &header, opts, on_progress)
+}
+
+/// 使用给定的盐和 IV 构建当前格式的文件头，安全级别、Argon2 参数、过期时间和接收者数量取自 `opts`。
+fn build_header(
+    original_filename: &str,
+    salt: [u8; SALT_LEN],
+    iv: [u8; IV_LEN],
+    opts: &EncryptionOptions,
+) -> Result<FileHeader> {
+    // 根据选择的安全级别获取 Argon2 参数
+    if opts.recipient_count == 0 {
+        bail!("接收者数量必须至少为 1");
+    }
+    let mut header = FileHeader::new(original_filename, salt, iv, opts.level.argon2_params());
+    header.set_security_level(Some(opts.level));
+    header.recipient_count = opts.recipient_count;
+    if let Some(expiry) = opts.expires_at {
+        header.set_expiry_time(unix_expiry_time(expiry)?);
+    }
+    Ok(header)
+}
+
+/// 把过期时间转换为文件头中记录的 Unix 时间戳（秒），过期时间必须晚于当前时间。
+fn unix_expiry_time(expiry: SystemTime) -> Result<u64> {
+    if expiry <= SystemTime::now() {
+        bail!("过期时间必须晚于当前时间");
+    }
+    let secs = expiry
+        .duration_since(UNIX_EPOCH)
+        .context("无效的过期时间")?
+        .as_secs();
+    Ok(secs)
+}
+
+/// 使用给定的文件头加密数据流。
+///
+/// 密钥派生使用文件头中的盐和 Argon2 参数（`opts.level` 会被忽略），
+/// 文件头按其自身的格式版本编码。使用密钥文件时，当前格式的文件头中
+/// 还会加入密钥文件承诺。错误带有出错的 [`Stage`]。
+pub(crate) fn encrypt_stream_with_header<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    header: &FileHeader,
+    opts: &EncryptionOptions,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    // --- 2. 密钥派生 ---
+    let (header, master_key) =
+        derive_encryption_key(header, opts).at_stage(Stage::KeyDerivation)?;
+
+    // --- 3. 分割主密钥并初始化加密器和 MAC ---
+    // 主密钥的前半部分用于 AES 加密，后半部分用于 HMAC 认证
+    let (aes_key, hmac_key) = master_key.split_at(AES_KEY_LEN);
+    let cipher = Aes256Ctr::new(aes_key.into(), &header.iv.into());
+    let mut mac = HmacSha256::new_from_slice(hmac_key)
+        .context("无法创建HMAC实例")
+        .at_stage(Stage::KeyDerivation)?;
+
+    // --- 4. 写入文件头 ---
+    // 文件头包含了恢复原始文件名和进行解密所需的所有元数据。
+    // 当前格式的认证标签同时覆盖文件头，防止元数据被篡改。
+    let header_bytes = header.encode().at_stage(Stage::HeaderParse)?;
+    writer
+        .write_all(&header_bytes)
+        .at_stage(Stage::HeaderParse)?;
+    if header.is_authenticated() {
+        mac.update(&header_bytes);
+    }
+
+    // --- 5. 流式加密和认证 ---
+    log::info!("开始流式加密文件...");
+    // Encrypt-then-MAC 模式: 先加密数据块，再将密文送入 HMAC 进行认证。
+    // 除非设置了 `single_thread`，两个阶段在独立的线程上以流水线方式运行；
+    // 设置了多个线程时，数据块由多个线程并行加密，再按顺序送入 HMAC。
+    let mut bytes_done = 0;
+    let streamed = process_stream(
+        reader,
+        writer,
+        &cipher,
+        &mut |chunk: &[u8]| mac.update(chunk),
+        CipherDirection::Encrypt,
+        &opts.chunk_settings(),
+        &mut |bytes| {
+            bytes_done += bytes;
+            on_progress(bytes);
+        },
+    );
+    streamed.at_stage(Stage::Streaming { bytes_done })?;
+
+    // --- 6. 写入认证标签 ---
+    // 在所有数据都处理完毕后，生成最终的 HMAC 认证标签
+    let tag = mac.finalize().into_bytes();
+    // 将标签写入末尾
+    writer.write_all(&tag).at_stage(Stage::Finalize)?;
+
+    // 安全地擦除内存中的主密钥（出错提前返回时由 `Drop` 擦除）
+    drop(master_key);
+    Ok(())
+}
+
+/// 按文件头中的参数派生主密钥，返回（使用密钥文件时加入了密钥文件承诺的）文件头和主密钥。
+pub(crate) fn derive_encryption_key(
+    header: &FileHeader,
+    opts: &EncryptionOptions,
+) -> Result<(FileHeader, SecureBuffer)> {
+    log::info!("正在从密码派生密钥...");
+    let (m_cost, t_cost, p_cost) = (header.m_cost, header.t_cost, header.p_cost);
+    let argon2_params = Params::new(m_cost, t_cost, p_cost, Some(MASTER_KEY_LEN))
+        .map_err(|e| anyhow!("创建 Argon2 参数失败: {}", e))?;
+    let argon2 = Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        argon2_params,
+    );
+
+    // 根据是否有密钥文件，选择不同的密码材料
+    let password_material = if let Some(kf) = opts.keyfile {
+        log::info!("使用密钥文件增强安全性。");
+        // 解密时只警告低熵的密钥文件，加密新文件时拒绝使用它
+        kf.validate_entropy(MIN_KEYFILE_ENTROPY_BITS)
+            .context("拒绝使用这个密钥文件加密")?;
+        let mut combined = combine_password_and_keyfile(opts.password, kf)?;
+        let material = SecureBuffer::from_slice(&combined, opts.lock_memory);
+        combined.zeroize();
+        material
+    } else {
+        SecureBuffer::from_slice(opts.password.as_bytes(), opts.lock_memory)
+    };
+
+    // 调用者要求时，在当前格式的文件头中记录密钥文件承诺
+    let mut header = header.clone();
+    if opts.keyfile_commitment && opts.keyfile.is_some() && header.is_authenticated() {
+        header.set_keyfile_commitment(keyfile_commitment(&password_material)?);
+    }
+
+    // 使用 Argon2 进行密钥派生
+    let mut master_key = SecureBuffer::new(MASTER_KEY_LEN, opts.lock_memory);
+    secure_memory::ensure_locked(
+        &[&password_material, &master_key],
+        opts.lock_memory,
+        opts.strict,
+    )?;
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(
+            tracing::Level::INFO,
+            "argon2_derive",
+            m_cost,
+            t_cost,
+            p_cost
+        )
+        .entered();
+        argon2
+            .hash_password_into(&password_material, &header.salt, &mut master_key)
+            .map_err(|e| anyhow!("Argon2密钥派生失败: {}", e))?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!("密钥派生完成");
+    }
+
+    // 安全地擦除内存中的密码材料
+    drop(password_material);
+    log::info!("密钥派生完成。");
+    Ok((header, master_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_delete_source_keeps_file_modified_during_encryption() -> Result<()> {
+        let dir = tempdir()?;
+        let source = dir.path().join("growing.log");
+        fs::write(&source, b"encrypted part")?;
+        let snapshot = fs::symlink_metadata(&source)?;
+
+        // 加密期间追加的数据不在加密文件中，不能删除
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&source)?
+            .write_all(b" + appended later")?;
+        let error = delete_source(&source, &snapshot, BUFFER_LEN).unwrap_err();
+        assert!(error.to_string().contains("被修改过"));
+        assert_eq!(fs::read(&source)?, b"encrypted part + appended later");
+
+        let snapshot = fs::symlink_metadata(&source)?;
+        delete_source(&source, &snapshot, BUFFER_LEN)?;
+        assert!(!source.exists());
+        Ok(())
+    }
 }
@@ -0,0 +1,566 @@
+// src/error.rs
+
+//! # 错误类型模块 (Error Types Module)
+//!
+//! 库内部的流程统一使用 `anyhow` 传播错误并附加中文上下文；
+//! 该模块定义的 [`FeroxError`] 用于需要按类别区分错误的地方，
+//! 例如批量处理结果中的失败列表。
+//!
+//! 文件加密和解密流程返回的错误还带有出错的文件路径和操作阶段 ([`Stage`])，
+//! 阶段作为上下文附加在错误链中，可以用 [`Stage::of`] 取出。
+//!
+//! *Internal flows propagate errors through `anyhow` with context. [`FeroxError`]
+//! is used where callers need to distinguish error categories, such as the
+//! failure list of a batch result. File flows also attach the failing [`Stage`].*
+
+use crate::constants::CURRENT_FORMAT_VERSION;
+use crate::inspect::format_utc_time;
+use crate::watchdog::IoOperation;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// # Ferox 错误 (Ferox Error)
+///
+/// 按类别区分的错误类型。
+#[derive(Debug)]
+pub enum FeroxError {
+    /// 认证标签验证失败：密码或密钥文件错误，或文件已被篡改。
+    AuthenticationFailed,
+    /// 凭据表中适用于该文件的凭据都没有通过认证（见 [`crate::credentials`]）。
+    CredentialsExhausted {
+        /// 尝试过的凭据名称，以及各自失败或被排除的原因；为空表示没有适用于该文件的凭据。
+        attempted: Vec<String>,
+    },
+    /// 文件系统或 I/O 错误，消息包含完整的上下文链。
+    Io(io::Error),
+    /// 输入不是有效的加密文件（文件过短、文件头损坏等）。
+    InvalidFormat(String),
+    /// 文件头损坏：某个字段被截断、超出上限或与格式不符。
+    InvalidHeader {
+        /// 具体是哪个字段、为什么无效。
+        reason: String,
+    },
+    /// 文件使用了比当前程序更新的格式版本，需要升级程序才能处理。
+    UnsupportedVersion(u8),
+    /// 文件使用了当前程序不支持的算法或关键扩展，需要升级程序才能处理。
+    UnsupportedFeature(String),
+    /// 文件头记录了多个接收者，当前程序只支持单个接收者的文件。
+    MultiRecipientNotSupported {
+        /// 文件头中记录的接收者数量。
+        count: u8,
+    },
+    /// 文件已超过加密时设置的过期时间，拒绝解密。
+    FileExpired {
+        /// 文件的过期时间。
+        expired_at: SystemTime,
+    },
+    /// 文件超过了设置的大小上限，在任何密钥派生之前被拒绝。
+    FileTooLarge {
+        /// 文件的表观大小（字节）。
+        size: u64,
+        /// 设置的大小上限（字节）。
+        limit: u64,
+        /// 检测到稀疏文件时，文件实际占用的磁盘空间（字节）。
+        allocated: Option<u64>,
+    },
+    /// 路径不是普通文件（命名管道、设备节点或套接字），读取它可能无限期阻塞或永不结束。
+    SpecialFile {
+        /// 文件类型的描述，例如 “命名管道 (FIFO)”。
+        kind: &'static str,
+    },
+    /// 加密文件能够通过认证，但解密结果与对应的明文文件不一致。
+    ContentMismatch {
+        /// 第一个不同字节的偏移量。
+        offset: u64,
+    },
+    /// 输出路径与源文件指向同一个文件（符号链接、硬链接或大小写不敏感的文件系统），写入会毁掉源文件。
+    SourceEqualsTarget {
+        /// 源文件路径。
+        source_path: PathBuf,
+        /// 与源文件相同的输出路径。
+        target_path: PathBuf,
+    },
+    /// 数据流处理期间超过设置的时间没有任何读写进展，已放弃该文件。
+    Stalled {
+        /// 停滞的文件。
+        path: PathBuf,
+        /// 停滞时源文件中已经读取到的位置。
+        offset: u64,
+        /// 卡住的读写操作。
+        operation: IoOperation,
+        /// 停滞了多久。
+        idle: Duration,
+    },
+    /// 文件正被其他程序以不共享的方式打开或锁定（Windows 上的共享冲突，OS 错误 32 / 33），
+    /// 消息包含完整的上下文链。关闭占用它的程序后重试通常就能成功。
+    FileLocked(String),
+    /// 严格模式下被当作错误的警告（例如无法恢复扩展属性、扫描时无法读取的目录），消息包含完整的上下文链。
+    StrictViolation(String),
+    /// 处理文件时发生了 panic（程序缺陷），消息包含 panic 信息和完整的上下文链。
+    /// 批量处理会把它记为该文件的失败并继续处理其余文件。
+    Panicked(String),
+    /// 其他错误，消息包含完整的上下文链。
+    Other(String),
+}
+
+impl FeroxError {
+    /// 是否为认证失败（包括凭据表中的凭据都未通过认证）。
+    pub fn is_authentication_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::AuthenticationFailed | Self::CredentialsExhausted { .. }
+        )
+    }
+
+    /// 是否需要升级程序才能处理（格式版本、算法、关键扩展或多个接收者不受支持）。
+    pub fn requires_upgrade(&self) -> bool {
+        matches!(
+            self,
+            Self::UnsupportedVersion(_)
+                | Self::UnsupportedFeature(_)
+                | Self::MultiRecipientNotSupported { .. }
+        )
+    }
+
+    /// 是否为 I/O 错误。
+    pub fn is_io(&self) -> bool {
+        matches!(self, Self::Io(_))
+    }
+
+    /// 是否因为文件正被其他程序占用而失败。
+    pub fn is_file_locked(&self) -> bool {
+        matches!(self, Self::FileLocked(_))
+    }
+
+    /// 是否为严格模式下被当作错误的警告。
+    pub fn is_strict_violation(&self) -> bool {
+        matches!(self, Self::StrictViolation(_))
+    }
+
+    /// 是否因为处理时发生 panic 而失败。
+    pub fn is_panic(&self) -> bool {
+        matches!(self, Self::Panicked(_))
+    }
+
+    /// 把流程返回的 `anyhow::Error` 归类为 `FeroxError`。
+    ///
+    /// 错误链中带有类型化的 `FeroxError`（包括作为 `io::Error` 内部错误的）时以它为准；
+    /// 否则，链中存在 `io::Error` 时归为 [`FeroxError::Io`]（共享冲突归为 [`FeroxError::FileLocked`]），
+    /// 其余归为 [`FeroxError::Other`]。
+    /// 除认证失败外，消息都保留完整的上下文链。
+    pub(crate) fn classify(error: &anyhow::Error) -> Self {
+        let message = format!("{error:#}");
+        for cause in error.chain() {
+            let typed = cause.downcast_ref::<FeroxError>().or_else(|| {
+                cause
+                    .downcast_ref::<io::Error>()?
+                    .get_ref()?
+                    .downcast_ref::<FeroxError>()
+            });
+            if let Some(typed) = typed {
+                return match typed {
+                    Self::AuthenticationFailed => Self::AuthenticationFailed,
+                    Self::CredentialsExhausted { attempted } => Self::CredentialsExhausted {
+                        attempted: attempted.clone(),
+                    },
+                    Self::Io(e) if is_sharing_violation(e) => Self::FileLocked(message),
+                    Self::Io(e) => Self::Io(io::Error::new(e.kind(), message)),
+                    Self::InvalidFormat(_) => Self::InvalidFormat(message),
+                    Self::InvalidHeader { reason } => Self::InvalidHeader {
+                        reason: reason.clone(),
+                    },
+                    Self::UnsupportedVersion(version) => Self::UnsupportedVersion(*version),
+                    Self::UnsupportedFeature(_) => Self::UnsupportedFeature(message),
+                    Self::MultiRecipientNotSupported { count } => {
+                        Self::MultiRecipientNotSupported { count: *count }
+                    }
+                    Self::FileExpired { expired_at } => Self::FileExpired {
+                        expired_at: *expired_at,
+                    },
+                    Self::FileTooLarge {
+                        size,
+                        limit,
+                        allocated,
+                    } => Self::FileTooLarge {
+                        size: *size,
+                        limit: *limit,
+                        allocated: *allocated,
+                    },
+                    Self::SpecialFile { kind } => Self::SpecialFile { kind },
+                    Self::ContentMismatch { offset } => Self::ContentMismatch { offset: *offset },
+                    Self::SourceEqualsTarget {
+                        source_path,
+                        target_path,
+                    } => Self::SourceEqualsTarget {
+                        source_path: source_path.clone(),
+                        target_path: target_path.clone(),
+                    },
+                    Self::Stalled {
+                        path,
+                        offset,
+                        operation,
+                        idle,
+                    } => Self::Stalled {
+                        path: path.clone(),
+                        offset: *offset,
+                        operation: *operation,
+                        idle: *idle,
+                    },
+                    Self::FileLocked(_) => Self::FileLocked(message),
+                    Self::StrictViolation(_) => Self::StrictViolation(message),
+                    Self::Panicked(_) => Self::Panicked(message),
+                    Self::Other(_) => Self::Other(message),
+                };
+            }
+        }
+        match error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<io::Error>())
+        {
+            Some(e) if is_sharing_violation(e) => Self::FileLocked(message),
+            Some(e) => Self::Io(io::Error::new(e.kind(), message)),
+            None => Self::Other(message),
+        }
+    }
+}
+
+impl fmt::Display for FeroxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AuthenticationFailed => {
+                write!(f, "严重错误: 认证失败! 文件可能已损坏，或密码/密钥文件错误。")
+            }
+            Self::CredentialsExhausted { attempted } if attempted.is_empty() => {
+                write!(f, "认证失败: 没有适用于该文件的凭据。")
+            }
+            Self::CredentialsExhausted { attempted } => write!(
+                f,
+                "认证失败: 所有适用的凭据都无法解密该文件 (已尝试: {})。",
+                attempted.join(", ")
+            ),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::InvalidHeader { reason } => write!(f, "文件头无效: {reason}"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "不支持的文件格式版本 {version} (当前程序最高支持版本 {CURRENT_FORMAT_VERSION})，请升级 Ferox Encryptor。"
+            ),
+            Self::MultiRecipientNotSupported { count } => write!(
+                f,
+                "文件有 {count} 个接收者，当前程序只支持单个接收者的文件，请升级 Ferox Encryptor。"
+            ),
+            Self::FileExpired { expired_at } => write!(
+                f,
+                "文件已于 {} 过期，拒绝解密。",
+                format_utc_time(*expired_at)
+            ),
+            Self::FileTooLarge {
+                size,
+                limit,
+                allocated,
+            } => {
+                write!(f, "文件大小 {size} 字节超过上限 {limit} 字节，已拒绝处理。")?;
+                if let Some(allocated) = allocated {
+                    write!(
+                        f,
+                        " 这是一个稀疏文件 (实际只占用 {allocated} 字节磁盘空间)，\
+                         但加密仍会读取并写出全部 {size} 字节。"
+                    )?;
+                }
+                Ok(())
+            }
+            Self::SpecialFile { kind } => {
+                write!(f, "不是普通文件而是{kind}，已拒绝处理。")
+            }
+            Self::ContentMismatch { offset } => {
+                write!(f, "解密结果与明文文件不一致，第一个不同的字节位于偏移量 {offset}。")
+            }
+            Self::SourceEqualsTarget {
+                source_path,
+                target_path,
+            } => write!(
+                f,
+                "输出路径 {} 与源文件 {} 是同一个文件，已拒绝加密。",
+                target_path.display(),
+                source_path.display()
+            ),
+            Self::Stalled {
+                path,
+                offset,
+                operation,
+                idle,
+            } => write!(
+                f,
+                "{} 在{operation}时停滞了 {} 秒 (源文件偏移量 {offset})，已放弃该文件。",
+                path.display(),
+                idle.as_secs()
+            ),
+            Self::InvalidFormat(message)
+            | Self::UnsupportedFeature(message)
+            | Self::FileLocked(message)
+            | Self::StrictViolation(message)
+            | Self::Panicked(message)
+            | Self::Other(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl Error for FeroxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FeroxError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<argon2::Error> for FeroxError {
+    fn from(error: argon2::Error) -> Self {
+        Self::Other(format!("Argon2 密钥派生失败: {error}"))
+    }
+}
+
+impl From<hmac::digest::InvalidLength> for FeroxError {
+    fn from(error: hmac::digest::InvalidLength) -> Self {
+        Self::Other(format!("HMAC 密钥长度无效: {error}"))
+    }
+}
+
+impl From<glob::PatternError> for FeroxError {
+    fn from(error: glob::PatternError) -> Self {
+        Self::Other(format!("无效的通配符模式: {error}"))
+    }
+}
+
+/// 发出警告；`strict` 为 `true` 时改为返回 [`FeroxError::StrictViolation`]。
+///
+/// 用于那些默认只警告、严格模式 (`--strict`) 下必须失败的情况。
+pub(crate) fn warn_or_fail(strict: bool, message: String) -> Result<(), FeroxError> {
+    if strict {
+        return Err(FeroxError::StrictViolation(format!("严格模式: {message}")));
+    }
+    log::warn!("⚠️  {message}");
+    Ok(())
+}
+
+/// 错误是否为共享冲突：文件正被其他程序以不共享的方式打开 (`ERROR_SHARING_VIOLATION`)，
+/// 或者要访问的区域被其他程序锁定 (`ERROR_LOCK_VIOLATION`)。
+///
+/// 只有 Windows 会强制这类锁；其他平台上的建议锁不会使打开或读写失败，始终返回 `false`。
+pub(crate) fn is_sharing_violation(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+        const ERROR_LOCK_VIOLATION: i32 = 33;
+        matches!(
+            error.raw_os_error(),
+            Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)
+        )
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = error;
+        false
+    }
+}
+
+/// # 操作阶段 (Operation Stage)
+///
+/// 文件加密或解密流程中出错的阶段，作为上下文附加在流程返回的 `anyhow::Error` 上。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// 验证输入、打开文件，以及读取并解析（解密）或构建并写入（加密）文件头。
+    HeaderParse,
+    /// 从密码（和密钥文件）派生密钥。
+    KeyDerivation,
+    /// 流式加密或解密数据。
+    Streaming {
+        /// 出错之前已经处理的字节数。
+        bytes_done: u64,
+    },
+    /// 写入或验证认证标签、刷新并提交输出文件，以及之后的收尾工作。
+    Finalize,
+}
+
+impl Stage {
+    /// 错误链中记录的操作阶段；错误不是由文件流程产生时返回 `None`。
+    pub fn of(error: &anyhow::Error) -> Option<Self> {
+        error.downcast_ref::<Self>().copied()
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HeaderParse => write!(f, "文件头阶段"),
+            Self::KeyDerivation => write!(f, "密钥派生阶段"),
+            Self::Streaming { bytes_done } => write!(f, "流式处理阶段 (已处理 {bytes_done} 字节)"),
+            Self::Finalize => write!(f, "完成阶段"),
+        }
+    }
+}
+
+/// 为错误标记操作阶段。
+pub(crate) trait StageContext<T> {
+    /// 错误还没有记录阶段时附加 `stage`；已经记录了更内层的阶段时保持不变。
+    fn at_stage(self, stage: Stage) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> StageContext<T> for Result<T, E> {
+    fn at_stage(self, stage: Stage) -> anyhow::Result<T> {
+        self.map_err(|error| {
+            let error = error.into();
+            if Stage::of(&error).is_some() {
+                error
+            } else {
+                error.context(stage)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 这些转换让 `?` 可以在返回 `Result<_, FeroxError>` 的函数中直接使用。
+    fn open_missing() -> Result<(), FeroxError> {
+        std::fs::File::open("/nonexistent/ferox/missing.feroxcrypt")?;
+        Ok(())
+    }
+
+    fn parse_pattern() -> Result<glob::Pattern, FeroxError> {
+        Ok(glob::Pattern::new("[unclosed")?)
+    }
+
+    #[test]
+    fn test_conversions_keep_category_and_source() {
+        let error = open_missing().unwrap_err();
+        assert!(error.is_io());
+        assert!(error
+            .source()
+            .and_then(|source| source.downcast_ref::<io::Error>())
+            .is_some_and(|e| e.kind() == io::ErrorKind::NotFound));
+
+        let error = parse_pattern().unwrap_err();
+        assert!(matches!(error, FeroxError::Other(ref message) if message.contains("通配符")));
+
+        let error = FeroxError::from(argon2::Params::new(0, 0, 0, None).unwrap_err());
+        assert!(error.to_string().contains("Argon2"));
+    }
+
+    #[test]
+    fn test_innermost_stage_wins_and_category_survives() {
+        let result: Result<(), FeroxError> = Err(FeroxError::AuthenticationFailed);
+        let error = result
+            .at_stage(Stage::Finalize)
+            .at_stage(Stage::HeaderParse)
+            .unwrap_err();
+        assert_eq!(Stage::of(&error), Some(Stage::Finalize));
+        assert!(FeroxError::classify(&error).is_authentication_failure());
+        assert_eq!(Stage::of(&anyhow::anyhow!("plain")), None);
+    }
+
+    #[test]
+    fn test_sharing_violation_is_file_locked_only_on_windows() {
+        // 32 在 Windows 上是 ERROR_SHARING_VIOLATION，在 Unix 上是 EPIPE
+        let error = anyhow::Error::new(io::Error::from_raw_os_error(32)).context("无法打开源文件");
+        let classified = FeroxError::classify(&error);
+        assert_eq!(classified.is_file_locked(), cfg!(windows));
+        assert_eq!(classified.is_io(), !cfg!(windows));
+        assert!(classified.to_string().contains("无法打开源文件"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_exclusively_opened_file_is_classified_as_locked() -> anyhow::Result<()> {
+        use anyhow::Context;
+        use std::os::windows::fs::OpenOptionsExt;
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("mailbox.pst");
+        std::fs::write(&path, b"open in another application")?;
+        let _exclusive = std::fs::OpenOptions::new()
+            .read(true)
+            .share_mode(0)
+            .open(&path)?;
+
+        let error = std::fs::File::open(&path)
+            .context("无法打开源文件")
+            .unwrap_err();
+        assert!(FeroxError::classify(&error).is_file_locked());
+        Ok(())
+    }
+
+    /// 写入 `budget` 字节之后每次写入都失败的输出。
+    struct FailingWriter {
+        budget: usize,
+    }
+
+    impl io::Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.budget < buf.len() {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "磁盘已满"));
+            }
+            self.budget -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encryption_stream_reports_failing_stage() -> anyhow::Result<()> {
+        use crate::constants::{IV_LEN, SALT_LEN};
+        use crate::encrypt::{encrypt_stream_with_header, EncryptionOptions};
+        use crate::format::FileHeader;
+
+        let plaintext = [0x42u8; 100];
+        let opts = EncryptionOptions::new("stage-password");
+        let encrypt = |params, budget| {
+            let header = FileHeader::new("a.txt", [1; SALT_LEN], [2; IV_LEN], params);
+            encrypt_stream_with_header(
+                &mut &plaintext[..],
+                &mut FailingWriter { budget },
+                &header,
+                &opts,
+                &mut |_| {},
+            )
+            .unwrap_err()
+        };
+        let params = (8, 1, 1);
+        let header_len = FileHeader::new("a.txt", [1; SALT_LEN], [2; IV_LEN], params)
+            .encode()?
+            .len();
+
+        // Argon2 要求 m_cost 至少为 8 KiB
+        assert_eq!(
+            Stage::of(&encrypt((1, 1, 1), usize::MAX)),
+            Some(Stage::KeyDerivation)
+        );
+        assert_eq!(Stage::of(&encrypt(params, 0)), Some(Stage::HeaderParse));
+        let streaming = encrypt(params, header_len);
+        assert!(
+            matches!(Stage::of(&streaming), Some(Stage::Streaming { bytes_done }) if bytes_done <= 100),
+            "{streaming:#}"
+        );
+        assert!(FeroxError::classify(&streaming).is_io());
+        assert_eq!(
+            Stage::of(&encrypt(params, header_len + plaintext.len())),
+            Some(Stage::Finalize)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,749 @@
+// src/format.rs
+
+//! # 文件格式模块 (File Format Module)
+//!
+//! 该模块负责加密文件头的编码和解析，并识别文件的格式版本。
+//!
+//! - **版本 0 (遗留格式)**: 没有魔数和版本字段，文件直接以文件名长度开头，
+//!   认证标签只覆盖密文。
+//! - **版本 1**: 以魔数 `FXC\0` 开头，随后是版本号、算法标识、
+//!   原有的各个字段以及一个 TLV 扩展区；认证标签同时覆盖整个文件头和密文。
+//! - **版本 2 (当前格式)**: 与版本 1 相同，但在扩展区之前多出 1 字节的接收者数量，
+//!   为以后的多接收者加密预留。当前程序只支持单个接收者。
+//!
+//! 遗留文件的前两个字节是文件名长度，魔数 `FXC\0` 对应的文件名会以 `C\0` 开头，
+//! 而文件名中不可能包含 NUL 字符，因此两种格式可以被无歧义地区分。
+//!
+//! *This module encodes and parses encrypted file headers and detects the format
+//! version. Version 0 is the unversioned legacy layout; version 1 starts with the
+//! `FXC\0` magic, adds algorithm identifiers and a TLV extension area, and its tag
+//! also authenticates the header. Version 2 adds a recipient count.*
+
+use crate::constants::{
+    header_size_for_filename, legacy_header_size_for_filename, CIPHER_AES256_CTR_HMAC_SHA256,
+    CURRENT_FORMAT_VERSION, EXPIRY_TIME_LEN, FILE_MODE_LEN, FORMAT_MAGIC, IV_LEN, KDF_ARGON2ID,
+    KEYFILE_COMMITMENT_LEN, LEGACY_FORMAT_VERSION, MAX_HEADER_EXTENSIONS_LEN,
+    RECIPIENT_COUNT_FORMAT_VERSION, RECIPIENT_COUNT_LEN, SALT_LEN, SECURITY_LEVEL_CUSTOM,
+    SECURITY_LEVEL_LEN, SOURCE_MTIME_LEN,
+};
+use crate::error::FeroxError;
+use crate::header_parser::{parse_header, validate_filename};
+use crate::permissions::PERMISSION_BITS;
+use crate::xattrs::{self, ExtendedAttribute};
+use crate::Level;
+use anyhow::{bail, Result};
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 扩展类型中的"关键"标志位。
+///
+/// 不认识的关键扩展会导致解析失败；不认识的非关键扩展会被忽略（但仍参与认证）。
+pub(crate) const EXTENSION_CRITICAL_BIT: u16 = 0x8000;
+
+/// 扩展类型：密钥文件承诺（非关键，32 字节）。
+///
+/// 加密时使用了密钥文件的文件会记录 `HMAC-SHA256(密码材料, "FEROX_KEYFILE_COMMIT")`，
+/// 用于在不解密整个文件的情况下确认密钥文件是否匹配。
+pub(crate) const EXTENSION_KEYFILE_COMMITMENT: u16 = 0x0001;
+
+/// 扩展类型：过期时间（关键，8 字节小端序 Unix 时间戳）。
+///
+/// 标记为关键扩展，不认识它的旧版本程序会拒绝解密，而不是忽略过期时间。
+pub(crate) const EXTENSION_EXPIRY_TIME: u16 = EXTENSION_CRITICAL_BIT | 0x0002;
+
+/// 扩展类型：源文件的扩展属性（非关键，可变长度，见 [`crate::xattrs`]）。
+///
+/// 不认识它的旧版本程序照常解密，只是不恢复扩展属性。
+pub(crate) const EXTENSION_XATTRS: u16 = 0x0003;
+
+/// 扩展类型：源文件的 Unix 权限位（非关键，4 字节小端序，见 [`crate::permissions`]）。
+///
+/// 只在保留扩展属性时记录；解密时按 [`OutputPermissions::FromHeader`] 使用。
+///
+/// [`OutputPermissions::FromHeader`]: crate::decrypt::OutputPermissions::FromHeader
+pub(crate) const EXTENSION_FILE_MODE: u16 = 0x0004;
+
+/// 扩展类型：产生 Argon2 参数的安全级别（非关键，1 字节，见 [`SECURITY_LEVEL_LEN`](crate::constants::SECURITY_LEVEL_LEN)）。
+///
+/// 让 `info` 和批量报告显示用户认识的级别名称而不是原始参数。没有该扩展的旧文件按参数推断级别。
+pub(crate) const EXTENSION_SECURITY_LEVEL: u16 = 0x0005;
+
+/// 扩展类型：加密时源文件的修改时间（非关键，[`SOURCE_MTIME_LEN`](crate::constants::SOURCE_MTIME_LEN) 字节）。
+///
+/// 只在 [`EncryptionOptions::record_source_mtime`] 时记录，用于重新运行批量加密时判断已有的加密文件
+/// 是否仍然对应源文件。
+///
+/// [`EncryptionOptions::record_source_mtime`]: crate::encrypt::EncryptionOptions::record_source_mtime
+pub(crate) const EXTENSION_SOURCE_MTIME: u16 = 0x0006;
+
+/// 本版本认识的扩展类型的名称和值的固定长度（`None` 表示长度可变）；不认识的类型返回 `None`。
+///
+/// 不在其中的非关键扩展来自更新版本的程序，解析时被忽略（见 [`FileHeader::ignored_extensions`]），
+/// 不在其中的关键扩展使解析失败。
+pub(crate) fn known_extension(kind: u16) -> Option<(&'static str, Option<usize>)> {
+    match kind {
+        EXTENSION_KEYFILE_COMMITMENT => Some(("密钥文件承诺", Some(KEYFILE_COMMITMENT_LEN))),
+        EXTENSION_EXPIRY_TIME => Some(("过期时间", Some(EXPIRY_TIME_LEN))),
+        EXTENSION_XATTRS => Some(("扩展属性", None)),
+        EXTENSION_FILE_MODE => Some(("权限位", Some(FILE_MODE_LEN))),
+        EXTENSION_SECURITY_LEVEL => Some(("安全级别", Some(SECURITY_LEVEL_LEN))),
+        EXTENSION_SOURCE_MTIME => Some(("源文件修改时间", Some(SOURCE_MTIME_LEN))),
+        _ => None,
+    }
+}
+
+/// 文件头扩展区中的一条 TLV 记录：`类型 (u16) | 长度 (u16) | 值`。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HeaderExtension {
+    /// 扩展类型，最高位为关键标志。
+    pub(crate) kind: u16,
+    /// 扩展的原始数据。
+    pub(crate) value: Vec<u8>,
+}
+
+impl HeaderExtension {
+    /// 解析器不认识该扩展时是否必须拒绝整个文件。
+    pub(crate) fn is_critical(&self) -> bool {
+        self.kind & EXTENSION_CRITICAL_BIT != 0
+    }
+
+    /// 该记录编码后占用的字节数。
+    fn encoded_len(&self) -> usize {
+        4 + self.value.len()
+    }
+}
+
+/// # 加密套件 (Cipher Suite)
+///
+/// 文件头中加密算法标识对应的加密和认证方式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CipherSuite {
+    /// AES-256-CTR 加密 + HMAC-SHA256 认证 (Encrypt-then-MAC)。
+    #[default]
+    Aes256CtrHmacSha256,
+}
+
+impl CipherSuite {
+    /// 由文件头中的加密算法标识解析加密套件，不认识的标识返回需要升级程序的错误。
+    pub fn from_header_byte(b: u8) -> Result<Self, FeroxError> {
+        match b {
+            CIPHER_AES256_CTR_HMAC_SHA256 => Ok(Self::Aes256CtrHmacSha256),
+            _ => Err(FeroxError::UnsupportedFeature(format!(
+                "不支持的加密算法标识: {b}"
+            ))),
+        }
+    }
+
+    /// 写入文件头的加密算法标识。
+    pub fn header_byte(&self) -> u8 {
+        match self {
+            Self::Aes256CtrHmacSha256 => CIPHER_AES256_CTR_HMAC_SHA256,
+        }
+    }
+
+    /// 用于界面显示的名称。
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Aes256CtrHmacSha256 => "AES-256-CTR + HMAC-SHA256",
+        }
+    }
+
+    /// 用于界面显示的相对安全评分 (0-100)，当前默认套件为 90。
+    pub fn security_rating(&self) -> u8 {
+        match self {
+            Self::Aes256CtrHmacSha256 => 90,
+        }
+    }
+
+    /// 是否为同时完成加密和认证的 AEAD 算法；否则认证标签由单独的 HMAC 计算。
+    pub fn is_aead(&self) -> bool {
+        match self {
+            Self::Aes256CtrHmacSha256 => false,
+        }
+    }
+}
+
+/// 加密文件的文件头。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileHeader {
+    /// 文件格式版本，遗留格式为 0。
+    pub(crate) version: u8,
+    /// 加密算法标识（遗留格式中没有该字段，取默认算法）。
+    pub(crate) cipher_id: u8,
+    /// 密钥派生算法标识（遗留格式中没有该字段，取默认算法）。
+    pub(crate) kdf_id: u8,
+    /// 加密前的原始文件名。
+    pub(crate) original_filename: String,
+    /// Argon2 密钥派生使用的盐。
+    pub(crate) salt: [u8; SALT_LEN],
+    /// AES-CTR 的初始化向量。
+    pub(crate) iv: [u8; IV_LEN],
+    /// Argon2 内存成本 (KiB)。
+    pub(crate) m_cost: u32,
+    /// Argon2 时间成本 (迭代次数)。
+    pub(crate) t_cost: u32,
+    /// Argon2 并行度。
+    pub(crate) p_cost: u32,
+    /// 接收者数量（版本 2 之前的格式中没有该字段，取 1）。
+    pub(crate) recipient_count: u8,
+    /// 扩展区中的记录，按文件中的顺序排列。遗留格式中始终为空。
+    pub(crate) extensions: Vec<HeaderExtension>,
+}
+
+impl FileHeader {
+    /// 使用当前格式版本和默认算法创建一个没有扩展的文件头。
+    pub(crate) fn new(
+        original_filename: &str,
+        salt: [u8; SALT_LEN],
+        iv: [u8; IV_LEN],
+        (m_cost, t_cost, p_cost): (u32, u32, u32),
+    ) -> Self {
+        Self {
+            version: CURRENT_FORMAT_VERSION,
+            cipher_id: CIPHER_AES256_CTR_HMAC_SHA256,
+            kdf_id: KDF_ARGON2ID,
+            original_filename: original_filename.to_string(),
+            salt,
+            iv,
+            m_cost,
+            t_cost,
+            p_cost,
+            recipient_count: 1,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// 文件头在加密数据中占用的字节数。
+    pub(crate) fn len(&self) -> u64 {
+        let filename_len = self.original_filename.len();
+        if self.version == LEGACY_FORMAT_VERSION {
+            legacy_header_size_for_filename(filename_len) as u64
+        } else if !self.has_recipient_count() {
+            (header_size_for_filename(filename_len) - RECIPIENT_COUNT_LEN + self.extensions_len())
+                as u64
+        } else {
+            (header_size_for_filename(filename_len) + self.extensions_len()) as u64
+        }
+    }
+
+    /// 该版本的文件头是否包含接收者数量字段。
+    pub(crate) fn has_recipient_count(&self) -> bool {
+        self.version >= RECIPIENT_COUNT_FORMAT_VERSION
+    }
+
+    /// 认证标签是否同时覆盖文件头（遗留格式只覆盖密文）。
+    pub(crate) fn is_authenticated(&self) -> bool {
+        self.version != LEGACY_FORMAT_VERSION
+    }
+
+    /// 把文件头编码为写入文件的字节序列。
+    ///
+    /// 对解析得到的文件头重新编码会得到与文件中完全相同的字节，
+    /// 因此解密时可以用它来计算文件头的认证标签。
+    pub(crate) fn encode(&self) -> Result<Vec<u8>> {
+        validate_filename(&self.original_filename)?;
+        let filename_bytes = self.original_filename.as_bytes();
+
+        let mut out = Vec::with_capacity(self.len() as usize);
+        if self.version != LEGACY_FORMAT_VERSION {
+            out.extend_from_slice(&FORMAT_MAGIC);
+            out.extend_from_slice(&[self.version, self.cipher_id, self.kdf_id]);
+        }
+        // 顺序: 文件名长度 -> 文件名 -> 盐 -> IV -> Argon2参数
+        out.extend_from_slice(&(filename_bytes.len() as u16).to_le_bytes());
+        out.extend_from_slice(filename_bytes);
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&self.iv);
+        out.extend_from_slice(&self.m_cost.to_le_bytes());
+        out.extend_from_slice(&self.t_cost.to_le_bytes());
+        out.extend_from_slice(&self.p_cost.to_le_bytes());
+
+        if self.has_recipient_count() {
+            out.push(self.recipient_count);
+        } else if self.recipient_count != 1 {
+            bail!("格式版本 {} 的文件头不支持多个接收者", self.version);
+        }
+
+        if self.version != LEGACY_FORMAT_VERSION {
+            let extensions_len = self.extensions_len();
+            if extensions_len > MAX_HEADER_EXTENSIONS_LEN {
+                bail!("文件头扩展区太大了 (超过{MAX_HEADER_EXTENSIONS_LEN}字节)");
+            }
+            out.extend_from_slice(&(extensions_len as u16).to_le_bytes());
+            for extension in &self.extensions {
+                out.extend_from_slice(&extension.kind.to_le_bytes());
+                out.extend_from_slice(&(extension.value.len() as u16).to_le_bytes());
+                out.extend_from_slice(&extension.value);
+            }
+        } else if !self.extensions.is_empty() {
+            bail!("遗留格式的文件头不支持扩展");
+        }
+        Ok(out)
+    }
+
+    /// 从 `reader` 中读取并解析文件头，自动识别格式版本。
+    ///
+    /// 解析由 [`header_parser`](crate::header_parser) 完成：文件头损坏时返回
+    /// [`FeroxError::InvalidHeader`]，高于 [`CURRENT_FORMAT_VERSION`] 的版本返回
+    /// [`FeroxError::UnsupportedVersion`]。
+    pub(crate) fn read_from<R: Read>(reader: &mut R) -> Result<Self> {
+        parse_header(reader)
+    }
+
+    /// 文件头中的加密套件。
+    pub(crate) fn cipher_suite(&self) -> Result<CipherSuite, FeroxError> {
+        CipherSuite::from_header_byte(self.cipher_id)
+    }
+
+    /// 文件头中记录的密钥文件承诺；加密时未使用密钥文件（或为遗留格式）时为 `None`。
+    pub(crate) fn keyfile_commitment(&self) -> Option<[u8; KEYFILE_COMMITMENT_LEN]> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.kind == EXTENSION_KEYFILE_COMMITMENT)
+            .and_then(|extension| extension.value.as_slice().try_into().ok())
+    }
+
+    /// 设置密钥文件承诺，替换已有的记录。
+    pub(crate) fn set_keyfile_commitment(&mut self, commitment: [u8; KEYFILE_COMMITMENT_LEN]) {
+        self.extensions
+            .retain(|extension| extension.kind != EXTENSION_KEYFILE_COMMITMENT);
+        self.extensions.push(HeaderExtension {
+            kind: EXTENSION_KEYFILE_COMMITMENT,
+            value: commitment.to_vec(),
+        });
+    }
+
+    /// 文件的过期时间（Unix 时间戳，秒）；0 表示永不过期。
+    pub(crate) fn expiry_time(&self) -> u64 {
+        self.extensions
+            .iter()
+            .find(|extension| extension.kind == EXTENSION_EXPIRY_TIME)
+            .and_then(|extension| extension.value.as_slice().try_into().ok())
+            .map_or(0, u64::from_le_bytes)
+    }
+
+    /// 设置过期时间，替换已有的记录；0 表示移除过期时间。
+    pub(crate) fn set_expiry_time(&mut self, expiry_time: u64) {
+        self.extensions
+            .retain(|extension| extension.kind != EXTENSION_EXPIRY_TIME);
+        if expiry_time > 0 {
+            self.extensions.push(HeaderExtension {
+                kind: EXTENSION_EXPIRY_TIME,
+                value: expiry_time.to_le_bytes().to_vec(),
+            });
+        }
+    }
+
+    /// 本版本不认识、解析时被忽略的非关键扩展的类型（按出现顺序）。
+    ///
+    /// 这类扩展由更新版本的程序写入（例如注释、附加元数据或填充），仍然参与认证，只是不起作用。
+    pub(crate) fn ignored_extensions(&self) -> Vec<u16> {
+        self.extensions
+            .iter()
+            .map(|extension| extension.kind)
+            .filter(|&kind| known_extension(kind).is_none())
+            .collect()
+    }
+
+    /// 加密时源文件的修改时间；没有记录时为 `None`。
+    pub(crate) fn source_mtime(&self) -> Option<SystemTime> {
+        let value = self
+            .extensions
+            .iter()
+            .find(|extension| extension.kind == EXTENSION_SOURCE_MTIME)?
+            .value
+            .as_slice();
+        let (secs, nanos) = value.split_first_chunk::<8>()?;
+        let nanos: [u8; 4] = nanos.try_into().ok()?;
+        let nanos = u32::from_le_bytes(nanos);
+        (nanos < 1_000_000_000)
+            .then(|| UNIX_EPOCH + Duration::new(u64::from_le_bytes(*secs), nanos))
+    }
+
+    /// 设置源文件的修改时间，替换已有的记录；`None` 或早于 Unix 纪元的时间表示移除记录。
+    pub(crate) fn set_source_mtime(&mut self, mtime: Option<SystemTime>) {
+        self.extensions
+            .retain(|extension| extension.kind != EXTENSION_SOURCE_MTIME);
+        if let Some(since_epoch) = mtime.and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok()) {
+            let mut value = since_epoch.as_secs().to_le_bytes().to_vec();
+            value.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+            self.extensions.push(HeaderExtension {
+                kind: EXTENSION_SOURCE_MTIME,
+                value,
+            });
+        }
+    }
+
+    /// 文件头中记录的扩展属性；没有记录时为空。
+    pub(crate) fn xattrs(&self) -> Vec<ExtendedAttribute> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.kind == EXTENSION_XATTRS)
+            .and_then(|extension| xattrs::decode(&extension.value).ok())
+            .unwrap_or_default()
+    }
+
+    /// 设置扩展属性，替换已有的记录；`attributes` 为空时移除记录。
+    ///
+    /// 为之后加入的密钥文件承诺预留空间，放不下的属性会被跳过；`strict` 时改为返回错误。
+    pub(crate) fn set_xattrs(
+        &mut self,
+        attributes: &[ExtendedAttribute],
+        strict: bool,
+    ) -> Result<(), FeroxError> {
+        self.extensions
+            .retain(|extension| extension.kind != EXTENSION_XATTRS);
+        let reserved = self.extensions_len() + 4 + (4 + KEYFILE_COMMITMENT_LEN);
+        let value = xattrs::encode(
+            attributes,
+            MAX_HEADER_EXTENSIONS_LEN.saturating_sub(reserved),
+            strict,
+        )?;
+        if !value.is_empty() {
+            self.extensions.push(HeaderExtension {
+                kind: EXTENSION_XATTRS,
+                value,
+            });
+        }
+        Ok(())
+    }
+
+    /// 文件头中记录的源文件权限位；没有记录时为 `None`。
+    pub(crate) fn file_mode(&self) -> Option<u32> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.kind == EXTENSION_FILE_MODE)
+            .and_then(|extension| extension.value.as_slice().try_into().ok())
+            .map(|bytes| u32::from_le_bytes(bytes) & PERMISSION_BITS)
+    }
+
+    /// 设置源文件权限位，替换已有的记录；`None` 表示移除记录。
+    pub(crate) fn set_file_mode(&mut self, mode: Option<u32>) {
+        self.extensions
+            .retain(|extension| extension.kind != EXTENSION_FILE_MODE);
+        if let Some(mode) = mode {
+            self.extensions.push(HeaderExtension {
+                kind: EXTENSION_FILE_MODE,
+                value: (mode & PERMISSION_BITS).to_le_bytes().to_vec(),
+            });
+        }
+    }
+
+    /// 产生文件头中 Argon2 参数的安全级别；参数是自定义的组合时为 `None`（显示为 `Custom`）。
+    ///
+    /// 优先使用安全级别扩展中记录的值，但只有在它与实际参数一致时才采用；
+    /// 没有该扩展的旧文件按参数推断级别。
+    pub(crate) fn security_level(&self) -> Option<Level> {
+        let params = (self.m_cost, self.t_cost, self.p_cost);
+        match self
+            .extensions
+            .iter()
+            .find(|extension| extension.kind == EXTENSION_SECURITY_LEVEL)
+            .and_then(|extension| extension.value.first())
+        {
+            Some(&byte) => {
+                Level::from_header_byte(byte).filter(|level| level.argon2_params() == params)
+            }
+            None => Level::from_argon2_params(params),
+        }
+    }
+
+    /// 设置安全级别，替换已有的记录；`None` 记录为自定义参数。
+    pub(crate) fn set_security_level(&mut self, level: Option<Level>) {
+        self.extensions
+            .retain(|extension| extension.kind != EXTENSION_SECURITY_LEVEL);
+        let byte = level.map_or(SECURITY_LEVEL_CUSTOM, |level| level.header_byte());
+        self.extensions.push(HeaderExtension {
+            kind: EXTENSION_SECURITY_LEVEL,
+            value: vec![byte],
+        });
+    }
+
+    /// 扩展区内容（不含长度字段）编码后的字节数。
+    fn extensions_len(&self) -> usize {
+        self.extensions
+            .iter()
+            .map(HeaderExtension::encoded_len)
+            .sum()
+    }
+}
+
+/// # 文件格式前导信息 (Format Preamble)
+///
+/// 文件头开头的格式版本和算法标识，不需要解析完整的文件头即可读取。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FormatPreamble {
+    /// 文件格式版本，遗留格式为 0。
+    pub version: u8,
+    /// 加密算法标识（遗留格式中取默认算法）。
+    pub cipher_id: u8,
+    /// 密钥派生算法标识（遗留格式中取默认算法）。
+    pub kdf_id: u8,
+}
+
+impl FormatPreamble {
+    /// 当前程序能否处理该格式版本和算法组合。
+    pub fn is_supported(&self) -> bool {
+        self.ensure_supported().is_ok()
+    }
+
+    /// 检查格式版本和算法标识，不支持时返回需要升级程序的错误。
+    pub(crate) fn ensure_supported(&self) -> Result<(), FeroxError> {
+        if self.version > CURRENT_FORMAT_VERSION {
+            return Err(FeroxError::UnsupportedVersion(self.version));
+        }
+        CipherSuite::from_header_byte(self.cipher_id)?;
+        if self.kdf_id != KDF_ARGON2ID {
+            return Err(FeroxError::UnsupportedFeature(format!(
+                "不支持的密钥派生算法标识: {}",
+                self.kdf_id
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// 读取文件头开头的格式版本和算法标识。
+///
+/// 版本化文件只读取前 7 个字节；遗留文件只读取前 4 个字节，算法标识取默认值。
+/// 不会检查版本或算法是否受支持。
+pub fn read_format_preamble<R: Read>(reader: &mut R) -> Result<FormatPreamble> {
+    let mut prefix = [0u8; FORMAT_MAGIC.len()];
+    reader
+        .read_exact(&mut prefix)
+        .map_err(|_| FeroxError::InvalidFormat("文件太短，无法读取文件头".to_string()))?;
+    if prefix != FORMAT_MAGIC {
+        return Ok(FormatPreamble {
+            version: LEGACY_FORMAT_VERSION,
+            cipher_id: CIPHER_AES256_CTR_HMAC_SHA256,
+            kdf_id: KDF_ARGON2ID,
+        });
+    }
+    let mut preamble = [0u8; 3];
+    reader
+        .read_exact(&mut preamble)
+        .map_err(|_| FeroxError::InvalidFormat("文件太短，无法读取文件格式版本".to_string()))?;
+    let [version, cipher_id, kdf_id] = preamble;
+    Ok(FormatPreamble {
+        version,
+        cipher_id,
+        kdf_id,
+    })
+}
+
+/// 读取文件的格式版本：以魔数开头时返回其后的版本字节，否则返回 [`LEGACY_FORMAT_VERSION`]。
+///
+/// 只读取前 5 个字节，不会解析或验证文件头的其余部分。
+///
+/// *Reads only the first 5 bytes (magic + version).*
+pub fn read_format_version<R: Read>(reader: &mut R) -> Result<u8> {
+    let mut prefix = [0u8; FORMAT_MAGIC.len() + 1];
+    reader
+        .read_exact(&mut prefix)
+        .map_err(|_| FeroxError::InvalidFormat("文件太短，无法读取文件格式版本".to_string()))?;
+    if prefix[..FORMAT_MAGIC.len()] == FORMAT_MAGIC {
+        Ok(prefix[FORMAT_MAGIC.len()])
+    } else {
+        Ok(LEGACY_FORMAT_VERSION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_header() -> FileHeader {
+        FileHeader::new(
+            "报告.pdf",
+            [7u8; SALT_LEN],
+            [9u8; IV_LEN],
+            (19 * 1024, 2, 1),
+        )
+    }
+
+    #[test]
+    fn test_header_roundtrip_for_each_version() -> Result<()> {
+        let mut current = sample_header();
+        current.extensions.push(HeaderExtension {
+            kind: 0x0042,
+            value: b"ignored".to_vec(),
+        });
+        let mut legacy = sample_header();
+        legacy.version = LEGACY_FORMAT_VERSION;
+
+        for header in [current, legacy] {
+            let bytes = header.encode()?;
+            assert_eq!(bytes.len() as u64, header.len());
+            assert_eq!(read_format_version(&mut bytes.as_slice())?, header.version);
+
+            let mut reader = bytes.as_slice();
+            assert_eq!(FileHeader::read_from(&mut reader)?, header);
+            assert!(reader.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_rejects_unknown_versions_and_critical_extensions() -> Result<()> {
+        let mut bytes = sample_header().encode()?;
+        bytes[FORMAT_MAGIC.len()] = CURRENT_FORMAT_VERSION + 1;
+        let err = FileHeader::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FeroxError>(),
+            Some(FeroxError::UnsupportedVersion(v)) if *v == CURRENT_FORMAT_VERSION + 1
+        ));
+
+        let mut header = sample_header();
+        header.extensions.push(HeaderExtension {
+            kind: EXTENSION_CRITICAL_BIT | 0x0042,
+            value: Vec::new(),
+        });
+        let err = FileHeader::read_from(&mut header.encode()?.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("关键扩展"));
+        Ok(())
+    }
+
+    /// 用 `tests/golden_tests.rs` 中相同的密码、明文、盐和 IV 按 `header` 加密，结果必须与提交的
+    /// 黄金文件 `name` 完全相同。设置 `FEROX_UPDATE_GOLDEN=1` 时重新生成。
+    fn assert_matches_golden(name: &str, header: &FileHeader) -> Result<()> {
+        use crate::encrypt::{encrypt_stream_with_header, EncryptionOptions};
+
+        let opts = EncryptionOptions::new("golden-fixture-password");
+        let mut encrypted = Vec::new();
+        encrypt_stream_with_header(
+            &mut &b"Ferox Encryptor golden fixture.\nThe bytes of this file must never change.\n"[..],
+            &mut encrypted,
+            header,
+            &opts,
+            &mut |_| {},
+        )?;
+
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/golden")
+            .join(name);
+        if std::env::var_os("FEROX_UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, &encrypted)?;
+        }
+        assert!(
+            encrypted == std::fs::read(&path)?,
+            "{name} 的字节发生了变化"
+        );
+        Ok(())
+    }
+
+    fn golden_header() -> FileHeader {
+        FileHeader::new(
+            "golden.txt",
+            [0x5a; SALT_LEN],
+            [0xa5; IV_LEN],
+            Level::Interactive.argon2_params(),
+        )
+    }
+
+    /// 遗留格式 (v0) 的黄金文件。
+    #[test]
+    fn test_legacy_format_matches_golden_bytes() -> Result<()> {
+        let mut header = golden_header();
+        header.version = LEGACY_FORMAT_VERSION;
+        assert_matches_golden("v0_legacy.feroxcrypt", &header)
+    }
+
+    /// 模拟更新版本的程序写入的黄金文件：`v2_future_ignorable` 带有一个本版本不认识的非关键扩展
+    /// (0x0f01，例如注释)，`v2_future_critical` 带有一个不认识的关键扩展 (0x8f01)。
+    /// 本版本对它们的处理（带警告解密、拒绝解密）由 `tests/golden_tests.rs` 检查。
+    #[test]
+    fn test_future_extension_fixtures_match_golden_bytes() -> Result<()> {
+        let cases = [
+            (
+                "v2_future_ignorable.feroxcrypt",
+                0x0f01,
+                &b"comment from a newer version"[..],
+            ),
+            (
+                "v2_future_critical.feroxcrypt",
+                EXTENSION_CRITICAL_BIT | 0x0f01,
+                &b"must understand"[..],
+            ),
+        ];
+        for (name, kind, value) in cases {
+            let mut header = golden_header();
+            header.set_security_level(Some(Level::Interactive));
+            header.extensions.push(HeaderExtension {
+                kind,
+                value: value.to_vec(),
+            });
+            assert_matches_golden(name, &header)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cipher_suite_header_byte_roundtrip() -> Result<()> {
+        let suite = CipherSuite::from_header_byte(CIPHER_AES256_CTR_HMAC_SHA256)?;
+        assert_eq!(suite, CipherSuite::default());
+        assert_eq!(suite.header_byte(), CIPHER_AES256_CTR_HMAC_SHA256);
+        assert_eq!(suite.display_name(), "AES-256-CTR + HMAC-SHA256");
+        assert_eq!(suite.security_rating(), 90);
+        assert!(!suite.is_aead());
+        assert_eq!(sample_header().cipher_suite()?, suite);
+
+        assert!(matches!(
+            CipherSuite::from_header_byte(0xEE),
+            Err(FeroxError::UnsupportedFeature(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_security_level_roundtrip_and_fallbacks() -> Result<()> {
+        for level in Level::ALL {
+            let mut header = FileHeader::new(
+                "a.txt",
+                [1u8; SALT_LEN],
+                [2u8; IV_LEN],
+                level.argon2_params(),
+            );
+            header.set_security_level(Some(level));
+            let parsed = FileHeader::read_from(&mut header.encode()?.as_slice())?;
+            assert_eq!(parsed.security_level(), Some(level));
+            assert_eq!(
+                parsed.security_level().unwrap().name(),
+                format!("{level:?}")
+            );
+        }
+
+        // 没有记录的旧文件按参数推断，自定义参数为 Custom
+        let mut header = sample_header();
+        assert_eq!(header.security_level(), Some(Level::Interactive));
+        header.m_cost += 1;
+        assert_eq!(header.security_level(), None);
+
+        // 记录与参数不一致或是未知的值时都按 Custom 处理
+        let mut header = sample_header();
+        header.set_security_level(Some(Level::Paranoid));
+        assert_eq!(header.security_level(), None);
+        header.set_security_level(None);
+        assert_eq!(header.extensions.len(), 1);
+        assert_eq!(header.security_level(), None);
+        header.extensions[0].value = vec![0x7f];
+        assert_eq!(header.security_level(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_mtime_roundtrip() -> Result<()> {
+        let mtime = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        let mut header = sample_header();
+        assert_eq!(header.source_mtime(), None);
+        header.set_source_mtime(Some(mtime));
+        let parsed = FileHeader::read_from(&mut header.encode()?.as_slice())?;
+        assert_eq!(parsed.source_mtime(), Some(mtime));
+
+        // 早于 Unix 纪元的时间不记录
+        header.set_source_mtime(Some(UNIX_EPOCH - Duration::from_secs(1)));
+        assert_eq!(header.source_mtime(), None);
+        assert!(header.extensions.is_empty());
+        Ok(())
+    }
+}
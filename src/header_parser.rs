@@ -0,0 +1,420 @@
+// src/header_parser.rs
+
+//! # 文件头解析模块 (Header Parser)
+//!
+//! 从不可信的输入中解析加密文件头。每个字段都有显式的上限：变长字段在分配缓冲区之前
+//! 与上限比较，扩展区中每条记录的长度都与剩余字节比较，Argon2 参数必须落在
+//! [`MAX_HEADER_ARGON2_M_COST`] 等常量规定的范围内。
+//!
+//! 任何输入都不会导致 panic。结构错误返回 [`FeroxError::InvalidHeader`]；
+//! 格式版本、算法或关键扩展不受支持时返回 [`FeroxError::UnsupportedVersion`] 或
+//! [`FeroxError::UnsupportedFeature`]，多个接收者返回 [`FeroxError::MultiRecipientNotSupported`]；只有底层读取失败才返回 I/O 错误。
+//!
+//! *Parses encrypted file headers from untrusted input. Every field is checked against an
+//! explicit bound before it is used or allocated, and no input can cause a panic: malformed
+//! headers yield [`FeroxError::InvalidHeader`], unsupported versions or features yield the
+//! corresponding upgrade errors.*
+
+use crate::constants::{
+    CIPHER_AES256_CTR_HMAC_SHA256, FORMAT_MAGIC, IV_LEN, KDF_ARGON2ID, LEGACY_FORMAT_VERSION,
+    MAX_HEADER_ARGON2_M_COST, MAX_HEADER_ARGON2_P_COST, MAX_HEADER_ARGON2_T_COST,
+    MAX_HEADER_EXTENSIONS_LEN, MAX_HEADER_FILENAME_LEN, SALT_LEN,
+};
+use crate::error::FeroxError;
+use crate::format::{
+    known_extension, FileHeader, FormatPreamble, HeaderExtension, EXTENSION_XATTRS,
+};
+use crate::xattrs;
+use anyhow::{Context, Result};
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+/// 从 `reader` 中读取并解析文件头，自动识别格式版本。
+///
+/// 只读取文件头本身，`reader` 停在密文的第一个字节。
+pub(crate) fn parse_header<R: Read>(reader: &mut R) -> Result<FileHeader> {
+    let mut fields = FieldReader(reader);
+    let prefix: [u8; FORMAT_MAGIC.len()] = fields.array("文件头")?;
+
+    if prefix != FORMAT_MAGIC {
+        // 遗留格式：已读取的 4 个字节属于文件名长度和文件名。
+        // 遗留格式只使用过默认算法，因此算法标识取默认值。
+        let mut legacy = FieldReader(Cursor::new(prefix).chain(fields.0));
+        return legacy.header_fields(
+            LEGACY_FORMAT_VERSION,
+            CIPHER_AES256_CTR_HMAC_SHA256,
+            KDF_ARGON2ID,
+        );
+    }
+
+    let [version, cipher_id, kdf_id] = fields.array("文件格式版本")?;
+    if version == LEGACY_FORMAT_VERSION {
+        return Err(invalid("带有魔数的文件头不能使用遗留格式版本 0").into());
+    }
+    FormatPreamble {
+        version,
+        cipher_id,
+        kdf_id,
+    }
+    .ensure_supported()?;
+
+    let mut header = fields.header_fields(version, cipher_id, kdf_id)?;
+    if header.has_recipient_count() {
+        header.recipient_count = fields.recipient_count()?;
+    }
+    header.extensions = fields.extensions()?;
+    Ok(header)
+}
+
+/// 检查原始文件名能否写入文件头：非空、不超过 [`MAX_HEADER_FILENAME_LEN`]、
+/// 不含 NUL，并且是单个路径成分（不能是 `..` 或包含路径分隔符）。
+///
+/// 解析和编码使用同一套规则，编码成功的文件头一定能被解析。
+pub(crate) fn validate_filename(name: &str) -> Result<(), FeroxError> {
+    if name.is_empty() {
+        return Err(invalid("原始文件名为空"));
+    }
+    if name.len() > MAX_HEADER_FILENAME_LEN {
+        return Err(invalid(format!(
+            "原始文件名太长: {} 字节 (上限 {MAX_HEADER_FILENAME_LEN} 字节)",
+            name.len()
+        )));
+    }
+    // 遗留格式与版本化格式的区分依赖于文件名中不含 NUL
+    if name.contains('\0') {
+        return Err(invalid("原始文件名包含 NUL 字符"));
+    }
+    if Path::new(name).file_name() != Some(name.as_ref()) {
+        return Err(invalid(format!("原始文件名包含路径成分: {name:?}")));
+    }
+    Ok(())
+}
+
+/// 创建一个 [`FeroxError::InvalidHeader`]。
+fn invalid(reason: impl Into<String>) -> FeroxError {
+    FeroxError::InvalidHeader {
+        reason: reason.into(),
+    }
+}
+
+/// 按字段读取文件头，输入提前结束时报告被截断的字段。
+struct FieldReader<R>(R);
+
+impl<R: Read> FieldReader<R> {
+    /// 按照加密时写入的顺序读取各版本共有的字段。
+    fn header_fields(&mut self, version: u8, cipher_id: u8, kdf_id: u8) -> Result<FileHeader> {
+        let filename_len = usize::from(self.u16("文件名长度")?);
+        if filename_len > MAX_HEADER_FILENAME_LEN {
+            return Err(invalid(format!(
+                "文件名长度 {filename_len} 字节超过上限 {MAX_HEADER_FILENAME_LEN} 字节"
+            ))
+            .into());
+        }
+        let filename_bytes = self.bytes(filename_len, "文件名")?;
+        let original_filename =
+            String::from_utf8(filename_bytes).map_err(|_| invalid("文件名包含无效的UTF-8字符"))?;
+        validate_filename(&original_filename)?;
+
+        // 读取密码学元数据，必须严格按照加密时写入的顺序来读取
+        let salt: [u8; SALT_LEN] = self.array("Salt")?;
+        let iv: [u8; IV_LEN] = self.array("IV")?;
+
+        let m_cost = self.u32("Argon2 m_cost")?;
+        let t_cost = self.u32("Argon2 t_cost")?;
+        let p_cost = self.u32("Argon2 p_cost")?;
+        validate_argon2_params(m_cost, t_cost, p_cost)?;
+
+        Ok(FileHeader {
+            version,
+            cipher_id,
+            kdf_id,
+            original_filename,
+            salt,
+            iv,
+            m_cost,
+            t_cost,
+            p_cost,
+            recipient_count: 1,
+            extensions: Vec::new(),
+        })
+    }
+
+    /// 读取接收者数量：必须至少为 1，当前程序只支持单个接收者。
+    fn recipient_count(&mut self) -> Result<u8> {
+        let [count] = self.array("接收者数量")?;
+        match count {
+            0 => Err(invalid("接收者数量为 0").into()),
+            1 => Ok(count),
+            _ => Err(FeroxError::MultiRecipientNotSupported { count }.into()),
+        }
+    }
+
+    /// 读取扩展区：`总长度 (u16)`，随后是若干条 TLV 记录。
+    ///
+    /// 记录必须恰好填满扩展区；已知扩展的长度必须正确且最多出现一次，
+    /// 不认识的关键扩展会导致解析失败。
+    fn extensions(&mut self) -> Result<Vec<HeaderExtension>> {
+        let area_len = usize::from(self.u16("文件头扩展区长度")?);
+        if area_len > MAX_HEADER_EXTENSIONS_LEN {
+            return Err(invalid(format!(
+                "文件头扩展区 {area_len} 字节超过上限 {MAX_HEADER_EXTENSIONS_LEN} 字节"
+            ))
+            .into());
+        }
+        let area = self.bytes(area_len, "文件头扩展区")?;
+
+        let mut extensions: Vec<HeaderExtension> = Vec::new();
+        let mut rest = area.as_slice();
+        while !rest.is_empty() {
+            let Some((record_header, after)) = rest.split_first_chunk::<4>() else {
+                return Err(invalid("文件头扩展记录不完整").into());
+            };
+            let kind = u16::from_le_bytes([record_header[0], record_header[1]]);
+            let value_len = usize::from(u16::from_le_bytes([record_header[2], record_header[3]]));
+            if value_len > after.len() {
+                return Err(invalid(format!(
+                    "文件头扩展记录 (类型 0x{kind:04x}) 的长度 {value_len} 超出扩展区剩余的 {} 字节",
+                    after.len()
+                ))
+                .into());
+            }
+            let (value, after) = after.split_at(value_len);
+            let extension = HeaderExtension {
+                kind,
+                value: value.to_vec(),
+            };
+
+            match known_extension(kind) {
+                Some((name, expected_len)) => {
+                    if expected_len.is_some_and(|len| value_len != len) {
+                        return Err(invalid(format!("{name}的长度无效: {value_len} 字节")).into());
+                    }
+                    if kind == EXTENSION_XATTRS {
+                        xattrs::decode(value)?;
+                    }
+                    if extensions.iter().any(|extension| extension.kind == kind) {
+                        return Err(invalid(format!("{name}扩展重复出现")).into());
+                    }
+                }
+                None if extension.is_critical() => {
+                    return Err(FeroxError::UnsupportedFeature(format!(
+                        "文件使用了不支持的关键扩展 (类型 0x{kind:04x})，请升级 Ferox Encryptor"
+                    ))
+                    .into());
+                }
+                // 可忽略的扩展照常保留，解密流程据此发出警告，见 `FileHeader::ignored_extensions`
+                None => log::debug!("忽略未知的文件头扩展 (类型 0x{kind:04x})"),
+            }
+            extensions.push(extension);
+            rest = after;
+        }
+        Ok(extensions)
+    }
+
+    /// 读取一个小端序 `u16` 字段。
+    fn u16(&mut self, field: &str) -> Result<u16> {
+        self.array(field).map(u16::from_le_bytes)
+    }
+
+    /// 读取一个小端序 `u32` 字段。
+    fn u32(&mut self, field: &str) -> Result<u32> {
+        self.array(field).map(u32::from_le_bytes)
+    }
+
+    /// 读取一个定长字段。
+    fn array<const N: usize>(&mut self, field: &str) -> Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.fill(&mut buf, field)?;
+        Ok(buf)
+    }
+
+    /// 读取一个长度已经与上限比较过的变长字段。
+    fn bytes(&mut self, len: usize, field: &str) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.fill(&mut buf, field)?;
+        Ok(buf)
+    }
+
+    /// 填满 `buf`；输入提前结束时返回 [`FeroxError::InvalidHeader`]。
+    fn fill(&mut self, buf: &mut [u8], field: &str) -> Result<()> {
+        match self.0.read_exact(buf) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(invalid(format!("文件太短，{field}被截断")).into())
+            }
+            Err(e) => Err(e).with_context(|| format!("无法读取{field}")),
+        }
+    }
+}
+
+/// 检查 Argon2 参数是否落在 Argon2 本身和本程序规定的范围内。
+///
+/// 这些参数在认证之前就决定了密钥派生的开销，因此必须先于密钥派生检查。
+fn validate_argon2_params(m_cost: u32, t_cost: u32, p_cost: u32) -> Result<(), FeroxError> {
+    if !(1..=MAX_HEADER_ARGON2_P_COST).contains(&p_cost) {
+        return Err(invalid(format!(
+            "Argon2 p_cost={p_cost} 超出范围 1..={MAX_HEADER_ARGON2_P_COST}"
+        )));
+    }
+    if !(1..=MAX_HEADER_ARGON2_T_COST).contains(&t_cost) {
+        return Err(invalid(format!(
+            "Argon2 t_cost={t_cost} 超出范围 1..={MAX_HEADER_ARGON2_T_COST}"
+        )));
+    }
+    // Argon2 要求每条并行通道至少 8 KiB
+    let min_m_cost = 8 * p_cost;
+    if !(min_m_cost..=MAX_HEADER_ARGON2_M_COST).contains(&m_cost) {
+        return Err(invalid(format!(
+            "Argon2 m_cost={m_cost} KiB 超出范围 {min_m_cost}..={MAX_HEADER_ARGON2_M_COST}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{CURRENT_FORMAT_VERSION, FORMAT_PREAMBLE_LEN, KEYFILE_COMMITMENT_LEN};
+    use proptest::prelude::*;
+
+    fn sample_header(filename: &str, expiry_time: u64) -> FileHeader {
+        let mut header =
+            FileHeader::new(filename, [7u8; SALT_LEN], [9u8; IV_LEN], (19 * 1024, 2, 1));
+        header.set_keyfile_commitment([3u8; KEYFILE_COMMITMENT_LEN]);
+        header.set_expiry_time(expiry_time);
+        header
+    }
+
+    /// 解析结果只能是成功，或者是类型化的 `FeroxError`（从不 panic，也不会是未分类的错误）。
+    fn assert_typed_outcome(bytes: &[u8]) {
+        if let Err(e) = parse_header(&mut &bytes[..]) {
+            assert!(
+                matches!(
+                    e.downcast_ref::<FeroxError>(),
+                    Some(
+                        FeroxError::InvalidHeader { .. }
+                            | FeroxError::UnsupportedVersion(_)
+                            | FeroxError::UnsupportedFeature(_)
+                            | FeroxError::MultiRecipientNotSupported { .. }
+                    )
+                ),
+                "解析 {bytes:02x?} 返回了未分类的错误: {e:#}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_rejects_out_of_bounds_fields() -> Result<()> {
+        let valid = sample_header("report.txt", 0).encode()?;
+        let filename_len_at = FORMAT_PREAMBLE_LEN;
+        let argon2_at = filename_len_at + 2 + "report.txt".len() + SALT_LEN + IV_LEN;
+
+        let mut cases: Vec<(Vec<u8>, &str)> = Vec::new();
+        let mut oversized_name = valid.clone();
+        oversized_name[filename_len_at..filename_len_at + 2]
+            .copy_from_slice(&u16::MAX.to_le_bytes());
+        cases.push((oversized_name, "文件名长度"));
+        let mut traversal = valid.clone();
+        traversal[filename_len_at + 2..filename_len_at + 4].copy_from_slice(b"..");
+        traversal[filename_len_at..filename_len_at + 2].copy_from_slice(&2u16.to_le_bytes());
+        traversal.drain(filename_len_at + 4..filename_len_at + 2 + "report.txt".len());
+        cases.push((traversal, "路径成分"));
+        let mut huge_memory = valid.clone();
+        huge_memory[argon2_at..argon2_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        cases.push((huge_memory, "m_cost"));
+        let mut zero_lanes = valid.clone();
+        zero_lanes[argon2_at + 8..argon2_at + 12].copy_from_slice(&0u32.to_le_bytes());
+        cases.push((zero_lanes, "p_cost"));
+        cases.push((valid[..valid.len() - 1].to_vec(), "被截断"));
+
+        for (bytes, expected) in cases {
+            let err = parse_header(&mut bytes.as_slice()).unwrap_err();
+            match err.downcast_ref::<FeroxError>() {
+                Some(FeroxError::InvalidHeader { reason }) => {
+                    assert!(reason.contains(expected), "{reason}")
+                }
+                other => panic!("期望 InvalidHeader ({expected})，得到 {other:?}"),
+            }
+        }
+
+        // 已知扩展重复出现时拒绝，而不是静默地取其中一条
+        let mut duplicated = sample_header("report.txt", 1_700_000_000);
+        let expiry = duplicated.extensions.last().cloned().unwrap();
+        duplicated.extensions.push(expiry);
+        let err = parse_header(&mut duplicated.encode()?.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("重复"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_recipient_count_is_gated_by_version() -> Result<()> {
+        // 版本 1 没有接收者数量字段
+        let mut v1 = sample_header("report.txt", 0);
+        v1.version = 1;
+        let bytes = v1.encode()?;
+        assert_eq!(bytes.len() as u64 + 1, sample_header("report.txt", 0).len());
+        assert_eq!(parse_header(&mut bytes.as_slice())?, v1);
+        v1.recipient_count = 2;
+        assert!(v1.encode().is_err());
+
+        let mut multi = sample_header("report.txt", 0);
+        multi.recipient_count = 3;
+        let err = parse_header(&mut multi.encode()?.as_slice()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FeroxError>(),
+            Some(FeroxError::MultiRecipientNotSupported { count: 3 })
+        ));
+
+        multi.recipient_count = 0;
+        let err = parse_header(&mut multi.encode()?.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("接收者数量"), "{err}");
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(512))]
+
+        #[test]
+        fn prop_parser_never_panics_on_random_input(
+            bytes in prop::collection::vec(any::<u8>(), 0..256),
+            versioned in any::<bool>(),
+        ) {
+            // 一半的输入带有魔数和当前版本，使随机字节能够到达更深的字段
+            let mut input = Vec::new();
+            if versioned {
+                input.extend_from_slice(&FORMAT_MAGIC);
+                input.extend_from_slice(&[CURRENT_FORMAT_VERSION, CIPHER_AES256_CTR_HMAC_SHA256, KDF_ARGON2ID]);
+            }
+            input.extend_from_slice(&bytes);
+            assert_typed_outcome(&input);
+        }
+
+        #[test]
+        fn prop_parser_survives_mutated_valid_headers(
+            filename in "[a-zA-Z0-9._ -]{1,40}",
+            expiry_time in any::<u64>(),
+            legacy in any::<bool>(),
+            mutations in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+            truncate in prop::option::of(any::<prop::sample::Index>()),
+        ) {
+            prop_assume!(validate_filename(&filename).is_ok());
+            let mut header = sample_header(&filename, expiry_time);
+            if legacy {
+                header.version = LEGACY_FORMAT_VERSION;
+                header.extensions.clear();
+            }
+            let valid = header.encode().unwrap();
+            prop_assert_eq!(&parse_header(&mut valid.as_slice()).unwrap(), &header);
+
+            let mut mutated = valid;
+            for (index, byte) in mutations {
+                let at = index.index(mutated.len());
+                mutated[at] = byte;
+            }
+            if let Some(index) = truncate {
+                mutated.truncate(index.index(mutated.len()));
+            }
+            assert_typed_outcome(&mutated);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// src/hook.rs
+
+//! # 完成通知钩子模块 (Completion Hook)
+//!
+//! 无人值守的长时间批量操作结束时，运行一个用户指定的命令（例如发送 webhook 或邮件）。
+//! 命令通过平台的 shell 执行（Unix 上为 `sh -c`，Windows 上为 `cmd /C`），标准输入是与
+//! [`BatchResult::to_json`] 相同的 JSON 摘要，另外带有 `operation` 和 `duration_secs` 字段；
+//! 成功和失败数量等基本字段同时以 `FEROX_*` 环境变量提供。
+//!
+//! 钩子只来自明确的配置：命令行参数 `--on-complete`，或用 `--on-complete-config` 指定的
+//! TOML 配置文件（见 [`CompletionHook::load`]），不会从继承的环境变量中读取。
+//!
+//! 钩子的输出写到标准错误，不会混入标准输出上的报告。钩子失败（无法启动、非零退出或超时）
+//! 只返回错误供调用者作为警告报告，不会改变批量操作的结果。钩子继承当前进程的环境变量，
+//! 但程序本身从不把密码放进环境变量；保存了密码的变量可以用 [`CompletionHook::without_env`] 移除。
+//!
+//! *Runs a user command after a batch finishes, with the JSON summary on stdin and the basic
+//! counters in `FEROX_*` environment variables. Hook failures and timeouts are reported to the
+//! caller as errors and never change the batch result.*
+
+use crate::batch::BatchResult;
+use crate::constants::ON_COMPLETE_TIMEOUT_SECS;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 等待钩子结束时检查的间隔。
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// # 完成通知钩子 (Completion Hook)
+///
+/// 批量操作结束后运行的命令，见[模块文档](self)。
+#[derive(Debug, Clone)]
+pub struct CompletionHook {
+    /// 交给 shell 执行的命令行。
+    command: String,
+    /// 等待钩子结束的最长时间，超时后终止它。
+    timeout: Duration,
+    /// 不传给钩子的环境变量。
+    removed_env: Vec<OsString>,
+}
+
+/// 钩子配置文件的内容。
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HookConfig {
+    /// 交给 shell 执行的命令行。
+    command: String,
+    /// 等待钩子结束的最长秒数。
+    timeout_secs: Option<u64>,
+}
+
+impl CompletionHook {
+    /// 从 TOML 配置文件加载钩子：
+    ///
+    /// ```toml
+    /// command = "curl -s --data-binary @- https://hooks.example.com/ferox"
+    /// timeout_secs = 60   # 可选，默认 ON_COMPLETE_TIMEOUT_SECS
+    /// ```
+    ///
+    /// # 错误
+    ///
+    /// 文件无法读取或解析、包含未知的字段、命令为空或超时为 0 时返回错误。
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("无法读取钩子配置文件: {}", path.display()))?;
+        let config: HookConfig = toml::from_str(&text)
+            .with_context(|| format!("无效的钩子配置文件: {}", path.display()))?;
+        if config.command.trim().is_empty() {
+            bail!("钩子配置文件 {} 中的命令为空", path.display());
+        }
+        let mut hook = Self::new(config.command);
+        match config.timeout_secs {
+            Some(0) => bail!("钩子配置文件 {} 中的超时必须大于 0", path.display()),
+            Some(secs) => hook = hook.with_timeout(Duration::from_secs(secs)),
+            None => {}
+        }
+        Ok(hook)
+    }
+
+    /// 创建一个钩子，超时为 [`ON_COMPLETE_TIMEOUT_SECS`] 秒。
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            timeout: Duration::from_secs(ON_COMPLETE_TIMEOUT_SECS),
+            removed_env: Vec::new(),
+        }
+    }
+
+    /// 设置等待钩子结束的最长时间。
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 不把环境变量 `name` 传给钩子，例如通过环境变量提供的密码。
+    pub fn without_env(mut self, name: impl Into<OsString>) -> Self {
+        self.removed_env.push(name.into());
+        self
+    }
+
+    /// 钩子的命令行。
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// 运行钩子并等待它结束。
+    ///
+    /// `operation` 是批量操作的名称（例如 `"batch-encrypt"`），`duration` 是操作耗费的时间。
+    ///
+    /// # 错误
+    ///
+    /// 钩子无法启动、以非零状态退出或超过超时时间（此时钩子被终止）时返回错误。
+    pub fn run(&self, operation: &str, result: &BatchResult, duration: Duration) -> Result<()> {
+        let payload = summary_json(operation, result, duration)?;
+
+        let mut command = shell_command(&self.command);
+        for name in &self.removed_env {
+            command.env_remove(name);
+        }
+        let mut child = command
+            .env("FEROX_OPERATION", operation)
+            .env("FEROX_SUCCESS_COUNT", result.success_count.to_string())
+            .env("FEROX_FAILURE_COUNT", result.failure_count.to_string())
+            .env("FEROX_SKIPPED_COUNT", result.skipped_count.to_string())
+            .env("FEROX_TOTAL_BYTES", result.total_bytes.to_string())
+            .env(
+                "FEROX_DURATION_SECS",
+                format!("{:.3}", duration.as_secs_f64()),
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::from(io::stderr()))
+            .spawn()
+            .with_context(|| format!("无法启动完成通知钩子: {}", self.command))?;
+
+        // 钩子不读取标准输入时写入会失败，这不算钩子的错误
+        let mut stdin = child.stdin.take().context("无法连接钩子的标准输入")?;
+        let writer = thread::spawn(move || {
+            let _ = stdin.write_all(&payload);
+        });
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait().context("无法等待完成通知钩子")? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!(
+                    "完成通知钩子在 {} 秒内没有结束，已终止: {}",
+                    self.timeout.as_secs(),
+                    self.command
+                );
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        let _ = writer.join();
+        if !status.success() {
+            bail!("完成通知钩子异常退出 ({status}): {}", self.command);
+        }
+        log::info!("🔔 完成通知钩子已运行: {}", self.command);
+        Ok(())
+    }
+}
+
+/// 钩子标准输入上的 JSON 摘要：[`BatchResult::to_json`] 的内容加上操作名称和耗时。
+fn summary_json(operation: &str, result: &BatchResult, duration: Duration) -> Result<Vec<u8>> {
+    let mut report = Vec::new();
+    result.to_json(&mut report)?;
+    let mut summary: serde_json::Value = serde_json::from_slice(&report)?;
+    summary["operation"] = operation.into();
+    summary["duration_secs"] = duration.as_secs_f64().into();
+    Ok(serde_json::to_vec_pretty(&summary)?)
+}
+
+/// 通过平台的 shell 执行 `command`。
+fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    }
+    #[cfg(not(windows))]
+    {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
@@ -0,0 +1,315 @@
+// src/index.rs
+
+//! # 加密索引模块 (Encrypted Index Module)
+//!
+//! 批量加密目录后，可以生成一个加密的文件名索引 (`vault.feroxidx`)，
+//! 之后不解密任何数据文件即可按原始文件名查找对应的加密文件。
+//!
+//! 索引中的每条记录把 `HMAC-SHA256(索引密钥, 原始文件名)` 映射到加密文件的相对路径；
+//! 索引密钥是随机生成的，和记录一起以普通的加密格式保存，因此查找时需要密码（和密钥文件）。
+//! 这只是文件名索引，只支持完整文件名的精确匹配，并不是通用的可搜索加密。
+//!
+//! *After batch-encrypting a directory, an encrypted filename index (`vault.feroxidx`)
+//! maps `HMAC-SHA256(index_key, original_filename)` to the encrypted output path, so
+//! files can be found by exact name without decrypting the vault.*
+
+use crate::{
+    batch::{batch_encrypt_directory, BatchConfig, BatchResult},
+    cleanup::{commit_partial_file, create_partial_file, partial_path_for},
+    constants::{
+        CUSTOM_FILE_EXTENSION, INDEX_FILE_NAME, INDEX_FORMAT_VERSION, INDEX_KEY_LEN, INDEX_MAGIC,
+    },
+    decrypt::{decrypt_in_memory, DecryptionOptions},
+    encrypt::{encrypt_in_memory, EncryptionOptions},
+    keyfile::KeyFile,
+};
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 索引中的一条记录。
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IndexEntry {
+    /// `HMAC-SHA256(索引密钥, 原始文件名)`。
+    filename_mac: [u8; 32],
+    /// 加密文件相对于索引所在目录的路径。
+    encrypted_path: PathBuf,
+}
+
+/// # 加密索引 (Encrypted Index)
+///
+/// 由 [`encrypt_directory_with_index`] 或 [`batch_encrypt_directory_with_index`]
+/// 生成并写入磁盘的文件名索引。
+pub struct EncryptedIndex {
+    /// 索引文件的路径。
+    path: PathBuf,
+    /// 计算文件名 HMAC 的随机密钥。
+    key: [u8; INDEX_KEY_LEN],
+    /// 索引记录。
+    entries: Vec<IndexEntry>,
+}
+
+impl EncryptedIndex {
+    /// 索引文件的路径。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 索引中的文件数量。
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 索引是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 计算文件名在该索引中的 HMAC。
+    fn filename_mac(&self, filename: &str) -> [u8; 32] {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC 接受任意长度的密钥");
+        mac.update(filename.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    /// 查找原始文件名为 `filename` 的所有加密文件，返回相对于索引目录的路径。
+    fn lookup(&self, filename: &str) -> Vec<&Path> {
+        let wanted = self.filename_mac(filename);
+        self.entries
+            .iter()
+            .filter(|entry| entry.filename_mac == wanted)
+            .map(|entry| entry.encrypted_path.as_path())
+            .collect()
+    }
+
+    /// 编码索引内容：魔数 | 版本 | 索引密钥 | 记录数 (u32) | 记录 (HMAC | 路径长度 u16 | 路径)。
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&INDEX_MAGIC);
+        out.push(INDEX_FORMAT_VERSION);
+        out.extend_from_slice(&self.key);
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for entry in &self.entries {
+            let path = entry
+                .encrypted_path
+                .to_str()
+                .context("加密文件路径包含无效的UTF-8字符")?;
+            if path.len() > u16::MAX as usize {
+                bail!("加密文件路径太长了: {path}");
+            }
+            out.extend_from_slice(&entry.filename_mac);
+            out.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            out.extend_from_slice(path.as_bytes());
+        }
+        Ok(out)
+    }
+
+    /// 解析 [`EncryptedIndex::encode`] 的输出。
+    fn decode(path: &Path, bytes: &[u8]) -> Result<Self> {
+        let mut rest = bytes;
+        let mut take = |len: usize| -> Result<&[u8]> {
+            if rest.len() < len {
+                bail!("索引内容不完整");
+            }
+            let (head, tail) = rest.split_at(len);
+            rest = tail;
+            Ok(head)
+        };
+
+        if take(INDEX_MAGIC.len())? != INDEX_MAGIC {
+            bail!("文件不是加密索引");
+        }
+        let version = take(1)?[0];
+        if version != INDEX_FORMAT_VERSION {
+            bail!("不支持的索引格式版本 {version}");
+        }
+        let mut key = [0u8; INDEX_KEY_LEN];
+        key.copy_from_slice(take(INDEX_KEY_LEN)?);
+        let count = u32::from_le_bytes(take(4)?.try_into()?);
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let mut filename_mac = [0u8; 32];
+            filename_mac.copy_from_slice(take(32)?);
+            let path_len = u16::from_le_bytes(take(2)?.try_into()?) as usize;
+            let encrypted_path =
+                std::str::from_utf8(take(path_len)?).context("索引中的路径不是有效的UTF-8")?;
+            entries.push(IndexEntry {
+                filename_mac,
+                encrypted_path: PathBuf::from(encrypted_path),
+            });
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            key,
+            entries,
+        })
+    }
+}
+
+impl Drop for EncryptedIndex {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// 加密目录中的文件（不递归），并在目录中生成加密的文件名索引 `vault.feroxidx`。
+///
+/// 索引只包含加密成功的文件。任何文件加密失败时，索引仍会写入，但返回错误；
+/// 需要逐个查看失败原因时请使用 [`batch_encrypt_directory_with_index`]。
+pub fn encrypt_directory_with_index(
+    dir: &Path,
+    opts: &EncryptionOptions,
+) -> Result<EncryptedIndex> {
+    let config = BatchConfig {
+        level: opts.level,
+        force_overwrite: opts.force_overwrite,
+        single_thread: opts.single_thread,
+        threads: opts.threads,
+        buffer_size: opts.buffer_size,
+        mmap: opts.mmap,
+        expires_at: opts.expires_at,
+        ..Default::default()
+    };
+    let (result, index) =
+        batch_encrypt_directory_with_index(dir, opts.password, opts.keyfile, &config)?;
+    if let Some((path, error)) = result.first_failure() {
+        bail!(
+            "{} 个文件加密失败，索引只包含成功加密的文件 (第一个失败: {}: {})",
+            result.failure_count,
+            path.display(),
+            error
+        );
+    }
+    Ok(index)
+}
+
+/// 按 `config` 批量加密目录，并在目录中生成加密的文件名索引 `vault.feroxidx`。
+///
+/// 返回批量处理结果和生成的索引；索引只包含加密成功的文件。
+/// 索引文件已存在且未设置 `force_overwrite` 时，在加密任何文件之前返回错误。
+pub fn batch_encrypt_directory_with_index(
+    dir: &Path,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<(BatchResult, EncryptedIndex)> {
+    let index_path = dir.join(INDEX_FILE_NAME);
+    if !config.force_overwrite && index_path.exists() {
+        bail!(
+            "索引文件 {} 已存在。如需覆盖，请使用 --force 标志。",
+            index_path.display()
+        );
+    }
+
+    let config = BatchConfig {
+        track_successful_paths: true,
+        ..config.clone()
+    };
+    let result = batch_encrypt_directory(dir, password, keyfile, &config)?;
+
+    let mut key = [0u8; INDEX_KEY_LEN];
+    OsRng.fill_bytes(&mut key);
+    let mut index = EncryptedIndex {
+        path: index_path,
+        key,
+        entries: Vec::new(),
+    };
+    key.zeroize();
+    // 加密结果已是最新而跳过的文件同样记录，重复运行时索引仍然完整
+    let indexed = result
+        .processed
+        .iter()
+        .filter(|(_, succeeded)| *succeeded)
+        .map(|(source, _)| source)
+        .chain(&result.skipped_up_to_date);
+    for source in indexed {
+        let Some(filename) = source.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let mut encrypted_path = source
+            .strip_prefix(dir)
+            .unwrap_or(source)
+            .as_os_str()
+            .to_owned();
+        encrypted_path.push(".");
+        encrypted_path.push(CUSTOM_FILE_EXTENSION);
+        let filename_mac = index.filename_mac(filename);
+        index.entries.push(IndexEntry {
+            filename_mac,
+            encrypted_path: PathBuf::from(encrypted_path),
+        });
+    }
+
+    let opts = EncryptionOptions {
+        keyfile,
+        level: config.level,
+        ..EncryptionOptions::new(password)
+    };
+    let mut plaintext = index.encode()?;
+    let encrypted = encrypt_in_memory(&plaintext, INDEX_FILE_NAME, &opts);
+    plaintext.zeroize();
+    write_index_file(&index.path, &encrypted?)?;
+
+    log::info!(
+        "已生成包含 {} 个文件的加密索引: {}",
+        index.len(),
+        index.path.display()
+    );
+    Ok((result, index))
+}
+
+/// 先写入带锁的 `.part` 文件，再原子地替换索引文件。
+fn write_index_file(index_path: &Path, data: &[u8]) -> Result<()> {
+    let part_path = partial_path_for(index_path);
+    let mut file = create_partial_file(&part_path)?;
+    let result = file
+        .write_all(data)
+        .context("写入索引文件失败")
+        .and_then(|()| file.sync_all().context("刷新索引文件失败"))
+        .and_then(|()| commit_partial_file(&part_path, index_path));
+    if result.is_err() {
+        let _ = fs::remove_file(&part_path);
+    }
+    result
+}
+
+/// 解密索引并查找原始文件名与 `search_term` 完全相同的加密文件。
+///
+/// 返回的路径以索引文件所在目录为基准；没有匹配时返回空列表。
+///
+/// # 错误
+///
+/// 索引文件无法读取、密码或密钥文件错误，或索引已损坏时返回错误。
+pub fn search_index(
+    index_path: &Path,
+    search_term: &str,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+) -> Result<Vec<PathBuf>> {
+    let encrypted = fs::read(index_path)
+        .with_context(|| format!("无法读取索引文件: {}", index_path.display()))?;
+    let opts = DecryptionOptions {
+        keyfile,
+        ..DecryptionOptions::new(password)
+    };
+    let (mut plaintext, _) = decrypt_in_memory(&encrypted, &opts)
+        .with_context(|| format!("无法解密索引文件: {}", index_path.display()))?;
+    let index = EncryptedIndex::decode(index_path, &plaintext);
+    plaintext.zeroize();
+    let index = index.with_context(|| format!("无效的索引文件: {}", index_path.display()))?;
+
+    let base = index_path.parent().unwrap_or(Path::new(""));
+    Ok(index
+        .lookup(search_term)
+        .into_iter()
+        .map(|path| base.join(path))
+        .collect())
+}
@@ -0,0 +1,930 @@
+// src/inspect.rs
+
+//! # 文件检查模块 (Inspection Module)
+//!
+//! 该模块提供不生成明文文件的只读操作：
+//! - 读取并展示加密文件的文件头信息（无需密码）。
+//! - 按文件格式版本统计目录中的加密文件（只读取每个文件开头的几个字节）。
+//! - 列出目录中的加密文件及其文件头参数（无需密码）。
+//! - 使用密码（和密钥文件）完整验证加密文件的认证标签，但不把明文写入磁盘。
+//! - 把加密文件的解密结果与磁盘上的明文文件逐块比较，同样不把明文写入磁盘。
+//!
+//! *This module provides read-only operations that never write plaintext to disk:
+//! inspecting the header of an encrypted file (no password required) and fully
+//! verifying its authentication tag with the password.*
+
+use crate::{
+    batch::{collect_files, BatchConfig},
+    constants::{
+        BUFFER_LEN, ESTIMATE_CALIBRATION_BYTES, LEGACY_FORMAT_VERSION, MASTER_KEY_LEN, SALT_LEN,
+        TAG_LEN,
+    },
+    decrypt::{ciphertext_len, decrypt_body, read_header, verify_body, DecryptionOptions},
+    encrypt::ensure_not_special_file,
+    error::FeroxError,
+    format::{read_format_preamble, read_format_version},
+    keyfile::KeyFile,
+    pipeline::ensure_valid_buffer_size,
+    progress::{ProgressEvent, ProgressSink},
+    Level,
+};
+use anyhow::{bail, Context, Result};
+use argon2::{Argon2, Params};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+/// # 加密文件信息 (Encrypted File Info)
+///
+/// 从加密文件的文件头中解析出的元数据。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    /// 加密文件的路径。
+    pub path: PathBuf,
+    /// 文件格式版本，遗留格式为 0。
+    pub format_version: u8,
+    /// 加密算法标识。
+    pub cipher_id: u8,
+    /// 密钥派生算法标识。
+    pub kdf_id: u8,
+    /// 文件头中记录的原始文件名。
+    pub original_filename: String,
+    /// Argon2 内存成本 (KiB)。
+    pub m_cost: u32,
+    /// Argon2 时间成本 (迭代次数)。
+    pub t_cost: u32,
+    /// Argon2 并行度。
+    pub p_cost: u32,
+    /// 产生 Argon2 参数的安全级别：取自文件头记录的级别，旧文件按参数推断；自定义参数时为 `None`。
+    pub level: Option<Level>,
+    /// 文件头中是否记录了密钥文件承诺（即加密时使用了密钥文件）。
+    ///
+    /// 遗留格式的文件没有该字段，即使使用了密钥文件也为 `false`。
+    pub has_keyfile_commitment: bool,
+    /// 加密时设置的过期时间；没有设置时为 `None`。
+    pub expires_at: Option<SystemTime>,
+    /// 加密时记录的源文件修改时间（见 [`EncryptionOptions::record_source_mtime`]）；没有记录时为 `None`。
+    ///
+    /// [`EncryptionOptions::record_source_mtime`]: crate::encrypt::EncryptionOptions::record_source_mtime
+    pub source_modified: Option<SystemTime>,
+    /// 文件头占用的字节数。
+    pub header_len: u64,
+    /// 密文部分的字节数（即原始明文的大小）。
+    pub ciphertext_len: u64,
+    /// 加密文件的总字节数。
+    pub file_len: u64,
+}
+
+/// 读取加密文件的文件头并返回其元数据。
+///
+/// 该操作不需要密码，也不会验证文件的完整性；
+/// 如需确认文件未被篡改，请使用 [`verify_file`]。
+pub fn inspect_file(path: &Path) -> Result<FileInfo> {
+    let file = open_encrypted_file(path)?;
+    let file_len = file.metadata()?.len();
+    let header = read_header(&mut BufReader::new(file))?;
+    let ciphertext_len = ciphertext_len(file_len, &header)?;
+
+    Ok(FileInfo {
+        path: path.to_path_buf(),
+        level: header.security_level(),
+        format_version: header.version,
+        cipher_id: header.cipher_id,
+        kdf_id: header.kdf_id,
+        original_filename: header.original_filename.clone(),
+        m_cost: header.m_cost,
+        t_cost: header.t_cost,
+        p_cost: header.p_cost,
+        has_keyfile_commitment: header.keyfile_commitment().is_some(),
+        expires_at: expiry_from_unix(header.expiry_time()),
+        source_modified: header.source_mtime(),
+        header_len: header.len(),
+        ciphertext_len,
+        file_len,
+    })
+}
+
+/// # 安全级别校准结果 (Level Benchmark)
+///
+/// [`benchmark_level`] 在当前机器上测得的耗时。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelBenchmark {
+    /// 以该级别的参数执行一次 Argon2 密钥派生所需的时间。
+    pub key_derivation: Duration,
+    /// AES-256-CTR 解密加 HMAC-SHA256 认证的吞吐量（字节/秒），不含磁盘 I/O。
+    pub throughput: f64,
+}
+
+/// 在当前机器上校准一个安全级别：执行一次该级别的 Argon2 密钥派生，
+/// 并测量 [`ESTIMATE_CALIBRATION_BYTES`] 字节数据的解密与认证吞吐量。
+///
+/// 耗时与一次真正的密钥派生相当（`Paranoid` 级别可能需要数秒）。
+pub fn benchmark_level(level: Level) -> Result<LevelBenchmark> {
+    let (m_cost, t_cost, p_cost) = level.argon2_params();
+    let key_derivation = time_key_derivation(m_cost, t_cost, p_cost)?;
+
+    let mut data = vec![0u8; ESTIMATE_CALIBRATION_BYTES];
+    let started = Instant::now();
+    let mut cipher = ctr::Ctr128BE::<aes::Aes256>::new(&[0u8; 32].into(), &[0u8; 16].into());
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&[0u8; 32])?;
+    for chunk in data.chunks_mut(BUFFER_LEN) {
+        mac.update(chunk);
+        cipher.apply_keystream(chunk);
+    }
+    std::hint::black_box(mac.finalize());
+    let elapsed = started.elapsed().as_secs_f64().max(f64::EPSILON);
+
+    Ok(LevelBenchmark {
+        key_derivation,
+        throughput: data.len() as f64 / elapsed,
+    })
+}
+
+/// 估计在当前机器上解密 `path` 大约需要多长时间，不需要密码。
+///
+/// 以 [`Level::Interactive`] 调用 [`benchmark_level`] 校准后，按 `m_cost × t_cost` 的比例
+/// 推算文件头中参数（或 `level_override` 给出的级别的参数）的密钥派生时间，
+/// 再加上密文大小除以测得的吞吐量。磁盘较慢时实际耗时会更长。
+///
+/// # 错误
+///
+/// 文件不存在或文件头无效时返回错误。
+pub fn estimate_decryption_time(path: &Path, level_override: Option<Level>) -> Result<Duration> {
+    let info = inspect_file(path)?;
+    let (m_cost, t_cost, _) = match level_override {
+        Some(level) => level.argon2_params(),
+        None => (info.m_cost, info.t_cost, info.p_cost),
+    };
+
+    let calibration = benchmark_level(Level::Interactive)?;
+    let (base_m_cost, base_t_cost, _) = Level::Interactive.argon2_params();
+    let kdf_scale =
+        (f64::from(m_cost) * f64::from(t_cost)) / (f64::from(base_m_cost) * f64::from(base_t_cost));
+    let key_derivation = calibration.key_derivation.mul_f64(kdf_scale);
+    let body = Duration::from_secs_f64(info.ciphertext_len as f64 / calibration.throughput);
+    Ok(key_derivation + body)
+}
+
+/// 以给定参数执行一次 Argon2id 密钥派生并返回耗时。
+fn time_key_derivation(m_cost: u32, t_cost: u32, p_cost: u32) -> Result<Duration> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(MASTER_KEY_LEN))
+        .map_err(|e| anyhow::anyhow!("创建 Argon2 参数失败: {e}"))?;
+    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut output = Zeroizing::new([0u8; MASTER_KEY_LEN]);
+    let started = Instant::now();
+    argon2
+        .hash_password_into(b"ferox-benchmark", &[0u8; SALT_LEN], output.as_mut())
+        .map_err(|e| anyhow::anyhow!("Argon2密钥派生失败: {e}"))?;
+    Ok(started.elapsed())
+}
+
+/// 把文件头中的过期时间戳转换为 `SystemTime`；0 表示没有过期时间。
+pub(crate) fn expiry_from_unix(expiry_time: u64) -> Option<SystemTime> {
+    (expiry_time > 0).then(|| UNIX_EPOCH + Duration::from_secs(expiry_time))
+}
+
+/// 把时间格式化为 `YYYY-MM-DD HH:MM:SS UTC`，用于显示文件的过期时间。
+pub fn format_utc_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // 由 1970-01-01 起的天数换算公历日期 (Howard Hinnant 的 civil_from_days 算法)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// 扫描目录中的 `.feroxcrypt` 文件，按文件格式版本分组。
+///
+/// 每个文件只读取开头的 5 个字节（魔数 + 版本号），不会解析完整的文件头，
+/// 因此可以快速扫描大量文件。遗留格式的文件归入版本 0。
+/// 无法读取的文件会记录警告并跳过。`config` 中的递归和过滤选项同样适用。
+pub fn scan_format_versions(
+    directory: &Path,
+    config: &BatchConfig,
+) -> Result<HashMap<u8, Vec<PathBuf>>> {
+    let mut versions: HashMap<u8, Vec<PathBuf>> = HashMap::new();
+    for path in collect_files(directory, config, true)? {
+        let version = File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut file| read_format_version(&mut file));
+        match version {
+            Ok(version) => versions.entry(version).or_default().push(path),
+            Err(e) => log::warn!("无法读取文件格式版本 {}: {e:#}", path.display()),
+        }
+    }
+    Ok(versions)
+}
+
+/// # 加密文件清单条目 (Encrypted File Entry)
+///
+/// [`scan_encrypted`] 为每个加密文件生成的条目，只来自文件头，不需要密码。
+/// 文件头无法解析时，除路径和（能读取到的）大小、格式版本之外的字段为 `None`，原因记录在 `error` 中。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EncryptedFileInfo {
+    /// 加密文件的路径。
+    pub path: PathBuf,
+    /// 加密文件的总字节数；无法读取元数据时为 `None`。
+    pub file_size: Option<u64>,
+    /// 文件格式版本，遗留格式为 0；无法读取时为 `None`。
+    pub format_version: Option<u8>,
+    /// Argon2 参数 `(m_cost KiB, t_cost, p_cost)`。
+    pub argon2_params: Option<(u32, u32, u32)>,
+    /// 安全级别的名称，参数不属于任何预设级别时为 `"Custom"`。
+    pub level: Option<String>,
+    /// 文件头中是否记录了密钥文件承诺（即加密时使用了密钥文件）。
+    pub has_keyfile_commitment: Option<bool>,
+    /// 文件头中记录的原始文件名。
+    pub original_filename: Option<String>,
+    /// 文件头无法解析的原因。
+    pub error: Option<String>,
+}
+
+impl EncryptedFileInfo {
+    /// 读取 `path` 的文件头；无法解析时返回带有 `error` 的条目，而不是错误。
+    pub fn inspect(path: &Path) -> Self {
+        match inspect_file(path) {
+            Ok(info) => Self {
+                path: info.path,
+                file_size: Some(info.file_len),
+                format_version: Some(info.format_version),
+                argon2_params: Some((info.m_cost, info.t_cost, info.p_cost)),
+                level: Some(
+                    info.level
+                        .map_or("Custom", |level| level.name())
+                        .to_string(),
+                ),
+                has_keyfile_commitment: Some(info.has_keyfile_commitment),
+                original_filename: Some(info.original_filename),
+                error: None,
+            },
+            Err(e) => Self {
+                path: path.to_path_buf(),
+                file_size: std::fs::metadata(path).ok().map(|metadata| metadata.len()),
+                format_version: File::open(path)
+                    .ok()
+                    .and_then(|mut file| read_format_version(&mut file).ok()),
+                argon2_params: None,
+                level: None,
+                has_keyfile_commitment: None,
+                original_filename: None,
+                error: Some(format!("{e:#}")),
+            },
+        }
+    }
+}
+
+/// 列出目录中的 `.feroxcrypt` 文件及其文件头参数，按路径排序。
+///
+/// 不需要密码，也不会验证认证标签；文件头损坏的文件同样出现在结果中，原因记录在
+/// [`EncryptedFileInfo::error`] 中。`recursive` 为 `true` 时包括所有子目录。
+///
+/// *Lists the encrypted files of a directory with their header parameters, including
+/// malformed files (with an `error`) instead of dropping them.*
+pub fn scan_encrypted(directory: &Path, recursive: bool) -> Result<Vec<EncryptedFileInfo>> {
+    let config = BatchConfig {
+        recursive,
+        ..Default::default()
+    };
+    Ok(collect_files(directory, &config, true)?
+        .iter()
+        .map(|path| EncryptedFileInfo::inspect(path))
+        .collect())
+}
+
+/// # 兼容性分类 (Compatibility Status)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compatibility {
+    /// 当前格式，可以被本程序解密。
+    Supported,
+    /// 遗留格式（版本 0），可以解密，但建议重新加密以获得文件头认证保护。
+    Legacy,
+    /// 使用了更新的格式版本、未知算法或关键扩展，需要升级程序。
+    NeedsUpgrade,
+    /// 文件头损坏或无法读取。
+    Unreadable,
+}
+
+impl Compatibility {
+    /// 当前程序能否解密该文件。
+    pub fn is_decryptable(&self) -> bool {
+        matches!(self, Self::Supported | Self::Legacy)
+    }
+}
+
+/// # 单个文件的兼容性检查结果 (Compatibility Entry)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompatibilityEntry {
+    /// 加密文件的路径。
+    pub path: PathBuf,
+    /// 文件格式版本；无法读取文件头时为 `None`。
+    pub version: Option<u8>,
+    /// 加密算法标识；无法读取文件头时为 `None`。
+    pub cipher_id: Option<u8>,
+    /// 密钥派生算法标识；无法读取文件头时为 `None`。
+    pub kdf_id: Option<u8>,
+    /// 兼容性分类。
+    pub status: Compatibility,
+    /// 需要升级或无法读取时的原因。
+    pub reason: Option<String>,
+}
+
+/// 检查目录中每个 `.feroxcrypt` 文件能否被当前程序解密。
+///
+/// 先读取文件头开头的格式版本和算法标识，版本或算法不受支持的文件归为
+/// [`Compatibility::NeedsUpgrade`]；其余文件再用 [`inspect_file`] 解析完整的文件头，
+/// 解析失败的归为 [`Compatibility::Unreadable`]。不需要密码，也不会验证认证标签。
+/// `config` 中的递归和过滤选项同样适用，结果按路径排序。
+pub fn check_compatibility(
+    directory: &Path,
+    config: &BatchConfig,
+) -> Result<Vec<CompatibilityEntry>> {
+    let mut files = collect_files(directory, config, true)?;
+    files.sort();
+    Ok(files.into_iter().map(check_file_compatibility).collect())
+}
+
+/// 检查单个文件的兼容性。
+fn check_file_compatibility(path: PathBuf) -> CompatibilityEntry {
+    let preamble = File::open(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|mut file| read_format_preamble(&mut file));
+    let preamble = match preamble {
+        Ok(preamble) => preamble,
+        Err(e) => {
+            return CompatibilityEntry {
+                path,
+                version: None,
+                cipher_id: None,
+                kdf_id: None,
+                status: Compatibility::Unreadable,
+                reason: Some(format!("{e:#}")),
+            }
+        }
+    };
+
+    let (status, reason) = if let Err(e) = preamble.ensure_supported() {
+        (Compatibility::NeedsUpgrade, Some(e.to_string()))
+    } else {
+        match inspect_file(&path) {
+            Ok(_) if preamble.version == LEGACY_FORMAT_VERSION => (Compatibility::Legacy, None),
+            Ok(_) => (Compatibility::Supported, None),
+            Err(e) => {
+                let status = match e.downcast_ref::<FeroxError>() {
+                    Some(error) if error.requires_upgrade() => Compatibility::NeedsUpgrade,
+                    _ => Compatibility::Unreadable,
+                };
+                (status, Some(format!("{e:#}")))
+            }
+        }
+    };
+
+    CompatibilityEntry {
+        path,
+        version: Some(preamble.version),
+        cipher_id: Some(preamble.cipher_id),
+        kdf_id: Some(preamble.kdf_id),
+        status,
+        reason,
+    }
+}
+
+/// 使用密码（和可选的密钥文件）验证加密文件的完整性。
+///
+/// 会完整地派生密钥并计算认证标签，但不会解密，也不会产生任何明文。`Ok(())` 表示文件完好且密码/密钥文件正确。
+pub fn verify_file(path: &Path, password: &str, keyfile: Option<&KeyFile>) -> Result<()> {
+    let opts = DecryptionOptions {
+        keyfile,
+        ..DecryptionOptions::new(password)
+    };
+    verify_file_with_progress(path, &opts, None)
+}
+
+/// 验证加密文件，并将进度报告给可选的 [`ProgressSink`]。
+pub(crate) fn verify_file_with_progress(
+    path: &Path,
+    opts: &DecryptionOptions,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<()> {
+    let file = open_encrypted_file(path)?;
+    let file_len = file.metadata()?.len();
+    ensure_valid_buffer_size(opts.buffer_size)?;
+    let mut reader = BufReader::with_capacity(opts.buffer_size, file);
+    let header = read_header(&mut reader)?;
+    let ciphertext_size = ciphertext_len(file_len, &header)?;
+
+    verify_body(&mut reader, &header, ciphertext_size, opts, &mut |bytes| {
+        if let Some(sink) = progress {
+            sink.on_event(&ProgressEvent::BytesProcessed { bytes });
+        }
+    })?;
+
+    log::info!("✅ 验证通过: {}", path.display());
+    Ok(())
+}
+
+/// 以常数时间逐字节比较两个文件的内容，返回两者是否完全相同。
+///
+/// 文件大小不同时直接返回 `false`（大小不是秘密）。大小相同时按块读取两个文件，
+/// 每块都用常数时间比较并累积结果，不会在第一个不同的字节处提前返回，
+/// 因此比较耗时只取决于文件大小，不会泄露差异出现的位置。
+/// 适合比较原始文件和解密输出等敏感数据。
+///
+/// # 错误
+///
+/// 任一文件无法打开或读取（包括比较期间文件被截断）时返回错误。
+pub fn secure_compare_files(path_a: &Path, path_b: &Path) -> Result<bool> {
+    let open =
+        |path: &Path| File::open(path).with_context(|| format!("无法打开文件: {}", path.display()));
+    let (mut file_a, mut file_b) = (open(path_a)?, open(path_b)?);
+    let len = file_a.metadata()?.len();
+    if len != file_b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let chunk_len = (len as usize).min(BUFFER_LEN);
+    let (mut chunk_a, mut chunk_b) = (vec![0u8; chunk_len], vec![0u8; chunk_len]);
+    let mut remaining = len;
+    let mut accumulator = 0u8;
+    let result = loop {
+        if remaining == 0 {
+            break Ok(accumulator == 0);
+        }
+        let n = remaining.min(chunk_len as u64) as usize;
+        if let Err(e) = file_a.read_exact(&mut chunk_a[..n]) {
+            break Err(e).with_context(|| format!("读取文件失败: {}", path_a.display()));
+        }
+        if let Err(e) = file_b.read_exact(&mut chunk_b[..n]) {
+            break Err(e).with_context(|| format!("读取文件失败: {}", path_b.display()));
+        }
+        accumulator |= (!chunk_a[..n].ct_eq(&chunk_b[..n])).unwrap_u8();
+        remaining -= n as u64;
+    };
+
+    chunk_a.zeroize();
+    chunk_b.zeroize();
+    result
+}
+
+/// # 比较结果 (Compare Report)
+///
+/// [`compare_file`] 的结果：加密文件的解密结果与明文文件是否完全相同。
+///
+/// *Outcome of comparing an encrypted file's decryption against a plaintext file.*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompareReport {
+    /// 明文文件的字节数。
+    pub plaintext_len: u64,
+    /// 解密结果的字节数。
+    pub decrypted_len: u64,
+    /// 第一个不同字节的偏移量；两者完全相同时为 `None`。
+    ///
+    /// 长度不同而较短一方是另一方的前缀时，为较短一方的长度。
+    pub first_difference: Option<u64>,
+}
+
+impl CompareReport {
+    /// 解密结果与明文文件是否完全相同。
+    pub fn is_match(&self) -> bool {
+        self.first_difference.is_none()
+    }
+}
+
+/// 流式解密 `encrypted` 并与明文文件 `plaintext` 逐块比较，不把任何明文写入磁盘。
+///
+/// 认证标签同样会被完整验证：只有标签验证通过后才返回比较结果，
+/// 因此 [`CompareReport::is_match`] 为 `true` 说明该加密文件确实能解密出这个明文文件。
+///
+/// # 错误
+///
+/// 任一文件无法读取、文件头无效，或认证失败（密码/密钥文件错误或文件已被篡改）时返回错误。
+pub fn compare_file(
+    plaintext: &Path,
+    encrypted: &Path,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+) -> Result<CompareReport> {
+    let opts = DecryptionOptions {
+        keyfile,
+        ..DecryptionOptions::new(password)
+    };
+    compare_file_with_progress(plaintext, encrypted, &opts, None)
+}
+
+/// 比较加密文件与明文文件，并将进度报告给可选的 [`ProgressSink`]。
+pub(crate) fn compare_file_with_progress(
+    plaintext: &Path,
+    encrypted: &Path,
+    opts: &DecryptionOptions,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<CompareReport> {
+    ensure_not_special_file(plaintext)
+        .with_context(|| format!("无法读取 {}", plaintext.display()))?;
+    let expected = File::open(plaintext)
+        .with_context(|| format!("无法打开明文文件: {}", plaintext.display()))?;
+    let plaintext_len = expected.metadata()?.len();
+
+    let file = open_encrypted_file(encrypted)?;
+    let file_len = file.metadata()?.len();
+    ensure_valid_buffer_size(opts.buffer_size)?;
+    let mut reader = BufReader::with_capacity(opts.buffer_size, file);
+    let header = read_header(&mut reader)?;
+    let decrypted_len = ciphertext_len(file_len, &header)?;
+
+    let mut comparer = CompareWriter {
+        expected: BufReader::with_capacity(opts.buffer_size, expected),
+        offset: 0,
+        first_difference: None,
+        buffer: Zeroizing::new(Vec::new()),
+    };
+    decrypt_body(
+        &mut reader,
+        &header,
+        decrypted_len,
+        &mut comparer,
+        opts,
+        &mut |bytes| {
+            if let Some(sink) = progress {
+                sink.on_event(&ProgressEvent::BytesProcessed { bytes });
+            }
+        },
+    )
+    .with_context(|| format!("无法解密 {}", encrypted.display()))?;
+
+    let first_difference = comparer
+        .first_difference
+        .or_else(|| (plaintext_len != decrypted_len).then(|| plaintext_len.min(decrypted_len)));
+    Ok(CompareReport {
+        plaintext_len,
+        decrypted_len,
+        first_difference,
+    })
+}
+
+/// 按文件头中记录的原始文件名，找到与加密文件位于同一目录的明文文件。
+pub(crate) fn paired_plaintext_path(encrypted: &Path) -> Result<PathBuf> {
+    let info = inspect_file(encrypted)?;
+    let directory = encrypted.parent().unwrap_or_else(|| Path::new(""));
+    Ok(directory.join(info.original_filename))
+}
+
+/// 把写入的数据与 `expected` 中相同位置的数据比较、然后丢弃的写入器，记录第一个不同的偏移量。
+struct CompareWriter<R> {
+    expected: R,
+    offset: u64,
+    first_difference: Option<u64>,
+    /// 从明文文件读出的数据，丢弃时擦除。
+    buffer: Zeroizing<Vec<u8>>,
+}
+
+impl<R: Read> Write for CompareWriter<R> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // 找到第一个差异后不再读取明文文件，只统计长度
+        if self.first_difference.is_none() {
+            self.buffer.clear();
+            self.expected
+                .by_ref()
+                .take(data.len() as u64)
+                .read_to_end(&mut self.buffer)?;
+            let common = self.buffer.len();
+            let position = data[..common]
+                .iter()
+                .zip(self.buffer.iter())
+                .position(|(a, b)| a != b)
+                .or_else(|| (common < data.len()).then_some(common));
+            self.first_difference = position.map(|i| self.offset + i as u64);
+        }
+        self.offset += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 打开一个加密文件，并检查它至少能容纳认证标签。
+fn open_encrypted_file(path: &Path) -> Result<File> {
+    ensure_not_special_file(path).with_context(|| format!("无法读取 {}", path.display()))?;
+    if !path.is_file() {
+        bail!("提供的路径不是一个文件: {}", path.display());
+    }
+    let file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+    if file.metadata()?.len() < TAG_LEN as u64 {
+        bail!("文件太短，不是有效的加密文件: {}", path.display());
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::{CURRENT_FORMAT_VERSION, IV_LEN, SALT_LEN},
+        decrypt::decrypt_in_memory,
+        encrypt::{encrypt_in_memory, encrypt_stream_with_header, EncryptionOptions},
+        format::FileHeader,
+    };
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scan_format_versions_groups_legacy_and_current_files() -> Result<()> {
+        let dir = tempdir()?;
+        let opts = EncryptionOptions {
+            level: Level::Interactive,
+            ..EncryptionOptions::new("version_password")
+        };
+
+        let mut legacy_header = FileHeader::new(
+            "old.txt",
+            [1u8; SALT_LEN],
+            [2u8; IV_LEN],
+            Level::Interactive.argon2_params(),
+        );
+        legacy_header.version = LEGACY_FORMAT_VERSION;
+        let mut legacy = Vec::new();
+        encrypt_stream_with_header(
+            &mut &b"legacy data"[..],
+            &mut legacy,
+            &legacy_header,
+            &opts,
+            &mut |_| {},
+        )?;
+        let legacy_path = dir.path().join("old.txt.feroxcrypt");
+        fs::write(&legacy_path, &legacy)?;
+
+        let current_path = dir.path().join("new.txt.feroxcrypt");
+        fs::write(
+            &current_path,
+            encrypt_in_memory(b"new data", "new.txt", &opts)?,
+        )?;
+        // 太短的文件无法读取版本号，会被跳过
+        fs::write(dir.path().join("tiny.feroxcrypt"), b"FX")?;
+
+        let versions = scan_format_versions(dir.path(), &BatchConfig::default())?;
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[&LEGACY_FORMAT_VERSION], vec![legacy_path.clone()]);
+        assert_eq!(versions[&CURRENT_FORMAT_VERSION], vec![current_path]);
+
+        // 遗留格式的文件仍然可以解密和查看
+        let (plaintext, name) =
+            decrypt_in_memory(&legacy, &DecryptionOptions::new("version_password"))?;
+        assert_eq!(
+            (plaintext.as_slice(), name.as_str()),
+            (&b"legacy data"[..], "old.txt")
+        );
+        let info = inspect_file(&legacy_path)?;
+        assert_eq!(info.format_version, LEGACY_FORMAT_VERSION);
+        assert_eq!(info.ciphertext_len, b"legacy data".len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_compatibility_classifies_versions_and_algorithms() -> Result<()> {
+        let dir = tempdir()?;
+        let opts = EncryptionOptions {
+            level: Level::Interactive,
+            ..EncryptionOptions::new("compat_password")
+        };
+        let current = encrypt_in_memory(b"data", "data.txt", &opts)?;
+        let version_offset = crate::constants::FORMAT_MAGIC.len();
+
+        let mut future_version = current.clone();
+        future_version[version_offset] = CURRENT_FORMAT_VERSION + 1;
+        let mut future_cipher = current.clone();
+        future_cipher[version_offset + 1] = 0xEE;
+
+        let mut legacy_header = FileHeader::new(
+            "old.txt",
+            [3u8; SALT_LEN],
+            [4u8; IV_LEN],
+            Level::Interactive.argon2_params(),
+        );
+        legacy_header.version = crate::constants::LEGACY_FORMAT_VERSION;
+        let mut legacy = Vec::new();
+        encrypt_stream_with_header(
+            &mut &b"old"[..],
+            &mut legacy,
+            &legacy_header,
+            &opts,
+            &mut |_| {},
+        )?;
+
+        let nested = dir.path().join("nested");
+        fs::create_dir(&nested)?;
+        fs::write(dir.path().join("a_current.feroxcrypt"), &current)?;
+        fs::write(
+            dir.path().join("b_future_version.feroxcrypt"),
+            &future_version,
+        )?;
+        fs::write(
+            dir.path().join("c_future_cipher.feroxcrypt"),
+            &future_cipher,
+        )?;
+        fs::write(nested.join("d_legacy.feroxcrypt"), &legacy)?;
+        fs::write(dir.path().join("e_truncated.feroxcrypt"), &current[..10])?;
+
+        let config = BatchConfig {
+            recursive: true,
+            ..Default::default()
+        };
+        let entries = check_compatibility(dir.path(), &config)?;
+        let statuses: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.path.file_name().unwrap().to_str().unwrap(),
+                    entry.status,
+                    entry.version,
+                )
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (
+                    "a_current.feroxcrypt",
+                    Compatibility::Supported,
+                    Some(CURRENT_FORMAT_VERSION)
+                ),
+                (
+                    "b_future_version.feroxcrypt",
+                    Compatibility::NeedsUpgrade,
+                    Some(CURRENT_FORMAT_VERSION + 1)
+                ),
+                (
+                    "c_future_cipher.feroxcrypt",
+                    Compatibility::NeedsUpgrade,
+                    Some(CURRENT_FORMAT_VERSION)
+                ),
+                (
+                    "e_truncated.feroxcrypt",
+                    Compatibility::Unreadable,
+                    Some(CURRENT_FORMAT_VERSION)
+                ),
+                ("d_legacy.feroxcrypt", Compatibility::Legacy, Some(0)),
+            ]
+        );
+        assert_eq!(entries[2].cipher_id, Some(0xEE));
+        assert!(entries
+            .iter()
+            .all(|entry| entry.status.is_decryptable() == entry.reason.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_encrypted_lists_parameters_and_malformed_files() -> Result<()> {
+        let dir = tempdir()?;
+        let keyfile = KeyFile::generate();
+        let password_only = EncryptionOptions {
+            level: Level::Interactive,
+            ..EncryptionOptions::new("scan_password")
+        };
+        let with_keyfile = EncryptionOptions {
+            keyfile: Some(&keyfile),
+            keyfile_commitment: true,
+            ..password_only
+        };
+        let nested = dir.path().join("nested");
+        fs::create_dir(&nested)?;
+        let plain = encrypt_in_memory(b"plain", "plain.txt", &password_only)?;
+        fs::write(dir.path().join("a.feroxcrypt"), &plain)?;
+        fs::write(
+            nested.join("b.feroxcrypt"),
+            encrypt_in_memory(b"keyed", "keyed.txt", &with_keyfile)?,
+        )?;
+        fs::write(dir.path().join("c.feroxcrypt"), &plain[..10])?;
+        fs::write(dir.path().join("notes.txt"), b"not encrypted")?;
+
+        let entries = scan_encrypted(dir.path(), false)?;
+        assert_eq!(entries.len(), 2);
+        let plain_entry = &entries[0];
+        assert_eq!(plain_entry.path, dir.path().join("a.feroxcrypt"));
+        assert_eq!(plain_entry.file_size, Some(plain.len() as u64));
+        assert_eq!(plain_entry.format_version, Some(CURRENT_FORMAT_VERSION));
+        assert_eq!(
+            plain_entry.argon2_params,
+            Some(Level::Interactive.argon2_params())
+        );
+        assert_eq!(plain_entry.level.as_deref(), Some("Interactive"));
+        assert_eq!(plain_entry.has_keyfile_commitment, Some(false));
+        assert_eq!(plain_entry.original_filename.as_deref(), Some("plain.txt"));
+        assert_eq!(plain_entry.error, None);
+
+        // 截断的文件保留在结果中，带有错误原因
+        let malformed = &entries[1];
+        assert_eq!(malformed.path, dir.path().join("c.feroxcrypt"));
+        assert_eq!(malformed.file_size, Some(10));
+        assert_eq!(malformed.format_version, Some(CURRENT_FORMAT_VERSION));
+        assert_eq!(malformed.original_filename, None);
+        assert!(malformed.error.is_some());
+
+        let entries = scan_encrypted(dir.path(), true)?;
+        assert_eq!(entries.len(), 3);
+        let keyed = entries
+            .iter()
+            .find(|entry| entry.path.starts_with(&nested))
+            .expect("递归扫描应包括子目录");
+        assert_eq!(keyed.has_keyfile_commitment, Some(true));
+        assert_eq!(keyed.original_filename.as_deref(), Some("keyed.txt"));
+
+        let json = serde_json::to_value(&entries)?;
+        assert!(json[0]["error"].is_null());
+        assert_eq!(json[0]["level"], "Interactive");
+        Ok(())
+    }
+
+    #[test]
+    fn test_secure_compare_files_identical() -> Result<()> {
+        let dir = tempdir()?;
+        // 跨越多个读取块，最后一块不满
+        let data: Vec<u8> = (0..BUFFER_LEN + 1234).map(|i| (i % 251) as u8).collect();
+        let (a, b) = (dir.path().join("a.bin"), dir.path().join("b.bin"));
+        fs::write(&a, &data)?;
+        fs::write(&b, &data)?;
+        assert!(secure_compare_files(&a, &b)?);
+
+        let (empty_a, empty_b) = (dir.path().join("empty_a"), dir.path().join("empty_b"));
+        fs::write(&empty_a, b"")?;
+        fs::write(&empty_b, b"")?;
+        assert!(secure_compare_files(&empty_a, &empty_b)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_secure_compare_files_differing() -> Result<()> {
+        let dir = tempdir()?;
+        let data: Vec<u8> = (0..BUFFER_LEN + 1234).map(|i| (i % 251) as u8).collect();
+        let original = dir.path().join("original.bin");
+        fs::write(&original, &data)?;
+
+        // 只有最后一个字节不同
+        let mut changed = data.clone();
+        *changed.last_mut().unwrap() ^= 1;
+        let changed_path = dir.path().join("changed.bin");
+        fs::write(&changed_path, &changed)?;
+        assert!(!secure_compare_files(&original, &changed_path)?);
+
+        // 大小不同
+        let shorter = dir.path().join("shorter.bin");
+        fs::write(&shorter, &data[..data.len() - 1])?;
+        assert!(!secure_compare_files(&original, &shorter)?);
+
+        assert!(secure_compare_files(&original, &dir.path().join("missing.bin")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_decryption_time_scales_with_level() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("estimate.bin.feroxcrypt");
+        let opts = EncryptionOptions {
+            level: Level::Interactive,
+            ..EncryptionOptions::new("estimate_password")
+        };
+        fs::write(
+            &path,
+            encrypt_in_memory(&[0u8; 1024], "estimate.bin", &opts)?,
+        )?;
+
+        let benchmark = benchmark_level(Level::Interactive)?;
+        assert!(benchmark.key_derivation > Duration::ZERO);
+        assert!(benchmark.throughput > 0.0);
+
+        let interactive = estimate_decryption_time(&path, None)?;
+        let paranoid = estimate_decryption_time(&path, Some(Level::Paranoid))?;
+        assert!(interactive > Duration::ZERO);
+        // Paranoid 的 m_cost × t_cost 约为 Interactive 的 27 倍
+        assert!(
+            paranoid > interactive * 5,
+            "{paranoid:?} vs {interactive:?}"
+        );
+        Ok(())
+    }
+}
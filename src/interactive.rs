@@ -10,16 +10,28 @@
 //! through menus and prompts at runtime.*
 
 use crate::{
-    batch::{batch_decrypt_directory, batch_decrypt_files, batch_encrypt_directory, batch_encrypt_files, BatchConfig},
+    batch::{
+        batch_decrypt_directory, batch_decrypt_files_with_config, batch_encrypt_files,
+        batch_verify_files, collect_files, execute_batch_plan, plan_batch_encrypt, BatchConfig,
+        BatchPlan, PlannedAction,
+    },
+    cleanup::cleanup_partial_output,
+    constants::{PREVIEW_SCAN_TIMEOUT_SECS, SESSION_PASSWORD_IDLE_TIMEOUT_SECS},
+    format::CipherSuite,
+    inspect::{format_utc_time, inspect_file, FileInfo},
     keyfile::{validate_keyfile, KeyFile},
+    progress::{ProgressEvent, ProgressSink},
     Level,
 };
 use anyhow::{Context, Result};
 use console::{style, Term};
 use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use zeroize::Zeroize;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
 
 /// # 主菜单选项
 ///
@@ -30,7 +42,11 @@ enum MainMenuOption {
     DecryptFile,
     BatchEncryptDirectory,
     BatchDecryptDirectory,
+    VerifyFiles,
+    InspectFile,
     GenerateKeyFile,
+    TogglePasswordCache,
+    ForgetPassword,
     Help,
     Exit,
 }
@@ -43,7 +59,11 @@ impl MainMenuOption {
             Self::DecryptFile => "🔓 解密文件 (Decrypt Files)",
             Self::BatchEncryptDirectory => "📁 批量加密目录 (Batch Encrypt Directory)",
             Self::BatchDecryptDirectory => "📂 批量解密目录 (Batch Decrypt Directory)",
+            Self::VerifyFiles => "🔍 验证加密文件 (Verify Encrypted Files)",
+            Self::InspectFile => "ℹ️ 查看文件信息 (Inspect File)",
             Self::GenerateKeyFile => "🔑 生成密钥文件 (Generate Key File)",
+            Self::TogglePasswordCache => "🧠 记住本次会话密码 (Remember password for this session)",
+            Self::ForgetPassword => "🧹 清除已记住的密码 (Forget remembered password)",
             Self::Help => "❓ 帮助信息 (Help)",
             Self::Exit => "🚪 退出程序 (Exit)",
         }
@@ -56,87 +76,265 @@ impl MainMenuOption {
             Self::DecryptFile,
             Self::BatchEncryptDirectory,
             Self::BatchDecryptDirectory,
+            Self::VerifyFiles,
+            Self::InspectFile,
             Self::GenerateKeyFile,
+            Self::TogglePasswordCache,
+            Self::ForgetPassword,
             Self::Help,
             Self::Exit,
         ]
     }
 }
 
+/// # 会话密码缓存 (Session Password Cache)
+///
+/// 用户在菜单中开启后，记住本次交互式会话中输入的密码，之后的操作不再重复询问。
+/// 密码只保存在内存中（丢弃时擦除），以下情况会被清除：关闭该选项、选择“清除已记住的密码”、
+/// 认证失败（密码错误）、超过闲置超时没有使用，以及退出交互式模式。
+///
+/// 所有方法都接收当前时间，便于脱离界面单独测试。
+struct PasswordCache {
+    enabled: bool,
+    password: Option<Zeroizing<String>>,
+    last_used: Instant,
+    idle_timeout: Duration,
+}
+
+impl PasswordCache {
+    /// 创建一个关闭的缓存。
+    fn new(idle_timeout: Duration) -> Self {
+        Self {
+            enabled: false,
+            password: None,
+            last_used: Instant::now(),
+            idle_timeout,
+        }
+    }
+
+    /// 开启或关闭缓存；关闭时清除已记住的密码。
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    /// 缓存开启时记住密码。
+    fn remember(&mut self, password: &str, now: Instant) {
+        if self.enabled {
+            self.password = Some(Zeroizing::new(password.to_string()));
+            self.last_used = now;
+        }
+    }
+
+    /// 取出记住的密码并刷新闲置计时；超过闲置超时时清除它并返回 `None`。
+    fn get(&mut self, now: Instant) -> Option<Zeroizing<String>> {
+        self.expire(now);
+        let password = self.password.clone()?;
+        self.last_used = now;
+        Some(password)
+    }
+
+    /// 当前是否记住了一个未过期的密码。
+    fn has_password(&mut self, now: Instant) -> bool {
+        self.expire(now);
+        self.password.is_some()
+    }
+
+    /// 清除记住的密码（缓存保持开启）。
+    fn clear(&mut self) {
+        self.password = None;
+    }
+
+    /// 超过闲置超时时清除密码。
+    fn expire(&mut self, now: Instant) {
+        if now.saturating_duration_since(self.last_used) > self.idle_timeout {
+            self.clear();
+        }
+    }
+}
+
 /// # 交互式CLI主入口
 ///
-/// 启动交互式用户界面，提供菜单驱动的操作体验
+/// 启动交互式用户界面，提供菜单驱动的操作体验。
+///
+/// 该函数使用自己的临时文件登记表；如需与 `Ctrl+C` 处理器共享登记表，
+/// 请使用 [`run_interactive_mode_with_registry`]。
 pub fn run_interactive_mode() -> Result<()> {
+    run_interactive_mode_with_registry(Arc::new(Mutex::new(None)))
+}
+
+/// # 使用共享临时文件登记表启动交互式模式
+///
+/// `temp_file_path` 会传递给每一次批量操作，应与
+/// [`install_interrupt_handler`](crate::cleanup::install_interrupt_handler)
+/// 使用同一个登记表，这样操作过程中按下 `Ctrl+C` 时能清理不完整的输出文件。
+///
+/// 在菜单或提示中按下 `Ctrl+C` 会干净地退出交互式模式，而不是作为错误报告。
+pub fn run_interactive_mode_with_registry(
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+) -> Result<()> {
+    run_interactive_session(
+        temp_file_path,
+        Duration::from_secs(SESSION_PASSWORD_IDLE_TIMEOUT_SECS),
+    )
+}
+
+/// # 使用共享临时文件登记表和自定义密码闲置超时启动交互式模式
+///
+/// 与 [`run_interactive_mode_with_registry`] 相同，但“记住本次会话密码”开启时，
+/// 记住的密码在闲置 `password_idle_timeout` 之后被清除。
+pub fn run_interactive_session(
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    password_idle_timeout: Duration,
+) -> Result<()> {
     let term = Term::stdout();
     let theme = ColorfulTheme::default();
+    // 退出交互式模式时随之丢弃，记住的密码被擦除
+    let mut passwords = PasswordCache::new(password_idle_timeout);
+
+    match run_main_menu(&term, &theme, &temp_file_path, &mut passwords) {
+        Err(e) if is_interrupted(&e) => {
+            // 提示被中断时 dialoguer 可能来不及恢复光标
+            cleanup_partial_output(&temp_file_path);
+            term.show_cursor()?;
+            term.write_line("")?;
+            term.write_line(
+                &style("⚠️  操作已中断，退出交互式模式 (Interrupted, leaving interactive mode)")
+                    .yellow()
+                    .to_string(),
+            )?;
+            Ok(())
+        }
+        other => other,
+    }
+}
 
+/// 运行主菜单循环
+fn run_main_menu(
+    term: &Term,
+    theme: &ColorfulTheme,
+    temp_file_path: &Arc<Mutex<Option<PathBuf>>>,
+    passwords: &mut PasswordCache,
+) -> Result<()> {
     // 显示欢迎信息
-    display_welcome_banner(&term)?;
-
-    // 创建临时文件路径共享状态
-    let temp_file_path = Arc::new(Mutex::new(None::<PathBuf>));
+    display_welcome_banner(term)?;
 
     loop {
         // 显示主菜单
         let options = MainMenuOption::all_options();
-        let option_texts: Vec<&str> = options.iter().map(|opt| opt.display_text()).collect();
+        let option_texts: Vec<String> = options
+            .iter()
+            .map(|opt| match opt {
+                MainMenuOption::TogglePasswordCache => {
+                    let state = if passwords.enabled {
+                        "开 (On)"
+                    } else {
+                        "关 (Off)"
+                    };
+                    format!("{} [{state}]", opt.display_text())
+                }
+                _ => opt.display_text().to_string(),
+            })
+            .collect();
 
         term.write_line("")?;
-        term.write_line(&style("📋 请选择操作 (Please select an operation):").bold().to_string())?;
+        if passwords.has_password(Instant::now()) {
+            term.write_line(
+                &style("🔑 已记住本次会话密码 (Session password remembered)")
+                    .green()
+                    .to_string(),
+            )?;
+        }
+        term.write_line(
+            &style("📋 请选择操作 (Please select an operation):")
+                .bold()
+                .to_string(),
+        )?;
 
-        let selection = Select::with_theme(&theme)
+        let selection = Select::with_theme(theme)
             .items(&option_texts)
             .default(0)
-            .interact_on(&term)?;
+            .interact_on(term)?;
 
         let selected_option = &options[selection];
 
         // 处理用户选择
-        match selected_option {
+        let outcome = match selected_option {
             MainMenuOption::EncryptFile => {
-                if let Err(e) = handle_encrypt_files(&term, &theme, Arc::clone(&temp_file_path)) {
-                    display_error(&term, &e)?;
-                }
+                handle_encrypt_files(term, theme, Arc::clone(temp_file_path), passwords)
             }
             MainMenuOption::DecryptFile => {
-                if let Err(e) = handle_decrypt_files(&term, &theme, Arc::clone(&temp_file_path)) {
-                    display_error(&term, &e)?;
-                }
+                handle_decrypt_files(term, theme, Arc::clone(temp_file_path), passwords)
             }
             MainMenuOption::BatchEncryptDirectory => {
-                if let Err(e) = handle_batch_encrypt_directory(&term, &theme, Arc::clone(&temp_file_path)) {
-                    display_error(&term, &e)?;
-                }
+                handle_batch_encrypt_directory(term, theme, Arc::clone(temp_file_path), passwords)
             }
             MainMenuOption::BatchDecryptDirectory => {
-                if let Err(e) = handle_batch_decrypt_directory(&term, &theme, Arc::clone(&temp_file_path)) {
-                    display_error(&term, &e)?;
-                }
+                handle_batch_decrypt_directory(term, theme, Arc::clone(temp_file_path), passwords)
             }
-            MainMenuOption::GenerateKeyFile => {
-                if let Err(e) = handle_generate_keyfile(&term, &theme) {
-                    display_error(&term, &e)?;
-                }
+            MainMenuOption::VerifyFiles => handle_verify_files(term, theme, passwords),
+            MainMenuOption::InspectFile => handle_inspect_file(term, theme),
+            MainMenuOption::GenerateKeyFile => handle_generate_keyfile(term, theme),
+            MainMenuOption::TogglePasswordCache => {
+                passwords.set_enabled(!passwords.enabled);
+                let message = if passwords.enabled {
+                    "🧠 已开启：本次会话输入的密码将被记住，直到退出或闲置超时 (Enabled)"
+                } else {
+                    "🧹 已关闭并清除记住的密码 (Disabled, remembered password cleared)"
+                };
+                term.write_line(&style(message).cyan().to_string())
+                    .map_err(Into::into)
             }
-            MainMenuOption::Help => {
-                display_help(&term)?;
+            MainMenuOption::ForgetPassword => {
+                passwords.clear();
+                term.write_line(
+                    &style("🧹 已清除记住的密码 (Remembered password cleared)")
+                        .cyan()
+                        .to_string(),
+                )
+                .map_err(Into::into)
             }
+            MainMenuOption::Help => display_help(term),
             MainMenuOption::Exit => {
-                term.write_line(&style("👋 感谢使用 Ferox Encryptor! (Thank you for using Ferox Encryptor!)").green().to_string())?;
+                term.write_line(
+                    &style("👋 感谢使用 Ferox Encryptor! (Thank you for using Ferox Encryptor!)")
+                        .green()
+                        .to_string(),
+                )?;
                 break;
             }
+        };
+
+        if let Err(e) = outcome {
+            // 中断需要一直传播到入口，由入口负责清理和退出
+            if is_interrupted(&e) {
+                return Err(e);
+            }
+            display_error(term, &e)?;
         }
 
         // 询问是否继续
-        if !matches!(selected_option, MainMenuOption::Exit | MainMenuOption::Help) {
+        if !matches!(
+            selected_option,
+            MainMenuOption::Exit
+                | MainMenuOption::Help
+                | MainMenuOption::TogglePasswordCache
+                | MainMenuOption::ForgetPassword
+        ) {
             term.write_line("")?;
-            let continue_prompt = Confirm::with_theme(&theme)
+            let continue_prompt = Confirm::with_theme(theme)
                 .with_prompt("是否继续使用? (Continue?)")
                 .default(true)
-                .interact_on(&term)?;
+                .interact_on(term)?;
 
             if !continue_prompt {
-                term.write_line(&style("👋 感谢使用 Ferox Encryptor! (Thank you for using Ferox Encryptor!)").green().to_string())?;
+                term.write_line(
+                    &style("👋 感谢使用 Ferox Encryptor! (Thank you for using Ferox Encryptor!)")
+                        .green()
+                        .to_string(),
+                )?;
                 break;
             }
         }
@@ -145,13 +343,40 @@ pub fn run_interactive_mode() -> Result<()> {
     Ok(())
 }
 
+/// 判断错误是否由用户在提示中按下 `Ctrl+C` 引起。
+///
+/// 终端处于原始模式时 `Ctrl+C` 不会产生 SIGINT，
+/// dialoguer 会把它作为 `Interrupted` 类型的 I/O 错误返回。
+fn is_interrupted(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        let io_error = match cause.downcast_ref::<dialoguer::Error>() {
+            Some(dialoguer::Error::IO(e)) => Some(e),
+            None => cause.downcast_ref::<std::io::Error>(),
+        };
+        io_error.is_some_and(|e| e.kind() == std::io::ErrorKind::Interrupted)
+    })
+}
+
 /// 显示欢迎横幅
 fn display_welcome_banner(term: &Term) -> Result<()> {
     term.clear_screen()?;
-    term.write_line(&style("🔐 Ferox Encryptor - 交互式模式 (Interactive Mode)").bold().cyan().to_string())?;
+    term.write_line(
+        &style("🔐 Ferox Encryptor - 交互式模式 (Interactive Mode)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
     term.write_line(&style("═".repeat(60)).dim().to_string())?;
-    term.write_line(&style("高性能文件加密工具 - 交互式用户界面").italic().to_string())?;
-    term.write_line(&style("High-performance file encryption tool - Interactive UI").italic().to_string())?;
+    term.write_line(
+        &style("高性能文件加密工具 - 交互式用户界面")
+            .italic()
+            .to_string(),
+    )?;
+    term.write_line(
+        &style("High-performance file encryption tool - Interactive UI")
+            .italic()
+            .to_string(),
+    )?;
     term.write_line(&style("═".repeat(60)).dim().to_string())?;
     Ok(())
 }
@@ -159,8 +384,13 @@ fn display_welcome_banner(term: &Term) -> Result<()> {
 /// 显示错误信息
 fn display_error(term: &Term, error: &anyhow::Error) -> Result<()> {
     term.write_line("")?;
-    term.write_line(&style("❌ 操作失败 (Operation Failed):").red().bold().to_string())?;
-    term.write_line(&style(format!("   {}", error)).red().to_string())?;
+    term.write_line(
+        &style("❌ 操作失败 (Operation Failed):")
+            .red()
+            .bold()
+            .to_string(),
+    )?;
+    term.write_line(&style(format!("   {:#}", error)).red().to_string())?;
     term.write_line("")?;
     Ok(())
 }
@@ -168,30 +398,53 @@ fn display_error(term: &Term, error: &anyhow::Error) -> Result<()> {
 /// 显示帮助信息
 fn display_help(term: &Term) -> Result<()> {
     term.write_line("")?;
-    term.write_line(&style("📖 Ferox Encryptor 帮助信息 (Help Information)").bold().cyan().to_string())?;
+    term.write_line(
+        &style("📖 Ferox Encryptor 帮助信息 (Help Information)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
     term.write_line(&style("─".repeat(50)).dim().to_string())?;
     term.write_line("")?;
-    
+
     term.write_line("🔒 加密功能 (Encryption Features):")?;
     term.write_line("   • 使用 AES-256-CTR + HMAC-SHA256 军用级加密")?;
     term.write_line("   • 支持三种安全级别: Interactive, Moderate, Paranoid")?;
     term.write_line("   • 可选密钥文件双重保护")?;
     term.write_line("")?;
-    
+
     term.write_line("📁 批量处理 (Batch Processing):")?;
     term.write_line("   • 支持目录递归处理")?;
     term.write_line("   • 文件模式过滤 (include/exclude patterns)")?;
     term.write_line("   • 详细的处理结果报告")?;
     term.write_line("")?;
-    
+
+    term.write_line("🔍 验证与检查 (Verify & Inspect):")?;
+    term.write_line("   • 验证加密文件的完整性，不生成明文文件")?;
+    term.write_line("   • 无需密码即可查看文件头信息")?;
+    term.write_line("")?;
+
+    term.write_line("🧠 会话密码 (Session Password):")?;
+    term.write_line("   • 开启“记住本次会话密码”后不再重复输入密码")?;
+    term.write_line("   • 认证失败、手动清除、闲置超时或退出时自动清除")?;
+    term.write_line("")?;
+
     term.write_line("🔑 密钥文件 (Key Files):")?;
     term.write_line("   • 生成安全的随机密钥文件")?;
     term.write_line("   • 提供额外的安全保护层")?;
     term.write_line("   • 即使密码泄露也无法解密")?;
     term.write_line("")?;
-    
-    term.write_line(&style("💡 提示: 使用方向键导航菜单，回车键确认选择").yellow().to_string())?;
-    term.write_line(&style("💡 Tip: Use arrow keys to navigate menus, Enter to confirm").yellow().to_string())?;
+
+    term.write_line(
+        &style("💡 提示: 使用方向键导航菜单，回车键确认选择")
+            .yellow()
+            .to_string(),
+    )?;
+    term.write_line(
+        &style("💡 Tip: Use arrow keys to navigate menus, Enter to confirm")
+            .yellow()
+            .to_string(),
+    )?;
 
     Ok(())
 }
@@ -200,14 +453,24 @@ fn display_help(term: &Term) -> Result<()> {
 fn handle_encrypt_files(
     term: &Term,
     theme: &ColorfulTheme,
-    _temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    passwords: &mut PasswordCache,
 ) -> Result<()> {
     term.write_line("")?;
-    term.write_line(&style("🔒 文件加密 (File Encryption)").bold().cyan().to_string())?;
+    term.write_line(
+        &style("🔒 文件加密 (File Encryption)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
     term.write_line(&style("─".repeat(30)).dim().to_string())?;
 
     // 获取要加密的文件路径
-    let file_paths = get_file_paths_input(term, theme, "请输入要加密的文件路径 (Enter file paths to encrypt)")?;
+    let file_paths = get_file_paths_input(
+        term,
+        theme,
+        "请输入要加密的文件路径 (Enter file paths to encrypt)",
+    )?;
 
     // 选择安全级别
     let level = select_security_level(term, theme)?;
@@ -222,7 +485,14 @@ fn handle_encrypt_files(
         .interact_on(term)?;
 
     // 显示操作预览
-    display_operation_preview(term, "加密 (Encryption)", &file_paths, level, keyfile.as_ref(), force_overwrite)?;
+    display_operation_preview(
+        term,
+        "加密 (Encryption)",
+        &file_paths,
+        level,
+        keyfile.as_ref(),
+        force_overwrite,
+    )?;
 
     // 确认执行
     let confirm = Confirm::with_theme(theme)
@@ -231,30 +501,40 @@ fn handle_encrypt_files(
         .interact_on(term)?;
 
     if !confirm {
-        term.write_line(&style("操作已取消 (Operation cancelled)").yellow().to_string())?;
+        term.write_line(
+            &style("操作已取消 (Operation cancelled)")
+                .yellow()
+                .to_string(),
+        )?;
         return Ok(());
     }
 
     // 获取密码
-    let mut password = rpassword::prompt_password("请输入密码 (输入时不可见): ")
-        .context("无法读取密码")?;
+    let password = obtain_password(term, passwords)?;
 
     // 执行加密
-    let config = BatchConfig {
-        level,
-        force_overwrite,
-        ..Default::default()
-    };
+    let config = with_live_progress(
+        BatchConfig {
+            level,
+            force_overwrite,
+            temp_file_path,
+            ..Default::default()
+        },
+        Arc::new(InteractiveProgress::new(term)),
+    );
 
     term.write_line("")?;
-    term.write_line(&style("正在执行加密操作... (Executing encryption...)").cyan().to_string())?;
+    term.write_line(
+        &style("正在执行加密操作... (Executing encryption...)")
+            .cyan()
+            .to_string(),
+    )?;
 
     let result = batch_encrypt_files(&file_paths, &password, keyfile.as_ref(), &config)?;
 
     // 显示结果
     display_batch_result(term, &result, "加密 (Encryption)")?;
 
-    password.zeroize();
     Ok(())
 }
 
@@ -262,19 +542,36 @@ fn handle_encrypt_files(
 fn handle_decrypt_files(
     term: &Term,
     theme: &ColorfulTheme,
-    _temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    passwords: &mut PasswordCache,
 ) -> Result<()> {
     term.write_line("")?;
-    term.write_line(&style("🔓 文件解密 (File Decryption)").bold().cyan().to_string())?;
+    term.write_line(
+        &style("🔓 文件解密 (File Decryption)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
     term.write_line(&style("─".repeat(30)).dim().to_string())?;
 
     // 获取要解密的文件路径
-    let file_paths = get_file_paths_input(term, theme, "请输入要解密的 .feroxcrypt 文件路径 (Enter .feroxcrypt file paths to decrypt)")?;
+    let file_paths = get_file_paths_input(
+        term,
+        theme,
+        "请输入要解密的 .feroxcrypt 文件路径 (Enter .feroxcrypt file paths to decrypt)",
+    )?;
 
     // 验证文件扩展名
     for path in &file_paths {
-        if !path.extension().map_or(false, |ext| ext == "feroxcrypt") {
-            term.write_line(&style(format!("⚠️  警告: {} 不是 .feroxcrypt 文件", path.display())).yellow().to_string())?;
+        if path.extension().is_none_or(|ext| ext != "feroxcrypt") {
+            term.write_line(
+                &style(format!(
+                    "⚠️  警告: {} 不是 .feroxcrypt 文件",
+                    path.display()
+                ))
+                .yellow()
+                .to_string(),
+            )?;
         }
     }
 
@@ -282,7 +579,14 @@ fn handle_decrypt_files(
     let keyfile = get_optional_keyfile(term, theme)?;
 
     // 显示操作预览
-    display_operation_preview(term, "解密 (Decryption)", &file_paths, Level::Moderate, keyfile.as_ref(), false)?;
+    display_operation_preview(
+        term,
+        "解密 (Decryption)",
+        &file_paths,
+        Level::Moderate,
+        keyfile.as_ref(),
+        false,
+    )?;
 
     // 确认执行
     let confirm = Confirm::with_theme(theme)
@@ -291,24 +595,39 @@ fn handle_decrypt_files(
         .interact_on(term)?;
 
     if !confirm {
-        term.write_line(&style("操作已取消 (Operation cancelled)").yellow().to_string())?;
+        term.write_line(
+            &style("操作已取消 (Operation cancelled)")
+                .yellow()
+                .to_string(),
+        )?;
         return Ok(());
     }
 
     // 获取密码
-    let mut password = rpassword::prompt_password("请输入密码 (输入时不可见): ")
-        .context("无法读取密码")?;
+    let password = obtain_password(term, passwords)?;
 
     // 执行解密
     term.write_line("")?;
-    term.write_line(&style("正在执行解密操作... (Executing decryption...)").cyan().to_string())?;
-
-    let result = batch_decrypt_files(&file_paths, &password, keyfile.as_ref())?;
+    term.write_line(
+        &style("正在执行解密操作... (Executing decryption...)")
+            .cyan()
+            .to_string(),
+    )?;
+
+    let config = with_live_progress(
+        BatchConfig {
+            temp_file_path,
+            ..Default::default()
+        },
+        Arc::new(InteractiveProgress::new(term)),
+    );
+    let result =
+        batch_decrypt_files_with_config(&file_paths, &password, keyfile.as_ref(), &config)?;
+    forget_password_on_auth_failure(term, passwords, &result)?;
 
     // 显示结果
     display_batch_result(term, &result, "解密 (Decryption)")?;
 
-    password.zeroize();
     Ok(())
 }
 
@@ -316,14 +635,24 @@ fn handle_decrypt_files(
 fn handle_batch_encrypt_directory(
     term: &Term,
     theme: &ColorfulTheme,
-    _temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    passwords: &mut PasswordCache,
 ) -> Result<()> {
     term.write_line("")?;
-    term.write_line(&style("📁 批量目录加密 (Batch Directory Encryption)").bold().cyan().to_string())?;
+    term.write_line(
+        &style("📁 批量目录加密 (Batch Directory Encryption)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
     term.write_line(&style("─".repeat(40)).dim().to_string())?;
 
     // 获取目录路径
-    let directory = get_directory_path_input(term, theme, "请输入要加密的目录路径 (Enter directory path to encrypt)")?;
+    let directory = get_directory_path_input(
+        term,
+        theme,
+        "请输入要加密的目录路径 (Enter directory path to encrypt)",
+    )?;
 
     // 选择安全级别
     let level = select_security_level(term, theme)?;
@@ -343,45 +672,85 @@ fn handle_batch_encrypt_directory(
         .default(false)
         .interact_on(term)?;
 
-    // 获取文件过滤模式
-    let (include_patterns, exclude_patterns) = get_file_patterns(term, theme)?;
-
-    // 显示批量操作预览
-    display_batch_operation_preview(term, "批量加密 (Batch Encryption)", &directory, recursive, &include_patterns, &exclude_patterns)?;
-
-    // 确认执行
-    let confirm = Confirm::with_theme(theme)
-        .with_prompt("确认执行批量加密操作? (Confirm batch encryption operation?)")
-        .default(true)
-        .interact_on(term)?;
+    // 获取文件过滤模式并预览实际扫描的结果；不满意时只需重新输入过滤模式
+    let (config, plan) = loop {
+        let (include_patterns, exclude_patterns) = get_file_patterns(term, theme)?;
+
+        let config = BatchConfig {
+            level,
+            force_overwrite,
+            recursive,
+            include_patterns: parse_patterns(&include_patterns)?,
+            exclude_patterns: parse_patterns(&exclude_patterns)?,
+            temp_file_path: Arc::clone(&temp_file_path),
+            ..Default::default()
+        };
+
+        // 显示批量操作预览和实际扫描得到的加密计划
+        display_batch_operation_preview(
+            term,
+            "批量加密 (Batch Encryption)",
+            &directory,
+            recursive,
+            &include_patterns,
+            &exclude_patterns,
+        )?;
+        let mut scan = PendingPlan::start(directory.clone(), config.clone());
+        let preview_deadline = Instant::now() + Duration::from_secs(PREVIEW_SCAN_TIMEOUT_SECS);
+        let plan = scan.wait(term, Some(preview_deadline))?;
+        match &plan {
+            Some(plan) => display_batch_plan(term, plan)?,
+            None => term.write_line(&style(format!(
+                "   ⏳ 目录较大，{PREVIEW_SCAN_TIMEOUT_SECS} 秒内未扫描完成，扫描继续在后台进行 (Scan still running)"
+            )).yellow().to_string())?,
+        }
 
-    if !confirm {
-        term.write_line(&style("操作已取消 (Operation cancelled)").yellow().to_string())?;
-        return Ok(());
-    }
+        // 确认执行、返回修改过滤模式或取消
+        let choice = Select::with_theme(theme)
+            .with_prompt("确认执行批量加密操作? (Confirm batch encryption operation?)")
+            .items(&[
+                "确认执行 (Confirm)",
+                "修改过滤模式 (Adjust patterns)",
+                "取消 (Cancel)",
+            ])
+            .default(0)
+            .interact_on(term)?;
+        match choice {
+            // 执行与预览相同的扫描结果，不再重新扫描
+            0 => match plan {
+                Some(plan) => break (config, plan),
+                None => break (config, scan.wait(term, None)?.context("目录扫描没有完成")?),
+            },
+            1 => continue,
+            _ => {
+                term.write_line(
+                    &style("操作已取消 (Operation cancelled)")
+                        .yellow()
+                        .to_string(),
+                )?;
+                return Ok(());
+            }
+        }
+    };
 
     // 获取密码
-    let mut password = rpassword::prompt_password("请输入密码 (输入时不可见): ")
-        .context("无法读取密码")?;
+    let password = obtain_password(term, passwords)?;
 
-    // 执行批量加密
-    let config = BatchConfig {
-        level,
-        force_overwrite,
-        recursive,
-        include_patterns: parse_patterns(&include_patterns)?,
-        exclude_patterns: parse_patterns(&exclude_patterns)?,
-    };
+    // 执行预览中展示的同一份计划
+    let config = with_live_progress(config, Arc::new(InteractiveProgress::new(term)));
 
     term.write_line("")?;
-    term.write_line(&style("正在执行批量加密操作... (Executing batch encryption...)").cyan().to_string())?;
+    term.write_line(
+        &style("正在执行批量加密操作... (Executing batch encryption...)")
+            .cyan()
+            .to_string(),
+    )?;
 
-    let result = batch_encrypt_directory(&directory, &password, keyfile.as_ref(), &config)?;
+    let result = execute_batch_plan(&plan, &password, keyfile.as_ref(), &config)?;
 
     // 显示结果
     display_batch_result(term, &result, "批量加密 (Batch Encryption)")?;
 
-    password.zeroize();
     Ok(())
 }
 
@@ -389,14 +758,24 @@ fn handle_batch_encrypt_directory(
 fn handle_batch_decrypt_directory(
     term: &Term,
     theme: &ColorfulTheme,
-    _temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    temp_file_path: Arc<Mutex<Option<PathBuf>>>,
+    passwords: &mut PasswordCache,
 ) -> Result<()> {
     term.write_line("")?;
-    term.write_line(&style("📂 批量目录解密 (Batch Directory Decryption)").bold().cyan().to_string())?;
+    term.write_line(
+        &style("📂 批量目录解密 (Batch Directory Decryption)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
     term.write_line(&style("─".repeat(40)).dim().to_string())?;
 
     // 获取目录路径
-    let directory = get_directory_path_input(term, theme, "请输入包含加密文件的目录路径 (Enter directory path containing encrypted files)")?;
+    let directory = get_directory_path_input(
+        term,
+        theme,
+        "请输入包含加密文件的目录路径 (Enter directory path containing encrypted files)",
+    )?;
 
     // 询问是否递归处理
     let recursive = Confirm::with_theme(theme)
@@ -408,7 +787,14 @@ fn handle_batch_decrypt_directory(
     let keyfile = get_optional_keyfile(term, theme)?;
 
     // 显示批量操作预览
-    display_batch_operation_preview(term, "批量解密 (Batch Decryption)", &directory, recursive, &[], &[])?;
+    display_batch_operation_preview(
+        term,
+        "批量解密 (Batch Decryption)",
+        &directory,
+        recursive,
+        &[],
+        &[],
+    )?;
 
     // 确认执行
     let confirm = Confirm::with_theme(theme)
@@ -417,36 +803,273 @@ fn handle_batch_decrypt_directory(
         .interact_on(term)?;
 
     if !confirm {
-        term.write_line(&style("操作已取消 (Operation cancelled)").yellow().to_string())?;
+        term.write_line(
+            &style("操作已取消 (Operation cancelled)")
+                .yellow()
+                .to_string(),
+        )?;
         return Ok(());
     }
 
     // 获取密码
-    let mut password = rpassword::prompt_password("请输入密码 (输入时不可见): ")
-        .context("无法读取密码")?;
+    let password = obtain_password(term, passwords)?;
 
     // 执行批量解密
-    let config = BatchConfig {
-        recursive,
-        ..Default::default()
-    };
+    let config = with_live_progress(
+        BatchConfig {
+            recursive,
+            temp_file_path,
+            ..Default::default()
+        },
+        Arc::new(InteractiveProgress::new(term)),
+    );
 
     term.write_line("")?;
-    term.write_line(&style("正在执行批量解密操作... (Executing batch decryption...)").cyan().to_string())?;
+    term.write_line(
+        &style("正在执行批量解密操作... (Executing batch decryption...)")
+            .cyan()
+            .to_string(),
+    )?;
 
     let result = batch_decrypt_directory(&directory, &password, keyfile.as_ref(), &config)?;
+    forget_password_on_auth_failure(term, passwords, &result)?;
 
     // 显示结果
     display_batch_result(term, &result, "批量解密 (Batch Decryption)")?;
 
-    password.zeroize();
     Ok(())
 }
 
+/// 处理加密文件验证操作
+fn handle_verify_files(
+    term: &Term,
+    theme: &ColorfulTheme,
+    passwords: &mut PasswordCache,
+) -> Result<()> {
+    term.write_line("")?;
+    term.write_line(
+        &style("🔍 验证加密文件 (Verify Encrypted Files)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
+    term.write_line(&style("─".repeat(30)).dim().to_string())?;
+
+    // 选择验证对象：指定文件或整个目录
+    let scope = Select::with_theme(theme)
+        .with_prompt("选择验证范围 (Select what to verify)")
+        .items(&["指定文件 (Specific files)", "整个目录 (Whole directory)"])
+        .default(0)
+        .interact_on(term)?;
+
+    let files = if scope == 0 {
+        get_file_paths_input(
+            term,
+            theme,
+            "请输入要验证的 .feroxcrypt 文件路径 (Enter .feroxcrypt file paths to verify)",
+        )?
+    } else {
+        let directory = get_directory_path_input(
+            term,
+            theme,
+            "请输入包含加密文件的目录路径 (Enter directory path containing encrypted files)",
+        )?;
+        let recursive = Confirm::with_theme(theme)
+            .with_prompt("是否递归处理子目录? (Process subdirectories recursively?)")
+            .default(true)
+            .interact_on(term)?;
+        let files = collect_files(
+            &directory,
+            &BatchConfig {
+                recursive,
+                ..Default::default()
+            },
+            true,
+        )?;
+        if files.is_empty() {
+            term.write_line(
+                &style("⚠️  目录中没有找到加密文件 (No encrypted files found)")
+                    .yellow()
+                    .to_string(),
+            )?;
+            return Ok(());
+        }
+        files
+    };
+
+    // 询问是否使用密钥文件
+    let keyfile = get_optional_keyfile(term, theme)?;
+
+    // 获取密码
+    let password = obtain_password(term, passwords)?;
+
+    // 执行验证（不会生成任何明文文件）
+    let config = with_live_progress(
+        BatchConfig::default(),
+        Arc::new(InteractiveProgress::new(term)),
+    );
+
+    term.write_line("")?;
+    term.write_line(
+        &style("正在验证文件... (Verifying files...)")
+            .cyan()
+            .to_string(),
+    )?;
+
+    let result = batch_verify_files(&files, &password, keyfile.as_ref(), &config)?;
+    forget_password_on_auth_failure(term, passwords, &result)?;
+
+    // 逐个文件显示结果
+    term.write_line("")?;
+    for line in verification_report_lines(&files, &result) {
+        term.write_line(&line)?;
+    }
+    display_batch_result(term, &result, "验证 (Verification)")?;
+
+    Ok(())
+}
+
+/// 读取本次操作的密码：有记住的会话密码时直接使用并给出提示，否则提示输入；
+/// 缓存开启时记住新输入的密码。
+fn obtain_password(term: &Term, passwords: &mut PasswordCache) -> Result<Zeroizing<String>> {
+    if let Some(password) = passwords.get(Instant::now()) {
+        term.write_line(
+            &style("🔑 使用已记住的会话密码 (Using remembered session password)")
+                .green()
+                .to_string(),
+        )?;
+        return Ok(password);
+    }
+    let password = Zeroizing::new(
+        rpassword::prompt_password("请输入密码 (输入时不可见): ").context("无法读取密码")?,
+    );
+    passwords.remember(&password, Instant::now());
+    Ok(password)
+}
+
+/// 有文件认证失败（很可能是密码错误）时清除记住的密码，下一次操作重新询问。
+fn forget_password_on_auth_failure(
+    term: &Term,
+    passwords: &mut PasswordCache,
+    result: &crate::BatchResult,
+) -> Result<()> {
+    if !result.all_authentication_failures().is_empty() && passwords.has_password(Instant::now()) {
+        passwords.clear();
+        term.write_line(&style("🧹 认证失败，已清除记住的密码 (Authentication failed, remembered password cleared)").yellow().to_string())?;
+    }
+    Ok(())
+}
+
+/// 为每个文件生成一行验证结果，失败的文件附带失败原因
+fn verification_report_lines(files: &[PathBuf], result: &crate::BatchResult) -> Vec<String> {
+    files
+        .iter()
+        .map(
+            |path| match result.failures.iter().find(|(failed, _)| failed == path) {
+                Some((_, error)) => style(format!("   ❌ {} - {}", path.display(), error))
+                    .red()
+                    .to_string(),
+                None => style(format!("   ✅ {} - 验证通过 (Verified)", path.display()))
+                    .green()
+                    .to_string(),
+            },
+        )
+        .collect()
+}
+
+/// 处理文件信息查看操作
+fn handle_inspect_file(term: &Term, theme: &ColorfulTheme) -> Result<()> {
+    term.write_line("")?;
+    term.write_line(
+        &style("ℹ️ 查看文件信息 (Inspect File)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
+    term.write_line(&style("─".repeat(30)).dim().to_string())?;
+
+    let files = get_file_paths_input(
+        term,
+        theme,
+        "请输入 .feroxcrypt 文件路径 (Enter .feroxcrypt file path)",
+    )?;
+
+    for path in &files {
+        term.write_line("")?;
+        match inspect_file(path) {
+            Ok(info) => {
+                for line in file_info_lines(&info) {
+                    term.write_line(&line)?;
+                }
+            }
+            Err(e) => display_error(term, &e)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// 将文件头信息格式化为多行文本
+fn file_info_lines(info: &FileInfo) -> Vec<String> {
+    let level = match info.level {
+        Some(level) => level.to_string(),
+        None => format!(
+            "Custom (m={}KiB, t={}, p={})",
+            info.m_cost, info.t_cost, info.p_cost
+        ),
+    };
+    let expiry = match info.expires_at {
+        Some(expires_at) => format_utc_time(expires_at),
+        None => "永不过期 (Never)".to_string(),
+    };
+    vec![
+        style(format!("📄 {}", info.path.display()))
+            .bold()
+            .to_string(),
+        format!(
+            "   📝 原始文件名 (Original name): {}",
+            info.original_filename
+        ),
+        format!("   🏷️  格式版本 (Format version): {}", info.format_version),
+        format!(
+            "   🔐 加密算法 (Cipher suite): {}",
+            CipherSuite::from_header_byte(info.cipher_id).map_or_else(
+                |_| format!("未知 (Unknown, id={})", info.cipher_id),
+                |suite| suite.display_name().to_string()
+            )
+        ),
+        format!(
+            "   🔑 密钥文件 (Keyfile): {}",
+            if info.has_keyfile_commitment {
+                "是 (Yes)"
+            } else {
+                "未记录 (Not recorded)"
+            }
+        ),
+        format!("   ⏳ 过期时间 (Expires): {expiry}"),
+        format!("   🛡️  安全级别 (Security level): {level}"),
+        format!(
+            "   ⚙️  Argon2 参数 (Parameters): m_cost={} KiB, t_cost={}, p_cost={}",
+            info.m_cost, info.t_cost, info.p_cost
+        ),
+        format!("   📏 文件头大小 (Header size): {} 字节", info.header_len),
+        format!(
+            "   🔐 加密数据大小 (Ciphertext size): {} 字节",
+            info.ciphertext_len
+        ),
+        format!("   💾 文件总大小 (Total size): {} 字节", info.file_len),
+    ]
+}
+
 /// 处理密钥文件生成操作
 fn handle_generate_keyfile(term: &Term, theme: &ColorfulTheme) -> Result<()> {
     term.write_line("")?;
-    term.write_line(&style("🔑 生成密钥文件 (Generate Key File)").bold().cyan().to_string())?;
+    term.write_line(
+        &style("🔑 生成密钥文件 (Generate Key File)")
+            .bold()
+            .cyan()
+            .to_string(),
+    )?;
     term.write_line(&style("─".repeat(30)).dim().to_string())?;
 
     // 获取输出路径
@@ -459,14 +1082,22 @@ fn handle_generate_keyfile(term: &Term, theme: &ColorfulTheme) -> Result<()> {
 
     // 检查文件是否已存在
     if output.exists() {
-        term.write_line(&style(format!("⚠️  文件已存在: {}", output.display())).yellow().to_string())?;
+        term.write_line(
+            &style(format!("⚠️  文件已存在: {}", output.display()))
+                .yellow()
+                .to_string(),
+        )?;
         let overwrite = Confirm::with_theme(theme)
             .with_prompt("是否覆盖现有文件? (Overwrite existing file?)")
             .default(false)
             .interact_on(term)?;
 
         if !overwrite {
-            term.write_line(&style("操作已取消 (Operation cancelled)").yellow().to_string())?;
+            term.write_line(
+                &style("操作已取消 (Operation cancelled)")
+                    .yellow()
+                    .to_string(),
+            )?;
             return Ok(());
         }
     }
@@ -475,7 +1106,7 @@ fn handle_generate_keyfile(term: &Term, theme: &ColorfulTheme) -> Result<()> {
     term.write_line("")?;
     term.write_line(&style("📋 操作预览 (Operation Preview):").bold().to_string())?;
     term.write_line(&format!("   📁 输出路径: {}", output.display()))?;
-    term.write_line(&format!("   🔐 密钥类型: 256-bit 随机密钥"))?;
+    term.write_line("   🔐 密钥类型: 256-bit 随机密钥")?;
     term.write_line("")?;
 
     // 确认执行
@@ -485,21 +1116,39 @@ fn handle_generate_keyfile(term: &Term, theme: &ColorfulTheme) -> Result<()> {
         .interact_on(term)?;
 
     if !confirm {
-        term.write_line(&style("操作已取消 (Operation cancelled)").yellow().to_string())?;
+        term.write_line(
+            &style("操作已取消 (Operation cancelled)")
+                .yellow()
+                .to_string(),
+        )?;
         return Ok(());
     }
 
     // 生成密钥文件
     term.write_line("")?;
-    term.write_line(&style("正在生成密钥文件... (Generating key file...)").cyan().to_string())?;
+    term.write_line(
+        &style("正在生成密钥文件... (Generating key file...)")
+            .cyan()
+            .to_string(),
+    )?;
 
     let keyfile = KeyFile::generate();
     keyfile.save_to_file(&output)?;
 
-    term.write_line(&style("✅ 密钥文件已成功生成! (Key file generated successfully!)").green().bold().to_string())?;
+    term.write_line(
+        &style("✅ 密钥文件已成功生成! (Key file generated successfully!)")
+            .green()
+            .bold()
+            .to_string(),
+    )?;
     term.write_line(&format!("📁 保存位置: {}", output.display()))?;
     term.write_line("")?;
-    term.write_line(&style("⚠️  重要提醒 (Important Reminder):").yellow().bold().to_string())?;
+    term.write_line(
+        &style("⚠️  重要提醒 (Important Reminder):")
+            .yellow()
+            .bold()
+            .to_string(),
+    )?;
     term.write_line("   • 请务必妥善保管此密钥文件")?;
     term.write_line("   • 建议制作多个备份副本")?;
     term.write_line("   • 如果丢失，使用此密钥文件加密的数据将永久无法恢复")?;
@@ -526,7 +1175,11 @@ fn get_file_paths_input(term: &Term, theme: &ColorfulTheme, prompt: &str) -> Res
 
         if path_input.trim().is_empty() {
             if paths.is_empty() {
-                term.write_line(&style("❌ 至少需要提供一个文件路径 (At least one file path is required)").red().to_string())?;
+                term.write_line(
+                    &style("❌ 至少需要提供一个文件路径 (At least one file path is required)")
+                        .red()
+                        .to_string(),
+                )?;
                 continue;
             } else {
                 break;
@@ -537,7 +1190,14 @@ fn get_file_paths_input(term: &Term, theme: &ColorfulTheme, prompt: &str) -> Res
 
         // 验证路径
         if !path.exists() {
-            term.write_line(&style(format!("⚠️  警告: 文件不存在 - {} (Warning: File does not exist)", path.display())).yellow().to_string())?;
+            term.write_line(
+                &style(format!(
+                    "⚠️  警告: 文件不存在 - {} (Warning: File does not exist)",
+                    path.display()
+                ))
+                .yellow()
+                .to_string(),
+            )?;
             let continue_anyway = Confirm::with_theme(theme)
                 .with_prompt("是否仍要添加此路径? (Add this path anyway?)")
                 .default(false)
@@ -549,7 +1209,14 @@ fn get_file_paths_input(term: &Term, theme: &ColorfulTheme, prompt: &str) -> Res
         }
 
         paths.push(path);
-        term.write_line(&style(format!("✅ 已添加: {} (Added)", paths.last().unwrap().display())).green().to_string())?;
+        term.write_line(
+            &style(format!(
+                "✅ 已添加: {} (Added)",
+                paths.last().unwrap().display()
+            ))
+            .green()
+            .to_string(),
+        )?;
     }
 
     Ok(paths)
@@ -565,12 +1232,26 @@ fn get_directory_path_input(term: &Term, theme: &ColorfulTheme, prompt: &str) ->
         let path = PathBuf::from(path_input.trim());
 
         if !path.exists() {
-            term.write_line(&style(format!("❌ 目录不存在: {} (Directory does not exist)", path.display())).red().to_string())?;
+            term.write_line(
+                &style(format!(
+                    "❌ 目录不存在: {} (Directory does not exist)",
+                    path.display()
+                ))
+                .red()
+                .to_string(),
+            )?;
             continue;
         }
 
         if !path.is_dir() {
-            term.write_line(&style(format!("❌ 路径不是目录: {} (Path is not a directory)", path.display())).red().to_string())?;
+            term.write_line(
+                &style(format!(
+                    "❌ 路径不是目录: {} (Path is not a directory)",
+                    path.display()
+                ))
+                .red()
+                .to_string(),
+            )?;
             continue;
         }
 
@@ -580,13 +1261,17 @@ fn get_directory_path_input(term: &Term, theme: &ColorfulTheme, prompt: &str) ->
 
 /// 选择安全级别
 fn select_security_level(term: &Term, theme: &ColorfulTheme) -> Result<Level> {
-    let levels = vec![
-        ("Interactive - 快速 (19 MiB 内存)", Level::Interactive),
-        ("Moderate - 推荐 (64 MiB 内存)", Level::Moderate),
-        ("Paranoid - 最安全 (256 MiB 内存)", Level::Paranoid),
-    ];
-
-    let level_texts: Vec<&str> = levels.iter().map(|(text, _)| *text).collect();
+    let level_texts: Vec<String> = Level::ALL
+        .iter()
+        .map(|level| {
+            format!(
+                "{} - {} ({} MiB 内存)",
+                level.name(),
+                level.description(),
+                level.memory_cost_bytes() / (1024 * 1024)
+            )
+        })
+        .collect();
 
     term.write_line("")?;
     let selection = Select::with_theme(theme)
@@ -595,7 +1280,7 @@ fn select_security_level(term: &Term, theme: &ColorfulTheme) -> Result<Level> {
         .default(1) // 默认选择 Moderate
         .interact_on(term)?;
 
-    Ok(levels[selection].1)
+    Ok(Level::ALL[selection])
 }
 
 /// 获取可选的密钥文件
@@ -617,18 +1302,36 @@ fn get_optional_keyfile(term: &Term, theme: &ColorfulTheme) -> Result<Option<Key
         let path = PathBuf::from(keyfile_path.trim());
 
         if !path.exists() {
-            term.write_line(&style(format!("❌ 密钥文件不存在: {} (Key file does not exist)", path.display())).red().to_string())?;
+            term.write_line(
+                &style(format!(
+                    "❌ 密钥文件不存在: {} (Key file does not exist)",
+                    path.display()
+                ))
+                .red()
+                .to_string(),
+            )?;
             continue;
         }
 
         match validate_keyfile(&path) {
             Ok(_) => {
                 let keyfile = KeyFile::load_from_file(&path)?;
-                term.write_line(&style("✅ 密钥文件验证成功 (Key file validated successfully)").green().to_string())?;
+                term.write_line(
+                    &style("✅ 密钥文件验证成功 (Key file validated successfully)")
+                        .green()
+                        .to_string(),
+                )?;
                 return Ok(Some(keyfile));
             }
             Err(e) => {
-                term.write_line(&style(format!("❌ 密钥文件验证失败: {} (Key file validation failed)", e)).red().to_string())?;
+                term.write_line(
+                    &style(format!(
+                        "❌ 密钥文件验证失败: {} (Key file validation failed)",
+                        e
+                    ))
+                    .red()
+                    .to_string(),
+                )?;
                 let retry = Confirm::with_theme(theme)
                     .with_prompt("是否重试? (Retry?)")
                     .default(true)
@@ -673,7 +1376,14 @@ fn get_file_patterns(term: &Term, theme: &ColorfulTheme) -> Result<(Vec<String>,
         }
 
         include_patterns.push(pattern.trim().to_string());
-        term.write_line(&style(format!("✅ 已添加包含模式: {} (Added include pattern)", include_patterns.last().unwrap())).green().to_string())?;
+        term.write_line(
+            &style(format!(
+                "✅ 已添加包含模式: {} (Added include pattern)",
+                include_patterns.last().unwrap()
+            ))
+            .green()
+            .to_string(),
+        )?;
     }
 
     // 获取排除模式
@@ -696,7 +1406,14 @@ fn get_file_patterns(term: &Term, theme: &ColorfulTheme) -> Result<(Vec<String>,
         }
 
         exclude_patterns.push(pattern.trim().to_string());
-        term.write_line(&style(format!("✅ 已添加排除模式: {} (Added exclude pattern)", exclude_patterns.last().unwrap())).green().to_string())?;
+        term.write_line(
+            &style(format!(
+                "✅ 已添加排除模式: {} (Added exclude pattern)",
+                exclude_patterns.last().unwrap()
+            ))
+            .green()
+            .to_string(),
+        )?;
     }
 
     Ok((include_patterns, exclude_patterns))
@@ -715,9 +1432,23 @@ fn display_operation_preview(
     term.write_line(&style("📋 操作预览 (Operation Preview):").bold().to_string())?;
     term.write_line(&format!("   🔧 操作类型: {}", operation))?;
     term.write_line(&format!("   📁 文件数量: {} 个", files.len()))?;
-    term.write_line(&format!("   🔒 安全级别: {:?}", level))?;
-    term.write_line(&format!("   🔑 密钥文件: {}", if keyfile.is_some() { "是 (Yes)" } else { "否 (No)" }))?;
-    term.write_line(&format!("   ⚡ 强制覆盖: {}", if force_overwrite { "是 (Yes)" } else { "否 (No)" }))?;
+    term.write_line(&format!("   🔒 安全级别: {}", level))?;
+    term.write_line(&format!(
+        "   🔑 密钥文件: {}",
+        if keyfile.is_some() {
+            "是 (Yes)"
+        } else {
+            "否 (No)"
+        }
+    ))?;
+    term.write_line(&format!(
+        "   ⚡ 强制覆盖: {}",
+        if force_overwrite {
+            "是 (Yes)"
+        } else {
+            "否 (No)"
+        }
+    ))?;
 
     if files.len() <= 5 {
         term.write_line("   📄 文件列表:")?;
@@ -729,7 +1460,11 @@ fn display_operation_preview(
         for file in files.iter().take(5) {
             term.write_line(&format!("      • {}", file.display()))?;
         }
-        term.write_line(&format!("      ... 还有 {} 个文件 (and {} more files)", files.len() - 5, files.len() - 5))?;
+        term.write_line(&format!(
+            "      ... 还有 {} 个文件 (and {} more files)",
+            files.len() - 5,
+            files.len() - 5
+        ))?;
     }
 
     term.write_line("")?;
@@ -746,10 +1481,17 @@ fn display_batch_operation_preview(
     exclude_patterns: &[String],
 ) -> Result<()> {
     term.write_line("")?;
-    term.write_line(&style("📋 批量操作预览 (Batch Operation Preview):").bold().to_string())?;
+    term.write_line(
+        &style("📋 批量操作预览 (Batch Operation Preview):")
+            .bold()
+            .to_string(),
+    )?;
     term.write_line(&format!("   🔧 操作类型: {}", operation))?;
     term.write_line(&format!("   📁 目标目录: {}", directory.display()))?;
-    term.write_line(&format!("   🔄 递归处理: {}", if recursive { "是 (Yes)" } else { "否 (No)" }))?;
+    term.write_line(&format!(
+        "   🔄 递归处理: {}",
+        if recursive { "是 (Yes)" } else { "否 (No)" }
+    ))?;
 
     if !include_patterns.is_empty() {
         term.write_line("   📥 包含模式:")?;
@@ -769,6 +1511,115 @@ fn display_batch_operation_preview(
     Ok(())
 }
 
+/// 批量加密预览中列出的示例文件数量
+const PREVIEW_EXAMPLE_COUNT: usize = 5;
+
+/// 在后台线程中生成的批量加密计划（扫描目录并预检每个文件）
+///
+/// 预览和执行共用同一次扫描：确认时如果扫描还没有完成，等待同一个线程的结果，而不是重新扫描。
+/// 返回修改过滤模式时，被放弃的扫描在后台运行结束后自行退出。
+struct PendingPlan {
+    receiver: mpsc::Receiver<Result<BatchPlan>>,
+}
+
+impl PendingPlan {
+    /// 开始扫描 `directory`
+    fn start(directory: PathBuf, config: BatchConfig) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(plan_batch_encrypt(
+                std::slice::from_ref(&directory),
+                &config,
+            ));
+        });
+        Self { receiver }
+    }
+
+    /// 显示旋转指示器等待扫描结果；超过 `deadline` 仍未完成时返回 `None`，扫描继续进行
+    fn wait(&mut self, term: &Term, deadline: Option<Instant>) -> Result<Option<BatchPlan>> {
+        let spinner =
+            ProgressBar::with_draw_target(None, ProgressDrawTarget::term(term.clone(), 10));
+        if let Ok(spinner_style) =
+            ProgressStyle::default_spinner().template("   {spinner:.green} {msg} [{elapsed}]")
+        {
+            spinner.set_style(spinner_style);
+        }
+        spinner.set_message("正在扫描目录... (Scanning...)");
+        let outcome = loop {
+            match self.receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(plan) => break plan.map(Some),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        break Ok(None);
+                    }
+                    spinner.tick();
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    break Err(anyhow::anyhow!("目录扫描线程意外退出"));
+                }
+            }
+        };
+        spinner.finish_and_clear();
+        outcome
+    }
+}
+
+/// 批量加密预览的内容：匹配的文件数量、数据量、前 `examples` 个将要加密的文件，以及所有预检警告
+fn batch_plan_lines(plan: &BatchPlan, examples: usize) -> Vec<String> {
+    let mut lines = vec![format!(
+        "   📄 将加密 {} 个文件 ({:.2} MB)，跳过 {} 个文件",
+        plan.encrypt_count(),
+        plan.total_bytes() as f64 / 1_048_576.0,
+        plan.skip_count()
+    )];
+    let to_encrypt: Vec<&Path> = plan
+        .items
+        .iter()
+        .filter(|item| item.action == PlannedAction::Encrypt)
+        .map(|item| item.source.as_path())
+        .collect();
+    if to_encrypt.is_empty() {
+        lines.push("   ⚠️  没有匹配的文件，可以返回修改过滤模式 (No files matched)".to_string());
+    } else {
+        lines.push(format!(
+            "   📄 示例文件 (前{}个):",
+            examples.min(to_encrypt.len())
+        ));
+        for path in to_encrypt.iter().take(examples) {
+            lines.push(format!("      • {}", path.display()));
+        }
+        if to_encrypt.len() > examples {
+            let more = to_encrypt.len() - examples;
+            lines.push(format!(
+                "      ... 还有 {more} 个文件 (and {more} more files)"
+            ));
+        }
+    }
+    for warning in &plan.warnings {
+        lines.push(format!("   ⚠️  {}", warning));
+    }
+    for item in plan.items.iter().filter(|item| !item.warnings.is_empty()) {
+        lines.push(format!("   📁 {}", item.source.display()));
+        for warning in &item.warnings {
+            lines.push(format!("      ⚠️  {}", warning));
+        }
+    }
+    lines
+}
+
+/// 显示批量加密计划，警告以黄色显示
+fn display_batch_plan(term: &Term, plan: &BatchPlan) -> Result<()> {
+    for line in batch_plan_lines(plan, PREVIEW_EXAMPLE_COUNT) {
+        if line.trim_start().starts_with("⚠️") {
+            term.write_line(&style(line).yellow().to_string())?;
+        } else {
+            term.write_line(&line)?;
+        }
+    }
+    term.write_line("")?;
+    Ok(())
+}
+
 /// 显示批量操作结果
 fn display_batch_result(term: &Term, result: &crate::BatchResult, operation: &str) -> Result<()> {
     term.write_line("")?;
@@ -776,14 +1627,30 @@ fn display_batch_result(term: &Term, result: &crate::BatchResult, operation: &st
     let total_files = result.success_count + result.failure_count;
 
     if result.failure_count == 0 {
-        term.write_line(&style(format!("✅ {}完成! (Completed!)", operation)).green().bold().to_string())?;
+        term.write_line(
+            &style(format!("✅ {}完成! (Completed!)", operation))
+                .green()
+                .bold()
+                .to_string(),
+        )?;
         term.write_line(&format!("📊 处理统计: {} 个文件全部成功处理", total_files))?;
     } else {
-        term.write_line(&style(format!("⚠️  {}完成，但有部分文件失败 (Completed with some failures)", operation)).yellow().bold().to_string())?;
+        term.write_line(
+            &style(format!(
+                "⚠️  {}完成，但有部分文件失败 (Completed with some failures)",
+                operation
+            ))
+            .yellow()
+            .bold()
+            .to_string(),
+        )?;
         term.write_line("📊 处理统计:")?;
         term.write_line(&format!("   ✅ 成功: {} 个文件", result.success_count))?;
         term.write_line(&format!("   ❌ 失败: {} 个文件", result.failure_count))?;
-        term.write_line(&format!("   📈 成功率: {:.1}%", (result.success_count as f64 / total_files as f64) * 100.0))?;
+        term.write_line(&format!(
+            "   📈 成功率: {:.1}%",
+            (result.success_count as f64 / total_files as f64) * 100.0
+        ))?;
 
         if result.failure_count > 0 {
             term.write_line("")?;
@@ -797,12 +1664,79 @@ fn display_batch_result(term: &Term, result: &crate::BatchResult, operation: &st
     }
 
     if result.total_bytes > 0 {
-        term.write_line(&format!("💾 处理数据量: {:.2} MB", result.total_bytes as f64 / 1_048_576.0))?;
+        term.write_line(&format!(
+            "💾 处理数据量: {:.2} MB",
+            result.total_bytes as f64 / 1_048_576.0
+        ))?;
     }
 
     Ok(())
 }
 
+/// # 交互式实时进度显示
+///
+/// 将批量处理的进度事件渲染到交互式界面的 `Term` 上：
+/// 一个按总字节数推进的聚合进度条，以及当前正在处理的文件名，原地刷新。
+struct InteractiveProgress {
+    bar: ProgressBar,
+}
+
+impl InteractiveProgress {
+    /// 创建一个绘制到指定终端的进度显示。
+    fn new(term: &Term) -> Self {
+        let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::term(term.clone(), 10));
+        if let Ok(bar_style) = ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})\n   📄 {msg}")
+        {
+            bar.set_style(bar_style.progress_chars("#>-"));
+        }
+        Self { bar }
+    }
+}
+
+impl ProgressSink for InteractiveProgress {
+    fn on_event(&self, event: &ProgressEvent<'_>) {
+        match event {
+            ProgressEvent::BatchStarted { total_bytes, .. } => {
+                self.bar.set_length(*total_bytes);
+                self.bar.set_position(0);
+            }
+            ProgressEvent::FileStarted {
+                index,
+                total_files,
+                path,
+            } => {
+                self.bar.set_message(format!(
+                    "[{}/{}] {}",
+                    index + 1,
+                    total_files,
+                    path.display()
+                ));
+            }
+            ProgressEvent::BytesProcessed { bytes } => self.bar.inc(*bytes),
+            ProgressEvent::Stalled {
+                path,
+                idle,
+                operation,
+                ..
+            } => self.bar.println(format!(
+                "⚠️  {} 已有 {} 秒没有进展，卡在{operation}",
+                path.display(),
+                idle.as_secs()
+            )),
+            ProgressEvent::KeyDerivationStarted { .. } | ProgressEvent::FileFinished { .. } => {}
+            // 清除进度条，让最终的结果摘要保持原样输出
+            ProgressEvent::BatchFinished { .. } => self.bar.finish_and_clear(),
+        }
+    }
+}
+
+/// 为批量配置挂载交互式进度接收器
+fn with_live_progress(mut config: BatchConfig, sink: Arc<dyn ProgressSink>) -> BatchConfig {
+    config.progress_sink = Some(sink);
+    config
+}
+
 /// 解析字符串模式为 glob::Pattern
 fn parse_patterns(patterns: &[String]) -> Result<Vec<glob::Pattern>> {
     if patterns.is_empty() {
@@ -814,3 +1748,261 @@ fn parse_patterns(patterns: &[String]) -> Result<Vec<glob::Pattern>> {
         .map(|s| glob::Pattern::new(s).with_context(|| format!("无效的模式: {}", s)))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// 记录所有收到的事件名称的测试接收器
+    #[derive(Default)]
+    struct RecordingSink {
+        events: Mutex<Vec<&'static str>>,
+    }
+
+    impl ProgressSink for RecordingSink {
+        fn on_event(&self, event: &ProgressEvent<'_>) {
+            let name = match event {
+                ProgressEvent::BatchStarted { .. } => "batch_started",
+                ProgressEvent::FileStarted { .. } => "file_started",
+                ProgressEvent::KeyDerivationStarted { .. } => "key_derivation",
+                ProgressEvent::BytesProcessed { .. } => "bytes",
+                ProgressEvent::Stalled { .. } => "stalled",
+                ProgressEvent::FileFinished { .. } => "file_finished",
+                ProgressEvent::BatchFinished { .. } => "batch_finished",
+            };
+            self.events.lock().unwrap().push(name);
+        }
+    }
+
+    /// 交互式模式构造的批量配置必须订阅进度事件
+    #[test]
+    fn test_with_live_progress_subscribes_to_batch_events() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("progress.txt");
+        fs::write(&file, b"interactive progress")?;
+
+        let sink = Arc::new(RecordingSink::default());
+        let config = with_live_progress(
+            BatchConfig {
+                level: Level::Interactive,
+                ..Default::default()
+            },
+            sink.clone(),
+        );
+
+        let result = batch_encrypt_files(&[file], "password", None, &config)?;
+        assert_eq!(result.success_count, 1);
+
+        let events = sink.events.lock().unwrap();
+        assert_eq!(events.first(), Some(&"batch_started"));
+        assert_eq!(events.get(1), Some(&"file_started"));
+        assert!(events.contains(&"key_derivation"));
+        assert!(events.contains(&"bytes"));
+        assert_eq!(events[events.len() - 2], "file_finished");
+        assert_eq!(events.last(), Some(&"batch_finished"));
+        Ok(())
+    }
+
+    /// 在写入过程中观察共享登记表内容的测试接收器
+    struct RegistryProbe {
+        registry: Arc<Mutex<Option<PathBuf>>>,
+        seen: Mutex<Vec<PathBuf>>,
+    }
+
+    impl ProgressSink for RegistryProbe {
+        fn on_event(&self, event: &ProgressEvent<'_>) {
+            if let ProgressEvent::BytesProcessed { .. } = event {
+                if let Some(path) = self.registry.lock().unwrap().clone() {
+                    self.seen.lock().unwrap().push(path);
+                }
+            }
+        }
+    }
+
+    /// 处理函数传入的临时文件登记表必须被批量操作实际使用
+    #[test]
+    fn test_batch_operations_use_shared_temp_registry() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("registry.txt");
+        fs::write(&file, b"shared registry")?;
+
+        let registry = Arc::new(Mutex::new(None::<PathBuf>));
+        let probe = Arc::new(RegistryProbe {
+            registry: Arc::clone(&registry),
+            seen: Mutex::new(Vec::new()),
+        });
+        let config = with_live_progress(
+            BatchConfig {
+                level: Level::Interactive,
+                temp_file_path: Arc::clone(&registry),
+                ..Default::default()
+            },
+            probe.clone(),
+        );
+
+        let result = batch_encrypt_files(std::slice::from_ref(&file), "password", None, &config)?;
+        assert_eq!(result.success_count, 1);
+
+        // 写入期间登记的是带锁的 `.part` 临时文件
+        let encrypted = temp_dir.path().join("registry.txt.feroxcrypt");
+        assert!(probe
+            .seen
+            .lock()
+            .unwrap()
+            .contains(&temp_dir.path().join("registry.txt.feroxcrypt.part")));
+        // 操作结束后登记表必须被清空，避免误删已完成的文件
+        assert!(registry.lock().unwrap().is_none());
+
+        fs::remove_file(&file)?;
+        let result = batch_decrypt_files_with_config(&[encrypted], "password", None, &config)?;
+        assert_eq!(result.success_count, 1);
+        assert!(probe
+            .seen
+            .lock()
+            .unwrap()
+            .contains(&temp_dir.path().join("registry.txt.part")));
+        assert!(registry.lock().unwrap().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_interrupted_detects_prompt_ctrl_c() {
+        let interrupted = std::io::Error::new(std::io::ErrorKind::Interrupted, "read interrupted");
+        assert!(is_interrupted(&anyhow::Error::from(dialoguer::Error::IO(
+            interrupted
+        ))));
+
+        let other = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        assert!(!is_interrupted(&anyhow::Error::from(dialoguer::Error::IO(
+            other
+        ))));
+        assert!(!is_interrupted(&anyhow::anyhow!("密码错误")));
+    }
+
+    #[test]
+    fn test_main_menu_includes_verify_and_inspect() {
+        let texts: Vec<&str> = MainMenuOption::all_options()
+            .iter()
+            .map(|opt| opt.display_text())
+            .collect();
+        assert!(texts.contains(&"🔍 验证加密文件 (Verify Encrypted Files)"));
+        assert!(texts.contains(&"ℹ️ 查看文件信息 (Inspect File)"));
+        // 退出始终是最后一项
+        assert_eq!(texts.last(), Some(&"🚪 退出程序 (Exit)"));
+    }
+
+    #[test]
+    fn test_password_cache_state_machine() {
+        let start = Instant::now();
+        let mut cache = PasswordCache::new(Duration::from_secs(60));
+
+        // 关闭时不记住任何密码
+        cache.remember("secret", start);
+        assert!(cache.get(start).is_none());
+
+        // 开启后记住并重复使用，每次使用都刷新闲置计时
+        cache.set_enabled(true);
+        cache.remember("secret", start);
+        let later = start + Duration::from_secs(50);
+        assert_eq!(
+            cache.get(later).as_deref().map(String::as_str),
+            Some("secret")
+        );
+        assert!(cache.has_password(later + Duration::from_secs(50)));
+
+        // 认证失败或用户清除时失效，缓存本身保持开启
+        cache.clear();
+        assert!(cache.get(later).is_none());
+        cache.remember("secret", later);
+        assert!(cache.has_password(later));
+
+        // 超过闲置超时后被清除
+        assert!(cache.get(later + Duration::from_secs(61)).is_none());
+        assert!(!cache.has_password(later));
+
+        // 关闭时清除
+        cache.remember("secret", later);
+        cache.set_enabled(false);
+        assert!(!cache.has_password(later));
+    }
+
+    #[test]
+    fn test_verify_report_and_file_info() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("verify.txt");
+        fs::write(&file, b"verify me")?;
+        let config = BatchConfig {
+            level: Level::Interactive,
+            ..Default::default()
+        };
+        batch_encrypt_files(std::slice::from_ref(&file), "password", None, &config)?;
+
+        let good = temp_dir.path().join("verify.txt.feroxcrypt");
+        let bad = temp_dir.path().join("broken.feroxcrypt");
+        let mut data = fs::read(&good)?;
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&bad, data)?;
+
+        let files = vec![good.clone(), bad.clone()];
+        let result = batch_verify_files(&files, "password", None, &config)?;
+        assert_eq!(result.success_count, 1);
+        assert_eq!(result.failure_count, 1);
+
+        let lines = verification_report_lines(&files, &result);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("✅") && lines[0].contains("verify.txt.feroxcrypt"));
+        assert!(lines[1].contains("❌") && lines[1].contains("认证失败"));
+
+        let info = inspect_file(&good)?;
+        assert_eq!(info.original_filename, "verify.txt");
+        assert_eq!(info.level, Some(Level::Interactive));
+        assert_eq!(info.ciphertext_len, 9);
+        let lines = file_info_lines(&info);
+        assert!(lines
+            .iter()
+            .any(|line| line.contains("verify.txt") && line.contains("原始文件名")));
+        assert!(lines.iter().any(|line| line.contains("Interactive")));
+        Ok(())
+    }
+
+    /// 预览使用真实的扫描结果，并且执行时复用同一份计划
+    #[test]
+    fn test_batch_preview_uses_scanned_plan() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        for index in 0..7 {
+            fs::write(
+                temp_dir.path().join(format!("report_{index}.txt")),
+                b"0123456789",
+            )?;
+        }
+        fs::write(temp_dir.path().join("notes.md"), b"not matched")?;
+        let config = BatchConfig {
+            level: Level::Interactive,
+            include_patterns: parse_patterns(&["*.txt".to_string()])?,
+            ..Default::default()
+        };
+
+        let mut scan = PendingPlan::start(temp_dir.path().to_path_buf(), config.clone());
+        let plan = scan
+            .wait(&Term::stderr(), None)?
+            .expect("没有截止时间时总会等到结果");
+        let lines = batch_plan_lines(&plan, 5);
+        assert!(lines[0].contains("将加密 7 个文件"));
+        assert_eq!(lines.iter().filter(|line| line.contains("• ")).count(), 5);
+        assert!(lines.iter().any(|line| line.contains("report_0.txt")));
+        assert!(!lines.iter().any(|line| line.contains("notes.md")));
+        assert!(lines.iter().any(|line| line.contains("还有 2 个文件")));
+
+        let result = execute_batch_plan(&plan, "password", None, &config)?;
+        assert_eq!(result.success_count, 7);
+
+        // 没有匹配的文件时提示修改过滤模式
+        let lines = batch_plan_lines(&BatchPlan::default(), 5);
+        assert!(lines[0].contains("将加密 0 个文件"));
+        assert!(lines[1].contains("没有匹配的文件"));
+        Ok(())
+    }
+}
@@ -7,19 +7,98 @@
 //! 提供双重保护。即使密码泄露，没有对应的密钥文件，数据也无法被解密。
 
 use crate::constants::{
-    KEYFILE_DERIVATION_SALT, KEYFILE_DERIVED_LEN, MAX_KEYFILE_SIZE, MIN_KEYFILE_SIZE,
+    HARDWARE_ENTROPY_TIMEOUT_SECS, KEYFILE_COMMITMENT_CONTEXT, KEYFILE_COMMITMENT_LEN,
+    KEYFILE_DERIVATION_SALT, KEYFILE_DERIVED_LEN, KEYFILE_ENTROPY_SAMPLE_LEN,
+    KEYSHARE_CHECKSUM_LEN, KEYSHARE_FORMAT_VERSION, KEYSHARE_MAGIC, MAX_KEYFILE_SIZE,
+    MIN_KEYFILE_ENTROPY_BITS, MIN_KEYFILE_SIZE,
 };
-use anyhow::{bail, Context, Result};
+use crate::error::FeroxError;
+use crate::format::FileHeader;
+use anyhow::{anyhow, bail, Context, Result};
 use argon2::{self, Argon2};
+use hmac::{Hmac, Mac};
 use rand::{rngs::OsRng, RngCore};
-use std::fs;
-use std::path::Path;
-use zeroize::Zeroize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+use zeroize::{Zeroize, Zeroizing};
+
+/// # 密钥材料输入格式 (Keyfile Input Format)
+///
+/// [`KeyFile::from_stdin`] 和 [`KeyFile::from_reader`] 读取的密钥材料的编码方式。
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum KeyfileInputFormat {
+    /// 原始字节，最多 [`MAX_KEYFILE_SIZE`] 字节。
+    #[default]
+    Binary,
+    /// 十六进制文本 (大小写均可)，忽略其中的空白字符。
+    Hex,
+}
 
 /// 定义 `KeyFile` 结构体，用于处理密钥文件的生成、加载和保存。
+///
+/// `KeyFile` 可以在线程之间共享：克隆只增加引用计数，所有克隆共享同一份密钥材料和派生值缓存，
+/// 而不会复制密钥材料。最后一个实例释放时，密钥材料和缓存的派生值被清零。
+///
+/// *Cloning shares the key material and the derivation caches instead of copying them;
+/// everything is zeroized when the last clone is dropped.*
+#[derive(Clone)]
 pub struct KeyFile {
     /// 存储密钥文件内容的字节向量。
-    data: Vec<u8>,
+    data: Arc<Zeroizing<Vec<u8>>>,
+    /// 首次调用 [`KeyFile::hash`] 时计算并缓存的 Argon2 派生值，批量处理时避免每个文件重复派生。
+    ///
+    /// 多个线程同时首次调用时只有一个线程执行派生，其余线程等待它的结果。
+    /// 派生失败的结果同样会被缓存，重复调用返回同一个错误。
+    derived: Arc<OnceLock<Result<Zeroizing<[u8; KEYFILE_DERIVED_LEN]>, argon2::Error>>>,
+    /// 最近一次 [`combine_password_and_keyfile`] 的结果，按密码的带密钥摘要缓存。
+    combined: Arc<Mutex<Option<CombinedMaterial>>>,
+}
+
+/// 缓存的密码与密钥文件组合材料。
+///
+/// 缓存不保存密码本身，只保存用随机密钥计算的密码摘要，用来判断后续调用是否使用同一个密码。
+/// 所有字段在释放时被清零。
+struct CombinedMaterial {
+    /// 计算密码摘要的随机密钥，每个缓存条目单独生成。
+    digest_key: Zeroizing<[u8; 32]>,
+    /// `HMAC-SHA256(digest_key, 密码)`。
+    password_digest: Zeroizing<[u8; 32]>,
+    /// [`combine_password_and_keyfile`] 的输出。
+    material: Zeroizing<Vec<u8>>,
+}
+
+impl CombinedMaterial {
+    fn new(password: &str, material: &[u8]) -> Result<Self> {
+        let mut digest_key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(digest_key.as_mut());
+        let password_digest = password_digest(&digest_key, password)?;
+        Ok(Self {
+            digest_key,
+            password_digest,
+            material: Zeroizing::new(material.to_vec()),
+        })
+    }
+
+    /// 该条目是否由 `password` 产生（常数时间比较）。
+    fn matches(&self, password: &str) -> Result<bool> {
+        let digest = password_digest(&self.digest_key, password)?;
+        Ok(constant_time_eq(
+            digest.as_ref(),
+            self.password_digest.as_ref(),
+        ))
+    }
+}
+
+/// 用 `key` 计算密码的 HMAC-SHA256 摘要。
+fn password_digest(key: &[u8; 32], password: &str) -> Result<Zeroizing<[u8; 32]>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).context("无法创建HMAC实例")?;
+    mac.update(password.as_bytes());
+    Ok(Zeroizing::new(mac.finalize().into_bytes().into()))
 }
 
 impl KeyFile {
@@ -33,7 +112,23 @@ impl KeyFile {
         let mut data = vec![0u8; MAX_KEYFILE_SIZE];
         // 使用操作系统提供的密码学安全随机数生成器填充向量
         OsRng.fill_bytes(&mut data);
-        Self { data }
+        Self::from_data(data)
+    }
+
+    /// 一次生成 `count` 个相互独立的随机密钥文件，例如为团队中的每个成员各分发一个。
+    ///
+    /// 每个密钥文件都单独从 `OsRng` 取得随机数据，与逐个调用 [`KeyFile::generate`] 相同。
+    pub fn generate_batch(count: u32) -> Vec<Self> {
+        (0..count).map(|_| Self::generate()).collect()
+    }
+
+    /// 由密钥材料创建实例，派生值缓存为空。
+    fn from_data(data: Vec<u8>) -> Self {
+        Self {
+            data: Arc::new(Zeroizing::new(data)),
+            derived: Arc::new(OnceLock::new()),
+            combined: Arc::new(Mutex::new(None)),
+        }
     }
 
     /// 从磁盘加载密钥文件。
@@ -46,6 +141,9 @@ impl KeyFile {
     ///
     /// 一个从指定路径加载的 `KeyFile` 实例。
     ///
+    /// 熵估计值低于 [`MIN_KEYFILE_ENTROPY_BITS`] 时只发出警告：用这样的密钥文件加密的旧文件
+    /// 仍然需要能够解密，加密时才会拒绝使用它（见 [`KeyFile::validate_entropy`]）。
+    ///
     /// # 错误
     ///
     /// 如果文件无法读取或大小不符合要求，则返回错误。
@@ -55,17 +153,104 @@ impl KeyFile {
         let data =
             fs::read(path).with_context(|| format!("无法读取密钥文件: {}", path.display()))?;
 
-        // 验证文件大小是否在允许范围内
+        let keyfile = Self::from_validated(data)?;
+        if let Err(e) = keyfile.validate_entropy(MIN_KEYFILE_ENTROPY_BITS) {
+            log::warn!(
+                "⚠️  密钥文件 {} 不适合用于加密新文件: {e:#}",
+                path.display()
+            );
+        }
+        Ok(keyfile)
+    }
+
+    /// 估算密钥材料的熵：开头 [`KEYFILE_ENTROPY_SAMPLE_LEN`] 字节的香农熵
+    /// `H = -Σ p(b) · log2 p(b)`，单位为比特/字节 (0.0 – 8.0)。
+    ///
+    /// 这只是按字节频率的粗略估算，用于发现误用的低熵数据（文本、全零、重复的模式），
+    /// 高估计值并不能证明材料不可预测。
+    pub fn entropy_estimate(&self) -> f64 {
+        shannon_entropy(&self.data[..self.data.len().min(KEYFILE_ENTROPY_SAMPLE_LEN)])
+    }
+
+    /// [`entropy_estimate`](Self::entropy_estimate) 低于 `min_bits_per_byte` 时返回错误。
+    ///
+    /// 密钥材料短于 [`KEYFILE_ENTROPY_SAMPLE_LEN`] 字节时，样本可达到的最大熵只有
+    /// `log2(长度)`，门槛按同样的比例降低。
+    pub fn validate_entropy(&self, min_bits_per_byte: f64) -> Result<()> {
+        check_entropy(&self.data, min_bits_per_byte)
+    }
+
+    /// 由内存中的密钥材料创建密钥文件。
+    ///
+    /// # 错误
+    ///
+    /// 如果材料的大小不在 [`MIN_KEYFILE_SIZE`] 和 [`MAX_KEYFILE_SIZE`] 之间，则返回错误。
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_validated(data.to_vec())
+    }
+
+    /// 从 `reader` 读取全部密钥材料，按 `format` 解码后创建密钥文件。
+    ///
+    /// 读取的原始输入和十六进制解码的中间结果在使用后都会被清零。
+    ///
+    /// # 错误
+    ///
+    /// 读取失败、十六进制内容无效，或解码后的大小不在允许范围内时返回错误。
+    pub fn from_reader<R: Read>(reader: R, format: KeyfileInputFormat) -> Result<Self> {
+        // 十六进制每个字节占两个字符，另外为换行等空白字符留出余量
+        let limit = match format {
+            KeyfileInputFormat::Binary => MAX_KEYFILE_SIZE,
+            KeyfileInputFormat::Hex => MAX_KEYFILE_SIZE * 4,
+        };
+        let mut input = Vec::new();
+        if let Err(e) = reader.take(limit as u64 + 1).read_to_end(&mut input) {
+            input.zeroize();
+            return Err(e).context("无法读取密钥材料");
+        }
+        if input.len() > limit {
+            input.zeroize();
+            bail!("密钥材料太大了 (超过 {limit} 字节)");
+        }
+
+        let data = match format {
+            KeyfileInputFormat::Binary => input,
+            KeyfileInputFormat::Hex => {
+                let decoded = decode_hex(&input);
+                input.zeroize();
+                decoded?
+            }
+        };
+        Self::from_validated(data)
+    }
+
+    /// 从标准输入读取密钥材料，例如 `hsm-cli get-key | ferox_encryptor encrypt ... --keyfile -`。
+    ///
+    /// # 错误
+    ///
+    /// 标准输入是终端时拒绝读取，避免密钥材料被手动输入并回显；其余错误同 [`KeyFile::from_reader`]。
+    pub fn from_stdin(format: KeyfileInputFormat) -> Result<Self> {
+        let stdin = io::stdin();
+        if stdin.is_terminal() {
+            bail!("标准输入是终端，拒绝从中读取密钥材料；请通过管道或重定向提供密钥文件");
+        }
+        Self::from_reader(stdin.lock(), format)
+    }
+
+    /// 验证密钥材料的大小后创建实例；验证失败时清零材料。
+    fn from_validated(mut data: Vec<u8>) -> Result<Self> {
+        // 验证大小是否在允许范围内
         if data.len() < MIN_KEYFILE_SIZE || data.len() > MAX_KEYFILE_SIZE {
+            let len = data.len();
+            data.zeroize();
             bail!(
                 "密钥文件大小无效: {} 字节 (必须在 {} 和 {} 字节之间)",
-                data.len(),
+                len,
                 MIN_KEYFILE_SIZE,
                 MAX_KEYFILE_SIZE
             );
         }
 
-        Ok(Self { data })
+        Ok(Self::from_data(data))
     }
 
     /// 将密钥文件保存到磁盘。
@@ -79,7 +264,7 @@ impl KeyFile {
     /// 如果文件无法写入，则返回错误。
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let path = path.as_ref();
-        fs::write(path, &self.data)
+        fs::write(path, self.data.as_slice())
             .with_context(|| format!("无法写入密钥文件: {}", path.display()))?;
 
         log::info!("密钥文件已保存: {}", path.display());
@@ -90,25 +275,430 @@ impl KeyFile {
     ///
     /// # 返回
     ///
-    /// 密钥文件数据的 SHA-256 哈希值。
-    pub fn hash(&self) -> [u8; 32] {
-        let mut output = [0u8; KEYFILE_DERIVED_LEN];
-        argon2_config()
-            .hash_password_into(&self.data, KEYFILE_DERIVATION_SALT, &mut output)
-            .unwrap();
-        output
+    /// 密钥文件数据的 Argon2 派生值。只在首次调用时计算，之后返回缓存的结果。
+    ///
+    /// # 错误
+    ///
+    /// Argon2 派生失败时返回错误。
+    pub fn hash(&self) -> Result<[u8; 32], FeroxError> {
+        let derived = self.derived.get_or_init(|| {
+            let mut output = Zeroizing::new([0u8; KEYFILE_DERIVED_LEN]);
+            argon2_config()?.hash_password_into(
+                &self.data,
+                KEYFILE_DERIVATION_SALT,
+                output.as_mut(),
+            )?;
+            Ok(output)
+        });
+        match derived {
+            Ok(output) => Ok(**output),
+            Err(error) => Err(FeroxError::from(*error)),
+        }
+    }
+
+    /// 使用 Shamir 秘密共享把密钥文件拆分为 `n` 个分片，其中任意 `k` 个即可重建密钥文件。
+    ///
+    /// 少于 `k` 个分片不会泄露密钥文件内容的任何信息。每个分片可以用
+    /// [`KeyShare::save_to_file`] 保存为 `.feroxshare` 文件，交给不同的人分别保管。
+    ///
+    /// # 错误
+    ///
+    /// `k` 小于 2 或大于 `n` 时返回错误。
+    pub fn split(&self, n: u8, k: u8) -> Result<Vec<KeyShare>> {
+        if k < 2 {
+            bail!("重建阈值必须至少为 2 (当前为 {k})");
+        }
+        if k > n {
+            bail!("重建阈值 ({k}) 不能大于分片数量 ({n})");
+        }
+
+        let checksum = keyfile_checksum(&self.data);
+        let mut shares: Vec<KeyShare> = (1..=n)
+            .map(|index| KeyShare {
+                index,
+                threshold: k,
+                checksum,
+                data: Vec::with_capacity(self.data.len()),
+            })
+            .collect();
+
+        // 对每个字节构造一个随机的 k-1 次多项式，常数项为该字节，分片 i 保存它在 x = i 处的值
+        let mut coefficients = vec![0u8; k as usize];
+        for &secret in self.data.iter() {
+            coefficients[0] = secret;
+            OsRng.fill_bytes(&mut coefficients[1..]);
+            for share in &mut shares {
+                share.data.push(gf_eval(&coefficients, share.index));
+            }
+        }
+        coefficients.zeroize();
+        Ok(shares)
+    }
+
+    /// 由分片重建密钥文件。
+    ///
+    /// 至少需要拆分时指定的阈值数量的分片，多出的分片会被忽略。重建后会用分片中记录的
+    /// 校验值确认结果，分片损坏或来自不同的拆分时返回错误。
+    pub fn from_shares(shares: &[KeyShare]) -> Result<KeyFile> {
+        let Some(first) = shares.first() else {
+            bail!("没有提供任何密钥文件分片");
+        };
+        let threshold = first.threshold as usize;
+        for share in shares {
+            if share.threshold != first.threshold
+                || share.checksum != first.checksum
+                || share.data.len() != first.data.len()
+            {
+                bail!("密钥文件分片 #{} 与其他分片不属于同一次拆分", share.index);
+            }
+        }
+
+        // 只使用序号互不相同的前 `threshold` 个分片
+        let mut selected: Vec<&KeyShare> = Vec::with_capacity(threshold);
+        for share in shares {
+            if !selected.iter().any(|s| s.index == share.index) {
+                selected.push(share);
+            }
+        }
+        if selected.len() < threshold {
+            bail!(
+                "密钥文件分片不足: 需要 {} 个不同的分片，只提供了 {} 个",
+                threshold,
+                selected.len()
+            );
+        }
+        selected.truncate(threshold);
+
+        // 在 x = 0 处进行拉格朗日插值，求得每个字节多项式的常数项
+        let xs: Vec<u8> = selected.iter().map(|share| share.index).collect();
+        let weights: Vec<u8> = xs
+            .iter()
+            .map(|&xi| {
+                xs.iter()
+                    .filter(|&&xj| xj != xi)
+                    .fold(1u8, |acc, &xj| gf_mul(acc, gf_mul(xj, gf_inverse(xj ^ xi))))
+            })
+            .collect();
+        let data: Vec<u8> = (0..first.data.len())
+            .map(|position| {
+                selected
+                    .iter()
+                    .zip(&weights)
+                    .fold(0u8, |acc, (share, &weight)| {
+                        acc ^ gf_mul(share.data[position], weight)
+                    })
+            })
+            .collect();
+
+        let keyfile = KeyFile::from_data(data);
+        if keyfile_checksum(&keyfile.data) != first.checksum {
+            bail!("由分片重建的密钥文件校验失败，分片可能已损坏或来自不同的密钥文件");
+        }
+        Ok(keyfile)
+    }
+}
+
+/// # 密钥文件分片 (Keyfile Share)
+///
+/// 由 [`KeyFile::split`] 生成的 Shamir 秘密共享分片，可以保存为 `.feroxshare` 文件。
+///
+/// 文件格式: 魔数 `FXS\0` (4) | 版本 (1) | 重建阈值 (1) | 分片序号 (1) | 密钥文件校验值 (8) | 分片数据。
+pub struct KeyShare {
+    /// 分片序号，即多项式的求值点 (1..=n)。
+    index: u8,
+    /// 重建密钥文件所需的分片数量。
+    threshold: u8,
+    /// 密钥文件 SHA-256 哈希的前 8 字节。
+    checksum: [u8; KEYSHARE_CHECKSUM_LEN],
+    /// 分片数据，与密钥文件等长。
+    data: Vec<u8>,
+}
+
+impl KeyShare {
+    /// 分片序号 (从 1 开始)。
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// 重建密钥文件所需的分片数量。
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// 将分片保存到磁盘。
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut bytes = Vec::with_capacity(KEYSHARE_HEADER_LEN + self.data.len());
+        bytes.extend_from_slice(&KEYSHARE_MAGIC);
+        bytes.push(KEYSHARE_FORMAT_VERSION);
+        bytes.push(self.threshold);
+        bytes.push(self.index);
+        bytes.extend_from_slice(&self.checksum);
+        bytes.extend_from_slice(&self.data);
+        let result = fs::write(path, &bytes)
+            .with_context(|| format!("无法写入密钥文件分片: {}", path.display()));
+        bytes.zeroize();
+        result?;
+
+        log::info!("密钥文件分片 #{} 已保存: {}", self.index, path.display());
+        Ok(())
+    }
+
+    /// 从磁盘加载分片。
+    ///
+    /// # 错误
+    ///
+    /// 文件无法读取、不是有效的分片文件或使用了不支持的格式版本时返回错误。
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut bytes =
+            fs::read(path).with_context(|| format!("无法读取密钥文件分片: {}", path.display()))?;
+        let share =
+            Self::parse(&bytes).with_context(|| format!("无效的密钥文件分片: {}", path.display()));
+        bytes.zeroize();
+        share
+    }
+
+    /// 解析分片文件的内容。
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < KEYSHARE_HEADER_LEN || bytes[..4] != KEYSHARE_MAGIC {
+            bail!("文件不是密钥文件分片");
+        }
+        if bytes[4] != KEYSHARE_FORMAT_VERSION {
+            bail!("不支持的分片格式版本 {}", bytes[4]);
+        }
+        let (threshold, index) = (bytes[5], bytes[6]);
+        if threshold < 2 || index == 0 {
+            bail!("分片的阈值或序号无效");
+        }
+        let data = &bytes[KEYSHARE_HEADER_LEN..];
+        if !(MIN_KEYFILE_SIZE..=MAX_KEYFILE_SIZE).contains(&data.len()) {
+            bail!("分片数据长度无效: {} 字节", data.len());
+        }
+        let mut checksum = [0u8; KEYSHARE_CHECKSUM_LEN];
+        checksum.copy_from_slice(&bytes[7..KEYSHARE_HEADER_LEN]);
+        Ok(Self {
+            index,
+            threshold,
+            checksum,
+            data: data.to_vec(),
+        })
     }
 }
 
-/// 实现 `Drop` trait，以在 `KeyFile` 实例离开作用域时安全地擦除其内存中的数据。
-/// 这是为了防止敏感数据（密钥材料）残留在内存中。
-impl Drop for KeyFile {
+impl Drop for KeyShare {
     fn drop(&mut self) {
-        // `zeroize` 会用零覆盖 `data` 向量的内容
         self.data.zeroize();
     }
 }
 
+/// 把一批密钥文件保存到 `output_dir` 中，文件名为 `{name_prefix}_01.key`、`{name_prefix}_02.key` ……
+///
+/// 序号至少两位，数量超过 99 时按需要补零，保证文件名按字典序排列。保存之前检查每个密钥文件都通过
+/// [`KeyFile::validate_entropy`] 并且内容互不相同，也不会覆盖已存在的文件：任何一项不满足时不写入任何文件。
+/// `output_dir` 不存在时会被创建。返回按序号排列的文件路径。
+pub fn save_batch(
+    keyfiles: &[KeyFile],
+    output_dir: &Path,
+    name_prefix: &str,
+) -> Result<Vec<PathBuf>> {
+    if name_prefix.is_empty() || name_prefix.contains(['/', '\\']) {
+        bail!("密钥文件名前缀不能为空，也不能包含路径分隔符: {name_prefix:?}");
+    }
+    let width = keyfiles.len().to_string().len().max(2);
+    let paths: Vec<PathBuf> = (1..=keyfiles.len())
+        .map(|index| output_dir.join(format!("{name_prefix}_{index:0width$}.key")))
+        .collect();
+
+    let mut checksums = std::collections::HashSet::with_capacity(keyfiles.len());
+    for (keyfile, path) in keyfiles.iter().zip(&paths) {
+        keyfile
+            .validate_entropy(MIN_KEYFILE_ENTROPY_BITS)
+            .with_context(|| format!("拒绝保存密钥文件: {}", path.display()))?;
+        if !checksums.insert(keyfile_checksum(&keyfile.data)) {
+            bail!("密钥文件 {} 与同一批中的另一个密钥文件相同", path.display());
+        }
+        if path.exists() {
+            bail!("密钥文件已存在，拒绝覆盖: {}", path.display());
+        }
+    }
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("无法创建密钥文件目录: {}", output_dir.display()))?;
+    for (keyfile, path) in keyfiles.iter().zip(&paths) {
+        keyfile.save_to_file(path)?;
+    }
+    Ok(paths)
+}
+
+/// 解码十六进制文本，忽略其中的 ASCII 空白字符。
+///
+/// 出错时清零已解码的部分，错误信息中不包含输入内容。
+fn decode_hex(input: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(input.len() / 2);
+    let mut high = None;
+    for (position, &c) in input.iter().enumerate() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        let Some(nibble) = (c as char).to_digit(16) else {
+            decoded.zeroize();
+            bail!("十六进制密钥材料在第 {} 个字节处包含无效字符", position + 1);
+        };
+        match high.take() {
+            Some(high) => decoded.push((high << 4 | nibble) as u8),
+            None => high = Some(nibble),
+        }
+    }
+    if high.is_some() {
+        decoded.zeroize();
+        bail!("十六进制密钥材料的位数必须是偶数");
+    }
+    Ok(decoded)
+}
+
+/// 分片文件头的长度：魔数、版本、阈值、序号和校验值。
+const KEYSHARE_HEADER_LEN: usize = KEYSHARE_MAGIC.len() + 3 + KEYSHARE_CHECKSUM_LEN;
+
+/// 密钥文件内容的校验值：SHA-256 哈希的前 8 字节。
+fn keyfile_checksum(data: &[u8]) -> [u8; KEYSHARE_CHECKSUM_LEN] {
+    let digest = Sha256::digest(data);
+    let mut checksum = [0u8; KEYSHARE_CHECKSUM_LEN];
+    checksum.copy_from_slice(&digest[..KEYSHARE_CHECKSUM_LEN]);
+    checksum
+}
+
+/// GF(2^8) 乘法 (AES 使用的约化多项式 x^8 + x^4 + x^3 + x + 1)。
+///
+/// 不使用查找表，也没有依赖于数据的分支，避免基于时间的侧信道。
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// GF(2^8) 乘法逆元，计算 `a^254`（`a` 不能为 0）。
+fn gf_inverse(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// 使用霍纳法则在 GF(2^8) 中计算多项式在 `x` 处的值，`coefficients[0]` 为常数项。
+fn gf_eval(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0u8, |acc, &coefficient| gf_mul(acc, x) ^ coefficient)
+}
+
+/// 从硬件随机数设备（如 `/dev/hwrng`、TRNG 或 HSM 暴露的设备节点）生成密钥文件。
+///
+/// 从 `device_path` 读取 `size` 字节，如果在
+/// [`HARDWARE_ENTROPY_TIMEOUT_SECS`] 秒内没有读完则返回错误。
+/// 读到的数据会经过 FIPS 140-2 扑克测试（基于半字节频率的卡方检验）；
+/// 如果检验未通过，数据会与操作系统随机数 (`OsRng`) 异或混合并记录警告，
+/// 因此结果的随机性不会低于 [`KeyFile::generate`]。
+///
+/// # 错误
+///
+/// `size` 不在允许的密钥文件大小范围内、设备无法打开、读取超时或数据不足时返回错误。
+pub fn generate_keyfile_from_hardware_entropy(device_path: &Path, size: usize) -> Result<KeyFile> {
+    if !(MIN_KEYFILE_SIZE..=MAX_KEYFILE_SIZE).contains(&size) {
+        bail!(
+            "密钥文件大小无效: {} 字节 (必须在 {} 和 {} 字节之间)",
+            size,
+            MIN_KEYFILE_SIZE,
+            MAX_KEYFILE_SIZE
+        );
+    }
+
+    let mut data = read_device_with_timeout(
+        device_path.to_path_buf(),
+        size,
+        Duration::from_secs(HARDWARE_ENTROPY_TIMEOUT_SECS),
+    )?;
+
+    if !passes_poker_test(&data) {
+        log::warn!(
+            "来自 {} 的数据未通过随机性检验，已与系统随机数混合。",
+            device_path.display()
+        );
+        let mut os_bytes = vec![0u8; size];
+        OsRng.fill_bytes(&mut os_bytes);
+        data.iter_mut()
+            .zip(&os_bytes)
+            .for_each(|(byte, os_byte)| *byte ^= os_byte);
+        os_bytes.zeroize();
+    }
+
+    Ok(KeyFile::from_data(data))
+}
+
+/// 在后台线程中从设备读取恰好 `size` 字节，超时则返回错误。
+///
+/// 阻塞的读取无法被取消，超时后读取线程会在后台继续等待，直到设备返回或进程退出。
+fn read_device_with_timeout(
+    device_path: PathBuf,
+    size: usize,
+    timeout: Duration,
+) -> Result<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    let display_path = device_path.display().to_string();
+
+    thread::spawn(move || {
+        let result = File::open(&device_path)
+            .with_context(|| format!("无法打开熵源设备: {}", device_path.display()))
+            .and_then(|mut device| {
+                let mut buffer = vec![0u8; size];
+                match device.read_exact(&mut buffer) {
+                    Ok(()) => Ok(buffer),
+                    Err(e) => {
+                        buffer.zeroize();
+                        Err(anyhow!("从熵源设备读取 {} 字节失败: {}", size, e))
+                    }
+                }
+            });
+        // 如果接收方已经超时放弃，发送失败也无需处理
+        let _ = sender.send(result);
+    });
+
+    receiver.recv_timeout(timeout).map_err(|_| {
+        anyhow!(
+            "读取熵源设备 {display_path} 超时 ({} 秒)",
+            timeout.as_secs()
+        )
+    })?
+}
+
+/// FIPS 140-2 扑克测试：把数据拆分为 4 位半字节并统计 16 种取值的出现次数，
+/// 计算 `X = 16/n * Σf² - n`，其中 `n` 是半字节数量。
+/// `X` 落在 (2.16, 46.17) 区间内时认为通过。
+fn passes_poker_test(data: &[u8]) -> bool {
+    let mut counts = [0u64; 16];
+    for byte in data {
+        counts[(byte >> 4) as usize] += 1;
+        counts[(byte & 0x0F) as usize] += 1;
+    }
+    let n = (data.len() * 2) as f64;
+    let sum_of_squares: f64 = counts.iter().map(|&c| (c * c) as f64).sum();
+    let x = 16.0 / n * sum_of_squares - n;
+    x > 2.16 && x < 46.17
+}
+
 /// 将用户密码和密钥文件结合起来，生成用于最终密钥派生的材料。
 /// 这种方法增强了安全性，因为攻击者需要同时获得密码和密钥文件才能破解加密。
 ///
@@ -120,25 +710,115 @@ impl Drop for KeyFile {
 /// # 返回
 ///
 /// 结合了密码和密钥文件信息的字节向量，将用作 Argon2 的输入。
+///
+/// 结果按密码缓存在 `keyfile` 实例中，批量处理中使用同一密码的后续文件不再重复派生。
 pub fn combine_password_and_keyfile(password: &str, keyfile: &KeyFile) -> Result<Vec<u8>> {
-    // 使用 Argon2 从密钥文件内容派生出一个哈希值
-    let keyfile_hash = keyfile.hash();
+    let mut cache = keyfile
+        .combined
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(cached) = cache.as_ref() {
+        if cached.matches(password)? {
+            return Ok(cached.material.to_vec());
+        }
+    }
+
+    // 使用 Argon2 从密钥文件内容派生出一个哈希值（已缓存时直接复用）
+    let keyfile_hash = keyfile.hash()?;
 
     // 使用 Argon2 将密码和密钥文件的哈希值结合起来
     let mut combined_hash = vec![0u8; KEYFILE_DERIVED_LEN];
     argon2_config()
-        .hash_password_into(
-            password.as_bytes(),
-            &keyfile_hash, // 使用密钥文件的哈希作为盐
-            &mut combined_hash,
-        )
+        .and_then(|argon2| {
+            argon2.hash_password_into(
+                password.as_bytes(),
+                &keyfile_hash, // 使用密钥文件的哈希作为盐
+                &mut combined_hash,
+            )
+        })
         .map_err(|e| anyhow::anyhow!("Argon2 error: {}", e))?;
 
+    *cache = Some(CombinedMaterial::new(password, &combined_hash)?);
     Ok(combined_hash)
 }
 
+/// 以常数时间（与内容无关）比较两个字节串，长度不同时直接返回 `false`。
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 由密码材料计算密钥文件承诺：`HMAC-SHA256(password_material, "FEROX_KEYFILE_COMMIT")`。
+///
+/// `password_material` 必须是 [`combine_password_and_keyfile`] 的输出。
+pub(crate) fn keyfile_commitment(password_material: &[u8]) -> Result<[u8; KEYFILE_COMMITMENT_LEN]> {
+    Ok(commitment_mac(password_material)?
+        .finalize()
+        .into_bytes()
+        .into())
+}
+
+/// 创建用于计算密钥文件承诺的 HMAC 实例，已写入固定消息。
+fn commitment_mac(password_material: &[u8]) -> Result<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(password_material).context("无法创建HMAC实例")?;
+    mac.update(KEYFILE_COMMITMENT_CONTEXT);
+    Ok(mac)
+}
+
+/// 检查密钥文件（和密码）是否与加密文件匹配，而不解密整个文件。
+///
+/// 启用 [`EncryptionOptions::keyfile_commitment`](crate::EncryptionOptions::keyfile_commitment)
+/// 加密的文件会在文件头中记录一个密钥文件承诺。本函数用密码和
+/// 给定的密钥文件重新计算承诺并以常数时间比较。返回 `false` 表示密钥文件或密码
+/// 至少有一个不正确；两者都需要才能得出匹配的结论。
+///
+/// 只需要两次密钥文件派生（约 19 MiB 内存），远快于完整的验证或解密。
+/// 注意：这也意味着承诺可以被用来以较低的代价离线猜测密码，所以它默认不写入。
+///
+/// # 错误
+///
+/// 密钥文件无法加载、加密文件无法读取，或文件头中没有密钥文件承诺
+/// （加密时没有启用承诺或没有使用密钥文件，或文件由旧版本创建）时返回错误。
+pub fn verify_keyfile_matches_encrypted_file(
+    keyfile_path: &Path,
+    encrypted_path: &Path,
+    password: &str,
+) -> Result<bool> {
+    let keyfile = KeyFile::load_from_file(keyfile_path)?;
+    let file = File::open(encrypted_path)
+        .with_context(|| format!("无法打开文件: {}", encrypted_path.display()))?;
+    let header = FileHeader::read_from(&mut BufReader::new(file))?;
+    match keyfile_commitment_matches(&header, password, &keyfile)? {
+        Some(matches) => Ok(matches),
+        None => bail!(
+            "文件头中没有密钥文件承诺 (加密时未使用密钥文件，或文件由旧版本创建): {}",
+            encrypted_path.display()
+        ),
+    }
+}
+
+/// 用密码和密钥文件重新计算承诺，与 `header` 中记录的承诺以常数时间比较。
+///
+/// 文件头中没有密钥文件承诺时返回 `None`。密钥文件的派生结果缓存在 `keyfile` 中，
+/// 对同一批文件重复调用只在第一次付出派生的代价。
+pub(crate) fn keyfile_commitment_matches(
+    header: &FileHeader,
+    password: &str,
+    keyfile: &KeyFile,
+) -> Result<Option<bool>> {
+    let Some(expected) = header.keyfile_commitment() else {
+        return Ok(None);
+    };
+    let mut password_material = combine_password_and_keyfile(password, keyfile)?;
+    let mac = commitment_mac(&password_material);
+    password_material.zeroize();
+    Ok(Some(mac?.verify_slice(&expected).is_ok()))
+}
+
 /// 验证一个文件是否可以用作密钥文件。
 ///
+/// 只检查文件是否存在及其大小。密钥材料的熵在 [`KeyFile::load_from_file`] 中检查：
+/// 熵太低时加载只发出警告，用它加密新文件时才会被拒绝。
+///
 /// # 参数
 ///
 /// * `path` - 指向潜在密钥文件的路径。
@@ -176,14 +856,49 @@ pub fn validate_keyfile<P: AsRef<Path>>(path: P) -> Result<()> {
     Ok(())
 }
 
+/// `data` 开头 [`KEYFILE_ENTROPY_SAMPLE_LEN`] 字节的熵低于（按样本长度调整后的）门槛时返回错误。
+fn check_entropy(data: &[u8], min_bits_per_byte: f64) -> Result<()> {
+    let sample = &data[..data.len().min(KEYFILE_ENTROPY_SAMPLE_LEN)];
+    let max_bits = (sample.len() as f64).log2().min(8.0);
+    let required = min_bits_per_byte * max_bits / 8.0;
+    let entropy = shannon_entropy(sample);
+    if entropy < required {
+        bail!(
+            "密钥材料的熵太低: {entropy:.2} 比特/字节 (至少需要 {required:.2})，\
+             它看起来不是随机数据。请用 `generate-key` 生成密钥文件"
+        );
+    }
+    Ok(())
+}
+
+/// 按字节频率计算的香农熵（比特/字节）；空输入为 0。
+fn shannon_entropy(sample: &[u8]) -> f64 {
+    if sample.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &byte in sample {
+        counts[usize::from(byte)] += 1;
+    }
+    let len = sample.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
 /// 返回一个静态的 Argon2 配置实例。
 /// 这确保了在整个程序中都使用一致的、预设的 Argon2 参数来处理密钥文件。
-fn argon2_config() -> Argon2<'static> {
+fn argon2_config() -> Result<Argon2<'static>, argon2::Error> {
     // 使用 Argon2id 算法, v19 版本
     // 参数: m_cost=19MiB, t_cost=2, p_cost=1, output_len=32
-    Argon2::new(
+    Ok(Argon2::new(
         argon2::Algorithm::Argon2id,
         argon2::Version::V0x13,
-        argon2::Params::new(19 * 1024, 2, 1, Some(KEYFILE_DERIVED_LEN)).unwrap(),
-    )
+        argon2::Params::new(19 * 1024, 2, 1, Some(KEYFILE_DERIVED_LEN))?,
+    ))
 }
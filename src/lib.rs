@@ -18,19 +18,22 @@
 //!   *Encryption parameters stored in file headers, ensuring future versions can decrypt old files.*
 //! - **可定制的安全等级 (Customizable Security Levels)**: 提供多个安全级别选项，允许用户在性能和安全性之间进行权衡。
 //!   *Provides multiple security level options, allowing users to balance performance and security.*
-//! - **健壮性 (Robustness)**: 能够优雅地处理 `Ctrl+C` 中断，自动清理不完整的输出文件。
-//!   *Gracefully handles `Ctrl+C` interrupts, automatically cleaning up incomplete output files.*
+//! - **健壮性 (Robustness)**: 能够优雅地处理 `Ctrl+C`、`SIGTERM` 等终止信号，自动清理不完整的输出文件。
+//!   *Gracefully handles `Ctrl+C` and termination signals, automatically cleaning up incomplete output files.*
 //!
 //! ## 加密文件格式
 //!
-//! 加密文件使用 `.feroxcrypt` 扩展名，其内部结构如下:
+//! 加密文件使用 `.feroxcrypt` 扩展名。当前格式（版本 2）的内部结构如下:
 //!
 //! ```text
-//! +-------------------------+-----------------------+------------------+----------------+-----------------------+--------------------+-------------------+
-//! | 文件名长度 (2字节)      | 原始文件名 (可变)     | Salt (16字节)    | IV (16字节)    | Argon2 参数 (12字节) | 加密数据 (可变)    | HMAC 标签 (32字节) |
-//! +-------------------------+-----------------------+------------------+----------------+-----------------------+--------------------+-------------------+
+//! +-----------------+-------------+--------------+--------------+-------------------+-------------------+---------------+-------------+----------------------+-----------------+--------------------+--------------------+-----------------+--------------------+
+//! | 魔数 "FXC\0" (4) | 版本号 (1)  | 加密算法 (1) | 密钥派生 (1) | 文件名长度 (2字节) | 原始文件名 (可变) | Salt (16字节) | IV (16字节) | Argon2 参数 (12字节) | 接收者数量 (1)  | 扩展区长度 (2字节) | 扩展区 TLV (可变)  | 加密数据 (可变) | HMAC 标签 (32字节) |
+//! +-----------------+-------------+--------------+--------------+-------------------+-------------------+---------------+-------------+----------------------+-----------------+--------------------+--------------------+-----------------+--------------------+
 //! ```
 //!
+//! HMAC 标签同时覆盖文件头和加密数据。没有魔数的遗留格式（版本 0）省略了前 7 个字节和扩展区，
+//! 其标签只覆盖加密数据；版本 1 没有接收者数量字段。这些文件仍然可以正常解密。详见 [`format`] 模块。
+//!
 //! ## 使用示例
 //!
 //! ```rust,no_run
@@ -62,24 +65,124 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! 加密令牌、配置值之类的小型机密时，可以完全在内存中完成，不涉及任何文件:
+//!
+//! ```rust,no_run
+//! use ferox_encryptor::{decrypt_to_vec, encrypt_to_vec};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let encrypted = encrypt_to_vec(b"api-token-123", "token.txt", "my_secure_password", None)?;
+//! let (plaintext, filename) = decrypt_to_vec(&encrypted, "my_secure_password", None)?;
+//! assert_eq!(plaintext, b"api-token-123");
+//! assert_eq!(filename, "token.txt");
+//! # Ok(())
+//! # }
+//! ```
 
 // 声明本 crate 的模块
+pub mod audit;
 pub mod batch;
+#[cfg(feature = "bench-internals")]
+#[doc(hidden)]
+pub mod bench_support;
+pub mod capabilities;
+pub mod cleanup;
 pub mod constants;
+pub mod credentials;
 pub mod decrypt;
 pub mod encrypt;
+pub mod error;
+pub mod format;
+mod header_parser;
+pub mod hook;
+pub mod index;
+pub mod inspect;
 pub mod interactive;
 pub mod keyfile;
+pub mod location;
+pub mod manifest;
+pub mod migration;
+pub mod password;
+mod permissions;
+mod pipeline;
+pub mod progress;
+pub mod readme;
+pub mod resume;
+pub mod secure_memory;
+mod stream_io;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod watchdog;
+mod xattrs;
 
 // 从子模块中重新导出公共类型，方便外部调用者使用。
 // 例如，外部可以直接使用 `ferox_encryptor::Level` 而不是 `ferox_encryptor::lib::Level`。
+pub use audit::{
+    verify_audit_log, AuditLog, AuditOperation, AuditOutcome, AuditRecord, AuditVerification,
+};
 pub use batch::{
-    batch_decrypt_directory, batch_decrypt_files, batch_encrypt_directory, batch_encrypt_files,
-    BatchConfig, BatchResult,
+    batch_compare_files, batch_decrypt_directory, batch_decrypt_files, batch_decrypt_paths,
+    batch_encrypt_directory, batch_encrypt_files, batch_encrypt_files_atomic, batch_encrypt_iter,
+    batch_encrypt_paths, batch_verify_directory, batch_verify_files, cleanup_orphaned_sidecars,
+    execute_batch_plan, expand_glob_arguments, find_orphaned_sidecars, plan_batch_encrypt,
+    plan_batch_encrypt_files, BatchBreakdown, BatchConfig, BatchPlan, BatchResult, GroupCounts,
+    PasswordValidator, PlannedAction, PlannedItem,
+};
+pub use capabilities::{crypto_capabilities, CapabilityReport};
+pub use cleanup::{
+    cleanup_partial_output, install_interrupt_handler, register_termination_hook,
+    run_termination_cleanup,
+};
+pub use credentials::{Credential, Credentials};
+pub use decrypt::{
+    compute_hmac_for_file, compute_streaming_hmac, decrypt_base64_to_file, decrypt_first_n_chunks,
+    decrypt_in_memory, decrypt_to_vec, decrypt_to_writer_vec, decrypt_unverified,
+    run_decryption_flow, run_decryption_flow_no_overwrite_check, DecryptionOptions,
+    NameNormalization, OutputPermissions, OverwriteMode, RecoveryReport,
+};
+pub use encrypt::{
+    detect_hardlinks, encrypt_deterministic, encrypt_file_to_base64, encrypt_in_memory,
+    encrypt_to_vec, encrypt_with_recipient_count_hint, encrypt_with_time_limit,
+    run_encryption_flow, run_encryption_flow_with_hardlink_detection, EncryptionOptions,
+};
+pub use error::{FeroxError, Stage};
+pub use format::CipherSuite;
+pub use hook::CompletionHook;
+pub use index::{
+    batch_encrypt_directory_with_index, encrypt_directory_with_index, search_index, EncryptedIndex,
 };
-pub use decrypt::run_decryption_flow;
-pub use encrypt::run_encryption_flow;
-pub use keyfile::{validate_keyfile, KeyFile};
+pub use inspect::{
+    benchmark_level, check_compatibility, compare_file, estimate_decryption_time, format_utc_time,
+    inspect_file, scan_encrypted, scan_format_versions, secure_compare_files, verify_file,
+    CompareReport, Compatibility, CompatibilityEntry, EncryptedFileInfo, FileInfo, LevelBenchmark,
+};
+pub use keyfile::{
+    save_batch, validate_keyfile, verify_keyfile_matches_encrypted_file, KeyFile, KeyShare,
+    KeyfileInputFormat,
+};
+pub use location::{check_encryption_location, check_location_with_roots, LocationWarning};
+pub use manifest::{
+    batch_encrypt_directory_with_manifest, verify_restore, ManifestEntry, RestoreManifest,
+    RestoreReport,
+};
+pub use migration::{
+    migration_report, MigrationEntry, MigrationPlan, MigrationReason, MigrationReport,
+};
+#[cfg(feature = "keyring")]
+pub use password::OsKeychain;
+pub use password::{forget_password, PasswordSource, PasswordStore};
+pub use pipeline::{Compression, Pipeline, PipelineBuilder};
+pub use progress::{ProgressEvent, ProgressSink};
+pub use readme::DirectoryReadme;
+pub use resume::{encrypt_resumable, encrypt_resumable_with_progress};
+pub use secure_memory::SecureBuffer;
+pub use watchdog::IoOperation;
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 /// # 安全级别 (Security Levels)
 ///
@@ -92,7 +195,12 @@ pub use keyfile::{validate_keyfile, KeyFile};
 /// *Each level represents a different trade-off between security and performance:*
 /// *- Higher levels provide better protection against brute-force attacks.*
 /// *- Lower levels encrypt/decrypt faster but with correspondingly reduced security.*
-#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+///
+/// 通过 serde 序列化为小写的级别名称（例如 `"moderate"`），与命令行中的写法相同。
+#[derive(
+    clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
 pub enum Level {
     /// **交互式 (Interactive)**: 速度最快，适用于普通文件。
     /// - Argon2 参数 (Parameters): m_cost=19MiB, t_cost=2, p_cost=1
@@ -111,7 +219,51 @@ pub enum Level {
     Paranoid,
 }
 
+/// 全局默认安全级别，所有线程共享；以 `Level as u8` 存储。
+static GLOBAL_DEFAULT_LEVEL: AtomicU8 = AtomicU8::new(Level::Moderate as u8);
+
+thread_local! {
+    /// 当前线程的默认安全级别；为 `None` 时使用全局默认值。
+    static DEFAULT_LEVEL: Cell<Option<Level>> = const { Cell::new(None) };
+}
+
+/// 设置**当前线程**的默认安全级别，覆盖全局默认值。
+///
+/// [`EncryptionOptions::new`] 和 [`BatchConfig::default`] 使用 [`default_level`] 作为安全级别，
+/// 因此总是使用同一级别的应用不必在每次调用时都指定它。该设置只影响调用它的线程，
+/// 不会影响其他线程（包括之后创建的线程）；需要对所有线程生效时使用 [`Level::set_global_default`]。
+pub fn set_default_level(level: Level) {
+    DEFAULT_LEVEL.with(|default| default.set(Some(level)));
+}
+
+/// 当前线程的默认安全级别：用 [`set_default_level`] 设置过时为该值，否则为 [`Level::global_default`]。
+pub fn default_level() -> Level {
+    DEFAULT_LEVEL
+        .with(Cell::get)
+        .unwrap_or_else(Level::global_default)
+}
+
 impl Level {
+    /// 所有安全级别，从低到高排列。
+    pub const ALL: [Level; 3] = [Level::Interactive, Level::Moderate, Level::Paranoid];
+
+    /// 设置所有线程共享的全局默认安全级别（初始为 [`Level::Moderate`]）。
+    ///
+    /// 立即对所有线程生效，包括正在其他线程中创建选项的代码；
+    /// 用 [`set_default_level`] 设置过线程默认值的线程不受影响。
+    pub fn set_global_default(level: Level) {
+        GLOBAL_DEFAULT_LEVEL.store(level as u8, Ordering::Relaxed);
+    }
+
+    /// 所有线程共享的全局默认安全级别。
+    pub fn global_default() -> Level {
+        match GLOBAL_DEFAULT_LEVEL.load(Ordering::Relaxed) {
+            level if level == Level::Interactive as u8 => Level::Interactive,
+            level if level == Level::Paranoid as u8 => Level::Paranoid,
+            _ => Level::Moderate,
+        }
+    }
+
     /// 返回此安全级别对应的 Argon2 参数 (Returns Argon2 parameters for this security level)
     ///
     /// # 返回 (Returns)
@@ -135,17 +287,234 @@ impl Level {
             Level::Paranoid => (256 * 1024, 4, 1),   // 256 MiB, 4 次迭代 (256 MiB, 4 iterations)
         }
     }
+
+    /// 级别的英文名称，例如 `Paranoid`；自定义参数在输出中统一显示为 `Custom`。
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Interactive => "Interactive",
+            Level::Moderate => "Moderate",
+            Level::Paranoid => "Paranoid",
+        }
+    }
+
+    /// 面向用户的简短说明：速度与安全性的权衡和适用场景，供交互式界面和图形前端显示。
+    pub fn description(&self) -> &'static str {
+        match self {
+            Level::Interactive => "快速，适合频繁访问的文件",
+            Level::Moderate => "推荐，个人文档、敏感数据的最佳选择",
+            Level::Paranoid => "最安全，适合高度敏感数据、长期存储",
+        }
+    }
+
+    /// 密钥派生时 Argon2 使用的内存（字节），即 [`argon2_params`](Self::argon2_params) 的内存成本。
+    pub fn memory_cost_bytes(&self) -> u64 {
+        u64::from(self.argon2_params().0) * 1024
+    }
+
+    /// 与 Argon2 参数完全匹配的预设级别；参数是自定义的组合时返回 `None`。
+    pub(crate) fn from_argon2_params(params: (u32, u32, u32)) -> Option<Level> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.argon2_params() == params)
+    }
+
+    /// 写入文件头安全级别扩展的标识字节。
+    pub(crate) fn header_byte(&self) -> u8 {
+        match self {
+            Level::Interactive => 1,
+            Level::Moderate => 2,
+            Level::Paranoid => 3,
+        }
+    }
+
+    /// 由文件头安全级别扩展的标识字节得到级别；自定义参数或未知的值返回 `None`。
+    pub(crate) fn from_header_byte(byte: u8) -> Option<Level> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.header_byte() == byte)
+    }
+
+    /// 高一档的安全级别；已是 [`Level::Paranoid`] 时返回 `None`。
+    ///
+    /// 适用于重新加密流程：“当前级别太低时升级到下一档”。
+    pub fn next_higher(&self) -> Option<Level> {
+        match self {
+            Level::Interactive => Some(Level::Moderate),
+            Level::Moderate => Some(Level::Paranoid),
+            Level::Paranoid => None,
+        }
+    }
+
+    /// 低一档的安全级别；已是 [`Level::Interactive`] 时返回 `None`。
+    pub fn next_lower(&self) -> Option<Level> {
+        match self {
+            Level::Interactive => None,
+            Level::Moderate => Some(Level::Interactive),
+            Level::Paranoid => Some(Level::Moderate),
+        }
+    }
+}
+
+/// “相反”的安全级别：`Interactive` 与 `Paranoid` 互换，处于中间的 `Moderate` 保持不变。
+///
+/// *`!Level::Interactive == Level::Paranoid` and vice versa; `Moderate` is its own opposite.*
+impl std::ops::Not for Level {
+    type Output = Level;
+
+    fn not(self) -> Level {
+        match self {
+            Level::Interactive => Level::Paranoid,
+            Level::Moderate => Level::Moderate,
+            Level::Paranoid => Level::Interactive,
+        }
+    }
+}
+
+/// 显示级别名称和 Argon2 参数，例如 `Moderate (m=64MiB, t=3, p=1)`，用于面向用户的输出。
+///
+/// *Shows the level name with its Argon2 parameters, e.g. `Moderate (m=64MiB, t=3, p=1)`.*
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.name();
+        let (m_cost, t_cost, p_cost) = self.argon2_params();
+        write!(f, "{name} (m={}MiB, t={t_cost}, p={p_cost})", m_cost / 1024)
+    }
+}
+
+/// 由级别名称解析安全级别，不区分大小写（例如 `moderate`、`Paranoid`），
+/// 也接受 [`Display`](std::fmt::Display) 的完整输出，因此 `level.to_string().parse()` 总是得到原来的级别。
+///
+/// *Parses a level name case-insensitively; the full `Display` output also round-trips.*
+impl std::str::FromStr for Level {
+    type Err = ParseLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Level::ALL
+            .into_iter()
+            .find(|level| {
+                s.eq_ignore_ascii_case(level.name()) || s.eq_ignore_ascii_case(&level.to_string())
+            })
+            .ok_or_else(|| ParseLevelError {
+                input: s.to_string(),
+            })
+    }
+}
+
+/// 无法解析的安全级别名称，由 [`Level`] 的 `FromStr` 实现返回。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelError {
+    input: String,
+}
+
+impl std::fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "未知的安全级别: {} (可选: interactive、moderate、paranoid)",
+            self.input
+        )
+    }
 }
 
+impl std::error::Error for ParseLevelError {}
+
 // --- 集成测试 ---
 #[cfg(test)]
 mod tests {
-    use super::{decrypt, encrypt, keyfile::KeyFile, Level};
+    use super::{decrypt, default_level, encrypt, keyfile::KeyFile, set_default_level, Level};
     use anyhow::Result;
     use std::fs;
     use std::sync::{Arc, Mutex};
     use tempfile::TempDir;
 
+    /// `constants.rs` 中相互依赖的常量保持一致。
+    #[test]
+    fn test_validate_constants() {
+        super::constants::validate_constants().unwrap();
+    }
+
+    /// `Level` 的 `Display` 输出包含级别名称和 Argon2 参数。
+    #[test]
+    fn test_level_display() {
+        assert_eq!(
+            Level::Interactive.to_string(),
+            "Interactive (m=19MiB, t=2, p=1)"
+        );
+        assert_eq!(Level::Moderate.to_string(), "Moderate (m=64MiB, t=3, p=1)");
+        assert_eq!(Level::Paranoid.to_string(), "Paranoid (m=256MiB, t=4, p=1)");
+    }
+
+    /// 每个级别的名称（任意大小写）和 `Display` 输出都能解析回原来的级别。
+    #[test]
+    fn test_level_parse_display_round_trip() {
+        for level in Level::ALL {
+            assert_eq!(level.to_string().parse::<Level>(), Ok(level));
+            assert_eq!(level.name().parse::<Level>(), Ok(level));
+            assert_eq!(level.name().to_lowercase().parse::<Level>(), Ok(level));
+            assert_eq!(level.name().to_uppercase().parse::<Level>(), Ok(level));
+            assert!(!level.description().is_empty());
+        }
+        assert!("extreme".parse::<Level>().is_err());
+        assert!("".parse::<Level>().is_err());
+        assert_eq!(Level::Interactive.memory_cost_bytes(), 19 * 1024 * 1024);
+        assert_eq!(Level::Paranoid.memory_cost_bytes(), 256 * 1024 * 1024);
+    }
+
+    /// 每个级别序列化为小写名称，并能反序列化回原来的级别。
+    #[test]
+    fn test_level_serde_round_trip() {
+        for level in Level::ALL {
+            let json = serde_json::to_string(&level).unwrap();
+            assert_eq!(json, format!("\"{}\"", level.name().to_lowercase()));
+            assert_eq!(serde_json::from_str::<Level>(&json).unwrap(), level);
+        }
+        assert!(serde_json::from_str::<Level>("\"extreme\"").is_err());
+    }
+
+    /// 相邻级别和“相反”级别的所有转换，包括两端的边界。
+    #[test]
+    fn test_level_transitions() {
+        assert_eq!(Level::Interactive.next_higher(), Some(Level::Moderate));
+        assert_eq!(Level::Moderate.next_higher(), Some(Level::Paranoid));
+        assert_eq!(Level::Paranoid.next_higher(), None);
+
+        assert_eq!(Level::Interactive.next_lower(), None);
+        assert_eq!(Level::Moderate.next_lower(), Some(Level::Interactive));
+        assert_eq!(Level::Paranoid.next_lower(), Some(Level::Moderate));
+
+        assert_eq!(!Level::Interactive, Level::Paranoid);
+        assert_eq!(!Level::Moderate, Level::Moderate);
+        assert_eq!(!Level::Paranoid, Level::Interactive);
+
+        for level in Level::ALL {
+            assert_eq!(!!level, level);
+            // 升一档再降一档（或反过来）回到原来的级别
+            if let Some(higher) = level.next_higher() {
+                assert_eq!(higher.next_lower(), Some(level));
+            }
+            if let Some(lower) = level.next_lower() {
+                assert_eq!(lower.next_higher(), Some(level));
+            }
+        }
+    }
+
+    /// 线程默认级别只影响设置它的线程。
+    #[test]
+    fn test_thread_default_level_is_thread_local() {
+        std::thread::spawn(|| {
+            set_default_level(Level::Paranoid);
+            assert_eq!(default_level(), Level::Paranoid);
+            assert_eq!(super::BatchConfig::default().level, Level::Paranoid);
+            assert_eq!(encrypt::EncryptionOptions::new("pw").level, Level::Paranoid);
+        })
+        .join()
+        .unwrap();
+
+        // 其他线程回到全局默认值
+        assert_eq!(default_level(), Level::global_default());
+    }
+
     /// 端到端测试：加密一个文件，然后解密，并验证内容是否一致。
     #[test]
     fn test_e2e_encryption_decryption() -> Result<()> {
@@ -0,0 +1,241 @@
+// src/location.rs
+
+//! # 位置检查模块 (Location Checks)
+//!
+//! 批量加密之前尽力检查目标目录是否位于云同步目录（Dropbox、OneDrive、Google Drive、iCloud，
+//! 或 [`SYNC_ROOTS_ENV_VAR`] 中列出的目录）之中，或者位于本身已经加密的文件系统
+//! （gocryptfs、EncFS、CryFS、eCryptfs、VeraCrypt 卷等）之上。前者会把成千上万个加密文件
+//! 同步出去，后者通常意味着重复加密。检查只用于提醒，识别不到不代表位置安全。
+//!
+//! *Best-effort detection of cloud-synced folders and already-encrypted filesystems before a
+//! batch encryption. It only produces warnings; a clean result does not prove anything.*
+
+use crate::constants::SYNC_ROOTS_ENV_VAR;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 同步客户端放在同步根目录中的标记文件或目录，以及对应的服务名。
+const SYNC_MARKERS: &[(&str, &str)] = &[
+    (".dropbox", "Dropbox"),
+    (".dropbox.cache", "Dropbox"),
+    (".tmp.drivedownload", "Google Drive"),
+    (".tmp.driveupload", "Google Drive"),
+];
+
+/// 本身就是加密容器的文件系统类型（`/proc/self/mounts` 中的第三列）。
+#[cfg(target_os = "linux")]
+const ENCRYPTED_FILESYSTEMS: &[(&str, &str)] = &[
+    ("ecryptfs", "eCryptfs"),
+    ("fuse.gocryptfs", "gocryptfs"),
+    ("fuse.encfs", "EncFS"),
+    ("fuse.cryfs", "CryFS"),
+    ("fuse.securefs", "securefs"),
+];
+
+/// # 位置警告 (Location Warning)
+///
+/// 批量加密的目标目录可能不适合存放加密文件的原因。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocationWarning {
+    /// 目录位于云同步目录之中，加密生成的文件会被同步出去。
+    SyncedFolder {
+        /// 同步服务的名称。
+        service: &'static str,
+        /// 识别出的同步根目录。
+        root: PathBuf,
+    },
+    /// 目录位于本身已经加密的文件系统上。
+    EncryptedFilesystem {
+        /// 文件系统的名称。
+        kind: &'static str,
+        /// 该文件系统的挂载点。
+        mount_point: PathBuf,
+    },
+}
+
+impl fmt::Display for LocationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SyncedFolder { service, root } => write!(
+                f,
+                "位于 {service} 同步目录 {} 中，加密生成的文件会被同步到云端",
+                root.display()
+            ),
+            Self::EncryptedFilesystem { kind, mount_point } => write!(
+                f,
+                "位于已加密的 {kind} 文件系统 {} 上，可能是重复加密",
+                mount_point.display()
+            ),
+        }
+    }
+}
+
+/// 检查 `directory` 是否位于云同步目录或已加密的文件系统中。
+///
+/// 除了内置识别的同步目录外，[`SYNC_ROOTS_ENV_VAR`] 中列出的目录也视为同步目录。
+/// 无法读取的目录或挂载信息会被忽略，因此结果可能不完整。
+pub fn check_encryption_location(directory: &Path) -> Vec<LocationWarning> {
+    let extra_roots: Vec<PathBuf> = std::env::var_os(SYNC_ROOTS_ENV_VAR)
+        .map(|value| std::env::split_paths(&value).collect())
+        .unwrap_or_default();
+    check_location_with_roots(directory, &extra_roots)
+}
+
+/// 与 [`check_encryption_location`] 相同，但额外的同步目录由调用者给出。
+pub fn check_location_with_roots(
+    directory: &Path,
+    extra_roots: &[PathBuf],
+) -> Vec<LocationWarning> {
+    let directory = directory
+        .canonicalize()
+        .unwrap_or_else(|_| directory.to_path_buf());
+    let mut warnings = Vec::new();
+    if let Some(warning) = synced_folder(&directory, extra_roots) {
+        warnings.push(warning);
+    }
+    if let Some(warning) = encrypted_filesystem(&directory) {
+        warnings.push(warning);
+    }
+    warnings
+}
+
+/// 从 `directory` 向上查找同步根目录，返回最近的一个。
+fn synced_folder(directory: &Path, extra_roots: &[PathBuf]) -> Option<LocationWarning> {
+    for ancestor in directory.ancestors() {
+        let name = ancestor
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let service = SYNC_MARKERS
+            .iter()
+            .find(|(marker, _)| ancestor.join(marker).exists())
+            .map(|(_, service)| *service)
+            .or_else(|| {
+                // OneDrive 不放标记文件，但根目录的名称是固定的
+                (name == "OneDrive" || name.starts_with("OneDrive - ")).then_some("OneDrive")
+            })
+            .or_else(|| (name == "com~apple~CloudDocs").then_some("iCloud Drive"))
+            .or_else(|| {
+                extra_roots
+                    .iter()
+                    .any(|root| root.canonicalize().is_ok_and(|root| root == ancestor))
+                    .then_some("已配置的同步服务")
+            });
+        if let Some(service) = service {
+            return Some(LocationWarning::SyncedFolder {
+                service,
+                root: ancestor.to_path_buf(),
+            });
+        }
+    }
+    None
+}
+
+/// `directory` 所在的文件系统是否本身已经加密（目前只在 Linux 上通过挂载表识别）。
+#[cfg(target_os = "linux")]
+fn encrypted_filesystem(directory: &Path) -> Option<LocationWarning> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    encrypted_mount(directory, &mounts)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn encrypted_filesystem(_directory: &Path) -> Option<LocationWarning> {
+    None
+}
+
+/// 在 `/proc/self/mounts` 格式的挂载表中找到 `directory` 所在的挂载点，判断它是否是加密容器。
+#[cfg(target_os = "linux")]
+fn encrypted_mount(directory: &Path, mounts: &str) -> Option<LocationWarning> {
+    let (device, mount_point, fs_type) = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            // 挂载表中的空格等字符以八进制转义
+            let mount_point = PathBuf::from(fields.next()?.replace("\\040", " "));
+            let fs_type = fields.next()?;
+            Some((device, mount_point, fs_type))
+        })
+        .filter(|(_, mount_point, _)| directory.starts_with(mount_point))
+        .max_by_key(|(_, mount_point, _)| mount_point.as_os_str().len())?;
+
+    let kind = ENCRYPTED_FILESYSTEMS
+        .iter()
+        .find(|(name, _)| *name == fs_type)
+        .map(|(_, kind)| *kind)
+        .or_else(|| {
+            device
+                .starts_with("/dev/mapper/veracrypt")
+                .then_some("VeraCrypt")
+        })?;
+    Some(LocationWarning::EncryptedFilesystem { kind, mount_point })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sync_markers_trigger_warning() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path().canonicalize()?;
+
+        let plain = root.join("plain/docs");
+        fs::create_dir_all(&plain)?;
+        assert!(synced_folder(&plain, &[]).is_none());
+
+        let dropbox = root.join("Dropbox");
+        fs::create_dir_all(dropbox.join("work/reports"))?;
+        fs::write(dropbox.join(".dropbox"), "{}")?;
+        assert_eq!(
+            synced_folder(&dropbox.join("work/reports"), &[]),
+            Some(LocationWarning::SyncedFolder {
+                service: "Dropbox",
+                root: dropbox,
+            })
+        );
+
+        let onedrive = root.join("OneDrive - Contoso/team");
+        fs::create_dir_all(&onedrive)?;
+        assert!(matches!(
+            synced_folder(&onedrive, &[]),
+            Some(LocationWarning::SyncedFolder {
+                service: "OneDrive",
+                ..
+            })
+        ));
+
+        // 配置的目录没有任何标记文件
+        let configured = check_location_with_roots(&plain, &[root.join("plain")]);
+        assert!(matches!(
+            configured.as_slice(),
+            [LocationWarning::SyncedFolder { root: found, .. }] if *found == root.join("plain")
+        ));
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_encrypted_mounts_are_recognised() {
+        let mounts = "/dev/sda1 / ext4 rw 0 0\n\
+                      /home/u/.vault /home/u/vault fuse.gocryptfs rw 0 0\n\
+                      /dev/mapper/veracrypt1 /media/veracrypt1 ext4 rw 0 0\n";
+        assert!(encrypted_mount(Path::new("/home/u/docs"), mounts).is_none());
+        assert_eq!(
+            encrypted_mount(Path::new("/home/u/vault/docs"), mounts),
+            Some(LocationWarning::EncryptedFilesystem {
+                kind: "gocryptfs",
+                mount_point: PathBuf::from("/home/u/vault"),
+            })
+        );
+        assert!(matches!(
+            encrypted_mount(Path::new("/media/veracrypt1/a"), mounts),
+            Some(LocationWarning::EncryptedFilesystem {
+                kind: "VeraCrypt",
+                ..
+            })
+        ));
+    }
+}
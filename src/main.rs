@@ -10,21 +10,57 @@
 //! 4. 处理用户交互，如密码输入。
 //! 5. 向用户报告操作结果。
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::{Parser, Subcommand};
+#[cfg(feature = "keyring")]
+use ferox_encryptor::password::{forget_password, OsKeychain, PasswordSource, PasswordStore};
 use ferox_encryptor::{
+    audit::{verify_audit_log, AuditLog, AuditOperation, AuditRecord},
     batch::{
-        batch_decrypt_directory, batch_decrypt_files, batch_encrypt_directory, batch_encrypt_files,
-        BatchConfig,
+        batch_compare_files, batch_decrypt_directory, batch_decrypt_paths, batch_encrypt_directory,
+        batch_encrypt_paths, batch_verify_files, cleanup_orphaned_sidecars, expand_glob_arguments,
+        plan_batch_encrypt, BatchConfig, BatchPlan, PlannedAction,
     },
-    interactive::run_interactive_mode,
-    keyfile::{validate_keyfile, KeyFile},
-    Level,
+    cleanup::{install_interrupt_handler, remove_stale_partials, scan_stale_partials},
+    constants::{
+        validate_constants, CURRENT_FORMAT_VERSION, KEYSHARE_FILE_EXTENSION, LEGACY_FORMAT_VERSION,
+        MANIFEST_FILE_NAME, MAX_BUFFER_SIZE, MAX_KEYFILE_SIZE, MIN_BUFFER_SIZE,
+        ON_COMPLETE_TIMEOUT_SECS, STALL_WARNING_SECS,
+    },
+    credentials::Credentials,
+    decrypt::{
+        decrypt_base64_to_file, decrypt_unverified, DecryptionOptions, NameNormalization,
+        OutputPermissions, OverwriteMode,
+    },
+    encrypt::{encrypt_file_to_base64, EncryptionOptions},
+    format_utc_time,
+    hook::CompletionHook,
+    index::{batch_encrypt_directory_with_index, search_index},
+    inspect::{
+        check_compatibility, compare_file, estimate_decryption_time, inspect_file, scan_encrypted,
+        scan_format_versions, Compatibility, CompatibilityEntry, EncryptedFileInfo,
+    },
+    interactive::run_interactive_session,
+    keyfile::{
+        generate_keyfile_from_hardware_entropy, save_batch, validate_keyfile,
+        verify_keyfile_matches_encrypted_file, KeyFile, KeyShare, KeyfileInputFormat,
+    },
+    location::check_encryption_location,
+    manifest::{verify_restore, RestoreManifest},
+    migration::{migration_report, MigrationReport},
+    progress::terminal_progress,
+    resume::encrypt_resumable,
+    CipherSuite, FeroxError, Level,
 };
 use glob::Pattern;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use zeroize::Zeroize;
+#[cfg(feature = "keyring")]
+use zeroize::Zeroizing;
 
 /// # Ferox Encryptor CLI
 ///
@@ -55,6 +91,162 @@ struct Cli {
     /// 定义所有可用的子命令 (Define all available subcommands)
     #[command(subcommand)]
     command: Commands,
+
+    /// 由程序展开路径参数中的通配符 (例如 `*.docx`)。在 Windows 上始终启用。
+    #[arg(long, global = true)]
+    expand_globs: bool,
+
+    /// 在单个线程中串行执行加密和认证，不使用流水线 (用于排查问题或限制 CPU 占用)。
+    #[arg(long, global = true)]
+    single_thread: bool,
+
+    /// 并行加密/解密单个文件的线程数 (适用于大文件，默认为 1)。
+    #[arg(long, global = true, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    threads: u16,
+
+    /// 读写缓冲区大小，例如 `256KB`、`8MB` (默认 4MB，范围 4KB - 256MB)。
+    #[arg(long, global = true, default_value = "4MB", value_parser = parse_buffer_size)]
+    buffer_size: usize,
+
+    /// 加密时拒绝超过该大小的源文件，例如 `10GB` (在派生密钥之前检查；批量加密时跳过这些文件)。
+    #[arg(long, global = true, value_name = "SIZE", value_parser = parse_max_file_size)]
+    max_size_hard_limit: Option<u64>,
+
+    /// 加密时通过内存映射读取源文件，减少大文件的系统调用和复制 (源文件在加密期间不能被截断)。
+    #[arg(long, global = true)]
+    mmap: bool,
+
+    /// 使用多个线程并行扫描目录 (适用于包含大量文件的目录树，结果与串行扫描相同)。
+    #[arg(long, global = true)]
+    parallel_scan: bool,
+
+    /// 不显示进度条 (标准错误不是终端时自动禁用，也可以设置 `FEROX_NO_PROGRESS=1`)。
+    #[arg(long, global = true)]
+    no_progress: bool,
+
+    /// 不显示任何提示，从标准输入读取两行 (密码和确认密码)，两者一致才继续。
+    /// 标准输入是终端时输入不回显。
+    #[arg(long, global = true)]
+    stdin_password_confirm: bool,
+
+    /// 配合 `--stdin-password-confirm` 使用：标准输入没有任何内容时，改为读取该环境变量中的密码
+    /// (用于 CI 等无法交互的环境)。
+    #[arg(
+        long,
+        global = true,
+        value_name = "VAR",
+        requires = "stdin_password_confirm"
+    )]
+    stdin_password_env_override: Option<String>,
+
+    /// `--keyfile -` 从标准输入读取密钥材料时使用的格式：原始字节 (binary) 或十六进制文本 (hex)。
+    #[arg(long, global = true, value_enum, default_value_t = KeyfileInputFormat::Binary)]
+    keyfile_stdin_format: KeyfileInputFormat,
+
+    /// 把密码材料和主密钥锁定在物理内存中 (Windows 上为 VirtualLock)，防止长时间运行时被换出到磁盘。
+    /// 权限或 RLIMIT_MEMLOCK 不足时只发出警告，操作照常进行。
+    #[arg(long, global = true)]
+    lock_memory: bool,
+
+    /// 严格模式：把警告当作错误，适用于无人值守的流水线。扫描时无法读取的目录、残留的 `.part` 文件、
+    /// 特殊文件、超过大小上限或被占用而跳过的文件、无法写入的说明文件、无法读取或恢复的扩展属性和权限、
+    /// 无法锁定的内存都会使操作失败；批量操作有任何失败时以非零状态退出。过滤模式排除的文件和
+    /// `--overwrite skip` 跳过的文件不受影响。
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// 批量操作结束时列出所有失败和跳过的文件 (默认超过 20 个时只显示按扩展名和目录分组的统计)。
+    #[arg(long, global = true)]
+    verbose: bool,
+
+    /// 加密时把源文件的扩展属性 (Linux 上为 user.*，macOS 上为全部属性) 记录在受认证保护的文件头中，
+    /// 解密时恢复到输出文件上；无法设置的属性只发出警告。不支持 Windows 的 NTFS 备用数据流。
+    #[arg(long, global = true)]
+    preserve_xattrs: bool,
+
+    /// 无法在目标文件旁边创建临时输出文件 (`.part`) 时 (例如目录只读或超出配额) 改用的临时目录。
+    /// 临时目录在另一个文件系统上时，完成后复制到目标位置；无法保证原子性时会发出警告。
+    #[arg(long, global = true, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// 数据流处理期间没有任何读写进展超过该秒数时发出停滞警告 (例如网络存储失去响应)。
+    /// 派生密钥的阶段不计时。
+    #[arg(long, global = true, value_name = "SECS", default_value_t = STALL_WARNING_SECS, value_parser = clap::value_parser!(u64).range(1..))]
+    stall_warning: u64,
+
+    /// 数据流处理期间没有任何读写进展超过该秒数时放弃当前文件 (报告为停滞错误)，
+    /// 批量操作继续处理下一个文件。默认只发出警告。
+    #[arg(long, global = true, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    stall_timeout: Option<u64>,
+
+    /// 把每一次加密/解密操作 (包括失败的操作) 追加到该审计日志 (JSON Lines)。
+    /// 记录以 HMAC 链接，可用 `audit verify` 检查是否被篡改；不会记录密码或密钥材料。
+    #[arg(long, global = true, value_name = "PATH", requires = "audit_keyfile")]
+    audit_log: Option<PathBuf>,
+
+    /// 用于派生审计日志 HMAC 密钥的专用密钥文件 (应与加密用的密钥文件不同)。
+    #[arg(long, global = true, value_name = "KEYFILE")]
+    audit_keyfile: Option<PathBuf>,
+
+    /// 批量操作 (加密或解密多个文件、batch-encrypt、batch-decrypt、verify、compare) 结束后通过 shell
+    /// 运行的命令。标准输入为 JSON 结果摘要，`FEROX_OPERATION`、`FEROX_SUCCESS_COUNT`、`FEROX_FAILURE_COUNT`、
+    /// `FEROX_SKIPPED_COUNT`、`FEROX_TOTAL_BYTES` 和 `FEROX_DURATION_SECS` 环境变量提供基本字段。
+    /// 钩子失败或超时只发出警告，不改变退出状态。
+    #[arg(long, global = true, value_name = "COMMAND")]
+    on_complete: Option<String>,
+
+    /// 从 TOML 配置文件读取完成通知钩子 (`command` 和可选的 `timeout_secs`)，效果与 `--on-complete` 相同。
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        conflicts_with = "on_complete"
+    )]
+    on_complete_config: Option<PathBuf>,
+
+    /// 等待 `--on-complete` 钩子结束的最长秒数，超时后终止钩子。
+    /// 使用 `--on-complete-config` 时由配置文件中的 `timeout_secs` 决定。
+    #[arg(long, global = true, value_name = "SECS", default_value_t = ON_COMPLETE_TIMEOUT_SECS, value_parser = clap::value_parser!(u64).range(1..))]
+    on_complete_timeout: u64,
+
+    /// 操作成功后，以该名称把输入的密码保存到操作系统密钥链。
+    #[cfg(feature = "keyring")]
+    #[arg(long, global = true, value_name = "NAME")]
+    save_password: Option<String>,
+
+    /// 不提示输入密码，而是使用以该名称保存在操作系统密钥链中的密码。
+    #[cfg(feature = "keyring")]
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        conflicts_with_all = ["save_password", "stdin_password_confirm"]
+    )]
+    use_saved: Option<String>,
+}
+
+/// # 加密数据的编码格式
+///
+/// 决定加密结果以二进制 `.feroxcrypt` 文件还是 Base64 文本的形式存在。
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum DataFormat {
+    /// 二进制 `.feroxcrypt` 文件 (默认)。
+    Binary,
+    /// URL 安全、无填充的 Base64 文本，便于嵌入 JSON/YAML (体积约增加 33%)。
+    Base64,
+}
+
+/// # 批量结果的报告格式
+///
+/// 决定批量命令结束后如何报告每个文件的处理结果。
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum ReportFormat {
+    /// 只在日志中打印统计信息 (默认)。
+    Text,
+    /// 额外把每个文件的结果以 CSV (`path,status,bytes,error_message`) 写到标准输出，便于管道处理。
+    Csv,
+    /// 额外把统计信息和每个文件的结果 (包括失败的阶段) 以 JSON 写到标准输出。
+    Json,
 }
 
 /// # 子命令枚举
@@ -62,12 +254,16 @@ struct Cli {
 /// 定义了所有用户可以执行的操作。
 #[derive(Subcommand)]
 enum Commands {
-    /// 加密单个文件或多个指定文件。
+    /// 加密单个文件或多个指定文件。也可以传入目录，等同于对该目录执行批量加密。
     Encrypt {
-        /// 要加密的一个或多个文件的路径。
+        /// 要加密的一个或多个文件或目录的路径。
         #[arg(required = true, num_args = 1..)]
         paths: Vec<PathBuf>,
 
+        /// 对传入的目录递归处理所有子目录。
+        #[arg(short, long)]
+        recursive: bool,
+
         /// 如果目标文件已存在，则强制覆盖。
         #[arg(short, long)]
         force: bool,
@@ -79,16 +275,88 @@ enum Commands {
         /// (可选) 提供一个密钥文件以增强安全性。
         #[arg(short, long)]
         keyfile: Option<PathBuf>,
+
+        /// 输出格式。`base64` 会把每个文件的加密结果以 Base64 文本打印到标准输出，
+        /// 而不是生成 `.feroxcrypt` 文件。
+        #[arg(long, value_enum, default_value_t = DataFormat::Binary)]
+        output_format: DataFormat,
+
+        /// (可选) 文件在多久之后过期，过期后拒绝解密 (例如: `30d`、`24h`、`1y`)。
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        expire_after: Option<Duration>,
+
+        /// 完成后在每个处理过的目录中写入说明文件 (`FEROX-ENCRYPTED.txt` 和 `.json`)，记录工具和格式版本、
+        /// 算法以及解密方法，方便把加密后的文件夹交给别人。不包含任何密码或密钥，重复运行时整体重新生成。
+        #[arg(long)]
+        write_readme: bool,
+
+        /// 先跳过正被其他程序占用 (Windows 上的共享冲突，例如 Outlook、Excel 打开的文件) 而无法读取的文件，
+        /// 其余文件处理完之后再各重试一次。
+        #[arg(long)]
+        retry_locked: bool,
+
+        /// 可续传地加密单个大文件: 定期把进度记录到目标文件旁边的 `.feroxstate` 检查点文件中，
+        /// 中断后用相同的参数再次运行即从检查点继续，而不是从头开始。源文件在两次运行之间不能被修改。
+        #[arg(long, conflicts_with_all = ["recursive", "write_readme", "retry_locked"])]
+        resume: bool,
+
+        /// 与 `--resume` 一起使用: 把加密速度限制在每秒给定的字节数以内 (例如 `20MB`)，
+        /// 避免长时间占满慢速磁盘或 USB 总线的带宽。
+        #[arg(long, value_name = "BYTES/S", value_parser = parse_rate_limit, requires = "resume")]
+        rate_limit: Option<u64>,
+
+        /// 在文件头中记录密钥文件承诺，之后可以用 `verify --check-keyfile` 快速确认密钥文件是否匹配。
+        /// 注意: 承诺让离线猜测密码只需要固定的密钥文件派生，而不是 `--level` 选择的参数。
+        #[arg(long, requires = "keyfile")]
+        keyfile_commitment: bool,
     },
-    /// 解密单个或多个文件。
+    /// 解密单个或多个文件。也可以传入目录，等同于对该目录执行批量解密。
     Decrypt {
-        /// 要解密的一个或多个 `.feroxcrypt` 文件的路径。
+        /// 要解密的一个或多个 `.feroxcrypt` 文件或目录的路径。
         #[arg(required = true, num_args = 1..)]
         paths: Vec<PathBuf>,
 
+        /// 对传入的目录递归处理所有子目录。
+        #[arg(short, long)]
+        recursive: bool,
+
         /// (可选) 提供加密时使用的密钥文件。
         #[arg(short, long)]
         keyfile: Option<PathBuf>,
+
+        /// 输入格式。`base64` 表示每个路径都是包含 Base64 加密数据的文本文件，
+        /// 解密结果写入该文本文件所在的目录。
+        #[arg(long, value_enum, default_value_t = DataFormat::Binary)]
+        input_format: DataFormat,
+
+        /// 恢复模式 (危险): 认证标签损坏时仍然解密所有能读取的密文，写入 `--output` 加上
+        /// `.UNVERIFIED` 后缀的文件。输出的完整性没有任何保证，只用于从损坏的介质上抢救数据；
+        /// 一次只能处理一个文件。
+        #[arg(long, requires = "output")]
+        ignore_auth_failure: bool,
+
+        /// 恢复模式的输出路径 (实际写入的文件名会加上 `.UNVERIFIED` 后缀)。
+        #[arg(long, requires = "ignore_auth_failure")]
+        output: Option<PathBuf>,
+
+        /// 解密出的文件的权限，在认证通过、原子重命名之后设置 (解密期间的临时文件始终为 0600):
+        /// 八进制权限位 (例如 `600`、`0640`)，`preserve` 使用加密时用 `--preserve-xattrs` 记录的权限
+        /// (没有记录时与 `umask` 相同)，`umask` 按当前 umask 设置 (默认)。只适用于 Unix 上的二进制加密文件。
+        #[arg(long, value_name = "MODE|preserve|umask", default_value = "umask", value_parser = parse_chmod)]
+        chmod: OutputPermissions,
+
+        /// 恢复文件名时使用的 Unicode 规范化形式: `none` 与加密时的字节完全相同 (默认)，
+        /// `nfc` 组合形式 (Linux/Windows 上常见)，`nfd` 分解形式 (macOS 上常见)。
+        #[arg(long, value_enum, default_value_t = NameNormalization::None)]
+        normalize_names: NameNormalization,
+
+        /// 不解密，只在当前机器上校准后估计解密每个文件大约需要多长时间 (不需要密码)。
+        #[arg(long)]
+        estimate_time: bool,
+
+        /// 估计时假设文件使用该安全级别，而不是文件头中的参数。
+        #[arg(long, value_enum, requires = "estimate_time")]
+        estimate_level: Option<Level>,
     },
     /// 批量加密一个目录中的所有文件。
     BatchEncrypt {
@@ -121,6 +389,67 @@ enum Commands {
         /// (可选) 提供一个密钥文件以增强安全性。
         #[arg(short, long)]
         keyfile: Option<PathBuf>,
+
+        /// (可选) 文件在多久之后过期，过期后拒绝解密 (例如: `30d`、`24h`、`1y`)。
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        expire_after: Option<Duration>,
+
+        /// 同时在目录中生成加密的文件名索引 (`vault.feroxidx`)，之后可用 `search` 按文件名查找。
+        #[arg(long)]
+        build_index: bool,
+
+        /// 同时在目录中生成恢复校验清单 (`ferox-manifest.json`)，记录每个文件明文的 SHA-256 和大小，
+        /// 恢复后可用 `verify-restore` 检查。清单不加密。
+        #[arg(long)]
+        manifest: bool,
+
+        /// 跳过正被其他进程打开写入的文件 (Unix 上检查 `flock` 锁，Windows 上检查共享冲突)，
+        /// 避免加密写入到一半的文件。
+        #[arg(long)]
+        skip_open_files: bool,
+
+        /// 完成后在每个处理过的目录中写入说明文件 (`FEROX-ENCRYPTED.txt` 和 `.json`)，记录工具和格式版本、
+        /// 算法以及解密方法，方便把加密后的文件夹交给别人。不包含任何密码或密钥，重复运行时整体重新生成。
+        #[arg(long)]
+        write_readme: bool,
+
+        /// 先跳过正被其他程序占用 (Windows 上的共享冲突，例如 Outlook、Excel 打开的文件) 而无法读取的文件，
+        /// 其余文件处理完之后再各重试一次。
+        #[arg(long)]
+        retry_locked: bool,
+
+        /// 同一份内容的多个硬链接只加密一次，其余路径跳过并报告节省的空间；
+        /// 与 `--manifest` 一起使用时链接关系记录在清单中，`batch-decrypt --preserve-hardlinks` 可以重建。
+        #[arg(long)]
+        dedupe_hardlinks: bool,
+
+        /// 目标位置已有同一源文件的加密结果 (文件头中的文件名、大小和源文件修改时间都一致) 时跳过该文件，
+        /// 不重新加密；不一致时照常按 `--force` 覆盖或失败。加密时同时记录源文件的修改时间，
+        /// 因此第一次运行也应使用该参数，之后重复运行同一批量加密只处理新增或修改过的文件。
+        #[arg(long)]
+        skip_if_encrypted_matches: bool,
+
+        /// 在文件头中记录密钥文件承诺，之后可以用 `verify --check-keyfile` 快速确认密钥文件是否匹配。
+        /// 注意: 承诺让离线猜测密码只需要固定的密钥文件派生，而不是 `--level` 选择的参数。
+        #[arg(long, requires = "keyfile")]
+        keyfile_commitment: bool,
+
+        /// 结果报告格式。`csv` 和 `json` 把每个文件的结果写到标准输出，日志仍写到标准错误。
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        output_format: ReportFormat,
+
+        /// 目录位于云同步目录 (Dropbox、OneDrive、Google Drive、iCloud 或 `FEROX_SYNC_ROOTS`
+        /// 中列出的目录) 或已加密的文件系统上时，不经确认直接继续。
+        #[arg(short, long)]
+        yes: bool,
+
+        /// 不检查目录是否位于云同步目录或已加密的文件系统上。
+        #[arg(long)]
+        no_location_check: bool,
+
+        /// 只显示加密计划 (将要加密和跳过的文件、目标路径、大小和预检警告)，不需要密码，也不修改任何文件。
+        #[arg(long)]
+        dry_run: bool,
     },
     /// 批量解密一个目录中的所有加密文件。
     BatchDecrypt {
@@ -135,88 +464,640 @@ enum Commands {
         /// (可选) 提供加密时使用的密钥文件。
         #[arg(short, long)]
         keyfile: Option<PathBuf>,
+
+        /// 解密后的目标文件已存在时的处理方式: `fail` 中止该文件 (默认)，
+        /// `skip` 跳过该文件，`overwrite` 在认证通过后原子地替换已存在的文件，
+        /// `rename` 保留已存在的文件，改写入 `名称 (1).扩展名` 等新名称。
+        #[arg(long, value_enum, default_value_t = OverwriteMode::Fail)]
+        overwrite: OverwriteMode,
+
+        /// (可选) 把解密结果写入该目录 (按需创建)，其中重建加密文件的子目录结构。
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+
+        /// 不保留子目录结构，所有结果只按原始文件名直接写入 `--output-dir`；
+        /// 同名文件按 `--overwrite` 处理 (通常配合 `--overwrite rename`)。
+        #[arg(long, requires = "output_dir")]
+        flatten: bool,
+
+        /// 解密出的文件的权限，对每个文件统一适用 (见 `decrypt --chmod`):
+        /// 八进制权限位、`preserve` 或 `umask` (默认)。
+        #[arg(long, value_name = "MODE|preserve|umask", default_value = "umask", value_parser = parse_chmod)]
+        chmod: OutputPermissions,
+
+        /// 恢复文件名时使用的 Unicode 规范化形式: `none` 与加密时的字节完全相同 (默认)，
+        /// `nfc` 组合形式 (Linux/Windows 上常见)，`nfd` 分解形式 (macOS 上常见)。
+        #[arg(long, value_enum, default_value_t = NameNormalization::None)]
+        normalize_names: NameNormalization,
+
+        /// 结果报告格式。`csv` 和 `json` 把每个文件的结果写到标准输出，日志仍写到标准错误。
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        output_format: ReportFormat,
+
+        /// 即使第一个文件显示密码或密钥文件不正确，也继续尝试其余文件。
+        /// 默认在尚无文件成功解密时遇到认证失败即中止，避免对每个文件重复昂贵的密钥派生。
+        #[arg(long)]
+        keep_going: bool,
+
+        /// (可选) 凭据表 (TOML，或扩展名为 `.json` 的 JSON)，按文件模式为各文件指定密码和密钥文件，
+        /// 或者列出对每个文件依次尝试的密码。使用凭据表时不再提示输入密码，`--keyfile` 作为最后尝试的凭据。
+        #[arg(long, value_name = "FILE")]
+        credentials_file: Option<PathBuf>,
+
+        /// 按目录中恢复校验清单 (`batch-encrypt --manifest --dedupe-hardlinks` 生成) 记录的链接关系重建硬链接。
+        #[arg(long, conflicts_with = "flatten")]
+        preserve_hardlinks: bool,
+    },
+    /// 验证一个或多个加密文件的完整性，不生成明文文件。
+    Verify {
+        /// 要验证的一个或多个 `.feroxcrypt` 文件的路径。
+        #[arg(required = true, num_args = 1..)]
+        paths: Vec<PathBuf>,
+
+        /// (可选) 提供加密时使用的密钥文件。
+        #[arg(short, long)]
+        keyfile: Option<PathBuf>,
+
+        /// 只检查给定的密钥文件（和密码）是否与文件匹配，不验证整个文件。
+        #[arg(long, value_name = "KEYFILE", conflicts_with = "keyfile")]
+        check_keyfile: Option<PathBuf>,
+    },
+    /// 检查加密文件是否确实能解密出给定的明文文件（不把明文写入磁盘）。
+    ///
+    /// 默认接收 `<明文文件> <加密文件>` 两个路径；使用 `--auto-pair` 时所有路径都是加密文件，
+    /// 按文件头中的原始文件名与同一目录下的明文文件配对。
+    Compare {
+        /// `<明文文件> <加密文件>`，或使用 `--auto-pair` 时的一个或多个加密文件。
+        #[arg(required = true, num_args = 1..)]
+        paths: Vec<PathBuf>,
+
+        /// 按文件头中的原始文件名自动寻找同一目录下的明文文件。
+        #[arg(long)]
+        auto_pair: bool,
+
+        /// (可选) 提供加密时使用的密钥文件。
+        #[arg(short, long)]
+        keyfile: Option<PathBuf>,
+    },
+    /// 在加密索引中按原始文件名查找加密文件（完整文件名精确匹配）。
+    Search {
+        /// 由 `batch-encrypt --build-index` 生成的索引文件 (`vault.feroxidx`)。
+        #[arg(required = true)]
+        index: PathBuf,
+
+        /// 要查找的原始文件名 (例如: "report.pdf")。
+        #[arg(required = true)]
+        term: String,
+
+        /// (可选) 提供加密时使用的密钥文件。
+        #[arg(short, long)]
+        keyfile: Option<PathBuf>,
+    },
+    /// 用 `batch-encrypt --manifest` 生成的清单检查恢复目录，报告缺失、多余和内容不一致的文件。
+    VerifyRestore {
+        /// 恢复校验清单 (`ferox-manifest.json`)。
+        #[arg(long, required = true)]
+        manifest: PathBuf,
+
+        /// 解密恢复后的目录。
+        #[arg(required = true)]
+        restored_dir: PathBuf,
+    },
+    /// 显示加密文件的文件头信息（无需密码）。也可以传入目录，列出其中的所有加密文件。
+    Info {
+        /// 要查看的一个或多个 `.feroxcrypt` 文件或目录的路径。
+        #[arg(required = true, num_args = 1..)]
+        paths: Vec<PathBuf>,
+
+        /// 传入目录时递归列出所有子目录中的加密文件。
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// 以 JSON 数组输出每个文件的路径、大小、格式版本、Argon2 参数、安全级别、是否使用密钥文件和原始文件名；
+        /// 文件头损坏的文件带有 `error` 字段，不会中止命令。
+        #[arg(long)]
+        json: bool,
+    },
+    /// 统计目录中加密文件的格式版本，检查是否存在遗留格式或不支持的文件。
+    VersionCheck {
+        /// 要扫描的目录。
+        #[arg(required = true)]
+        directory: PathBuf,
+
+        /// 递归扫描所有子目录。
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// 递归检查目录中的加密文件能否被当前版本解密，按格式版本和算法分组报告。
+    CheckCompat {
+        /// 要检查的目录。
+        #[arg(required = true)]
+        directory: PathBuf,
+
+        /// 以 JSON 格式输出报告。
+        #[arg(long)]
+        json: bool,
+    },
+    /// 迁移前的只读检查：验证遗留格式文件仍能用密码通过认证，列出建议重新加密的文件并估计迁移耗时。
+    MigrationReport {
+        /// 要递归检查的目录。
+        #[arg(required = true)]
+        directory: PathBuf,
+
+        /// 迁移的目标安全级别，Argon2 成本低于它的文件建议重新加密。
+        #[arg(long, value_enum, default_value_t = Level::Moderate)]
+        level: Level,
+
+        /// (可选) 密钥文件：用于验证使用它加密的遗留格式文件，同时表示迁移目标使用密钥文件，
+        /// 没有使用密钥文件的文件建议重新加密。
+        #[arg(short, long)]
+        keyfile: Option<PathBuf>,
+
+        /// 以 JSON 格式输出报告。
+        #[arg(long)]
+        json: bool,
+
+        /// 把建议重新加密的文件保存为 JSON 迁移计划。
+        #[arg(long, value_name = "FILE")]
+        save_plan: Option<PathBuf>,
+    },
+    /// 扫描并清理被强制终止的操作残留的不完整输出文件 (`.part`)。
+    Cleanup {
+        /// 要扫描的目录。
+        #[arg(required = true)]
+        directory: PathBuf,
+
+        /// 递归扫描所有子目录。
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// 不经确认直接删除找到的文件。
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// 查找并删除对应的加密文件已不存在的孤立旁路元数据文件 (`*.feroxcrypt.meta.json`)。
+    CleanupSidecars {
+        /// 要扫描的目录。
+        #[arg(required = true)]
+        directory: PathBuf,
+
+        /// 递归扫描所有子目录。
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// 只列出找到的文件，不删除。
+        #[arg(long)]
+        dry_run: bool,
     },
     /// 生成一个新的、安全的密钥文件。
     GenerateKey {
         /// 新密钥文件的保存路径。
         #[arg(required = true)]
         output: PathBuf,
+
+        /// (可选) 从硬件随机数设备读取熵来生成密钥文件 (例如: /dev/hwrng)。
+        #[arg(long, value_name = "PATH", conflicts_with = "combine_keyfile_shares")]
+        keyfile_from_device: Option<PathBuf>,
+
+        /// 同时把新密钥文件拆分为 Shamir 分片 (`<output>.<序号>.feroxshare`)，
+        /// 任意 `--threshold` 个分片即可恢复密钥文件。
+        #[arg(long, conflicts_with = "combine_keyfile_shares")]
+        split_keyfile: bool,
+
+        /// 拆分的分片数量。
+        #[arg(long, default_value_t = 5, requires = "split_keyfile")]
+        shares: u8,
+
+        /// 恢复密钥文件所需的最少分片数量。
+        #[arg(long, default_value_t = 3, requires = "split_keyfile")]
+        threshold: u8,
+
+        /// 不生成新密钥，而是由给定的分片文件恢复密钥文件并保存到输出路径。
+        #[arg(long, value_name = "SHARE", num_args = 1..)]
+        combine_keyfile_shares: Vec<PathBuf>,
+    },
+    /// 一次生成多个相互独立的密钥文件 (`<前缀>_01.key`、`<前缀>_02.key` ……)，用于向团队成员分发。
+    GenerateKeys {
+        /// 要生成的密钥文件数量。
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+
+        /// 密钥文件名的前缀。
+        #[arg(long, default_value = "key")]
+        prefix: String,
+
+        /// 保存密钥文件的目录，不存在时会被创建。
+        #[arg(long, value_name = "DIR")]
+        output_dir: PathBuf,
+    },
+    /// 审计日志相关操作。
+    Audit {
+        #[command(subcommand)]
+        action: AuditCommands,
+    },
+    /// 管理保存在操作系统密钥链中的密码。
+    #[cfg(feature = "keyring")]
+    Password {
+        #[command(subcommand)]
+        action: PasswordCommands,
     },
     /// 启动交互式用户界面模式。
-    Interactive,
+    Interactive {
+        /// 开启“记住本次会话密码”时，记住的密码闲置多久后被清除，例如 `10m`、`1h`
+        /// (单位: s、m、h、d、w、y)。
+        #[arg(long, value_name = "DURATION", default_value = "5m", value_parser = parse_duration)]
+        password_timeout: Duration,
+    },
+}
+
+/// # 审计日志子命令
+#[derive(Subcommand)]
+enum AuditCommands {
+    /// 检查审计日志的 HMAC 链条，指出被修改、插入或删除记录的位置。
+    Verify {
+        /// 审计日志的路径。
+        #[arg(required = true)]
+        log: PathBuf,
+
+        /// 写入日志时使用的审计密钥文件。
+        #[arg(short, long, required = true)]
+        keyfile: PathBuf,
+    },
+}
+
+/// # 密码子命令
+#[cfg(feature = "keyring")]
+#[derive(Subcommand)]
+enum PasswordCommands {
+    /// 从操作系统密钥链中删除以 `--save-password` 保存的密码。
+    Forget {
+        /// 保存密码时使用的名称。
+        #[arg(required = true)]
+        name: String,
+    },
 }
 
+/// 不使用 `--verbose` 时，批量结果中逐个列出失败或跳过文件的上限。
+const BATCH_DETAIL_LIMIT: usize = 20;
+
+/// `--save-password` 指定的名称和输入的密码，在操作成功后才保存到密钥链。
+#[cfg(feature = "keyring")]
+static PASSWORD_TO_SAVE: Mutex<Option<(String, Zeroizing<String>)>> = Mutex::new(None);
+
 /// 主函数入口。
 fn main() -> Result<()> {
+    run()?;
+
+    #[cfg(feature = "keyring")]
+    if let Some((name, password)) = PASSWORD_TO_SAVE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+    {
+        OsKeychain.set(&name, &password)?;
+        log::info!("🔑 密码已保存到密钥链: {name}");
+    }
+
+    Ok(())
+}
+
+/// 执行命令行指定的操作。
+fn run() -> Result<()> {
     // 初始化日志记录器，默认日志级别为 "info"
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    validate_constants().context("内部常量不一致，程序已损坏")?;
 
     // 创建一个线程安全的共享变量，用于在程序中断时传递临时文件名。
     // `Arc` 用于多线程所有权，`Mutex` 用于安全地修改数据。
     let temp_file_path = Arc::new(Mutex::new(None::<PathBuf>));
-    let handler_path_ref = Arc::clone(&temp_file_path);
-
-    // 设置 Ctrl+C 信号处理器。
-    // 当用户按下 Ctrl+C 时，这个闭包会被执行。
-    ctrlc::set_handler(move || {
-        log::info!("\n接收到 Ctrl+C 信号，正在准备退出...");
-        // 检查共享变量中是否有临时文件名
-        if let Some(path) = handler_path_ref.lock().unwrap().as_ref() {
-            if path.exists() {
-                log::warn!(
-                    "检测到操作被中断，正在清理不完整的输出文件: {}",
-                    path.display()
-                );
-                // 尝试删除不完整的文件
-                if let Err(e) = std::fs::remove_file(path) {
-                    log::error!("清理文件 {} 失败: {}", path.display(), e);
-                } else {
-                    log::info!("清理完成。");
-                }
-            }
-        }
-        // 以标准的中断退出码 (130) 退出程序
-        std::process::exit(130);
-    })
-    .context("设置 Ctrl-C 处理器时出错")?;
+
+    // 设置终止信号处理器 (Ctrl+C、SIGTERM、SIGHUP、Windows 控制台关闭事件)。
+    // 收到信号时，处理器会清理登记的不完整输出文件，并以惯用的退出码 (130、143、129) 退出。
+    install_interrupt_handler(Arc::clone(&temp_file_path))?;
 
     // 解析命令行参数
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    // 完成通知钩子报告的耗时从这里开始计算 (包括输入密码的时间)
+    let started = Instant::now();
+
+    // 只检测一次终端；不是终端或使用了 --no-progress 时，各流程不创建任何进度对象
+    let progress_sink = if cli.no_progress {
+        None
+    } else {
+        terminal_progress()
+    };
+
+    // Windows 的 shell 不会展开通配符，由程序自行展开
+    if cfg!(windows) || cli.expand_globs {
+        if let Commands::Encrypt { paths, .. }
+        | Commands::Decrypt { paths, .. }
+        | Commands::Verify { paths, .. }
+        | Commands::Compare { paths, .. }
+        | Commands::Info { paths, .. } = &mut cli.command
+        {
+            *paths = expand_glob_arguments(paths)?;
+        }
+    }
+
+    let audit_log = open_audit_log(&cli)?;
+    // 在批量操作开始之前加载钩子，配置有误时立即报错
+    let completion_hook = completion_hook(&cli)?;
 
     // 使用 match 语句处理不同的子命令
     match &cli.command {
         // --- 加密命令 ---
         Commands::Encrypt {
             paths,
+            recursive,
             force,
             level,
             keyfile,
+            output_format,
+            expire_after,
+            write_readme,
+            retry_locked,
+            resume,
+            rate_limit,
+            keyfile_commitment,
         } => {
-            let mut password = rpassword::prompt_password("请输入密码 (输入时不可见): ")
-                .context("无法读取密码")?;
+            let mut password = read_password(&cli)?;
 
-            let loaded_keyfile = load_keyfile_if_provided(keyfile)?;
+            let loaded_keyfile = load_keyfile_if_provided(keyfile, &cli)?;
 
-            let config = BatchConfig {
-                level: *level,
-                force_overwrite: *force,
-                ..Default::default()
-            };
+            if *resume {
+                if *output_format == DataFormat::Base64 {
+                    bail!("可续传加密 (--resume) 不支持 Base64 输出");
+                }
+                let opts = EncryptionOptions {
+                    keyfile: loaded_keyfile.as_ref(),
+                    level: *level,
+                    force_overwrite: *force,
+                    buffer_size: cli.buffer_size,
+                    lock_memory: cli.lock_memory,
+                    max_file_size: cli.max_size_hard_limit,
+                    expires_at: expire_after.map(|duration| SystemTime::now() + duration),
+                    preserve_xattrs: cli.preserve_xattrs,
+                    strict: cli.strict,
+                    keyfile_commitment: *keyfile_commitment,
+                    rate_limit: *rate_limit,
+                    ..EncryptionOptions::new(&password)
+                };
+                for path in paths {
+                    if path.is_dir() {
+                        bail!("可续传加密 (--resume) 只能用于单个文件: {}", path.display());
+                    }
+                    let result = encrypt_resumable(path, &opts);
+                    if let Some(audit_log) = &audit_log {
+                        audit_log.append(&match &result {
+                            Ok(()) => AuditRecord::success(
+                                AuditOperation::Encrypt,
+                                path,
+                                None,
+                                Some(*level),
+                            ),
+                            Err(e) => AuditRecord::failure(
+                                AuditOperation::Encrypt,
+                                path,
+                                None,
+                                Some(*level),
+                                e,
+                            ),
+                        })?;
+                    }
+                    result?;
+                }
+            } else if *output_format == DataFormat::Base64 {
+                let opts = EncryptionOptions {
+                    keyfile: loaded_keyfile.as_ref(),
+                    level: *level,
+                    single_thread: cli.single_thread,
+                    threads: usize::from(cli.threads),
+                    buffer_size: cli.buffer_size,
+                    lock_memory: cli.lock_memory,
+                    temp_dir: cli.temp_dir.as_deref(),
+                    max_file_size: cli.max_size_hard_limit,
+                    expires_at: expire_after.map(|duration| SystemTime::now() + duration),
+                    strict: cli.strict,
+                    keyfile_commitment: *keyfile_commitment,
+                    ..EncryptionOptions::new(&password)
+                };
+                for path in paths {
+                    if path.is_dir() {
+                        bail!("Base64 输出不支持目录: {}", path.display());
+                    }
+                    let encoded = encrypt_file_to_base64(path, &opts);
+                    if let Some(audit_log) = &audit_log {
+                        audit_log.append(&match &encoded {
+                            Ok(_) => AuditRecord::success(
+                                AuditOperation::Encrypt,
+                                path,
+                                None,
+                                Some(*level),
+                            ),
+                            Err(e) => AuditRecord::failure(
+                                AuditOperation::Encrypt,
+                                path,
+                                None,
+                                Some(*level),
+                                e,
+                            ),
+                        })?;
+                    }
+                    let encoded =
+                        encoded.with_context(|| format!("加密失败: {}", path.display()))?;
+                    println!("{encoded}");
+                }
+            } else {
+                let config = BatchConfig {
+                    level: *level,
+                    force_overwrite: *force,
+                    recursive: *recursive,
+                    temp_file_path: Arc::clone(&temp_file_path),
+                    progress_sink: progress_sink.clone(),
+                    parallel_scan: cli.parallel_scan,
+                    single_thread: cli.single_thread,
+                    threads: usize::from(cli.threads),
+                    buffer_size: cli.buffer_size,
+                    lock_memory: cli.lock_memory,
+                    temp_dir: cli.temp_dir.clone(),
+                    preserve_xattrs: cli.preserve_xattrs,
+                    mmap: cli.mmap,
+                    max_file_size: cli.max_size_hard_limit,
+                    expires_at: expire_after.map(|duration| SystemTime::now() + duration),
+                    // 结果摘要按扩展名和目录统计成功的文件
+                    track_successful_paths: true,
+                    stall_warning: Duration::from_secs(cli.stall_warning),
+                    stall_timeout: cli.stall_timeout.map(Duration::from_secs),
+                    write_readme: *write_readme,
+                    retry_locked: *retry_locked,
+                    strict: cli.strict,
+                    keyfile_commitment: *keyfile_commitment,
+                    ..Default::default()
+                };
 
-            let result = batch_encrypt_files(paths, &password, loaded_keyfile.as_ref(), &config)?;
-            print_batch_result(&result, "加密");
+                let result =
+                    batch_encrypt_paths(paths, &password, loaded_keyfile.as_ref(), &config);
+                audit_batch(
+                    audit_log.as_ref(),
+                    AuditOperation::Encrypt,
+                    Some(*level),
+                    &result,
+                    paths,
+                )?;
+                let result = result?;
+                print_batch_result(&result, "加密", None, cli.verbose);
+                notify_completion(completion_hook.as_ref(), "encrypt", &result, started);
+                ensure_strict_success(&result, cli.strict)?;
+            }
 
             password.zeroize();
         }
         // --- 解密命令 ---
-        Commands::Decrypt { paths, keyfile } => {
-            let mut password = rpassword::prompt_password("请输入密码 (输入时不可见): ")
-                .context("无法读取密码")?;
+        Commands::Decrypt {
+            paths,
+            recursive,
+            keyfile,
+            input_format,
+            chmod,
+            normalize_names,
+            ignore_auth_failure,
+            output,
+            estimate_time,
+            estimate_level,
+        } => {
+            if *estimate_time {
+                for path in paths {
+                    let estimate = estimate_decryption_time(path, *estimate_level)?;
+                    println!("{}: 预计约 {}", path.display(), format_duration(estimate));
+                }
+                return Ok(());
+            }
+            let mut password = read_password(&cli)?;
 
-            let loaded_keyfile = load_keyfile_if_provided(keyfile)?;
+            let loaded_keyfile = load_keyfile_if_provided(keyfile, &cli)?;
 
-            let result = batch_decrypt_files(paths, &password, loaded_keyfile.as_ref())?;
-            print_batch_result(&result, "解密");
+            if *ignore_auth_failure {
+                let ([path], Some(output)) = (paths.as_slice(), output) else {
+                    bail!("恢复模式 (--ignore-auth-failure) 一次只能处理一个文件");
+                };
+                if path.is_dir() || *input_format == DataFormat::Base64 {
+                    bail!("恢复模式 (--ignore-auth-failure) 只能用于单个二进制加密文件");
+                }
+                let opts = DecryptionOptions {
+                    keyfile: loaded_keyfile.as_ref(),
+                    buffer_size: cli.buffer_size,
+                    lock_memory: cli.lock_memory,
+                    temp_dir: cli.temp_dir.as_deref(),
+                    ..DecryptionOptions::new(&password)
+                };
+                let report = decrypt_unverified(path, output, &opts);
+                password.zeroize();
+                if let Some(audit_log) = &audit_log {
+                    audit_log.append(&match &report {
+                        Ok(report) => AuditRecord::success(
+                            AuditOperation::Decrypt,
+                            path,
+                            Some(&report.output_path),
+                            None,
+                        ),
+                        Err(e) => {
+                            AuditRecord::failure(AuditOperation::Decrypt, path, None, None, e)
+                        }
+                    })?;
+                }
+                let report = report.with_context(|| format!("恢复失败: {}", path.display()))?;
+                if report.tag_verified {
+                    log::info!(
+                        "✅ 认证标签验证通过，已写出 {} 字节: {}",
+                        report.recovered_bytes,
+                        report.output_path.display()
+                    );
+                } else {
+                    log::warn!("⚠️  ===== 未经认证的恢复输出 =====");
+                    log::warn!(
+                        "⚠️  已恢复 {} 字节: {}",
+                        report.recovered_bytes,
+                        report.output_path.display()
+                    );
+                    if let Some(error) = &report.read_error {
+                        log::warn!("⚠️  读取在此之后失败: {error}");
+                    }
+                    log::warn!("⚠️  完整性没有任何保证: 数据可能已损坏或被篡改，密码错误时也只是随机数据。");
+                }
+                return Ok(());
+            }
+
+            if *input_format == DataFormat::Base64 {
+                let opts = DecryptionOptions {
+                    keyfile: loaded_keyfile.as_ref(),
+                    single_thread: cli.single_thread,
+                    threads: usize::from(cli.threads),
+                    buffer_size: cli.buffer_size,
+                    lock_memory: cli.lock_memory,
+                    temp_dir: cli.temp_dir.as_deref(),
+                    normalize_names: *normalize_names,
+                    strict: cli.strict,
+                    ..DecryptionOptions::new(&password)
+                };
+                for path in paths {
+                    let encoded = std::fs::read_to_string(path)
+                        .with_context(|| format!("无法读取 Base64 文件: {}", path.display()))?;
+                    let output_dir = path
+                        .parent()
+                        .filter(|dir| !dir.as_os_str().is_empty())
+                        .unwrap_or(Path::new("."));
+                    let target = decrypt_base64_to_file(&encoded, output_dir, &opts);
+                    if let Some(audit_log) = &audit_log {
+                        audit_log.append(&match &target {
+                            Ok(target) => AuditRecord::success(
+                                AuditOperation::Decrypt,
+                                path,
+                                Some(target),
+                                None,
+                            ),
+                            Err(e) => {
+                                AuditRecord::failure(AuditOperation::Decrypt, path, None, None, e)
+                            }
+                        })?;
+                    }
+                    let target = target.with_context(|| format!("解密失败: {}", path.display()))?;
+                    log::info!("✅ 成功解密: {} -> {}", path.display(), target.display());
+                }
+            } else {
+                let config = BatchConfig {
+                    recursive: *recursive,
+                    temp_file_path: Arc::clone(&temp_file_path),
+                    progress_sink: progress_sink.clone(),
+                    parallel_scan: cli.parallel_scan,
+                    single_thread: cli.single_thread,
+                    threads: usize::from(cli.threads),
+                    buffer_size: cli.buffer_size,
+                    lock_memory: cli.lock_memory,
+                    temp_dir: cli.temp_dir.clone(),
+                    preserve_xattrs: cli.preserve_xattrs,
+                    permissions: *chmod,
+                    normalize_names: *normalize_names,
+                    // 结果摘要按扩展名和目录统计成功的文件
+                    track_successful_paths: true,
+                    stall_warning: Duration::from_secs(cli.stall_warning),
+                    stall_timeout: cli.stall_timeout.map(Duration::from_secs),
+                    strict: cli.strict,
+                    ..Default::default()
+                };
+                let result =
+                    batch_decrypt_paths(paths, &password, loaded_keyfile.as_ref(), &config);
+                audit_batch(
+                    audit_log.as_ref(),
+                    AuditOperation::Decrypt,
+                    None,
+                    &result,
+                    paths,
+                )?;
+                let result = result?;
+                print_batch_result(&result, "解密", None, cli.verbose);
+                notify_completion(completion_hook.as_ref(), "decrypt", &result, started);
+                ensure_strict_success(&result, cli.strict)?;
+            }
 
             password.zeroize();
         }
@@ -229,11 +1110,28 @@ fn main() -> Result<()> {
             include_patterns,
             exclude_patterns,
             keyfile,
+            expire_after,
+            build_index,
+            manifest,
+            skip_open_files,
+            write_readme,
+            retry_locked,
+            dedupe_hardlinks,
+            skip_if_encrypted_matches,
+            keyfile_commitment,
+            output_format,
+            yes,
+            no_location_check,
+            dry_run,
         } => {
-            let mut password = rpassword::prompt_password("请输入密码 (输入时不可见): ")
-                .context("无法读取密码")?;
-
-            let loaded_keyfile = load_keyfile_if_provided(keyfile)?;
+            if *dry_run {
+                for warning in check_encryption_location(directory) {
+                    log::warn!("⚠️  目录 {}: {warning}", directory.display());
+                }
+            } else if !*no_location_check && !confirm_encryption_location(directory, *yes)? {
+                log::info!("操作已取消。");
+                return Ok(());
+            }
 
             let config = BatchConfig {
                 level: *level,
@@ -241,73 +1139,839 @@ fn main() -> Result<()> {
                 recursive: *recursive,
                 include_patterns: parse_patterns(include_patterns, "include")?,
                 exclude_patterns: parse_patterns(exclude_patterns, "exclude")?,
+                temp_file_path: Arc::clone(&temp_file_path),
+                progress_sink: progress_sink.clone(),
+                parallel_scan: cli.parallel_scan,
+                single_thread: cli.single_thread,
+                threads: usize::from(cli.threads),
+                buffer_size: cli.buffer_size,
+                lock_memory: cli.lock_memory,
+                temp_dir: cli.temp_dir.clone(),
+                preserve_xattrs: cli.preserve_xattrs,
+                permissions: OutputPermissions::ProcessUmask,
+                normalize_names: NameNormalization::None,
+                password_validator: None,
+                mmap: cli.mmap,
+                expires_at: expire_after.map(|duration| SystemTime::now() + duration),
+                // 结果摘要按扩展名和目录统计成功的文件
+                track_successful_paths: true,
+                skip_open_files: *skip_open_files,
+                overwrite_mode: OverwriteMode::Fail,
+                max_file_size: cli.max_size_hard_limit,
+                output_dir: None,
+                flatten: false,
+                keep_going: false,
+                stall_warning: Duration::from_secs(cli.stall_warning),
+                stall_timeout: cli.stall_timeout.map(Duration::from_secs),
+                write_readme: *write_readme,
+                retry_locked: *retry_locked,
+                strict: cli.strict,
+                credentials: None,
+                dedupe_hardlinks: *dedupe_hardlinks,
+                preserve_hardlinks: false,
+                skip_if_encrypted_matches: *skip_if_encrypted_matches,
+                keyfile_commitment: *keyfile_commitment,
             };
 
-            let result =
-                batch_encrypt_directory(directory, &password, loaded_keyfile.as_ref(), &config)?;
-            print_batch_result(&result, "批量加密");
+            if *dry_run {
+                let plan = plan_batch_encrypt(std::slice::from_ref(directory), &config)?;
+                print_batch_plan(&plan, cli.verbose);
+                return Ok(());
+            }
+            let mut password = read_password(&cli)?;
+            let loaded_keyfile = load_keyfile_if_provided(keyfile, &cli)?;
+
+            let manifest_path = directory.join(MANIFEST_FILE_NAME);
+            if *manifest && !*force && manifest_path.exists() {
+                bail!(
+                    "清单文件 {} 已存在。如需覆盖，请使用 --force 标志。",
+                    manifest_path.display()
+                );
+            }
+
+            let result = if *build_index {
+                batch_encrypt_directory_with_index(
+                    directory,
+                    &password,
+                    loaded_keyfile.as_ref(),
+                    &config,
+                )
+                .map(|(result, index)| {
+                    log::info!(
+                        "🔎 已生成加密索引 ({} 个文件): {}",
+                        index.len(),
+                        index.path().display()
+                    );
+                    result
+                })
+            } else {
+                batch_encrypt_directory(directory, &password, loaded_keyfile.as_ref(), &config)
+            };
+            audit_batch(
+                audit_log.as_ref(),
+                AuditOperation::Encrypt,
+                Some(*level),
+                &result,
+                std::slice::from_ref(directory),
+            )?;
+            let result = result?;
+            if *manifest {
+                let restore_manifest =
+                    RestoreManifest::from_batch_result(directory, &result, &config)?;
+                restore_manifest.save(&manifest_path)?;
+                log::info!(
+                    "🧾 已生成恢复校验清单 ({} 个文件): {}",
+                    restore_manifest.len(),
+                    manifest_path.display()
+                );
+            }
+            print_batch_result(&result, "批量加密", Some(directory), cli.verbose);
+            write_batch_report(&result, *output_format)?;
+            notify_completion(completion_hook.as_ref(), "batch-encrypt", &result, started);
 
             password.zeroize();
+            ensure_strict_success(&result, cli.strict)?;
         }
         // --- 批量解密命令 ---
         Commands::BatchDecrypt {
             directory,
             recursive,
             keyfile,
+            overwrite,
+            output_dir,
+            flatten,
+            chmod,
+            normalize_names,
+            output_format,
+            keep_going,
+            credentials_file,
+            preserve_hardlinks,
         } => {
-            let mut password = rpassword::prompt_password("请输入密码 (输入时不可见): ")
-                .context("无法读取密码")?;
+            let credentials = credentials_file
+                .as_ref()
+                .map(|path| Credentials::load(path).map(Arc::new))
+                .transpose()?;
+            // 凭据表中已经有密码，不再提示输入
+            let mut password = match credentials {
+                Some(_) => String::new(),
+                None => read_password(&cli)?,
+            };
 
-            let loaded_keyfile = load_keyfile_if_provided(keyfile)?;
+            let loaded_keyfile = load_keyfile_if_provided(keyfile, &cli)?;
 
             let config = BatchConfig {
                 recursive: *recursive,
+                temp_file_path: Arc::clone(&temp_file_path),
+                progress_sink: progress_sink.clone(),
+                parallel_scan: cli.parallel_scan,
+                single_thread: cli.single_thread,
+                threads: usize::from(cli.threads),
+                buffer_size: cli.buffer_size,
+                lock_memory: cli.lock_memory,
+                temp_dir: cli.temp_dir.clone(),
+                preserve_xattrs: cli.preserve_xattrs,
+                // 结果摘要按扩展名和目录统计成功的文件
+                track_successful_paths: true,
+                overwrite_mode: *overwrite,
+                permissions: *chmod,
+                normalize_names: *normalize_names,
+                output_dir: output_dir.clone(),
+                flatten: *flatten,
+                keep_going: *keep_going,
+                stall_warning: Duration::from_secs(cli.stall_warning),
+                stall_timeout: cli.stall_timeout.map(Duration::from_secs),
+                strict: cli.strict,
+                credentials,
+                preserve_hardlinks: *preserve_hardlinks,
                 ..Default::default()
             };
 
             let result =
-                batch_decrypt_directory(directory, &password, loaded_keyfile.as_ref(), &config)?;
-            print_batch_result(&result, "批量解密");
+                batch_decrypt_directory(directory, &password, loaded_keyfile.as_ref(), &config);
+            audit_batch(
+                audit_log.as_ref(),
+                AuditOperation::Decrypt,
+                None,
+                &result,
+                std::slice::from_ref(directory),
+            )?;
+            let result = result?;
+            print_batch_result(&result, "批量解密", Some(directory), cli.verbose);
+            write_batch_report(&result, *output_format)?;
+            notify_completion(completion_hook.as_ref(), "batch-decrypt", &result, started);
 
             password.zeroize();
+            ensure_strict_success(&result, cli.strict)?;
         }
-        // --- 生成密钥文件命令 ---
-        Commands::GenerateKey { output } => {
-            if output.exists() {
-                log::warn!("密钥文件已存在: {}", output.display());
-                let confirm = rpassword::prompt_password("是否覆盖? (输入 'yes' 确认): ")?;
-                if confirm.to_lowercase() != "yes" {
-                    log::info!("操作已取消。");
-                    return Ok(());
+        // --- 验证命令 ---
+        Commands::Verify {
+            paths,
+            keyfile,
+            check_keyfile,
+        } => {
+            let mut password = read_password(&cli)?;
+
+            if let Some(keyfile_path) = check_keyfile {
+                let mut mismatches = 0;
+                for path in paths {
+                    match verify_keyfile_matches_encrypted_file(keyfile_path, path, &password) {
+                        Ok(true) => log::info!("✅ 密钥文件匹配: {}", path.display()),
+                        Ok(false) => {
+                            mismatches += 1;
+                            log::error!("❌ 密钥文件或密码不匹配: {}", path.display());
+                        }
+                        Err(e) => {
+                            mismatches += 1;
+                            log::error!("❌ {}: {e:#}", path.display());
+                        }
+                    }
                 }
+                password.zeroize();
+                if mismatches > 0 {
+                    bail!("有 {mismatches} 个文件与密钥文件不匹配或无法检查");
+                }
+                return Ok(());
             }
 
-            let keyfile = KeyFile::generate();
-            keyfile.save_to_file(output)?;
-            log::info!("✅ 密钥文件已成功生成: {}", output.display());
-            log::warn!("请务必妥善保管此密钥文件，并制作备份。如果丢失，任何使用此密钥文件加密的数据都将永久无法恢复！");
-        }
-        // --- 交互式模式命令 ---
-        Commands::Interactive => {
-            run_interactive_mode()?;
-        }
-    };
+            let loaded_keyfile = load_keyfile_if_provided(keyfile, &cli)?;
 
-    Ok(())
-}
+            let result = batch_verify_files(
+                paths,
+                &password,
+                loaded_keyfile.as_ref(),
+                &BatchConfig {
+                    single_thread: cli.single_thread,
+                    threads: usize::from(cli.threads),
+                    buffer_size: cli.buffer_size,
+                    lock_memory: cli.lock_memory,
+                    track_successful_paths: true,
+                    strict: cli.strict,
+                    ..Default::default()
+                },
+            )?;
+            print_batch_result(&result, "验证", None, cli.verbose);
+            notify_completion(completion_hook.as_ref(), "verify", &result, started);
 
-/// 如果用户提供了密钥文件路径，则加载并验证它。
-fn load_keyfile_if_provided(keyfile_path: &Option<PathBuf>) -> Result<Option<KeyFile>> {
-    match keyfile_path {
-        Some(path) => {
-            validate_keyfile(path)?;
-            let keyfile = KeyFile::load_from_file(path)?;
-            Ok(Some(keyfile))
+            password.zeroize();
+            ensure_strict_success(&result, cli.strict)?;
+        }
+        // --- 比较命令 ---
+        Commands::Compare {
+            paths,
+            auto_pair,
+            keyfile,
+        } => {
+            if !*auto_pair && paths.len() != 2 {
+                bail!("compare 需要 <明文文件> <加密文件> 两个路径，或使用 --auto-pair");
+            }
+            let mut password = read_password(&cli)?;
+            let loaded_keyfile = load_keyfile_if_provided(keyfile, &cli)?;
+
+            if *auto_pair {
+                let result = batch_compare_files(
+                    paths,
+                    &password,
+                    loaded_keyfile.as_ref(),
+                    &BatchConfig {
+                        single_thread: cli.single_thread,
+                        threads: usize::from(cli.threads),
+                        buffer_size: cli.buffer_size,
+                        lock_memory: cli.lock_memory,
+                        track_successful_paths: true,
+                        strict: cli.strict,
+                        ..Default::default()
+                    },
+                );
+                password.zeroize();
+                let result = result?;
+                print_batch_result(&result, "比较", None, cli.verbose);
+                notify_completion(completion_hook.as_ref(), "compare", &result, started);
+                if !result.was_successful() {
+                    bail!("有 {} 个文件与明文不一致或无法比较", result.failure_count);
+                }
+                return Ok(());
+            }
+
+            let (plaintext, encrypted) = (&paths[0], &paths[1]);
+            let report = compare_file(plaintext, encrypted, &password, loaded_keyfile.as_ref());
+            password.zeroize();
+            let report = report?;
+            match report.first_difference {
+                None => println!(
+                    "✅ 一致: {} 能解密出 {} ({} 字节)",
+                    encrypted.display(),
+                    plaintext.display(),
+                    report.decrypted_len
+                ),
+                Some(offset) => bail!(
+                    "{} 与 {} 不一致: 第一个不同的字节位于偏移量 {offset} (明文 {} 字节，解密结果 {} 字节)",
+                    encrypted.display(),
+                    plaintext.display(),
+                    report.plaintext_len,
+                    report.decrypted_len
+                ),
+            }
+        }
+        // --- 索引搜索命令 ---
+        Commands::Search {
+            index,
+            term,
+            keyfile,
+        } => {
+            let mut password = read_password(&cli)?;
+
+            let loaded_keyfile = load_keyfile_if_provided(keyfile, &cli)?;
+
+            let matches = search_index(index, term, &password, loaded_keyfile.as_ref());
+            password.zeroize();
+            let matches = matches?;
+
+            if matches.is_empty() {
+                log::info!("未在索引中找到文件名为 \"{term}\" 的文件。");
+            }
+            for path in matches {
+                println!("{}", path.display());
+            }
+        }
+        // --- 恢复校验命令 ---
+        Commands::VerifyRestore {
+            manifest,
+            restored_dir,
+        } => {
+            let report = verify_restore(manifest, restored_dir, usize::from(cli.threads))?;
+            for (label, paths) in [
+                ("缺失", &report.missing),
+                ("多余", &report.extra),
+                ("内容不一致", &report.mismatched),
+            ] {
+                for path in paths {
+                    println!("{label}: {path}");
+                }
+            }
+            if !report.is_clean() {
+                bail!(
+                    "恢复结果与清单不一致: {} 个文件缺失, {} 个多余, {} 个内容不一致",
+                    report.missing.len(),
+                    report.extra.len(),
+                    report.mismatched.len()
+                );
+            }
+            log::info!("✅ 恢复结果与清单一致: {} 个文件", report.verified);
+        }
+        // --- 文件信息命令 ---
+        Commands::Info {
+            paths,
+            recursive,
+            json,
+        } => {
+            if *json {
+                let mut entries = Vec::new();
+                for path in paths {
+                    if path.is_dir() {
+                        entries.extend(scan_encrypted(path, *recursive)?);
+                    } else {
+                        entries.push(EncryptedFileInfo::inspect(path));
+                    }
+                }
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
+            for path in paths {
+                if path.is_dir() {
+                    print_encrypted_file_list(path, &scan_encrypted(path, *recursive)?);
+                    continue;
+                }
+                let info = inspect_file(path)?;
+                let level = info.level.map_or_else(
+                    || {
+                        format!(
+                            "Custom (m={}KiB, t={}, p={})",
+                            info.m_cost, info.t_cost, info.p_cost
+                        )
+                    },
+                    |level| level.to_string(),
+                );
+                println!("{}", path.display());
+                println!("  原始文件名:   {}", info.original_filename);
+                println!("  格式版本:     {}", info.format_version);
+                println!(
+                    "  加密算法:     {}",
+                    CipherSuite::from_header_byte(info.cipher_id).map_or_else(
+                        |_| format!("未知 (id={})", info.cipher_id),
+                        |suite| suite.display_name().to_string()
+                    )
+                );
+                println!(
+                    "  密钥文件:     {}",
+                    if info.has_keyfile_commitment {
+                        "是 (已记录承诺)"
+                    } else {
+                        "未记录"
+                    }
+                );
+                if let Some(expires_at) = info.expires_at {
+                    println!("  过期时间:     {}", format_utc_time(expires_at));
+                }
+                if let Some(source_modified) = info.source_modified {
+                    println!("  源文件修改于: {}", format_utc_time(source_modified));
+                }
+                println!("  安全级别:     {level}");
+                println!(
+                    "  Argon2 参数:  m_cost={} KiB, t_cost={}, p_cost={}",
+                    info.m_cost, info.t_cost, info.p_cost
+                );
+                println!("  文件头大小:   {} 字节", info.header_len);
+                println!("  加密数据大小: {} 字节", info.ciphertext_len);
+                println!("  文件总大小:   {} 字节", info.file_len);
+            }
+        }
+        // --- 格式版本检查命令 ---
+        Commands::VersionCheck {
+            directory,
+            recursive,
+        } => {
+            let config = BatchConfig {
+                recursive: *recursive,
+                strict: cli.strict,
+                ..Default::default()
+            };
+            let versions = scan_format_versions(directory, &config)?;
+            if versions.is_empty() {
+                log::info!("目录中没有找到加密文件: {}", directory.display());
+                return Ok(());
+            }
+
+            let mut sorted: Vec<_> = versions.iter().collect();
+            sorted.sort_by_key(|(version, _)| **version);
+            for (version, files) in sorted {
+                let label = match *version {
+                    LEGACY_FORMAT_VERSION => "legacy",
+                    CURRENT_FORMAT_VERSION => "current",
+                    v if v < CURRENT_FORMAT_VERSION => "supported",
+                    _ => "unsupported",
+                };
+                println!(
+                    "Version {version} ({label}): {} files",
+                    format_count(files.len())
+                );
+            }
+
+            if let Some(legacy) = versions.get(&LEGACY_FORMAT_VERSION) {
+                log::warn!(
+                    "💡 发现 {} 个遗留格式文件，其文件头不受认证保护。建议先解密再使用当前版本重新加密。",
+                    format_count(legacy.len())
+                );
+            }
+            let newer: usize = versions
+                .iter()
+                .filter(|(version, _)| **version > CURRENT_FORMAT_VERSION)
+                .map(|(_, files)| files.len())
+                .sum();
+            if newer > 0 {
+                log::warn!(
+                    "⚠️  有 {} 个文件使用了比当前程序更新的格式版本，请升级 Ferox Encryptor 后再处理。",
+                    format_count(newer)
+                );
+            }
+        }
+        // --- 兼容性检查命令 ---
+        Commands::CheckCompat { directory, json } => {
+            let config = BatchConfig {
+                recursive: true,
+                strict: cli.strict,
+                ..Default::default()
+            };
+            let entries = check_compatibility(directory, &config)?;
+            let count = |status: Compatibility| {
+                entries
+                    .iter()
+                    .filter(|entry| entry.status == status)
+                    .count()
+            };
+
+            if *json {
+                let report = serde_json::json!({
+                    "current_format_version": CURRENT_FORMAT_VERSION,
+                    "summary": {
+                        "supported": count(Compatibility::Supported),
+                        "legacy": count(Compatibility::Legacy),
+                        "needs_upgrade": count(Compatibility::NeedsUpgrade),
+                        "unreadable": count(Compatibility::Unreadable),
+                    },
+                    "files": entries,
+                });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_compatibility_report(&entries);
+            }
+
+            let unreadable = entries
+                .iter()
+                .filter(|entry| !entry.status.is_decryptable())
+                .count();
+            if unreadable > 0 {
+                bail!(
+                    "有 {} 个文件无法被当前版本的 Ferox Encryptor 解密",
+                    format_count(unreadable)
+                );
+            }
+        }
+        // --- 迁移报告命令 ---
+        Commands::MigrationReport {
+            directory,
+            level,
+            keyfile,
+            json,
+            save_plan,
+        } => {
+            let mut password = read_password(&cli)?;
+            let loaded_keyfile = load_keyfile_if_provided(keyfile, &cli)?;
+            let report =
+                migration_report(directory, true, &password, loaded_keyfile.as_ref(), *level);
+            password.zeroize();
+            let report = report?;
+
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_migration_report(&report, cli.verbose);
+            }
+            if let Some(plan_path) = save_plan {
+                let plan = report.plan();
+                plan.save(plan_path)?;
+                log::info!(
+                    "📝 已保存迁移计划 ({} 个文件): {}",
+                    format_count(plan.files.len()),
+                    plan_path.display()
+                );
+            }
+
+            let failures = report.failures().count();
+            if failures > 0 {
+                bail!("有 {} 个文件无法读取或验证失败", format_count(failures));
+            }
+        }
+        // --- 清理残留文件命令 ---
+        Commands::Cleanup {
+            directory,
+            recursive,
+            yes,
+        } => {
+            let partials = scan_stale_partials(directory, *recursive)?;
+            if partials.is_empty() {
+                log::info!("✅ 没有发现残留的不完整输出文件。");
+                return Ok(());
+            }
+
+            log::warn!("发现 {} 个残留的不完整输出文件:", partials.len());
+            for partial in &partials {
+                let age = partial.age.map_or_else(|| "未知".to_string(), format_age);
+                log::warn!(
+                    "   📁 {} ({} 字节, {})",
+                    partial.path.display(),
+                    partial.size,
+                    age
+                );
+            }
+
+            if !*yes {
+                let confirm = rpassword::prompt_password("是否删除这些文件? (输入 'yes' 确认): ")?;
+                if confirm.to_lowercase() != "yes" {
+                    log::info!("操作已取消。");
+                    return Ok(());
+                }
+            }
+
+            let removed = remove_stale_partials(&partials)?;
+            log::info!("✅ 已删除 {} 个残留文件。", removed.len());
+        }
+        Commands::CleanupSidecars {
+            directory,
+            recursive,
+            dry_run,
+        } => {
+            let config = BatchConfig {
+                recursive: *recursive,
+                parallel_scan: cli.parallel_scan,
+                strict: cli.strict,
+                ..Default::default()
+            };
+            let orphans = cleanup_orphaned_sidecars(directory, &config, *dry_run)?;
+            if orphans.is_empty() {
+                log::info!("✅ 没有发现孤立的旁路元数据文件。");
+            } else if *dry_run {
+                log::warn!("发现 {} 个孤立的旁路元数据文件 (未删除):", orphans.len());
+                for path in &orphans {
+                    log::warn!("   📁 {}", path.display());
+                }
+            } else {
+                log::info!("✅ 已删除 {} 个孤立的旁路元数据文件。", orphans.len());
+            }
+        }
+        // --- 生成密钥文件命令 ---
+        Commands::GenerateKey {
+            output,
+            keyfile_from_device,
+            split_keyfile,
+            shares,
+            threshold,
+            combine_keyfile_shares,
+        } => {
+            if output.exists() {
+                log::warn!("密钥文件已存在: {}", output.display());
+                let confirm = rpassword::prompt_password("是否覆盖? (输入 'yes' 确认): ")?;
+                if confirm.to_lowercase() != "yes" {
+                    log::info!("操作已取消。");
+                    return Ok(());
+                }
+            }
+
+            if !combine_keyfile_shares.is_empty() {
+                let loaded = combine_keyfile_shares
+                    .iter()
+                    .map(KeyShare::load_from_file)
+                    .collect::<Result<Vec<_>>>()?;
+                let keyfile = KeyFile::from_shares(&loaded)?;
+                keyfile.save_to_file(output)?;
+                log::info!(
+                    "✅ 已由 {} 个分片恢复密钥文件: {}",
+                    loaded.len(),
+                    output.display()
+                );
+                return Ok(());
+            }
+
+            let keyfile = match keyfile_from_device {
+                Some(device) => {
+                    log::info!("正在从硬件熵源读取密钥材料: {}", device.display());
+                    generate_keyfile_from_hardware_entropy(device, MAX_KEYFILE_SIZE)?
+                }
+                None => KeyFile::generate(),
+            };
+            // 先拆分再保存，阈值参数无效时不会留下没有分片的密钥文件
+            let key_shares = if *split_keyfile {
+                keyfile.split(*shares, *threshold)?
+            } else {
+                Vec::new()
+            };
+            keyfile.save_to_file(output)?;
+            log::info!("✅ 密钥文件已成功生成: {}", output.display());
+            for share in &key_shares {
+                let mut share_path = output.clone().into_os_string();
+                share_path.push(format!(".{}.{}", share.index(), KEYSHARE_FILE_EXTENSION));
+                share.save_to_file(PathBuf::from(share_path))?;
+            }
+            if !key_shares.is_empty() {
+                log::info!(
+                    "已生成 {} 个密钥文件分片，任意 {} 个即可恢复密钥文件",
+                    key_shares.len(),
+                    threshold
+                );
+            }
+            log::warn!("请务必妥善保管此密钥文件，并制作备份。如果丢失，任何使用此密钥文件加密的数据都将永久无法恢复！");
+        }
+        Commands::GenerateKeys {
+            count,
+            prefix,
+            output_dir,
+        } => {
+            let keyfiles = KeyFile::generate_batch(*count);
+            let paths = save_batch(&keyfiles, output_dir, prefix)?;
+            log::info!(
+                "✅ 已在 {} 中生成 {} 个密钥文件",
+                output_dir.display(),
+                paths.len()
+            );
+            log::warn!("请把每个密钥文件分别交给对应的成员妥善保管并备份。如果丢失，用它加密的数据将永久无法恢复！");
+        }
+        // --- 审计日志命令 ---
+        Commands::Audit {
+            action: AuditCommands::Verify { log, keyfile },
+        } => {
+            validate_keyfile(keyfile)?;
+            let audit_keyfile = KeyFile::load_from_file(keyfile)?;
+            let verification = verify_audit_log(log, &audit_keyfile)?;
+            for line in &verification.invalid_lines {
+                println!("第 {line} 行: HMAC 链条断开 (记录被修改，或之前的记录被插入/删除)");
+            }
+            if !verification.is_intact() {
+                bail!(
+                    "审计日志已被篡改: {} 条记录中有 {} 处链条断开",
+                    verification.records,
+                    verification.invalid_lines.len()
+                );
+            }
+            log::info!("✅ 审计日志完好: {} 条记录", verification.records);
+        }
+        // --- 密钥链密码命令 ---
+        #[cfg(feature = "keyring")]
+        Commands::Password {
+            action: PasswordCommands::Forget { name },
+        } => {
+            forget_password(&OsKeychain, name)?;
+            log::info!("✅ 已从密钥链删除密码: {name}");
+        }
+        // --- 交互式模式命令 ---
+        Commands::Interactive { password_timeout } => {
+            run_interactive_session(Arc::clone(&temp_file_path), *password_timeout)?;
+        }
+    };
+
+    Ok(())
+}
+
+/// 读取用户的密码。
+///
+/// 默认在终端显示提示并隐藏输入；启用 `--stdin-password-confirm` 时改为
+/// [`read_confirmed_password_from_stdin`]。启用 `keyring` 特性时，`--use-saved` 从密钥链读取密码，
+/// `--save-password` 记下输入的密码，待操作成功后保存到密钥链。
+fn read_password(cli: &Cli) -> Result<String> {
+    #[cfg(feature = "keyring")]
+    if let Some(name) = &cli.use_saved {
+        let password = PasswordSource::Stored {
+            store: &OsKeychain,
+            name,
+        }
+        .resolve()?;
+        return Ok(password.to_string());
+    }
+
+    let password = if cli.stdin_password_confirm {
+        read_confirmed_password_from_stdin(cli.stdin_password_env_override.as_deref())?
+    } else {
+        rpassword::prompt_password("请输入密码 (输入时不可见): ").context("无法读取密码")?
+    };
+
+    #[cfg(feature = "keyring")]
+    if let Some(name) = &cli.save_password {
+        *PASSWORD_TO_SAVE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+            Some((name.clone(), Zeroizing::new(password.clone())));
+    }
+    Ok(password)
+}
+
+/// 批量加密前检查目录的位置，有警告时请求确认；返回是否继续。
+///
+/// 设置了 `yes` 时只显示警告。标准输入不是终端时无法确认，必须使用 `--yes` 或 `--no-location-check`。
+fn confirm_encryption_location(directory: &Path, yes: bool) -> Result<bool> {
+    let warnings = check_encryption_location(directory);
+    if warnings.is_empty() {
+        return Ok(true);
+    }
+    log::warn!("⚠️  目录 {} 可能不适合批量加密:", directory.display());
+    for warning in &warnings {
+        log::warn!("   ⚠️  {warning}");
+    }
+    if yes {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        bail!("无法确认加密位置：请使用 --yes 继续，或使用 --no-location-check 跳过检查");
+    }
+    let confirm = rpassword::prompt_password("仍然继续加密? (输入 'yes' 确认): ")?;
+    Ok(confirm.to_lowercase() == "yes")
+}
+
+/// 不显示提示，从标准输入读取密码和确认密码，两者一致时返回密码。
+///
+/// 标准输入的格式为两行：第一行是密码，第二行是相同的密码，每行以换行符 (`\n` 或 `\r\n`) 结束。
+/// 标准输入是终端时输入不回显。如果在读到第一行之前标准输入就已结束，并且给出了
+/// `env_override`，则改为使用该环境变量的值（此时不需要确认）。
+fn read_confirmed_password_from_stdin(env_override: Option<&str>) -> Result<String> {
+    let stdin = io::stdin();
+    let read_line = || {
+        if stdin.is_terminal() {
+            rpassword::read_password()
+        } else {
+            rpassword::read_password_from_bufread(&mut stdin.lock())
+        }
+    };
+
+    let mut password = match read_line() {
+        Ok(password) => password,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+            if let Some(var) = env_override {
+                match std::env::var(var) {
+                    Ok(password) if !password.is_empty() => return Ok(password),
+                    _ => bail!("标准输入没有提供密码，环境变量 {var} 也未设置"),
+                }
+            }
+            bail!("标准输入没有提供密码");
+        }
+        Err(e) => return Err(e).context("无法从标准输入读取密码"),
+    };
+    let mut confirmation = match read_line() {
+        Ok(confirmation) => confirmation,
+        Err(e) => {
+            password.zeroize();
+            return Err(e).context("无法从标准输入读取确认密码 (需要第二行)");
+        }
+    };
+
+    let matches = password == confirmation;
+    confirmation.zeroize();
+    if !matches {
+        password.zeroize();
+        bail!("两次输入的密码不一致");
+    }
+    Ok(password)
+}
+
+/// 如果用户提供了密钥文件路径，则加载并验证它。
+///
+/// 路径为 `-` 时按 `--keyfile-stdin-format` 从标准输入读取密钥材料。
+fn load_keyfile_if_provided(keyfile_path: &Option<PathBuf>, cli: &Cli) -> Result<Option<KeyFile>> {
+    match keyfile_path {
+        Some(path) if path.as_os_str() == "-" => {
+            if cli.stdin_password_confirm {
+                bail!(
+                    "--keyfile - 和 --stdin-password-confirm 不能同时使用，两者都需要读取标准输入"
+                );
+            }
+            Ok(Some(KeyFile::from_stdin(cli.keyfile_stdin_format)?))
+        }
+        Some(path) => {
+            validate_keyfile(path)?;
+            let keyfile = KeyFile::load_from_file(path)?;
+            Ok(Some(keyfile))
         }
         None => Ok(None),
     }
 }
 
+/// 给出 `--audit-log` 时，用 `--audit-keyfile` 打开审计日志。
+fn open_audit_log(cli: &Cli) -> Result<Option<AuditLog>> {
+    match (&cli.audit_log, &cli.audit_keyfile) {
+        (Some(path), Some(keyfile)) => {
+            validate_keyfile(keyfile)?;
+            let audit_keyfile = KeyFile::load_from_file(keyfile)?;
+            AuditLog::open(path, &audit_keyfile).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 把批量操作的结果写入审计日志；整个操作在处理任何文件之前失败时，为每个输入路径记录一次失败。
+fn audit_batch(
+    audit_log: Option<&AuditLog>,
+    operation: AuditOperation,
+    level: Option<Level>,
+    result: &Result<ferox_encryptor::BatchResult>,
+    inputs: &[PathBuf],
+) -> Result<()> {
+    let Some(audit_log) = audit_log else {
+        return Ok(());
+    };
+    match result {
+        Ok(result) => audit_log.record_batch(operation, level, result),
+        Err(e) => inputs.iter().try_for_each(|input| {
+            audit_log.append(&AuditRecord::failure(operation, input, None, level, e))
+        }),
+    }
+}
+
 /// 解析字符串形式的 glob 模式。
 fn parse_patterns(patterns_str: &[String], pattern_type: &str) -> Result<Vec<Pattern>> {
     if patterns_str.is_empty() && pattern_type == "include" {
@@ -319,12 +1983,164 @@ fn parse_patterns(patterns_str: &[String], pattern_type: &str) -> Result<Vec<Pat
         .collect()
 }
 
+/// 解析 `--expire-after` 等时长参数：正整数加单位 (`s` 秒、`m` 分钟、`h` 小时、`d` 天、`w` 周、`y` 年，
+/// 1 年按 365 天计算)。
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("缺少时间单位: {value} (可用单位: s、m、h、d、w、y)"))?;
+    let (number, unit) = value.split_at(split);
+    let count: u64 = number.parse().map_err(|_| format!("无效的时长: {value}"))?;
+    let unit_secs = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        "y" => 365 * 86_400,
+        _ => {
+            return Err(format!(
+                "未知的时间单位: {unit} (可用单位: s、m、h、d、w、y)"
+            ))
+        }
+    };
+    match count.checked_mul(unit_secs) {
+        Some(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        Some(_) => Err("时长必须大于 0".to_string()),
+        None => Err(format!("时长太长了: {value}")),
+    }
+}
+
+/// 解析带单位的字节数，支持 `B`、`KB`/`K`/`KiB`、`MB`/`M`/`MiB`、`GB`/`G`/`GiB` 和
+/// `TB`/`T`/`TiB` 后缀 (按 1024 进制)。
+fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("无效的大小: {value}"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("无法识别的单位 '{unit}'，请使用 KB、MB、GB 或 TB")),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("大小过大: {value}"))
+}
+
+/// 解析 `--buffer-size` 参数 (见 [`parse_byte_size`])。
+fn parse_buffer_size(value: &str) -> Result<usize, String> {
+    let size = parse_byte_size(value)?;
+    match usize::try_from(size) {
+        Ok(size) if (MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&size) => Ok(size),
+        _ => Err(format!(
+            "缓冲区大小必须在 4KB 到 256MB 之间，当前为 {}",
+            value.trim()
+        )),
+    }
+}
+
+/// 解析 `--max-size-hard-limit` 参数 (见 [`parse_byte_size`])，上限必须大于 0。
+fn parse_max_file_size(value: &str) -> Result<u64, String> {
+    match parse_byte_size(value)? {
+        0 => Err("大小上限必须大于 0".to_string()),
+        size => Ok(size),
+    }
+}
+
+/// 解析 `--rate-limit` 参数 (见 [`parse_byte_size`])，速率上限必须大于 0。
+fn parse_rate_limit(value: &str) -> Result<u64, String> {
+    match parse_byte_size(value)? {
+        0 => Err("速率上限必须大于 0".to_string()),
+        rate => Ok(rate),
+    }
+}
+
+/// 解析 `--chmod` 参数：八进制权限位 (可带 `0` 或 `0o` 前缀)、`preserve` 或 `umask`。
+fn parse_chmod(value: &str) -> Result<OutputPermissions, String> {
+    let value = value.trim();
+    match value.to_ascii_lowercase().as_str() {
+        "preserve" => return Ok(OutputPermissions::FromHeader),
+        "umask" => return Ok(OutputPermissions::ProcessUmask),
+        _ => {}
+    }
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(OutputPermissions::Fixed(mode)),
+        _ => Err(format!(
+            "无效的权限: {value} (请使用 600、0640 之类的八进制权限位，或 preserve、umask)"
+        )),
+    }
+}
+
+/// 按 `format` 把批量结果的逐文件报告写到标准输出；`Text` 格式不输出任何内容。
+fn write_batch_report(result: &ferox_encryptor::BatchResult, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Text => Ok(()),
+        ReportFormat::Csv => result.to_csv(io::stdout().lock()),
+        ReportFormat::Json => result.to_json(io::stdout().lock()),
+    }
+}
+
+/// 严格模式 (`--strict`) 下批量操作有任何失败时返回错误，使进程以非零状态退出。
+///
+/// 非严格模式下失败只在结果摘要中列出，退出状态不变。
+fn ensure_strict_success(result: &ferox_encryptor::BatchResult, strict: bool) -> Result<()> {
+    if strict && !result.was_successful() {
+        bail!("严格模式: 有 {} 个文件失败", result.failure_count);
+    }
+    Ok(())
+}
+
+/// 按 `--on-complete` 或 `--on-complete-config` 创建完成通知钩子，两者都没有指定时返回 `None`。
+fn completion_hook(cli: &Cli) -> Result<Option<CompletionHook>> {
+    let hook = match (&cli.on_complete, &cli.on_complete_config) {
+        (Some(command), _) => CompletionHook::new(command.as_str())
+            .with_timeout(Duration::from_secs(cli.on_complete_timeout)),
+        (None, Some(path)) => CompletionHook::load(path)?,
+        (None, None) => return Ok(None),
+    };
+    // 通过环境变量提供的密码不能传给钩子
+    Ok(Some(match &cli.stdin_password_env_override {
+        Some(var) => hook.without_env(var),
+        None => hook,
+    }))
+}
+
+/// 批量操作结束后运行完成通知钩子 (Run the completion hook)
+///
+/// 钩子失败只发出警告，不影响批量操作的结果和退出状态。
+fn notify_completion(
+    hook: Option<&CompletionHook>,
+    operation: &str,
+    result: &ferox_encryptor::BatchResult,
+    started: Instant,
+) {
+    let Some(hook) = hook else {
+        return;
+    };
+    if let Err(e) = hook.run(operation, result, started.elapsed()) {
+        log::warn!("⚠️  {e:#}");
+    }
+}
+
 /// 打印批量操作的结果 (Print batch operation results)
 ///
 /// 提供详细的操作统计信息和用户友好的结果展示
 ///
 /// *Provides detailed operation statistics and user-friendly result display*
-fn print_batch_result(result: &ferox_encryptor::BatchResult, operation_name: &str) {
+fn print_batch_result(
+    result: &ferox_encryptor::BatchResult,
+    operation_name: &str,
+    root: Option<&Path>,
+    verbose: bool,
+) {
     // 计算总文件数 (Calculate total file count)
     let total_files = result.success_count + result.failure_count;
 
@@ -338,23 +2154,172 @@ fn print_batch_result(result: &ferox_encryptor::BatchResult, operation_name: &st
         log::info!("📊 处理统计:");
         log::info!("   ✅ 成功: {} 个文件", result.success_count);
         log::warn!("   ❌ 失败: {} 个文件", result.failure_count);
-        log::info!("   📈 成功率: {:.1}%",
-            (result.success_count as f64 / total_files as f64) * 100.0);
+        log::info!(
+            "   📈 成功率: {:.1}%",
+            (result.success_count as f64 / total_files as f64) * 100.0
+        );
+    }
+
+    // 流式处理中实际读写的数据量 (Bytes actually read and written while streaming)
+    if result.bytes_read > 0 || result.bytes_written > 0 {
+        log::info!(
+            "   💾 数据量: 读取 {} 字节 / 写入 {} 字节",
+            result.bytes_read,
+            result.bytes_written
+        );
+    }
+
+    // 显示因目标文件已存在而跳过的文件数量 (Show files skipped because the target exists)
+    if result.skipped_count > 0 {
+        log::info!(
+            "   ⏭️  跳过: {} 个文件 (目标文件已存在)",
+            result.skipped_count
+        );
+    }
+
+    // 只加密了一次的硬链接和解密后重建的硬链接 (Deduplicated and restored hard links)
+    if !result.skipped_hardlinks.is_empty() {
+        log::info!(
+            "   🔗 硬链接: {} 个文件与已加密的文件共享内容，未重复加密，节省 {} 字节",
+            result.skipped_hardlinks.len(),
+            result.hardlink_bytes_saved
+        );
+    }
+    if !result.restored_hardlinks.is_empty() {
+        log::info!("   🔗 重建了 {} 个硬链接", result.restored_hardlinks.len());
+    }
+    if !result.skipped_up_to_date.is_empty() {
+        log::info!(
+            "   ⏩ 已是最新: {} 个文件的加密结果与源文件一致，未重新加密",
+            result.skipped_up_to_date.len()
+        );
+    }
+
+    // 解密时显示各安全级别的文件数量 (Security levels found in the decrypted headers)
+    if !result.levels.is_empty() {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, level) in &result.levels {
+            *counts
+                .entry(level.map_or("Custom", |level| level.name()))
+                .or_default() += 1;
+        }
+        let summary: Vec<String> = counts
+            .iter()
+            .map(|(name, count)| format!("{name} {count} 个"))
+            .collect();
+        log::info!("   🔐 安全级别: {}", summary.join(", "));
+    }
+
+    // 有失败或跳过时按扩展名和目录分组，一眼看出问题集中在哪里 (Grouped summary)
+    let skipped_total = result.skipped_count
+        + result.skipped_locked.len()
+        + result.skipped_too_large.len()
+        + result.skipped_special.len();
+    if result.failure_count > 0 || skipped_total > 0 {
+        let breakdown = result.breakdown(root);
+        print_breakdown_table("扩展名", &breakdown.by_extension);
+        print_breakdown_table("目录", &breakdown.by_directory);
+    }
+
+    // 文件太多时只有 --verbose 才逐个列出 (Long per-file lists require --verbose)
+    let list_paths = |paths: &[PathBuf]| {
+        if verbose || paths.len() <= BATCH_DETAIL_LIMIT {
+            for path in paths {
+                log::warn!("   📁 {}", path.display());
+            }
+        } else {
+            log::info!("   (共 {} 个，使用 --verbose 列出全部)", paths.len());
+        }
+    };
+
+    // 显示因正被其他进程打开而跳过的文件 (Show files skipped because they were open)
+    if !result.skipped_locked.is_empty() {
+        log::warn!(
+            "⏭️  跳过了 {} 个正被其他进程打开的文件:",
+            result.skipped_locked.len()
+        );
+        list_paths(&result.skipped_locked);
+    }
+
+    // 显示因超过大小上限而跳过的文件 (Show files skipped for exceeding the size limit)
+    if !result.skipped_too_large.is_empty() {
+        log::warn!(
+            "⏭️  跳过了 {} 个超过大小上限的文件:",
+            result.skipped_too_large.len()
+        );
+        list_paths(&result.skipped_too_large);
+    }
+
+    // 显示因不是普通文件而跳过的文件 (Show skipped FIFOs, device nodes and sockets)
+    if !result.skipped_special.is_empty() {
+        log::warn!(
+            "⏭️  跳过了 {} 个不是普通文件的路径 (命名管道、设备或套接字):",
+            result.skipped_special.len()
+        );
+        list_paths(&result.skipped_special);
+    }
+
+    // 文件头含有本版本不认识的扩展的文件 (Files carrying extensions from a newer version)
+    if !result.ignored_extensions.is_empty() {
+        log::warn!(
+            "🧩 {} 个文件含有本版本不认识的文件头扩展，已忽略 (可能由更新版本加密，建议升级):",
+            result.ignored_extensions.len()
+        );
+        let paths: Vec<PathBuf> = result
+            .ignored_extensions
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect();
+        list_paths(&paths);
+    }
+
+    // 凭据不匹配而提前中止时列出未处理的文件 (Show files left untouched after an early abort)
+    if result.aborted_early {
+        log::warn!(
+            "🛑 凭据似乎不正确，批量解密已提前中止，{} 个文件未处理 (使用 --keep-going 继续尝试):",
+            result.unprocessed.len()
+        );
+        list_paths(&result.unprocessed);
+    }
+
+    // 正被其他程序占用的文件单独列出，不混在其他失败中 (Group files locked by other applications)
+    let locked: Vec<PathBuf> = result.all_locked_failures().into_iter().cloned().collect();
+    if !locked.is_empty() {
+        log::warn!(
+            "🔒 {} 个文件正被其他程序占用，无法读取 (关闭占用它们的程序后重试，或使用 --retry-locked):",
+            locked.len()
+        );
+        list_paths(&locked);
     }
 
     // 显示失败文件的详细信息 (Show detailed information for failed files)
-    if result.failure_count > 0 {
-        log::warn!("\n💥 失败文件详情:");
-        for (path, error) in &result.failures {
-            log::warn!("   📁 {}", path.display());
-            log::warn!("   🔍 错误: {error}");
+    let other_failures: Vec<_> = result
+        .failures
+        .iter()
+        .filter(|(_, error)| !error.is_file_locked())
+        .collect();
+    if !other_failures.is_empty() {
+        if verbose || other_failures.len() <= BATCH_DETAIL_LIMIT {
+            log::warn!("\n💥 失败文件详情:");
+            for (path, error) in &other_failures {
+                log::warn!("   📁 {}", path.display());
+                log::warn!("   🔍 错误: {error}");
+                if let Some(stage) = result.failure_stage(path) {
+                    log::warn!("   🧭 阶段: {stage}");
+                }
 
-            // 提供针对性的解决建议 (Provide targeted solution suggestions)
-            let suggestion = get_error_suggestion(error);
-            if !suggestion.is_empty() {
-                log::info!("   💡 建议: {suggestion}");
+                // 提供针对性的解决建议 (Provide targeted solution suggestions)
+                let suggestion = get_error_suggestion(error);
+                if !suggestion.is_empty() {
+                    log::info!("   💡 建议: {suggestion}");
+                }
+                log::warn!(""); // 空行分隔 (Empty line separator)
             }
-            log::warn!(""); // 空行分隔 (Empty line separator)
+        } else {
+            log::warn!(
+                "\n💥 {} 个文件失败，使用 --verbose 查看每个文件的错误详情",
+                other_failures.len()
+            );
         }
 
         // 提供通用的故障排除建议 (Provide general troubleshooting suggestions)
@@ -366,11 +2331,311 @@ fn print_batch_result(result: &ferox_encryptor::BatchResult, operation_name: &st
     }
 }
 
+/// 打印目录中加密文件的清单，每个文件一行。
+fn print_encrypted_file_list(directory: &Path, entries: &[EncryptedFileInfo]) {
+    println!("{} ({} 个加密文件)", directory.display(), entries.len());
+    for entry in entries {
+        match (&entry.error, &entry.original_filename) {
+            (None, Some(original_filename)) => println!(
+                "  {}  v{}  {}{}  {} 字节  原始文件名: {original_filename}",
+                entry.path.display(),
+                entry.format_version.unwrap_or_default(),
+                entry.level.as_deref().unwrap_or("Custom"),
+                if entry.has_keyfile_commitment == Some(true) {
+                    " + 密钥文件"
+                } else {
+                    ""
+                },
+                entry.file_size.unwrap_or_default(),
+            ),
+            (error, _) => println!(
+                "  {}  ❌ 无法解析文件头: {}",
+                entry.path.display(),
+                error.as_deref().unwrap_or("未知错误")
+            ),
+        }
+    }
+}
+
+/// 打印批量加密计划：统计、整体警告，以及每个文件的目标路径和预检警告。
+///
+/// 文件太多时只有 `--verbose` 才列出没有警告的文件。
+fn print_batch_plan(plan: &BatchPlan, verbose: bool) {
+    const MIB: f64 = 1_048_576.0;
+    log::info!(
+        "📋 批量加密计划: {} 个文件将被加密 ({:.2} MB，加密后约 {:.2} MB)，{} 个文件将被跳过",
+        plan.encrypt_count(),
+        plan.total_bytes() as f64 / MIB,
+        plan.estimated_output_bytes() as f64 / MIB,
+        plan.skip_count()
+    );
+    for warning in &plan.warnings {
+        log::warn!("   ⚠️  {warning}");
+    }
+    let list_all = verbose || plan.items.len() <= BATCH_DETAIL_LIMIT;
+    for item in &plan.items {
+        if !list_all && item.warnings.is_empty() {
+            continue;
+        }
+        let marker = if item.action == PlannedAction::Encrypt {
+            "🔒"
+        } else {
+            "⏭️ "
+        };
+        log::info!(
+            "   {marker} {} -> {} ({} 字节)",
+            item.source.display(),
+            item.target.display(),
+            item.size
+        );
+        for warning in &item.warnings {
+            log::warn!("      ⚠️  {warning}");
+        }
+    }
+    if !list_all {
+        log::info!(
+            "   (共 {} 个文件，使用 --verbose 列出全部)",
+            plan.items.len()
+        );
+    }
+}
+
+/// 打印一张分组统计表，失败最多的组排在前面，最多显示 [`BATCH_DETAIL_LIMIT`] 组。
+fn print_breakdown_table(
+    group_name: &str,
+    groups: &BTreeMap<String, ferox_encryptor::GroupCounts>,
+) {
+    let mut rows: Vec<_> = groups
+        .iter()
+        .map(|(name, counts)| {
+            (
+                if name.is_empty() {
+                    "(无)"
+                } else {
+                    name.as_str()
+                },
+                counts,
+            )
+        })
+        .collect();
+    rows.sort_by_key(|(_, counts)| {
+        (
+            std::cmp::Reverse(counts.failed),
+            std::cmp::Reverse(counts.skipped),
+        )
+    });
+    // 按显示宽度对齐，中文字符占两列
+    let width = rows
+        .iter()
+        .map(|(name, _)| console::measure_text_width(name))
+        .chain([console::measure_text_width(group_name)])
+        .max()
+        .unwrap_or(0);
+    let pad =
+        |text: &str| console::pad_str(text, width, console::Alignment::Left, None).into_owned();
+
+    log::info!("📋 按{group_name}分组:");
+    log::info!(
+        "   {}  {:>6}  {:>6}  {:>6}",
+        pad(group_name),
+        "成功",
+        "失败",
+        "跳过"
+    );
+    for (name, counts) in rows.iter().take(BATCH_DETAIL_LIMIT) {
+        log::info!(
+            "   {}  {:>8}  {:>8}  {:>8}",
+            pad(name),
+            counts.succeeded,
+            counts.failed,
+            counts.skipped
+        );
+    }
+    if rows.len() > BATCH_DETAIL_LIMIT {
+        log::info!("   ... 其余 {} 组", rows.len() - BATCH_DETAIL_LIMIT);
+    }
+}
+
+/// 按格式版本和算法标识分组打印兼容性检查结果。
+fn print_compatibility_report(entries: &[CompatibilityEntry]) {
+    if entries.is_empty() {
+        println!("没有找到加密文件。");
+        return;
+    }
+
+    let mut groups: BTreeMap<_, usize> = BTreeMap::new();
+    for entry in entries {
+        *groups
+            .entry((entry.status, entry.version, entry.cipher_id, entry.kdf_id))
+            .or_default() += 1;
+    }
+
+    let id = |value: Option<u8>| value.map_or_else(|| "?".to_string(), |v| v.to_string());
+    for ((status, version, cipher_id, kdf_id), files) in &groups {
+        let label = match status {
+            Compatibility::Supported => "✅ 可解密 (supported)",
+            Compatibility::Legacy => "⚠️  遗留格式，建议重新加密 (legacy)",
+            Compatibility::NeedsUpgrade => "⛔ 需要升级程序 (needs upgrade)",
+            Compatibility::Unreadable => "❌ 无法读取 (unreadable)",
+        };
+        println!(
+            "Version {}, cipher {}, KDF {}: {} files — {label}",
+            id(*version),
+            id(*cipher_id),
+            id(*kdf_id),
+            format_count(*files)
+        );
+    }
+
+    for entry in entries
+        .iter()
+        .filter(|entry| !entry.status.is_decryptable())
+    {
+        println!(
+            "  {} ({})",
+            entry.path.display(),
+            entry.reason.as_deref().unwrap_or("未知原因")
+        );
+    }
+}
+
+/// 打印迁移报告：建议重新加密的文件及原因、验证失败的文件和预计耗时。
+fn print_migration_report(report: &MigrationReport, verbose: bool) {
+    if report.entries.is_empty() {
+        println!("没有找到加密文件。");
+        return;
+    }
+
+    let legacy: Vec<_> = report
+        .entries
+        .iter()
+        .filter(|entry| entry.verified.is_some())
+        .collect();
+    let verified = legacy
+        .iter()
+        .filter(|entry| entry.verified == Some(true))
+        .count();
+    println!(
+        "共 {} 个加密文件，其中 {} 个遗留格式文件验证通过 {} 个",
+        format_count(report.entries.len()),
+        format_count(legacy.len()),
+        format_count(verified)
+    );
+
+    let recommended: Vec<_> = report.recommended().collect();
+    println!(
+        "建议以 {} 级别{}重新加密 {} 个文件，预计约 {}:",
+        report.target_level,
+        if report.target_keyfile {
+            "并使用密钥文件"
+        } else {
+            ""
+        },
+        format_count(recommended.len()),
+        format_duration(report.estimated_duration())
+    );
+    let shown = if verbose {
+        recommended.len()
+    } else {
+        BATCH_DETAIL_LIMIT
+    };
+    for entry in recommended.iter().take(shown) {
+        let reasons: Vec<_> = entry
+            .reasons
+            .iter()
+            .map(|reason| reason.description())
+            .collect();
+        println!(
+            "  {} [{}] ({})",
+            entry.path.display(),
+            entry.level.as_deref().unwrap_or("Custom"),
+            reasons.join("；")
+        );
+    }
+    if recommended.len() > shown {
+        println!(
+            "  (共 {} 个文件，使用 --verbose 列出全部)",
+            recommended.len()
+        );
+    }
+
+    for entry in report.failures() {
+        println!(
+            "❌ {} ({})",
+            entry.path.display(),
+            entry.error.as_deref().unwrap_or("未知原因")
+        );
+    }
+}
+
+/// 使用千位分隔符格式化数量，例如 `1203` -> `1,203`。
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// 把估计的耗时格式化为易读的文本。
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    match duration.as_secs() {
+        0 => format!("{:.0} 毫秒", secs * 1000.0),
+        1..=59 => format!("{secs:.1} 秒"),
+        60..=3599 => format!("{:.1} 分钟", secs / 60.0),
+        _ => format!("{:.1} 小时", secs / 3600.0),
+    }
+}
+
+/// 把文件的修改时长格式化为易读的文本。
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{secs} 秒前"),
+        60..=3599 => format!("{} 分钟前", secs / 60),
+        3600..=86399 => format!("{} 小时前", secs / 3600),
+        _ => format!("{} 天前", secs / 86400),
+    }
+}
+
 /// 根据错误类型提供针对性的解决建议 (Provide targeted suggestions based on error type)
 ///
 /// *Provides targeted suggestions based on error type*
-fn get_error_suggestion(error: &str) -> &'static str {
-    let error_lower = error.to_lowercase();
+fn get_error_suggestion(error: &FeroxError) -> &'static str {
+    if matches!(error, FeroxError::CredentialsExhausted { .. }) {
+        return "检查凭据表中的文件模式是否覆盖该文件，或补充该文件使用的密码和密钥文件";
+    }
+    if error.is_authentication_failure() {
+        return "确认密码正确，检查是否使用了正确的密钥文件";
+    }
+    if matches!(
+        error,
+        FeroxError::UnsupportedVersion(_) | FeroxError::MultiRecipientNotSupported { .. }
+    ) {
+        return "该文件由更新版本的 Ferox Encryptor 创建，请升级后再处理";
+    }
+    if matches!(error, FeroxError::FileExpired { .. }) {
+        return "该文件已超过加密者设置的有效期，请联系加密者重新提供文件";
+    }
+    if matches!(error, FeroxError::FileTooLarge { .. }) {
+        return "确认确实需要加密该文件后，提高或去掉 --max-size-hard-limit";
+    }
+    if error.is_file_locked() {
+        return "关闭正在使用该文件的程序 (例如 Outlook、Excel) 后重试，或使用 --retry-locked";
+    }
+    if matches!(error, FeroxError::SpecialFile { .. }) {
+        return "命名管道、设备节点和套接字无法加密，请用 --exclude 排除它们";
+    }
+    if error.is_strict_violation() {
+        return "解决上述问题后重试；确认可以忽略时去掉 --strict";
+    }
+
+    let error_lower = error.to_string().to_lowercase();
 
     if error_lower.contains("permission") || error_lower.contains("权限") {
         "尝试使用管理员权限运行，或检查文件权限设置"
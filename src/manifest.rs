@@ -0,0 +1,335 @@
+// src/manifest.rs
+
+//! # 恢复校验清单模块 (Restore Verification Manifest)
+//!
+//! 批量加密目录时，可以同时生成一个 `ferox-manifest.json` 清单，记录每个源文件
+//! （相对于目录的路径）明文的 SHA-256 哈希和大小。解密恢复之后，用清单重新计算
+//! 恢复目录中文件的哈希，报告缺失、多余和内容不一致的文件，确认恢复结果与加密时完全相同。
+//!
+//! 清单本身不加密，会暴露文件名、大小和明文哈希；需要保密时请单独加密清单。
+//!
+//! *During batch encryption a `ferox-manifest.json` can be written that maps each
+//! relative source path to its plaintext SHA-256 and size. After a restore the manifest
+//! is used to re-hash the restored directory and report missing, extra and mismatched
+//! files. The manifest itself is stored unencrypted.*
+
+use crate::{
+    batch::{batch_encrypt_directory, collect_files, BatchBreakdown, BatchConfig, BatchResult},
+    cleanup::{commit_partial_file, create_partial_file, partial_path_for},
+    constants::{BUFFER_LEN, MANIFEST_FILE_NAME, MANIFEST_FORMAT_VERSION},
+    keyfile::KeyFile,
+    pipeline::read_stream,
+    stream_io::InputFile,
+};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// 清单中一个文件的记录。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// 明文的 SHA-256 哈希（小写十六进制）。
+    pub sha256: String,
+    /// 明文的大小（字节）。
+    pub size: u64,
+}
+
+/// # 恢复校验清单 (Restore Manifest)
+///
+/// 由 [`batch_encrypt_directory_with_manifest`] 生成，由 [`verify_restore`] 使用。
+/// 路径相对于加密的目录，以 `/` 分隔。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreManifest {
+    /// 清单的格式版本。
+    pub version: u32,
+    /// 加密时是否递归处理了子目录；校验时按相同的方式扫描恢复目录。
+    pub recursive: bool,
+    /// 相对路径到文件记录的映射。
+    pub files: BTreeMap<String, ManifestEntry>,
+    /// 加密时按扩展名和顶层子目录分组的处理统计（包括失败和跳过的文件）。
+    ///
+    /// 只用于查看，校验时不使用；旧版本生成的清单中没有该字段。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<BatchBreakdown>,
+    /// 加密时因与另一个文件互为硬链接而没有单独加密的文件，映射到保存了这份内容的文件
+    /// （见 [`BatchConfig::dedupe_hardlinks`]）。
+    ///
+    /// 这些文件同样出现在 `files` 中，记录与被链接的文件相同；旧版本生成的清单中没有该字段。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hardlinks: BTreeMap<String, String>,
+}
+
+impl RestoreManifest {
+    /// 为 `result` 中加密成功（或加密结果已是最新）的文件生成清单，路径相对于 `directory`。
+    ///
+    /// `result` 必须记录了成功处理的文件路径（见 [`BatchConfig::track_successful_paths`]）。
+    /// 按 `config` 的 `threads` 并行计算哈希，数据块大小为 `config.buffer_size`。
+    pub fn from_batch_result(
+        directory: &Path,
+        result: &BatchResult,
+        config: &BatchConfig,
+    ) -> Result<Self> {
+        let mut keyed = Vec::new();
+        // 加密结果已是最新而跳过的文件同样在加密目录中，重复运行时清单仍然完整
+        let succeeded = result
+            .processed
+            .iter()
+            .filter(|(_, succeeded)| *succeeded)
+            .map(|(source, _)| source)
+            .chain(&result.skipped_up_to_date);
+        for source in succeeded {
+            match relative_key(directory, source) {
+                Some(key) => keyed.push((key, source.clone())),
+                None => log::warn!("路径无法记录到清单中，已跳过: {}", source.display()),
+            }
+        }
+        let paths: Vec<PathBuf> = keyed.iter().map(|(_, path)| path.clone()).collect();
+        let hashes = hash_files(&paths, config.threads, config.buffer_size);
+
+        let mut files = BTreeMap::new();
+        for ((key, _), entry) in keyed.into_iter().zip(hashes) {
+            files.insert(key, entry?);
+        }
+        let mut hardlinks = BTreeMap::new();
+        for (duplicate, original) in &result.skipped_hardlinks {
+            let (Some(duplicate), Some(original)) = (
+                relative_key(directory, duplicate),
+                relative_key(directory, original),
+            ) else {
+                log::warn!("硬链接无法记录到清单中，已跳过: {}", duplicate.display());
+                continue;
+            };
+            if let Some(entry) = files.get(&original).cloned() {
+                files.insert(duplicate.clone(), entry);
+                hardlinks.insert(duplicate, original);
+            }
+        }
+        Ok(Self {
+            version: MANIFEST_FORMAT_VERSION,
+            recursive: config.recursive,
+            files,
+            breakdown: Some(result.breakdown(Some(directory))),
+            hardlinks,
+        })
+    }
+
+    /// 从磁盘加载清单。
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("无法读取清单文件: {}", path.display()))?;
+        let manifest: Self = serde_json::from_str(&json)
+            .with_context(|| format!("无效的清单文件: {}", path.display()))?;
+        if manifest.version != MANIFEST_FORMAT_VERSION {
+            bail!("不支持的清单格式版本 {}", manifest.version);
+        }
+        Ok(manifest)
+    }
+
+    /// 先写入带锁的 `.part` 文件，再原子地替换 `path`。
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let part_path = partial_path_for(path);
+        let mut file = create_partial_file(&part_path)?;
+        let result = file
+            .write_all(json.as_bytes())
+            .context("写入清单文件失败")
+            .and_then(|()| file.sync_all().context("刷新清单文件失败"))
+            .and_then(|()| commit_partial_file(&part_path, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&part_path);
+        }
+        result
+    }
+
+    /// 清单中的文件数量。
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// 清单是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// # 恢复校验报告 (Restore Report)
+///
+/// [`verify_restore`] 的结果，各列表中的路径都是清单中的相对路径。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// 内容与清单一致的文件数量。
+    pub verified: usize,
+    /// 清单中有、恢复目录中没有的文件。
+    pub missing: Vec<String>,
+    /// 恢复目录中有、清单中没有的文件。
+    pub extra: Vec<String>,
+    /// 大小或哈希与清单不一致的文件。
+    pub mismatched: Vec<String>,
+}
+
+impl RestoreReport {
+    /// 恢复结果是否与清单完全一致。
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// 按 `config` 批量加密目录，并在目录中生成恢复校验清单 `ferox-manifest.json`。
+///
+/// 返回批量处理结果和生成的清单；清单只包含加密成功的文件。
+/// 清单文件已存在且未设置 `force_overwrite` 时，在加密任何文件之前返回错误。
+pub fn batch_encrypt_directory_with_manifest(
+    directory: &Path,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    config: &BatchConfig,
+) -> Result<(BatchResult, RestoreManifest)> {
+    let manifest_path = directory.join(MANIFEST_FILE_NAME);
+    if !config.force_overwrite && manifest_path.exists() {
+        bail!(
+            "清单文件 {} 已存在。如需覆盖，请使用 --force 标志。",
+            manifest_path.display()
+        );
+    }
+
+    let config = BatchConfig {
+        track_successful_paths: true,
+        ..config.clone()
+    };
+    let result = batch_encrypt_directory(directory, password, keyfile, &config)?;
+    let manifest = RestoreManifest::from_batch_result(directory, &result, &config)?;
+    manifest.save(&manifest_path)?;
+
+    log::info!(
+        "已生成包含 {} 个文件的恢复校验清单: {}",
+        manifest.len(),
+        manifest_path.display()
+    );
+    Ok((result, manifest))
+}
+
+/// 用清单检查恢复目录：重新计算文件的哈希，报告缺失、多余和内容不一致的文件。
+///
+/// 最多使用 `threads` 个线程并行计算哈希。恢复目录按加密时的方式扫描（是否递归由清单决定），
+/// 与批量加密一样跳过 `.feroxcrypt` 加密文件、索引文件和清单文件本身，
+/// 因此可以直接检查原地解密的目录。
+///
+/// # 错误
+///
+/// 清单无法读取或解析、恢复目录不存在，或文件读取失败时返回错误。
+pub fn verify_restore(
+    manifest_path: &Path,
+    restored_dir: &Path,
+    threads: usize,
+) -> Result<RestoreReport> {
+    let manifest = RestoreManifest::load(manifest_path)?;
+    let config = BatchConfig {
+        recursive: manifest.recursive,
+        ..Default::default()
+    };
+    let mut present = BTreeMap::new();
+    for path in collect_files(restored_dir, &config, false)? {
+        match relative_key(restored_dir, &path) {
+            Some(key) => {
+                present.insert(key, path);
+            }
+            None => log::warn!("路径无法与清单比较，已跳过: {}", path.display()),
+        }
+    }
+
+    let mut report = RestoreReport {
+        extra: present
+            .keys()
+            .filter(|key| !manifest.files.contains_key(*key))
+            .cloned()
+            .collect(),
+        ..Default::default()
+    };
+    let mut expected = Vec::new();
+    let mut paths = Vec::new();
+    for (key, entry) in &manifest.files {
+        match present.get(key) {
+            Some(path) => {
+                expected.push((key, entry));
+                paths.push(path.clone());
+            }
+            None => report.missing.push(key.clone()),
+        }
+    }
+
+    for ((key, entry), actual) in expected
+        .into_iter()
+        .zip(hash_files(&paths, threads, BUFFER_LEN))
+    {
+        if actual? == *entry {
+            report.verified += 1;
+        } else {
+            report.mismatched.push(key.clone());
+        }
+    }
+    Ok(report)
+}
+
+/// 清单中使用的相对路径：`path` 相对于 `directory`，以 `/` 分隔；路径不是有效的 UTF-8 时返回 `None`。
+fn relative_key(directory: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(directory).ok()?;
+    let parts = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(parts.join("/"))
+}
+
+/// 使用最多 `threads` 个线程计算每个文件的哈希，结果与 `paths` 的顺序一致。
+fn hash_files(paths: &[PathBuf], threads: usize, buffer_size: usize) -> Vec<Result<ManifestEntry>> {
+    let next = AtomicUsize::new(0);
+    let workers = threads.clamp(1, paths.len().max(1));
+
+    let worker = || {
+        let mut hashed = Vec::new();
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let Some(path) = paths.get(index) else {
+                return hashed;
+            };
+            hashed.push((index, hash_file(path, buffer_size)));
+        }
+    };
+
+    let mut hashed: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+    hashed.sort_unstable_by_key(|(index, _)| *index);
+    hashed.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// 按数据块读取文件并计算明文的 SHA-256 哈希和大小。
+fn hash_file(path: &Path, buffer_size: usize) -> Result<ManifestEntry> {
+    let file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+    let len = file.metadata()?.len();
+    let mut reader = InputFile::open(file, len, buffer_size);
+    let mut hasher = Sha256::new();
+    let size = read_stream(&mut reader, buffer_size, |data| hasher.update(data))
+        .with_context(|| format!("读取文件失败: {}", path.display()))?;
+
+    let mut sha256 = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        let _ = write!(sha256, "{byte:02x}");
+    }
+    Ok(ManifestEntry { sha256, size })
+}
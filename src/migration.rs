@@ -0,0 +1,301 @@
+// src/migration.rs
+
+//! # 迁移报告模块 (Migration Report)
+//!
+//! 在把旧文件迁移到当前格式之前，先只读地确认它们的状况：扫描目录中的加密文件，
+//! 用密码（和可选的密钥文件）完整验证每个遗留格式（没有版本字节）的文件仍能通过认证，
+//! 并按以下原因列出建议重新加密的文件：
+//!
+//! - 遗留格式：文件头不受认证保护；
+//! - 旧格式版本：早于当前格式版本，缺少新版本的文件头字段；
+//! - 安全级别较弱：Argon2 成本低于迁移的目标级别；
+//! - 没有使用密钥文件：只在迁移目标使用密钥文件时检查。
+//!
+//! 报告同时按当前机器的校准结果估计迁移（解密后以目标级别重新加密）所需的时间，
+//! 建议迁移的文件可以保存为 [`MigrationPlan`] 文件，交给后续的重新加密步骤。
+//! 整个过程不写入任何明文，也不修改被扫描的文件。
+//!
+//! *A read-only pre-migration check: legacy files are fully verified with the password, and
+//! every file is classified by why it should be re-encrypted (legacy or outdated format, weak
+//! level, no keyfile) with an estimated migration time at the chosen target level. The
+//! recommendations can be saved as a plan file.*
+
+use crate::{
+    constants::{CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION},
+    inspect::{benchmark_level, scan_encrypted, verify_file, EncryptedFileInfo, LevelBenchmark},
+    keyfile::KeyFile,
+    Level,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 建议重新加密一个文件的原因。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationReason {
+    /// 遗留格式（版本 0），文件头不受认证保护。
+    LegacyFormat,
+    /// 早于当前格式版本的版本化格式。
+    OutdatedFormat,
+    /// Argon2 成本低于迁移的目标级别。
+    WeakLevel,
+    /// 没有使用密钥文件，而迁移目标使用密钥文件。
+    NoKeyfile,
+}
+
+impl MigrationReason {
+    /// 在报告中显示的说明。
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::LegacyFormat => "遗留格式，文件头不受认证保护",
+            Self::OutdatedFormat => "旧格式版本",
+            Self::WeakLevel => "安全级别低于目标级别",
+            Self::NoKeyfile => "没有使用密钥文件",
+        }
+    }
+}
+
+/// # 迁移报告条目 (Migration Entry)
+///
+/// [`migration_report`] 为每个加密文件生成的条目。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MigrationEntry {
+    /// 加密文件的路径。
+    pub path: PathBuf,
+    /// 文件格式版本，遗留格式为 0；无法读取时为 `None`。
+    pub format_version: Option<u8>,
+    /// Argon2 参数 `(m_cost KiB, t_cost, p_cost)`。
+    pub argon2_params: Option<(u32, u32, u32)>,
+    /// 安全级别的名称，参数不属于任何预设级别时为 `"Custom"`。
+    pub level: Option<String>,
+    /// 加密时是否使用了密钥文件。文件头记录了密钥文件承诺时为 `Some(true)`，
+    /// 遗留格式的文件在验证通过后才能确定；其他文件无法确定，为 `None`。
+    pub uses_keyfile: Option<bool>,
+    /// 加密文件的总字节数。
+    pub file_size: u64,
+    /// 遗留格式文件的验证结果；其他文件不验证，为 `None`。
+    pub verified: Option<bool>,
+    /// 建议重新加密的原因，为空表示无需迁移。
+    pub reasons: Vec<MigrationReason>,
+    /// 以目标级别迁移该文件的预计耗时（秒），无需迁移时为 0。
+    pub estimated_secs: f64,
+    /// 文件头无法解析或验证失败的原因。
+    pub error: Option<String>,
+}
+
+impl MigrationEntry {
+    /// 是否建议重新加密并且可以用给定的凭据迁移（验证没有失败、文件头可以读取）。
+    pub fn is_migratable(&self) -> bool {
+        !self.reasons.is_empty() && self.error.is_none()
+    }
+}
+
+/// # 迁移报告 (Migration Report)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MigrationReport {
+    /// 迁移的目标安全级别的名称。
+    pub target_level: String,
+    /// 迁移目标是否使用密钥文件。
+    pub target_keyfile: bool,
+    /// 每个加密文件的条目，按路径排序。
+    pub entries: Vec<MigrationEntry>,
+}
+
+impl MigrationReport {
+    /// 建议重新加密并且可以迁移的文件。
+    pub fn recommended(&self) -> impl Iterator<Item = &MigrationEntry> {
+        self.entries.iter().filter(|entry| entry.is_migratable())
+    }
+
+    /// 验证失败或文件头无法读取的文件。
+    pub fn failures(&self) -> impl Iterator<Item = &MigrationEntry> {
+        self.entries.iter().filter(|entry| entry.error.is_some())
+    }
+
+    /// 迁移所有建议的文件的预计总耗时。
+    pub fn estimated_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.recommended().map(|entry| entry.estimated_secs).sum())
+    }
+
+    /// 由建议迁移的文件生成迁移计划。
+    pub fn plan(&self) -> MigrationPlan {
+        MigrationPlan {
+            target_level: self.target_level.clone(),
+            keyfile: self.target_keyfile,
+            files: self
+                .recommended()
+                .map(|entry| MigrationPlanItem {
+                    path: entry.path.clone(),
+                    reasons: entry.reasons.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 迁移计划中的一个文件。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationPlanItem {
+    /// 要重新加密的文件。
+    pub path: PathBuf,
+    /// 建议重新加密的原因。
+    pub reasons: Vec<MigrationReason>,
+}
+
+/// # 迁移计划 (Migration Plan)
+///
+/// [`MigrationReport::plan`] 生成、以 JSON 保存的待重新加密文件列表和目标参数。
+/// 计划中只有文件路径和原因，不包含密码或密钥文件内容。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationPlan {
+    /// 目标安全级别的名称。
+    pub target_level: String,
+    /// 重新加密时是否使用密钥文件。
+    pub keyfile: bool,
+    /// 要重新加密的文件。
+    pub files: Vec<MigrationPlanItem>,
+}
+
+impl MigrationPlan {
+    /// 目标安全级别。
+    ///
+    /// # 错误
+    ///
+    /// 计划中的级别名称无效时返回错误。
+    pub fn target_level(&self) -> Result<Level> {
+        self.target_level
+            .parse()
+            .map_err(|_| anyhow!("迁移计划中的安全级别无效: {}", self.target_level))
+    }
+
+    /// 把计划保存为 JSON 文件。
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("无法写入迁移计划: {}", path.display()))
+    }
+
+    /// 读取 [`save`](Self::save) 保存的计划。
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("无法读取迁移计划: {}", path.display()))?;
+        let plan: Self = serde_json::from_str(&json)
+            .with_context(|| format!("无效的迁移计划: {}", path.display()))?;
+        plan.target_level()?;
+        Ok(plan)
+    }
+}
+
+/// 扫描 `directory` 中的加密文件，生成迁移报告，见[模块文档](self)。
+///
+/// 遗留格式的文件先只用密码验证，失败且给出了 `keyfile` 时再加上密钥文件验证；
+/// 其他文件只读取文件头。`keyfile` 同时表示迁移目标使用密钥文件。
+/// 有建议迁移的文件时会在当前机器上校准一次（与 [`benchmark_level`] 相同）来估计耗时。
+///
+/// # 错误
+///
+/// 目录无法扫描或校准失败时返回错误；单个文件的问题记录在条目的 `error` 中。
+pub fn migration_report(
+    directory: &Path,
+    recursive: bool,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    target_level: Level,
+) -> Result<MigrationReport> {
+    let mut files = scan_encrypted(directory, recursive)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut entries: Vec<MigrationEntry> = files
+        .into_iter()
+        .map(|info| assess_file(info, password, keyfile, target_level))
+        .collect();
+
+    if entries.iter().any(MigrationEntry::is_migratable) {
+        let calibration = benchmark_level(Level::Interactive)?;
+        let (target_m_cost, target_t_cost, _) = target_level.argon2_params();
+        let encryption = key_derivation_secs(&calibration, target_m_cost, target_t_cost);
+        for entry in entries.iter_mut().filter(|entry| entry.is_migratable()) {
+            let (m_cost, t_cost, _) = entry.argon2_params.unwrap_or_default();
+            // 解密和重新加密各读写一遍数据
+            let body = 2.0 * entry.file_size as f64 / calibration.throughput;
+            entry.estimated_secs =
+                key_derivation_secs(&calibration, m_cost, t_cost) + encryption + body;
+        }
+    }
+
+    Ok(MigrationReport {
+        target_level: target_level.name().to_string(),
+        target_keyfile: keyfile.is_some(),
+        entries,
+    })
+}
+
+/// 检查单个文件，必要时验证遗留格式的文件。
+fn assess_file(
+    info: EncryptedFileInfo,
+    password: &str,
+    keyfile: Option<&KeyFile>,
+    target_level: Level,
+) -> MigrationEntry {
+    let mut entry = MigrationEntry {
+        path: info.path,
+        format_version: info.format_version,
+        argon2_params: info.argon2_params,
+        level: info.level,
+        // 密钥文件承诺是可选的，没有承诺不代表没有使用密钥文件
+        uses_keyfile: info.has_keyfile_commitment.filter(|&committed| committed),
+        file_size: info.file_size.unwrap_or(0),
+        verified: None,
+        reasons: Vec::new(),
+        estimated_secs: 0.0,
+        error: info.error,
+    };
+    let (Some(version), Some((m_cost, t_cost, _)), None) =
+        (entry.format_version, entry.argon2_params, &entry.error)
+    else {
+        return entry;
+    };
+
+    if version == LEGACY_FORMAT_VERSION {
+        entry.reasons.push(MigrationReason::LegacyFormat);
+        // 遗留格式的文件头没有密钥文件承诺，验证通过时才知道是否使用了密钥文件
+        entry.uses_keyfile = None;
+        let mut outcome = verify_file(&entry.path, password, None).map(|()| false);
+        if let (Err(_), Some(keyfile)) = (&outcome, keyfile) {
+            outcome = verify_file(&entry.path, password, Some(keyfile)).map(|()| true);
+        }
+        match outcome {
+            Ok(uses_keyfile) => {
+                entry.verified = Some(true);
+                entry.uses_keyfile = Some(uses_keyfile);
+            }
+            Err(e) => {
+                log::warn!("❌ 遗留格式文件验证失败 {}: {e:#}", entry.path.display());
+                entry.verified = Some(false);
+                entry.error = Some(format!("{e:#}"));
+            }
+        }
+    } else if version < CURRENT_FORMAT_VERSION {
+        entry.reasons.push(MigrationReason::OutdatedFormat);
+    }
+
+    let (target_m_cost, target_t_cost, _) = target_level.argon2_params();
+    if u64::from(m_cost) * u64::from(t_cost) < u64::from(target_m_cost) * u64::from(target_t_cost) {
+        entry.reasons.push(MigrationReason::WeakLevel);
+    }
+    if keyfile.is_some() && entry.uses_keyfile == Some(false) {
+        entry.reasons.push(MigrationReason::NoKeyfile);
+    }
+    entry
+}
+
+/// 按 `m_cost × t_cost` 相对于 [`Level::Interactive`] 的比例推算密钥派生耗时（秒）。
+fn key_derivation_secs(calibration: &LevelBenchmark, m_cost: u32, t_cost: u32) -> f64 {
+    let (base_m_cost, base_t_cost, _) = Level::Interactive.argon2_params();
+    let scale =
+        (f64::from(m_cost) * f64::from(t_cost)) / (f64::from(base_m_cost) * f64::from(base_t_cost));
+    calibration.key_derivation.as_secs_f64() * scale
+}
@@ -0,0 +1,186 @@
+// src/password.rs
+
+//! # 密码来源模块 (Password Sources)
+//!
+//! 加解密流程只接收密码字符串；密码从哪里来由调用者通过 [`PasswordSource`] 决定：
+//! 直接给出，或者按名称从某个 [`PasswordStore`] 中读取。
+//!
+//! 启用 `keyring` 特性后，[`OsKeychain`] 把密码保存在操作系统的密钥链中
+//! (macOS 钥匙串、Windows 凭据管理器、Linux 内核密钥环)。Linux 内核密钥环中的条目
+//! 在重新启动后不再保留。
+//!
+//! *Callers choose where the password comes from through [`PasswordSource`]. With the
+//! `keyring` feature, [`OsKeychain`] stores passwords in the operating system keychain.*
+
+use anyhow::{bail, Result};
+use zeroize::Zeroizing;
+
+/// # 密码存储 (Password Store)
+///
+/// 按名称保存、读取和删除密码的后端。
+pub trait PasswordStore {
+    /// 读取名为 `name` 的密码；不存在时返回 `Ok(None)`。
+    fn get(&self, name: &str) -> Result<Option<Zeroizing<String>>>;
+
+    /// 保存名为 `name` 的密码，已存在时覆盖。
+    fn set(&self, name: &str, password: &str) -> Result<()>;
+
+    /// 删除名为 `name` 的密码，返回它之前是否存在。
+    fn delete(&self, name: &str) -> Result<bool>;
+}
+
+/// # 密码来源 (Password Source)
+///
+/// 调用者提供密码的方式。
+pub enum PasswordSource<'a> {
+    /// 直接给出的密码。
+    Literal(&'a str),
+    /// 按名称从密码存储中读取的密码。
+    Stored {
+        /// 密码存储后端。
+        store: &'a dyn PasswordStore,
+        /// 条目名称。
+        name: &'a str,
+    },
+}
+
+impl PasswordSource<'_> {
+    /// 取得密码。
+    ///
+    /// # 错误
+    ///
+    /// 密码存储中没有该名称的条目，或读取密码存储失败时返回错误。
+    pub fn resolve(&self) -> Result<Zeroizing<String>> {
+        match self {
+            Self::Literal(password) => Ok(Zeroizing::new(password.to_string())),
+            Self::Stored { store, name } => match store.get(name)? {
+                Some(password) => Ok(password),
+                None => bail!("没有名为 “{name}” 的已保存密码"),
+            },
+        }
+    }
+}
+
+/// 从密码存储中删除名为 `name` 的密码。
+///
+/// # 错误
+///
+/// 条目不存在或删除失败时返回错误。
+pub fn forget_password(store: &dyn PasswordStore, name: &str) -> Result<()> {
+    if !store.delete(name)? {
+        bail!("没有名为 “{name}” 的已保存密码");
+    }
+    Ok(())
+}
+
+/// 在操作系统密钥链中保存条目时使用的服务名。
+#[cfg(feature = "keyring")]
+const KEYCHAIN_SERVICE: &str = "ferox_encryptor";
+
+/// # 操作系统密钥链 (OS Keychain)
+///
+/// 使用操作系统密钥链的 [`PasswordStore`]，条目以 `ferox_encryptor` 服务名保存。
+#[cfg(feature = "keyring")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsKeychain;
+
+#[cfg(feature = "keyring")]
+impl OsKeychain {
+    fn entry(name: &str) -> Result<keyring::Entry> {
+        use anyhow::Context;
+
+        keyring::Entry::new(KEYCHAIN_SERVICE, name)
+            .with_context(|| format!("无法访问密钥链条目 “{name}”"))
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl PasswordStore for OsKeychain {
+    fn get(&self, name: &str) -> Result<Option<Zeroizing<String>>> {
+        match Self::entry(name)?.get_password() {
+            Ok(password) => Ok(Some(Zeroizing::new(password))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(anyhow::Error::from(e).context("无法从密钥链读取密码")),
+        }
+    }
+
+    fn set(&self, name: &str, password: &str) -> Result<()> {
+        use anyhow::Context;
+
+        Self::entry(name)?
+            .set_password(password)
+            .context("无法把密码保存到密钥链")
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        match Self::entry(name)?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(e) => Err(anyhow::Error::from(e).context("无法从密钥链删除密码")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    /// 保存在内存中的密码存储，代替操作系统密钥链。
+    #[derive(Default)]
+    struct MockStore(RefCell<HashMap<String, String>>);
+
+    impl PasswordStore for MockStore {
+        fn get(&self, name: &str) -> Result<Option<Zeroizing<String>>> {
+            Ok(self.0.borrow().get(name).cloned().map(Zeroizing::new))
+        }
+
+        fn set(&self, name: &str, password: &str) -> Result<()> {
+            self.0
+                .borrow_mut()
+                .insert(name.to_string(), password.to_string());
+            Ok(())
+        }
+
+        fn delete(&self, name: &str) -> Result<bool> {
+            Ok(self.0.borrow_mut().remove(name).is_some())
+        }
+    }
+
+    #[test]
+    fn test_store_retrieve_and_forget() -> Result<()> {
+        let store = MockStore::default();
+        store.set("work", "correct horse battery staple")?;
+
+        let source = PasswordSource::Stored {
+            store: &store,
+            name: "work",
+        };
+        assert_eq!(source.resolve()?.as_str(), "correct horse battery staple");
+
+        forget_password(&store, "work")?;
+        assert!(source.resolve().is_err());
+        // 再次删除同一条目是错误
+        assert!(forget_password(&store, "work").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_entry_is_an_error() {
+        let store = MockStore::default();
+        let error = PasswordSource::Stored {
+            store: &store,
+            name: "missing",
+        }
+        .resolve()
+        .unwrap_err();
+        assert!(error.to_string().contains("missing"));
+    }
+
+    #[test]
+    fn test_literal_source() -> Result<()> {
+        assert_eq!(PasswordSource::Literal("pw").resolve()?.as_str(), "pw");
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+// src/permissions.rs
+
+//! # 权限位模块 (Permission Bits)
+//!
+//! 启用 `--preserve-xattrs` 时，加密除了扩展属性之外还把源文件的权限位 (`rwxrwxrwx`) 记录在文件头中；
+//! 解密按 [`OutputPermissions`] 决定输出文件的权限。解密期间的 `.part` 文件始终只有所有者可以读写，
+//! 权限在原子重命名之后才设置，未经认证的明文不会被同组或其他用户读到。
+//!
+//! setuid、setgid 和粘滞位从不记录也不恢复。Windows 等非 Unix 平台没有对应的权限位，策略不起作用。
+//!
+//! *Unix permission bits of the source are recorded next to the extended attributes; decryption
+//! applies an [`OutputPermissions`] policy after the atomic rename. Partial outputs are always 0600.*
+
+use crate::decrypt::OutputPermissions;
+use crate::error::{warn_or_fail, FeroxError};
+use std::fs::File;
+use std::path::Path;
+
+/// 记录和恢复的权限位（不含 setuid、setgid 和粘滞位）。
+pub(crate) const PERMISSION_BITS: u32 = 0o777;
+
+/// 读取 `path` 的权限位；无法读取时发出警告并返回 `None`（`strict` 时返回错误）。
+#[cfg(unix)]
+pub(crate) fn capture(path: &Path, strict: bool) -> Result<Option<u32>, FeroxError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(Some(metadata.permissions().mode() & PERMISSION_BITS)),
+        Err(e) => {
+            warn_or_fail(strict, format!("无法读取 {} 的权限: {e}", path.display()))?;
+            Ok(None)
+        }
+    }
+}
+
+/// 没有 Unix 权限位的平台。
+#[cfg(not(unix))]
+pub(crate) fn capture(_path: &Path, _strict: bool) -> Result<Option<u32>, FeroxError> {
+    Ok(None)
+}
+
+/// 把解密期间的 `.part` 文件限制为只有所有者可以读写。
+#[cfg(unix)]
+pub(crate) fn restrict_partial(file: &File) -> std::io::Result<()> {
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+
+    file.set_permissions(Permissions::from_mode(0o600))
+}
+
+/// 没有 Unix 权限位的平台。
+#[cfg(not(unix))]
+pub(crate) fn restrict_partial(_file: &File) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// 按 `policy` 设置已提交的输出文件 `path` 的权限，返回实际设置的权限位。
+///
+/// `recorded` 是文件头中记录的权限位；[`OutputPermissions::FromHeader`] 在没有记录时
+/// 退回到 [`OutputPermissions::ProcessUmask`]。`strict` 只影响没有权限位的平台。
+#[cfg(unix)]
+pub(crate) fn apply(
+    path: &Path,
+    policy: OutputPermissions,
+    recorded: Option<u32>,
+    _strict: bool,
+) -> std::io::Result<Option<u32>> {
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = match (policy, recorded) {
+        (OutputPermissions::FromHeader, Some(mode)) => mode,
+        (OutputPermissions::Fixed(mode), _) => mode,
+        (OutputPermissions::FromHeader, None) | (OutputPermissions::ProcessUmask, _) => {
+            0o666 & !process_umask()
+        }
+    } & PERMISSION_BITS;
+    std::fs::set_permissions(path, Permissions::from_mode(mode))?;
+    Ok(Some(mode))
+}
+
+/// 没有 Unix 权限位的平台：不设置权限；指定了固定权限时发出警告（`strict` 时返回错误）。
+#[cfg(not(unix))]
+pub(crate) fn apply(
+    _path: &Path,
+    policy: OutputPermissions,
+    _recorded: Option<u32>,
+    strict: bool,
+) -> std::io::Result<Option<u32>> {
+    if let OutputPermissions::Fixed(mode) = policy {
+        warn_or_fail(
+            strict,
+            format!("当前平台没有 Unix 权限位，--chmod {mode:o} 不起作用"),
+        )
+        .map_err(std::io::Error::other)?;
+    }
+    Ok(None)
+}
+
+/// 当前进程的 umask，只读取一次。
+///
+/// Linux 上从 `/proc/self/status` 读取；其他系统只能先设置再恢复 umask，
+/// 因此在第一次需要时读取后缓存，避免与其他线程创建文件竞争。
+#[cfg(unix)]
+fn process_umask() -> u32 {
+    static UMASK: std::sync::OnceLock<u32> = std::sync::OnceLock::new();
+    *UMASK.get_or_init(|| {
+        #[cfg(target_os = "linux")]
+        if let Some(umask) = std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| {
+                let line = status.lines().find(|line| line.starts_with("Umask:"))?;
+                u32::from_str_radix(line["Umask:".len()..].trim(), 8).ok()
+            })
+        {
+            return umask;
+        }
+        // SAFETY: umask 总是成功，立即恢复原来的值
+        let umask = unsafe {
+            let previous = libc::umask(0o022);
+            libc::umask(previous);
+            previous
+        };
+        // macOS 上 mode_t 是 u16
+        #[allow(clippy::useless_conversion)]
+        let umask = u32::from(umask);
+        umask & PERMISSION_BITS
+    })
+}
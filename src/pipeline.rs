@@ -0,0 +1,1212 @@
+// src/pipeline.rs
+
+//! # 数据块流水线模块 (Chunk Pipeline Module)
+//!
+//! 加密和解密的热循环都由两个依次作用于同一数据块的阶段组成：
+//! 加密时先 AES-CTR 再 HMAC，解密时先 HMAC 再 AES-CTR。
+//! 该模块提供两种执行方式：
+//!
+//! - **串行**: 在当前线程中对每个数据块依次执行读取、两个阶段和写入。
+//! - **流水线**: 两个阶段分别运行在独立的线程上，通过有界通道传递数据块，
+//!   使读取、加密、认证和写入可以重叠进行。读取和写入始终在调用者的线程中完成。
+//! - **并行 CTR**: CTR 模式的密钥流块相互独立，多个工作线程各自把计数器定位到
+//!   数据块的偏移量后并行加解密，再按原始顺序重组并送入唯一的 HMAC。
+//!
+//! 所有方式产生的输出逐字节相同。数据块缓冲区在处理完一个文件后留在当前线程的
+//! 缓冲区池中，供下一个文件复用；包含过明文的缓冲区在归还时清零，出错时未能归还的
+//! 数据块在释放之前整体清零。
+//!
+//! 此外，[`PipelineBuilder`] 把文件级的"压缩 → 加密 → 分割"组合为一个声明式的流水线，
+//! 数据以流的方式依次经过各个步骤。
+//!
+//! *The encryption and decryption hot loops apply two stages (cipher and MAC) to
+//! every chunk. This module runs them serially, as a pipeline with one thread per
+//! stage, or with several cipher workers that seek the CTR keystream to each
+//! chunk's offset and an ordered reassembly stage feeding the single MAC. All
+//! modes produce identical output. Chunk buffers are pooled per thread and reused
+//! by the next file; buffers that held plaintext are zeroized when released, and
+//! chunks dropped on an error path are zeroized before being freed.
+//! [`PipelineBuilder`] additionally chains file-level compress → encrypt → split steps.*
+
+use crate::constants::{
+    fixed_overhead_bytes, BUFFER_POOL_MAX_BYTES, CUSTOM_FILE_EXTENSION, EXPIRY_TIME_LEN,
+    KEYFILE_COMMITMENT_LEN, MAX_BUFFER_SIZE, MIN_BUFFER_SIZE, PARALLEL_CHUNKS_PER_THREAD,
+    PIPELINE_DEPTH, SECURITY_LEVEL_LEN,
+};
+use crate::encrypt::{
+    encrypt_stream, ensure_within_size_limit, validate_source, EncryptionOptions,
+};
+use crate::stream_io::InputFile;
+use anyhow::{anyhow, bail, Context, Result};
+use ctr::cipher::{StreamCipher, StreamCipherSeek};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use zeroize::{Zeroize, Zeroizing};
+
+/// 作用于一个数据块的处理阶段。
+type Stage<'a> = &'a mut (dyn FnMut(&mut [u8]) + Send);
+
+/// 数据块的处理方向，决定 HMAC 作用于输入还是输出。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CipherDirection {
+    /// 加密：HMAC 作用于 AES-CTR 输出的密文。
+    Encrypt,
+    /// 解密：HMAC 作用于读取到的密文，在解密之前计算。
+    Decrypt,
+}
+
+impl CipherDirection {
+    /// 处理完成的数据块是否为明文，决定缓冲区归还到池中之前是否需要清零。
+    fn output_is_plaintext(self) -> bool {
+        self == CipherDirection::Decrypt
+    }
+}
+
+/// 数据块的处理方式，由加密/解密选项决定。
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChunkSettings {
+    /// 每个数据块的最大字节数。
+    pub(crate) buffer_size: usize,
+    /// 是否在调用者的线程中串行处理。
+    pub(crate) single_thread: bool,
+    /// 并行应用 CTR 密钥流的线程数；大于 1 时启用并行 CTR 模式。
+    pub(crate) threads: usize,
+}
+
+/// 检查缓冲区大小是否在允许的范围内。
+pub(crate) fn ensure_valid_buffer_size(buffer_size: usize) -> Result<()> {
+    if !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&buffer_size) {
+        bail!(
+            "缓冲区大小 {} 字节超出允许的范围 ({} 字节 - {} 字节)",
+            buffer_size,
+            MIN_BUFFER_SIZE,
+            MAX_BUFFER_SIZE
+        );
+    }
+    Ok(())
+}
+
+thread_local! {
+    /// 当前线程中可以复用的缓冲区；其中的内容总是已经清零或只包含密文。
+    static BUFFER_POOL: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 一个数据块及其缓冲区。
+///
+/// 缓冲区始终保持分配时的完整长度，只有前 `len` 字节是有效数据，
+/// 因此在文件之间复用时既不需要重新分配，也不需要重新清零整个缓冲区。
+/// 没有通过 [`Chunk::release`] 归还就被丢弃的数据块（出错提前返回、流水线中途放弃等），
+/// 其缓冲区在释放之前整体清零。
+struct Chunk {
+    buffer: Zeroizing<Vec<u8>>,
+    /// 有效数据的字节数。
+    len: usize,
+    /// 自从取出以来写入过数据的前缀长度，归还时只需清零这一部分。
+    dirty: usize,
+}
+
+impl Chunk {
+    /// 从当前线程的缓冲区池取出一个大小为 `buffer_size` 的缓冲区，没有合适的缓冲区时才新分配。
+    fn take(buffer_size: usize) -> Self {
+        let pooled = BUFFER_POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            let index = pool.iter().position(|buffer| buffer.len() == buffer_size)?;
+            Some(pool.swap_remove(index))
+        });
+        Chunk {
+            buffer: Zeroizing::new(pooled.unwrap_or_else(|| vec![0u8; buffer_size])),
+            len: 0,
+            dirty: 0,
+        }
+    }
+
+    /// 把缓冲区归还到当前线程的缓冲区池。
+    ///
+    /// `holds_plaintext` 为 `true` 时先清零写入过数据的部分。池中的缓冲区总量超过
+    /// [`BUFFER_POOL_MAX_BYTES`] 时，最早归还的缓冲区会被释放。
+    fn release(mut self, holds_plaintext: bool) {
+        if holds_plaintext {
+            self.buffer[..self.dirty].zeroize();
+        }
+        if self.buffer.len() > BUFFER_POOL_MAX_BYTES {
+            return;
+        }
+        // 取出缓冲区后 `Zeroizing` 中只剩一个空的 `Vec`，丢弃时不会再清零整个缓冲区
+        let buffer = std::mem::take(&mut *self.buffer);
+        BUFFER_POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            let mut pooled_bytes: usize = pool.iter().map(Vec::len).sum();
+            while pooled_bytes + buffer.len() > BUFFER_POOL_MAX_BYTES {
+                pooled_bytes -= pool.remove(0).len();
+            }
+            pool.push(buffer);
+        });
+    }
+
+    /// 从 `reader` 读取下一个数据块，返回读取的字节数；为 0 表示输入已经结束。
+    fn fill<R: Read>(&mut self, reader: &mut R) -> Result<usize> {
+        self.len = reader.read(&mut self.buffer).context("读取输入数据失败")?;
+        self.dirty = self.dirty.max(self.len);
+        Ok(self.len)
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer[..self.len]
+    }
+}
+
+/// 按数据块读取 `reader` 的全部数据（不做加解密），对每个数据块调用 `on_chunk`，
+/// 返回读取的总字节数。
+///
+/// 与加解密流程共用当前线程的缓冲区池；数据块视为明文，归还时清零。
+pub(crate) fn read_stream<R: Read>(
+    reader: &mut R,
+    buffer_size: usize,
+    mut on_chunk: impl FnMut(&[u8]),
+) -> Result<u64> {
+    let mut chunk = Chunk::take(buffer_size);
+    let mut total = 0u64;
+    let result = loop {
+        match chunk.fill(reader) {
+            Ok(0) => break Ok(total),
+            Ok(n) => {
+                on_chunk(chunk.data());
+                total += n as u64;
+            }
+            Err(e) => break Err(e),
+        }
+    };
+    chunk.release(true);
+    result
+}
+
+/// 从 `reader` 读取全部数据，应用 CTR 密钥流并按顺序计算 HMAC，再写入 `writer`。
+///
+/// `cipher` 必须位于密钥流的起始位置。根据 `settings` 选择串行、流水线或并行 CTR 方式，
+/// 所有方式的输出逐字节相同。每读取一个数据块，都会以该块的字节数调用 `on_progress`。
+/// 启用 `tracing` 特性时，处理过程记录在 `process_stream` span 中，结束时记录处理的字节数。
+pub(crate) fn process_stream<R, W, C>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &C,
+    mac: &mut (dyn FnMut(&[u8]) + Send),
+    direction: CipherDirection,
+    settings: &ChunkSettings,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()>
+where
+    R: Read,
+    W: Write,
+    C: StreamCipher + StreamCipherSeek + Clone + Send + Sync,
+{
+    ensure_valid_buffer_size(settings.buffer_size)?;
+
+    #[cfg(feature = "tracing")]
+    {
+        let _span = tracing::span!(
+            tracing::Level::INFO,
+            "process_stream",
+            ?direction,
+            buffer_size = settings.buffer_size,
+            single_thread = settings.single_thread,
+            threads = settings.threads
+        )
+        .entered();
+        let mut bytes = 0u64;
+        let result = run_stream(
+            reader,
+            writer,
+            cipher,
+            mac,
+            direction,
+            settings,
+            &mut |chunk_bytes| {
+                bytes += chunk_bytes;
+                on_progress(chunk_bytes);
+            },
+        );
+        match &result {
+            Ok(()) => tracing::debug!(bytes, "数据流处理完成"),
+            Err(e) => tracing::debug!(bytes, error = %e, "数据流处理失败"),
+        }
+        result
+    }
+    #[cfg(not(feature = "tracing"))]
+    run_stream(
+        reader,
+        writer,
+        cipher,
+        mac,
+        direction,
+        settings,
+        on_progress,
+    )
+}
+
+/// [`process_stream`] 的实现：按 `settings` 选择串行、流水线或并行 CTR 方式。
+fn run_stream<R, W, C>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &C,
+    mac: &mut (dyn FnMut(&[u8]) + Send),
+    direction: CipherDirection,
+    settings: &ChunkSettings,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()>
+where
+    R: Read,
+    W: Write,
+    C: StreamCipher + StreamCipherSeek + Clone + Send + Sync,
+{
+    if settings.threads > 1 && !settings.single_thread {
+        return process_chunks_parallel(
+            reader,
+            writer,
+            cipher,
+            mac,
+            direction,
+            settings,
+            on_progress,
+        );
+    }
+
+    let mut cipher = cipher.clone();
+    let mut apply_keystream = |chunk: &mut [u8]| cipher.apply_keystream(chunk);
+    let mut authenticate = |chunk: &mut [u8]| mac(chunk);
+    // Encrypt-then-MAC：加密时先加密再认证，解密时先认证再解密
+    let (first, second): (Stage<'_>, Stage<'_>) = match direction {
+        CipherDirection::Encrypt => (&mut apply_keystream, &mut authenticate),
+        CipherDirection::Decrypt => (&mut authenticate, &mut apply_keystream),
+    };
+    process_chunks(
+        reader,
+        writer,
+        first,
+        second,
+        direction,
+        settings,
+        on_progress,
+    )
+}
+
+/// 从 `reader` 读取全部数据，对每个数据块依次执行 `first` 和 `second`，再写入 `writer`。
+///
+/// `settings.single_thread` 为 `false` 时两个阶段在独立的线程上并行执行。
+/// `direction` 只用来判断处理完成的数据块是否为明文。
+fn process_chunks<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    first: Stage<'_>,
+    second: Stage<'_>,
+    direction: CipherDirection,
+    settings: &ChunkSettings,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    let holds_plaintext = direction.output_is_plaintext();
+    if settings.single_thread {
+        process_serial(
+            reader,
+            writer,
+            first,
+            second,
+            settings.buffer_size,
+            holds_plaintext,
+            on_progress,
+        )
+    } else {
+        process_pipelined(
+            reader,
+            writer,
+            first,
+            second,
+            settings.buffer_size,
+            holds_plaintext,
+            on_progress,
+        )
+    }
+}
+
+/// 在当前线程中串行处理所有数据块。
+fn process_serial<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    first: Stage<'_>,
+    second: Stage<'_>,
+    buffer_size: usize,
+    holds_plaintext: bool,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    let mut chunk = Chunk::take(buffer_size);
+    loop {
+        let bytes_read = chunk.fill(reader)?;
+        if bytes_read == 0 {
+            chunk.release(holds_plaintext);
+            return Ok(());
+        }
+        on_progress(bytes_read as u64);
+        first(chunk.data_mut());
+        second(chunk.data_mut());
+        writer.write_all(chunk.data()).context("写入目标文件失败")?;
+    }
+}
+
+/// 使用两个工作线程以流水线方式处理所有数据块。
+///
+/// 同时在途的数据块最多为 [`PIPELINE_DEPTH`] 个，每个通道的容量也是这个值，
+/// 因此工作线程的发送永远不会阻塞，调用者提前返回错误时也不会死锁。
+fn process_pipelined<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    first: Stage<'_>,
+    second: Stage<'_>,
+    buffer_size: usize,
+    holds_plaintext: bool,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    thread::scope(|scope| {
+        let (input_tx, input_rx) = sync_channel::<Chunk>(PIPELINE_DEPTH);
+        let (middle_tx, middle_rx) = sync_channel::<Chunk>(PIPELINE_DEPTH);
+        let (output_tx, output_rx) = sync_channel::<Chunk>(PIPELINE_DEPTH);
+
+        scope.spawn(move || run_stage(first, input_rx, middle_tx));
+        scope.spawn(move || run_stage(second, middle_rx, output_tx));
+
+        // 返回时 `input_tx` 被丢弃，工作线程处理完剩余的数据块后自行退出
+        feed_and_drain(
+            reader,
+            writer,
+            input_tx,
+            &output_rx,
+            buffer_size,
+            holds_plaintext,
+            on_progress,
+        )
+    })
+}
+
+/// 工作线程：从 `input` 接收数据块，处理后发送给 `output`，直到上游关闭。
+fn run_stage(stage: Stage<'_>, input: Receiver<Chunk>, output: SyncSender<Chunk>) {
+    for mut chunk in input {
+        stage(chunk.data_mut());
+        if output.send(chunk).is_err() {
+            break;
+        }
+    }
+}
+
+/// 调用者线程：读取数据块送入流水线，并按顺序写出处理完成的数据块。
+fn feed_and_drain<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    input: SyncSender<Chunk>,
+    output: &Receiver<Chunk>,
+    buffer_size: usize,
+    holds_plaintext: bool,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()> {
+    let worker_exited = || anyhow!("流水线工作线程意外退出");
+    let mut in_flight = 0;
+    // 写完的数据块会被回收复用，避免每个数据块都重新取出缓冲区
+    let mut spare_chunks: Vec<Chunk> = Vec::new();
+
+    loop {
+        let mut chunk = spare_chunks
+            .pop()
+            .unwrap_or_else(|| Chunk::take(buffer_size));
+        let bytes_read = chunk.fill(reader)?;
+        if bytes_read == 0 {
+            spare_chunks.push(chunk);
+            break;
+        }
+        on_progress(bytes_read as u64);
+        input.send(chunk).map_err(|_| worker_exited())?;
+        in_flight += 1;
+
+        // 在途数据块达到上限时，等待最早的一个完成并写出
+        if in_flight == PIPELINE_DEPTH {
+            let chunk = output.recv().map_err(|_| worker_exited())?;
+            writer.write_all(chunk.data()).context("写入目标文件失败")?;
+            spare_chunks.push(chunk);
+            in_flight -= 1;
+        }
+    }
+
+    // 关闭输入，写出剩余的数据块
+    drop(input);
+    for _ in 0..in_flight {
+        let chunk = output.recv().map_err(|_| worker_exited())?;
+        writer.write_all(chunk.data()).context("写入目标文件失败")?;
+        spare_chunks.push(chunk);
+    }
+    for chunk in spare_chunks {
+        chunk.release(holds_plaintext);
+    }
+    Ok(())
+}
+
+/// 使用 `settings.threads` 个工作线程并行地对数据流应用 CTR 密钥流，并按顺序计算 HMAC。
+///
+/// `cipher` 必须位于密钥流的起始位置；每个工作线程克隆它并定位到数据块在流中的偏移量。
+/// 读取、HMAC 和写入都在调用者的线程中按原始顺序完成，同时在途的数据块最多为
+/// 线程数 × [`PARALLEL_CHUNKS_PER_THREAD`] 个，用以限制内存占用。
+/// 输出与串行处理逐字节相同。
+fn process_chunks_parallel<R, W, C>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &C,
+    mac: &mut dyn FnMut(&[u8]),
+    direction: CipherDirection,
+    settings: &ChunkSettings,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<()>
+where
+    R: Read,
+    W: Write,
+    C: StreamCipher + StreamCipherSeek + Clone + Send + Sync,
+{
+    let threads = settings.threads.max(1);
+    let max_in_flight = threads * PARALLEL_CHUNKS_PER_THREAD;
+
+    thread::scope(|scope| {
+        let (job_tx, job_rx) = sync_channel::<(u64, Chunk)>(max_in_flight);
+        let (done_tx, done_rx) = sync_channel::<(u64, Chunk)>(max_in_flight);
+        // 标准库的接收端不能共享，工作线程通过互斥锁轮流领取任务
+        let job_rx = Arc::new(Mutex::new(job_rx));
+
+        for _ in 0..threads {
+            let job_rx = Arc::clone(&job_rx);
+            let done_tx = done_tx.clone();
+            scope.spawn(move || run_ctr_worker(cipher, &job_rx, &done_tx));
+        }
+        drop(done_tx);
+
+        let mut reassembly = Reassembly {
+            writer,
+            mac,
+            direction,
+            done_rx,
+            pending: BTreeMap::new(),
+            write_offset: 0,
+            in_flight: 0,
+            spare_chunks: Vec::new(),
+        };
+        let mut read_offset = 0u64;
+
+        loop {
+            let mut chunk = reassembly
+                .spare_chunks
+                .pop()
+                .unwrap_or_else(|| Chunk::take(settings.buffer_size));
+            let bytes_read = chunk.fill(reader)?;
+            if bytes_read == 0 {
+                reassembly.spare_chunks.push(chunk);
+                break;
+            }
+            on_progress(bytes_read as u64);
+            if direction == CipherDirection::Decrypt {
+                (reassembly.mac)(chunk.data());
+            }
+
+            // 每个任务携带数据块在流中的偏移量，工作线程据此定位计数器
+            job_tx
+                .send((read_offset, chunk))
+                .map_err(|_| worker_exited())?;
+            read_offset += bytes_read as u64;
+            reassembly.in_flight += 1;
+
+            // 在途数据块达到上限时，等待最早的数据块完成并按顺序写出
+            while reassembly.in_flight >= max_in_flight {
+                reassembly.receive_one()?;
+            }
+        }
+
+        // 关闭任务通道，写出剩余的数据块
+        drop(job_tx);
+        while reassembly.in_flight > 0 {
+            reassembly.receive_one()?;
+        }
+        for chunk in reassembly.spare_chunks {
+            chunk.release(direction.output_is_plaintext());
+        }
+        Ok(())
+    })
+}
+
+/// 并行 CTR 工作线程：领取数据块，把克隆的密钥流定位到数据块的偏移量后应用。
+fn run_ctr_worker<C: StreamCipher + StreamCipherSeek + Clone>(
+    cipher: &C,
+    jobs: &Mutex<Receiver<(u64, Chunk)>>,
+    done: &SyncSender<(u64, Chunk)>,
+) {
+    loop {
+        // 只在领取任务时持有锁，处理数据块时其他线程可以继续领取
+        let job = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+        let Ok((offset, mut chunk)) = job else {
+            return;
+        };
+        let mut cipher = cipher.clone();
+        cipher.seek(offset);
+        cipher.apply_keystream(chunk.data_mut());
+        if done.send((offset, chunk)).is_err() {
+            return;
+        }
+    }
+}
+
+/// 并行 CTR 模式的顺序重组阶段：按偏移量排序已完成的数据块，并依次认证和写出。
+struct Reassembly<'a, W: Write> {
+    writer: &'a mut W,
+    mac: &'a mut dyn FnMut(&[u8]),
+    direction: CipherDirection,
+    done_rx: Receiver<(u64, Chunk)>,
+    /// 已完成但还未轮到写出的数据块，以它们在流中的偏移量为键。
+    pending: BTreeMap<u64, Chunk>,
+    /// 下一个要写出的数据块的偏移量。
+    write_offset: u64,
+    /// 已分发但还未写出的数据块数量。
+    in_flight: usize,
+    /// 写出后回收的数据块。
+    spare_chunks: Vec<Chunk>,
+}
+
+impl<W: Write> Reassembly<'_, W> {
+    /// 接收一个完成的数据块，并写出所有已经按顺序就绪的数据块。
+    fn receive_one(&mut self) -> Result<()> {
+        let (offset, chunk) = self.done_rx.recv().map_err(|_| worker_exited())?;
+        self.pending.insert(offset, chunk);
+        while let Some(chunk) = self.pending.remove(&self.write_offset) {
+            if self.direction == CipherDirection::Encrypt {
+                (self.mac)(chunk.data());
+            }
+            self.writer
+                .write_all(chunk.data())
+                .context("写入目标文件失败")?;
+            self.write_offset += chunk.len as u64;
+            self.in_flight -= 1;
+            self.spare_chunks.push(chunk);
+        }
+        Ok(())
+    }
+}
+
+/// 工作线程提前退出（例如发生 panic）时返回的错误。
+fn worker_exited() -> anyhow::Error {
+    anyhow!("并行加密工作线程意外退出")
+}
+
+/// # 压缩算法 (Compression)
+///
+/// [`PipelineBuilder::compress`] 在加密之前使用的压缩算法。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Compression {
+    /// LZ4 帧格式，与 `lz4` 命令行工具兼容。
+    #[default]
+    Lz4,
+}
+
+impl Compression {
+    /// 压缩后的文件追加的扩展名（不含点），也会记录在加密文件头的原始文件名中。
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Lz4 => "lz4",
+        }
+    }
+
+    /// `len` 字节的输入压缩后最多占用的字节数（无法压缩的数据会略微变大）。
+    fn max_compressed_len(&self, len: u64) -> u64 {
+        match self {
+            // 帧头最多 19 字节，结束标记和内容校验和各 4 字节；
+            // 每个数据块（至少 64 KiB）带有 4 字节的块头，无法压缩的块按原样存储
+            Self::Lz4 => len + len.div_ceil(64 * 1024) * 4 + 19 + 4 + 4,
+        }
+    }
+
+    /// 把 `reader` 包装为输出压缩数据的读取器。
+    fn reader<'r, R: Read + 'r>(&self, reader: R, buffer_size: usize) -> Box<dyn Read + 'r> {
+        match self {
+            Self::Lz4 => Box::new(CompressingReader {
+                source: reader,
+                encoder: Some(lz4_flex::frame::FrameEncoder::new(Vec::new())),
+                input: Zeroizing::new(vec![0u8; buffer_size]),
+                pending: Zeroizing::new(Vec::new()),
+                position: 0,
+            }),
+        }
+    }
+}
+
+/// 流水线中的一个步骤，按照添加的顺序记录。
+#[derive(Debug, Clone, Copy)]
+enum PipelineStep<'a> {
+    Compress(Compression),
+    Encrypt(EncryptionOptions<'a>),
+    Split(u64),
+}
+
+/// # 流水线构建器 (Pipeline Builder)
+///
+/// 以声明方式组合"压缩 → 加密 → 分割"，例如：
+///
+/// ```rust,no_run
+/// use ferox_encryptor::{Compression, EncryptionOptions, PipelineBuilder};
+/// use std::path::Path;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let pipeline = PipelineBuilder::new()
+///     .compress(Compression::Lz4)
+///     .encrypt(EncryptionOptions::new("my_secure_password"))
+///     .split(100 * 1024 * 1024)
+///     .build()?;
+/// let parts = pipeline.run(Path::new("backup.tar"), Path::new("/mnt/usb"))?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// `encrypt` 必须恰好出现一次；`compress` 最多出现一次且必须在 `encrypt` 之前（密文无法压缩）；
+/// `split` 最多出现一次且必须在 `encrypt` 之后，分割的是加密输出。
+#[derive(Debug, Clone, Default)]
+pub struct PipelineBuilder<'a> {
+    steps: Vec<PipelineStep<'a>>,
+}
+
+impl<'a> PipelineBuilder<'a> {
+    /// 创建一个没有任何步骤的构建器。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加压缩步骤。
+    pub fn compress(mut self, algo: Compression) -> Self {
+        self.steps.push(PipelineStep::Compress(algo));
+        self
+    }
+
+    /// 添加加密步骤。
+    pub fn encrypt(mut self, opts: EncryptionOptions<'a>) -> Self {
+        self.steps.push(PipelineStep::Encrypt(opts));
+        self
+    }
+
+    /// 添加分割步骤：加密输出按 `chunk_size` 字节分割为多个文件。
+    pub fn split(mut self, chunk_size: u64) -> Self {
+        self.steps.push(PipelineStep::Split(chunk_size));
+        self
+    }
+
+    /// 检查步骤的组合和顺序并生成流水线。
+    ///
+    /// # 错误
+    ///
+    /// 缺少 `encrypt` 或重复出现任何步骤、`compress` 位于 `encrypt` 之后、
+    /// `split` 位于 `encrypt` 之前或分割大小为 0 时返回错误。
+    pub fn build(self) -> Result<Pipeline<'a>> {
+        let mut compression = None;
+        let mut encryption = None;
+        let mut split = None;
+        for step in self.steps {
+            match step {
+                PipelineStep::Compress(algo) => {
+                    if compression.replace(algo).is_some() {
+                        bail!("流水线中的 compress 最多只能出现一次");
+                    }
+                    if encryption.is_some() {
+                        bail!("compress 必须位于 encrypt 之前，密文无法被有效压缩");
+                    }
+                }
+                PipelineStep::Encrypt(opts) => {
+                    if encryption.replace(opts).is_some() {
+                        bail!("流水线中的 encrypt 只能出现一次");
+                    }
+                }
+                PipelineStep::Split(chunk_size) => {
+                    if chunk_size == 0 {
+                        bail!("分割大小必须大于 0");
+                    }
+                    if split.replace(chunk_size).is_some() {
+                        bail!("流水线中的 split 最多只能出现一次");
+                    }
+                    if encryption.is_none() {
+                        bail!("split 必须位于 encrypt 之后，分割的是加密输出");
+                    }
+                }
+            }
+        }
+        let encryption = encryption.context("流水线必须包含一次 encrypt")?;
+        Ok(Pipeline {
+            compression,
+            encryption,
+            split,
+        })
+    }
+}
+
+/// # 流水线 (Pipeline)
+///
+/// 由 [`PipelineBuilder`] 生成、已经检查过的处理步骤。
+#[derive(Debug, Clone, Copy)]
+pub struct Pipeline<'a> {
+    /// (可选) 加密之前的压缩算法。
+    compression: Option<Compression>,
+    /// 加密选项。
+    encryption: EncryptionOptions<'a>,
+    /// (可选) 加密输出的分割大小（字节）。
+    split: Option<u64>,
+}
+
+impl Pipeline<'_> {
+    /// 处理 `source`，把结果写入 `output_dir`，按顺序返回生成的文件。
+    ///
+    /// 输出文件名为 `原始文件名[.lz4].feroxcrypt`；分割时每个部分再追加 `.001`、`.002` 等序号，
+    /// 按序号拼接各部分即可得到完整的加密文件。加密文件头中记录的原始文件名包含压缩扩展名，
+    /// 因此解密得到的是压缩文件。
+    ///
+    /// 数据以流的方式经过各个步骤，不会写出中间文件。
+    ///
+    /// # 错误
+    ///
+    /// 源文件无效、输出文件已存在（且未设置 `force_overwrite`）或任何步骤失败时返回错误，
+    /// 已经写出的输出文件会被删除。
+    pub fn run(&self, source: &Path, output_dir: &Path) -> Result<Vec<PathBuf>> {
+        let opts = &self.encryption;
+        let mut stored_name = validate_source(source)?;
+        if let Some(compression) = self.compression {
+            stored_name = format!("{stored_name}.{}", compression.extension());
+        }
+        ensure_valid_buffer_size(opts.buffer_size)?;
+        if !output_dir.is_dir() {
+            bail!("提供的路径不是一个目录: {}", output_dir.display());
+        }
+
+        let source_file = File::open(source).context("无法打开源文件")?;
+        let metadata = source_file.metadata()?;
+        ensure_within_size_limit(&metadata, opts.max_file_size)?;
+        let input = InputFile::open(source_file, metadata.len(), opts.buffer_size);
+        let mut reader: Box<dyn Read> = match self.compression {
+            Some(compression) => compression.reader(input, opts.buffer_size),
+            None => Box::new(input),
+        };
+
+        let mut writer = PartWriter {
+            base: output_dir.join(format!("{stored_name}.{CUSTOM_FILE_EXTENSION}")),
+            part_size: self.split,
+            force_overwrite: opts.force_overwrite,
+            current: None,
+            written_in_part: 0,
+            paths: Vec::new(),
+        };
+        let result = encrypt_stream(&mut reader, &mut writer, &stored_name, opts, &mut |_| {})
+            .and_then(|()| writer.finish_part());
+        match result {
+            Ok(()) => Ok(writer.paths),
+            Err(e) => {
+                writer.current = None;
+                for path in &writer.paths {
+                    if let Err(remove_error) = fs::remove_file(path) {
+                        log::warn!(
+                            "无法删除不完整的输出文件 {}: {remove_error}",
+                            path.display()
+                        );
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// 估算处理 `source_size` 字节的源文件时所有输出文件的总大小（字节）。
+    ///
+    /// 结果不包含文件头中原始文件名的字节数（压缩时还有压缩扩展名）；
+    /// 不压缩时加上文件名长度恰好等于实际大小，压缩时为上限（按无法压缩的数据计算）。
+    pub fn estimate_output_size(&self, source_size: u64) -> u64 {
+        let payload = self.compression.map_or(source_size, |compression| {
+            compression.max_compressed_len(source_size)
+        });
+        // 每条 TLV 扩展记录带有 4 字节的类型和长度；安全级别总是会被记录
+        let mut overhead = (fixed_overhead_bytes() + 4 + SECURITY_LEVEL_LEN) as u64;
+        if self.encryption.keyfile.is_some() {
+            overhead += 4 + KEYFILE_COMMITMENT_LEN as u64;
+        }
+        if self.encryption.expires_at.is_some() {
+            overhead += 4 + EXPIRY_TIME_LEN as u64;
+        }
+        payload + overhead
+    }
+}
+
+/// 在读取时压缩数据的读取器：从源读取明文，交给压缩器，再交出压缩器的输出。
+struct CompressingReader<R> {
+    source: R,
+    /// 压缩器；源读取完毕并写出帧尾之后为 `None`。
+    encoder: Option<lz4_flex::frame::FrameEncoder<Vec<u8>>>,
+    /// 从源读取明文的缓冲区。
+    input: Zeroizing<Vec<u8>>,
+    /// 压缩器已经输出、还未交给调用者的数据。
+    pending: Zeroizing<Vec<u8>>,
+    /// `pending` 中下一个要交出的字节。
+    position: usize,
+}
+
+impl<R: Read> Read for CompressingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.position < self.pending.len() {
+                let available = &self.pending[self.position..];
+                let len = available.len().min(buf.len());
+                buf[..len].copy_from_slice(&available[..len]);
+                self.position += len;
+                return Ok(len);
+            }
+            self.pending.zeroize();
+            self.position = 0;
+
+            let Some(encoder) = self.encoder.as_mut() else {
+                return Ok(0);
+            };
+            let n = self.source.read(&mut self.input)?;
+            if n == 0 {
+                if let Some(encoder) = self.encoder.take() {
+                    *self.pending = encoder.finish().map_err(io::Error::other)?;
+                }
+            } else {
+                encoder.write_all(&self.input[..n])?;
+                self.input[..n].zeroize();
+                std::mem::swap(encoder.get_mut(), &mut *self.pending);
+            }
+        }
+    }
+}
+
+/// 把加密输出写入一个或多个文件：不分割时写入 `base`，分割时写入 `base.001`、`base.002` 等。
+struct PartWriter {
+    /// 输出文件的路径（分割时为各部分的公共前缀）。
+    base: PathBuf,
+    /// 每个部分的大小；不分割时为 `None`。
+    part_size: Option<u64>,
+    /// 是否覆盖已存在的输出文件。
+    force_overwrite: bool,
+    /// 正在写入的文件。
+    current: Option<io::BufWriter<File>>,
+    /// 已写入当前文件的字节数。
+    written_in_part: u64,
+    /// 已创建的输出文件。
+    paths: Vec<PathBuf>,
+}
+
+impl PartWriter {
+    /// 刷新并关闭正在写入的文件。
+    fn finish_part(&mut self) -> Result<()> {
+        if let Some(mut file) = self.current.take() {
+            file.flush().context("刷新输出文件失败")?;
+        }
+        Ok(())
+    }
+
+    /// 打开下一个输出文件。
+    fn open_next_part(&mut self) -> io::Result<()> {
+        self.finish_part().map_err(io::Error::other)?;
+        let path = match self.part_size {
+            Some(_) => {
+                let mut name = self.base.clone().into_os_string();
+                name.push(format!(".{:03}", self.paths.len() + 1));
+                PathBuf::from(name)
+            }
+            None => self.base.clone(),
+        };
+        let file = if self.force_overwrite {
+            File::create(&path)
+        } else {
+            File::create_new(&path)
+        }
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("无法创建输出文件 {}: {e}", path.display()),
+            )
+        })?;
+        self.paths.push(path);
+        self.current = Some(io::BufWriter::new(file));
+        self.written_in_part = 0;
+        Ok(())
+    }
+}
+
+impl Write for PartWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let part_full = self
+            .part_size
+            .is_some_and(|part_size| self.written_in_part >= part_size);
+        if self.current.is_none() || part_full {
+            self.open_next_part()?;
+        }
+        let len = match self.part_size {
+            Some(part_size) => buf
+                .len()
+                .min(usize::try_from(part_size - self.written_in_part).unwrap_or(usize::MAX)),
+            None => buf.len(),
+        };
+        let file = self.current.as_mut().expect("刚刚打开了输出文件");
+        let written = file.write(&buf[..len])?;
+        self.written_in_part += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::BUFFER_LEN,
+        decrypt::{decrypt_in_memory, DecryptionOptions},
+        encrypt::{encrypt_stream_with_header, EncryptionOptions},
+        format::FileHeader,
+    };
+
+    /// 测试只关心数据块的处理，使用最小的 Argon2 成本以加快密钥派生。
+    const TEST_ARGON2_PARAMS: (u32, u32, u32) = (8, 1, 1);
+
+    /// 每次读取返回的最大字节数，不是 AES 分组大小 (16 字节) 的整数倍。
+    const SHORT_READ_LEN: usize = 1001;
+
+    /// 每次最多返回 [`SHORT_READ_LEN`] 字节的读取器，用少量数据产生大量数据块。
+    struct ShortReads<'a>(&'a [u8]);
+
+    impl Read for ShortReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(SHORT_READ_LEN);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_pipelined_output_matches_serial() -> Result<()> {
+        // 数据块数量超过在途上限，最后一个数据块不完整
+        let input: Vec<u8> = (0..SHORT_READ_LEN * 12 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+
+        let mut outputs = Vec::new();
+        for single_thread in [true, false] {
+            let mut offset = 0usize;
+            let mut checksum = 0u64;
+            let mut first = |chunk: &mut [u8]| {
+                for byte in chunk.iter_mut() {
+                    *byte = byte.wrapping_add((offset % 7) as u8);
+                    offset += 1;
+                }
+            };
+            let mut second = |chunk: &mut [u8]| {
+                checksum = chunk.iter().fold(checksum, |acc, &b| {
+                    acc.wrapping_mul(31).wrapping_add(b as u64)
+                });
+            };
+            let mut output = Vec::new();
+            let mut progress = 0;
+            process_chunks(
+                &mut ShortReads(&input),
+                &mut output,
+                &mut first,
+                &mut second,
+                CipherDirection::Decrypt,
+                &ChunkSettings {
+                    buffer_size: BUFFER_LEN,
+                    single_thread,
+                    threads: 1,
+                },
+                &mut |bytes| progress += bytes,
+            )?;
+            assert_eq!(progress, input.len() as u64);
+            outputs.push((output, checksum));
+        }
+        assert_eq!(outputs[0], outputs[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pipelined_encryption_matches_single_thread() -> Result<()> {
+        // 注入固定的盐和 IV，使两次加密的输出可以逐字节比较
+        let header = FileHeader::new("pipeline.bin", [7u8; 16], [9u8; 16], TEST_ARGON2_PARAMS);
+        let plaintext: Vec<u8> = (0..SHORT_READ_LEN * 10 + 17)
+            .map(|i| (i % 253) as u8)
+            .collect();
+
+        let mut outputs = Vec::new();
+        for single_thread in [true, false] {
+            let opts = EncryptionOptions {
+                single_thread,
+                ..EncryptionOptions::new("pipeline_password")
+            };
+            let mut encrypted = Vec::new();
+            encrypt_stream_with_header(
+                &mut ShortReads(&plaintext),
+                &mut encrypted,
+                &header,
+                &opts,
+                &mut |_| {},
+            )?;
+            outputs.push(encrypted);
+        }
+        assert!(
+            outputs[0] == outputs[1],
+            "流水线模式的密文应与单线程模式完全相同"
+        );
+
+        // 两种模式都能解密对方的输出
+        for single_thread in [true, false] {
+            let opts = DecryptionOptions {
+                single_thread,
+                ..DecryptionOptions::new("pipeline_password")
+            };
+            let (decrypted, filename) = decrypt_in_memory(&outputs[0], &opts)?;
+            assert!(decrypted == plaintext);
+            assert_eq!(filename, "pipeline.bin");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_ctr_matches_serial_keystream() -> Result<()> {
+        type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+        use ctr::cipher::KeyIvInit;
+
+        let cipher = Aes256Ctr::new(&[3u8; 32].into(), &[5u8; 16].into());
+        let plaintext: Vec<u8> = (0..SHORT_READ_LEN * 20 + 999)
+            .map(|i| (i % 241) as u8)
+            .collect();
+        let mut expected = plaintext.clone();
+        cipher.clone().apply_keystream(&mut expected);
+
+        // 数据块的偏移量不与 AES 分组对齐，数量超过在途上限，覆盖等待和乱序重组的路径
+        for direction in [CipherDirection::Encrypt, CipherDirection::Decrypt] {
+            let mut authenticated = Vec::new();
+            let mut output = Vec::new();
+            process_chunks_parallel(
+                &mut ShortReads(&plaintext),
+                &mut output,
+                &cipher,
+                &mut |chunk: &[u8]| authenticated.extend_from_slice(chunk),
+                direction,
+                &ChunkSettings {
+                    buffer_size: BUFFER_LEN,
+                    single_thread: false,
+                    threads: 2,
+                },
+                &mut |_| {},
+            )?;
+            assert!(output == expected);
+            // HMAC 总是按顺序看到密文：加密时是输出，解密时是输入
+            let mac_input = match direction {
+                CipherDirection::Encrypt => &expected,
+                CipherDirection::Decrypt => &plaintext,
+            };
+            assert!(&authenticated == mac_input);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_encryption_matches_single_thread() -> Result<()> {
+        let header = FileHeader::new("parallel.bin", [1u8; 16], [2u8; 16], TEST_ARGON2_PARAMS);
+        let plaintext: Vec<u8> = (0..SHORT_READ_LEN * 10 + 3)
+            .map(|i| (i % 239) as u8)
+            .collect();
+
+        let mut outputs = Vec::new();
+        for (single_thread, threads) in [(true, 1), (false, 2)] {
+            let opts = EncryptionOptions {
+                single_thread,
+                threads,
+                ..EncryptionOptions::new("parallel_password")
+            };
+            let mut encrypted = Vec::new();
+            encrypt_stream_with_header(
+                &mut ShortReads(&plaintext),
+                &mut encrypted,
+                &header,
+                &opts,
+                &mut |_| {},
+            )?;
+            outputs.push(encrypted);
+        }
+        assert!(
+            outputs[0] == outputs[1],
+            "并行模式的密文应与单线程模式完全相同"
+        );
+
+        let opts = DecryptionOptions {
+            threads: 2,
+            ..DecryptionOptions::new("parallel_password")
+        };
+        let (decrypted, _) = decrypt_in_memory(&outputs[0], &opts)?;
+        assert!(decrypted == plaintext);
+
+        // 并行解密同样会检测到篡改
+        let mut tampered = outputs[0].clone();
+        let index = tampered.len() / 2;
+        tampered[index] ^= 0x01;
+        assert!(decrypt_in_memory(&tampered, &opts).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_buffers_are_reused_and_scrubbed() -> Result<()> {
+        // 测试线程开始时池是空的；归还的明文缓冲区被清零，下一次取出的是同一块内存
+        let mut chunk = Chunk::take(MIN_BUFFER_SIZE);
+        let allocation = chunk.buffer.as_ptr();
+        chunk.fill(&mut &[0xAAu8; 100][..])?;
+        assert_eq!(chunk.data(), &[0xAAu8; 100][..]);
+        chunk.release(true);
+
+        let chunk = Chunk::take(MIN_BUFFER_SIZE);
+        assert_eq!(chunk.buffer.as_ptr(), allocation);
+        assert!(chunk.buffer.iter().all(|&byte| byte == 0));
+        chunk.release(true);
+
+        // 连续处理多个文件时复用池中的缓冲区，输出不受影响
+        let input: Vec<u8> = (0..SHORT_READ_LEN * 3).map(|i| (i % 13) as u8).collect();
+        let settings = ChunkSettings {
+            buffer_size: MIN_BUFFER_SIZE,
+            single_thread: false,
+            threads: 1,
+        };
+        for _ in 0..3 {
+            let mut output = Vec::new();
+            process_chunks(
+                &mut ShortReads(&input),
+                &mut output,
+                &mut |chunk: &mut [u8]| chunk.iter_mut().for_each(|byte| *byte ^= 0x5A),
+                &mut |_: &mut [u8]| {},
+                CipherDirection::Decrypt,
+                &settings,
+                &mut |_| {},
+            )?;
+            let expected: Vec<u8> = input.iter().map(|byte| byte ^ 0x5A).collect();
+            assert!(output == expected);
+        }
+        BUFFER_POOL.with(|pool| {
+            let pool = pool.borrow();
+            assert!(!pool.is_empty());
+            assert!(pool.iter().flatten().all(|&byte| byte == 0));
+        });
+        Ok(())
+    }
+}
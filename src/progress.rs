@@ -0,0 +1,163 @@
+// src/progress.rs
+
+//! # 进度事件模块 (Progress Events Module)
+//!
+//! 该模块定义了批量处理和单文件流程向外报告进度的抽象。
+//! 调用者（例如 CLI 或交互式界面）实现 [`ProgressSink`] 并挂载到
+//! [`BatchConfig`](crate::batch::BatchConfig) 上，即可接收统一的进度事件，
+//! 而不必依赖各个流程内部自行创建的进度条。没有挂载接收器时，各流程不报告任何进度。
+//!
+//! *This module defines the abstraction used by batch processing and the
+//! single-file flows to report progress. Callers implement [`ProgressSink`]
+//! and attach it to a `BatchConfig` to receive uniform progress events.*
+
+use crate::constants::NO_PROGRESS_ENV_VAR;
+use crate::watchdog::IoOperation;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// # 进度事件 (Progress Event)
+///
+/// 批量处理过程中按顺序发出的事件。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressEvent<'a> {
+    /// 批量操作开始，包含文件总数和源文件总字节数。
+    BatchStarted {
+        /// 待处理的文件总数（惰性产生文件的批量操作事先无法确定时为 0）。
+        total_files: usize,
+        /// 所有待处理文件的总字节数（基于开始时的元数据；事先无法确定时为 0）。
+        total_bytes: u64,
+    },
+    /// 开始处理某个文件。
+    FileStarted {
+        /// 当前文件的序号（从 0 开始）。
+        index: usize,
+        /// 待处理的文件总数（事先无法确定时为 0）。
+        total_files: usize,
+        /// 当前文件的路径。
+        path: &'a Path,
+    },
+    /// 当前文件又处理了若干字节（增量）。
+    BytesProcessed {
+        /// 本次新处理的字节数。
+        bytes: u64,
+    },
+    /// 开始派生密钥。这一阶段没有字节进度，`expected` 是按 Argon2 参数估算的耗时。
+    KeyDerivationStarted {
+        /// 当前文件的路径。
+        path: &'a Path,
+        /// 预计耗时。
+        expected: Duration,
+    },
+    /// 当前文件已经超过设置的间隔没有任何读写进展（每次停滞只发出一次）。
+    Stalled {
+        /// 当前文件的路径。
+        path: &'a Path,
+        /// 源文件中已经读取到的位置。
+        offset: u64,
+        /// 卡住的读写操作。
+        operation: IoOperation,
+        /// 已经停滞了多久。
+        idle: Duration,
+    },
+    /// 某个文件处理结束。
+    FileFinished {
+        /// 当前文件的路径。
+        path: &'a Path,
+        /// 是否处理成功。
+        success: bool,
+    },
+    /// 批量操作结束。
+    BatchFinished {
+        /// 成功处理的文件数量。
+        success_count: usize,
+        /// 处理失败的文件数量。
+        failure_count: usize,
+    },
+}
+
+/// # 进度接收器 (Progress Sink)
+///
+/// 接收 [`ProgressEvent`] 的对象。实现必须是线程安全的，
+/// 因为同一个接收器可能被多个处理流程共享。
+///
+/// *Receiver of [`ProgressEvent`]s. Implementations must be thread-safe.*
+pub trait ProgressSink: Send + Sync {
+    /// 处理一个进度事件。
+    fn on_event(&self, event: &ProgressEvent<'_>);
+}
+
+/// # 终端进度条 (Terminal Progress)
+///
+/// 在标准错误上绘制一个按总字节数推进的进度条。只应在标准错误连接到终端时创建，
+/// 请使用 [`terminal_progress`] 获取。
+pub struct TerminalProgress {
+    bar: ProgressBar,
+}
+
+impl TerminalProgress {
+    /// 创建一个长度为 `total_bytes` 的进度条。
+    fn new(total_bytes: u64) -> Self {
+        let bar = ProgressBar::new(total_bytes);
+        if let Ok(bar_style) = ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        {
+            bar.set_style(bar_style.progress_chars("#>-"));
+        }
+        Self { bar }
+    }
+}
+
+impl ProgressSink for TerminalProgress {
+    fn on_event(&self, event: &ProgressEvent<'_>) {
+        match event {
+            ProgressEvent::BatchStarted { total_bytes, .. } => {
+                self.bar.set_length(*total_bytes);
+                self.bar.set_position(0);
+            }
+            ProgressEvent::BytesProcessed { bytes } => self.bar.inc(*bytes),
+            ProgressEvent::BatchFinished { .. } => self.bar.finish(),
+            ProgressEvent::FileStarted { .. }
+            | ProgressEvent::KeyDerivationStarted { .. }
+            | ProgressEvent::Stalled { .. }
+            | ProgressEvent::FileFinished { .. } => {}
+        }
+    }
+}
+
+/// 进度输出（标准错误）是否连接到终端。
+///
+/// 设置了非空的 [`NO_PROGRESS_ENV_VAR`] 环境变量时，总是视为没有连接终端。
+pub fn progress_output_is_terminal() -> bool {
+    std::env::var_os(NO_PROGRESS_ENV_VAR).is_none_or(|value| value.is_empty())
+        && io::stderr().is_terminal()
+}
+
+/// 标准错误连接到终端时，返回一个可以挂载到
+/// [`BatchConfig::progress_sink`](crate::batch::BatchConfig::progress_sink) 的终端进度条。
+///
+/// 否则返回 `None`：此时各流程不会创建任何进度对象，输出被重定向时也不会写入控制序列。
+pub fn terminal_progress() -> Option<Arc<dyn ProgressSink>> {
+    if progress_output_is_terminal() {
+        Some(Arc::new(TerminalProgress::new(0)))
+    } else {
+        None
+    }
+}
+
+/// 执行单个文件的流程；标准错误连接到终端时显示一个长度为该文件大小的进度条。
+pub(crate) fn with_terminal_progress(
+    path: &Path,
+    flow: impl FnOnce(Option<&dyn ProgressSink>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if !progress_output_is_terminal() {
+        return flow(None);
+    }
+    let terminal = TerminalProgress::new(path.metadata().map_or(0, |metadata| metadata.len()));
+    flow(Some(&terminal))?;
+    terminal.bar.finish();
+    Ok(())
+}
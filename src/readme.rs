@@ -0,0 +1,218 @@
+// src/readme.rs
+
+//! # 目录说明文件模块 (Directory Readme)
+//!
+//! 把加密后的文件夹交给别人时，对方往往不知道它是用什么工具、什么参数加密的。
+//! 设置了 [`BatchConfig::write_readme`](crate::batch::BatchConfig::write_readme) 时，
+//! 批量加密完成后会在每个处理过的目录中写入 `FEROX-ENCRYPTED.txt` 和内容相同的
+//! `FEROX-ENCRYPTED.json`，记录工具版本、格式版本、加密和密钥派生算法、安全级别、
+//! 目录中加密文件的数量以及解密方法。
+//!
+//! 说明文件不包含任何机密（密码、密钥文件内容、盐或密钥），每次运行都整体重新生成，
+//! 批量加密扫描目录时会自动跳过它们。
+//!
+//! *After a batch encryption with `write_readme`, every directory that received encrypted
+//! files gets a `FEROX-ENCRYPTED.txt` and `.json` describing the tool, format, algorithms,
+//! file count and how to decrypt. They hold no secrets, are regenerated on every run and
+//! are skipped by later scans.*
+
+use crate::{
+    cleanup::{commit_partial_file, create_partial_file, partial_path_for},
+    constants::{
+        CIPHER_AES256_CTR_HMAC_SHA256, CURRENT_FORMAT_VERSION, CUSTOM_FILE_EXTENSION, KDF_ARGON2ID,
+        README_FILE_NAME, README_JSON_FILE_NAME,
+    },
+    format::CipherSuite,
+    Level,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 记录在说明文件中的密钥派生算法名称。
+const KDF_NAME: &str = "Argon2id v0x13";
+
+/// # 目录说明 (Directory Readme)
+///
+/// 写入 `FEROX-ENCRYPTED.json` 的内容，`FEROX-ENCRYPTED.txt` 是它的文本形式。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectoryReadme {
+    /// 生成说明文件的工具名称。
+    pub tool: String,
+    /// 工具的版本。
+    pub tool_version: String,
+    /// 加密文件的格式版本。
+    pub format_version: u8,
+    /// 加密套件的名称。
+    pub cipher: String,
+    /// 文件头中的加密算法标识。
+    pub cipher_id: u8,
+    /// 密钥派生算法的名称。
+    pub kdf: String,
+    /// 文件头中的密钥派生算法标识。
+    pub kdf_id: u8,
+    /// 加密时使用的安全级别。
+    pub level: String,
+    /// Argon2 参数 `(m_cost KiB, t_cost, p_cost)`。
+    pub argon2_params: (u32, u32, u32),
+    /// 解密时是否还需要密钥文件（不记录密钥文件本身）。
+    pub keyfile_required: bool,
+    /// 生成时目录中（不含子目录）的加密文件数量。
+    pub file_count: usize,
+    /// 解密方法，每项一行。
+    pub instructions: Vec<String>,
+}
+
+impl DirectoryReadme {
+    /// 为使用 `level` 加密、包含 `file_count` 个加密文件的目录生成说明。
+    pub fn new(level: Level, keyfile_required: bool, file_count: usize) -> Self {
+        let keyfile_arg = if keyfile_required {
+            " --keyfile <密钥文件>"
+        } else {
+            ""
+        };
+        let mut instructions = vec![
+            format!(
+                "安装 Ferox Encryptor {} 或更高版本 (cargo install ferox_encryptor)",
+                env!("CARGO_PKG_VERSION")
+            ),
+            format!("在此目录中运行: ferox-encryptor batch-decrypt .{keyfile_arg}"),
+            "输入加密时使用的密码；密码不在此文件中，请通过其他渠道获取".to_string(),
+        ];
+        if keyfile_required {
+            instructions.push("解密还需要加密时使用的密钥文件，它同样不在此目录中".to_string());
+        }
+        Self {
+            tool: "Ferox Encryptor".to_string(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            format_version: CURRENT_FORMAT_VERSION,
+            cipher: CipherSuite::Aes256CtrHmacSha256.display_name().to_string(),
+            cipher_id: CIPHER_AES256_CTR_HMAC_SHA256,
+            kdf: KDF_NAME.to_string(),
+            kdf_id: KDF_ARGON2ID,
+            level: level.name().to_string(),
+            argon2_params: level.argon2_params(),
+            keyfile_required,
+            file_count,
+            instructions,
+        }
+    }
+
+    /// 说明文件的文本形式。
+    pub fn to_text(&self) -> String {
+        let (m_cost, t_cost, p_cost) = self.argon2_params;
+        let mut text = String::new();
+        let _ = writeln!(
+            text,
+            "此目录中的 .{CUSTOM_FILE_EXTENSION} 文件由 {} {} 加密。",
+            self.tool, self.tool_version
+        );
+        let _ = writeln!(
+            text,
+            "The .{CUSTOM_FILE_EXTENSION} files in this directory were encrypted with {} {}.",
+            self.tool, self.tool_version
+        );
+        text.push('\n');
+        let _ = writeln!(text, "格式版本 (Format version): {}", self.format_version);
+        let _ = writeln!(
+            text,
+            "加密算法 (Cipher): {} (id {})",
+            self.cipher, self.cipher_id
+        );
+        let _ = writeln!(text, "密钥派生 (KDF): {} (id {})", self.kdf, self.kdf_id);
+        let _ = writeln!(
+            text,
+            "安全级别 (Level): {} (m_cost={} KiB, t_cost={t_cost}, p_cost={p_cost})",
+            self.level, m_cost
+        );
+        let _ = writeln!(
+            text,
+            "需要密钥文件 (Keyfile required): {}",
+            if self.keyfile_required {
+                "是 (yes)"
+            } else {
+                "否 (no)"
+            }
+        );
+        let _ = writeln!(text, "加密文件数量 (Encrypted files): {}", self.file_count);
+        text.push('\n');
+        text.push_str("解密方法 (How to decrypt):\n");
+        for (index, step) in self.instructions.iter().enumerate() {
+            let _ = writeln!(text, "  {}. {step}", index + 1);
+        }
+        text.push('\n');
+        text.push_str("此文件不包含任何密码或密钥。 (This file contains no passwords or keys.)\n");
+        text
+    }
+
+    /// 把说明写入 `directory`，整体替换已有的说明文件，返回写入的两个文件路径。
+    pub fn save(&self, directory: &Path) -> Result<[PathBuf; 2]> {
+        let json = serde_json::to_string_pretty(self)?;
+        let text_path = directory.join(README_FILE_NAME);
+        let json_path = directory.join(README_JSON_FILE_NAME);
+        replace_file(&text_path, self.to_text().as_bytes())?;
+        replace_file(&json_path, json.as_bytes())?;
+        Ok([text_path, json_path])
+    }
+}
+
+/// 先写入带锁的 `.part` 文件，再原子地替换 `path`。
+fn replace_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let part_path = partial_path_for(path);
+    let mut file = create_partial_file(&part_path)?;
+    let result = file
+        .write_all(contents)
+        .with_context(|| format!("写入说明文件失败: {}", path.display()))
+        .and_then(|()| file.sync_all().context("刷新说明文件失败"))
+        .and_then(|()| commit_partial_file(&part_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&part_path);
+    }
+    result
+}
+
+/// 目录中（不含子目录）的加密文件数量。
+fn count_encrypted_files(directory: &Path) -> Result<usize> {
+    let entries = fs::read_dir(directory)
+        .with_context(|| format!("无法读取目录: {}", directory.display()))?;
+    Ok(entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter(|entry| {
+            Path::new(&entry.file_name())
+                .extension()
+                .is_some_and(|ext| ext == CUSTOM_FILE_EXTENSION)
+        })
+        .count())
+}
+
+/// 在每个目录中写入说明文件；单个目录写入失败时发出警告，继续处理其余目录。
+///
+/// 返回成功写入的文本说明文件路径，以及写入失败的目录和错误（严格模式下由调用者记为失败）。
+pub(crate) fn write_directory_readmes<'a>(
+    directories: impl IntoIterator<Item = &'a Path>,
+    level: Level,
+    keyfile_required: bool,
+) -> (Vec<PathBuf>, Vec<(PathBuf, anyhow::Error)>) {
+    let mut written = Vec::new();
+    let mut failed = Vec::new();
+    for directory in directories {
+        let saved = count_encrypted_files(directory).and_then(|file_count| {
+            DirectoryReadme::new(level, keyfile_required, file_count).save(directory)
+        });
+        match saved {
+            Ok([text_path, _]) => {
+                log::info!("📝 已写入说明文件: {}", text_path.display());
+                written.push(text_path);
+            }
+            Err(e) => {
+                log::warn!("⚠️  无法在 {} 中写入说明文件: {e:#}", directory.display());
+                failed.push((directory.to_path_buf(), e));
+            }
+        }
+    }
+    (written, failed)
+}
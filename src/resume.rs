@@ -0,0 +1,521 @@
+// src/resume.rs
+
+//! # 可续传加密模块 (Resumable Encryption)
+//!
+//! 加密非常大的单个文件时（例如从偶尔断开的 USB 磁盘上加密几百 GB 的文件），普通流程中断后
+//! 只能从头开始。可续传加密每处理一定量的数据就写入一个检查点：先把 `<目标文件>.part`
+//! 同步到磁盘，再原子地更新旁边的 `<目标文件>.feroxstate`，记录源文件的大小和修改时间、
+//! 文件头长度和已经加密的明文字节数，以及用单独的检查点密钥对这些字段和已写入内容计算的认证值。
+//!
+//! 检查点不保存文件本身的 HMAC：文件头不记录明文长度，截断位置处的 HMAC 就是截断后文件的
+//! 有效认证标签，能读取检查点的人可以据此伪造一个截断的加密文件。检查点密钥由 HMAC 密钥以
+//! [`RESUME_CHECKPOINT_CONTEXT`] 派生，与文件 HMAC 相互独立。
+//!
+//! 再次运行时，如果存在检查点，就用同样的密码重新派生密钥，对 `.part` 文件中检查点之前的
+//! 内容重新计算 HMAC 并据此验证检查点，一致后截掉检查点之后的数据，把 CTR 计数器和源文件
+//! 定位到检查点处继续加密。HMAC 的内部状态不会写入磁盘（`hmac` 不提供可序列化的状态，
+//! 而且它与密钥等价），续传时通过重新读取已写入的数据重建，代价是读取一遍 `.part` 文件。
+//! 成功后删除检查点文件，结果与不中断的加密逐字节相同。
+//!
+//! 设置了 [`EncryptionOptions::rate_limit`] 时，处理速度被限制在每秒给定的字节数以内，
+//! 长时间的加密不会占满慢速磁盘或 USB 总线的带宽；续传时限速从本次运行开始重新计算。
+//!
+//! 续传要求源文件没有变化：大小或修改时间与检查点不一致时拒绝续传。为了让 `.part` 文件在
+//! 中断后保留下来，可续传加密不登记到中断清理中，也不会改用临时目录暂存；
+//! 数据在单个线程中按顺序处理，不使用内存映射。
+//!
+//! *Resumable single-file encryption for huge files on flaky storage. Progress is
+//! checkpointed to `<target>.feroxstate` next to `<target>.part`: the source size and
+//! mtime, the header length and the plaintext bytes done, authenticated together with the
+//! data written so far under a domain-separated checkpoint key (the file HMAC itself is never
+//! stored, as it would be a valid tag for the truncated file). On restart the HMAC state is
+//! rebuilt by re-reading the partial output, checked against the checkpoint, and encryption
+//! continues from the checkpointed CTR position. The output is byte-identical to an
+//! uninterrupted run.*
+
+use crate::{
+    cleanup::{commit_partial_file, create_partial_file, open_partial_file, partial_path_for},
+    constants::{
+        AES_KEY_LEN, RESUME_CHECKPOINT_CONTEXT, RESUME_CHECKPOINT_INTERVAL, STATE_FILE_EXTENSION,
+    },
+    encrypt::{
+        build_source_header, derive_encryption_key, encrypted_target_path,
+        ensure_within_size_limit, prepare_encryption, EncryptionOptions,
+    },
+    error::{FeroxError, Stage, StageContext},
+    format::FileHeader,
+    pipeline::ensure_valid_buffer_size,
+    progress::{with_terminal_progress, ProgressEvent, ProgressSink},
+    secure_memory::SecureBuffer,
+};
+use anyhow::{bail, Context, Result};
+use ctr::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt::Write as _;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+type HmacSha256 = Hmac<Sha256>;
+
+/// 检查点文件的格式版本。
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// 写入 `.feroxstate` 文件的检查点。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Checkpoint {
+    /// 检查点文件的格式版本。
+    version: u32,
+    /// 开始加密时源文件的大小（字节）。
+    source_len: u64,
+    /// 开始加密时源文件的修改时间（Unix 纳秒）；无法获取时为 `None`。
+    source_mtime_ns: Option<u64>,
+    /// `.part` 文件中文件头的长度（字节）。
+    header_len: u64,
+    /// 已经加密并同步到磁盘的明文字节数，也是 CTR 密钥流的位置。
+    bytes_done: u64,
+    /// 用检查点密钥对以上字段以及文件头和前 `bytes_done` 字节密文的 HMAC 计算的认证值
+    /// （小写十六进制），见 [`checkpoint_auth`]。
+    auth: String,
+}
+
+/// 加密过程中的状态：加锁的 `.part` 文件、定位好的加密器和 MAC，以及检查点密钥。
+struct Progress {
+    part: File,
+    cipher: Aes256Ctr,
+    mac: HmacSha256,
+    checkpoint_key: Zeroizing<[u8; 32]>,
+    header_len: u64,
+    bytes_done: u64,
+}
+
+/// 返回目标文件对应的检查点文件路径 (`<目标文件>.feroxstate`)。
+pub(crate) fn state_path_for(target_path: &Path) -> PathBuf {
+    let mut name = target_path.as_os_str().to_owned();
+    name.push(".");
+    name.push(STATE_FILE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// 可续传地加密单个文件，每 [`RESUME_CHECKPOINT_INTERVAL`] 字节写入一个检查点；
+/// 标准错误连接到终端时显示进度条。
+///
+/// 目标文件旁边存在上次中断留下的检查点时，从检查点处继续加密（此时安全级别、盐和 IV
+/// 等参数取自已经写入的文件头，`opts` 中只有凭据、缓冲区大小和速率上限起作用），否则从头开始。
+/// 参见 [模块文档](self)。
+///
+/// # 错误
+///
+/// 源文件的大小或修改时间与检查点不一致、`.part` 文件比检查点记录的短，或者检查点的认证值
+/// 与 `.part` 文件的内容不符（密码或密钥文件错误时返回 [`FeroxError::AuthenticationFailed`]）时
+/// 拒绝续传，不会修改任何文件。失败时保留 `.part` 和检查点文件，以便修复问题后继续。
+pub fn encrypt_resumable(source_path: &Path, opts: &EncryptionOptions) -> Result<()> {
+    with_terminal_progress(source_path, |progress| {
+        encrypt_resumable_with_progress(source_path, opts, RESUME_CHECKPOINT_INTERVAL, progress)
+    })
+}
+
+/// 与 [`encrypt_resumable`] 相同，但使用给定的检查点间隔（明文字节数，必须大于 0），
+/// 并把进度报告给可选的 [`ProgressSink`]。续传时先报告检查点之前已经完成的字节数。
+pub fn encrypt_resumable_with_progress(
+    source_path: &Path,
+    opts: &EncryptionOptions,
+    checkpoint_interval: u64,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<()> {
+    let result = run_resumable_encryption(source_path, opts, checkpoint_interval, progress)
+        .with_context(|| format!("无法加密 {}", source_path.display()));
+    let state_path = state_path_for(&encrypted_target_path(source_path));
+    if result.is_err() && state_path.is_file() {
+        log::warn!(
+            "进度保存在 {} 中，修复问题后再次运行可续传加密即可从检查点继续",
+            state_path.display()
+        );
+    }
+    result
+}
+
+/// 可续传加密的实现。
+fn run_resumable_encryption(
+    source_path: &Path,
+    opts: &EncryptionOptions,
+    checkpoint_interval: u64,
+    progress: Option<&dyn ProgressSink>,
+) -> Result<()> {
+    if checkpoint_interval == 0 {
+        bail!("检查点间隔必须大于 0");
+    }
+    let mut limiter = match opts.rate_limit {
+        Some(0) => bail!("速率上限必须大于 0"),
+        Some(bytes_per_sec) => Some(RateLimiter::new(bytes_per_sec)),
+        None => None,
+    };
+    let (original_filename, target_path) =
+        prepare_encryption(source_path, opts.force_overwrite).at_stage(Stage::HeaderParse)?;
+    let part_path = partial_path_for(&target_path);
+    let state_path = state_path_for(&target_path);
+
+    let mut source = File::open(source_path)
+        .context("无法打开源文件")
+        .at_stage(Stage::HeaderParse)?;
+    let metadata = source.metadata().at_stage(Stage::HeaderParse)?;
+    ensure_within_size_limit(&metadata, opts.max_file_size).at_stage(Stage::HeaderParse)?;
+    ensure_valid_buffer_size(opts.buffer_size).at_stage(Stage::HeaderParse)?;
+    let source_len = metadata.len();
+    let source_mtime_ns = mtime_ns(&metadata);
+
+    let mut state = if state_path.exists() {
+        let checkpoint = read_checkpoint(&state_path).at_stage(Stage::HeaderParse)?;
+        if checkpoint.source_len != source_len || checkpoint.source_mtime_ns != source_mtime_ns {
+            bail!(
+                "源文件 {} 在上次中断后被修改过（大小或修改时间与检查点不一致），无法续传；\
+                 删除 {} 和 {} 后重新加密",
+                source_path.display(),
+                part_path.display(),
+                state_path.display()
+            );
+        }
+        let state = resume_from_checkpoint(&part_path, &original_filename, &checkpoint, opts)?;
+        source
+            .seek(SeekFrom::Start(state.bytes_done))
+            .context("无法定位源文件")
+            .at_stage(Stage::HeaderParse)?;
+        log::info!(
+            "从检查点继续加密: 已完成 {} / {} 字节",
+            state.bytes_done,
+            source_len
+        );
+        if let Some(sink) = progress {
+            sink.on_event(&ProgressEvent::BytesProcessed {
+                bytes: state.bytes_done,
+            });
+        }
+        state
+    } else {
+        log::info!("加密后的文件将保存为: {}", target_path.display());
+        let header = build_source_header(source_path, &original_filename, &metadata, opts, None)
+            .at_stage(Stage::HeaderParse)?;
+        start_fresh(&part_path, &header, opts)?
+    };
+
+    // 处理数据之前先记录检查点（续传时与原检查点相同），此后的任何中断都可以续传
+    write_checkpoint(&state_path, &state, source_len, source_mtime_ns).at_stage(
+        Stage::Streaming {
+            bytes_done: state.bytes_done,
+        },
+    )?;
+
+    let mut buffer = Zeroizing::new(vec![0u8; opts.buffer_size]);
+    let mut since_checkpoint = 0;
+    loop {
+        let len = read_chunk(&mut source, &mut buffer)
+            .context("读取源文件失败")
+            .at_stage(Stage::Streaming {
+                bytes_done: state.bytes_done,
+            })?;
+        if len == 0 {
+            break;
+        }
+        let chunk = &mut buffer[..len];
+        state.cipher.apply_keystream(chunk);
+        state.mac.update(chunk);
+        state
+            .part
+            .write_all(chunk)
+            .context("写入临时输出文件失败")
+            .at_stage(Stage::Streaming {
+                bytes_done: state.bytes_done,
+            })?;
+        state.bytes_done += len as u64;
+        since_checkpoint += len as u64;
+        if let Some(sink) = progress {
+            sink.on_event(&ProgressEvent::BytesProcessed { bytes: len as u64 });
+        }
+        if let Some(limiter) = &mut limiter {
+            limiter.throttle(len as u64);
+        }
+        if since_checkpoint >= checkpoint_interval {
+            write_checkpoint(&state_path, &state, source_len, source_mtime_ns).at_stage(
+                Stage::Streaming {
+                    bytes_done: state.bytes_done,
+                },
+            )?;
+            since_checkpoint = 0;
+        }
+    }
+
+    // 写入认证标签，同步并关闭文件（释放锁）后再提交
+    let Progress { mut part, mac, .. } = state;
+    let tag = mac.finalize().into_bytes();
+    part.write_all(&tag).at_stage(Stage::Finalize)?;
+    part.sync_all()
+        .context("同步临时输出文件失败")
+        .at_stage(Stage::Finalize)?;
+    drop(part);
+    commit_partial_file(&part_path, &target_path).at_stage(Stage::Finalize)?;
+    if let Err(e) = fs::remove_file(&state_path) {
+        log::warn!("无法删除检查点文件 {}: {}", state_path.display(), e);
+    }
+    log::info!("--- ✅ 加密成功! ---");
+    Ok(())
+}
+
+/// 从头开始：派生密钥，创建（或截断）`.part` 文件并写入文件头。
+fn start_fresh(
+    part_path: &Path,
+    header: &FileHeader,
+    opts: &EncryptionOptions,
+) -> Result<Progress> {
+    let (header, master_key) =
+        derive_encryption_key(header, opts).at_stage(Stage::KeyDerivation)?;
+    let (cipher, mut mac, checkpoint_key) = init_cipher_and_mac(&header, &master_key)?;
+    let header_bytes = header.encode().at_stage(Stage::HeaderParse)?;
+    let mut part = create_partial_file(part_path).at_stage(Stage::HeaderParse)?;
+    part.write_all(&header_bytes).at_stage(Stage::HeaderParse)?;
+    mac.update(&header_bytes);
+    Ok(Progress {
+        part,
+        cipher,
+        mac,
+        checkpoint_key,
+        header_len: header_bytes.len() as u64,
+        bytes_done: 0,
+    })
+}
+
+/// 从检查点继续：验证 `.part` 文件与检查点一致，截掉检查点之后的数据并定位加密器。
+fn resume_from_checkpoint(
+    part_path: &Path,
+    original_filename: &str,
+    checkpoint: &Checkpoint,
+    opts: &EncryptionOptions,
+) -> Result<Progress> {
+    let mut part = open_partial_file(part_path).at_stage(Stage::HeaderParse)?;
+    let durable_len = checkpoint.header_len + checkpoint.bytes_done;
+    let part_len = part.metadata().at_stage(Stage::HeaderParse)?.len();
+    if part_len < durable_len {
+        bail!(
+            "临时输出文件 {} 只有 {} 字节，短于检查点记录的 {} 字节，无法续传",
+            part_path.display(),
+            part_len,
+            durable_len
+        );
+    }
+
+    let header = FileHeader::read_from(&mut part).at_stage(Stage::HeaderParse)?;
+    if !header.is_authenticated()
+        || header.len() != checkpoint.header_len
+        || header.original_filename != original_filename
+    {
+        bail!(
+            "临时输出文件 {} 的文件头与检查点不符，无法续传",
+            part_path.display()
+        );
+    }
+    let (header, master_key) =
+        derive_encryption_key(&header, opts).at_stage(Stage::KeyDerivation)?;
+    let (mut cipher, mut mac, checkpoint_key) = init_cipher_and_mac(&header, &master_key)?;
+
+    // 重新计算检查点之前内容的 HMAC，重建 MAC 状态并确认数据没有被修改
+    part.seek(SeekFrom::Start(0)).at_stage(Stage::HeaderParse)?;
+    let mut buffer = vec![0u8; opts.buffer_size];
+    let mut remaining = durable_len;
+    while remaining > 0 {
+        let len = remaining.min(buffer.len() as u64) as usize;
+        part.read_exact(&mut buffer[..len])
+            .context("读取临时输出文件失败")
+            .at_stage(Stage::HeaderParse)?;
+        mac.update(&buffer[..len]);
+        remaining -= len as u64;
+    }
+    let expected = checkpoint_auth(&checkpoint_key, checkpoint, &mac)?;
+    if !bool::from(expected.as_bytes().ct_eq(checkpoint.auth.as_bytes())) {
+        return Err(FeroxError::AuthenticationFailed)
+            .context("检查点与临时输出文件不符：密码或密钥文件错误，或者 .part 文件已被修改")
+            .at_stage(Stage::KeyDerivation);
+    }
+
+    // 丢弃检查点之后写入、但没有记录在检查点中的数据
+    part.set_len(durable_len)
+        .context("无法截断临时输出文件")
+        .at_stage(Stage::HeaderParse)?;
+    part.seek(SeekFrom::Start(durable_len))
+        .at_stage(Stage::HeaderParse)?;
+    cipher.seek(checkpoint.bytes_done);
+    Ok(Progress {
+        part,
+        cipher,
+        mac,
+        checkpoint_key,
+        header_len: checkpoint.header_len,
+        bytes_done: checkpoint.bytes_done,
+    })
+}
+
+/// 用主密钥初始化 AES-CTR 加密器（位于密钥流开头）和 HMAC，并派生检查点密钥。
+fn init_cipher_and_mac(
+    header: &FileHeader,
+    master_key: &SecureBuffer,
+) -> Result<(Aes256Ctr, HmacSha256, Zeroizing<[u8; 32]>)> {
+    let (aes_key, hmac_key) = master_key.split_at(AES_KEY_LEN);
+    let cipher = Aes256Ctr::new(aes_key.into(), &header.iv.into());
+    let mac = HmacSha256::new_from_slice(hmac_key)
+        .context("无法创建HMAC实例")
+        .at_stage(Stage::KeyDerivation)?;
+    let mut derive = mac.clone();
+    derive.update(RESUME_CHECKPOINT_CONTEXT);
+    let checkpoint_key = Zeroizing::new(derive.finalize().into_bytes().into());
+    Ok((cipher, mac, checkpoint_key))
+}
+
+/// 计算检查点的认证值：用检查点密钥对检查点的各个字段和到 `bytes_done` 为止的文件 HMAC
+/// 计算 HMAC（小写十六进制）。
+///
+/// 文件 HMAC 只作为输入参与计算，不会出现在检查点文件中。
+fn checkpoint_auth(
+    checkpoint_key: &[u8; 32],
+    checkpoint: &Checkpoint,
+    mac: &HmacSha256,
+) -> Result<String> {
+    let mut auth = HmacSha256::new_from_slice(checkpoint_key).context("无法创建HMAC实例")?;
+    auth.update(&checkpoint.version.to_le_bytes());
+    auth.update(&checkpoint.source_len.to_le_bytes());
+    match checkpoint.source_mtime_ns {
+        Some(mtime) => {
+            auth.update(&[1]);
+            auth.update(&mtime.to_le_bytes());
+        }
+        None => auth.update(&[0]),
+    }
+    auth.update(&checkpoint.header_len.to_le_bytes());
+    auth.update(&checkpoint.bytes_done.to_le_bytes());
+    auth.update(&mac.clone().finalize().into_bytes());
+    Ok(hex(&auth.finalize().into_bytes()))
+}
+
+/// 把 `.part` 文件同步到磁盘，然后原子地更新检查点文件。
+///
+/// 先同步数据再写检查点，检查点记录的位置之前的数据总是已经持久化的。
+fn write_checkpoint(
+    state_path: &Path,
+    state: &Progress,
+    source_len: u64,
+    source_mtime_ns: Option<u64>,
+) -> Result<()> {
+    state.part.sync_data().context("同步临时输出文件失败")?;
+    let mut checkpoint = Checkpoint {
+        version: CHECKPOINT_VERSION,
+        source_len,
+        source_mtime_ns,
+        header_len: state.header_len,
+        bytes_done: state.bytes_done,
+        auth: String::new(),
+    };
+    checkpoint.auth = checkpoint_auth(&state.checkpoint_key, &checkpoint, &state.mac)?;
+
+    let mut temp_path = state_path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+    let mut file = File::create(&temp_path)
+        .with_context(|| format!("无法创建检查点文件: {}", temp_path.display()))?;
+    serde_json::to_writer_pretty(&mut file, &checkpoint)?;
+    file.sync_all().context("同步检查点文件失败")?;
+    drop(file);
+    fs::rename(&temp_path, state_path)
+        .with_context(|| format!("无法更新检查点文件: {}", state_path.display()))
+}
+
+/// 读取并检查检查点文件。
+fn read_checkpoint(state_path: &Path) -> Result<Checkpoint> {
+    let data = fs::read(state_path)
+        .with_context(|| format!("无法读取检查点文件: {}", state_path.display()))?;
+    let checkpoint: Checkpoint = serde_json::from_slice(&data)
+        .with_context(|| format!("检查点文件已损坏: {}", state_path.display()))?;
+    if checkpoint.version != CHECKPOINT_VERSION {
+        bail!(
+            "不支持的检查点文件版本 {}: {}",
+            checkpoint.version,
+            state_path.display()
+        );
+    }
+    Ok(checkpoint)
+}
+
+/// 把处理速度限制在每秒 `bytes_per_sec` 字节以内。
+struct RateLimiter {
+    bytes_per_sec: u64,
+    started: Instant,
+    bytes: u64,
+}
+
+impl RateLimiter {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            started: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// 记录刚处理的 `len` 字节；处理得比上限快时休眠，直到平均速度回到上限以内。
+    fn throttle(&mut self, len: u64) {
+        self.bytes += len;
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.bytes_per_sec as f64);
+        if let Some(ahead) = due.checked_sub(self.started.elapsed()) {
+            thread::sleep(ahead);
+        }
+    }
+}
+
+/// 尽量读满 `buffer`，返回读取的字节数；只有到达文件末尾时才少于缓冲区长度。
+fn read_chunk<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// 源文件的修改时间（Unix 纳秒）。
+fn mtime_ns(metadata: &Metadata) -> Option<u64> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
+/// 小写十六进制编码。
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_keeps_average_below_limit() {
+        let mut limiter = RateLimiter::new(1024 * 1024);
+        let started = Instant::now();
+        for _ in 0..4 {
+            limiter.throttle(128 * 1024);
+        }
+        // 512 KiB 在 1 MiB/s 的限制下至少需要半秒
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
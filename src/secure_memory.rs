@@ -0,0 +1,299 @@
+// src/secure_memory.rs
+
+//! # 安全内存模块 (Secure Memory)
+//!
+//! 提供 [`SecureBuffer`]：保存密码材料和密钥的堆缓冲区，释放时先清零。
+//! 启用内存锁定 (`--lock-memory`) 时，缓冲区在整个生命周期内被 `mlock`（Windows 上为
+//! `VirtualLock`），不会被换出到交换分区。锁定失败（例如 `RLIMIT_MEMLOCK` 太低）时只发出
+//! 一次警告，操作照常进行；严格模式 (`--strict`) 下锁定失败会使操作失败。
+//!
+//! 内存锁定以页为单位，解锁一个缓冲区会同时解锁与它共享内存页的其他数据。因此要求锁定的缓冲区
+//! 单独按页对齐分配、长度补齐到整页，不与任何其他分配共享内存页，释放时解锁不会影响其他缓冲区。
+//!
+//! 注意：AES 和 HMAC 实例内部展开的密钥副本不在锁定的缓冲区中，仍可能被换出。
+//!
+//! *[`SecureBuffer`] holds password material and keys, zeroizing them on drop. With memory
+//! locking enabled the buffer is `mlock`ed (`VirtualLock` on Windows) for its lifetime;
+//! if locking fails a single warning is logged and the operation continues.*
+
+use crate::error::FeroxError;
+use std::alloc::{self, Layout};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use zeroize::Zeroize;
+
+/// 是否已经发出过锁定失败的警告（每个进程只警告一次，避免批量处理时刷屏）。
+static LOCK_FAILURE_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// # 安全缓冲区 (Secure Buffer)
+///
+/// 固定长度的堆缓冲区，释放时清零；按需锁定在物理内存中。
+pub struct SecureBuffer {
+    /// 分配的起始地址；分配之后地址不再变化，锁定的始终是同一段内存。长度为零时不分配。
+    ptr: NonNull<u8>,
+    /// 缓冲区内容的长度。
+    len: usize,
+    /// 分配的布局。要求锁定时按页对齐并补齐到整页，大小可能超过 `len`。
+    layout: Layout,
+    /// 缓冲区是否已成功锁定。
+    locked: bool,
+}
+
+// SAFETY: `SecureBuffer` 独占它的分配，与 `Box<[u8]>` 一样可以在线程之间转移和共享。
+unsafe impl Send for SecureBuffer {}
+// SAFETY: 同上，`&SecureBuffer` 只提供只读访问。
+unsafe impl Sync for SecureBuffer {}
+
+impl SecureBuffer {
+    /// 创建一个长度为 `len`、内容全为零的缓冲区；`lock` 为 `true` 时尝试锁定它。
+    pub fn new(len: usize, lock: bool) -> Self {
+        Self::with_locker(len, lock, lock_region)
+    }
+
+    /// 创建一个内容为 `bytes` 副本的缓冲区；`lock` 为 `true` 时在复制之前锁定它。
+    ///
+    /// 调用者负责擦除 `bytes` 本身。
+    pub fn from_slice(bytes: &[u8], lock: bool) -> Self {
+        let mut buffer = Self::new(bytes.len(), lock);
+        buffer.copy_from_slice(bytes);
+        buffer
+    }
+
+    /// 缓冲区是否已被锁定在物理内存中。
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// 使用给定的锁定函数创建缓冲区，锁定失败时发出警告并继续使用未锁定的缓冲区。
+    ///
+    /// 要求锁定时，传给 `locker` 的是整个按页对齐的分配，而不只是前 `len` 字节。
+    fn with_locker(len: usize, lock: bool, locker: fn(&[u8]) -> io::Result<()>) -> Self {
+        let layout = if lock {
+            let page = page_size();
+            Layout::from_size_align(len.next_multiple_of(page), page)
+        } else {
+            Layout::array::<u8>(len)
+        }
+        .expect("缓冲区长度超出了可分配的范围");
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            // SAFETY: `layout` 的大小不为零。
+            let raw = unsafe { alloc::alloc_zeroed(layout) };
+            NonNull::new(raw).unwrap_or_else(|| alloc::handle_alloc_error(layout))
+        };
+        let mut buffer = Self {
+            ptr,
+            len,
+            layout,
+            locked: false,
+        };
+        buffer.locked = lock
+            && len != 0
+            && match locker(buffer.allocation()) {
+                Ok(()) => true,
+                Err(e) => {
+                    if !LOCK_FAILURE_WARNED.swap(true, Ordering::Relaxed) {
+                        log::warn!(
+                            "⚠️  无法锁定密钥材料所在的内存 ({e})，密钥可能被换出到磁盘。\
+                             可以提高 RLIMIT_MEMLOCK (`ulimit -l`) 后重试。"
+                        );
+                    }
+                    false
+                }
+            };
+        buffer
+    }
+
+    /// 整个分配，包括补齐到整页的部分。
+    fn allocation(&self) -> &[u8] {
+        // SAFETY: `ptr` 指向 `layout.size()` 字节已清零初始化的内存（大小为零时是悬空但对齐的指针）。
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+
+    /// 整个分配的可变引用。
+    fn allocation_mut(&mut self) -> &mut [u8] {
+        // SAFETY: 同 [`SecureBuffer::allocation`]，并且 `&mut self` 保证独占访问。
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Deref for SecureBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.allocation()[..self.len]
+    }
+}
+
+impl DerefMut for SecureBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.allocation_mut()[..len]
+    }
+}
+
+impl Drop for SecureBuffer {
+    fn drop(&mut self) {
+        // 先清零再解锁，保证清零之前内容不会被换出；
+        // 锁定的内存页只属于这个缓冲区，解锁整个分配不会影响其他数据
+        self.allocation_mut().zeroize();
+        if self.locked {
+            if let Err(e) = unlock_region(self.allocation()) {
+                log::debug!("解锁内存失败: {e}");
+            }
+        }
+        if self.layout.size() != 0 {
+            // SAFETY: `ptr` 由 `alloc_zeroed(layout)` 分配，且只释放这一次。
+            unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+        }
+    }
+}
+
+/// 内存页的大小，锁定的缓冲区按它对齐和补齐。
+#[cfg(unix)]
+fn page_size() -> usize {
+    // SAFETY: `sysconf` 没有内存安全方面的前提条件。
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    usize::try_from(size)
+        .ok()
+        .filter(|size| size.is_power_of_two())
+        .unwrap_or(4096)
+}
+
+/// 内存页的大小，锁定的缓冲区按它对齐和补齐（Windows 支持的架构上都是 4 KiB）。
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+/// 要求锁定内存 (`lock`) 的严格模式下，任何一个非空缓冲区未能锁定都返回
+/// [`FeroxError::StrictViolation`]；非严格模式下锁定失败已经在创建缓冲区时警告过。
+pub(crate) fn ensure_locked(
+    buffers: &[&SecureBuffer],
+    lock: bool,
+    strict: bool,
+) -> Result<(), FeroxError> {
+    if lock
+        && strict
+        && buffers
+            .iter()
+            .any(|buffer| !buffer.is_empty() && !buffer.is_locked())
+    {
+        return Err(FeroxError::StrictViolation(
+            "严格模式: 无法锁定密钥材料所在的内存，密钥可能被换出到磁盘".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 把 `region` 所在的内存页锁定在物理内存中。
+#[cfg(unix)]
+fn lock_region(region: &[u8]) -> io::Result<()> {
+    // SAFETY: `region` 是一段有效的、已分配的内存。
+    let ret = unsafe { libc::mlock(region.as_ptr().cast(), region.len()) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 解除 [`lock_region`] 的锁定。
+#[cfg(unix)]
+fn unlock_region(region: &[u8]) -> io::Result<()> {
+    // SAFETY: `region` 是一段有效的、已分配的内存。
+    let ret = unsafe { libc::munlock(region.as_ptr().cast(), region.len()) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 把 `region` 所在的内存页锁定在物理内存中。
+#[cfg(windows)]
+fn lock_region(region: &[u8]) -> io::Result<()> {
+    use windows_sys::Win32::System::Memory::VirtualLock;
+    // SAFETY: `region` 是一段有效的、已分配的内存。
+    if unsafe { VirtualLock(region.as_ptr().cast(), region.len()) } != 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 解除 [`lock_region`] 的锁定。
+#[cfg(windows)]
+fn unlock_region(region: &[u8]) -> io::Result<()> {
+    use windows_sys::Win32::System::Memory::VirtualUnlock;
+    // SAFETY: `region` 是一段有效的、已分配的内存。
+    if unsafe { VirtualUnlock(region.as_ptr().cast(), region.len()) } != 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 不支持内存锁定的平台。
+#[cfg(not(any(unix, windows)))]
+fn lock_region(_region: &[u8]) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// 不支持内存锁定的平台。
+#[cfg(not(any(unix, windows)))]
+fn unlock_region(_region: &[u8]) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secure_buffer_lock_path_runs() {
+        let mut buffer = SecureBuffer::from_slice(b"master key material", true);
+        assert_eq!(&buffer[..], b"master key material");
+        buffer[0] = b'M';
+        assert_eq!(buffer[0], b'M');
+        // 是否真正锁定取决于 RLIMIT_MEMLOCK 和权限；两种情况都必须可用
+        let _ = buffer.is_locked();
+
+        let unlocked = SecureBuffer::new(32, false);
+        assert!(!unlocked.is_locked());
+        assert_eq!(&unlocked[..], &[0u8; 32]);
+    }
+
+    #[test]
+    fn test_locked_buffers_own_whole_pages() {
+        let page = page_size();
+        let first = SecureBuffer::from_slice(b"first key", true);
+        let second = SecureBuffer::from_slice(b"second key", true);
+        for buffer in [&first, &second] {
+            assert_eq!(buffer.as_ptr() as usize % page, 0);
+            assert_eq!(buffer.allocation().len(), page);
+        }
+        assert_eq!(first.len(), 9);
+
+        // 释放一个缓冲区（并解锁它的页）不影响另一个
+        drop(first);
+        assert_eq!(&second[..], b"second key");
+
+        let empty = SecureBuffer::new(0, true);
+        assert!(empty.is_empty());
+        assert!(!empty.is_locked());
+    }
+
+    #[test]
+    fn test_secure_buffer_degrades_when_lock_is_denied() {
+        let denied = |_: &[u8]| Err(io::Error::from_raw_os_error(libc::EPERM));
+        let mut buffer = SecureBuffer::with_locker(64, true, denied);
+        assert!(!buffer.is_locked());
+        buffer.copy_from_slice(&[7u8; 64]);
+        assert_eq!(&buffer[..], &[7u8; 64]);
+        assert!(LOCK_FAILURE_WARNED.load(Ordering::Relaxed));
+    }
+}
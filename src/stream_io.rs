@@ -0,0 +1,343 @@
+// src/stream_io.rs
+
+//! # 文件读写模块 (File Stream I/O Module)
+//!
+//! 加解密流程读取源文件和写入输出文件的方式。默认使用带缓冲的读写；
+//! 启用 `uring` 特性的 Linux 构建在文件大于一个数据块、且内核支持 io_uring 时，
+//! 改用 [`crate::uring`] 的预读读取器和后台写入器，否则静默回退到缓冲读写。
+//! 两种方式产生的输出完全相同。
+//!
+//! 加密时读取的源文件和解密时写入的输出都是明文，因此缓冲读写不使用标准库的
+//! `BufReader`/`BufWriter`（无法在释放前擦除它们的内部缓冲区），而是使用
+//! [`ScrubbedReader`] 和 [`ScrubbedWriter`]：缓冲区中写入过数据的部分在释放前清零。
+//!
+//! *How the encryption and decryption flows read source files and write outputs:
+//! buffered by default, or io_uring read-ahead / write-behind on Linux builds with the
+//! `uring` feature when the kernel supports it. Output is identical either way. The
+//! buffered reader and writer zeroize the used part of their buffers when dropped, since
+//! they carry plaintext.*
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+use crate::uring::{UringReader, UringWriter};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use zeroize::Zeroize;
+
+/// 读取源文件的方式。
+pub(crate) enum InputFile {
+    /// 普通的缓冲读取。
+    Buffered(ScrubbedReader),
+    /// io_uring 预读。
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    Uring(Box<UringReader>),
+}
+
+impl InputFile {
+    /// 以 `buffer_size` 为数据块大小打开长度为 `len` 的源文件（必须位于偏移量 0）。
+    pub(crate) fn open(file: File, len: u64, buffer_size: usize) -> Self {
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        if len > buffer_size as u64 {
+            if let Some(reader) = UringReader::new(&file, buffer_size) {
+                log::debug!("使用 io_uring 读取源文件");
+                return Self::Uring(Box::new(reader));
+            }
+        }
+        #[cfg(not(all(feature = "uring", target_os = "linux")))]
+        let _ = len;
+        Self::Buffered(ScrubbedReader::with_capacity(buffer_size, file))
+    }
+}
+
+impl Read for InputFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Buffered(reader) => reader.read(buf),
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            Self::Uring(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// 写入输出文件的方式。调用者必须在丢弃之前调用 [`Write::flush`]。
+pub(crate) enum OutputFile {
+    /// 普通的缓冲写入。
+    Buffered(ScrubbedWriter),
+    /// io_uring 后台写入。
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    Uring(Box<UringWriter>),
+}
+
+impl OutputFile {
+    /// 以 `buffer_size` 为数据块大小写入新创建的空文件，预计写入 `expected_len` 字节。
+    pub(crate) fn create(file: File, expected_len: u64, buffer_size: usize) -> Self {
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        if expected_len > buffer_size as u64 {
+            if let Some(writer) = UringWriter::new(&file, buffer_size) {
+                log::debug!("使用 io_uring 写入输出文件");
+                return Self::Uring(Box::new(writer));
+            }
+        }
+        #[cfg(not(all(feature = "uring", target_os = "linux")))]
+        let _ = expected_len;
+        Self::Buffered(ScrubbedWriter::with_capacity(buffer_size, file))
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Buffered(writer) => writer.write(buf),
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            Self::Uring(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Buffered(writer) => writer.flush(),
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            Self::Uring(writer) => writer.flush(),
+        }
+    }
+}
+
+/// 释放前清零的缓冲区，只记录并清零曾经写入过数据的前缀，
+/// 避免在只用到一小部分时触碰整个缓冲区。
+struct ScrubbedBuffer<B: AsRef<[u8]> + AsMut<[u8]> = Vec<u8>> {
+    bytes: B,
+    /// 写入过数据的前缀长度。
+    dirty: usize,
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> ScrubbedBuffer<B> {
+    fn new(bytes: B) -> Self {
+        Self { bytes, dirty: 0 }
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.as_ref().len()
+    }
+
+    /// 记录前 `len` 字节已经写入过数据。
+    fn mark_dirty(&mut self, len: usize) {
+        self.dirty = self.dirty.max(len);
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Drop for ScrubbedBuffer<B> {
+    fn drop(&mut self) {
+        self.bytes.as_mut()[..self.dirty].zeroize();
+    }
+}
+
+/// 替代 `BufReader<File>` 的缓冲读取器，释放时清零缓冲区。
+///
+/// 与 `BufReader` 一样，缓冲区为空且请求的长度不小于缓冲区时直接读入调用者的缓冲区。
+pub(crate) struct ScrubbedReader {
+    file: File,
+    buffer: ScrubbedBuffer,
+    /// 缓冲区中下一个未读字节的位置。
+    position: usize,
+    /// 缓冲区中有效数据的长度。
+    filled: usize,
+}
+
+impl ScrubbedReader {
+    pub(crate) fn with_capacity(capacity: usize, file: File) -> Self {
+        Self {
+            file,
+            buffer: ScrubbedBuffer::new(vec![0u8; capacity]),
+            position: 0,
+            filled: 0,
+        }
+    }
+}
+
+impl Read for ScrubbedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.filled {
+            if buf.len() >= self.buffer.capacity() {
+                return self.file.read(buf);
+            }
+            self.filled = self.file.read(&mut self.buffer.bytes)?;
+            self.position = 0;
+            self.buffer.mark_dirty(self.filled);
+        }
+        let available = &self.buffer.bytes[self.position..self.filled];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// 替代 `BufWriter<File>` 的缓冲写入器，释放时清零缓冲区。
+///
+/// 与 `BufWriter` 不同，丢弃时不会写出缓冲的数据，调用者必须先调用 [`Write::flush`]。
+pub(crate) struct ScrubbedWriter {
+    file: File,
+    buffer: ScrubbedBuffer,
+    /// 缓冲区中尚未写出的字节数。
+    len: usize,
+}
+
+impl ScrubbedWriter {
+    pub(crate) fn with_capacity(capacity: usize, file: File) -> Self {
+        Self {
+            file,
+            buffer: ScrubbedBuffer::new(vec![0u8; capacity]),
+            len: 0,
+        }
+    }
+
+    /// 写出缓冲区中的全部数据。
+    fn flush_buffer(&mut self) -> io::Result<()> {
+        if self.len > 0 {
+            self.file.write_all(&self.buffer.bytes[..self.len])?;
+            self.len = 0;
+        }
+        Ok(())
+    }
+}
+
+impl Write for ScrubbedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.len + buf.len() > self.buffer.capacity() {
+            self.flush_buffer()?;
+        }
+        if buf.len() >= self.buffer.capacity() {
+            return self.file.write(buf);
+        }
+        let end = self.len + buf.len();
+        self.buffer.bytes[self.len..end].copy_from_slice(buf);
+        self.buffer.mark_dirty(end);
+        self.len = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buffer()?;
+        self.file.flush()
+    }
+}
+
+/// 一个文件在加密或解密时实际读取和写入的字节数。
+///
+/// 由流式处理中经过读写器的字节数统计得出，而不是操作前的文件元数据，
+/// 因此包括文件头和认证标签，也反映了处理期间文件大小的变化。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ByteCounts {
+    /// 从输入文件读取的字节数。
+    pub(crate) read: u64,
+    /// 写入输出文件的字节数。
+    pub(crate) written: u64,
+}
+
+/// 统计经过的字节数的读写包装。
+pub(crate) struct Counting<T> {
+    inner: T,
+    bytes: u64,
+}
+
+impl<T> Counting<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self { inner, bytes: 0 }
+    }
+
+    /// 到目前为止读取或写入的字节数。
+    pub(crate) fn count(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// 在自身被释放时记录内容是否已经全部为零的存储，用来观察 [`ScrubbedBuffer`] 的清零。
+    struct DropProbe {
+        bytes: Vec<u8>,
+        zeroed_on_drop: Rc<Cell<Option<bool>>>,
+    }
+
+    impl AsRef<[u8]> for DropProbe {
+        fn as_ref(&self) -> &[u8] {
+            &self.bytes
+        }
+    }
+
+    impl AsMut<[u8]> for DropProbe {
+        fn as_mut(&mut self) -> &mut [u8] {
+            &mut self.bytes
+        }
+    }
+
+    impl Drop for DropProbe {
+        fn drop(&mut self) {
+            self.zeroed_on_drop
+                .set(Some(self.bytes.iter().all(|&byte| byte == 0)));
+        }
+    }
+
+    #[test]
+    fn test_scrubbed_buffer_zeroizes_before_release() {
+        let zeroed_on_drop = Rc::new(Cell::new(None));
+        let mut buffer = ScrubbedBuffer::new(DropProbe {
+            bytes: vec![0u8; 64],
+            zeroed_on_drop: Rc::clone(&zeroed_on_drop),
+        });
+        buffer.bytes.as_mut()[..11].copy_from_slice(b"secret data");
+        buffer.mark_dirty(11);
+        drop(buffer);
+        assert_eq!(zeroed_on_drop.get(), Some(true));
+    }
+
+    #[test]
+    fn test_scrubbed_reader_and_writer_round_trip() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("plain.bin");
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+
+        // 小于、等于和大于缓冲区的写入混合在一起
+        let mut writer = ScrubbedWriter::with_capacity(1024, File::create(&path)?);
+        for piece in data[..2100].chunks(700) {
+            writer.write_all(piece)?;
+        }
+        writer.write_all(&data[2100..3124])?;
+        writer.write_all(&data[3124..])?;
+        writer.flush()?;
+        drop(writer);
+        assert!(std::fs::read(&path)? == data);
+
+        let mut reader = ScrubbedReader::with_capacity(1024, File::open(&path)?);
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest)?;
+        assert_eq!(&header[..], &data[..5]);
+        assert!(rest == data[5..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,443 @@
+// src/uring.rs
+
+//! # io_uring 文件读写模块 (io_uring File I/O Module)
+//!
+//! 只在启用 `uring` 特性的 Linux 构建中编译。[`UringReader`] 在调用者处理当前数据块时，
+//! 已经向内核提交了后续数据块的读取；[`UringWriter`] 提交写入后立即返回，只在在途写入
+//! 达到 [`URING_QUEUE_DEPTH`] 个时才等待最早的一个完成。这样读取、加解密和写入可以重叠，
+//! 存储设备不会在两次系统调用之间空闲。
+//!
+//! 内核不支持 io_uring（或读写操作码）时，构造函数返回 `None`，调用者改用普通的缓冲读写。
+//!
+//! *Compiled only on Linux with the `uring` feature. The reader keeps reads of the next
+//! chunks in flight while the caller processes the current one; the writer submits writes
+//! and only waits once [`URING_QUEUE_DEPTH`] are in flight. Constructors return `None`
+//! when the kernel lacks io_uring, so callers fall back to buffered I/O.*
+
+use crate::constants::URING_QUEUE_DEPTH;
+use io_uring::{opcode, types, IoUring, Probe};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use zeroize::Zeroize;
+
+/// 一个已提交的读写操作。缓冲区在操作完成之前必须保持存活且不能移动其堆内存。
+struct Pending {
+    /// 提交时的 `user_data`，用于把完成事件对应到操作。
+    id: u64,
+    /// 操作的文件偏移量。
+    offset: u64,
+    buffer: Vec<u8>,
+    /// 完成事件的结果；`None` 表示内核还没有完成该操作。
+    result: Option<i32>,
+}
+
+/// 提交队列和在途操作，读取端和写入端共用。
+struct Ring {
+    ring: IoUring,
+    /// 按提交顺序排列的在途操作。
+    in_flight: VecDeque<Pending>,
+    next_id: u64,
+}
+
+impl Ring {
+    /// 创建 io_uring 实例，并确认内核支持 `opcode`；不支持时返回 `None`。
+    fn new(opcode: u8) -> Option<Self> {
+        let ring = match IoUring::new(URING_QUEUE_DEPTH as u32) {
+            Ok(ring) => ring,
+            Err(e) => {
+                log::debug!("io_uring 不可用，改用缓冲读写: {}", e);
+                return None;
+            }
+        };
+        let mut probe = Probe::new();
+        if ring.submitter().register_probe(&mut probe).is_err() || !probe.is_supported(opcode) {
+            log::debug!("内核不支持所需的 io_uring 操作，改用缓冲读写");
+            return None;
+        }
+        Some(Self {
+            ring,
+            in_flight: VecDeque::with_capacity(URING_QUEUE_DEPTH),
+            next_id: 0,
+        })
+    }
+
+    /// 提交一个操作。`entry` 必须引用 `buffer` 的堆内存。
+    ///
+    /// # Safety
+    ///
+    /// `entry` 中的指针必须指向 `buffer` 的堆内存，且长度不超过缓冲区。
+    unsafe fn submit(
+        &mut self,
+        entry: io_uring::squeue::Entry,
+        offset: u64,
+        buffer: Vec<u8>,
+    ) -> io::Result<()> {
+        let id = self.next_id;
+        self.next_id += 1;
+        // SAFETY: 缓冲区随后被放入 `in_flight`，在对应的完成事件到达之前不会被释放；
+        // 移动 `Vec` 本身不会移动它的堆内存。
+        unsafe {
+            self.ring
+                .submission()
+                .push(&entry.user_data(id))
+                .map_err(|_| io::Error::other("io_uring 提交队列已满"))?;
+        }
+        self.in_flight.push_back(Pending {
+            id,
+            offset,
+            buffer,
+            result: None,
+        });
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    /// 等待最早提交的操作完成并取出它。
+    fn wait_front(&mut self) -> io::Result<Option<Pending>> {
+        let Some(front) = self.in_flight.front() else {
+            return Ok(None);
+        };
+        let front_id = front.id;
+        while self.in_flight[0].result.is_none() {
+            self.ring.submit_and_wait(1)?;
+            for cqe in self.ring.completion() {
+                let index = (cqe.user_data() - front_id) as usize;
+                if let Some(pending) = self.in_flight.get_mut(index) {
+                    pending.result = Some(cqe.result());
+                }
+            }
+        }
+        Ok(self.in_flight.pop_front())
+    }
+
+    /// 等待所有在途操作完成，丢弃结果并清零它们的缓冲区。
+    ///
+    /// 内核仍可能访问在途缓冲区时不能释放它们；等待失败时宁可泄漏缓冲区。
+    fn drain(&mut self) {
+        while !self.in_flight.is_empty() {
+            if let Ok(Some(mut pending)) = self.wait_front() {
+                pending.buffer.zeroize();
+            } else {
+                for pending in self.in_flight.drain(..) {
+                    std::mem::forget(pending.buffer);
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// 把完成事件的结果转换为字节数。
+fn completion_result(result: Option<i32>) -> io::Result<usize> {
+    match result {
+        Some(n) if n >= 0 => Ok(n as usize),
+        Some(errno) => Err(io::Error::from_raw_os_error(-errno)),
+        None => Err(io::Error::other("io_uring 操作尚未完成")),
+    }
+}
+
+/// # io_uring 预读读取器 (Read-ahead Reader)
+///
+/// 从文件开头顺序读取，始终保持最多 [`URING_QUEUE_DEPTH`] 个 `chunk_size` 大小的读取在途。
+pub(crate) struct UringReader {
+    ring: Ring,
+    file: File,
+    chunk_size: usize,
+    /// 下一个要提交的读取的偏移量。
+    next_offset: u64,
+    /// 已经读到文件末尾，不再提交新的读取。
+    reached_eof: bool,
+    /// 正在被调用者消费的数据块。
+    current: Vec<u8>,
+    position: usize,
+    spare: Vec<Vec<u8>>,
+}
+
+impl UringReader {
+    /// 为 `file`（必须位于偏移量 0）创建预读读取器；内核不支持 io_uring 时返回 `None`。
+    pub(crate) fn new(file: &File, chunk_size: usize) -> Option<Self> {
+        let ring = Ring::new(opcode::Read::CODE)?;
+        let file = file.try_clone().ok()?;
+        Some(Self {
+            ring,
+            file,
+            chunk_size,
+            next_offset: 0,
+            reached_eof: false,
+            current: Vec::new(),
+            position: 0,
+            spare: Vec::new(),
+        })
+    }
+
+    /// 提交读取，直到在途数量达到上限。
+    fn fill_queue(&mut self) -> io::Result<()> {
+        while !self.reached_eof && self.ring.in_flight.len() < URING_QUEUE_DEPTH {
+            let mut buffer = self.spare.pop().unwrap_or_default();
+            buffer.resize(self.chunk_size, 0);
+            let entry = opcode::Read::new(
+                types::Fd(self.file.as_raw_fd()),
+                buffer.as_mut_ptr(),
+                buffer.len() as u32,
+            )
+            .offset(self.next_offset)
+            .build();
+            // SAFETY: `entry` 指向 `buffer` 的全部内存。
+            unsafe { self.ring.submit(entry, self.next_offset, buffer)? };
+            self.next_offset += self.chunk_size as u64;
+        }
+        Ok(())
+    }
+
+    /// 取出下一个数据块放入 `current`；返回 `false` 表示已经读完。
+    fn next_chunk(&mut self) -> io::Result<bool> {
+        self.fill_queue()?;
+        let Some(mut pending) = self.ring.wait_front()? else {
+            return Ok(false);
+        };
+        let mut len = completion_result(pending.result)?;
+        if len == 0 {
+            // 文件末尾：之后提交的读取也只会返回 0
+            self.reached_eof = true;
+            self.spare.push(pending.buffer);
+            return Ok(false);
+        }
+        // 短读取：同步补齐剩余部分，保证后续已提交的读取与数据块衔接
+        while len < pending.buffer.len() {
+            let n = self
+                .file
+                .read_at(&mut pending.buffer[len..], pending.offset + len as u64)?;
+            if n == 0 {
+                self.reached_eof = true;
+                break;
+            }
+            len += n;
+        }
+        pending.buffer.truncate(len);
+        let previous = std::mem::replace(&mut self.current, pending.buffer);
+        self.spare.push(previous);
+        self.position = 0;
+        Ok(true)
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.current.len() && !self.next_chunk()? {
+            return Ok(0);
+        }
+        let available = &self.current[self.position..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl Drop for UringReader {
+    fn drop(&mut self) {
+        // 加密时读取的是明文，释放之前清零所有缓冲区
+        self.ring.drain();
+        self.current.zeroize();
+        self.spare.iter_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+/// # io_uring 后台写入器 (Write-behind Writer)
+///
+/// 把写入的数据攒成 `chunk_size` 大小的数据块，从文件开头顺序提交，
+/// 最多保持 [`URING_QUEUE_DEPTH`] 个写入在途。
+///
+/// 与 `BufWriter` 不同，丢弃时不会写出尚未提交的数据，调用者必须先调用 [`Write::flush`]。
+pub(crate) struct UringWriter {
+    ring: Ring,
+    file: File,
+    chunk_size: usize,
+    /// 下一个要提交的写入的偏移量。
+    next_offset: u64,
+    /// 尚未提交的数据。
+    buffer: Vec<u8>,
+    spare: Vec<Vec<u8>>,
+}
+
+impl UringWriter {
+    /// 为 `file`（必须为空）创建后台写入器；内核不支持 io_uring 时返回 `None`。
+    pub(crate) fn new(file: &File, chunk_size: usize) -> Option<Self> {
+        let ring = Ring::new(opcode::Write::CODE)?;
+        let file = file.try_clone().ok()?;
+        Some(Self {
+            ring,
+            file,
+            chunk_size,
+            next_offset: 0,
+            buffer: Vec::with_capacity(chunk_size),
+            spare: Vec::new(),
+        })
+    }
+
+    /// 提交缓冲的数据；在途写入已达上限时先等待最早的一个完成。
+    fn submit_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        if self.ring.in_flight.len() == URING_QUEUE_DEPTH {
+            self.complete_front()?;
+        }
+        let next = self
+            .spare
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(self.chunk_size));
+        let buffer = std::mem::replace(&mut self.buffer, next);
+        let entry = opcode::Write::new(
+            types::Fd(self.file.as_raw_fd()),
+            buffer.as_ptr(),
+            buffer.len() as u32,
+        )
+        .offset(self.next_offset)
+        .build();
+        let len = buffer.len() as u64;
+        // SAFETY: `entry` 指向 `buffer` 的全部有效数据。
+        unsafe { self.ring.submit(entry, self.next_offset, buffer)? };
+        self.next_offset += len;
+        Ok(())
+    }
+
+    /// 等待最早的写入完成；短写入时同步写出剩余部分。
+    fn complete_front(&mut self) -> io::Result<()> {
+        let Some(mut pending) = self.ring.wait_front()? else {
+            return Ok(());
+        };
+        let written = completion_result(pending.result)?;
+        if written < pending.buffer.len() {
+            self.file
+                .write_all_at(&pending.buffer[written..], pending.offset + written as u64)?;
+        }
+        pending.buffer.clear();
+        self.spare.push(pending.buffer);
+        Ok(())
+    }
+}
+
+impl Write for UringWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.chunk_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == self.chunk_size {
+            self.submit_buffer()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit_buffer()?;
+        while !self.ring.in_flight.is_empty() {
+            self.complete_front()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UringWriter {
+    fn drop(&mut self) {
+        // 解密时写入的是明文，释放之前清零所有缓冲区
+        self.ring.drain();
+        self.buffer.zeroize();
+        self.spare.iter_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::time::Instant;
+    use tempfile::TempDir;
+
+    /// 数据块大小不是读写长度的整数倍，覆盖跨数据块的读写。
+    const CHUNK: usize = 4096;
+
+    #[test]
+    fn test_uring_write_then_read_roundtrip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("uring.bin");
+        let data: Vec<u8> = (0..CHUNK * 13 + 777).map(|i| (i % 251) as u8).collect();
+
+        let file = File::create(&path)?;
+        let Some(mut writer) = UringWriter::new(&file, CHUNK) else {
+            eprintln!("io_uring 不可用，跳过测试");
+            return Ok(());
+        };
+        for piece in data.chunks(1000) {
+            writer.write_all(piece)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        assert!(std::fs::read(&path)? == data);
+
+        let file = File::open(&path)?;
+        let mut reader = UringReader::new(&file, CHUNK).expect("写入可用时读取也应可用");
+        let mut read_back = Vec::new();
+        let mut piece = [0u8; 999];
+        loop {
+            let n = reader.read(&mut piece)?;
+            if n == 0 {
+                break;
+            }
+            read_back.extend_from_slice(&piece[..n]);
+        }
+        assert!(read_back == data);
+
+        // 空文件立即返回 0；提前丢弃仍有在途读取的读取器是安全的
+        let empty = temp_dir.path().join("empty.bin");
+        File::create(&empty)?;
+        let mut reader = UringReader::new(&File::open(&empty)?, CHUNK).unwrap();
+        assert_eq!(reader.read(&mut piece)?, 0);
+        let mut reader = UringReader::new(&File::open(&path)?, CHUNK).unwrap();
+        assert_eq!(reader.read(&mut piece)?, piece.len());
+        drop(reader);
+        Ok(())
+    }
+
+    /// 把 `source` 复制到 `target`，返回 MB/s。
+    fn copy_throughput(
+        source: &std::path::Path,
+        target: &std::path::Path,
+        uring: bool,
+    ) -> Result<f64> {
+        let chunk = crate::constants::BUFFER_LEN;
+        let input = File::open(source)?;
+        let output = File::create(target)?;
+        let start = Instant::now();
+        let copied = if uring {
+            let mut reader = UringReader::new(&input, chunk).expect("io_uring 不可用");
+            let mut writer = UringWriter::new(&output, chunk).expect("io_uring 不可用");
+            let copied = io::copy(&mut reader, &mut writer)?;
+            writer.flush()?;
+            copied
+        } else {
+            let mut reader = io::BufReader::with_capacity(chunk, input);
+            let mut writer = io::BufWriter::with_capacity(chunk, output);
+            let copied = io::copy(&mut reader, &mut writer)?;
+            writer.flush()?;
+            copied
+        };
+        Ok(copied as f64 / start.elapsed().as_secs_f64() / (1024.0 * 1024.0))
+    }
+
+    #[test]
+    #[ignore]
+    fn test_uring_copy_throughput() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let source = temp_dir.path().join("source.bin");
+        std::fs::write(&source, vec![0x5Au8; 512 * 1024 * 1024])?;
+        let target = temp_dir.path().join("target.bin");
+
+        let buffered = copy_throughput(&source, &target, false)?;
+        let uring = copy_throughput(&source, &target, true)?;
+        println!("[Copy 512MB] Buffered: {buffered:.2} MB/s, io_uring: {uring:.2} MB/s");
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+// src/wasm.rs
+
+//! # WebAssembly 绑定 (WebAssembly Bindings)
+//!
+//! 启用 `wasm` 特性时，通过 `wasm-bindgen` 向 JavaScript 导出内存加解密函数，
+//! 供在浏览器中运行的加密工具使用（`wasm-pack build --features wasm`）。
+//! 生成的数据与 [`encrypt_in_memory`] 相同，是完整的 `.feroxcrypt` 格式，可以用命令行工具解密。
+//!
+//! 浏览器中没有文件系统，也没有线程；随机数来自 `crypto.getRandomValues`。
+//!
+//! *With the `wasm` feature, in-memory encryption and decryption are exported to JavaScript
+//! through `wasm-bindgen`. The output is a regular `.feroxcrypt` file.*
+
+use crate::{
+    decrypt::{decrypt_in_memory, DecryptionOptions},
+    encrypt::{encrypt_in_memory, EncryptionOptions},
+};
+use wasm_bindgen::prelude::*;
+
+/// 加密数据时写入文件头的原始文件名；JavaScript 调用者不提供文件名。
+const WASM_FILENAME: &str = "data";
+
+/// 用密码加密一段数据，返回完整的 `.feroxcrypt` 格式字节。
+///
+/// 安全级别为 [`default_level`](crate::default_level)。失败时抛出带有中文错误消息的异常。
+#[wasm_bindgen]
+pub fn encrypt_wasm(plaintext: &[u8], password: &str) -> Result<Vec<u8>, JsValue> {
+    encrypt_in_memory(plaintext, WASM_FILENAME, &EncryptionOptions::new(password))
+        .map_err(|e| JsValue::from_str(&format!("{e:#}")))
+}
+
+/// 用密码解密 `.feroxcrypt` 格式的数据，只有认证标签验证通过后才返回明文。
+///
+/// 密码错误或数据被篡改时抛出异常。
+#[wasm_bindgen]
+pub fn decrypt_wasm(ciphertext: &[u8], password: &str) -> Result<Vec<u8>, JsValue> {
+    decrypt_in_memory(ciphertext, &DecryptionOptions::new(password))
+        .map(|(plaintext, _)| plaintext)
+        .map_err(|e| JsValue::from_str(&format!("{e:#}")))
+}
@@ -0,0 +1,647 @@
+// src/watchdog.rs
+
+//! # 停滞检测模块 (Stall Watchdog Module)
+//!
+//! 文件加密和解密流程在数据流处理期间通过 [`WatchedReader`] 和 [`WatchedWriter`] 读写文件，
+//! 每完成一次读写就记录一次进展。后台的监视线程发现超过设置的间隔（默认
+//! [`STALL_WARNING_SECS`] 秒）没有任何进展时，发出一次警告并向进度接收器发送
+//! [`ProgressEvent::Stalled`]，指出当前文件、源文件偏移量和卡住的读写操作。
+//!
+//! 设置了超时时，读写改在独立的工作线程中执行，调用者对每次读写最多等待这么久；
+//! 超时后放弃该文件并返回 [`FeroxError::Stalled`]，批量操作可以继续处理下一个文件。
+//! 卡在系统调用中的工作线程无法被中断（例如失去响应的 NFS 挂载），只能留在后台，
+//! 等系统调用返回后自行退出。
+//!
+//! 计时从第一次经过包装的读写开始。文件流程在密钥派生之后才通过包装读写数据，
+//! 因此耗时较长的 Argon2 永远不会被判定为停滞；它有单独的预计耗时提示
+//! ([`ProgressEvent::KeyDerivationStarted`])。
+//!
+//! *Watches the streaming phase of the file flows. When no read or write completes for the
+//! warning interval, a [`ProgressEvent::Stalled`] event names the file, offset and stuck
+//! operation. With a timeout, I/O runs on a worker thread and a stalled file is abandoned with
+//! [`FeroxError::Stalled`]. Timing starts at the first wrapped I/O, after key derivation.*
+
+use crate::constants::{ARGON2_NANOS_PER_KIB_PASS, STALL_WARNING_SECS};
+use crate::error::FeroxError;
+use crate::progress::{ProgressEvent, ProgressSink};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// # 读写操作 (I/O Operation)
+///
+/// 数据流处理中正在进行（或最近一次进行）的读写操作，用于指出停滞发生在哪里。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IoOperation {
+    /// 读取源文件。
+    Read,
+    /// 写入输出文件。
+    Write,
+    /// 刷新输出文件。
+    Flush,
+}
+
+impl fmt::Display for IoOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read => write!(f, "读取源文件"),
+            Self::Write => write!(f, "写入输出文件"),
+            Self::Flush => write!(f, "刷新输出文件"),
+        }
+    }
+}
+
+/// 停滞检测的设置。
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StallSettings {
+    /// 没有进展多久之后发出警告。
+    pub(crate) warning: Duration,
+    /// 没有进展多久之后放弃该文件；为 `None` 时只警告。
+    pub(crate) timeout: Option<Duration>,
+}
+
+/// 默认的停滞警告间隔，见 [`STALL_WARNING_SECS`]。
+pub(crate) const fn default_stall_warning() -> Duration {
+    Duration::from_secs(STALL_WARNING_SECS)
+}
+
+/// 按 Argon2 参数粗略估算密钥派生的耗时，见 [`ARGON2_NANOS_PER_KIB_PASS`]。
+pub(crate) fn expected_key_derivation_time((m_cost, t_cost, _): (u32, u32, u32)) -> Duration {
+    Duration::from_nanos(u64::from(m_cost) * u64::from(t_cost) * ARGON2_NANOS_PER_KIB_PASS)
+}
+
+/// 在密钥派生开始之前给出预计耗时的提示：记录日志，并向进度接收器发送
+/// [`ProgressEvent::KeyDerivationStarted`]。
+pub(crate) fn announce_key_derivation(
+    path: &Path,
+    argon2_params: (u32, u32, u32),
+    progress: Option<&dyn ProgressSink>,
+) {
+    let expected = expected_key_derivation_time(argon2_params);
+    log::info!(
+        "正在派生密钥，预计需要约 {:.1} 秒 (此阶段没有字节进度)",
+        expected.as_secs_f64()
+    );
+    if let Some(sink) = progress {
+        sink.on_event(&ProgressEvent::KeyDerivationStarted { path, expected });
+    }
+}
+
+/// 监视线程和读写包装共享的进展记录。
+struct Activity {
+    /// 是否已经开始计时（第一次经过包装的读写时开始）。
+    armed: bool,
+    /// 被监视的阶段是否已经结束。
+    finished: bool,
+    /// 最近一次进展（或开始计时）的时间。
+    last_progress: Instant,
+    /// 源文件中已经读取到的位置。
+    offset: u64,
+    /// 正在进行或最近一次进行的读写操作。
+    operation: IoOperation,
+    /// 当前这次停滞是否已经警告过。
+    warned: bool,
+}
+
+struct Shared {
+    path: PathBuf,
+    activity: Mutex<Activity>,
+    finished: Condvar,
+}
+
+impl Shared {
+    /// 锁定读写状态；某个线程在持有锁时 panic 后状态仍然可用，照常继续。
+    fn activity(&self) -> MutexGuard<'_, Activity> {
+        self.activity
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 开始一次读写操作。
+    fn begin(&self, operation: IoOperation) {
+        let mut activity = self.activity();
+        if !activity.armed {
+            activity.armed = true;
+            activity.last_progress = Instant::now();
+        }
+        activity.operation = operation;
+    }
+
+    /// 完成一次读写操作，`bytes_read` 为从源文件读取的字节数。
+    fn complete(&self, bytes_read: u64) {
+        let mut activity = self.activity();
+        activity.offset += bytes_read;
+        activity.last_progress = Instant::now();
+        activity.warned = false;
+    }
+
+    /// 结束监视，唤醒监视线程。
+    fn finish(&self) {
+        self.activity().finished = true;
+        self.finished.notify_all();
+    }
+
+    /// 描述当前停滞的错误。
+    fn stalled(&self) -> FeroxError {
+        let activity = self.activity();
+        FeroxError::Stalled {
+            path: self.path.clone(),
+            offset: activity.offset,
+            operation: activity.operation,
+            idle: activity.last_progress.elapsed(),
+        }
+    }
+}
+
+/// 被监视阶段结束（包括出错或 panic 时）结束监视，使 [`watch`] 的线程作用域能够退出。
+struct FinishOnDrop<'a>(&'a Shared);
+
+impl Drop for FinishOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+/// # 停滞监视器 (Watchdog)
+///
+/// 由 [`watch`] 创建，用来包装需要监视的读写对象。
+pub(crate) struct Watchdog {
+    shared: Shared,
+    timeout: Option<Duration>,
+}
+
+impl Watchdog {
+    /// 包装源文件的读取，`offset` 为 `inner` 当前在源文件中的位置；
+    /// 设置了超时时读取在工作线程中执行。
+    pub(crate) fn reader<R: Read + Send + 'static>(
+        &self,
+        inner: R,
+        offset: u64,
+    ) -> io::Result<WatchedReader<'_, R>> {
+        self.shared.activity().offset = offset;
+        Ok(WatchedReader {
+            backend: Backend::new(inner, self.timeout, |reader, _, buffer| reader.read(buffer))?,
+            shared: &self.shared,
+        })
+    }
+
+    /// 包装输出文件的写入；设置了超时时写入在工作线程中执行。
+    pub(crate) fn writer<W: Write + Send + 'static>(
+        &self,
+        inner: W,
+    ) -> io::Result<WatchedWriter<'_, W>> {
+        Ok(WatchedWriter {
+            backend: Backend::new(
+                inner,
+                self.timeout,
+                |writer, operation, buffer| match operation {
+                    IoOperation::Flush => writer.flush().map(|()| 0),
+                    _ => writer.write_all(buffer).map(|()| buffer.len()),
+                },
+            )?,
+            shared: &self.shared,
+        })
+    }
+}
+
+/// 在停滞监视下执行 `body`：监视线程在 `body` 运行期间检查经过包装的读写是否有进展。
+///
+/// 发现停滞时记录警告并向 `progress` 发送 [`ProgressEvent::Stalled`]；每次停滞只警告一次，
+/// 读写恢复后重新计时。
+pub(crate) fn watch<T>(
+    path: &Path,
+    settings: StallSettings,
+    progress: Option<&dyn ProgressSink>,
+    body: impl FnOnce(&Watchdog) -> T,
+) -> T {
+    let watchdog = Watchdog {
+        shared: Shared {
+            path: path.to_path_buf(),
+            activity: Mutex::new(Activity {
+                armed: false,
+                finished: false,
+                last_progress: Instant::now(),
+                offset: 0,
+                operation: IoOperation::Read,
+                warned: false,
+            }),
+            finished: Condvar::new(),
+        },
+        timeout: settings.timeout,
+    };
+    thread::scope(|scope| {
+        scope.spawn(|| monitor(&watchdog.shared, settings.warning, progress));
+        let _finish = FinishOnDrop(&watchdog.shared);
+        body(&watchdog)
+    })
+}
+
+/// 监视线程：没有进展超过 `warning` 时警告一次，直到监视结束。
+fn monitor(shared: &Shared, warning: Duration, progress: Option<&dyn ProgressSink>) {
+    let mut activity = shared.activity();
+    while !activity.finished {
+        let idle = activity.last_progress.elapsed();
+        if activity.armed && !activity.warned && idle >= warning {
+            activity.warned = true;
+            let (offset, operation) = (activity.offset, activity.operation);
+            drop(activity);
+            log::warn!(
+                "⚠️  {} 已有 {} 秒没有进展，卡在{operation} (源文件偏移量 {offset})",
+                shared.path.display(),
+                idle.as_secs()
+            );
+            if let Some(sink) = progress {
+                sink.on_event(&ProgressEvent::Stalled {
+                    path: &shared.path,
+                    offset,
+                    operation,
+                    idle,
+                });
+            }
+            activity = shared.activity();
+            continue;
+        }
+        let wait = if activity.armed && !activity.warned {
+            warning - idle
+        } else {
+            warning
+        };
+        activity = shared
+            .finished
+            .wait_timeout(activity, wait)
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .0;
+    }
+}
+
+/// 在工作线程中执行的一次读写：读取到缓冲区，或写出缓冲区中的数据。
+type Serve<T> = fn(&mut T, IoOperation, &mut [u8]) -> io::Result<usize>;
+
+/// 在调用者的线程中直接读写，或者交给工作线程并限时等待。
+enum Backend<T> {
+    Direct(T),
+    Offloaded(Worker),
+}
+
+impl<T: Send + 'static> Backend<T> {
+    fn new(inner: T, timeout: Option<Duration>, serve: Serve<T>) -> io::Result<Self> {
+        Ok(match timeout {
+            Some(timeout) => Self::Offloaded(Worker::spawn(inner, serve, timeout)?),
+            None => Self::Direct(inner),
+        })
+    }
+}
+
+/// 交给工作线程的数据：读取的目标，或要写出的数据。
+enum IoBuf<'a> {
+    Read(&'a mut [u8]),
+    Write(&'a [u8]),
+}
+
+/// 交给工作线程的一次读写。
+struct Job {
+    operation: IoOperation,
+    /// 读取时接收数据、写入时携带数据的缓冲区，只有前 `len` 字节有效。
+    buffer: Zeroizing<Vec<u8>>,
+    len: usize,
+}
+
+/// 工作线程完成的一次读写，缓冲区交还给调用者复用。
+struct Done {
+    buffer: Zeroizing<Vec<u8>>,
+    result: io::Result<usize>,
+}
+
+/// 拥有读写对象的工作线程。
+///
+/// 数据经过一个在调用者和工作线程之间来回传递的缓冲区，可能包含明文，释放时清零。
+struct Worker {
+    jobs: Option<Sender<Job>>,
+    done: Receiver<Done>,
+    handle: Option<JoinHandle<()>>,
+    /// 空闲时的缓冲区；请求在途或已经超时时为 `None`。
+    buffer: Option<Zeroizing<Vec<u8>>>,
+    timeout: Duration,
+    /// 是否已经超时：之后的读写立即失败，工作线程留在后台。
+    stalled: bool,
+}
+
+impl Worker {
+    fn spawn<T: Send + 'static>(
+        mut inner: T,
+        serve: Serve<T>,
+        timeout: Duration,
+    ) -> io::Result<Self> {
+        let (jobs, job_rx) = mpsc::channel::<Job>();
+        let (done_tx, done) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name("ferox-io".to_string())
+            .spawn(move || {
+                for mut job in job_rx {
+                    let result = serve(&mut inner, job.operation, &mut job.buffer[..job.len]);
+                    if done_tx
+                        .send(Done {
+                            buffer: job.buffer,
+                            result,
+                        })
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            })?;
+        Ok(Self {
+            jobs: Some(jobs),
+            done,
+            handle: Some(handle),
+            buffer: Some(Zeroizing::new(Vec::new())),
+            timeout,
+            stalled: false,
+        })
+    }
+
+    /// 把一次读写交给工作线程，最多等待 `timeout`；超时后这个工作线程不再接受新的读写。
+    fn run(
+        &mut self,
+        shared: &Shared,
+        operation: IoOperation,
+        buf: IoBuf<'_>,
+    ) -> io::Result<usize> {
+        if self.stalled {
+            return Err(stalled_error(shared));
+        }
+        let (Some(jobs), Some(mut buffer)) = (&self.jobs, self.buffer.take()) else {
+            return Err(worker_exited());
+        };
+        let len = match &buf {
+            IoBuf::Read(out) => out.len(),
+            IoBuf::Write(data) => data.len(),
+        };
+        if buffer.len() < len {
+            // 换成新的缓冲区而不是扩容，扩容会在释放的旧内存中留下未清零的数据
+            buffer = Zeroizing::new(vec![0u8; len]);
+        }
+        if let IoBuf::Write(data) = &buf {
+            buffer[..len].copy_from_slice(data);
+        }
+        let job = Job {
+            operation,
+            buffer,
+            len,
+        };
+        jobs.send(job).map_err(|_| worker_exited())?;
+        match self.done.recv_timeout(self.timeout) {
+            Ok(Done { buffer, result }) => {
+                if let (Ok(done), IoBuf::Read(out)) = (&result, buf) {
+                    out[..*done].copy_from_slice(&buffer[..*done]);
+                }
+                self.buffer = Some(buffer);
+                result
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                self.stalled = true;
+                Err(stalled_error(shared))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(worker_exited()),
+        }
+    }
+}
+
+impl Drop for Worker {
+    /// 没有超时时等待工作线程退出，使读写对象（例如持有锁的输出文件）在返回前被关闭。
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+        if let (Some(handle), false) = (self.handle.take(), self.stalled) {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// 工作线程已经退出（读写对象 panic）时的错误。
+fn worker_exited() -> io::Error {
+    io::Error::other("读写工作线程意外退出")
+}
+
+/// 以 [`FeroxError::Stalled`] 为内部错误的 I/O 错误，经过任意层上下文后仍能被
+/// [`FeroxError::classify`] 识别。
+fn stalled_error(shared: &Shared) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, shared.stalled())
+}
+
+/// 受停滞监视的源文件读取，见 [`Watchdog::reader`]。
+pub(crate) struct WatchedReader<'a, R> {
+    backend: Backend<R>,
+    shared: &'a Shared,
+}
+
+impl<R: Read> Read for WatchedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.shared.begin(IoOperation::Read);
+        let len = match &mut self.backend {
+            Backend::Direct(reader) => reader.read(buf)?,
+            Backend::Offloaded(worker) => {
+                worker.run(self.shared, IoOperation::Read, IoBuf::Read(buf))?
+            }
+        };
+        self.shared.complete(len as u64);
+        Ok(len)
+    }
+}
+
+/// 受停滞监视的输出文件写入，见 [`Watchdog::writer`]。
+///
+/// 与 [`OutputFile`](crate::stream_io::OutputFile) 一样，调用者必须在丢弃之前调用 [`Write::flush`]。
+pub(crate) struct WatchedWriter<'a, W> {
+    backend: Backend<W>,
+    shared: &'a Shared,
+}
+
+impl<W: Write> WatchedWriter<'_, W> {
+    fn run(&mut self, operation: IoOperation, buf: &[u8]) -> io::Result<usize> {
+        self.shared.begin(operation);
+        let len = match (&mut self.backend, operation) {
+            (Backend::Direct(writer), IoOperation::Flush) => writer.flush().map(|()| 0)?,
+            (Backend::Direct(writer), _) => writer.write(buf)?,
+            (Backend::Offloaded(worker), _) => {
+                worker.run(self.shared, operation, IoBuf::Write(buf))?
+            }
+        };
+        self.shared.complete(0);
+        Ok(len)
+    }
+}
+
+impl<W: Write> Write for WatchedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.run(IoOperation::Write, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.run(IoOperation::Flush, &[]).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// 先返回 `prefix`，之后每次读取都阻塞到测试结束的读取器。
+    struct BlockingReader {
+        prefix: Vec<u8>,
+        release: Arc<(Mutex<bool>, Condvar)>,
+    }
+
+    impl Read for BlockingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.prefix.is_empty() {
+                let len = self.prefix.len().min(buf.len());
+                buf[..len].copy_from_slice(&self.prefix[..len]);
+                self.prefix.drain(..len);
+                return Ok(len);
+            }
+            let (released, changed) = &*self.release;
+            let mut released = released.lock().unwrap();
+            while !*released {
+                released = changed.wait(released).unwrap();
+            }
+            Ok(0)
+        }
+    }
+
+    /// 记录停滞事件的接收器。
+    #[derive(Default)]
+    struct StallRecorder {
+        stalls: Mutex<Vec<(u64, IoOperation)>>,
+    }
+
+    impl ProgressSink for StallRecorder {
+        fn on_event(&self, event: &ProgressEvent<'_>) {
+            if let ProgressEvent::Stalled {
+                offset, operation, ..
+            } = event
+            {
+                self.stalls.lock().unwrap().push((*offset, *operation));
+            }
+        }
+    }
+
+    fn blocking_reader(prefix: &[u8]) -> (BlockingReader, Arc<(Mutex<bool>, Condvar)>) {
+        let release = Arc::new((Mutex::new(false), Condvar::new()));
+        let reader = BlockingReader {
+            prefix: prefix.to_vec(),
+            release: Arc::clone(&release),
+        };
+        (reader, release)
+    }
+
+    fn release(release: &(Mutex<bool>, Condvar)) {
+        *release.0.lock().unwrap() = true;
+        release.1.notify_all();
+    }
+
+    #[test]
+    fn test_stalled_read_times_out_with_typed_error() {
+        let (reader, gate) = blocking_reader(b"0123456789");
+        let recorder = StallRecorder::default();
+        let settings = StallSettings {
+            warning: Duration::from_millis(20),
+            timeout: Some(Duration::from_millis(200)),
+        };
+        let error = watch(
+            Path::new("stuck.bin"),
+            settings,
+            Some(&recorder),
+            |watchdog| {
+                let mut reader = watchdog.reader(reader, 0).unwrap();
+                let mut buf = [0u8; 64];
+                assert_eq!(reader.read(&mut buf).unwrap(), 10);
+                reader.read(&mut buf).unwrap_err()
+            },
+        );
+        release(&gate);
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        let error = anyhow::Error::new(error).context("读取输入数据失败");
+        match FeroxError::classify(&error) {
+            FeroxError::Stalled {
+                path,
+                offset,
+                operation,
+                idle,
+            } => {
+                assert_eq!(path, Path::new("stuck.bin"));
+                assert_eq!(offset, 10);
+                assert_eq!(operation, IoOperation::Read);
+                assert!(idle >= Duration::from_millis(200));
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert_eq!(*recorder.stalls.lock().unwrap(), [(10, IoOperation::Read)]);
+    }
+
+    #[test]
+    fn test_no_warning_before_first_io() {
+        let recorder = StallRecorder::default();
+        let settings = StallSettings {
+            warning: Duration::from_millis(10),
+            timeout: None,
+        };
+        watch(
+            Path::new("slow-kdf.bin"),
+            settings,
+            Some(&recorder),
+            |watchdog| {
+                let mut writer = watchdog.writer(Vec::new()).unwrap();
+                // 模拟耗时的密钥派生：还没有任何读写，不计时
+                thread::sleep(Duration::from_millis(60));
+                writer.write_all(b"header").unwrap();
+                writer.flush().unwrap();
+            },
+        );
+        assert!(recorder.stalls.lock().unwrap().is_empty());
+    }
+
+    /// 交给工作线程之后仍能检查写入内容的输出。
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_offloaded_io_round_trip() -> io::Result<()> {
+        let settings = StallSettings {
+            warning: default_stall_warning(),
+            timeout: Some(Duration::from_secs(5)),
+        };
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        let output = SharedOutput::default();
+        watch(Path::new("data.bin"), settings, None, |watchdog| {
+            let mut reader = watchdog.reader(io::Cursor::new(data.clone()), 0)?;
+            let mut writer = watchdog.writer(output.clone())?;
+            io::copy(&mut reader, &mut writer)?;
+            writer.flush()
+        })?;
+        assert!(*output.0.lock().unwrap() == data);
+        assert!(
+            expected_key_derivation_time(crate::Level::Paranoid.argon2_params()) > Duration::ZERO
+        );
+        Ok(())
+    }
+}
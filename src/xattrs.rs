@@ -0,0 +1,200 @@
+// src/xattrs.rs
+
+//! # 扩展属性模块 (Extended Attributes)
+//!
+//! 启用 `--preserve-xattrs` 时，加密把源文件的扩展属性编码进文件头的扩展区（因此受认证标签保护），
+//! 解密在认证通过之后、提交输出文件之前把它们设置到输出文件上。无法读取或设置的属性只发出警告；
+//! 严格模式 (`--strict`) 下这些警告都会使操作失败。
+//!
+//! 在 Linux 等区分命名空间的系统上只保留 `user.*` 属性：`security.*`、`trusted.*` 和 `system.*`
+//! 与权限相关（例如 `security.capability` 会授予程序特权），不应随解密出的文件一起恢复。
+//! macOS 上保留所有属性（如 `com.apple.quarantine` 和 Finder 标签）。Windows 的 NTFS 备用数据流
+//! 暂不支持。
+//!
+//! 编码为若干条 `名称长度 (u16) | 名称 | 值长度 (u16) | 值` 记录。所有扩展加起来不能超过
+//! [`MAX_HEADER_EXTENSIONS_LEN`]，放不下的属性会被跳过并发出警告。
+//!
+//! *With `--preserve-xattrs`, extended attributes of the source are stored in an authenticated
+//! header extension and restored on the decrypted output. Only `user.*` attributes are kept on
+//! namespaced systems; NTFS alternate data streams are not supported.*
+
+#[cfg(doc)]
+use crate::constants::MAX_HEADER_EXTENSIONS_LEN;
+use crate::error::{warn_or_fail, FeroxError};
+use std::path::Path;
+
+/// 一个扩展属性。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ExtendedAttribute {
+    /// 属性名的原始字节。
+    pub(crate) name: Vec<u8>,
+    /// 属性值。
+    pub(crate) value: Vec<u8>,
+}
+
+/// 读取 `path` 上需要保留的扩展属性；无法读取的属性发出警告并跳过（`strict` 时返回错误）。
+#[cfg(unix)]
+pub(crate) fn capture(path: &Path, strict: bool) -> Result<Vec<ExtendedAttribute>, FeroxError> {
+    use std::os::unix::ffi::OsStrExt;
+
+    if !xattr::SUPPORTED_PLATFORM {
+        warn_or_fail(
+            strict,
+            "当前平台不支持扩展属性，--preserve-xattrs 不起作用".to_string(),
+        )?;
+        return Ok(Vec::new());
+    }
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(e) => {
+            warn_or_fail(
+                strict,
+                format!("无法列出 {} 的扩展属性: {e}", path.display()),
+            )?;
+            return Ok(Vec::new());
+        }
+    };
+    let mut attributes = Vec::new();
+    for name in names.filter(|name| is_preserved(name.as_bytes())) {
+        match xattr::get(path, &name) {
+            Ok(value) => attributes.extend(value.map(|value| ExtendedAttribute {
+                name: name.as_bytes().to_vec(),
+                value,
+            })),
+            Err(e) => warn_or_fail(
+                strict,
+                format!("无法读取扩展属性 {}，已跳过: {e}", name.to_string_lossy()),
+            )?,
+        }
+    }
+    Ok(attributes)
+}
+
+/// 不支持扩展属性的平台。
+#[cfg(not(unix))]
+pub(crate) fn capture(_path: &Path, strict: bool) -> Result<Vec<ExtendedAttribute>, FeroxError> {
+    warn_or_fail(
+        strict,
+        "当前平台不支持扩展属性，--preserve-xattrs 不起作用".to_string(),
+    )?;
+    Ok(Vec::new())
+}
+
+/// 把扩展属性设置到 `path` 上；不应恢复或无法设置的属性发出警告并跳过（`strict` 时返回错误）。
+#[cfg(unix)]
+pub(crate) fn restore(
+    path: &Path,
+    attributes: &[ExtendedAttribute],
+    strict: bool,
+) -> Result<(), FeroxError> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    for attribute in attributes {
+        let name = OsStr::from_bytes(&attribute.name);
+        // 文件头中的属性来自加密方，不恢复与权限相关的命名空间
+        if !is_preserved(&attribute.name) {
+            warn_or_fail(strict, format!("不恢复扩展属性 {}", name.to_string_lossy()))?;
+            continue;
+        }
+        if let Err(e) = xattr::set(path, name, &attribute.value) {
+            warn_or_fail(
+                strict,
+                format!("无法恢复扩展属性 {}，已跳过: {e}", name.to_string_lossy()),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// 不支持扩展属性的平台。
+#[cfg(not(unix))]
+pub(crate) fn restore(
+    _path: &Path,
+    attributes: &[ExtendedAttribute],
+    strict: bool,
+) -> Result<(), FeroxError> {
+    if !attributes.is_empty() {
+        warn_or_fail(
+            strict,
+            format!(
+                "当前平台不支持扩展属性，文件中的 {} 个扩展属性未被恢复",
+                attributes.len()
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// 是否保留该属性：区分命名空间的系统上只保留 `user.*`。
+#[cfg(unix)]
+fn is_preserved(name: &[u8]) -> bool {
+    cfg!(target_os = "macos") || name.starts_with(b"user.")
+}
+
+/// 把扩展属性编码为文件头扩展的值，总长度不超过 `budget` 字节。
+///
+/// 放不下的属性发出警告并跳过（`strict` 时返回错误）。
+pub(crate) fn encode(
+    attributes: &[ExtendedAttribute],
+    budget: usize,
+    strict: bool,
+) -> Result<Vec<u8>, FeroxError> {
+    let mut out = Vec::new();
+    for attribute in attributes {
+        let record_len = 4 + attribute.name.len() + attribute.value.len();
+        let fits = attribute.name.len() <= u16::MAX as usize
+            && attribute.value.len() <= u16::MAX as usize
+            && out.len() + record_len <= budget;
+        if !fits {
+            warn_or_fail(
+                strict,
+                format!(
+                    "扩展属性 {} ({} 字节) 超出文件头扩展区的容量，已跳过",
+                    String::from_utf8_lossy(&attribute.name),
+                    attribute.value.len()
+                ),
+            )?;
+            continue;
+        }
+        out.extend_from_slice(&(attribute.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&attribute.name);
+        out.extend_from_slice(&(attribute.value.len() as u16).to_le_bytes());
+        out.extend_from_slice(&attribute.value);
+    }
+    Ok(out)
+}
+
+/// 解码文件头扩展中的扩展属性，每个长度都与剩余字节比较。
+pub(crate) fn decode(mut bytes: &[u8]) -> Result<Vec<ExtendedAttribute>, FeroxError> {
+    let mut attributes = Vec::new();
+    while !bytes.is_empty() {
+        let name = take_field(&mut bytes, "名称")?;
+        if name.is_empty() || name.contains(&0) {
+            return Err(FeroxError::InvalidHeader {
+                reason: "扩展属性名无效".to_string(),
+            });
+        }
+        let value = take_field(&mut bytes, "值")?;
+        attributes.push(ExtendedAttribute {
+            name: name.to_vec(),
+            value: value.to_vec(),
+        });
+    }
+    Ok(attributes)
+}
+
+/// 读取一个 `长度 (u16) | 内容` 字段。
+fn take_field<'a>(bytes: &mut &'a [u8], field: &str) -> Result<&'a [u8], FeroxError> {
+    let truncated = || FeroxError::InvalidHeader {
+        reason: format!("扩展属性的{field}被截断"),
+    };
+    let (len, rest) = bytes.split_first_chunk::<2>().ok_or_else(truncated)?;
+    let len = usize::from(u16::from_le_bytes(*len));
+    if len > rest.len() {
+        return Err(truncated());
+    }
+    let (field, rest) = rest.split_at(len);
+    *bytes = rest;
+    Ok(field)
+}
@@ -3,9 +3,18 @@
 //! Tests for batch processing functionality
 
 use anyhow::Result;
-use ferox_encryptor::{batch_decrypt_directory, batch_encrypt_directory, BatchConfig, Level};
+use ferox_encryptor::{
+    batch_decrypt_directory, batch_decrypt_files, batch_decrypt_paths, batch_encrypt_directory,
+    batch_encrypt_directory_with_manifest, batch_encrypt_files_atomic, batch_encrypt_iter,
+    batch_encrypt_paths, encrypt_file_to_base64, execute_batch_plan, expand_glob_arguments,
+    plan_batch_encrypt, verify_restore, BatchConfig, BatchPlan, Credentials, DirectoryReadme,
+    EncryptionOptions, FeroxError, KeyFile, Level, OverwriteMode, PlannedAction, ProgressEvent,
+    ProgressSink, Stage,
+};
 use glob::Pattern;
 use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 use tempfile::TempDir;
 
 #[test]
@@ -319,3 +328,1540 @@ fn test_batch_empty_directory() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_batch_paths_mix_files_and_directories() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "mixed_paths_password";
+
+    let single = temp_dir.path().join("single.txt");
+    fs::write(&single, b"single file")?;
+    let docs = temp_dir.path().join("docs");
+    let nested = docs.join("nested");
+    fs::create_dir_all(&nested)?;
+    fs::write(docs.join("a.txt"), b"doc a")?;
+    fs::write(nested.join("b.txt"), b"doc b")?;
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    let paths = vec![single.clone(), docs.clone()];
+
+    // 不递归时只处理目录的顶层文件
+    let result = batch_encrypt_paths(&paths, password, None, &config)?;
+    assert_eq!(result.success_count, 2);
+    assert_eq!(result.failure_count, 0);
+    assert!(temp_dir.path().join("single.txt.feroxcrypt").exists());
+    assert!(docs.join("a.txt.feroxcrypt").exists());
+    assert!(!nested.join("b.txt.feroxcrypt").exists());
+
+    // 递归时处理子目录；.feroxcrypt 文件本身不会被再次加密
+    let recursive = BatchConfig {
+        recursive: true,
+        ..config.clone()
+    };
+    let result = batch_encrypt_paths(std::slice::from_ref(&docs), password, None, &recursive)?;
+    assert_eq!(result.success_count, 1);
+    assert_eq!(result.failure_count, 1); // docs/a.txt.feroxcrypt 已存在
+    assert!(nested.join("b.txt.feroxcrypt").exists());
+
+    fs::remove_file(&single)?;
+    fs::remove_file(docs.join("a.txt"))?;
+    fs::remove_file(nested.join("b.txt"))?;
+
+    let encrypted_paths = vec![temp_dir.path().join("single.txt.feroxcrypt"), docs.clone()];
+    let result = batch_decrypt_paths(&encrypted_paths, password, None, &recursive)?;
+    assert_eq!(result.success_count, 3);
+    assert_eq!(result.failure_count, 0);
+    assert_eq!(fs::read(&single)?, b"single file");
+    assert_eq!(fs::read(docs.join("a.txt"))?, b"doc a");
+    assert_eq!(fs::read(nested.join("b.txt"))?, b"doc b");
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_result_failure_accessors() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "accessor_password";
+
+    let good = temp_dir.path().join("good.txt");
+    let tampered = temp_dir.path().join("tampered.txt");
+    fs::write(&good, b"good content")?;
+    fs::write(&tampered, b"tampered content")?;
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    let result = batch_encrypt_directory(temp_dir.path(), password, None, &config)?;
+    assert!(result.was_successful());
+    assert!(result.first_failure().is_none());
+    assert!(result.last_failure().is_none());
+    fs::remove_file(&good)?;
+    fs::remove_file(&tampered)?;
+
+    // 篡改认证标签
+    let tampered_encrypted = temp_dir.path().join("tampered.txt.feroxcrypt");
+    let mut data = fs::read(&tampered_encrypted)?;
+    let last = data.len() - 1;
+    data[last] ^= 0x01;
+    fs::write(&tampered_encrypted, data)?;
+
+    let missing = temp_dir.path().join("missing.txt.feroxcrypt");
+    // 第一个文件解密成功确认了凭据，之后篡改文件的认证失败不会中止批量解密
+    let files = vec![
+        temp_dir.path().join("good.txt.feroxcrypt"),
+        tampered_encrypted.clone(),
+        missing.clone(),
+    ];
+    let result = batch_decrypt_files(&files, password, None)?;
+
+    assert!(!result.was_successful());
+    assert_eq!(result.success_count, 1);
+    assert_eq!(
+        result.first_failure().map(|(path, _)| path),
+        Some(&tampered_encrypted)
+    );
+    assert_eq!(result.last_failure().map(|(path, _)| path), Some(&missing));
+    assert_eq!(
+        result.all_authentication_failures(),
+        vec![&tampered_encrypted]
+    );
+    assert_eq!(result.all_io_failures(), vec![&missing]);
+    assert!(matches!(
+        result.first_failure(),
+        Some((_, FeroxError::AuthenticationFailed))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_expand_glob_arguments() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    for name in ["b.docx", "a.docx", "notes.txt", "[draft].txt"] {
+        fs::write(temp_dir.path().join(name), b"content")?;
+    }
+
+    let pattern = temp_dir.path().join("*.docx");
+    let plain = temp_dir.path().join("notes.txt");
+    let literal = temp_dir.path().join("[draft].txt");
+    let expanded = expand_glob_arguments(&[pattern, plain.clone(), literal.clone()])?;
+    assert_eq!(
+        expanded,
+        vec![
+            temp_dir.path().join("a.docx"),
+            temp_dir.path().join("b.docx"),
+            plain,
+            literal,
+        ]
+    );
+
+    let err = expand_glob_arguments(&[temp_dir.path().join("*.pdf")]).unwrap_err();
+    assert!(err.to_string().contains("没有匹配任何文件"));
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_encrypt_files_atomic() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "atomic_password";
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    let files: Vec<_> = ["a.txt", "b.txt"]
+        .iter()
+        .map(|name| temp_dir.path().join(name))
+        .collect();
+    for file in &files {
+        fs::write(file, b"related content")?;
+    }
+    let encrypted = |name: &str| temp_dir.path().join(format!("{name}.feroxcrypt"));
+
+    // 预先验证失败：不加密任何文件
+    let missing = temp_dir.path().join("missing.txt");
+    let with_missing = [files.clone(), vec![missing.clone()]].concat();
+    let result = batch_encrypt_files_atomic(&with_missing, password, None, &config)?;
+    assert!(result.was_atomic);
+    assert_eq!(result.success_count, 0);
+    assert_eq!(result.failure_count, 1);
+    assert_eq!(result.first_failure().map(|(path, _)| path), Some(&missing));
+    assert!(!encrypted("a.txt").exists());
+
+    // 加密阶段失败：另一个实例正在写入 b.txt 的临时文件，已完成的 a.txt 输出被回滚
+    let locked_part = temp_dir.path().join("b.txt.feroxcrypt.part");
+    let holder = fs::File::create(&locked_part)?;
+    holder.lock()?;
+    let result = batch_encrypt_files_atomic(&files, password, None, &config)?;
+    assert!(result.was_atomic);
+    assert_eq!(result.success_count, 0);
+    assert_eq!(
+        result.first_failure().map(|(path, _)| path),
+        Some(&files[1])
+    );
+    assert!(!encrypted("a.txt").exists());
+    assert!(!temp_dir.path().join("a.txt.feroxcrypt.part").exists());
+    assert!(locked_part.exists());
+    drop(holder);
+    fs::remove_file(&locked_part)?;
+
+    // 全部成功
+    let result = batch_encrypt_files_atomic(&files, password, None, &config)?;
+    assert!(result.was_atomic);
+    assert!(result.was_successful());
+    assert_eq!(result.success_count, 2);
+    assert!(encrypted("a.txt").exists() && encrypted("b.txt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_result_into_iter_tracks_processed_files() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "tracking_password";
+    let good = temp_dir.path().join("good.txt");
+    let missing = temp_dir.path().join("missing.txt");
+    fs::write(&good, b"tracked content")?;
+    let paths = vec![good.clone(), missing.clone()];
+
+    // 默认只记录失败的文件
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    let result = batch_encrypt_paths(&paths, password, None, &config)?;
+    let entries: Vec<_> = result.into_iter().collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].0, missing);
+    assert!(entries[0].1.is_err());
+
+    // 启用后同时记录成功的文件，并保持处理顺序
+    fs::remove_file(temp_dir.path().join("good.txt.feroxcrypt"))?;
+    let config = BatchConfig {
+        track_successful_paths: true,
+        ..config
+    };
+    let result = batch_encrypt_paths(&paths, password, None, &config)?;
+    assert_eq!(
+        result.processed,
+        vec![(good.clone(), true), (missing.clone(), false)]
+    );
+    let succeeded: Vec<_> = result
+        .into_iter()
+        .filter(|(_, outcome)| outcome.is_ok())
+        .map(|(path, _)| path)
+        .collect();
+    assert_eq!(succeeded, vec![good]);
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_encrypt_iter_processes_files_lazily() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "lazy_password";
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(temp_dir.path().join(name), name)?;
+    }
+
+    // 惰性地读取目录项，并只取前两个文件
+    let mut discovered = Vec::new();
+    let files = fs::read_dir(temp_dir.path())?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .inspect(|path| discovered.push(path.clone()))
+        .take(2);
+    let config = BatchConfig {
+        level: Level::Interactive,
+        track_successful_paths: true,
+        ..Default::default()
+    };
+    let result = batch_encrypt_iter(files, password, None, &config)?;
+
+    assert!(result.was_successful());
+    assert_eq!(result.success_count, 2);
+    assert_eq!(discovered.len(), 2);
+    let processed: Vec<_> = result
+        .processed
+        .iter()
+        .map(|(path, _)| path.clone())
+        .collect();
+    assert_eq!(processed, discovered);
+    let encrypted = fs::read_dir(temp_dir.path())?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .is_some_and(|ext| ext == "feroxcrypt")
+        })
+        .count();
+    assert_eq!(encrypted, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_encrypt_skips_files_open_by_another_process() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+    let password = "open_files_password";
+    fs::write(dir.join("idle.txt"), b"finished")?;
+    fs::write(dir.join("busy.log"), b"still being writ")?;
+
+    // 模拟另一个仍在写入的进程：保持文件打开并持有独占锁
+    let writer = fs::OpenOptions::new()
+        .append(true)
+        .open(dir.join("busy.log"))?;
+    writer.lock()?;
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        skip_open_files: true,
+        ..Default::default()
+    };
+    let result = batch_encrypt_directory(dir, password, None, &config)?;
+    assert!(result.was_successful());
+    assert_eq!(result.success_count, 1);
+    assert_eq!(result.skipped_locked, [dir.join("busy.log")]);
+    assert!(dir.join("idle.txt.feroxcrypt").exists());
+    assert!(!dir.join("busy.log.feroxcrypt").exists());
+
+    // 写入者关闭文件后，再次运行会加密它
+    drop(writer);
+    let config = BatchConfig {
+        force_overwrite: true,
+        ..config
+    };
+    let result = batch_encrypt_directory(dir, password, None, &config)?;
+    assert!(result.skipped_locked.is_empty());
+    assert!(dir.join("busy.log.feroxcrypt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_restore_reports_missing_extra_and_mismatched() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+    let password = "manifest_password";
+    fs::create_dir(dir.join("sub"))?;
+    for (name, content) in [
+        ("a.txt", "alpha"),
+        ("b.txt", "bravo"),
+        ("sub/c.txt", "charlie"),
+    ] {
+        fs::write(dir.join(name), content)?;
+    }
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        recursive: true,
+        threads: 2,
+        ..Default::default()
+    };
+    let (result, manifest) = batch_encrypt_directory_with_manifest(dir, password, None, &config)?;
+    assert!(result.was_successful());
+    let mut keys: Vec<_> = manifest.files.keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, ["a.txt", "b.txt", "sub/c.txt"]);
+    assert_eq!(manifest.files["b.txt"].size, 5);
+    let manifest_path = dir.join("ferox-manifest.json");
+    assert!(manifest_path.exists());
+
+    // 删除原文件后原地解密恢复，结果与清单一致；加密文件和清单本身不算多余
+    for name in ["a.txt", "b.txt", "sub/c.txt"] {
+        fs::remove_file(dir.join(name))?;
+    }
+    assert!(batch_decrypt_directory(dir, password, None, &config)?.was_successful());
+    let report = verify_restore(&manifest_path, dir, 2)?;
+    assert!(report.is_clean(), "{report:?}");
+    assert_eq!(report.verified, 3);
+
+    // 一个文件内容被篡改（大小不变），一个文件被删除，另有一个多余的文件
+    fs::write(dir.join("a.txt"), "alphA")?;
+    fs::remove_file(dir.join("sub/c.txt"))?;
+    fs::write(dir.join("sub/extra.txt"), "extra")?;
+    let report = verify_restore(&manifest_path, dir, 2)?;
+    assert!(!report.is_clean());
+    assert_eq!(report.verified, 1);
+    assert_eq!(report.mismatched, ["a.txt"]);
+    assert_eq!(report.missing, ["sub/c.txt"]);
+    assert_eq!(report.extra, ["sub/extra.txt"]);
+
+    // 清单已存在时，不使用 force 不会重新加密
+    assert!(batch_encrypt_directory_with_manifest(dir, password, None, &config).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_decrypt_overwrite_modes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+    let password = "overwrite_password";
+    fs::write(dir.join("a.txt"), "original a")?;
+    fs::write(dir.join("b.txt"), "original b")?;
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    assert!(batch_encrypt_directory(dir, password, None, &config)?.was_successful());
+    // `b.txt` 的目标文件已存在且被修改过；`a.txt` 被删除，它的解密不受覆盖模式影响
+    fs::write(dir.join("b.txt"), "edited b")?;
+    fs::remove_file(dir.join("a.txt"))?;
+
+    // Fail: 已存在的目标文件导致该文件失败，内容不变
+    let result = batch_decrypt_directory(dir, password, None, &config)?;
+    assert_eq!(
+        (
+            result.success_count,
+            result.failure_count,
+            result.skipped_count
+        ),
+        (1, 1, 0)
+    );
+    assert_eq!(fs::read_to_string(dir.join("b.txt"))?, "edited b");
+
+    // Skip: 已存在的目标文件被跳过，既不算成功也不算失败
+    fs::remove_file(dir.join("a.txt"))?;
+    let skip = BatchConfig {
+        overwrite_mode: OverwriteMode::Skip,
+        ..config.clone()
+    };
+    let result = batch_decrypt_directory(dir, password, None, &skip)?;
+    assert_eq!(
+        (
+            result.success_count,
+            result.failure_count,
+            result.skipped_count
+        ),
+        (1, 0, 1)
+    );
+    assert_eq!(fs::read_to_string(dir.join("b.txt"))?, "edited b");
+
+    // Overwrite: 认证通过后替换已存在的文件，不留下 `.part` 文件
+    let overwrite = BatchConfig {
+        overwrite_mode: OverwriteMode::Overwrite,
+        ..config.clone()
+    };
+    let result = batch_decrypt_directory(dir, password, None, &overwrite)?;
+    assert_eq!(
+        (
+            result.success_count,
+            result.failure_count,
+            result.skipped_count
+        ),
+        (2, 0, 0)
+    );
+    assert_eq!(fs::read_to_string(dir.join("a.txt"))?, "original a");
+    assert_eq!(fs::read_to_string(dir.join("b.txt"))?, "original b");
+    assert!(fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .all(|entry| !entry.file_name().to_string_lossy().ends_with(".part")));
+
+    // 密码错误时 Overwrite 不会破坏已存在的文件
+    // (第一个文件认证失败后其余文件不再尝试)
+    let result = batch_decrypt_directory(dir, "wrong_password", None, &overwrite)?;
+    assert_eq!(result.failure_count, 1);
+    assert!(result.aborted_early);
+    assert_eq!(result.unprocessed.len(), 1);
+    assert_eq!(fs::read_to_string(dir.join("b.txt"))?, "original b");
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_decrypt_output_dir_preserves_or_flattens_structure() -> Result<()> {
+    let source = TempDir::new()?;
+    let output = TempDir::new()?;
+    let dir = source.path();
+    let password = "flatten_password";
+    fs::create_dir_all(dir.join("a/deep"))?;
+    fs::create_dir(dir.join("b"))?;
+    fs::write(dir.join("top.txt"), "top")?;
+    fs::write(dir.join("a/deep/notes.txt"), "deep notes")?;
+    fs::write(dir.join("b/notes.txt"), "b notes")?;
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        recursive: true,
+        track_successful_paths: true,
+        ..Default::default()
+    };
+    assert!(batch_encrypt_directory(dir, password, None, &config)?.was_successful());
+    for name in ["top.txt", "a/deep/notes.txt", "b/notes.txt"] {
+        fs::remove_file(dir.join(name))?;
+    }
+
+    // 默认在输出目录中重建子目录结构
+    let preserve = BatchConfig {
+        output_dir: Some(output.path().join("preserved")),
+        ..config.clone()
+    };
+    let result = batch_decrypt_directory(dir, password, None, &preserve)?;
+    assert_eq!(result.success_count, 3);
+    let preserved = output.path().join("preserved");
+    assert_eq!(fs::read_to_string(preserved.join("top.txt"))?, "top");
+    assert_eq!(
+        fs::read_to_string(preserved.join("a/deep/notes.txt"))?,
+        "deep notes"
+    );
+    assert_eq!(
+        fs::read_to_string(preserved.join("b/notes.txt"))?,
+        "b notes"
+    );
+    assert!(result.output_paths.contains(&(
+        dir.join("a/deep/notes.txt.feroxcrypt"),
+        preserved.join("a/deep/notes.txt")
+    )));
+    // 加密文件旁边没有生成明文
+    assert!(!dir.join("top.txt").exists());
+    // 每个文件都报告文件头中记录的安全级别
+    assert_eq!(result.levels.len(), 3);
+    assert!(result
+        .levels
+        .iter()
+        .all(|(_, level)| *level == Some(Level::Interactive)));
+
+    // 平铺时同名文件按覆盖模式改名
+    let flat = output.path().join("flat");
+    let flatten = BatchConfig {
+        output_dir: Some(flat.clone()),
+        flatten: true,
+        overwrite_mode: OverwriteMode::Rename,
+        ..config.clone()
+    };
+    let result = batch_decrypt_directory(dir, password, None, &flatten)?;
+    assert_eq!(result.success_count, 3);
+    let mut names: Vec<String> = fs::read_dir(&flat)?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<_>>()?;
+    names.sort();
+    assert_eq!(names, ["notes (1).txt", "notes.txt", "top.txt"]);
+    let mut contents: Vec<String> = result
+        .output_paths
+        .iter()
+        .map(|(_, target)| {
+            assert_eq!(target.parent(), Some(flat.as_path()));
+            fs::read_to_string(target)
+        })
+        .collect::<std::io::Result<_>>()?;
+    contents.sort();
+    assert_eq!(contents, ["b notes", "deep notes", "top"]);
+
+    // 不指定输出目录时不能平铺
+    let invalid = BatchConfig {
+        flatten: true,
+        ..config
+    };
+    assert!(batch_decrypt_directory(dir, password, None, &invalid).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_max_file_size_skips_batch_files_and_rejects_single_files() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+    let password = "max_size_password";
+    let limit = 1024;
+    fs::write(dir.join("at_limit.bin"), vec![1u8; limit])?;
+    fs::write(dir.join("over_limit.bin"), vec![2u8; limit + 1])?;
+
+    // 批量加密：恰好等于上限的文件被加密，超过上限的文件被跳过而不是算作失败
+    let config = BatchConfig {
+        level: Level::Interactive,
+        max_file_size: Some(limit as u64),
+        ..Default::default()
+    };
+    let result = batch_encrypt_directory(dir, password, None, &config)?;
+    assert_eq!((result.success_count, result.failure_count), (1, 0));
+    assert_eq!(result.skipped_too_large, [dir.join("over_limit.bin")]);
+    assert!(dir.join("at_limit.bin.feroxcrypt").exists());
+    assert!(!dir.join("over_limit.bin.feroxcrypt").exists());
+
+    // 单个文件：返回类型化的错误
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        max_file_size: Some(limit as u64),
+        ..EncryptionOptions::new(password)
+    };
+    let error = encrypt_file_to_base64(&dir.join("over_limit.bin"), &opts).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<FeroxError>(),
+        Some(FeroxError::FileTooLarge {
+            size: 1025,
+            limit: 1024,
+            allocated: None
+        })
+    ));
+    assert!(encrypt_file_to_base64(&dir.join("at_limit.bin"), &opts).is_ok());
+
+    // 稀疏文件：错误中给出实际占用的磁盘空间
+    #[cfg(unix)]
+    {
+        let sparse = dir.join("disk.img");
+        fs::File::create(&sparse)?.set_len(64 * 1024 * 1024)?;
+        let error = encrypt_file_to_base64(&sparse, &opts).unwrap_err();
+        match error.downcast_ref::<FeroxError>() {
+            Some(FeroxError::FileTooLarge {
+                allocated: Some(allocated),
+                ..
+            }) => assert!(*allocated < 8 * 1024 * 1024),
+            other => panic!("应检测到稀疏文件: {other:?}"),
+        }
+        assert!(error.to_string().contains("稀疏文件"));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_batch_skips_fifos_instead_of_hanging() -> Result<()> {
+    use ferox_encryptor::{batch_encrypt_files, run_encryption_flow};
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+    let password = "fifo_password";
+    let fifo = dir.join("pipe");
+    let status = Command::new("mkfifo").arg(&fifo).status()?;
+    assert!(status.success(), "mkfifo 失败");
+    let regular = dir.join("regular.txt");
+    fs::write(&regular, b"plain data")?;
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+
+    // 显式列出的命名管道被跳过，而不是在读取时阻塞
+    let result = batch_encrypt_files(&[fifo.clone(), regular.clone()], password, None, &config)?;
+    assert!(result.was_successful());
+    assert_eq!(result.success_count, 1);
+    assert_eq!(result.skipped_special, std::slice::from_ref(&fifo));
+    assert!(!dir.join("pipe.feroxcrypt").exists());
+
+    // 扫描目录时不会收集命名管道
+    let config = BatchConfig {
+        force_overwrite: true,
+        ..config
+    };
+    let result = batch_encrypt_directory(dir, password, None, &config)?;
+    assert!(result.was_successful());
+    assert_eq!(result.success_count, 1);
+    assert!(result.skipped_special.is_empty());
+
+    // 单个文件的请求返回类型化的错误
+    let error = run_encryption_flow(
+        &fifo,
+        false,
+        password,
+        Level::Interactive,
+        None,
+        Arc::new(Mutex::new(None)),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<FeroxError>(),
+        Some(FeroxError::SpecialFile { .. })
+    ));
+
+    Ok(())
+}
+
+/// 严格模式测试使用的问题目录：一个正常文件、一个超过 1 KB 上限的文件和一个命名管道。
+#[cfg(unix)]
+fn build_problematic_tree() -> Result<TempDir> {
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+    fs::write(dir.join("ok.txt"), b"small file")?;
+    fs::write(dir.join("big.bin"), vec![0x5a; 4096])?;
+    let status = std::process::Command::new("mkfifo")
+        .arg(dir.join("pipe"))
+        .status()?;
+    assert!(status.success(), "mkfifo 失败");
+    Ok(temp_dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_strict_mode_turns_warnings_into_failures() -> Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let password = "strict_password";
+    let config = BatchConfig {
+        level: Level::Interactive,
+        max_file_size: Some(1024),
+        ..Default::default()
+    };
+
+    // 默认：超过上限的文件被跳过，命名管道在扫描时被略过，批量操作视为成功
+    let tree = build_problematic_tree()?;
+    let lenient = batch_encrypt_directory(tree.path(), password, None, &config)?;
+    assert!(lenient.was_successful());
+    assert_eq!(lenient.success_count, 1);
+    assert_eq!(lenient.skipped_too_large, [tree.path().join("big.bin")]);
+    assert!(lenient.skipped_special.is_empty());
+
+    // 严格模式：同一个目录中的两个问题文件都记录为失败
+    let tree = build_problematic_tree()?;
+    let strict_config = BatchConfig {
+        strict: true,
+        ..config.clone()
+    };
+    let strict = batch_encrypt_directory(tree.path(), password, None, &strict_config)?;
+    assert_eq!(strict.success_count, 1);
+    assert_eq!(strict.failure_count, 2);
+    assert!(strict.skipped_too_large.is_empty() && strict.skipped_special.is_empty());
+    assert!(
+        strict
+            .failures
+            .iter()
+            .any(|(path, e)| path.ends_with("big.bin")
+                && matches!(e, FeroxError::FileTooLarge { .. }))
+    );
+    assert!(strict
+        .failures
+        .iter()
+        .any(|(path, e)| path.ends_with("pipe") && matches!(e, FeroxError::SpecialFile { .. })));
+
+    // 残留的 `.part` 文件在严格模式下使整个批量操作失败
+    fs::write(tree.path().join("stale.txt.feroxcrypt.part"), b"partial")?;
+    let error = batch_encrypt_directory(tree.path(), password, None, &strict_config).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<FeroxError>(),
+        Some(FeroxError::StrictViolation(_))
+    ));
+
+    // 命令行：同一个目录，默认以 0 退出，--strict 以非零状态退出
+    let run_cli = |strict: bool| -> Result<bool> {
+        let tree = build_problematic_tree()?;
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ferox_encryptor"));
+        command
+            .args([
+                "--stdin-password-confirm",
+                "--no-progress",
+                "--max-size-hard-limit",
+                "1KB",
+            ])
+            .arg("batch-encrypt")
+            .arg(tree.path())
+            .args(["--level", "interactive", "--no-location-check"]);
+        if strict {
+            command.arg("--strict");
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        writeln!(
+            child.stdin.take().expect("标准输入"),
+            "{password}\n{password}"
+        )?;
+        let status = child.wait()?;
+        assert!(tree.path().join("ok.txt.feroxcrypt").exists());
+        Ok(status.success())
+    };
+    assert!(run_cli(false)?, "默认模式下跳过文件不应改变退出状态");
+    assert!(!run_cli(true)?, "严格模式下有失败时应以非零状态退出");
+
+    Ok(())
+}
+
+#[test]
+fn test_password_validator_rejects_before_touching_files() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+    fs::write(dir.join("a.txt"), b"alpha")?;
+    fs::write(dir.join("b.txt"), b"bravo")?;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&calls);
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    }
+    .with_password_validator(move |password| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        anyhow::ensure!(password.len() >= 12, "密码至少需要 12 个字符");
+        Ok(())
+    });
+
+    let error = batch_encrypt_directory(dir, "short", None, &config).unwrap_err();
+    assert!(format!("{error:#}").contains("至少需要 12 个字符"));
+    assert!(!dir.join("a.txt.feroxcrypt").exists());
+    assert!(!dir.join("b.txt.feroxcrypt").exists());
+
+    // 混合了文件和目录的路径列表只检查一次密码
+    calls.store(0, Ordering::Relaxed);
+    let sub = dir.join("sub");
+    fs::create_dir(&sub)?;
+    fs::write(sub.join("c.txt"), b"charlie")?;
+    let result = batch_encrypt_paths(
+        &[dir.join("a.txt"), dir.join("b.txt"), sub],
+        "a sufficiently long password",
+        None,
+        &config,
+    )?;
+    assert_eq!(result.success_count, 3);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_plan_matches_single_call_encryption() -> Result<()> {
+    let password = "plan_password";
+    let config = BatchConfig {
+        level: Level::Interactive,
+        max_file_size: Some(64),
+        track_successful_paths: true,
+        ..Default::default()
+    };
+    let populate = |dir: &std::path::Path| -> Result<()> {
+        fs::write(dir.join("a.txt"), "alpha")?;
+        fs::write(dir.join("b.txt"), "bravo")?;
+        fs::write(dir.join("b.txt.feroxcrypt"), "already there")?;
+        fs::write(dir.join("big.bin"), vec![7u8; 128])?;
+        Ok(())
+    };
+    let planned = TempDir::new()?;
+    let direct = TempDir::new()?;
+    populate(planned.path())?;
+    populate(direct.path())?;
+
+    // 生成计划不需要密码，也不修改任何文件
+    let plan = plan_batch_encrypt(&[planned.path().to_path_buf()], &config)?;
+    let item = |name: &str| {
+        plan.items
+            .iter()
+            .find(|item| item.source.ends_with(name))
+            .unwrap()
+    };
+    assert_eq!(plan.items.len(), 3);
+    assert_eq!(plan.encrypt_count(), 2);
+    assert_eq!(plan.skip_count(), 1);
+    assert_eq!(plan.total_bytes(), 10);
+    assert_eq!(item("big.bin").action, PlannedAction::SkipTooLarge);
+    assert!(item("a.txt").warnings.is_empty());
+    assert_eq!(
+        item("a.txt").target,
+        planned.path().join("a.txt.feroxcrypt")
+    );
+    assert!(item("b.txt").warnings[0].contains("已存在"));
+    assert!(!planned.path().join("a.txt.feroxcrypt").exists());
+
+    // 计划可以序列化后交给其他进程执行
+    let plan: BatchPlan = serde_json::from_str(&serde_json::to_string(&plan)?)?;
+    let from_plan = execute_batch_plan(&plan, password, None, &config)?;
+    let single_call = batch_encrypt_directory(direct.path(), password, None, &config)?;
+
+    assert_eq!(from_plan.success_count, single_call.success_count);
+    assert_eq!(from_plan.failure_count, single_call.failure_count);
+    assert_eq!(
+        from_plan.skipped_too_large.len(),
+        single_call.skipped_too_large.len()
+    );
+    assert_eq!(from_plan.total_bytes, single_call.total_bytes);
+    let names = |result: &ferox_encryptor::BatchResult| -> Vec<String> {
+        result
+            .processed
+            .iter()
+            .map(|(path, ok)| format!("{}:{ok}", path.file_name().unwrap().to_string_lossy()))
+            .collect()
+    };
+    assert_eq!(names(&from_plan), names(&single_call));
+    assert_eq!(names(&from_plan), ["a.txt:true", "b.txt:false"]);
+
+    // 预计的输出大小与实际加密文件的大小一致
+    assert_eq!(
+        fs::metadata(planned.path().join("a.txt.feroxcrypt"))?.len(),
+        item("a.txt").estimated_output_size
+    );
+    Ok(())
+}
+
+#[test]
+fn test_batch_failures_report_stage() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    fs::write(temp_dir.path().join("good.txt"), b"stage test")?;
+    let config = BatchConfig {
+        track_successful_paths: true,
+        ..Default::default()
+    };
+    batch_encrypt_directory(temp_dir.path(), "right password", None, &config)?;
+    fs::remove_file(temp_dir.path().join("good.txt"))?;
+    let corrupt = temp_dir.path().join("corrupt.txt.feroxcrypt");
+    fs::write(&corrupt, b"not an encrypted file")?;
+
+    let result = batch_decrypt_directory(temp_dir.path(), "wrong password", None, &config)?;
+    assert_eq!(result.failure_count, 2);
+    let good = temp_dir.path().join("good.txt.feroxcrypt");
+    // 认证标签在流式解密结束后验证
+    assert_eq!(result.failure_stage(&good), Some(Stage::Finalize));
+    assert_eq!(result.failure_stage(&corrupt), Some(Stage::HeaderParse));
+
+    let mut json = Vec::new();
+    result.to_json(&mut json)?;
+    let report: serde_json::Value = serde_json::from_slice(&json)?;
+    assert_eq!(report["failure_count"], 2);
+    let stages: Vec<&str> = report["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["stage"].as_str().unwrap())
+        .collect();
+    assert_eq!(stages.len(), 2);
+    assert!(stages.contains(&"finalize") && stages.contains(&"header_parse"));
+    assert_eq!(report["files"][0]["status"], "failure");
+    Ok(())
+}
+
+/// 统计开始处理的文件数。
+#[derive(Default)]
+struct StartedCounter(AtomicUsize);
+
+impl ProgressSink for StartedCounter {
+    fn on_event(&self, event: &ProgressEvent<'_>) {
+        if let ProgressEvent::FileStarted { .. } = event {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+#[test]
+fn test_batch_decrypt_aborts_on_wrong_keyfile() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(temp_dir.path().join(name), name.as_bytes())?;
+    }
+    let right = KeyFile::generate();
+    let wrong = KeyFile::generate();
+    let config = BatchConfig {
+        level: Level::Interactive,
+        keyfile_commitment: true,
+        ..Default::default()
+    };
+    batch_encrypt_directory(temp_dir.path(), "password", Some(&right), &config)?;
+
+    let counter = Arc::new(StartedCounter::default());
+    let config = BatchConfig {
+        progress_sink: Some(counter.clone()),
+        ..Default::default()
+    };
+    let result = batch_decrypt_directory(temp_dir.path(), "password", Some(&wrong), &config)?;
+    // 只尝试了第一个文件，其余文件未处理
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert!(result.aborted_early);
+    assert_eq!(result.failure_count, 1);
+    assert_eq!(result.unprocessed.len(), 2);
+    assert!(result.failures[0].1.is_authentication_failure());
+
+    let counter = Arc::new(StartedCounter::default());
+    let config = BatchConfig {
+        progress_sink: Some(counter.clone()),
+        keep_going: true,
+        ..Default::default()
+    };
+    let result = batch_decrypt_directory(temp_dir.path(), "password", Some(&wrong), &config)?;
+    assert_eq!(counter.0.load(Ordering::SeqCst), 3);
+    assert!(!result.aborted_early);
+    assert_eq!(result.failure_count, 3);
+    assert!(result.unprocessed.is_empty());
+    Ok(())
+}
+
+/// 处理指定名称的文件时在字节进度事件中 panic，模拟某个文件触发的程序缺陷。
+struct PanicOnFile {
+    name: &'static str,
+    active: AtomicBool,
+}
+
+impl ProgressSink for PanicOnFile {
+    fn on_event(&self, event: &ProgressEvent<'_>) {
+        match event {
+            ProgressEvent::FileStarted { path, .. } => {
+                let matches = path.file_name().is_some_and(|name| name == self.name);
+                self.active.store(matches, Ordering::SeqCst);
+            }
+            ProgressEvent::BytesProcessed { .. } if self.active.load(Ordering::SeqCst) => {
+                panic!("注入的 panic");
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_batch_continues_after_per_file_panic() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let files: Vec<_> = ["a.txt", "bad.txt", "c.txt"]
+        .iter()
+        .map(|name| temp_dir.path().join(name))
+        .collect();
+    for file in &files {
+        fs::write(file, b"panic safety")?;
+    }
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        progress_sink: Some(Arc::new(PanicOnFile {
+            name: "bad.txt",
+            active: AtomicBool::new(false),
+        })),
+        ..Default::default()
+    };
+    let result = batch_encrypt_iter(files.clone().into_iter(), "password", None, &config)?;
+    assert_eq!(result.success_count, 2);
+    assert_eq!(result.failure_count, 1);
+    let (failed, error) = &result.failures[0];
+    assert_eq!(failed, &files[1]);
+    assert!(error.is_panic(), "{error}");
+    assert!(error.to_string().contains("注入的 panic"));
+    // 发生 panic 的文件不留下不完整的输出，之后的文件照常加密
+    assert!(!temp_dir.path().join("bad.txt.feroxcrypt").exists());
+    assert!(!temp_dir.path().join("bad.txt.feroxcrypt.part").exists());
+    assert!(temp_dir.path().join("c.txt.feroxcrypt").exists());
+
+    let encrypted = vec![
+        temp_dir.path().join("a.txt.feroxcrypt"),
+        temp_dir.path().join("c.txt.feroxcrypt"),
+    ];
+    for file in &files {
+        fs::remove_file(file)?;
+    }
+    let config = BatchConfig {
+        progress_sink: Some(Arc::new(PanicOnFile {
+            name: "a.txt.feroxcrypt",
+            active: AtomicBool::new(false),
+        })),
+        ..Default::default()
+    };
+    let result = batch_decrypt_paths(&encrypted, "password", None, &config)?;
+    assert_eq!(result.success_count, 1);
+    assert!(result.failures[0].1.is_panic());
+    assert!(!files[0].exists());
+    assert_eq!(fs::read(&files[2])?, b"panic safety");
+    Ok(())
+}
+
+#[test]
+fn test_batch_encrypt_rerun_skips_up_to_date_targets() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    for name in ["a.txt", "b.txt"] {
+        fs::write(temp_dir.path().join(name), name.as_bytes())?;
+    }
+    let run = |force_overwrite: bool| -> Result<(ferox_encryptor::BatchResult, usize)> {
+        let counter = Arc::new(StartedCounter::default());
+        let config = BatchConfig {
+            level: Level::Interactive,
+            skip_if_encrypted_matches: true,
+            force_overwrite,
+            track_successful_paths: true,
+            progress_sink: Some(counter.clone()),
+            ..Default::default()
+        };
+        let result = batch_encrypt_directory(temp_dir.path(), "password", None, &config)?;
+        Ok((result, counter.0.load(Ordering::SeqCst)))
+    };
+    let encrypted_a = temp_dir.path().join("a.txt.feroxcrypt");
+
+    let (first, started) = run(false)?;
+    assert_eq!((first.success_count, started), (2, 2));
+    let before = fs::read(&encrypted_a)?;
+
+    // 第二次运行不处理任何文件，加密文件保持不变（重新加密会生成新的盐和 IV）
+    let (second, started) = run(false)?;
+    assert_eq!(started, 0);
+    assert_eq!((second.success_count, second.failure_count), (0, 0));
+    assert_eq!(second.skipped_up_to_date.len(), 2);
+    assert_eq!(fs::read(&encrypted_a)?, before);
+
+    // 大小不变但修改时间变了的文件不再匹配，按常规逻辑处理
+    let source = temp_dir.path().join("a.txt");
+    fs::write(&source, b"A.txt")?;
+    fs::File::options()
+        .write(true)
+        .open(&source)?
+        .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(3600))?;
+    let (third, started) = run(false)?;
+    assert_eq!((third.failure_count, started), (1, 1));
+    assert_eq!(third.skipped_up_to_date, [temp_dir.path().join("b.txt")]);
+
+    let (fourth, started) = run(true)?;
+    assert_eq!((fourth.success_count, started), (1, 1));
+    assert_eq!(fourth.skipped_up_to_date.len(), 1);
+    assert_ne!(fs::read(&encrypted_a)?, before);
+
+    // 计划同样把已是最新的文件标记为跳过
+    let config = BatchConfig {
+        skip_if_encrypted_matches: true,
+        ..Default::default()
+    };
+    let plan = plan_batch_encrypt(&[temp_dir.path().to_path_buf()], &config)?;
+    assert_eq!(plan.encrypt_count(), 0);
+    assert!(plan
+        .items
+        .iter()
+        .all(|item| item.action == PlannedAction::SkipAlreadyUpToDate));
+    Ok(())
+}
+
+#[test]
+fn test_batch_reports_bytes_read_and_written() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let files = [("small.txt", 10), ("large.bin", 70_000)];
+    for (name, len) in files {
+        fs::write(temp_dir.path().join(name), vec![0x5a; len])?;
+    }
+    let plaintext_total: u64 = files.iter().map(|(_, len)| *len as u64).sum();
+    let config = BatchConfig {
+        level: Level::Interactive,
+        track_successful_paths: true,
+        ..Default::default()
+    };
+
+    // 加密读取明文，写入带文件头和认证标签的加密文件
+    let encrypted = batch_encrypt_directory(temp_dir.path(), "password", None, &config)?;
+    let encrypted_total: u64 = files
+        .iter()
+        .map(|(name, _)| fs::metadata(temp_dir.path().join(format!("{name}.feroxcrypt"))))
+        .map(|metadata| metadata.map(|m| m.len()))
+        .sum::<std::io::Result<_>>()?;
+    assert_eq!(encrypted.bytes_read, plaintext_total);
+    assert_eq!(encrypted.bytes_written, encrypted_total);
+
+    let mut json = Vec::new();
+    encrypted.to_json(&mut json)?;
+    let report: serde_json::Value = serde_json::from_slice(&json)?;
+    assert_eq!(report["bytes_written"], encrypted_total);
+    let small = report["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|file| file["path"].as_str().unwrap().ends_with("small.txt"))
+        .unwrap();
+    assert_eq!(small["bytes_read"], 10);
+    assert!(small["bytes_written"].as_u64().unwrap() > 10);
+
+    // 解密正好相反，读取的是整个加密文件
+    for (name, _) in files {
+        fs::remove_file(temp_dir.path().join(name))?;
+    }
+    let decrypted = batch_decrypt_directory(temp_dir.path(), "password", None, &config)?;
+    assert_eq!(decrypted.success_count, 2);
+    assert_eq!(decrypted.bytes_read, encrypted_total);
+    assert_eq!(decrypted.bytes_written, plaintext_total);
+    // `total_bytes` 仍按操作前的元数据统计
+    assert_eq!(decrypted.total_bytes, encrypted_total);
+    Ok(())
+}
+
+#[test]
+fn test_batch_decrypt_with_credentials_map() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path().join("archive");
+    let keys_dir = TempDir::new()?;
+    for (name, content) in [
+        ("old/a.txt", "old a"),
+        ("old/b.txt", "old b"),
+        ("new/c.txt", "new c"),
+        ("d.txt", "new d"),
+        ("e.txt", "unknown e"),
+    ] {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, content)?;
+    }
+    let keyfile = KeyFile::generate();
+    keyfile.save_to_file(keys_dir.path().join("new.key"))?;
+    let config = BatchConfig {
+        level: Level::Interactive,
+        keyfile_commitment: true,
+        ..Default::default()
+    };
+    batch_encrypt_directory(&root.join("old"), "old-password", None, &config)?;
+    batch_encrypt_directory(&root.join("new"), "new-password", Some(&keyfile), &config)?;
+    batch_encrypt_paths(&[root.join("d.txt")], "new-password", None, &config)?;
+    batch_encrypt_paths(
+        &[root.join("e.txt")],
+        "other",
+        Some(&KeyFile::generate()),
+        &config,
+    )?;
+    for name in ["old/a.txt", "old/b.txt", "new/c.txt", "d.txt", "e.txt"] {
+        fs::remove_file(root.join(name))?;
+    }
+
+    let map_path = keys_dir.path().join("credentials.toml");
+    fs::write(
+        &map_path,
+        r#"
+        passwords = ["new-password"]
+
+        [[credentials]]
+        name = "old"
+        password = "old-password"
+        files = ["old/**"]
+
+        [[credentials]]
+        name = "new-with-key"
+        password = "new-password"
+        keyfile = "new.key"
+        files = ["new/*"]
+        "#,
+    )?;
+    let config = BatchConfig {
+        recursive: true,
+        credentials: Some(Arc::new(Credentials::load(&map_path)?)),
+        ..Default::default()
+    };
+    let result = batch_decrypt_directory(&root, "", None, &config)?;
+
+    assert_eq!(result.success_count, 4);
+    assert!(!result.aborted_early);
+    for (name, content) in [
+        ("old/a.txt", "old a"),
+        ("old/b.txt", "old b"),
+        ("new/c.txt", "new c"),
+        ("d.txt", "new d"),
+    ] {
+        assert_eq!(fs::read_to_string(root.join(name))?, content);
+    }
+    // e.txt 用密钥文件加密，唯一适用的凭据不带密钥文件，被文件头中的承诺直接排除
+    assert_eq!(result.failure_count, 1);
+    let (failed, error) = &result.failures[0];
+    assert!(failed.ends_with("e.txt.feroxcrypt"));
+    assert!(error.is_authentication_failure());
+    match error {
+        FeroxError::CredentialsExhausted { attempted } => {
+            assert_eq!(attempted, &["密码 #3 (文件加密时使用了密钥文件)"]);
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_batch_hardlinks_encrypted_once_and_restored() -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+    let password = "hardlink_password";
+    let build_tree = || -> Result<()> {
+        fs::create_dir_all(dir.join("albums/2024"))?;
+        fs::write(dir.join("photo.jpg"), vec![7u8; 3000])?;
+        fs::hard_link(dir.join("photo.jpg"), dir.join("albums/2024/photo.jpg"))?;
+        fs::write(dir.join("notes.txt"), "notes")?;
+        Ok(())
+    };
+    build_tree()?;
+    let config = BatchConfig {
+        level: Level::Interactive,
+        recursive: true,
+        dedupe_hardlinks: true,
+        ..Default::default()
+    };
+
+    // 只跳过重复的硬链接：计划和执行结果一致，内容只加密一次
+    let plan = plan_batch_encrypt(&[dir.to_path_buf()], &config)?;
+    assert_eq!(plan.encrypt_count(), 2);
+    assert_eq!(
+        plan.items
+            .iter()
+            .filter(|item| item.action == PlannedAction::SkipHardLinkDuplicate)
+            .count(),
+        1
+    );
+    let result = batch_encrypt_directory(dir, password, None, &config)?;
+    assert_eq!(result.success_count, 2);
+    assert_eq!(result.skipped_hardlinks.len(), 1);
+    assert_eq!(result.hardlink_bytes_saved, 3000);
+    let (duplicate, original) = &result.skipped_hardlinks[0];
+    assert!(!duplicate.with_extension("jpg.feroxcrypt").exists());
+    assert!(original.with_extension("jpg.feroxcrypt").exists());
+
+    // 记录到清单中时，解密可以重建硬链接
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+    }
+    build_tree()?;
+    let (result, manifest) = batch_encrypt_directory_with_manifest(dir, password, None, &config)?;
+    assert_eq!(result.skipped_hardlinks.len(), 1);
+    assert_eq!(manifest.hardlinks.len(), 1);
+    for name in ["photo.jpg", "albums/2024/photo.jpg", "notes.txt"] {
+        fs::remove_file(dir.join(name))?;
+    }
+    let config = BatchConfig {
+        recursive: true,
+        preserve_hardlinks: true,
+        ..Default::default()
+    };
+    let result = batch_decrypt_directory(dir, password, None, &config)?;
+    assert!(result.was_successful());
+    assert_eq!(result.success_count, 2);
+    assert_eq!(result.restored_hardlinks.len(), 1);
+    let first = fs::metadata(dir.join("photo.jpg"))?;
+    let second = fs::metadata(dir.join("albums/2024/photo.jpg"))?;
+    assert_eq!(first.ino(), second.ino());
+    assert_eq!(first.nlink(), 2);
+    let report = verify_restore(&dir.join("ferox-manifest.json"), dir, 1)?;
+    assert!(report.is_clean(), "{report:?}");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_on_complete_hook_receives_summary_without_password() -> Result<()> {
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new()?;
+    let hook_dir = TempDir::new()?;
+    let hook = hook_dir.path().join("hook.sh");
+    fs::write(
+        &hook,
+        format!(
+            "cat > '{dir}/payload.json'\nenv > '{dir}/env.txt'\n",
+            dir = hook_dir.path().display()
+        ),
+    )?;
+    let secret = "hook-secret-password";
+
+    // 密码来自环境变量，标准输入为空
+    let run_cli = |hook_command: &str, dir: &std::path::Path| -> Result<bool> {
+        let status = Command::new(env!("CARGO_BIN_EXE_ferox_encryptor"))
+            .env("FEROX_TEST_HOOK_PASSWORD", secret)
+            .args(["--stdin-password-confirm", "--no-progress"])
+            .args(["--stdin-password-env-override", "FEROX_TEST_HOOK_PASSWORD"])
+            .args(["--on-complete", hook_command, "--on-complete-timeout", "1"])
+            .arg("batch-encrypt")
+            .arg(dir)
+            .args(["--level", "interactive", "--no-location-check"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        Ok(status.success())
+    };
+
+    for name in ["a.txt", "b.txt"] {
+        fs::write(temp_dir.path().join(name), name)?;
+    }
+    assert!(run_cli(
+        &format!("sh '{}'", hook.display()),
+        temp_dir.path()
+    )?);
+    let payload: serde_json::Value =
+        serde_json::from_slice(&fs::read(hook_dir.path().join("payload.json"))?)?;
+    assert_eq!(payload["operation"], "batch-encrypt");
+    assert_eq!(payload["success_count"], 2);
+    assert_eq!(payload["failure_count"], 0);
+    assert_eq!(payload["files"].as_array().map(Vec::len), Some(2));
+    assert!(payload["duration_secs"].as_f64().is_some());
+    let env = fs::read_to_string(hook_dir.path().join("env.txt"))?;
+    assert!(env.contains("FEROX_OPERATION=batch-encrypt"));
+    assert!(env.contains("FEROX_SUCCESS_COUNT=2"));
+    assert!(env.contains("FEROX_FAILURE_COUNT=0"));
+    assert!(env.contains("FEROX_DURATION_SECS="));
+    assert!(!env.contains(secret), "钩子的环境变量中不能有密码");
+
+    // 钩子失败或超时只是警告，批量操作仍然成功
+    for (index, command) in ["exit 3", "sleep 5"].into_iter().enumerate() {
+        let dir = temp_dir.path().join(format!("run{index}"));
+        fs::create_dir(&dir)?;
+        fs::write(dir.join("c.txt"), "c")?;
+        let started = Instant::now();
+        assert!(run_cli(command, &dir)?, "钩子 {command} 不应改变退出状态");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(dir.join("c.txt.feroxcrypt").exists());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_on_complete_hook_only_from_flag_or_config_file() -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    let hook_dir = TempDir::new()?;
+    let payload = hook_dir.path().join("payload.json");
+    let hook_command = format!("cat > '{}'", payload.display());
+
+    let run_cli = |dir: &std::path::Path, extra: &[&std::ffi::OsStr]| -> Result<bool> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("a.txt"), "a")?;
+        let status = Command::new(env!("CARGO_BIN_EXE_ferox_encryptor"))
+            .env("FEROX_TEST_HOOK_PASSWORD", "hook-config-password")
+            // 继承的环境变量不能触发钩子
+            .env("FEROX_ON_COMPLETE", &hook_command)
+            .args(["--stdin-password-confirm", "--no-progress"])
+            .args(["--stdin-password-env-override", "FEROX_TEST_HOOK_PASSWORD"])
+            .args(extra)
+            .arg("batch-encrypt")
+            .arg(dir)
+            .args(["--level", "interactive", "--no-location-check"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        Ok(status.success())
+    };
+
+    let work_dir = TempDir::new()?;
+    assert!(run_cli(&work_dir.path().join("env"), &[])?);
+    assert!(!payload.exists(), "不应从环境变量读取钩子");
+
+    let config = hook_dir.path().join("hook.toml");
+    fs::write(
+        &config,
+        format!("command = \"{hook_command}\"\ntimeout_secs = 5\n"),
+    )?;
+    let flag = std::ffi::OsStr::new("--on-complete-config");
+    assert!(run_cli(
+        &work_dir.path().join("config"),
+        &[flag, config.as_os_str()]
+    )?);
+    let summary: serde_json::Value = serde_json::from_slice(&fs::read(&payload)?)?;
+    assert_eq!(summary["operation"], "batch-encrypt");
+    assert_eq!(summary["success_count"], 1);
+
+    // 配置文件有误时在批量操作开始之前失败
+    fs::write(&config, "command = \"\"\n")?;
+    let dir = work_dir.path().join("invalid");
+    assert!(!run_cli(&dir, &[flag, config.as_os_str()])?);
+    assert!(!dir.join("a.txt.feroxcrypt").exists());
+    Ok(())
+}
+
+#[test]
+fn test_batch_encrypt_writes_directory_readme() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    for name in ["a.txt", "b.txt"] {
+        fs::write(temp_dir.path().join(name), name.as_bytes())?;
+    }
+    let keyfile = KeyFile::generate();
+    let config = BatchConfig {
+        level: Level::Interactive,
+        write_readme: true,
+        ..Default::default()
+    };
+    let result =
+        batch_encrypt_directory(temp_dir.path(), "readme-secret", Some(&keyfile), &config)?;
+    let text_path = temp_dir.path().join("FEROX-ENCRYPTED.txt");
+    assert_eq!(result.readme_paths, std::slice::from_ref(&text_path));
+
+    let text = fs::read_to_string(&text_path)?;
+    assert!(text.contains(env!("CARGO_PKG_VERSION")));
+    assert!(text.contains("AES-256-CTR + HMAC-SHA256 (id 1)"));
+    assert!(text.contains("Argon2id v0x13 (id 1)"));
+    assert!(text.contains("安全级别 (Level): Interactive"));
+    assert!(text.contains("加密文件数量 (Encrypted files): 2"));
+    assert!(text.contains("batch-decrypt . --keyfile <密钥文件>"));
+    assert!(!text.contains("readme-secret"));
+    let readme: DirectoryReadme = serde_json::from_str(&fs::read_to_string(
+        temp_dir.path().join("FEROX-ENCRYPTED.json"),
+    )?)?;
+    assert_eq!(readme.file_count, 2);
+    assert!(readme.keyfile_required);
+    assert_eq!(readme.format_version, 2);
+
+    // 第二次运行不加密说明文件本身，并整体重新生成说明
+    fs::write(temp_dir.path().join("c.txt"), b"c")?;
+    let result =
+        batch_encrypt_directory(temp_dir.path(), "readme-secret", Some(&keyfile), &config)?;
+    assert_eq!(result.success_count, 1);
+    assert!(!temp_dir
+        .path()
+        .join("FEROX-ENCRYPTED.txt.feroxcrypt")
+        .exists());
+    assert!(!temp_dir
+        .path()
+        .join("FEROX-ENCRYPTED.json.feroxcrypt")
+        .exists());
+    let text = fs::read_to_string(&text_path)?;
+    assert!(text.contains("加密文件数量 (Encrypted files): 3"));
+    assert_eq!(text.matches("加密文件数量").count(), 1);
+    Ok(())
+}
+
+/// 第一次处理某个文件失败后释放它的独占句柄，模拟用户关闭了占用它的程序。
+#[cfg(windows)]
+struct ReleaseOnFailure(std::sync::Mutex<Option<fs::File>>);
+
+#[cfg(windows)]
+impl ProgressSink for ReleaseOnFailure {
+    fn on_event(&self, event: &ProgressEvent<'_>) {
+        if let ProgressEvent::FileFinished { success: false, .. } = event {
+            self.0.lock().unwrap().take();
+        }
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn test_batch_encrypt_groups_and_retries_locked_files() -> Result<()> {
+    use ferox_encryptor::batch_encrypt_files;
+    use std::os::windows::fs::OpenOptionsExt;
+
+    let temp_dir = TempDir::new()?;
+    let locked = temp_dir.path().join("inbox.pst");
+    let free = temp_dir.path().join("notes.txt");
+    fs::write(&locked, b"held open by a mail client")?;
+    fs::write(&free, b"free to read")?;
+    let exclusive = || {
+        fs::OpenOptions::new()
+            .read(true)
+            .share_mode(0)
+            .open(&locked)
+    };
+    let files = [locked.clone(), free.clone()];
+
+    // 不重试：共享冲突归类为 FileLocked，与其他失败分开
+    let handle = exclusive()?;
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    let result = batch_encrypt_files(&files, "locked_password", None, &config)?;
+    drop(handle);
+    assert_eq!(result.success_count, 1);
+    assert_eq!(result.all_locked_failures(), vec![&locked]);
+    assert!(result.all_io_failures().is_empty());
+    assert!(!temp_dir.path().join("inbox.pst.feroxcrypt").exists());
+
+    // 重试：第一次失败后句柄被释放，最后的重试成功
+    let config = BatchConfig {
+        force_overwrite: true,
+        retry_locked: true,
+        track_successful_paths: true,
+        progress_sink: Some(Arc::new(ReleaseOnFailure(std::sync::Mutex::new(Some(
+            exclusive()?,
+        ))))),
+        ..config
+    };
+    let result = batch_encrypt_files(&files, "locked_password", None, &config)?;
+    assert!(result.was_successful());
+    assert_eq!(result.success_count, 2);
+    assert_eq!(result.processed.last(), Some(&(locked, true)));
+    Ok(())
+}
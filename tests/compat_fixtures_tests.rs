@@ -0,0 +1,164 @@
+// tests/compat_fixtures_tests.rs
+
+//! 兼容性夹具测试：`tests/fixtures/compat` 中保存了由 `examples/generate_fixtures.rs` 生成的样本，
+//! 每个格式版本的每种组合（加密算法 × 密钥派生算法 × 安全级别 × 是否使用密钥文件）各一个。
+//!
+//! 清单 `manifest.json` 中的每个样本都必须能解密，明文的长度、SHA-256 和原始文件名都必须与清单一致；
+//! 目录中不允许出现清单之外的样本。格式版本升级后用 `FEROX_ALLOW_DETERMINISTIC=1 cargo run --example generate_fixtures`
+//! 为新版本生成样本，旧版本的样本保持不变，继续参与这里的解密测试。
+
+use anyhow::{Context, Result};
+use ferox_encryptor::constants::CURRENT_FORMAT_VERSION;
+use ferox_encryptor::keyfile::KeyFile;
+use ferox_encryptor::{decrypt_in_memory, DecryptionOptions};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// 清单中一个样本的记录。
+#[derive(Debug, Deserialize)]
+struct FixtureEntry {
+    format_version: u8,
+    level: String,
+    keyfile: bool,
+    original_filename: String,
+    plaintext_len: usize,
+    plaintext_sha256: String,
+    ciphertext_sha256: String,
+}
+
+/// `manifest.json` 的内容。
+#[derive(Debug, Deserialize)]
+struct FixtureManifest {
+    password: String,
+    keyfile: String,
+    fixtures: BTreeMap<String, FixtureEntry>,
+}
+
+fn compat_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/compat")
+}
+
+fn load_manifest() -> Result<FixtureManifest> {
+    let path = compat_dir().join("manifest.json");
+    let json = fs::read_to_string(&path)
+        .with_context(|| format!("无法读取夹具清单: {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("无效的夹具清单: {}", path.display()))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(data) {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+/// 收集 `dir` 下所有 `.feroxcrypt` 文件相对于 `root` 的路径（使用 `/` 分隔）。
+fn collect_fixtures(root: &Path, dir: &Path, found: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_fixtures(root, &path, found)?;
+        } else if path.extension().is_some_and(|ext| ext == "feroxcrypt") {
+            let relative = path.strip_prefix(root)?;
+            let parts: Vec<_> = relative.iter().map(|part| part.to_string_lossy()).collect();
+            found.push(parts.join("/"));
+        }
+    }
+    Ok(())
+}
+
+fn check_fixture(
+    relative: &str,
+    entry: &FixtureEntry,
+    password: &str,
+    keyfile: &KeyFile,
+) -> Result<()> {
+    let ciphertext = fs::read(compat_dir().join(relative))
+        .with_context(|| format!("无法读取夹具: {relative}"))?;
+    assert_eq!(
+        sha256_hex(&ciphertext),
+        entry.ciphertext_sha256,
+        "{relative}: 夹具文件被修改过"
+    );
+    let opts = DecryptionOptions {
+        keyfile: entry.keyfile.then_some(keyfile),
+        ..DecryptionOptions::new(password)
+    };
+    let (plaintext, original_filename) = decrypt_in_memory(&ciphertext, &opts)
+        .with_context(|| format!("{relative} ({}) 解密失败", entry.level))?;
+    assert_eq!(
+        plaintext.len(),
+        entry.plaintext_len,
+        "{relative}: 明文长度不符"
+    );
+    assert_eq!(
+        sha256_hex(&plaintext),
+        entry.plaintext_sha256,
+        "{relative}: 明文哈希不符"
+    );
+    assert_eq!(
+        original_filename, entry.original_filename,
+        "{relative}: 原始文件名不符"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_every_compat_fixture_decrypts() -> Result<()> {
+    let manifest = load_manifest()?;
+    let keyfile = KeyFile::load_from_file(compat_dir().join(&manifest.keyfile))?;
+
+    // Paranoid 级别的样本在调试构建中派生很慢，每个样本使用一个线程
+    thread::scope(|scope| {
+        let handles: Vec<_> = manifest
+            .fixtures
+            .iter()
+            .map(|(relative, entry)| {
+                let (password, keyfile) = (&manifest.password, &keyfile);
+                scope.spawn(move || check_fixture(relative, entry, password, keyfile))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("夹具检查线程崩溃"))
+    })
+}
+
+#[test]
+fn test_compat_fixtures_are_listed_in_manifest() -> Result<()> {
+    let manifest = load_manifest()?;
+    let root = compat_dir();
+    let mut found = Vec::new();
+    collect_fixtures(&root, &root, &mut found)?;
+    found.sort();
+    let listed: Vec<_> = manifest.fixtures.keys().cloned().collect();
+    assert_eq!(
+        found, listed,
+        "夹具目录与清单不一致，请运行 FEROX_ALLOW_DETERMINISTIC=1 cargo run --example generate_fixtures"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_compat_fixtures_cover_current_format() -> Result<()> {
+    let manifest = load_manifest()?;
+    let current: Vec<_> = manifest
+        .fixtures
+        .values()
+        .filter(|entry| entry.format_version == CURRENT_FORMAT_VERSION)
+        .collect();
+    assert!(
+        !current.is_empty(),
+        "缺少格式版本 {CURRENT_FORMAT_VERSION} 的兼容性夹具，请运行 FEROX_ALLOW_DETERMINISTIC=1 cargo run --example generate_fixtures"
+    );
+    for keyfile in [false, true] {
+        assert!(current.iter().any(|entry| entry.keyfile == keyfile));
+    }
+    Ok(())
+}
@@ -3,7 +3,13 @@
 //! Edge case and boundary tests for Ferox Encryptor
 
 use anyhow::Result;
-use ferox_encryptor::{run_decryption_flow, run_encryption_flow, Level};
+use ferox_encryptor::cleanup::{remove_stale_partials, scan_stale_partials};
+use ferox_encryptor::constants::NO_PROGRESS_ENV_VAR;
+use ferox_encryptor::progress::{progress_output_is_terminal, terminal_progress};
+use ferox_encryptor::{
+    cleanup_orphaned_sidecars, find_orphaned_sidecars, run_decryption_flow, run_encryption_flow,
+    BatchConfig, Level,
+};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
@@ -33,7 +39,7 @@ fn test_maximum_filename_length() -> Result<()> {
     // This should fail gracefully if the OS filesystem has a shorter limit.
     // If it succeeds, that's also fine, as some filesystems might support it.
     if let Err(e) = result {
-        let error_msg = e.to_string();
+        let error_msg = format!("{e:#}");
         // We expect an error related to file creation or the OS limit
         assert!(
             error_msg.contains("无法创建目标文件")
@@ -339,7 +345,7 @@ fn test_overwrite_protection() -> Result<()> {
         Arc::clone(&temp_file_path),
     );
     assert!(result.is_err(), "Should fail without --force");
-    let error_msg = result.unwrap_err().to_string();
+    let error_msg = format!("{:#}", result.unwrap_err());
     assert!(
         error_msg.contains("already exists")
             || error_msg.contains("已存在")
@@ -376,7 +382,7 @@ fn test_non_feroxcrypt_file_decryption() -> Result<()> {
         "Should not allow decrypting non-encrypted files"
     );
 
-    let error_msg = result.unwrap_err().to_string();
+    let error_msg = format!("{:#}", result.unwrap_err());
     assert!(
         error_msg.contains("does not appear to be encrypted")
             || error_msg.contains("不是加密文件")
@@ -432,7 +438,7 @@ fn test_decryption_with_wrong_password() -> Result<()> {
         result.is_err(),
         "Decryption should fail with the wrong password"
     );
-    let error_msg = result.unwrap_err().to_string();
+    let error_msg = format!("{:#}", result.unwrap_err());
     assert!(
         error_msg.contains("Authentication failed")
             || error_msg.contains("认证失败")
@@ -483,7 +489,7 @@ fn test_decryption_with_tampered_hmac() -> Result<()> {
         result.is_err(),
         "Decryption should fail with a tampered HMAC"
     );
-    let error_msg = result.unwrap_err().to_string();
+    let error_msg = format!("{:#}", result.unwrap_err());
     assert!(
         error_msg.contains("Authentication failed")
             || error_msg.contains("认证失败")
@@ -535,7 +541,7 @@ fn test_decryption_with_tampered_ciphertext() -> Result<()> {
         result.is_err(),
         "Decryption should fail with tampered ciphertext"
     );
-    let error_msg = result.unwrap_err().to_string();
+    let error_msg = format!("{:#}", result.unwrap_err());
     assert!(
         error_msg.contains("Authentication failed")
             || error_msg.contains("认证失败")
@@ -565,7 +571,7 @@ fn test_encrypt_non_existent_file() -> Result<()> {
     );
 
     assert!(result.is_err());
-    let error_msg = result.unwrap_err().to_string();
+    let error_msg = format!("{:#}", result.unwrap_err());
     assert!(
         error_msg.contains("File does not exist")
             || error_msg.contains("文件不存在")
@@ -590,7 +596,7 @@ fn test_encrypt_directory_as_input() -> Result<()> {
     );
 
     assert!(result.is_err());
-    let error_msg = result.unwrap_err().to_string();
+    let error_msg = format!("{:#}", result.unwrap_err());
     assert!(
         error_msg.contains("Path is not a file")
             || error_msg.contains("不是文件")
@@ -604,3 +610,157 @@ fn test_encrypt_directory_as_input() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_stale_partial_detection_and_removal() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+
+    // 未完成的加密输出
+    let encrypt_partial = dir.join("report.pdf.feroxcrypt.part");
+    fs::write(&encrypt_partial, vec![0u8; 100])?;
+    // 未完成的解密输出（同目录存在对应的加密文件）
+    fs::write(dir.join("notes.txt.feroxcrypt"), b"encrypted")?;
+    let decrypt_partial = dir.join("notes.txt.part");
+    fs::write(&decrypt_partial, b"partial plaintext")?;
+    // 与本工具无关的 .part 文件不能被当作残留文件
+    let unrelated = dir.join("download.iso.part");
+    fs::write(&unrelated, b"browser download")?;
+    // 正被另一个实例锁定的文件必须被跳过
+    let locked_partial = dir.join("busy.bin.feroxcrypt.part");
+    let lock_holder = fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&locked_partial)?;
+    lock_holder.lock()?;
+    // 子目录中的残留文件只有递归扫描才会发现
+    let subdir = dir.join("sub");
+    fs::create_dir(&subdir)?;
+    let nested_partial = subdir.join("deep.txt.feroxcrypt.part");
+    fs::write(&nested_partial, b"nested")?;
+
+    let mut found: Vec<PathBuf> = scan_stale_partials(dir, false)?
+        .into_iter()
+        .map(|partial| partial.path)
+        .collect();
+    found.sort();
+    let mut expected = vec![encrypt_partial.clone(), decrypt_partial.clone()];
+    expected.sort();
+    assert_eq!(found, expected);
+
+    let partials = scan_stale_partials(dir, true)?;
+    assert_eq!(partials.len(), 3);
+    let report = partials
+        .iter()
+        .find(|partial| partial.path == encrypt_partial)
+        .unwrap();
+    assert_eq!(report.size, 100);
+    assert!(report.age.is_some());
+
+    let removed = remove_stale_partials(&partials)?;
+    assert_eq!(removed.len(), 3);
+    assert!(!encrypt_partial.exists());
+    assert!(!decrypt_partial.exists());
+    assert!(!nested_partial.exists());
+    assert!(unrelated.exists());
+    assert!(locked_partial.exists());
+
+    drop(lock_holder);
+    assert_eq!(scan_stale_partials(dir, false)?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_orphaned_sidecars_are_found_and_removed() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let dir = temp_dir.path();
+
+    // 加密文件仍然存在的旁路文件不是孤立文件
+    fs::write(dir.join("kept.txt.feroxcrypt"), b"encrypted")?;
+    let kept = dir.join("kept.txt.feroxcrypt.meta.json");
+    fs::write(&kept, b"{}")?;
+    // 加密文件已被删除
+    let orphan = dir.join("gone.txt.feroxcrypt.meta.json");
+    fs::write(&orphan, b"{}")?;
+    // 其他 JSON 文件与旁路文件无关
+    let unrelated = dir.join("settings.meta.json");
+    fs::write(&unrelated, b"{}")?;
+    // 子目录中的孤立文件只有递归扫描才会发现
+    fs::create_dir(dir.join("sub"))?;
+    let nested = dir.join("sub").join("deep.bin.feroxcrypt.meta.json");
+    fs::write(&nested, b"{}")?;
+
+    let config = BatchConfig::default();
+    assert_eq!(find_orphaned_sidecars(dir, &config)?, vec![orphan.clone()]);
+
+    let recursive = BatchConfig {
+        recursive: true,
+        ..Default::default()
+    };
+    let found = cleanup_orphaned_sidecars(dir, &recursive, true)?;
+    assert_eq!(found, vec![orphan.clone(), nested.clone()]);
+    // 试运行不删除任何文件
+    assert!(orphan.exists() && nested.exists());
+
+    assert_eq!(cleanup_orphaned_sidecars(dir, &recursive, false)?, found);
+    assert!(!orphan.exists());
+    assert!(!nested.exists());
+    assert!(kept.exists());
+    assert!(unrelated.exists());
+    assert!(find_orphaned_sidecars(dir, &recursive)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_failed_decryption_leaves_no_partial_output() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let original = temp_dir.path().join("secret.txt");
+    fs::write(&original, b"do not leak")?;
+
+    let temp_file_path = Arc::new(Mutex::new(None::<PathBuf>));
+    run_encryption_flow(
+        &original,
+        false,
+        "right",
+        Level::Interactive,
+        None,
+        Arc::clone(&temp_file_path),
+    )?;
+    fs::remove_file(&original)?;
+
+    let encrypted = temp_dir.path().join("secret.txt.feroxcrypt");
+    assert!(run_decryption_flow(&encrypted, "wrong", None, Arc::clone(&temp_file_path)).is_err());
+    assert!(!original.exists());
+    assert!(!temp_dir.path().join("secret.txt.part").exists());
+    assert!(temp_file_path.lock().unwrap().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_no_progress_sink_without_terminal() -> Result<()> {
+    // 环境变量覆盖让结果与测试是否在终端中运行无关
+    std::env::set_var(NO_PROGRESS_ENV_VAR, "1");
+    assert!(!progress_output_is_terminal());
+    assert!(terminal_progress().is_none());
+
+    // 没有进度接收器时，单文件流程照常完成
+    let temp_dir = TempDir::new()?;
+    let file = temp_dir.path().join("quiet.txt");
+    fs::write(&file, b"no progress bar here")?;
+    let temp_file_path = Arc::new(Mutex::new(None::<PathBuf>));
+    let encrypted = temp_dir.path().join("quiet.txt.feroxcrypt");
+    run_encryption_flow(
+        &file,
+        false,
+        "quiet",
+        Level::Interactive,
+        None,
+        Arc::clone(&temp_file_path),
+    )?;
+    fs::remove_file(&file)?;
+    run_decryption_flow(&encrypted, "quiet", None, temp_file_path)?;
+    assert_eq!(fs::read(&file)?, b"no progress bar here");
+
+    Ok(())
+}
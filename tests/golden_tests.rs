@@ -0,0 +1,309 @@
+// tests/golden_tests.rs
+
+//! 黄金文件测试：`tests/fixtures/golden` 中保存了各格式版本逐字节固定的 `.feroxcrypt` 文件。
+//!
+//! 当前格式的夹具由固定的盐和 IV ([`EncryptionOptions::test_nonces`]) 重新生成，必须与提交的字节完全相同；
+//! 所有夹具（包括遗留格式）都必须能解密出原来的明文。任何一项失败都意味着文件格式发生了不兼容的变化。
+//!
+//! `v2_interactive` 和 `v2_interactive_keyfile` 是加入安全级别扩展之前的当前格式文件，保持原来的字节，
+//! 只参与解密测试；`v2_labelled_*` 是记录了安全级别的当前格式夹具。
+//! `v2_low_entropy_keyfile` 用熵太低的密钥文件加密，现在的版本不再用这样的密钥文件加密新文件，
+//! 它也只参与解密测试。
+//! 迁移报告 (`migration_report`) 也在这些不同版本的夹具组成的目录上测试。
+//! `v2_future_ignorable` 和 `v2_future_critical` 模拟更新版本写入的文件，分别带有本版本不认识的
+//! 非关键扩展和关键扩展，用来检查带警告解密和拒绝解密。
+//!
+//! 有意修改格式时，用 `FEROX_UPDATE_GOLDEN=1 cargo test --test golden_tests` 重新生成当前格式的夹具
+//! （遗留格式和未来扩展的夹具由 `src/format.rs` 中的单元测试生成；旧版本的夹具保持不变，只参与解密测试）。
+
+use anyhow::Result;
+use ferox_encryptor::constants::{ALLOW_DETERMINISTIC_ENV_VAR, IV_LEN, SALT_LEN};
+use ferox_encryptor::keyfile::KeyFile;
+use ferox_encryptor::{
+    batch_decrypt_directory, decrypt_in_memory, encrypt_in_memory, encrypt_with_time_limit,
+    inspect_file, migration_report, BatchConfig, BatchResult, DecryptionOptions, EncryptionOptions,
+    FeroxError, Level, MigrationPlan, MigrationReason,
+};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+/// 所有夹具使用的密码。
+const PASSWORD: &str = "golden-fixture-password";
+/// 所有夹具加密的明文。
+const PLAINTEXT: &[u8] =
+    b"Ferox Encryptor golden fixture.\nThe bytes of this file must never change.\n";
+/// 写入文件头的原始文件名。
+const FILENAME: &str = "golden.txt";
+/// 固定的盐。
+const SALT: [u8; SALT_LEN] = [0x5a; SALT_LEN];
+/// 固定的 IV。
+const IV: [u8; IV_LEN] = [0xa5; IV_LEN];
+
+/// 设置后重新生成当前格式的夹具，而不是与它们比较。
+const UPDATE_ENV_VAR: &str = "FEROX_UPDATE_GOLDEN";
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/golden")
+        .join(name)
+}
+
+/// `v2_low_entropy_keyfile` 使用的密钥材料：重复的文本，熵低于加密时要求的门槛。
+fn low_entropy_keyfile() -> Result<KeyFile> {
+    KeyFile::from_bytes("golden low entropy keyfile. ".repeat(4).as_bytes())
+}
+
+/// 带密钥文件的夹具使用的密钥材料 (64 字节的固定序列)。
+fn golden_keyfile() -> Result<KeyFile> {
+    let data: Vec<u8> = (0..64u8)
+        .map(|i| i.wrapping_mul(73).wrapping_add(29))
+        .collect();
+    KeyFile::from_bytes(&data)
+}
+
+/// 固定盐和 IV 的加密选项；同时设置允许使用固定盐和 IV 的环境变量。
+fn golden_options<'a>(keyfile: Option<&'a KeyFile>) -> EncryptionOptions<'a> {
+    std::env::set_var(ALLOW_DETERMINISTIC_ENV_VAR, "1");
+    EncryptionOptions {
+        keyfile,
+        level: Level::Interactive,
+        test_nonces: Some((SALT, IV)),
+        ..EncryptionOptions::new(PASSWORD)
+    }
+}
+
+/// 比较（或在设置了 `FEROX_UPDATE_GOLDEN` 时更新）一个当前格式的夹具。
+fn assert_matches_fixture(name: &str, actual: &[u8]) -> Result<()> {
+    let path = fixture_path(name);
+    if std::env::var_os(UPDATE_ENV_VAR).is_some() {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, actual)?;
+        return Ok(());
+    }
+    let expected = fs::read(&path)?;
+    assert!(
+        actual == expected.as_slice(),
+        "{name} 的字节与黄金文件不同：文件格式发生了变化"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_current_format_output_matches_golden_bytes() -> Result<()> {
+    let keyfile = golden_keyfile()?;
+    let cases = [
+        ("v2_labelled_interactive.feroxcrypt", golden_options(None)),
+        (
+            "v2_labelled_interactive_keyfile.feroxcrypt",
+            EncryptionOptions {
+                keyfile_commitment: true,
+                ..golden_options(Some(&keyfile))
+            },
+        ),
+    ];
+    for (name, opts) in cases {
+        assert_matches_fixture(name, &encrypt_in_memory(PLAINTEXT, FILENAME, &opts)?)?;
+    }
+    Ok(())
+}
+
+/// 文件加密流程与内存流程一样使用固定的盐和 IV。
+#[test]
+fn test_file_flow_honours_fixed_nonces() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source = temp_dir.path().join(FILENAME);
+    fs::write(&source, PLAINTEXT)?;
+
+    // 过期时间只是让两条流程的文件头相同，不影响比较
+    let expiry = SystemTime::now() + Duration::from_secs(3600);
+    encrypt_with_time_limit(&source, expiry, &golden_options(None))?;
+    let encrypted = fs::read(temp_dir.path().join("golden.txt.feroxcrypt"))?;
+
+    let opts = EncryptionOptions {
+        expires_at: Some(expiry),
+        ..golden_options(None)
+    };
+    assert_eq!(encrypted, encrypt_in_memory(PLAINTEXT, FILENAME, &opts)?);
+    Ok(())
+}
+
+#[test]
+fn test_golden_fixtures_decrypt() -> Result<()> {
+    let keyfile = golden_keyfile()?;
+    let low_entropy = low_entropy_keyfile()?;
+    let cases = [
+        ("v0_legacy.feroxcrypt", None),
+        ("v1_interactive.feroxcrypt", None),
+        ("v1_interactive_keyfile.feroxcrypt", Some(&keyfile)),
+        ("v2_interactive.feroxcrypt", None),
+        ("v2_interactive_keyfile.feroxcrypt", Some(&keyfile)),
+        ("v2_labelled_interactive.feroxcrypt", None),
+        ("v2_labelled_interactive_keyfile.feroxcrypt", Some(&keyfile)),
+        ("v2_low_entropy_keyfile.feroxcrypt", Some(&low_entropy)),
+    ];
+    for (name, keyfile) in cases {
+        // 没有安全级别记录的旧文件按 Argon2 参数推断出级别
+        assert_eq!(
+            inspect_file(&fixture_path(name))?.level,
+            Some(Level::Interactive),
+            "{name}"
+        );
+        let ciphertext = fs::read(fixture_path(name))?;
+        let opts = DecryptionOptions {
+            keyfile,
+            ..DecryptionOptions::new(PASSWORD)
+        };
+        let (plaintext, filename) = decrypt_in_memory(&ciphertext, &opts)?;
+        assert_eq!(plaintext, PLAINTEXT, "{name}");
+        assert_eq!(filename, FILENAME, "{name}");
+    }
+    Ok(())
+}
+
+/// 迁移报告验证遗留格式的夹具，并按格式、级别和密钥文件给出重新加密的建议。
+#[test]
+fn test_migration_report_over_mixed_fixtures() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    for name in [
+        "v0_legacy.feroxcrypt",
+        "v1_interactive.feroxcrypt",
+        "v2_interactive_keyfile.feroxcrypt",
+    ] {
+        fs::copy(fixture_path(name), temp_dir.path().join(name))?;
+    }
+    // 认证标签被破坏的遗留格式文件无法通过验证
+    let mut broken = fs::read(fixture_path("v0_legacy.feroxcrypt"))?;
+    *broken.last_mut().unwrap() ^= 0x01;
+    fs::write(temp_dir.path().join("broken_legacy.feroxcrypt"), broken)?;
+    // 已经符合目标的当前格式文件
+    let keyfile = golden_keyfile()?;
+    let current = encrypt_in_memory(
+        PLAINTEXT,
+        FILENAME,
+        &EncryptionOptions {
+            level: Level::Moderate,
+            keyfile_commitment: true,
+            ..golden_options(Some(&keyfile))
+        },
+    )?;
+    fs::write(temp_dir.path().join("moderate_keyfile.feroxcrypt"), current)?;
+
+    let report = migration_report(
+        temp_dir.path(),
+        true,
+        PASSWORD,
+        Some(&keyfile),
+        Level::Moderate,
+    )?;
+    let entry = |name: &str| {
+        report
+            .entries
+            .iter()
+            .find(|entry| entry.path.ends_with(name))
+            .unwrap_or_else(|| panic!("报告中缺少 {name}"))
+    };
+
+    let legacy = entry("v0_legacy.feroxcrypt");
+    assert_eq!(legacy.verified, Some(true));
+    assert_eq!(legacy.uses_keyfile, Some(false));
+    assert_eq!(
+        legacy.reasons,
+        [
+            MigrationReason::LegacyFormat,
+            MigrationReason::WeakLevel,
+            MigrationReason::NoKeyfile
+        ]
+    );
+    assert!(legacy.estimated_secs > 0.0);
+    assert_eq!(
+        entry("v1_interactive.feroxcrypt").reasons,
+        [MigrationReason::OutdatedFormat, MigrationReason::WeakLevel]
+    );
+    // 没有密钥文件承诺时无法确定是否使用了密钥文件
+    assert_eq!(entry("v1_interactive.feroxcrypt").uses_keyfile, None);
+    assert_eq!(
+        entry("v2_interactive_keyfile.feroxcrypt").reasons,
+        [MigrationReason::WeakLevel]
+    );
+    assert!(entry("moderate_keyfile.feroxcrypt").reasons.is_empty());
+
+    let broken = entry("broken_legacy.feroxcrypt");
+    assert_eq!(broken.verified, Some(false));
+    assert!(broken.error.is_some());
+    assert_eq!(report.failures().count(), 1);
+
+    // 计划只包含可以迁移的文件，保存后可以原样读回
+    let plan_path = temp_dir.path().join("migration-plan.json");
+    report.plan().save(&plan_path)?;
+    let plan = MigrationPlan::load(&plan_path)?;
+    assert_eq!(plan.target_level()?, Level::Moderate);
+    assert!(plan.keyfile);
+    let planned: Vec<_> = plan
+        .files
+        .iter()
+        .map(|item| {
+            item.path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    assert_eq!(
+        planned,
+        [
+            "v0_legacy.feroxcrypt",
+            "v1_interactive.feroxcrypt",
+            "v2_interactive_keyfile.feroxcrypt"
+        ]
+    );
+    assert!(report.estimated_duration() > Duration::ZERO);
+    Ok(())
+}
+
+/// 更新版本写入的可忽略扩展不影响解密，只在结果中留下警告；不认识的关键扩展使解密被干净地拒绝。
+#[test]
+fn test_future_extensions_are_ignored_or_refused() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let decrypt_fixture = |name: &str, strict: bool| -> Result<(PathBuf, BatchResult)> {
+        let dir = temp_dir.path().join(format!("{name}.{strict}"));
+        fs::create_dir(&dir)?;
+        let encrypted = dir.join(name);
+        fs::copy(fixture_path(name), &encrypted)?;
+        let config = BatchConfig {
+            strict,
+            ..Default::default()
+        };
+        let result = batch_decrypt_directory(&dir, PASSWORD, None, &config)?;
+        Ok((encrypted, result))
+    };
+
+    let (encrypted, result) = decrypt_fixture("v2_future_ignorable.feroxcrypt", false)?;
+    assert_eq!((result.success_count, result.failure_count), (1, 0));
+    assert_eq!(
+        result.ignored_extensions,
+        [(encrypted.clone(), vec![0x0f01])]
+    );
+    assert_eq!(fs::read(encrypted.with_file_name(FILENAME))?, PLAINTEXT);
+
+    // 严格模式下可忽略的扩展同样导致失败
+    let (_, result) = decrypt_fixture("v2_future_ignorable.feroxcrypt", true)?;
+    assert!(matches!(
+        result.first_failure(),
+        Some((_, FeroxError::StrictViolation(_)))
+    ));
+
+    let (encrypted, result) = decrypt_fixture("v2_future_critical.feroxcrypt", false)?;
+    assert_eq!((result.success_count, result.failure_count), (0, 1));
+    assert!(result.ignored_extensions.is_empty());
+    match result.first_failure() {
+        Some((_, FeroxError::UnsupportedFeature(message))) => {
+            assert!(message.contains("0x8f01"), "{message}")
+        }
+        other => panic!("关键扩展应被拒绝: {other:?}"),
+    }
+    assert!(!encrypted.with_file_name(FILENAME).exists());
+    Ok(())
+}
@@ -1,12 +1,32 @@
 // tests/integration_tests.rs
 
 use anyhow::Result;
-use ferox_encryptor::{run_decryption_flow, run_encryption_flow, Level};
+use ferox_encryptor::batch::batch_decrypt_files_with_config;
+use ferox_encryptor::constants::{
+    fixed_overhead_bytes, header_size_for_filename, min_header_size, BUFFER_LEN,
+    SECURITY_LEVEL_LEN, TAG_LEN,
+};
+use ferox_encryptor::{
+    batch_compare_files, batch_encrypt_files, compare_file, compute_hmac_for_file,
+    compute_streaming_hmac, decrypt_base64_to_file, decrypt_first_n_chunks, decrypt_in_memory,
+    decrypt_to_vec, decrypt_to_writer_vec, detect_hardlinks, encrypt_deterministic,
+    encrypt_directory_with_index, encrypt_file_to_base64, encrypt_in_memory,
+    encrypt_resumable_with_progress, encrypt_to_vec, encrypt_with_recipient_count_hint,
+    run_decryption_flow, run_decryption_flow_no_overwrite_check, run_encryption_flow,
+    run_encryption_flow_with_hardlink_detection, search_index, BatchConfig, DecryptionOptions,
+    EncryptionOptions, FeroxError, Level, NameNormalization, OutputPermissions, ProgressEvent,
+    ProgressSink,
+};
 use std::fs;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
 
+/// 修改 `FEROX_ALLOW_DETERMINISTIC` 或依赖它的测试持有这把锁，避免并行运行时互相影响。
+static DETERMINISTIC_ENV_LOCK: Mutex<()> = Mutex::new(());
+
 /// Helper function to create a test file with content
 fn create_test_file(dir: &TempDir, filename: &str, content: &[u8]) -> Result<PathBuf> {
     let file_path = dir.path().join(filename);
@@ -171,3 +191,1135 @@ fn test_unicode_filename() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_in_memory_roundtrip() -> Result<()> {
+    let plaintext = b"in-memory secret token";
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new("memory_password")
+    };
+
+    let encrypted = encrypt_in_memory(plaintext, "token.txt", &opts)?;
+    assert_ne!(&encrypted[..], &plaintext[..]);
+
+    let (decrypted, filename) =
+        decrypt_in_memory(&encrypted, &DecryptionOptions::new("memory_password"))?;
+    assert_eq!(decrypted, plaintext);
+    assert_eq!(filename, "token.txt");
+
+    // 错误的密码必须认证失败
+    assert!(decrypt_in_memory(&encrypted, &DecryptionOptions::new("wrong")).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_to_vec_convenience_roundtrip() -> Result<()> {
+    let encrypted = encrypt_to_vec(b"api-token-123", "token.txt", "vec_password", None)?;
+
+    let (decrypted, filename) = decrypt_to_vec(&encrypted, "vec_password", None)?;
+    assert_eq!(decrypted, b"api-token-123");
+    assert_eq!(filename, "token.txt");
+    assert_eq!(
+        decrypt_to_writer_vec(&encrypted, "vec_password", None)?,
+        b"api-token-123"
+    );
+
+    assert!(decrypt_to_vec(&encrypted, "wrong", None).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_compare_file_against_plaintext() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let content: Vec<u8> = (0..BUFFER_LEN * 2 + 123).map(|i| (i % 251) as u8).collect();
+    let plaintext = create_test_file(&temp_dir, "report.docx", &content)?;
+    let encrypted = temp_dir.path().join("report.docx.feroxcrypt");
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new("compare_password")
+    };
+    fs::write(
+        &encrypted,
+        encrypt_in_memory(&content, "report.docx", &opts)?,
+    )?;
+
+    // 完全一致
+    let report = compare_file(&plaintext, &encrypted, "compare_password", None)?;
+    assert!(report.is_match());
+    assert_eq!(report.decrypted_len, content.len() as u64);
+
+    // 自动按原始文件名配对
+    let result = batch_compare_files(
+        std::slice::from_ref(&encrypted),
+        "compare_password",
+        None,
+        &BatchConfig::default(),
+    )?;
+    assert_eq!(result.success_count, 1);
+
+    // 一个字节不同
+    let mut modified = content.clone();
+    modified[BUFFER_LEN + 7] ^= 0x01;
+    fs::write(&plaintext, &modified)?;
+    let report = compare_file(&plaintext, &encrypted, "compare_password", None)?;
+    assert_eq!(report.first_difference, Some(BUFFER_LEN as u64 + 7));
+    let result = batch_compare_files(
+        std::slice::from_ref(&encrypted),
+        "compare_password",
+        None,
+        &BatchConfig::default(),
+    )?;
+    assert_eq!(result.failure_count, 1);
+
+    // 长度不同：明文多出一个字节
+    let mut longer = content.clone();
+    longer.push(0);
+    fs::write(&plaintext, &longer)?;
+    let report = compare_file(&plaintext, &encrypted, "compare_password", None)?;
+    assert_eq!(report.first_difference, Some(content.len() as u64));
+    assert_eq!(report.plaintext_len, content.len() as u64 + 1);
+
+    // 长度不同：明文少一个字节
+    fs::write(&plaintext, &content[..content.len() - 1])?;
+    let report = compare_file(&plaintext, &encrypted, "compare_password", None)?;
+    assert_eq!(report.first_difference, Some(content.len() as u64 - 1));
+
+    // 错误的密码不能得出比较结果
+    assert!(compare_file(&plaintext, &encrypted, "wrong", None).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_base64_roundtrip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let test_content = b"embedded in a Kubernetes ConfigMap";
+    let original_file = create_test_file(&temp_dir, "config.yaml", test_content)?;
+    let password = "base64_test";
+
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new(password)
+    };
+    let encoded = encrypt_file_to_base64(&original_file, &opts)?;
+
+    // URL 安全字符集且无填充
+    assert!(encoded
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    // 不应在磁盘上生成 .feroxcrypt 文件
+    assert!(!temp_dir.path().join("config.yaml.feroxcrypt").exists());
+
+    // 目标已存在时必须拒绝覆盖
+    let decrypt_opts = DecryptionOptions::new(password);
+    assert!(decrypt_base64_to_file(&encoded, temp_dir.path(), &decrypt_opts).is_err());
+
+    let output_dir = temp_dir.path().join("restored");
+    fs::create_dir(&output_dir)?;
+    let restored = decrypt_base64_to_file(&encoded, &output_dir, &decrypt_opts)?;
+    assert_eq!(restored, output_dir.join("config.yaml"));
+    assert_eq!(fs::read(&restored)?, test_content);
+
+    Ok(())
+}
+
+#[test]
+fn test_encrypted_size_matches_header_computations() -> Result<()> {
+    let plaintext = b"size accounting";
+    let filename = "size.txt";
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new("size_password")
+    };
+
+    let encrypted = encrypt_in_memory(plaintext, filename, &opts)?;
+    // 文件头扩展区总是包含一条安全级别记录
+    let level_extension = 4 + SECURITY_LEVEL_LEN;
+    assert_eq!(
+        encrypted.len(),
+        header_size_for_filename(filename.len()) + level_extension + plaintext.len() + TAG_LEN
+    );
+    assert_eq!(
+        encrypted.len(),
+        fixed_overhead_bytes() + level_extension + filename.len() + plaintext.len()
+    );
+    assert_eq!(header_size_for_filename(0), min_header_size());
+
+    Ok(())
+}
+
+#[test]
+fn test_decryption_to_explicit_path_skips_overwrite_check() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let original_file = create_test_file(&temp_dir, "report.txt", b"explicit target content")?;
+    let temp_file_path = Arc::new(Mutex::new(None::<PathBuf>));
+    let password = "explicit_target_password";
+
+    run_encryption_flow(
+        &original_file,
+        false,
+        password,
+        Level::Interactive,
+        None,
+        Arc::clone(&temp_file_path),
+    )?;
+    let encrypted_file = temp_dir.path().join("report.txt.feroxcrypt");
+
+    // 默认流程拒绝覆盖已存在的原始文件
+    assert!(
+        run_decryption_flow(&encrypted_file, password, None, Arc::clone(&temp_file_path)).is_err()
+    );
+
+    // 指定的输出路径可以使用任意文件名，已存在的文件会被替换
+    let target = temp_dir.path().join("restored.bin");
+    fs::write(&target, b"preallocated")?;
+    run_decryption_flow_no_overwrite_check(
+        &encrypted_file,
+        password,
+        None,
+        Arc::clone(&temp_file_path),
+        &target,
+    )?;
+    assert_eq!(fs::read(&target)?, b"explicit target content");
+
+    // 认证失败时保留原有的目标文件
+    fs::write(&target, b"untouched")?;
+    assert!(run_decryption_flow_no_overwrite_check(
+        &encrypted_file,
+        "wrong_password",
+        None,
+        temp_file_path,
+        &target,
+    )
+    .is_err());
+    assert_eq!(fs::read(&target)?, b"untouched");
+
+    Ok(())
+}
+
+#[test]
+fn test_roundtrip_with_custom_buffer_sizes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "buffer_size_password";
+    // 数据大于较小的缓冲区，且不是任何一个缓冲区大小的整数倍
+    let content: Vec<u8> = (0..1024 * 1024 + 321).map(|i| (i % 251) as u8).collect();
+
+    for buffer_size in [64 * 1024, 16 * 1024 * 1024] {
+        let original_file = create_test_file(&temp_dir, "buffered.bin", &content)?;
+        let config = BatchConfig {
+            level: Level::Interactive,
+            buffer_size,
+            ..Default::default()
+        };
+        let result = batch_encrypt_files(
+            std::slice::from_ref(&original_file),
+            password,
+            None,
+            &config,
+        )?;
+        assert!(result.was_successful());
+        fs::remove_file(&original_file)?;
+
+        let encrypted_file = temp_dir.path().join("buffered.bin.feroxcrypt");
+        let result = batch_decrypt_files_with_config(
+            std::slice::from_ref(&encrypted_file),
+            password,
+            None,
+            &config,
+        )?;
+        assert!(result.was_successful());
+        assert!(fs::read(&original_file)? == content);
+        fs::remove_file(&encrypted_file)?;
+        fs::remove_file(&original_file)?;
+    }
+
+    // 超出允许范围的缓冲区大小会被拒绝
+    let opts = EncryptionOptions {
+        buffer_size: 0,
+        ..EncryptionOptions::new(password)
+    };
+    assert!(encrypt_in_memory(&content, "buffered.bin", &opts).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_mmap_encryption_matches_buffered() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "mmap_password";
+    let content: Vec<u8> = (0..3 * 64 * 1024 + 77).map(|i| (i % 241) as u8).collect();
+
+    // 空文件无法映射，会自动回退到缓冲读取
+    for (name, content) in [("mapped.bin", content.as_slice()), ("empty.bin", &[][..])] {
+        let mut encrypted = Vec::new();
+        for mmap in [false, true] {
+            let original_file = create_test_file(&temp_dir, name, content)?;
+            let config = BatchConfig {
+                level: Level::Interactive,
+                buffer_size: 64 * 1024,
+                mmap,
+                ..Default::default()
+            };
+            let result = batch_encrypt_files(
+                std::slice::from_ref(&original_file),
+                password,
+                None,
+                &config,
+            )?;
+            assert!(result.was_successful());
+            fs::remove_file(&original_file)?;
+
+            let encrypted_file = temp_dir.path().join(format!("{name}.feroxcrypt"));
+            encrypted.push(fs::read(&encrypted_file)?);
+            let result = batch_decrypt_files_with_config(
+                std::slice::from_ref(&encrypted_file),
+                password,
+                None,
+                &config,
+            )?;
+            assert!(result.was_successful());
+            assert!(fs::read(&original_file)? == content);
+            fs::remove_file(&encrypted_file)?;
+            fs::remove_file(&original_file)?;
+        }
+        // 盐和 IV 是随机的，但两种读取方式产生的文件结构完全相同
+        assert_eq!(encrypted[0].len(), encrypted[1].len());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_directory_index_finds_encrypted_files_by_name() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "index_password";
+    create_test_file(&temp_dir, "report.pdf", b"quarterly numbers")?;
+    create_test_file(&temp_dir, "notes.txt", b"meeting notes")?;
+
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new(password)
+    };
+    let index = encrypt_directory_with_index(temp_dir.path(), &opts)?;
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.path(), temp_dir.path().join("vault.feroxidx"));
+
+    // 索引文件中不应出现明文文件名
+    let index_bytes = fs::read(index.path())?;
+    assert!(!index_bytes
+        .windows(b"notes.txt".len())
+        .any(|w| w == b"notes.txt"));
+
+    let found = search_index(index.path(), "report.pdf", password, None)?;
+    assert_eq!(found, vec![temp_dir.path().join("report.pdf.feroxcrypt")]);
+    assert!(found[0].exists());
+    assert!(search_index(index.path(), "report", password, None)?.is_empty());
+    assert!(search_index(index.path(), "report.pdf", "wrong_password", None).is_err());
+
+    // 已存在的索引不会被覆盖，也不会被当作普通文件再次加密
+    assert!(encrypt_directory_with_index(temp_dir.path(), &opts).is_err());
+    let overwrite = EncryptionOptions {
+        force_overwrite: true,
+        ..opts
+    };
+    let index = encrypt_directory_with_index(temp_dir.path(), &overwrite)?;
+    assert_eq!(index.len(), 2);
+    assert!(!temp_dir.path().join("vault.feroxidx.feroxcrypt").exists());
+
+    Ok(())
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+#[tracing_test::traced_test]
+fn test_tracing_spans_cover_encryption_and_decryption() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let original_file = create_test_file(&temp_dir, "traced.txt", b"traced content")?;
+    let temp_file_path = Arc::new(Mutex::new(None::<PathBuf>));
+
+    run_encryption_flow(
+        &original_file,
+        false,
+        "tracing_password",
+        Level::Interactive,
+        None,
+        Arc::clone(&temp_file_path),
+    )?;
+    assert!(logs_contain("run_encryption_flow{"));
+    assert!(logs_contain("level=Interactive"));
+    assert!(logs_contain(":argon2_derive{"));
+    assert!(logs_contain(":process_stream{direction=Encrypt"));
+    assert!(logs_contain("bytes=14"));
+    assert!(!logs_contain("tracing_password"));
+
+    fs::remove_file(&original_file)?;
+    let encrypted_file = temp_dir.path().join("traced.txt.feroxcrypt");
+    let result = run_decryption_flow(&encrypted_file, "wrong_password", None, temp_file_path);
+    assert!(result.is_err());
+    assert!(logs_contain("run_decryption_flow{"));
+    assert!(logs_contain(":process_stream{direction=Decrypt"));
+    // The authentication failure is recorded as an error event on the span
+    assert!(logs_contain("ERROR"));
+    Ok(())
+}
+
+#[cfg(feature = "uring")]
+#[test]
+fn test_uring_roundtrip_across_buffer_boundaries() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "uring_password";
+    let buffer_size = 64 * 1024;
+    let large: Vec<u8> = (0..5 * buffer_size + 1234)
+        .map(|i| (i % 239) as u8)
+        .collect();
+    let exact: Vec<u8> = vec![0x42; 4 * buffer_size];
+
+    // 大于一个数据块的文件使用 io_uring 读写，其余文件（以及不支持 io_uring 的内核）回退到缓冲读写
+    let cases = [
+        ("large.bin", large.as_slice()),
+        ("exact.bin", exact.as_slice()),
+        ("small.bin", &b"small"[..]),
+        ("empty.bin", &[][..]),
+    ];
+    for (name, content) in cases {
+        for (single_thread, threads, mmap) in
+            [(true, 1, false), (false, 1, false), (false, 2, true)]
+        {
+            let original_file = create_test_file(&temp_dir, name, content)?;
+            let config = BatchConfig {
+                level: Level::Interactive,
+                buffer_size,
+                single_thread,
+                threads,
+                mmap,
+                ..Default::default()
+            };
+            let result = batch_encrypt_files(
+                std::slice::from_ref(&original_file),
+                password,
+                None,
+                &config,
+            )?;
+            assert!(result.was_successful());
+            fs::remove_file(&original_file)?;
+
+            let encrypted_file = temp_dir.path().join(format!("{name}.feroxcrypt"));
+            let result = batch_decrypt_files_with_config(
+                std::slice::from_ref(&encrypted_file),
+                password,
+                None,
+                &config,
+            )?;
+            assert!(result.was_successful());
+            assert!(
+                fs::read(&original_file)? == content,
+                "{name} 的内容应保持不变"
+            );
+            fs::remove_file(&original_file)?;
+
+            // 篡改后认证失败，不会留下明文输出
+            let mut tampered = fs::read(&encrypted_file)?;
+            let index = tampered.len() - 1;
+            tampered[index] ^= 0x01;
+            fs::write(&encrypted_file, &tampered)?;
+            let result = batch_decrypt_files_with_config(
+                std::slice::from_ref(&encrypted_file),
+                password,
+                None,
+                &config,
+            )?;
+            assert!(!result.was_successful());
+            assert!(!original_file.exists());
+            fs::remove_file(&encrypted_file)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_encrypt_deterministic_is_reproducible_and_gated() -> Result<()> {
+    let _env = DETERMINISTIC_ENV_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let temp_dir = TempDir::new()?;
+    let source = create_test_file(&temp_dir, "fixture.txt", b"deterministic fixture")?;
+    let encrypted = temp_dir.path().join("fixture.txt.feroxcrypt");
+    let password = "fixture_password";
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        force_overwrite: true,
+        ..EncryptionOptions::new(password)
+    };
+
+    // 没有设置环境变量时拒绝执行
+    std::env::remove_var("FEROX_ALLOW_DETERMINISTIC");
+    let err = encrypt_deterministic(&source, [7; 32], &opts).unwrap_err();
+    assert!(err.to_string().contains("FEROX_ALLOW_DETERMINISTIC"));
+    assert!(!encrypted.exists());
+    // 选项中固定的盐和 IV 同样被拒绝，文件和内存流程都一样
+    let fixed = EncryptionOptions {
+        test_nonces: Some(([7u8; 16], [9u8; 16])),
+        ..opts
+    };
+    let err = encrypt_with_recipient_count_hint(&source, 1, &fixed).unwrap_err();
+    assert!(format!("{err:#}").contains("FEROX_ALLOW_DETERMINISTIC"));
+    assert!(!encrypted.exists());
+    let err = encrypt_in_memory(b"data", "data.txt", &fixed).unwrap_err();
+    assert!(format!("{err:#}").contains("FEROX_ALLOW_DETERMINISTIC"));
+
+    std::env::set_var("FEROX_ALLOW_DETERMINISTIC", "1");
+    encrypt_deterministic(&source, [7; 32], &opts)?;
+    let first = fs::read(&encrypted)?;
+    encrypt_deterministic(&source, [7; 32], &opts)?;
+    assert_eq!(fs::read(&encrypted)?, first);
+
+    // 不同的种子得到不同的输出
+    encrypt_deterministic(&source, [8; 32], &opts)?;
+    assert_ne!(fs::read(&encrypted)?, first);
+
+    // 输出是普通的加密文件
+    let (plaintext, filename) = decrypt_in_memory(&first, &DecryptionOptions::new(password))?;
+    assert_eq!(plaintext, b"deterministic fixture");
+    assert_eq!(filename, "fixture.txt");
+
+    Ok(())
+}
+
+#[test]
+fn test_compute_hmac_for_file_matches_reference() -> Result<()> {
+    use hmac::{Hmac, Mac};
+
+    let temp_dir = TempDir::new()?;
+    let key = [0x42u8; 32];
+    // 超过一个数据块，且不是数据块大小的整数倍
+    let data: Vec<u8> = (0..5 * 1024 * 1024 + 123)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let path = create_test_file(&temp_dir, "hmac.bin", &data)?;
+
+    let mut reference = Hmac::<sha2::Sha256>::new_from_slice(&key)?;
+    reference.update(&data);
+    let expected: [u8; 32] = reference.finalize().into_bytes().into();
+
+    assert_eq!(compute_streaming_hmac(&data[..], &key)?, expected);
+    assert_eq!(compute_hmac_for_file(&path, &key)?, expected);
+    assert_ne!(compute_hmac_for_file(&path, &[0x43u8; 32])?, expected);
+    assert!(compute_hmac_for_file(&temp_dir.path().join("missing.bin"), &key).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_pipeline_compress_encrypt_split_roundtrip() -> Result<()> {
+    use ferox_encryptor::{Compression, PipelineBuilder};
+    use std::io::Read;
+
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir(&output_dir)?;
+    // 可压缩的数据，压缩后仍然跨越多个分割部分
+    let data: Vec<u8> = (0..400_000u32)
+        .flat_map(|i| (i.wrapping_mul(2_654_435_761) >> 24).to_le_bytes())
+        .collect();
+    let source = create_test_file(&temp_dir, "archive.bin", &data)?;
+    let password = "pipeline_password";
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new(password)
+    };
+
+    let pipeline = PipelineBuilder::new()
+        .compress(Compression::Lz4)
+        .encrypt(opts)
+        .split(64 * 1024)
+        .build()?;
+    let parts = pipeline.run(&source, &output_dir)?;
+    assert!(parts.len() > 1);
+    assert_eq!(parts[0], output_dir.join("archive.bin.lz4.feroxcrypt.001"));
+
+    let mut encrypted = Vec::new();
+    for part in &parts {
+        encrypted.extend(fs::read(part)?);
+    }
+    assert!(encrypted.len() < data.len());
+    let estimate = pipeline.estimate_output_size(data.len() as u64);
+    assert!(encrypted.len() as u64 <= estimate + "archive.bin.lz4".len() as u64);
+
+    let (compressed, filename) = decrypt_in_memory(&encrypted, &DecryptionOptions::new(password))?;
+    assert_eq!(filename, "archive.bin.lz4");
+    let mut decompressed = Vec::new();
+    lz4_flex::frame::FrameDecoder::new(compressed.as_slice()).read_to_end(&mut decompressed)?;
+    assert_eq!(decompressed, data);
+
+    // 输出已存在时拒绝覆盖，且不留下新的部分
+    assert!(pipeline.run(&source, &output_dir).is_err());
+    assert_eq!(fs::read_dir(&output_dir)?.count(), parts.len());
+
+    // 不压缩也不分割时，估算值加上文件名长度恰好等于实际大小
+    let plain = PipelineBuilder::new().encrypt(opts).build()?;
+    let fresh_dir = temp_dir.path().join("plain");
+    fs::create_dir(&fresh_dir)?;
+    let outputs = plain.run(&source, &fresh_dir)?;
+    assert_eq!(outputs, vec![fresh_dir.join("archive.bin.feroxcrypt")]);
+    assert_eq!(
+        fs::metadata(&outputs[0])?.len(),
+        plain.estimate_output_size(data.len() as u64) + "archive.bin".len() as u64
+    );
+
+    // 步骤的组合和顺序
+    assert!(PipelineBuilder::new()
+        .compress(Compression::Lz4)
+        .build()
+        .is_err());
+    assert!(PipelineBuilder::new()
+        .encrypt(opts)
+        .encrypt(opts)
+        .build()
+        .is_err());
+    assert!(PipelineBuilder::new()
+        .encrypt(opts)
+        .compress(Compression::Lz4)
+        .build()
+        .is_err());
+    assert!(PipelineBuilder::new()
+        .split(1024)
+        .encrypt(opts)
+        .build()
+        .is_err());
+    assert!(PipelineBuilder::new()
+        .encrypt(opts)
+        .split(0)
+        .build()
+        .is_err());
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_preserve_xattrs_roundtrip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source = create_test_file(&temp_dir, "tagged.txt", b"file with attributes")?;
+    if xattr::set(&source, "user.ferox.tag", b"blue").is_err() {
+        eprintln!("临时目录所在的文件系统不支持 user.* 扩展属性，跳过测试");
+        return Ok(());
+    }
+    let password = "xattr_password";
+    let config = BatchConfig {
+        level: Level::Interactive,
+        preserve_xattrs: true,
+        ..Default::default()
+    };
+
+    let result = batch_encrypt_files(std::slice::from_ref(&source), password, None, &config)?;
+    assert_eq!(result.success_count, 1);
+    fs::remove_file(&source)?;
+    let encrypted = temp_dir.path().join("tagged.txt.feroxcrypt");
+
+    // 不保留扩展属性时照常解密，但不恢复属性
+    let plain_config = BatchConfig {
+        preserve_xattrs: false,
+        ..config.clone()
+    };
+    batch_decrypt_files_with_config(
+        std::slice::from_ref(&encrypted),
+        password,
+        None,
+        &plain_config,
+    )?;
+    assert_eq!(fs::read(&source)?, b"file with attributes");
+    assert_eq!(xattr::get(&source, "user.ferox.tag")?, None);
+    fs::remove_file(&source)?;
+
+    let result = batch_decrypt_files_with_config(&[encrypted], password, None, &config)?;
+    assert_eq!(result.success_count, 1);
+    assert_eq!(fs::read(&source)?, b"file with attributes");
+    assert_eq!(
+        xattr::get(&source, "user.ferox.tag")?,
+        Some(b"blue".to_vec())
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_output_permissions_policies() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new()?;
+    let source = create_test_file(&temp_dir, "shared.txt", b"team document")?;
+    // 新建文件的权限反映了当前进程的 umask
+    let umask_mode = fs::metadata(&source)?.permissions().mode() & 0o777;
+    fs::set_permissions(&source, fs::Permissions::from_mode(0o640))?;
+    let password = "chmod_password";
+    let config = BatchConfig {
+        level: Level::Interactive,
+        preserve_xattrs: true,
+        track_successful_paths: true,
+        ..Default::default()
+    };
+    batch_encrypt_files(std::slice::from_ref(&source), password, None, &config)?;
+    fs::remove_file(&source)?;
+    let encrypted = temp_dir.path().join("shared.txt.feroxcrypt");
+
+    let cases = [
+        (OutputPermissions::FromHeader, 0o640),
+        (OutputPermissions::Fixed(0o600), 0o600),
+        (OutputPermissions::ProcessUmask, umask_mode),
+    ];
+    for (permissions, expected) in cases {
+        let config = BatchConfig {
+            permissions,
+            ..config.clone()
+        };
+        let result = batch_decrypt_files_with_config(
+            std::slice::from_ref(&encrypted),
+            password,
+            None,
+            &config,
+        )?;
+        assert_eq!(
+            fs::metadata(&source)?.permissions().mode() & 0o777,
+            expected,
+            "{permissions:?}"
+        );
+        assert_eq!(result.output_modes, vec![(source.clone(), expected)]);
+        fs::remove_file(&source)?;
+    }
+
+    // 没有记录权限位时 FromHeader 退回到 umask
+    let unrecorded = create_test_file(&temp_dir, "plain.txt", b"no metadata")?;
+    fs::set_permissions(&unrecorded, fs::Permissions::from_mode(0o604))?;
+    let plain_config = BatchConfig {
+        preserve_xattrs: false,
+        permissions: OutputPermissions::FromHeader,
+        ..config
+    };
+    batch_encrypt_files(
+        std::slice::from_ref(&unrecorded),
+        password,
+        None,
+        &plain_config,
+    )?;
+    fs::remove_file(&unrecorded)?;
+    let encrypted = temp_dir.path().join("plain.txt.feroxcrypt");
+    batch_decrypt_files_with_config(&[encrypted], password, None, &plain_config)?;
+    assert_eq!(
+        fs::metadata(&unrecorded)?.permissions().mode() & 0o777,
+        umask_mode
+    );
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_delete_source_refuses_hardlinked_files() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source = create_test_file(&temp_dir, "secret.txt", b"hardlinked plaintext")?;
+    let link = temp_dir.path().join("secret-link.txt");
+    let encrypted = temp_dir.path().join("secret.txt.feroxcrypt");
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new("hardlink_password")
+    };
+    assert!(!detect_hardlinks(&source)?);
+    fs::hard_link(&source, &link)?;
+    assert!(detect_hardlinks(&source)?);
+
+    // 有其他硬链接时拒绝加密，不生成任何输出，源文件保持不变
+    let error = run_encryption_flow_with_hardlink_detection(
+        &source,
+        &opts,
+        false,
+        Arc::new(Mutex::new(None)),
+    )
+    .unwrap_err();
+    assert!(format!("{error:#}").contains("2 个硬链接"));
+    assert!(!encrypted.exists());
+    assert_eq!(fs::read(&source)?, b"hardlinked plaintext");
+
+    // 强制执行时只删除这个路径，另一个硬链接仍然指向明文
+    run_encryption_flow_with_hardlink_detection(&source, &opts, true, Arc::new(Mutex::new(None)))?;
+    assert!(encrypted.exists());
+    assert!(!source.exists());
+    assert_eq!(fs::read(&link)?, b"hardlinked plaintext");
+    assert!(!detect_hardlinks(&link)?);
+
+    // 没有其他硬链接时覆写并删除
+    run_encryption_flow_with_hardlink_detection(&link, &opts, false, Arc::new(Mutex::new(None)))?;
+    assert!(!link.exists());
+    assert!(temp_dir.path().join("secret-link.txt.feroxcrypt").exists());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_delete_source_does_not_wipe_symlink_target() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let target = create_test_file(&temp_dir, "real.txt", b"linked plaintext")?;
+    let link = temp_dir.path().join("link.txt");
+    std::os::unix::fs::symlink(&target, &link)?;
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new("symlink_password")
+    };
+
+    // 加密成功，删除被拒绝只发出警告：链接和它指向的文件都保持不变
+    run_encryption_flow_with_hardlink_detection(&link, &opts, false, Arc::new(Mutex::new(None)))?;
+    assert!(temp_dir.path().join("link.txt.feroxcrypt").exists());
+    assert!(fs::symlink_metadata(&link)?.file_type().is_symlink());
+    assert_eq!(fs::read(&target)?, b"linked plaintext");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_encryption_refuses_target_that_is_the_source() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let source = create_test_file(&temp_dir, "loop.txt", b"must survive")?;
+    let target = temp_dir.path().join("loop.txt.feroxcrypt");
+    std::os::unix::fs::symlink(&source, &target)?;
+
+    // 即使强制覆盖，指向源文件的输出路径也会在打开写入端之前被拒绝
+    let error = run_encryption_flow(
+        &source,
+        true,
+        "loop_password",
+        Level::Interactive,
+        None,
+        Arc::new(Mutex::new(None)),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<FeroxError>(),
+        Some(FeroxError::SourceEqualsTarget { .. })
+    ));
+    assert_eq!(fs::read(&source)?, b"must survive");
+    assert!(fs::symlink_metadata(&target)?.file_type().is_symlink());
+
+    // 批量加密把它归为同一类失败
+    let config = BatchConfig {
+        level: Level::Interactive,
+        force_overwrite: true,
+        ..Default::default()
+    };
+    let result = batch_encrypt_files(
+        std::slice::from_ref(&source),
+        "loop_password",
+        None,
+        &config,
+    )?;
+    assert!(matches!(
+        result.failures.as_slice(),
+        [(_, FeroxError::SourceEqualsTarget { .. })]
+    ));
+    assert_eq!(fs::read(&source)?, b"must survive");
+    Ok(())
+}
+
+#[test]
+fn test_decrypt_first_n_chunks_returns_unauthenticated_prefix() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let mut content = b"%PDF-1.7\n".to_vec();
+    content.extend((0..BUFFER_LEN as u32 * 2).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8));
+    let original_file = create_test_file(&temp_dir, "report.pdf", &content)?;
+    let password = "peek_password";
+    run_encryption_flow(
+        &original_file,
+        false,
+        password,
+        Level::Interactive,
+        None,
+        Arc::new(Mutex::new(None)),
+    )?;
+    let encrypted_file = temp_dir.path().join("report.pdf.feroxcrypt");
+
+    let prefix = decrypt_first_n_chunks(&encrypted_file, 1, password, None)?;
+    assert_eq!(prefix, &content[..BUFFER_LEN]);
+    assert!(prefix.starts_with(b"%PDF-"));
+
+    // 请求的长度超过文件时返回全部明文
+    let all = decrypt_first_n_chunks(&encrypted_file, 10, password, None)?;
+    assert_eq!(all, content);
+
+    // 不验证认证标签：错误的密码不会报错，只会得到错误的数据
+    let wrong = decrypt_first_n_chunks(&encrypted_file, 1, "wrong_password", None)?;
+    assert_eq!(wrong.len(), BUFFER_LEN);
+    assert!(!wrong.starts_with(b"%PDF-"));
+    Ok(())
+}
+
+#[test]
+fn test_restored_filename_normalization() -> Result<()> {
+    // macOS 上常见的分解形式 (NFD)：e + U+0301 组合重音符
+    let nfd_name = "cafe\u{301}.txt";
+    let nfc_name = "caf\u{e9}.txt";
+    let temp_dir = TempDir::new()?;
+    let source = create_test_file(&temp_dir, nfd_name, b"menu")?;
+    let password = "unicode_password";
+    run_encryption_flow(
+        &source,
+        false,
+        password,
+        Level::Interactive,
+        None,
+        Arc::new(Mutex::new(None)),
+    )?;
+    fs::remove_file(&source)?;
+    let encrypted = temp_dir.path().join(format!("{nfd_name}.feroxcrypt"));
+    let names = || -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(temp_dir.path())? {
+            let name = entry?.file_name().into_string().unwrap();
+            if !name.ends_with(".feroxcrypt") {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    };
+
+    // 默认保持文件头中记录的原始字节
+    let config = BatchConfig::default();
+    batch_decrypt_files_with_config(std::slice::from_ref(&encrypted), password, None, &config)?;
+    assert_eq!(names()?, [nfd_name]);
+    fs::remove_file(temp_dir.path().join(nfd_name))?;
+
+    // 规范化为组合形式 (NFC)
+    let config = BatchConfig {
+        normalize_names: NameNormalization::Nfc,
+        ..Default::default()
+    };
+    batch_decrypt_files_with_config(std::slice::from_ref(&encrypted), password, None, &config)?;
+    assert_eq!(names()?, [nfc_name]);
+    assert_eq!(fs::read(temp_dir.path().join(nfc_name))?, b"menu");
+
+    assert_eq!(NameNormalization::Nfd.apply(nfc_name), nfd_name);
+    assert_eq!(NameNormalization::None.apply(nfc_name), nfc_name);
+    Ok(())
+}
+
+#[test]
+fn test_temp_dir_staging_when_target_directory_cannot_stage() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let scratch = TempDir::new()?;
+    let password = "temp_dir_password";
+    let original_file = create_test_file(&temp_dir, "staged.txt", b"staged through temp dir")?;
+
+    // 目标旁边的 `.part` 路径被目录占用，无法在目标目录中暂存
+    fs::create_dir(temp_dir.path().join("staged.txt.feroxcrypt.part"))?;
+    fs::create_dir(temp_dir.path().join("staged.txt.part"))?;
+
+    let without_temp_dir = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    let result = batch_encrypt_files(
+        std::slice::from_ref(&original_file),
+        password,
+        None,
+        &without_temp_dir,
+    )?;
+    assert_eq!(result.failure_count, 1);
+
+    let config = BatchConfig {
+        temp_dir: Some(scratch.path().to_path_buf()),
+        ..without_temp_dir
+    };
+    let result = batch_encrypt_files(
+        std::slice::from_ref(&original_file),
+        password,
+        None,
+        &config,
+    )?;
+    assert!(result.was_successful());
+    fs::remove_file(&original_file)?;
+
+    let encrypted_file = temp_dir.path().join("staged.txt.feroxcrypt");
+    let result = batch_decrypt_files_with_config(
+        std::slice::from_ref(&encrypted_file),
+        password,
+        None,
+        &config,
+    )?;
+    assert!(result.was_successful());
+    assert_eq!(fs::read(&original_file)?, b"staged through temp dir");
+    // 暂存文件提交后不会留在临时目录中
+    assert_eq!(fs::read_dir(scratch.path())?.count(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_recipient_count_hint() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "recipient_password";
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        force_overwrite: true,
+        ..EncryptionOptions::new(password)
+    };
+    let original_file = create_test_file(&temp_dir, "shared.txt", b"one recipient for now")?;
+    let encrypted_file = temp_dir.path().join("shared.txt.feroxcrypt");
+    let decrypt_opts = DecryptionOptions::new(password);
+
+    // 单个接收者与普通加密相同
+    encrypt_with_recipient_count_hint(&original_file, 1, &opts)?;
+    let (plaintext, _) = decrypt_in_memory(&fs::read(&encrypted_file)?, &decrypt_opts)?;
+    assert_eq!(plaintext, b"one recipient for now");
+
+    // 多个接收者的文件需要支持多接收者的版本才能解密
+    encrypt_with_recipient_count_hint(&original_file, 3, &opts)?;
+    let err = decrypt_in_memory(&fs::read(&encrypted_file)?, &decrypt_opts).unwrap_err();
+    let error = err.downcast_ref::<FeroxError>();
+    assert!(matches!(
+        error,
+        Some(FeroxError::MultiRecipientNotSupported { count: 3 })
+    ));
+    assert!(error.is_some_and(FeroxError::requires_upgrade));
+
+    assert!(encrypt_with_recipient_count_hint(&original_file, 0, &opts).is_err());
+    Ok(())
+}
+
+/// 处理的字节数达到 `limit` 时 panic，模拟加密中途被中断（例如 USB 磁盘断开）。
+struct InterruptAfter {
+    limit: u64,
+    seen: AtomicU64,
+}
+
+impl ProgressSink for InterruptAfter {
+    fn on_event(&self, event: &ProgressEvent<'_>) {
+        if let ProgressEvent::BytesProcessed { bytes } = event {
+            if self.seen.fetch_add(*bytes, Ordering::SeqCst) + bytes >= self.limit {
+                panic!("simulated interruption");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resumable_encryption_honours_rate_limit() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let plaintext: Vec<u8> = (0..512 * 1024u32).map(|i| (i % 241) as u8).collect();
+    let source = create_test_file(&temp_dir, "limited.bin", &plaintext)?;
+    let password = "rate_limit_password";
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        buffer_size: 64 * 1024,
+        ..EncryptionOptions::new(password)
+    };
+
+    let refused = EncryptionOptions {
+        rate_limit: Some(0),
+        ..opts
+    };
+    assert!(encrypt_resumable_with_progress(&source, &refused, 256 * 1024, None).is_err());
+
+    // 512 KiB 在 1 MiB/s 的限制下至少需要半秒（不含密钥派生）
+    let limited = EncryptionOptions {
+        rate_limit: Some(1024 * 1024),
+        ..opts
+    };
+    let started = std::time::Instant::now();
+    encrypt_resumable_with_progress(&source, &limited, 256 * 1024, None)?;
+    assert!(started.elapsed() >= std::time::Duration::from_millis(500));
+
+    let encrypted = fs::read(temp_dir.path().join("limited.bin.feroxcrypt"))?;
+    let (decrypted, _) = decrypt_in_memory(&encrypted, &DecryptionOptions::new(password))?;
+    assert_eq!(decrypted, plaintext);
+    assert!(!temp_dir
+        .path()
+        .join("limited.bin.feroxcrypt.feroxstate")
+        .exists());
+    Ok(())
+}
+
+#[test]
+fn test_resumable_encryption_continues_after_interruption() -> Result<()> {
+    let _env = DETERMINISTIC_ENV_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    std::env::set_var("FEROX_ALLOW_DETERMINISTIC", "1");
+    let temp_dir = TempDir::new()?;
+    let plaintext: Vec<u8> = (0..1_100_000u32).map(|i| (i % 251) as u8).collect();
+    let source = create_test_file(&temp_dir, "big.bin", &plaintext)?;
+    let target = temp_dir.path().join("big.bin.feroxcrypt");
+    let part = temp_dir.path().join("big.bin.feroxcrypt.part");
+    let state = temp_dir.path().join("big.bin.feroxcrypt.feroxstate");
+    let password = "resume_password";
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        buffer_size: 64 * 1024,
+        test_nonces: Some(([7u8; 16], [9u8; 16])),
+        ..EncryptionOptions::new(password)
+    };
+    let checkpoint_interval = 256 * 1024;
+
+    // 处理到 700 000 字节时中断：最后一个检查点在 512 KiB 处
+    let interrupt = InterruptAfter {
+        limit: 700_000,
+        seen: AtomicU64::new(0),
+    };
+    let interrupted = panic::catch_unwind(AssertUnwindSafe(|| {
+        encrypt_resumable_with_progress(&source, &opts, checkpoint_interval, Some(&interrupt))
+    }));
+    assert!(interrupted.is_err());
+    assert!(!target.exists());
+    assert!(part.exists() && state.exists());
+
+    // 密码错误时拒绝续传，不修改 `.part` 文件
+    let partial = fs::read(&part)?;
+    let wrong = EncryptionOptions {
+        password: "wrong_password",
+        ..opts
+    };
+    let error = encrypt_resumable_with_progress(&source, &wrong, checkpoint_interval, None)
+        .expect_err("错误的密码不能续传");
+    assert!(matches!(
+        error.downcast_ref::<FeroxError>(),
+        Some(FeroxError::AuthenticationFailed)
+    ));
+    assert_eq!(fs::read(&part)?, partial);
+
+    // 检查点不泄露文件 HMAC：把 `.part` 截断到检查点位置并附上检查点中的认证值，
+    // 得到的文件不能通过认证
+    let checkpoint: serde_json::Value = serde_json::from_slice(&fs::read(&state)?)?;
+    let durable_len =
+        checkpoint["header_len"].as_u64().unwrap() + checkpoint["bytes_done"].as_u64().unwrap();
+    let auth = checkpoint["auth"].as_str().unwrap();
+    let mut forged = partial[..durable_len as usize].to_vec();
+    forged.extend(
+        (0..auth.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&auth[i..i + 2], 16).unwrap()),
+    );
+    let forged_dir = temp_dir.path().join("forged");
+    fs::create_dir(&forged_dir)?;
+    let forged_path = forged_dir.join("big.bin.feroxcrypt");
+    fs::write(&forged_path, forged)?;
+    let error = run_decryption_flow(&forged_path, password, None, Arc::new(Mutex::new(None)))
+        .expect_err("截断的文件不能通过认证");
+    assert!(matches!(
+        error.downcast_ref::<FeroxError>(),
+        Some(FeroxError::AuthenticationFailed)
+    ));
+    assert!(!forged_dir.join("big.bin").exists());
+
+    // 续传从检查点开始，先报告已经完成的字节数
+    let resumed = InterruptAfter {
+        limit: u64::MAX,
+        seen: AtomicU64::new(0),
+    };
+    encrypt_resumable_with_progress(&source, &opts, checkpoint_interval, Some(&resumed))?;
+    assert_eq!(resumed.seen.load(Ordering::SeqCst), plaintext.len() as u64);
+    assert!(!part.exists() && !state.exists());
+
+    // 与不中断的加密逐字节相同，并且可以正常解密
+    let uninterrupted = encrypt_in_memory(&plaintext, "big.bin", &opts)?;
+    assert!(fs::read(&target)? == uninterrupted);
+    fs::remove_file(&source)?;
+    run_decryption_flow(&target, password, None, Arc::new(Mutex::new(None)))?;
+    assert!(fs::read(&source)? == plaintext);
+    Ok(())
+}
@@ -5,12 +5,12 @@
 //! 测试交互式模块的核心功能，确保所有组件正常工作。
 //! 由于交互式功能主要依赖用户输入，这里主要测试非交互部分。
 //!
-//! *Tests for interactive functionality. Since interactive features mainly 
+//! *Tests for interactive functionality. Since interactive features mainly
 //! depend on user input, this primarily tests non-interactive components.*
 
 use anyhow::Result;
 use ferox_encryptor::{
-    batch::{batch_encrypt_files, batch_decrypt_files, BatchConfig},
+    batch::{batch_decrypt_files, batch_encrypt_files, BatchConfig},
     keyfile::KeyFile,
     Level,
 };
@@ -31,7 +31,9 @@ fn test_security_levels_compatibility() -> Result<()> {
         fs::write(&test_file, format!("Test content for {:?} level", level))?;
 
         // 加密后的文件路径
-        let encrypted_file = temp_dir.path().join(format!("test_{:?}.txt.feroxcrypt", level));
+        let encrypted_file = temp_dir
+            .path()
+            .join(format!("test_{:?}.txt.feroxcrypt", level));
 
         // 测试加密
         let config = BatchConfig {
@@ -40,12 +42,8 @@ fn test_security_levels_compatibility() -> Result<()> {
             ..Default::default()
         };
 
-        let encrypt_result = batch_encrypt_files(
-            &[test_file.clone()],
-            "test_password",
-            None,
-            &config,
-        )?;
+        let encrypt_result =
+            batch_encrypt_files(&[test_file.clone()], "test_password", None, &config)?;
 
         assert_eq!(encrypt_result.success_count, 1);
         assert_eq!(encrypt_result.failure_count, 0);
@@ -55,11 +53,7 @@ fn test_security_levels_compatibility() -> Result<()> {
         fs::remove_file(&test_file)?;
 
         // 测试解密
-        let decrypt_result = batch_decrypt_files(
-            &[encrypted_file],
-            "test_password",
-            None,
-        )?;
+        let decrypt_result = batch_decrypt_files(&[encrypted_file], "test_password", None)?;
 
         assert_eq!(decrypt_result.success_count, 1);
         assert_eq!(decrypt_result.failure_count, 0);
@@ -67,7 +61,10 @@ fn test_security_levels_compatibility() -> Result<()> {
 
         // 验证内容
         let decrypted_content = fs::read_to_string(&test_file)?;
-        assert_eq!(decrypted_content, format!("Test content for {:?} level", level));
+        assert_eq!(
+            decrypted_content,
+            format!("Test content for {:?} level", level)
+        );
     }
 
     Ok(())
@@ -113,11 +110,8 @@ fn test_keyfile_compatibility() -> Result<()> {
     let encrypted_file = temp_dir.path().join("test.txt.feroxcrypt");
     assert!(encrypted_file.exists());
 
-    let decrypt_result = batch_decrypt_files(
-        &[encrypted_file],
-        "test_password",
-        Some(&loaded_keyfile),
-    )?;
+    let decrypt_result =
+        batch_decrypt_files(&[encrypted_file], "test_password", Some(&loaded_keyfile))?;
 
     assert_eq!(decrypt_result.success_count, 1);
     assert_eq!(decrypt_result.failure_count, 0);
@@ -134,18 +128,18 @@ fn test_keyfile_compatibility() -> Result<()> {
 #[test]
 fn test_batch_configuration_options() -> Result<()> {
     let temp_dir = TempDir::new()?;
-    
+
     // 创建测试文件
     let files = vec![
         temp_dir.path().join("test1.txt"),
         temp_dir.path().join("test2.txt"),
         temp_dir.path().join("test3.txt"),
     ];
-    
+
     for file in &files {
         fs::write(file, "Test content")?;
     }
-    
+
     // 测试不同的批量配置
     let configs = vec![
         BatchConfig {
@@ -154,6 +148,7 @@ fn test_batch_configuration_options() -> Result<()> {
             recursive: false,
             include_patterns: vec![],
             exclude_patterns: vec![],
+            ..Default::default()
         },
         BatchConfig {
             level: Level::Moderate,
@@ -161,6 +156,7 @@ fn test_batch_configuration_options() -> Result<()> {
             recursive: true,
             include_patterns: vec![],
             exclude_patterns: vec![],
+            ..Default::default()
         },
         BatchConfig {
             level: Level::Paranoid,
@@ -168,31 +164,38 @@ fn test_batch_configuration_options() -> Result<()> {
             recursive: false,
             include_patterns: vec![],
             exclude_patterns: vec![],
+            ..Default::default()
         },
     ];
-    
+
     for (i, config) in configs.iter().enumerate() {
-        let test_files: Vec<PathBuf> = files.iter()
+        let test_files: Vec<PathBuf> = files
+            .iter()
             .map(|f| {
                 let mut new_path = f.clone();
-                new_path.set_file_name(format!("test{}_config{}.txt", 
-                    new_path.file_stem().unwrap().to_str().unwrap().chars().last().unwrap(), i));
+                new_path.set_file_name(format!(
+                    "test{}_config{}.txt",
+                    new_path
+                        .file_stem()
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .chars()
+                        .last()
+                        .unwrap(),
+                    i
+                ));
                 fs::copy(f, &new_path).unwrap();
                 new_path
             })
             .collect();
-        
-        let result = batch_encrypt_files(
-            &test_files,
-            "test_password",
-            None,
-            config,
-        )?;
-        
+
+        let result = batch_encrypt_files(&test_files, "test_password", None, config)?;
+
         assert_eq!(result.success_count, test_files.len());
         assert_eq!(result.failure_count, 0);
     }
-    
+
     Ok(())
 }
 
@@ -200,18 +203,13 @@ fn test_batch_configuration_options() -> Result<()> {
 #[test]
 fn test_error_handling() -> Result<()> {
     let temp_dir = TempDir::new()?;
-    
+
     // 测试不存在的文件
     let nonexistent_file = temp_dir.path().join("nonexistent.txt");
     let config = BatchConfig::default();
-    
-    let result = batch_encrypt_files(
-        &[nonexistent_file],
-        "test_password",
-        None,
-        &config,
-    );
-    
+
+    let result = batch_encrypt_files(&[nonexistent_file], "test_password", None, &config);
+
     // 应该返回错误或者在结果中标记失败
     match result {
         Ok(batch_result) => {
@@ -222,7 +220,7 @@ fn test_error_handling() -> Result<()> {
             // 返回错误也是可接受的
         }
     }
-    
+
     Ok(())
 }
 
@@ -243,12 +241,8 @@ fn test_large_file_handling() -> Result<()> {
     };
 
     // 测试加密
-    let encrypt_result = batch_encrypt_files(
-        &[large_file.clone()],
-        "test_password",
-        None,
-        &config,
-    )?;
+    let encrypt_result =
+        batch_encrypt_files(&[large_file.clone()], "test_password", None, &config)?;
 
     assert_eq!(encrypt_result.success_count, 1);
     assert_eq!(encrypt_result.failure_count, 0);
@@ -261,11 +255,7 @@ fn test_large_file_handling() -> Result<()> {
     let encrypted_file = temp_dir.path().join("large_test.txt.feroxcrypt");
     assert!(encrypted_file.exists());
 
-    let decrypt_result = batch_decrypt_files(
-        &[encrypted_file],
-        "test_password",
-        None,
-    )?;
+    let decrypt_result = batch_decrypt_files(&[encrypted_file], "test_password", None)?;
 
     assert_eq!(decrypt_result.success_count, 1);
     assert_eq!(decrypt_result.failure_count, 0);
@@ -282,22 +272,22 @@ fn test_large_file_handling() -> Result<()> {
 #[test]
 fn test_cli_backward_compatibility() {
     use std::process::Command;
-    
+
     // 测试帮助命令
     let output = Command::new("cargo")
         .args(&["run", "--", "--help"])
         .output()
         .expect("Failed to execute command");
-    
+
     let help_text = String::from_utf8_lossy(&output.stdout);
-    
+
     // 验证所有原有命令仍然存在
     assert!(help_text.contains("encrypt"));
     assert!(help_text.contains("decrypt"));
     assert!(help_text.contains("batch-encrypt"));
     assert!(help_text.contains("batch-decrypt"));
     assert!(help_text.contains("generate-key"));
-    
+
     // 验证新的交互式命令存在
     assert!(help_text.contains("interactive"));
 }
@@ -306,11 +296,7 @@ fn test_cli_backward_compatibility() {
 #[test]
 fn test_module_integration() {
     // 验证所有模块都可以正确导入
-    use ferox_encryptor::{
-        batch::BatchConfig,
-        keyfile::KeyFile,
-        Level,
-    };
+    use ferox_encryptor::{batch::BatchConfig, keyfile::KeyFile, Level};
 
     // 测试枚举值
     let _levels = vec![Level::Interactive, Level::Moderate, Level::Paranoid];
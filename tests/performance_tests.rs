@@ -5,7 +5,11 @@
 //! Run them with `cargo test -- --ignored`
 
 use anyhow::Result;
-use ferox_encryptor::{run_decryption_flow, run_encryption_flow, Level};
+use ferox_encryptor::batch::batch_decrypt_files_with_config;
+use ferox_encryptor::{
+    batch_encrypt_files, decrypt_in_memory, encrypt_in_memory, run_decryption_flow,
+    run_encryption_flow, BatchConfig, DecryptionOptions, EncryptionOptions, KeyFile, Level,
+};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
@@ -299,3 +303,234 @@ fn test_many_small_files_performance() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[ignore]
+fn test_small_files_batch_buffer_reuse() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "buffer_reuse_password";
+    let num_files = 1000;
+
+    let mut test_files = Vec::new();
+    for i in 0..num_files {
+        let test_file = temp_dir.path().join(format!("tiny_{i}.txt"));
+        fs::write(&test_file, format!("tiny file {i}").repeat(40))?;
+        test_files.push(test_file);
+    }
+
+    // Each worker thread reuses its pooled chunk buffers across files instead of
+    // allocating a fresh 4MB buffer per file.
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    let encrypt_start = Instant::now();
+    let result = batch_encrypt_files(&test_files, password, None, &config)?;
+    let encrypt_duration = encrypt_start.elapsed();
+    assert_eq!(result.success_count, num_files);
+
+    let encrypted_files: Vec<PathBuf> = test_files
+        .iter()
+        .map(|file| PathBuf::from(format!("{}.feroxcrypt", file.display())))
+        .collect();
+    for test_file in &test_files {
+        fs::remove_file(test_file)?;
+    }
+    let decrypt_start = Instant::now();
+    let result = batch_decrypt_files_with_config(&encrypted_files, password, None, &config)?;
+    let decrypt_duration = decrypt_start.elapsed();
+    assert_eq!(result.success_count, num_files);
+
+    println!(
+        "[Buffer Reuse] {num_files} small files: Encrypt: {:.2} ms/file, Decrypt: {:.2} ms/file",
+        encrypt_duration.as_secs_f64() * 1000.0 / num_files as f64,
+        decrypt_duration.as_secs_f64() * 1000.0 / num_files as f64
+    );
+
+    for (i, test_file) in test_files.iter().enumerate() {
+        assert_eq!(
+            fs::read_to_string(test_file)?,
+            format!("tiny file {i}").repeat(40)
+        );
+    }
+    Ok(())
+}
+
+/// Encrypts and decrypts a 100MB buffer in memory with the given threading mode
+/// and returns the (encrypt, decrypt) throughput in MB/s.
+fn in_memory_throughput(single_thread: bool) -> Result<(f64, f64)> {
+    let data_size = 100 * 1024 * 1024; // 100MB
+    let plaintext: Vec<u8> = (0..data_size).map(|i| (i % 251) as u8).collect();
+    let password = "pipeline_performance_password";
+
+    let encrypt_opts = EncryptionOptions {
+        level: Level::Interactive,
+        single_thread,
+        ..EncryptionOptions::new(password)
+    };
+    let encrypt_start = Instant::now();
+    let encrypted = encrypt_in_memory(&plaintext, "pipeline.bin", &encrypt_opts)?;
+    let encrypt_duration = encrypt_start.elapsed();
+
+    let decrypt_opts = DecryptionOptions {
+        single_thread,
+        ..DecryptionOptions::new(password)
+    };
+    let decrypt_start = Instant::now();
+    let (decrypted, _) = decrypt_in_memory(&encrypted, &decrypt_opts)?;
+    let decrypt_duration = decrypt_start.elapsed();
+    assert!(decrypted == plaintext);
+
+    let mb = data_size as f64 / (1024.0 * 1024.0);
+    Ok((
+        mb / encrypt_duration.as_secs_f64(),
+        mb / decrypt_duration.as_secs_f64(),
+    ))
+}
+
+#[test]
+#[ignore]
+fn test_single_thread_throughput() -> Result<()> {
+    let (encrypt, decrypt) = in_memory_throughput(true)?;
+    println!("[Single Thread] Encrypt: {encrypt:.2} MB/s, Decrypt: {decrypt:.2} MB/s");
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_pipelined_throughput() -> Result<()> {
+    let (serial_encrypt, serial_decrypt) = in_memory_throughput(true)?;
+    let (pipelined_encrypt, pipelined_decrypt) = in_memory_throughput(false)?;
+    println!(
+        "[Pipelined] Encrypt: {pipelined_encrypt:.2} MB/s (single thread {serial_encrypt:.2} MB/s), \
+         Decrypt: {pipelined_decrypt:.2} MB/s (single thread {serial_decrypt:.2} MB/s)"
+    );
+    Ok(())
+}
+
+/// Encrypts a sparse file with the given number of threads and returns the throughput in MB/s.
+fn sparse_file_throughput(threads: usize, single_thread: bool) -> Result<f64> {
+    let temp_dir = TempDir::new()?;
+    let file_size: u64 = 2 * 1024 * 1024 * 1024; // 2GB sparse file
+    let test_file = temp_dir.path().join("sparse.bin");
+    fs::File::create(&test_file)?.set_len(file_size)?;
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        single_thread,
+        threads,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let result = batch_encrypt_files(&[test_file], "parallel_performance_password", None, &config)?;
+    let duration = start.elapsed();
+    assert!(result.was_successful());
+
+    Ok(file_size as f64 / duration.as_secs_f64() / (1024.0 * 1024.0))
+}
+
+#[test]
+#[ignore]
+fn test_parallel_ctr_sparse_file_throughput() -> Result<()> {
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get().max(2));
+    let serial = sparse_file_throughput(1, true)?;
+    let parallel = sparse_file_throughput(threads, false)?;
+    println!("[Sparse 2GB] Single thread: {serial:.2} MB/s, {threads} threads: {parallel:.2} MB/s");
+    Ok(())
+}
+
+/// Encrypts a 512MB file on disk, optionally through a memory map, and returns the throughput in MB/s.
+fn file_encryption_throughput(mmap: bool) -> Result<f64> {
+    let temp_dir = TempDir::new()?;
+    let file_size = 512 * 1024 * 1024; // 512MB
+    let test_file = temp_dir.path().join("mmap.bin");
+    fs::write(&test_file, vec![0x5A; file_size])?;
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        mmap,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let result = batch_encrypt_files(&[test_file], "mmap_performance_password", None, &config)?;
+    let duration = start.elapsed();
+    assert!(result.was_successful());
+
+    Ok(file_size as f64 / duration.as_secs_f64() / (1024.0 * 1024.0))
+}
+
+#[test]
+#[ignore]
+fn test_mmap_encryption_throughput() -> Result<()> {
+    let buffered = file_encryption_throughput(false)?;
+    let mapped = file_encryption_throughput(true)?;
+    println!("[512MB File] Buffered: {buffered:.2} MB/s, Memory-mapped: {mapped:.2} MB/s");
+    Ok(())
+}
+
+/// Run once with and once without `--features uring` to compare the io_uring
+/// read-ahead / write-behind path with the default buffered path.
+#[test]
+#[ignore]
+fn test_file_io_backend_throughput() -> Result<()> {
+    let backend = if cfg!(feature = "uring") {
+        "io_uring"
+    } else {
+        "buffered"
+    };
+    let throughput = file_encryption_throughput(false)?;
+    println!("[512MB File] {backend} I/O: {throughput:.2} MB/s");
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_many_small_files_with_keyfile_performance() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "keyfile_batch_password";
+    let num_files = 200;
+    let keyfile = KeyFile::generate();
+
+    let mut test_files = Vec::new();
+    for i in 0..num_files {
+        let test_file = temp_dir.path().join(format!("small_file_{i}.txt"));
+        fs::write(&test_file, format!("Small file content {i}"))?;
+        test_files.push(test_file);
+    }
+
+    let config = BatchConfig {
+        level: Level::Interactive,
+        ..Default::default()
+    };
+    let encrypt_start = Instant::now();
+    let result = batch_encrypt_files(&test_files, password, Some(&keyfile), &config)?;
+    let encrypt_duration = encrypt_start.elapsed();
+    assert!(result.was_successful());
+
+    let encrypted_files: Vec<PathBuf> = test_files
+        .iter()
+        .map(|file| {
+            fs::remove_file(file)?;
+            Ok(file.with_extension("txt.feroxcrypt"))
+        })
+        .collect::<Result<_>>()?;
+    let decrypt_start = Instant::now();
+    let result =
+        batch_decrypt_files_with_config(&encrypted_files, password, Some(&keyfile), &config)?;
+    let decrypt_duration = decrypt_start.elapsed();
+    assert!(result.was_successful());
+
+    println!(
+        "[Small Files + Keyfile] Total time for {} files: Encrypt: {:.2}s, Decrypt: {:.2}s",
+        num_files,
+        encrypt_duration.as_secs_f64(),
+        decrypt_duration.as_secs_f64()
+    );
+    for (i, test_file) in test_files.iter().enumerate() {
+        assert_eq!(
+            fs::read_to_string(test_file)?,
+            format!("Small file content {i}")
+        );
+    }
+    Ok(())
+}
@@ -3,10 +3,24 @@
 //! Security-focused tests for Ferox Encryptor
 
 use anyhow::Result;
-use ferox_encryptor::{run_decryption_flow, run_encryption_flow, Level};
+use ferox_encryptor::constants::{
+    CURRENT_FORMAT_VERSION, FILENAME_LEN_FIELD_LEN, FORMAT_MAGIC, FORMAT_PREAMBLE_LEN,
+    MAX_KEYFILE_SIZE, MIN_KEYFILE_SIZE, TAG_LEN,
+};
+use ferox_encryptor::keyfile::{
+    combine_password_and_keyfile, generate_keyfile_from_hardware_entropy,
+};
+use ferox_encryptor::{
+    decrypt_in_memory, decrypt_unverified, encrypt_in_memory, encrypt_with_time_limit,
+    format_utc_time, inspect_file, run_decryption_flow, run_encryption_flow, save_batch,
+    validate_keyfile, verify_audit_log, verify_file, verify_keyfile_matches_encrypted_file,
+    AuditLog, AuditOperation, AuditRecord, DecryptionOptions, EncryptionOptions, FeroxError,
+    KeyFile, KeyShare, KeyfileInputFormat, Level,
+};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tempfile::TempDir;
 
 #[test]
@@ -50,7 +64,7 @@ fn test_wrong_password_fails() -> Result<()> {
     );
 
     // Verify the error is authentication-related
-    let error_msg = format!("{}", decrypt_result.unwrap_err());
+    let error_msg = format!("{:#}", decrypt_result.unwrap_err());
     assert!(
         error_msg.contains("Authentication failed")
             || error_msg.contains("CRITICAL ERROR")
@@ -94,7 +108,8 @@ fn test_corrupted_file_detection() -> Result<()> {
 
     // Corrupt the encrypted file by modifying some bytes
     let mut encrypted_data = fs::read(&encrypted_file)?;
-    let corruption_pos = encrypted_data.len() / 2;
+    // 篡改密文中间的一个字节（文件头的长度随格式版本变化，因此从末尾计算位置）
+    let corruption_pos = encrypted_data.len() - TAG_LEN - test_content.len() / 2;
     encrypted_data[corruption_pos] ^= 0xFF; // Flip all bits in one byte
     fs::write(&encrypted_file, &encrypted_data)?;
 
@@ -111,7 +126,7 @@ fn test_corrupted_file_detection() -> Result<()> {
     );
 
     // Verify the error is authentication-related
-    let error_msg = format!("{}", decrypt_result.unwrap_err());
+    let error_msg = format!("{:#}", decrypt_result.unwrap_err());
     assert!(
         error_msg.contains("Authentication failed")
             || error_msg.contains("CRITICAL ERROR")
@@ -355,3 +370,684 @@ fn test_concurrent_operations() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_header_tampering_is_detected() -> Result<()> {
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new("header_password")
+    };
+    let mut encrypted = encrypt_in_memory(b"header integrity", "report.txt", &opts)?;
+    assert!(encrypted.starts_with(&FORMAT_MAGIC));
+    assert_eq!(encrypted[FORMAT_MAGIC.len()], CURRENT_FORMAT_VERSION);
+
+    // 修改文件头中记录的原始文件名 ("report.txt" -> "Report.txt")
+    let name_offset = FORMAT_PREAMBLE_LEN + FILENAME_LEN_FIELD_LEN;
+    encrypted[name_offset] = b'R';
+
+    let err =
+        decrypt_in_memory(&encrypted, &DecryptionOptions::new("header_password")).unwrap_err();
+    assert!(err
+        .downcast_ref::<FeroxError>()
+        .is_some_and(FeroxError::is_authentication_failure));
+    Ok(())
+}
+
+#[test]
+fn test_keyfile_commitment_identifies_matching_keyfile() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "commitment_password";
+    let keyfile_path = temp_dir.path().join("right.key");
+    let other_keyfile_path = temp_dir.path().join("other.key");
+    KeyFile::generate().save_to_file(&keyfile_path)?;
+    KeyFile::generate().save_to_file(&other_keyfile_path)?;
+    let keyfile = KeyFile::load_from_file(&keyfile_path)?;
+
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        keyfile: Some(&keyfile),
+        keyfile_commitment: true,
+        ..EncryptionOptions::new(password)
+    };
+    let with_keyfile = temp_dir.path().join("with_keyfile.txt.feroxcrypt");
+    fs::write(
+        &with_keyfile,
+        encrypt_in_memory(b"data", "with_keyfile.txt", &opts)?,
+    )?;
+    let without_keyfile = temp_dir.path().join("plain.txt.feroxcrypt");
+    fs::write(
+        &without_keyfile,
+        encrypt_in_memory(
+            b"data",
+            "plain.txt",
+            &EncryptionOptions {
+                keyfile: None,
+                ..opts
+            },
+        )?,
+    )?;
+
+    assert!(inspect_file(&with_keyfile)?.has_keyfile_commitment);
+    assert!(verify_keyfile_matches_encrypted_file(
+        &keyfile_path,
+        &with_keyfile,
+        password
+    )?);
+    assert!(!verify_keyfile_matches_encrypted_file(
+        &other_keyfile_path,
+        &with_keyfile,
+        password
+    )?);
+    assert!(!verify_keyfile_matches_encrypted_file(
+        &keyfile_path,
+        &with_keyfile,
+        "wrong"
+    )?);
+
+    assert!(!inspect_file(&without_keyfile)?.has_keyfile_commitment);
+    assert!(
+        verify_keyfile_matches_encrypted_file(&keyfile_path, &without_keyfile, password).is_err()
+    );
+
+    // 承诺默认不写入
+    let uncommitted = temp_dir.path().join("uncommitted.txt.feroxcrypt");
+    fs::write(
+        &uncommitted,
+        encrypt_in_memory(
+            b"data",
+            "uncommitted.txt",
+            &EncryptionOptions {
+                level: Level::Interactive,
+                keyfile: Some(&keyfile),
+                ..EncryptionOptions::new(password)
+            },
+        )?,
+    )?;
+    assert!(!inspect_file(&uncommitted)?.has_keyfile_commitment);
+    assert!(verify_keyfile_matches_encrypted_file(&keyfile_path, &uncommitted, password).is_err());
+
+    // 记录了承诺的文件仍然可以正常解密
+    let decrypt_opts = DecryptionOptions {
+        keyfile: Some(&keyfile),
+        ..DecryptionOptions::new(password)
+    };
+    let (plaintext, _) = decrypt_in_memory(&fs::read(&with_keyfile)?, &decrypt_opts)?;
+    assert_eq!(plaintext, b"data");
+    Ok(())
+}
+
+#[test]
+fn test_hardware_entropy_keyfile_mixes_biased_source() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    // 一个全零的"设备"必然无法通过随机性检验，必须与系统随机数混合
+    let biased_device = temp_dir.path().join("biased_device");
+    fs::write(&biased_device, vec![0u8; 1024])?;
+    let keyfile = generate_keyfile_from_hardware_entropy(&biased_device, 512)?;
+
+    let keyfile_path = temp_dir.path().join("hw.key");
+    keyfile.save_to_file(&keyfile_path)?;
+    let data = fs::read(&keyfile_path)?;
+    assert_eq!(data.len(), 512);
+    assert!(data.iter().any(|&byte| byte != 0));
+    validate_keyfile(&keyfile_path)?;
+
+    // 大小越界、设备数据不足或设备不存在都必须报错
+    assert!(generate_keyfile_from_hardware_entropy(&biased_device, 16).is_err());
+    assert!(generate_keyfile_from_hardware_entropy(&biased_device, 8192).is_err());
+    assert!(generate_keyfile_from_hardware_entropy(&biased_device, 2048).is_err());
+    assert!(generate_keyfile_from_hardware_entropy(&temp_dir.path().join("missing"), 512).is_err());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hardware_entropy_keyfile_from_urandom() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let keyfile = generate_keyfile_from_hardware_entropy(Path::new("/dev/urandom"), 4096)?;
+
+    let keyfile_path = temp_dir.path().join("urandom.key");
+    keyfile.save_to_file(&keyfile_path)?;
+    assert_eq!(fs::read(&keyfile_path)?.len(), 4096);
+    Ok(())
+}
+
+#[test]
+fn test_keyfile_shares_reconstruct_with_threshold() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let keyfile = KeyFile::generate();
+    let original_path = temp_dir.path().join("original.key");
+    keyfile.save_to_file(&original_path)?;
+    let original = fs::read(&original_path)?;
+    let rebuilt_path = temp_dir.path().join("rebuilt.key");
+    let rebuilt_bytes = |rebuilt: KeyFile| -> Result<Vec<u8>> {
+        rebuilt.save_to_file(&rebuilt_path)?;
+        Ok(fs::read(&rebuilt_path)?)
+    };
+    let shares = keyfile.split(5, 3)?;
+    assert_eq!(shares.len(), 5);
+    for share in &shares {
+        share.save_to_file(
+            temp_dir
+                .path()
+                .join(format!("key.{}.feroxshare", share.index())),
+        )?;
+    }
+    let load = |index: u8| {
+        KeyShare::load_from_file(temp_dir.path().join(format!("key.{index}.feroxshare")))
+    };
+
+    // 分片文件往返后，任意 3 个分片都能重建出原密钥文件
+    for picked in [[1, 2, 3], [1, 3, 5], [2, 4, 5], [5, 4, 3]] {
+        let picked = picked.map(|index| load(index).unwrap());
+        assert_eq!(rebuilt_bytes(KeyFile::from_shares(&picked)?)?, original);
+    }
+    assert_eq!(rebuilt_bytes(KeyFile::from_shares(&shares)?)?, original);
+
+    // 低于阈值或序号重复的分片不能重建
+    assert!(KeyFile::from_shares(&[load(1)?, load(4)?]).is_err());
+    assert!(KeyFile::from_shares(&[load(1)?, load(1)?, load(2)?]).is_err());
+    assert!(keyfile.split(5, 1).is_err());
+    assert!(keyfile.split(2, 3).is_err());
+
+    // 分片来自不同的密钥文件时被拒绝
+    let mut mixed = KeyFile::generate().split(5, 3)?;
+    mixed.truncate(2);
+    mixed.push(load(3)?);
+    assert!(KeyFile::from_shares(&mixed).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_keyfile_batch_is_unique_and_numbered() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("keys");
+    let keyfiles = KeyFile::generate_batch(3);
+    assert_eq!(keyfiles.len(), 3);
+    for keyfile in &keyfiles {
+        keyfile.validate_entropy(7.0)?;
+    }
+
+    let paths = save_batch(&keyfiles, &output_dir, "team")?;
+    let names: Vec<_> = paths
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["team_01.key", "team_02.key", "team_03.key"]);
+    let contents = paths
+        .iter()
+        .map(fs::read)
+        .collect::<std::io::Result<Vec<_>>>()?;
+    assert!(contents[0] != contents[1] && contents[1] != contents[2] && contents[0] != contents[2]);
+    for path in &paths {
+        validate_keyfile(path)?;
+    }
+
+    // 已存在的文件不会被覆盖，重复的密钥文件被拒绝，两种情况都不写入任何文件
+    assert!(save_batch(&keyfiles, &output_dir, "team").is_err());
+    let duplicate = KeyFile::from_bytes(&contents[0])?;
+    let again = KeyFile::from_bytes(&contents[0])?;
+    assert!(save_batch(&[duplicate, again], &output_dir, "dup").is_err());
+    assert!(!output_dir.join("dup_01.key").exists());
+    assert!(save_batch(&keyfiles, &output_dir, "../escape").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_cached_keyfile_material_is_keyed_by_password() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let keyfile_path = temp_dir.path().join("cached.key");
+    KeyFile::generate().save_to_file(&keyfile_path)?;
+    let keyfile = KeyFile::load_from_file(&keyfile_path)?;
+
+    // 同一个实例先后用两个密码加密，缓存不能把第一个密码的材料用于第二个密码
+    let encrypt = |password| {
+        let opts = EncryptionOptions {
+            level: Level::Interactive,
+            keyfile: Some(&keyfile),
+            ..EncryptionOptions::new(password)
+        };
+        encrypt_in_memory(b"cached", "cached.txt", &opts)
+    };
+    let first = encrypt("first_password")?;
+    let second = encrypt("second_password")?;
+
+    // 用新加载（没有缓存）的密钥文件实例解密，结果与缓存前一致
+    let fresh = KeyFile::load_from_file(&keyfile_path)?;
+    for (encrypted, password) in [(&first, "first_password"), (&second, "second_password")] {
+        let opts = DecryptionOptions {
+            keyfile: Some(&fresh),
+            ..DecryptionOptions::new(password)
+        };
+        assert_eq!(decrypt_in_memory(encrypted, &opts)?.0, b"cached");
+    }
+    let wrong = DecryptionOptions {
+        keyfile: Some(&keyfile),
+        ..DecryptionOptions::new("first_password")
+    };
+    assert!(decrypt_in_memory(&second, &wrong).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_shared_keyfile_across_threads() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let keyfile_path = temp_dir.path().join("shared.key");
+    let keyfile = KeyFile::generate();
+    keyfile.save_to_file(&keyfile_path)?;
+
+    // 期望值来自单独加载、没有被其他线程用过的实例
+    let fresh = KeyFile::load_from_file(&keyfile_path)?;
+    let passwords = ["first_password", "second_password"];
+    let expected: Vec<Vec<u8>> = passwords
+        .iter()
+        .map(|password| combine_password_and_keyfile(password, &fresh))
+        .collect::<Result<_>>()?;
+
+    // 八个线程共享同一份密钥材料，交替使用两个密码，使缓存反复失效
+    let workers: Vec<_> = (0..8)
+        .map(|index| {
+            let keyfile = keyfile.clone();
+            std::thread::spawn(move || -> Result<Vec<(usize, Vec<u8>)>> {
+                (0..3)
+                    .map(|round| {
+                        let which = (index + round) % 2;
+                        let material = combine_password_and_keyfile(passwords[which], &keyfile)?;
+                        Ok((which, material))
+                    })
+                    .collect()
+            })
+        })
+        .collect();
+    for worker in workers {
+        for (which, material) in worker.join().expect("worker thread panicked")? {
+            assert_eq!(material, expected[which]);
+        }
+    }
+
+    // 克隆共享派生值缓存，原实例在克隆全部释放后仍然可用
+    assert_eq!(keyfile.hash()?, fresh.hash()?);
+    assert_eq!(keyfile.clone().hash()?, keyfile.hash()?);
+    Ok(())
+}
+
+#[test]
+fn test_keyfile_from_reader_binary_and_hex() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let original_path = temp_dir.path().join("original.key");
+    KeyFile::generate().save_to_file(&original_path)?;
+    let original = fs::read(&original_path)?;
+    let saved_bytes = |keyfile: KeyFile| -> Result<Vec<u8>> {
+        let path = temp_dir.path().join("piped.key");
+        keyfile.save_to_file(&path)?;
+        Ok(fs::read(&path)?)
+    };
+
+    // 原始字节和十六进制 (大写、按行折断) 读取的结果与密钥文件相同
+    let binary = KeyFile::from_reader(original.as_slice(), KeyfileInputFormat::Binary)?;
+    assert_eq!(saved_bytes(binary)?, original);
+    let hex: String = original
+        .chunks(32)
+        .map(|line| line.iter().map(|b| format!("{b:02X}")).collect::<String>() + "\n")
+        .collect();
+    let decoded = KeyFile::from_reader(hex.as_bytes(), KeyfileInputFormat::Hex)?;
+    assert_eq!(saved_bytes(decoded)?, original);
+    assert_eq!(saved_bytes(KeyFile::from_bytes(&original)?)?, original);
+
+    // 无效字符、奇数位数和大小超出范围的材料被拒绝
+    let odd = &hex[..hex.len() - 2];
+    assert!(KeyFile::from_reader(odd.as_bytes(), KeyfileInputFormat::Hex).is_err());
+    let invalid = format!("g{}", &hex[1..]);
+    assert!(KeyFile::from_reader(invalid.as_bytes(), KeyfileInputFormat::Hex).is_err());
+    let too_short = vec![7u8; MIN_KEYFILE_SIZE - 1];
+    assert!(KeyFile::from_reader(too_short.as_slice(), KeyfileInputFormat::Binary).is_err());
+    let too_long = vec![7u8; MAX_KEYFILE_SIZE + 1];
+    assert!(KeyFile::from_reader(too_long.as_slice(), KeyfileInputFormat::Binary).is_err());
+    assert!(KeyFile::from_bytes(&too_long).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_expired_file_is_refused_before_key_derivation() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "expiry_password";
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new(password)
+    };
+
+    // 尚未过期的文件可以正常解密，info 显示过期时间
+    let source = temp_dir.path().join("shared.txt");
+    fs::write(&source, b"time limited")?;
+    let expiry = SystemTime::now() + Duration::from_secs(24 * 3600);
+    encrypt_with_time_limit(&source, expiry, &opts)?;
+    fs::remove_file(&source)?;
+    let encrypted = temp_dir.path().join("shared.txt.feroxcrypt");
+    let expires_at = inspect_file(&encrypted)?.expires_at.unwrap();
+    assert_eq!(
+        expires_at.duration_since(UNIX_EPOCH)?.as_secs(),
+        expiry.duration_since(UNIX_EPOCH)?.as_secs()
+    );
+    run_decryption_flow(&encrypted, password, None, Arc::new(Mutex::new(None)))?;
+    assert_eq!(fs::read(&source)?, b"time limited");
+
+    // 过期后，即使密码错误也直接返回 FileExpired（在密钥派生之前拒绝）
+    let expiry = SystemTime::now() + Duration::from_secs(1);
+    let data = encrypt_in_memory(
+        b"short lived",
+        "short.txt",
+        &EncryptionOptions {
+            expires_at: Some(expiry),
+            ..opts
+        },
+    )?;
+    while SystemTime::now() <= expiry {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    for attempt in [password, "wrong_password"] {
+        let err = decrypt_in_memory(&data, &DecryptionOptions::new(attempt)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FeroxError>(),
+            Some(FeroxError::FileExpired { .. })
+        ));
+    }
+
+    // 过期时间必须晚于当前时间
+    let overwrite = EncryptionOptions {
+        force_overwrite: true,
+        ..opts
+    };
+    let err = encrypt_with_time_limit(&source, SystemTime::now(), &overwrite).unwrap_err();
+    assert!(format!("{err:#}").contains("过期时间"));
+    assert_eq!(
+        format_utc_time(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        "2023-11-14 22:13:20 UTC"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_audit_log_chain_pinpoints_tampered_record() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let log_path = temp_dir.path().join("audit.jsonl");
+    let audit_keyfile = KeyFile::generate();
+    let audit_log = AuditLog::open(&log_path, &audit_keyfile)?;
+
+    let source = Path::new("/data/report.pdf");
+    let target = Path::new("/data/report.pdf.feroxcrypt");
+    audit_log.append(&AuditRecord::success(
+        AuditOperation::Encrypt,
+        source,
+        Some(target),
+        Some(Level::Moderate),
+    ))?;
+    audit_log.append(&AuditRecord::failure(
+        AuditOperation::Decrypt,
+        target,
+        None,
+        None,
+        &FeroxError::AuthenticationFailed,
+    ))?;
+    audit_log.append(&AuditRecord::success(
+        AuditOperation::Decrypt,
+        target,
+        Some(source),
+        Some(Level::Moderate),
+    ))?;
+    audit_log.append(&AuditRecord::success(
+        AuditOperation::Encrypt,
+        Path::new("/data/notes.txt"),
+        None,
+        Some(Level::Paranoid),
+    ))?;
+
+    let verification = verify_audit_log(&log_path, &audit_keyfile)?;
+    assert_eq!(verification.records, 4);
+    assert!(verification.is_intact());
+
+    // 记录中只有级别的 Display 输出，没有密钥材料
+    let log = fs::read_to_string(&log_path)?;
+    assert!(log.contains("\"level\":\"Moderate (m=64MiB, t=3, p=1)\""));
+    assert!(log.contains("\"result\":\"failure\""));
+
+    // 其他密钥文件无法通过校验
+    let other = verify_audit_log(&log_path, &KeyFile::generate())?;
+    assert_eq!(other.invalid_lines, [1, 2, 3, 4]);
+
+    // 把第 2 条的失败记录改为成功：只有第 2 行失效
+    let tampered: Vec<String> = log
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 1 {
+                line.replace("\"result\":\"failure\"", "\"result\":\"success\"")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    fs::write(&log_path, tampered.join("\n") + "\n")?;
+    let verification = verify_audit_log(&log_path, &audit_keyfile)?;
+    assert_eq!(verification.records, 4);
+    assert_eq!(verification.invalid_lines, [2]);
+
+    // 删除第 3 条记录：紧随其后的记录失效
+    let mut removed = tampered.clone();
+    removed.remove(2);
+    fs::write(&log_path, removed.join("\n") + "\n")?;
+    assert_eq!(
+        verify_audit_log(&log_path, &audit_keyfile)?.invalid_lines,
+        [2, 3]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_lock_memory_round_trip() -> Result<()> {
+    // 无论内存锁定成功与否（取决于 RLIMIT_MEMLOCK 和权限），加解密都必须成功
+    let keyfile = KeyFile::generate();
+    let encrypt_opts = EncryptionOptions {
+        level: Level::Interactive,
+        keyfile: Some(&keyfile),
+        lock_memory: true,
+        ..EncryptionOptions::new("locked_password")
+    };
+    let data = encrypt_in_memory(b"pinned secret", "pinned.txt", &encrypt_opts)?;
+
+    let decrypt_opts = DecryptionOptions {
+        keyfile: Some(&keyfile),
+        lock_memory: true,
+        ..DecryptionOptions::new("locked_password")
+    };
+    let (plaintext, filename) = decrypt_in_memory(&data, &decrypt_opts)?;
+    assert_eq!(plaintext, b"pinned secret");
+    assert_eq!(filename, "pinned.txt");
+
+    // 与未锁定内存时的加密结果可以互相解密
+    let (plaintext, _) = decrypt_in_memory(
+        &data,
+        &DecryptionOptions {
+            keyfile: Some(&keyfile),
+            ..DecryptionOptions::new("locked_password")
+        },
+    )?;
+    assert_eq!(plaintext, b"pinned secret");
+    Ok(())
+}
+
+#[test]
+fn test_verify_file_checks_tag_without_decrypting() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "verify_password";
+    let source = temp_dir.path().join("report.txt");
+    fs::write(&source, b"contents that must verify")?;
+    run_encryption_flow(
+        &source,
+        false,
+        password,
+        Level::Interactive,
+        None,
+        Arc::new(Mutex::new(None)),
+    )?;
+    fs::remove_file(&source)?;
+    let encrypted = temp_dir.path().join("report.txt.feroxcrypt");
+
+    verify_file(&encrypted, password, None)?;
+    // 验证不会生成明文文件
+    assert!(!source.exists());
+
+    let error = verify_file(&encrypted, "wrong_password", None).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<FeroxError>(),
+        Some(FeroxError::AuthenticationFailed)
+    ));
+
+    let mut data = fs::read(&encrypted)?;
+    let index = data.len() - TAG_LEN - 1;
+    data[index] ^= 0x01;
+    fs::write(&encrypted, &data)?;
+    assert!(verify_file(&encrypted, password, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_low_entropy_keyfiles_are_rejected_for_encryption() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    let generated = KeyFile::generate();
+    assert!(generated.entropy_estimate() > 6.5);
+    let generated_path = temp_dir.path().join("generated.key");
+    generated.save_to_file(&generated_path)?;
+    validate_keyfile(&generated_path)?;
+    KeyFile::load_from_file(&generated_path)?;
+
+    // 全零和文本都不是随机数据
+    let zeros = KeyFile::from_bytes(&[0u8; MIN_KEYFILE_SIZE])?;
+    assert_eq!(zeros.entropy_estimate(), 0.0);
+    assert!(zeros.validate_entropy(6.0).is_err());
+
+    // 低熵的密钥文件仍然可以加载（用来解密旧文件），但不能用来加密新文件
+    let text_path = temp_dir.path().join("notes.txt");
+    fs::write(
+        &text_path,
+        "remember to buy milk and eggs on the way home. ".repeat(8),
+    )?;
+    validate_keyfile(&text_path)?;
+    let text = KeyFile::load_from_file(&text_path)?;
+    let opts = EncryptionOptions {
+        keyfile: Some(&text),
+        level: Level::Interactive,
+        ..EncryptionOptions::new("low entropy password")
+    };
+    let error = encrypt_in_memory(b"secret", "secret.txt", &opts).unwrap_err();
+    assert!(format!("{error:#}").contains("熵太低"));
+
+    // 只有 64 字节的随机材料最多只有 6 比特/字节，门槛相应降低
+    let mut random = [0u8; MIN_KEYFILE_SIZE];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut random);
+    let short = KeyFile::from_bytes(&random)?;
+    assert!(short.entropy_estimate() <= 6.0);
+    short.validate_entropy(6.0)?;
+    Ok(())
+}
+
+#[test]
+fn test_decrypt_unverified_recovers_prefix_of_damaged_file() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let password = "recovery_password";
+    let plaintext: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    let opts = EncryptionOptions {
+        level: Level::Interactive,
+        ..EncryptionOptions::new(password)
+    };
+    let encrypted = encrypt_in_memory(&plaintext, "data.bin", &opts)?;
+    let decrypt_opts = DecryptionOptions::new(password);
+
+    // 认证标签被整个截掉：末尾的字节被当作标签，其余的明文前缀可以恢复
+    let truncated = temp_dir.path().join("truncated.bin.feroxcrypt");
+    fs::write(&truncated, &encrypted[..encrypted.len() - TAG_LEN])?;
+    let report = decrypt_unverified(
+        &truncated,
+        &temp_dir.path().join("truncated.bin"),
+        &decrypt_opts,
+    )?;
+    assert_eq!(
+        report.output_path,
+        temp_dir.path().join("truncated.bin.UNVERIFIED")
+    );
+    assert!(!report.tag_verified);
+    assert!(report.read_error.is_none());
+    assert_eq!(report.recovered_bytes, (plaintext.len() - TAG_LEN) as u64);
+    assert_eq!(
+        fs::read(&report.output_path)?,
+        &plaintext[..plaintext.len() - TAG_LEN]
+    );
+
+    // 标签损坏但密文完好：恢复全部明文，仍然标记为未验证
+    let mut damaged_tag = encrypted.clone();
+    let last = damaged_tag.len() - 1;
+    damaged_tag[last] ^= 0xFF;
+    let damaged = temp_dir.path().join("damaged.bin.feroxcrypt");
+    fs::write(&damaged, &damaged_tag)?;
+    let report = decrypt_unverified(
+        &damaged,
+        &temp_dir.path().join("damaged.bin"),
+        &decrypt_opts,
+    )?;
+    assert!(!report.tag_verified);
+    assert_eq!(fs::read(&report.output_path)?, plaintext);
+
+    // 正常解密仍然拒绝该文件
+    assert!(decrypt_in_memory(&damaged_tag, &decrypt_opts).is_err());
+
+    // 完好的文件：标签验证通过
+    let intact = temp_dir.path().join("intact.bin.feroxcrypt");
+    fs::write(&intact, &encrypted)?;
+    let output = temp_dir.path().join("intact.bin");
+    let report = decrypt_unverified(&intact, &output, &decrypt_opts)?;
+    assert!(report.tag_verified);
+    assert_eq!(fs::read(&report.output_path)?, plaintext);
+
+    // 不会覆盖已存在的恢复文件
+    assert!(decrypt_unverified(&intact, &output, &decrypt_opts).is_err());
+
+    // 凭据被文件头拒绝时不创建恢复文件，改正后可以直接重试
+    let keyfile = KeyFile::generate();
+    let keyed = temp_dir.path().join("keyed.bin.feroxcrypt");
+    let keyed_opts = EncryptionOptions {
+        keyfile: Some(&keyfile),
+        keyfile_commitment: true,
+        ..opts
+    };
+    fs::write(
+        &keyed,
+        encrypt_in_memory(&plaintext, "keyed.bin", &keyed_opts)?,
+    )?;
+    let keyed_output = temp_dir.path().join("keyed.bin");
+    let wrong_keyfile = KeyFile::generate();
+    let error = decrypt_unverified(
+        &keyed,
+        &keyed_output,
+        &DecryptionOptions {
+            keyfile: Some(&wrong_keyfile),
+            ..DecryptionOptions::new(password)
+        },
+    )
+    .unwrap_err();
+    assert!(error
+        .downcast_ref::<FeroxError>()
+        .is_some_and(FeroxError::is_authentication_failure));
+    assert!(!temp_dir.path().join("keyed.bin.UNVERIFIED").exists());
+    let report = decrypt_unverified(
+        &keyed,
+        &keyed_output,
+        &DecryptionOptions {
+            keyfile: Some(&keyfile),
+            ..DecryptionOptions::new(password)
+        },
+    )?;
+    assert!(report.tag_verified);
+    Ok(())
+}